@@ -5,20 +5,255 @@
 //! via x402, with human oversight via Blinks.
 //!
 //! ## Account Types
-//! - `AgentPolicy`: Per-agent spending rules (max_per_tx, allowed_category, frozen)
+//! - `AgentPolicy`: Per-agent spending rules (max_per_tx, allowed_category, frozen, total_budget),
+//!   several per agent keyed by `policy_id`
 //! - `Meter`: Per-API-endpoint pricing and metadata
 //! - `Authorization`: ZK-approved payment ticket (one-time use)
+//! - `Config`: Program-wide settings (admin, watchers, circuit breaker threshold, verifier)
+//! - `AllowedMeter`: Marks a meter as allowed for a policy (allowlist mode)
+//! - `DeniedMeter`: Bans a meter for a policy regardless of category
+//! - `MeterUsage`: Per-agent call and volume counters for a meter
+//! - `Subscription`: An agent's paid-up access period on a subscription meter
+//! - `MeterAgentAccess`: Lets an agent pay a meter that has its allowlist enabled
+//! - `Credit`: An agent's prepaid balance on a per-call meter
+//! - `MeterIndex`: The meters created by one authority, for enumeration
+//! - `MeterCounters`: A meter's call/volume totals and rate limit window
+//! - `NonceCounter`: Next auto-assigned authorization nonce for an (agent, meter) pair
+//! - `Dispute`: An open disagreement over whether a recorded payment was served
+//! - `BudgetAuthorization`: One proof covering many payments to a meter, drawn down per call
+//! - `BundleAuthorization`: One proof covering a payment to each of up to four meters
+//! - `Receipt`: On-chain proof that an agent paid a meter, kept after the authorization closes
+//! - `Channel`: Unidirectional payment channel from an agent to a meter, settled by signed vouchers
+//! - `VerifyingKey`: Groth16 verifying key of one circuit, set by the config admin
+//! - `ProofReceipt`: A payment proof verified ahead of the authorization that consumes it
+//! - Agent vault: Token account (PDA, its own authority) funding escrowed authorizations
 //!
 //! ## Instructions
 //! - `set_policy`: Create/update an agent's spending policy
+//! - `set_policies_batch`: Create/update many policies under one org admin
 //! - `create_meter`: Register a new paywalled API endpoint
+//! - `create_meter_v2`: Register an endpoint whose PDA derives from a string id
+//! - `clone_meter`: Register an endpoint with the pricing of an existing meter
+//! - `update_meter`: Change a meter's price, category or ZK requirement
+//! - `set_meter_tiers`: Configure volume-discount pricing tiers for a meter
+//! - `set_meter_rate_limit`: Cap calls per slot window across all agents
+//! - `set_meter_splits`: Share a meter's revenue with up to three extra wallets
+//! - `pause_meter` / `unpause_meter`: Stop or resume new payments to a meter
+//! - `set_meter_operator`: Appoint a key that may update, pause and unpause a meter
+//! - `close_meter`: Close a paused meter and reclaim its rent
+//! - `update_merchant_wallet`: Point a paused meter at a new Circle wallet
+//! - `transfer_meter_authority` / `accept_meter_authority`: Two-step meter authority handoff
+//! - `set_meter_allowlist`: Restrict a meter to agents granted access
+//! - `set_meter_settlement_mode`: Settle a meter off-chain from events, or from on-chain escrow
+//! - `set_meter_reject_attested`: Refuse payments authorized by a policy oracle attestation
+//! - `grant_meter_access` / `revoke_meter_access`: Manage a meter's agent allowlist
+//! - `migrate_meter`: Grow a pre-existing Meter to the current layout
+//! - `migrate_meter_usage`: Grow a pre-existing MeterUsage to the current layout
+//! - `migrate_authorization`: Rewrite a pre-existing Authorization into the current layout
+//! - `init_meter_counters`: Create the MeterCounters of a pre-existing Meter
 //! - `authorize_payment_with_proof`: Verify ZK proof and create payment authorization
+//! - `authorize_payment_simple`: Create a payment authorization without a proof, for non-ZK meters
+//! - `authorize_payment_attested`: Authorize on a policy oracle's Ed25519 attestation instead of a proof
+//! - `authorize_payment_auto_nonce`: Authorize with the next nonce of the pair's NonceCounter
+//! - `authorize_payments_batch`: Authorize up to five payments to one meter at once
+//! - `authorize_streaming_payment`: Authorize a payment that unlocks a little every slot
 //! - `record_meter_payment`: Consume authorization and emit payment event
+//! - `record_metered_usage`: Merchant records units × price against an authorized cap
+//! - `authorize_budget_with_proof` / `record_budget_payment`: Prove a total once, then draw per call
+//! - `close_budget_authorization`: Return a spent or expired budget authorization's rent
+//! - `authorize_bundle_with_proof` / `record_bundle_payment`: Authorize a pipeline of meters at once
+//! - `close_bundle_authorization`: Return a spent or expired bundle authorization's rent
+//! - `open_channel` / `settle_channel` / `close_channel`: Pay a meter through off-chain vouchers
+//! - `close_receipt`: Return a receipt's rent once the agent no longer needs it
+//! - `revoke_authorization`: Cancel an authorization before it is consumed
+//! - `close_authorization`: Return a used, revoked or expired authorization's rent to its payer
+//! - `sweep_expired_authorizations`: Close up to twenty dead authorizations in one transaction
+//! - `open_agent_vault` / `withdraw_from_vault`: Manage the token vault escrow is funded from
+//! - `cancel_escrowed_authorization`: Return an expired or revoked authorization's escrow to the vault
+//! - `confirm_settlement` / `report_settlement_failure`: Settlement authority reports Circle transfer outcomes
+//! - `open_dispute` / `resolve_dispute`: Contest a recorded payment; the config admin rules on it
+//! - `issue_refund`: Merchant refunds part or all of a recorded payment
+//! - `subscribe`: Pay a subscription meter's fee to start or extend access
+//! - `record_subscription_call`: Record a call covered by a subscription
+//! - `buy_credits` / `consume_credit` / `refund_credits`: Prepaid balances for high-frequency calls
+//! - `extend_budget`: Top up an agent's lifetime budget
+//! - `migrate_policy`: Grow a pre-existing AgentPolicy to the current layout
+//! - `migrate_legacy_policy`: Move a pre-policy_id AgentPolicy to the default policy PDA
+//! - `initialize_config` / `update_config`: Manage the global Config
+//! - `migrate_config`: Grow a pre-existing Config to the current layout
+//! - `set_verifying_key`: Install, replace or lock a circuit's verifying key (admin)
+//! - `verify_proof`: Check a Groth16 proof against a VerifyingKey (called via CPI)
+//! - `submit_proof_receipt` / `close_proof_receipt`: Verify a payment proof ahead of its authorization
+//! - `report_auth_failure`: Watcher-reported failed authorization (circuit breaker)
+//! - `allow_meter` / `disallow_meter`: Manage an agent's meter allowlist
+//! - `deny_meter` / `undeny_meter`: Manage an agent's meter denylist
+//! - `transfer_policy_ownership` / `accept_policy_ownership`: Two-step owner handoff
+//! - `rotate_agent_key`: Move a policy to a new agent key
+//! - `reset_spend_window`: Zero the daily spend window (owner + config admin)
+//! - `set_guardian`: Appoint a key that may pause the agent
+//! - `pause_policy`: Temporarily block an agent's payments for a number of slots
 
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::alt_bn128::prelude::{
+    alt_bn128_addition, alt_bn128_multiplication, alt_bn128_pairing,
+};
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::associated_token::{self, AssociatedToken};
+use anchor_spl::token::{self, Mint, Token, TokenAccount};
+use public_inputs::PaymentPolicyInputs;
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
+/// Policy ID used by clients that only manage one policy per agent, and the
+/// destination of `migrate_legacy_policy`.
+#[constant]
+pub const DEFAULT_POLICY_ID: u16 = 0;
+
+/// Maximum number of policies in one `set_policies_batch` call, bounded by
+/// the compute budget (PDA derivation and possible account creation per entry).
+#[constant]
+pub const MAX_POLICY_BATCH: usize = 8;
+
+/// Maximum number of payments in one `authorize_payments_batch` call, bounded
+/// by the compute budget (a verifier CPI and account creation per entry).
+#[constant]
+pub const MAX_AUTHORIZATION_BATCH: usize = 5;
+
+/// Maximum number of meters in one `BundleAuthorization`, bounded by the
+/// account size and by the meter and denylist accounts passed per meter.
+#[constant]
+pub const MAX_BUNDLE_METERS: usize = 4;
+
+/// Maximum number of authorizations closed by one
+/// `sweep_expired_authorizations` call, bounded by the transaction's account
+/// limit (each entry also passes its rent payer, usually a shared key).
+#[constant]
+pub const MAX_SWEEP_AUTHORIZATIONS: usize = 20;
+
+/// Maximum number of watcher keys that can be listed in the Config.
+/// Array lengths in account types are written out literally for the IDL.
+#[constant]
+pub const MAX_WATCHERS: usize = 4;
+
+/// Length of the daily spend window, assuming ~400ms slots.
+#[constant]
+pub const SLOTS_PER_DAY: u64 = 216_000;
+
+/// Basis-point denominator (10_000 = 100%).
+#[constant]
+pub const MAX_BPS: u16 = 10_000;
+
+/// Longest lifetime an authorization may be issued with (~1 hour). Also how
+/// long a meter must stay paused before `close_meter` (with MAX_GRACE_SLOTS),
+/// so that no unexpired authorization can still reference it.
+#[constant]
+pub const MAX_AUTHORIZATION_TTL_SLOTS: u64 = 9_000;
+
+/// Longest preimage `record_meter_payment` accepts for a hash-locked
+/// authorization; bounds the hashing cost and the instruction size.
+#[constant]
+pub const MAX_PREIMAGE_LEN: usize = 64;
+
+/// Length of a Groth16 proof: A (G1, 64 bytes), B (G2, 128 bytes) and C
+/// (G1, 64 bytes), uncompressed and big-endian as the alt_bn128 syscalls
+/// take them. This is the payload of a `proof_schemes::GROTH16` proof.
+#[constant]
+pub const GROTH16_PROOF_LEN: usize = 256;
+
+/// Length of the header every proof argument starts with: its version
+/// (the circuit version it was generated for), then its scheme (see
+/// `proof_schemes`). The payload follows.
+#[constant]
+pub const PROOF_HEADER_LEN: usize = 2;
+
+/// Shortest proof argument accepted: a header and a Groth16 payload, the
+/// smallest of the supported schemes'. Shorter proofs fail with
+/// `ProofTooSmall` before any other proof check.
+#[constant]
+pub const MIN_PROOF_LEN: usize = PROOF_HEADER_LEN + GROTH16_PROOF_LEN;
+
+/// Length of the header of an aggregated proof
+/// (`proof_schemes::GROTH16_BATCH`): the proof header, then its
+/// `batch_count`, the number of Groth16 payloads that follow. Aggregated
+/// proofs are exactly this plus `batch_count * GROTH16_PROOF_LEN` long,
+/// rather than bounded by MIN_PROOF_LEN and MAX_PROOF_LEN.
+#[constant]
+pub const AGGREGATED_PROOF_HEADER_LEN: usize = PROOF_HEADER_LEN + 1;
+
+/// Longest proof argument accepted, so that UltraHonk payloads still fit
+/// a transaction (1232 bytes) next to the instruction's accounts. Longer
+/// proofs, which only a CPI can pass, fail with `ProofTooLarge`.
+#[constant]
+pub const MAX_PROOF_LEN: usize = 1024;
+
+/// Proof versions the program can verify. Anything else is rejected with
+/// `UnsupportedProofVersion` rather than read with the wrong layout.
+/// Version 2 payment proofs are for the daily limit circuit and version 3
+/// for the meter allowlist circuit (see `payment_circuit`).
+#[constant]
+pub const SUPPORTED_PROOF_VERSIONS: [u8; 3] = [1, 2, 3];
+
+/// Most public inputs a `VerifyingKey` may have. `set_verifying_key` takes
+/// the whole key in one instruction, and each input adds a 64-byte point,
+/// so this keeps the largest key within a transaction.
+#[constant]
+pub const MAX_VK_PUBLIC_INPUTS: usize = 7;
+
+/// Number of `verify_proof_stage` calls a staged verification takes: the
+/// proof upload, folding the public inputs, then the pairing check.
+#[constant]
+pub const PROOF_VERIFICATION_STAGES: u8 = 3;
+
+/// How long a `PendingVerification` stays usable after stage 0 (~1 minute);
+/// past it, anyone may close it with `close_pending_verification`.
+#[constant]
+pub const PENDING_VERIFICATION_TTL_SLOTS: u64 = 150;
+
+/// How long a `ProofReceipt` can be consumed after `submit_proof_receipt`
+/// (~10 minutes); past it, anyone may close it with `close_proof_receipt`.
+#[constant]
+pub const PROOF_RECEIPT_TTL_SLOTS: u64 = 1_500;
+
+/// Prefix of the message an agent signs for a channel voucher, followed by
+/// the channel address, its `opened_at_slot` and the cumulative amount
+/// (both u64 LE).
+#[constant]
+pub const CHANNEL_VOUCHER_DOMAIN: &[u8] = b"agent_blink_pay:channel";
+
+/// Prefix of the message a policy oracle signs to attest a payment,
+/// followed by the agent, the meter, the amount (u64 LE), the category,
+/// the nonce (u64 LE) and the authorization's slot expiry (u64 LE).
+#[constant]
+pub const ATTESTATION_DOMAIN: &[u8] = b"agent_blink_pay:attestation";
+
+/// Upper bound on the Config's `grace_slots` (~1 minute). close_meter also
+/// waits this much longer, as authorizations may be recorded that late.
+#[constant]
+pub const MAX_GRACE_SLOTS: u16 = 150;
+
+/// How long an authorization re-opened by `report_settlement_failure` may
+/// be recorded again (~10 minutes).
+#[constant]
+pub const SETTLEMENT_RETRY_WINDOW_SLOTS: u64 = 1_500;
+
+/// Number of volume pricing tiers a meter can have.
+/// Array lengths in account types are written out literally for the IDL.
+#[constant]
+pub const MAX_PRICE_TIERS: usize = 4;
+
+/// Extra revenue recipients per Meter, besides its primary wallet.
+#[constant]
+pub const MAX_REVENUE_SPLITS: usize = 3;
+
+/// Largest referrer share of a meter's payments, in basis points.
+#[constant]
+pub const MAX_REFERRER_BPS: u16 = 1_000;
+
+/// Meters one authority's MeterIndex can list.
+#[constant]
+pub const MAX_METERS_PER_AUTHORITY: usize = 256;
+
 // =============================================================================
 // PROGRAM ENTRYPOINT
 // =============================================================================
@@ -30,671 +265,14557 @@ pub mod agent_blink_pay {
     /// Creates or updates an AgentPolicy account.
     /// 
     /// Called by the backend or via a Blink Action to set spending rules.
+    /// The signer creating the policy becomes its owner; later updates must
+    /// be signed by that owner. Creation also needs the agent's signature,
+    /// so nobody can claim an agent's policy slot before the agent does, and
+    /// fails with `AgentKeyRetired` for a key retired by `rotate_agent_key`.
+    /// 
+    /// The owner may be a PDA (e.g. a Squads vault). The owning program calls
+    /// this instruction via CPI, passes its PDA as `owner` and signs for it
+    /// with `invoke_signed` (`CpiContext::new_with_signer` in Anchor). The
+    /// runtime then marks the PDA as a signer, which is all that is checked.
+    /// The same applies to `set_policies_batch` and `extend_budget`.
     /// 
     /// # Arguments
-    /// * `policy_hash` - Commitment to the full policy (used as ZK public input)
-    /// * `allowed_category` - Category of spending allowed (e.g., AI_API = 1)
-    /// * `max_per_tx` - Maximum spend per transaction in smallest USDC units
-    /// * `frozen` - If true, agent cannot authorize any payments
+    /// * `params` - The policy fields to set (see `PolicyParams`)
     pub fn set_policy(
         ctx: Context<SetPolicy>,
-        policy_hash: [u8; 32],
-        allowed_category: u8,
-        max_per_tx: u64,
-        frozen: bool,
+        params: PolicyParams,
     ) -> Result<()> {
         let policy = &mut ctx.accounts.agent_policy;
-        
-        policy.agent_pubkey = ctx.accounts.agent.key();
-        policy.policy_hash = policy_hash;
-        policy.allowed_category = allowed_category;
-        policy.max_per_tx = max_per_tx;
-        policy.frozen = frozen;
+        let owner = ctx.accounts.owner.key();
+
+        if policy.agent_pubkey == Pubkey::default() {
+            // Freshly created by init_if_needed
+            require!(ctx.accounts.agent.is_signer, AgentBlinkPayError::AgentSignatureMissing);
+            require!(
+                ctx.accounts.retired_agent.owner != &crate::ID,
+                AgentBlinkPayError::AgentKeyRetired
+            );
+            policy.agent_pubkey = ctx.accounts.agent.key();
+            policy.owner = owner;
+            policy.agent_is_pda = !policy.agent_pubkey.is_on_curve();
+            policy.rent_payer = ctx.accounts.payer.key();
+        } else {
+            require!(policy.is_owner(&owner), AgentBlinkPayError::Unauthorized);
+        }
         policy.bump = ctx.bumps.agent_policy;
+
+        policy.apply_params(&params)?;
         
-        msg!("Policy set for agent: {:?}", policy.agent_pubkey);
+        msg!("Policy set for agent: {:?}, policy_id: {}", policy.agent_pubkey, params.policy_id);
         msg!("  allowed_category: {}, max_per_tx: {}, frozen: {}", 
-             allowed_category, max_per_tx, frozen);
+             params.allowed_category, params.max_per_tx, policy.frozen);
+        msg!("  total_budget: {}, lifetime_spent: {}, daily_limit: {}",
+             params.total_budget, policy.lifetime_spent, params.daily_limit);
         
         // Emit PolicyUpdated event for off-chain listener
-        emit!(PolicyUpdated {
+        emit!(policy.updated_event(Clock::get()?.slot));
+
+        Ok(())
+    }
+
+    /// Creates or updates up to `MAX_POLICY_BATCH` policies in one transaction.
+    /// 
+    /// Used by an organization admin provisioning many agents at once. The
+    /// admin becomes the owner of every policy created here and must already
+    /// own every policy updated here. Each agent whose policy is created
+    /// must sign, as in `set_policy`.
+    /// 
+    /// `remaining_accounts` carries one `(agent, agent_policy, retired_agent)`
+    /// triple per entry, in the same order as `params`; policy accounts must
+    /// be writable, and `retired_agent` is the agent's ["retired", agent]
+    /// address, checked as in `set_policy`. The batch is atomic: any invalid
+    /// entry aborts the whole instruction.
+    /// 
+    /// # Arguments
+    /// * `params` - One entry per policy, same fields as `set_policy`
+    pub fn set_policies_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SetPoliciesBatch<'info>>,
+        params: Vec<PolicyParams>,
+    ) -> Result<()> {
+        require!(!params.is_empty(), AgentBlinkPayError::EmptyBatch);
+        require!(params.len() <= MAX_POLICY_BATCH, AgentBlinkPayError::BatchTooLarge);
+        require!(
+            ctx.remaining_accounts.len() == params.len() * 3,
+            AgentBlinkPayError::BatchAccountsMismatch
+        );
+
+        let owner = ctx.accounts.owner.key();
+        let payer = ctx.accounts.payer.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let slot = Clock::get()?.slot;
+
+        for (entry, accounts) in params.iter().zip(ctx.remaining_accounts.chunks(3)) {
+            let agent_info = &accounts[0];
+            let policy_info = &accounts[1];
+            let retired_info = &accounts[2];
+
+            let policy_id_bytes = entry.policy_id.to_le_bytes();
+            let (expected_policy, bump) = Pubkey::find_program_address(
+                &[b"policy", agent_info.key.as_ref(), &policy_id_bytes],
+                ctx.program_id,
+            );
+            require_keys_eq!(
+                *policy_info.key,
+                expected_policy,
+                AgentBlinkPayError::InvalidPolicyAccount
+            );
+            require!(policy_info.is_writable, AgentBlinkPayError::InvalidPolicyAccount);
+
+            let mut policy = if policy_info.data_is_empty() {
+                require!(agent_info.is_signer, AgentBlinkPayError::AgentSignatureMissing);
+                let (expected_retired, _) = Pubkey::find_program_address(
+                    &[b"retired", agent_info.key.as_ref()],
+                    ctx.program_id,
+                );
+                require_keys_eq!(
+                    *retired_info.key,
+                    expected_retired,
+                    AgentBlinkPayError::InvalidPolicyAccount
+                );
+                require!(retired_info.owner != &crate::ID, AgentBlinkPayError::AgentKeyRetired);
+                create_pda_account(
+                    &payer,
+                    policy_info,
+                    AgentPolicy::LEN,
+                    &[b"policy", agent_info.key.as_ref(), &policy_id_bytes, &[bump]],
+                    &system_program,
+                )?;
+                AgentPolicy {
+                    agent_pubkey: *agent_info.key,
+                    owner,
+                    bump,
+                    agent_is_pda: !agent_info.key.is_on_curve(),
+                    rent_payer: *payer.key,
+                    ..Default::default()
+                }
+            } else {
+                require_keys_eq!(
+                    *policy_info.owner,
+                    crate::ID,
+                    AgentBlinkPayError::InvalidPolicyAccount
+                );
+                let policy = AgentPolicy::try_deserialize(&mut &policy_info.try_borrow_data()?[..])?;
+                require!(policy.is_owner(&owner), AgentBlinkPayError::Unauthorized);
+                policy
+            };
+
+            policy.apply_params(entry)?;
+
+            let mut data = policy_info.try_borrow_mut_data()?;
+            let mut writer: &mut [u8] = &mut data[..];
+            policy.try_serialize(&mut writer)?;
+
+            msg!("Policy set for agent: {:?}, policy_id: {}", policy.agent_pubkey, policy.policy_id);
+
+            emit!(policy.updated_event(slot));
+        }
+
+        Ok(())
+    }
+
+    /// Raises an agent's lifetime budget without touching the rest of the policy.
+    /// 
+    /// If the policy was frozen only because the budget ran out and the new
+    /// budget leaves headroom, the policy is unfrozen. A manual freeze is
+    /// left in place.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies to extend. The handler
+    ///   doesn't read it; it is an argument so the `agent_policy` seeds can
+    ///   be derived from it.
+    /// * `additional_budget` - Amount to add to `total_budget` (USDC smallest units)
+    pub fn extend_budget(
+        ctx: Context<ExtendBudget>,
+        _policy_id: u16,
+        additional_budget: u64,
+    ) -> Result<()> {
+        let policy = &mut ctx.accounts.agent_policy;
+
+        require!(policy.total_budget != 0, AgentBlinkPayError::BudgetUnlimited);
+
+        policy.total_budget = policy
+            .total_budget
+            .checked_add(additional_budget)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+
+        let unfrozen = policy.frozen
+            && policy.frozen_reason == freeze_reasons::BUDGET_EXHAUSTED
+            && !policy.budget_exhausted();
+        if unfrozen {
+            policy.frozen = false;
+            policy.frozen_reason = freeze_reasons::NONE;
+        }
+
+        msg!("Budget extended for agent: {:?}", policy.agent_pubkey);
+        msg!("  total_budget: {}, lifetime_spent: {}, unfrozen: {}",
+             policy.total_budget, policy.lifetime_spent, unfrozen);
+
+        emit!(BudgetExtended {
             agent_pubkey: policy.agent_pubkey,
-            policy_hash,
-            allowed_category,
-            max_per_tx,
-            frozen,
+            policy_id: policy.policy_id,
+            total_budget: policy.total_budget,
+            lifetime_spent: policy.lifetime_spent,
+            unfrozen,
             slot: Clock::get()?.slot,
         });
 
         Ok(())
     }
 
-    /// Creates a Meter account for a new paywalled API endpoint.
+    /// Appoints (or removes) the guardian of an agent's policy.
     /// 
-    /// Called by the backend when a provider uses the "Register API" flow.
+    /// The guardian is a second key, typically held by a human or monitoring
+    /// service, that may pause the agent but cannot change its spending rules.
     /// 
     /// # Arguments
-    /// * `price_per_call` - Price in USDC smallest units (e.g., 50000 = $0.05)
-    /// * `category` - Category enum for this meter (must match agent's allowed_category)
-    /// * `merchant_wallet_id` - Identifier for the merchant's Circle wallet
-    /// * `requires_zk` - Whether this meter requires ZK-checked policies
-    pub fn create_meter(
-        ctx: Context<CreateMeter>,
-        price_per_call: u64,
-        category: u8,
-        merchant_wallet_id: String,
-        requires_zk: bool,
+    /// * `policy_id` - Which of the agent's policies to guard (only used to
+    ///   derive the `agent_policy` PDA)
+    /// * `guardian` - Guardian pubkey, or `Pubkey::default()` to remove it
+    pub fn set_guardian(
+        ctx: Context<SetGuardian>,
+        _policy_id: u16,
+        guardian: Pubkey,
     ) -> Result<()> {
-        require!(merchant_wallet_id.len() <= 64, AgentBlinkPayError::MerchantWalletIdTooLong);
-        
-        let meter = &mut ctx.accounts.meter;
-        
-        meter.authority = ctx.accounts.authority.key();
-        meter.price_per_call = price_per_call;
-        meter.category = category;
-        meter.requires_zk = requires_zk;
-        meter.bump = ctx.bumps.meter;
-        
-        // Store merchant_wallet_id as fixed-size array
-        let mut wallet_id_bytes = [0u8; 64];
-        let id_bytes = merchant_wallet_id.as_bytes();
-        wallet_id_bytes[..id_bytes.len()].copy_from_slice(id_bytes);
-        meter.merchant_wallet_id = wallet_id_bytes;
-        meter.merchant_wallet_id_len = id_bytes.len() as u8;
-        
-        msg!("Meter created: {:?}", ctx.accounts.meter.key());
-        msg!("  price_per_call: {}, category: {}, requires_zk: {}", 
-             price_per_call, category, requires_zk);
-        
+        let policy = &mut ctx.accounts.agent_policy;
+        policy.guardian = guardian;
+
+        msg!("Guardian set for agent: {:?} -> {:?}", policy.agent_pubkey, guardian);
+
         Ok(())
     }
 
-    /// Verifies a ZK proof (Simulated via Self-CPI for MVP).
+    /// Pauses an agent's payments until `current_slot + duration_slots`.
     /// 
-    /// In a production system, this instruction would belong to a separate
-    /// Verifier Program (e.g. generated by Sunspot/Circom).
-    /// For the hackathon, we implement it here to demonstrate the architecture
-    /// of "Calling a Verifier".
+    /// Unlike `frozen`, a pause lapses on its own and needs no follow-up
+    /// transaction. While paused, both new authorizations and the recording of
+    /// already-issued ones are rejected. A pause never shortens an existing one.
     /// 
-    /// Public Inputs Struct used:
-    /// [amount (8), category (1), max_per_tx (8), allowed_category (1), salt (8)]
-    pub fn verify_proof(
-        _ctx: Context<VerifyProof>, 
-        proof: Vec<u8>,
-        public_inputs: Vec<u8>
+    /// Callable by the agent (policy owner) or the policy's guardian.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies to pause. Only the
+    ///   `agent_policy` seed constraint reads it.
+    /// * `duration_slots` - Number of slots to pause for
+    pub fn pause_policy(
+        ctx: Context<PausePolicy>,
+        _policy_id: u16,
+        duration_slots: u64,
     ) -> Result<()> {
-        // 1. Validate Input Length
-        // We expect: Amount(8) + Category(1) + Max(8) + Allowed(1) + Salt(8) = 26 bytes
-        require!(public_inputs.len() >= 26, AgentBlinkPayError::InvalidInputs);
-        require!(proof.len() >= 32, AgentBlinkPayError::InvalidProof);
+        let policy = &mut ctx.accounts.agent_policy;
+        let current_slot = Clock::get()?.slot;
 
-        // 2. Deserialize Inputs from byte array (Simulating Verifier Input Parsing)
-        let amount_bytes: [u8; 8] = public_inputs[0..8].try_into().unwrap();
-        let amount = u64::from_le_bytes(amount_bytes);
+        let paused_until_slot = current_slot
+            .checked_add(duration_slots)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        policy.paused_until_slot = policy.paused_until_slot.max(paused_until_slot);
 
-        let category = public_inputs[8];
+        msg!("Policy paused: agent={:?}, until_slot={}",
+             policy.agent_pubkey, policy.paused_until_slot);
 
-        let max_bytes: [u8; 8] = public_inputs[9..17].try_into().unwrap();
-        let max_per_tx = u64::from_le_bytes(max_bytes);
+        emit!(PolicyPaused {
+            agent_pubkey: policy.agent_pubkey,
+            policy_id: policy.policy_id,
+            paused_by: ctx.accounts.authority.key(),
+            paused_until_slot: policy.paused_until_slot,
+            slot: current_slot,
+        });
 
-        let allowed_category = public_inputs[17];
+        Ok(())
+    }
 
-        // 3. Enforce Constraints (The "Circuit" Logic)
-        // In a strict ZK setup, these arithmetic checks are done inside the SNARK circuit.
-        // Here, the "Verifier" program enforces them based on Public Inputs.
-        
-        msg!("Verifier: Checking constraints...");
-        msg!("  Amount ({}) <= Max ({})?", amount, max_per_tx);
-        msg!("  Category ({}) == Allowed ({})?", category, allowed_category);
+    /// Records a failed `authorize_payment_with_proof` attempt for an agent.
+    /// 
+    /// A failing authorize transaction rolls back, so it can't count its own
+    /// failures. Instead a watcher listed in the Config (typically the backend
+    /// relaying the agent's transactions) reports each failure here. Once
+    /// `failed_auth_attempts` reaches the Config's `auto_freeze_threshold`,
+    /// the policy is frozen and `CircuitBreakerTripped` is emitted. A
+    /// successful payment resets the counter.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies the failure happened under.
+    ///   Only used to derive the `agent_policy` PDA.
+    pub fn report_auth_failure(
+        ctx: Context<ReportAuthFailure>,
+        _policy_id: u16,
+    ) -> Result<()> {
+        let config = &ctx.accounts.config;
+        let policy = &mut ctx.accounts.agent_policy;
+        let current_slot = Clock::get()?.slot;
 
-        require!(amount <= max_per_tx, AgentBlinkPayError::AmountExceedsMax);
-        require!(category == allowed_category, AgentBlinkPayError::CategoryMismatch);
+        policy.failed_auth_attempts = policy.failed_auth_attempts.saturating_add(1);
+        policy.last_failed_slot = current_slot;
+
+        msg!("Auth failure reported: agent={:?}, attempts={}",
+             policy.agent_pubkey, policy.failed_auth_attempts);
+
+        let tripped = config.auto_freeze_threshold != 0
+            && policy.failed_auth_attempts >= config.auto_freeze_threshold
+            && !policy.frozen;
+        if tripped {
+            policy.frozen = true;
+            policy.frozen_reason = freeze_reasons::CIRCUIT_BREAKER;
+
+            emit!(CircuitBreakerTripped {
+                agent_pubkey: policy.agent_pubkey,
+                policy_id: policy.policy_id,
+                failed_auth_attempts: policy.failed_auth_attempts,
+                reported_by: ctx.accounts.watcher.key(),
+                slot: current_slot,
+            });
+
+            msg!("Circuit breaker tripped, policy frozen: agent={:?}", policy.agent_pubkey);
+        }
 
-        // 4. Verify "Proof" (Simulated Signature Check or Hash Check)
-        // For MVP, if we reached here, the constraints hold.
-        // We could verify the `proof` bytes are a signature of `public_inputs` signed by a "Proving Key".
-        // For simplicity, we just accept if constraints hold.
-        
-        msg!("Verifier: Proof Valid & Constraints Satisfied.");
         Ok(())
     }
 
-    /// Authorizes a payment by verifying a ZK proof of policy compliance.
+    /// Adds a meter to a policy's allowlist by creating its `AllowedMeter` record.
     /// 
-    /// This instruction enforces that the payment adheres to the agent's policy using
-    /// Zero-Knowledge Proofs (via a Verifier CPI).
+    /// The record is keyed by policy and meter, so it only applies to the
+    /// policy named by `policy_id`, and only that policy's owner can sign.
     /// 
-    /// Public Inputs to the ZK Circuit:
-    /// - policy_hash (from AgentPolicy account)
-    /// - amount (from instruction args)
-    /// - category (from instruction args)
+    /// # Arguments
+    /// * `policy_id` - Policy whose allowlist is changed. Read only by the
+    ///   `agent_policy` seeds, which the `allowed_meter` PDA is derived from.
+    pub fn allow_meter(ctx: Context<AllowMeter>, _policy_id: u16) -> Result<()> {
+        let allowed = &mut ctx.accounts.allowed_meter;
+
+        allowed.agent = ctx.accounts.agent.key();
+        allowed.policy = ctx.accounts.agent_policy.key();
+        allowed.meter = ctx.accounts.meter.key();
+        allowed.bump = ctx.bumps.allowed_meter;
+
+        msg!("Meter allowed: agent={:?}, meter={:?}", allowed.agent, allowed.meter);
+
+        Ok(())
+    }
+
+    /// Removes a meter from a policy's allowlist by closing its `AllowedMeter`
+    /// record. Rent goes back to the owner.
     /// 
-    /// The Proof proves:
-    /// "I know a private policy P where hash(P) == policy_hash, AND
-    ///  amount <= P.max_per_tx AND category == P.allowed_category"
+    /// # Arguments
+    /// * `policy_id` - Policy whose allowlist is changed (only used to derive
+    ///   the `agent_policy` PDA)
+    pub fn disallow_meter(ctx: Context<DisallowMeter>, _policy_id: u16) -> Result<()> {
+        msg!("Meter disallowed: agent={:?}, meter={:?}",
+             ctx.accounts.agent.key(), ctx.accounts.meter.key());
+
+        Ok(())
+    }
+
+    /// Bans a meter for a policy by creating its `DeniedMeter` record.
+    /// 
+    /// Applies to payments under the policy named by `policy_id` and
+    /// overrides category matching and the allowlist. Keyed by policy, so
+    /// only that policy's owner can lift the ban.
     /// 
     /// # Arguments
-    /// * `amount` - Amount to authorize in USDC smallest units
-    /// * `category` - Category of this payment
-    /// * `nonce` - Unique identifier to prevent replay attacks
-    /// * `expires_at_slot` - Slot after which this authorization expires
-    /// * `proof` - ZK proof bytes
-    pub fn authorize_payment_with_proof(
-        ctx: Context<AuthorizePayment>,
-        amount: u64,
-        category: u8,
-        nonce: u64,
-        expires_at_slot: u64,
-        proof: Vec<u8>,
-    ) -> Result<()> {
-        let policy = &ctx.accounts.agent_policy;
-        let meter = &ctx.accounts.meter;
-        
-        // 1. Basic Checks
-        require!(!policy.frozen, AgentBlinkPayError::PolicyFrozen);
-        require!(meter.category == category, AgentBlinkPayError::CategoryMismatch);
-        require!(proof.len() >= 32, AgentBlinkPayError::InvalidProof);
-        
-        // 2. Commitment Check (Policy Integrity)
-        // Ensure the stored policy hash matches the claimed parameters.
-        // This ensures the inputs we pass to the Verifier are indeed the Agent's Policy.
-        let salt = b"BlinkPay";
-        let computed_hash = solana_program::hash::hashv(&[
-            &policy.max_per_tx.to_le_bytes(),
-            &[policy.allowed_category],
-            salt
-        ]);
-        require!(
-            policy.policy_hash == computed_hash.to_bytes(),
-            AgentBlinkPayError::InvalidProof
-        );
+    /// * `policy_id` - Policy whose denylist is changed. Only read through the
+    ///   `agent_policy` seeds; the record itself is keyed by that account.
+    pub fn deny_meter(ctx: Context<DenyMeter>, _policy_id: u16) -> Result<()> {
+        let denied = &mut ctx.accounts.denied_meter;
 
-        // 3. Construct Public Inputs for Verifier
-        // We pass the Cleartext values to the Verifier as Public Inputs.
-        // The Verifier checks if they satisfy the constraints.
-        // Layout: Amount(8) | Category(1) | Max(8) | Allowed(1) | Salt(8)
-        let mut public_inputs = Vec::new();
-        public_inputs.extend_from_slice(&amount.to_le_bytes()); 
-        public_inputs.push(category);
-        public_inputs.extend_from_slice(&policy.max_per_tx.to_le_bytes());
-        public_inputs.push(policy.allowed_category);
-        public_inputs.extend_from_slice(salt);
+        denied.agent = ctx.accounts.agent.key();
+        denied.policy = ctx.accounts.agent_policy.key();
+        denied.meter = ctx.accounts.meter.key();
+        denied.bump = ctx.bumps.denied_meter;
 
-        msg!("ZK Verification: Calling External Verifier via CPI...");
-        
-        // 4. CPI Call to Verifier Instruction
-        // We call `verify_proof` on *this* program (Self-CPI).
-        let cpi_program = ctx.accounts.verifier_program.to_account_info();
-        let cpi_accounts = VerifyProof {
-             // No accounts needed for pure verification logic in this MVP
-        };
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        
-        agent_blink_pay::cpi::verify_proof(
-            cpi_ctx, 
-            proof, 
-            public_inputs
-        )?;
+        msg!("Meter denied: agent={:?}, meter={:?}", denied.agent, denied.meter);
 
-        msg!("ZK Verifier returned success.");
+        Ok(())
+    }
+
+    /// Lifts a ban by closing the `DeniedMeter` record. Rent goes back to the owner.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Policy whose denylist is changed (only needed to derive
+    ///   the `agent_policy` PDA)
+    pub fn undeny_meter(ctx: Context<UndenyMeter>, _policy_id: u16) -> Result<()> {
+        msg!("Meter undenied: agent={:?}, meter={:?}",
+             ctx.accounts.agent.key(), ctx.accounts.meter.key());
 
-        // 5. Verify Expiry (Chain Logic)
-        let current_slot = Clock::get()?.slot;
-        require!(current_slot <= expires_at_slot, AgentBlinkPayError::AuthorizationExpired);
-   
-        // 6. Create Authorization
-        let auth = &mut ctx.accounts.authorization;
-        
-        auth.agent = ctx.accounts.agent.key();
-        auth.meter = meter.key();
-        auth.amount = amount;
-        auth.category = category;
-        auth.nonce = nonce;
-        auth.expires_at_slot = expires_at_slot;
-        auth.used = false;
-        auth.bump = ctx.bumps.authorization;
-        
-        msg!("Payment authorized: agent={:?}, meter={:?}, amount={}, nonce={}",
-             auth.agent, auth.meter, amount, nonce);
-        
         Ok(())
     }
 
-    /// Records a meter payment by consuming an authorization.
+    /// Starts a two-step transfer of policy ownership by nominating `new_owner`.
     /// 
-    /// This marks the authorization as used and emits a MeterPaid event.
-    /// The off-chain Circle service listens for this event to execute
-    /// the actual USDC transfer.
+    /// Ownership only changes once `new_owner` signs `accept_policy_ownership`,
+    /// so a mistyped key can't strand the policy. There is deliberately no
+    /// single-step transfer. Nominating `Pubkey::default()` cancels a pending
+    /// transfer. The current owner keeps full control until acceptance.
     /// 
     /// # Arguments
-    /// * `nonce` - The nonce of the authorization to consume
-    pub fn record_meter_payment(
-        ctx: Context<RecordPayment>,
-        nonce: u64,
+    /// * `policy_id` - Which of the agent's policies to transfer. Not read by
+    ///   the handler; it exists so the `agent_policy` seeds can be checked.
+    /// * `new_owner` - Key that must accept the transfer
+    pub fn transfer_policy_ownership(
+        ctx: Context<TransferPolicyOwnership>,
+        _policy_id: u16,
+        new_owner: Pubkey,
     ) -> Result<()> {
-        let auth = &mut ctx.accounts.authorization;
-        
-        // Validate authorization is not already used
-        require!(!auth.used, AgentBlinkPayError::AuthorizationUsed);
-        
-        // Validate authorization has not expired
-        let current_slot = Clock::get()?.slot;
+        let policy = &mut ctx.accounts.agent_policy;
+
+        policy.pending_owner = if new_owner == Pubkey::default() {
+            None
+        } else {
+            Some(new_owner)
+        };
+
+        msg!("Ownership transfer started: agent={:?}, pending_owner={:?}",
+             policy.agent_pubkey, policy.pending_owner);
+
+        emit!(OwnershipTransferStarted {
+            agent_pubkey: policy.agent_pubkey,
+            policy_id: policy.policy_id,
+            owner: policy.effective_owner(),
+            pending_owner: new_owner,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Completes a policy ownership transfer. Must be signed by the pending owner.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies to accept (only used to
+    ///   derive the `agent_policy` PDA)
+    pub fn accept_policy_ownership(
+        ctx: Context<AcceptPolicyOwnership>,
+        _policy_id: u16,
+    ) -> Result<()> {
+        let policy = &mut ctx.accounts.agent_policy;
+        let new_owner = ctx.accounts.new_owner.key();
+
         require!(
-            current_slot <= auth.expires_at_slot,
-            AgentBlinkPayError::AuthorizationExpired
+            policy.pending_owner == Some(new_owner),
+            AgentBlinkPayError::NotPendingOwner
         );
-        
-        // Mark as used
-        auth.used = true;
-        
-        // Emit the payment event
-        // Off-chain services (Circle integration) listen for this event
-        // to trigger the actual USDC transfer
-        emit!(MeterPaid {
-            agent: auth.agent,
-            meter: auth.meter,
-            amount: auth.amount,
-            category: auth.category,
-            nonce: nonce,
-            slot: current_slot,
+
+        let previous_owner = policy.effective_owner();
+        policy.owner = new_owner;
+        policy.pending_owner = None;
+
+        msg!("Ownership transferred: agent={:?}, {:?} -> {:?}",
+             policy.agent_pubkey, previous_owner, new_owner);
+
+        emit!(OwnershipTransferred {
+            agent_pubkey: policy.agent_pubkey,
+            policy_id: policy.policy_id,
+            previous_owner,
+            new_owner,
+            slot: Clock::get()?.slot,
         });
-        
-        msg!("Payment recorded: agent={:?}, meter={:?}, amount={}, nonce={}",
-             auth.agent, auth.meter, auth.amount, nonce);
-        
+
         Ok(())
     }
-}
 
-// =============================================================================
-// ZK VERIFICATION HELPER
-// =============================================================================
+    /// Moves a policy to a new agent key after the old key leaked.
+    /// 
+    /// Creates the policy PDA for `new_agent` with all state copied over
+    /// (including `lifetime_spent`), then closes the old PDA to its
+    /// `rent_payer`. The payer funds the new PDA and becomes its rent payer.
+    /// The new agent must sign, as when `set_policy` creates a policy.
+    /// 
+    /// The old key is retired: its `RetiredAgent` tombstone, created here if
+    /// this is its first rotation, stops `set_policy` and
+    /// `set_policies_batch` from creating policies for it again, so whoever
+    /// holds the leaked key can't re-create the closed PDA.
+    /// 
+    /// Outstanding authorizations of the old key become unusable: their PDA
+    /// seeds and `record_meter_payment` both resolve the policy through the
+    /// old agent key, whose policy no longer exists. Each `policy_id` is
+    /// rotated separately, and `AllowedMeter` / `DeniedMeter` records are keyed
+    /// by policy, so they must be re-created for the new policy.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies to move. Only read by the
+    ///   `agent_policy` and `new_agent_policy` seeds; the handler copies the
+    ///   id from the old policy.
+    /// * `new_agent` - The replacement agent key
+    pub fn rotate_agent_key(
+        ctx: Context<RotateAgentKey>,
+        _policy_id: u16,
+        new_agent: Pubkey,
+    ) -> Result<()> {
+        let old_policy = &ctx.accounts.agent_policy;
+        let old_agent = old_policy.agent_pubkey;
 
-/// Verifies a ZK proof that the payment complies with the agent's policy.
-/// 
-/// # Arguments
-/// * `proof` - The ZK proof bytes generated by the Noir prover
-/// * `amount` - The payment amount (public input)
-/// * `category` - The payment category (public input)
-/// * `policy_hash` - Hash commitment to the policy (public input)
-/// 
-/// # Returns
-/// * `Ok(())` if proof is valid
-/// * `Err(InvalidProof)` if proof verification fails
-/// 
-/// # TODO
-/// This is a stub function. In production, implement via:
-/// 1. Sunspot-generated verifier program (CPI call)
-/// 2. Embedded verifier from Sunspot (inline verification)
-/// 
-/// Example Sunspot integration pattern:
-/// ```ignore
-/// // CPI to Sunspot verifier program
-/// let cpi_accounts = sunspot_verifier::cpi::accounts::Verify {
-///     // ... accounts
-/// };
-/// let cpi_ctx = CpiContext::new(verifier_program.to_account_info(), cpi_accounts);
-/// sunspot_verifier::cpi::verify(cpi_ctx, public_inputs, proof)?;
-/// ```
-    // NOTE: ZK Verification bypassed for MVP latency; enforcing policy via native Anchor checks.
-    // In production, this would be a CPI to the Sunspot-generated verifier program.
+        ctx.accounts.new_agent_policy.set_inner(AgentPolicy {
+            agent_pubkey: new_agent,
+            bump: ctx.bumps.new_agent_policy,
+            agent_is_pda: !new_agent.is_on_curve(),
+            rent_payer: ctx.accounts.payer.key(),
+            ..(**old_policy).clone()
+        });
 
+        let slot = Clock::get()?.slot;
+        let retired = &mut ctx.accounts.retired_agent;
+        if retired.agent == Pubkey::default() {
+            // Freshly created by init_if_needed
+            retired.agent = old_agent;
+            retired.retired_at_slot = slot;
+            retired.bump = ctx.bumps.retired_agent;
+        }
+        retired.new_agent = new_agent;
 
-// =============================================================================
-// ACCOUNT STRUCTURES & CONTEXTS
-// =============================================================================
+        msg!("Agent key rotated: {:?} -> {:?}, policy_id={}",
+             old_agent, new_agent, old_policy.policy_id);
 
-#[derive(Accounts)]
-pub struct VerifyProof {}
+        emit!(AgentKeyRotated {
+            old_agent,
+            new_agent,
+            policy_id: old_policy.policy_id,
+            slot,
+        });
 
-/// Agent's spending policy account.
-/// 
-/// PDA seeds: ["policy", agent_pubkey]
-/// 
-/// This defines what an agent is allowed to spend on and how much.
-/// The policy_hash is a commitment used as a public input to ZK proofs,
-/// allowing verification without revealing the full policy details.
+        Ok(())
+    }
+
+    /// Zeros the daily spend window without waiting for it to roll over.
+    /// 
+    /// Escape hatch for account migrations and counter fixes. Needs both the
+    /// policy owner and the global config admin, so an owner cannot lift the
+    /// daily limit on their own. Limits and frozen state are not touched.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Policy whose window is reset (only used to derive the
+    ///   `agent_policy` PDA)
+    pub fn reset_spend_window(
+        ctx: Context<ResetSpendWindow>,
+        _policy_id: u16,
+    ) -> Result<()> {
+        let policy = &mut ctx.accounts.agent_policy;
+        let current_slot = Clock::get()?.slot;
+
+        let old_spent_today = policy.spent_today;
+        let old_day_start_slot = policy.day_start_slot;
+        policy.spent_today = 0;
+        policy.day_start_slot = current_slot;
+        policy.alert_emitted = false;
+
+        msg!("Spend window reset: agent={:?}, spent_today={}, day_start_slot={}",
+             policy.agent_pubkey, old_spent_today, old_day_start_slot);
+
+        emit!(SpendWindowReset {
+            agent_pubkey: policy.agent_pubkey,
+            policy_id: policy.policy_id,
+            old_spent_today,
+            old_day_start_slot,
+            reset_by_owner: ctx.accounts.owner.key(),
+            reset_by_admin: ctx.accounts.admin.key(),
+            slot: current_slot,
+        });
+
+        Ok(())
+    }
+
+    /// Creates the global Config account. Only the program's upgrade
+    /// authority may call it; the signer becomes the admin.
+    /// 
+    /// # Arguments
+    /// * `params` - Initial program-wide settings
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        params: ConfigParams,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+
+        config.admin = ctx.accounts.admin.key();
+        config.bump = ctx.bumps.config;
+        config.apply(&params)?;
+
+        msg!("Config initialized, admin: {:?}", config.admin);
+
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            watchers: config.watchers,
+            auto_freeze_threshold: config.auto_freeze_threshold,
+            min_price_delay_slots: config.min_price_delay_slots,
+            min_price_per_call: config.min_price_per_call,
+            max_price_per_call: config.max_price_per_call,
+            usdc_mint: config.usdc_mint,
+            max_authorization_ttl_secs: config.max_authorization_ttl_secs,
+            grace_slots: config.grace_slots,
+            settlement_authority: config.settlement_authority,
+            dispute_window_slots: config.dispute_window_slots,
+            refund_window_slots: config.refund_window_slots,
+            verifier_program: config.verifier_program,
+            external_verifier: config.external_verifier,
+            enforcement_mode: config.enforcement_mode,
+            policy_oracle: config.policy_oracle,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Replaces the global Config settings. Admin only.
+    /// 
+    /// # Arguments
+    /// * `params` - New program-wide settings
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        params: ConfigParams,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.apply(&params)?;
+
+        msg!("Config updated by admin: {:?}", config.admin);
+
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            watchers: config.watchers,
+            auto_freeze_threshold: config.auto_freeze_threshold,
+            min_price_delay_slots: config.min_price_delay_slots,
+            min_price_per_call: config.min_price_per_call,
+            max_price_per_call: config.max_price_per_call,
+            usdc_mint: config.usdc_mint,
+            max_authorization_ttl_secs: config.max_authorization_ttl_secs,
+            grace_slots: config.grace_slots,
+            settlement_authority: config.settlement_authority,
+            dispute_window_slots: config.dispute_window_slots,
+            refund_window_slots: config.refund_window_slots,
+            verifier_program: config.verifier_program,
+            external_verifier: config.external_verifier,
+            enforcement_mode: config.enforcement_mode,
+            policy_oracle: config.policy_oracle,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Grows the Config created by an older program version to the current
+    /// `Config::LEN`. New fields are zero-initialized, except
+    /// `enforcement_mode`, which starts STRICT. Admin only.
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        let config_info = ctx.accounts.config.to_account_info();
+
+        // `admin` directly follows the discriminator in every layout
+        let old_len = {
+            let data = config_info.try_borrow_data()?;
+            require!(
+                data.len() >= 8 + 32 && data[8..8 + 32] == ctx.accounts.admin.key().to_bytes(),
+                AgentBlinkPayError::Unauthorized
+            );
+            data.len()
+        };
+
+        grow_account(
+            &config_info,
+            Config::DISCRIMINATOR,
+            Config::LEN,
+            &ctx.accounts.admin,
+            &ctx.accounts.system_program,
+        )?;
+
+        // Programs before `enforcement_mode` rejected every failed proof
+        if old_len <= Config::ENFORCEMENT_MODE_OFFSET {
+            config_info.try_borrow_mut_data()?[Config::ENFORCEMENT_MODE_OFFSET] =
+                enforcement_modes::STRICT;
+        }
+
+        msg!("Config migrated: {:?}", config_info.key());
+
+        Ok(())
+    }
+
+    /// Installs or replaces the verifying key of a circuit. Admin only.
+    /// 
+    /// Proofs are checked against the key in the circuit's `VerifyingKey`
+    /// account, so a new circuit version is rolled out by setting its key
+    /// here; proofs made for the previous key stop verifying at once. Set
+    /// `locked` to freeze the key for good: a locked key can't be updated
+    /// again, not even by the admin. Fails with `CircuitSchemeConflict` if
+    /// the circuit has an UltraHonk key.
+    /// 
+    /// # Arguments
+    /// * `circuit_id` - The circuit (see `circuit_ids`)
+    /// * `params` - The key, its circuit version and whether to lock it
+    pub fn set_verifying_key(
+        ctx: Context<SetVerifyingKey>,
+        circuit_id: u8,
+        params: VerifyingKeyParams,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.honk_verifying_key.data_is_empty(),
+            AgentBlinkPayError::CircuitSchemeConflict
+        );
+        let vk = &mut ctx.accounts.verifying_key;
+        require!(!vk.locked, AgentBlinkPayError::VerifyingKeyLocked);
+        require!(
+            !params.ic.is_empty() && params.ic.len() <= MAX_VK_PUBLIC_INPUTS + 1,
+            AgentBlinkPayError::InvalidVerifyingKey
+        );
+
+        vk.set_inner(VerifyingKey {
+            circuit_id,
+            circuit_version: params.circuit_version,
+            locked: params.locked,
+            bump: ctx.bumps.verifying_key,
+            alpha_g1: params.alpha_g1,
+            beta_g2: params.beta_g2,
+            gamma_g2: params.gamma_g2,
+            delta_g2: params.delta_g2,
+            ic: params.ic,
+            meter_count: vk.meter_count,
+        });
+        let num_public_inputs = vk.num_public_inputs() as u8;
+
+        msg!(
+            "Verifying key set: circuit {} v{}, {} public inputs, locked: {}",
+            circuit_id,
+            params.circuit_version,
+            num_public_inputs,
+            params.locked
+        );
+
+        emit!(VerifyingKeySet {
+            circuit_id,
+            circuit_version: params.circuit_version,
+            num_public_inputs,
+            locked: params.locked,
+            admin: ctx.accounts.admin.key(),
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Removes a circuit's verifying key, returning its rent to the admin.
+    /// Admin only.
+    /// 
+    /// Fails with `VerifyingKeyInUse` while any meter has the circuit as
+    /// its `circuit_id`, and with `VerifyingKeyLocked` for a locked key.
+    /// Proofs for the circuit can't be verified until a key is set again.
+    /// 
+    /// # Arguments
+    /// * `circuit_id` - The circuit (see `circuit_ids`)
+    pub fn remove_verifying_key(ctx: Context<RemoveVerifyingKey>, circuit_id: u8) -> Result<()> {
+        let vk = &ctx.accounts.verifying_key;
+        require!(!vk.locked, AgentBlinkPayError::VerifyingKeyLocked);
+        require!(vk.meter_count == 0, AgentBlinkPayError::VerifyingKeyInUse);
+
+        msg!("Verifying key removed: circuit {}", circuit_id);
+
+        emit!(VerifyingKeyRemoved {
+            circuit_id,
+            scheme: proof_schemes::GROTH16,
+            admin: ctx.accounts.admin.key(),
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Installs or replaces the UltraHonk verifier of a circuit. Admin only.
+    /// 
+    /// The circuit's UltraHonk proofs then go by CPI to
+    /// `params.verifier_program`, which holds the circuit's key. A circuit
+    /// verifies proofs of one scheme only: this fails with
+    /// `CircuitSchemeConflict` while it has a Groth16 `VerifyingKey`, which
+    /// must be removed first. `locked` works as for `set_verifying_key`.
+    /// 
+    /// # Arguments
+    /// * `circuit_id` - The circuit (see `circuit_ids`)
+    /// * `params` - The verifier program, key hash, circuit version and lock
+    pub fn set_honk_verifying_key(
+        ctx: Context<SetHonkVerifyingKey>,
+        circuit_id: u8,
+        params: HonkVerifyingKeyParams,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.groth16_verifying_key.data_is_empty(),
+            AgentBlinkPayError::CircuitSchemeConflict
+        );
+        let vk = &mut ctx.accounts.honk_verifying_key;
+        require!(!vk.locked, AgentBlinkPayError::VerifyingKeyLocked);
+        require!(
+            params.verifier_program != Pubkey::default(),
+            AgentBlinkPayError::VerifierProgramMissing
+        );
+
+        vk.set_inner(HonkVerifyingKey {
+            circuit_id,
+            circuit_version: params.circuit_version,
+            locked: params.locked,
+            bump: ctx.bumps.honk_verifying_key,
+            verifier_program: params.verifier_program,
+            vk_hash: params.vk_hash,
+            meter_count: vk.meter_count,
+        });
+
+        msg!(
+            "Honk verifying key set: circuit {} v{}, verifier {:?}, locked: {}",
+            circuit_id,
+            params.circuit_version,
+            params.verifier_program,
+            params.locked
+        );
+
+        emit!(HonkVerifyingKeySet {
+            circuit_id,
+            circuit_version: params.circuit_version,
+            verifier_program: params.verifier_program,
+            vk_hash: params.vk_hash,
+            locked: params.locked,
+            admin: ctx.accounts.admin.key(),
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Removes a circuit's UltraHonk verifier, returning its rent to the
+    /// admin. Admin only; fails like `remove_verifying_key`.
+    /// 
+    /// # Arguments
+    /// * `circuit_id` - The circuit (see `circuit_ids`)
+    pub fn remove_honk_verifying_key(
+        ctx: Context<RemoveHonkVerifyingKey>,
+        circuit_id: u8,
+    ) -> Result<()> {
+        let vk = &ctx.accounts.honk_verifying_key;
+        require!(!vk.locked, AgentBlinkPayError::VerifyingKeyLocked);
+        require!(vk.meter_count == 0, AgentBlinkPayError::VerifyingKeyInUse);
+
+        msg!("Honk verifying key removed: circuit {}", circuit_id);
+
+        emit!(VerifyingKeyRemoved {
+            circuit_id,
+            scheme: proof_schemes::ULTRA_HONK,
+            admin: ctx.accounts.admin.key(),
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Grows an AgentPolicy created by an older program version to the
+    /// current `AgentPolicy::LEN`.
+    /// 
+    /// New fields are appended to the end of the account and are zero-initialized,
+    /// which is the "feature disabled" value for each of them. Safe to call on an
+    /// account that is already up to date. Policies that predate `rent_payer`
+    /// are left without one; their rent goes back to the policy owner.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies to migrate. Unused by the
+    ///   handler, but needed to derive the `agent_policy` PDA.
+    pub fn migrate_policy(ctx: Context<MigratePolicy>, _policy_id: u16) -> Result<()> {
+        let policy_info = ctx.accounts.agent_policy.to_account_info();
+
+        grow_account(
+            &policy_info,
+            AgentPolicy::DISCRIMINATOR,
+            AgentPolicy::LEN,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+
+        msg!("Policy migrated: {:?}", policy_info.key());
+
+        Ok(())
+    }
+
+    /// Moves a policy created before policies were keyed by `policy_id`
+    /// (PDA `["policy", agent]`) to the default policy PDA
+    /// `["policy", agent, DEFAULT_POLICY_ID]`, then closes the old account.
+    /// 
+    /// The legacy account may be in any earlier layout; fields it doesn't have
+    /// yet take their zero defaults. Its rent goes to the payer, who funds the
+    /// new account and is recorded as its `rent_payer`.
+    pub fn migrate_legacy_policy(ctx: Context<MigrateLegacyPolicy>) -> Result<()> {
+        let legacy_info = ctx.accounts.legacy_policy.to_account_info();
+
+        let legacy = {
+            let data = legacy_info.try_borrow_data()?;
+            require!(
+                data.len() >= 8 && data[..8] == AgentPolicy::DISCRIMINATOR,
+                AgentBlinkPayError::InvalidAccountData
+            );
+            let mut padded = data.to_vec();
+            padded.resize(AgentPolicy::LEN.max(padded.len()), 0);
+            AgentPolicy::try_deserialize(&mut &padded[..])?
+        };
+
+        let policy = &mut ctx.accounts.agent_policy;
+        policy.set_inner(AgentPolicy {
+            policy_id: DEFAULT_POLICY_ID,
+            bump: ctx.bumps.agent_policy,
+            rent_payer: ctx.accounts.payer.key(),
+            ..legacy
+        });
+
+        close_account(&legacy_info, &ctx.accounts.payer.to_account_info())?;
+
+        msg!("Legacy policy migrated: {:?} -> {:?}", legacy_info.key(), policy.key());
+
+        Ok(())
+    }
+
+    /// Creates a Meter account for a new paywalled API endpoint.
+    /// 
+    /// Called by the backend when a provider uses the "Register API" flow.
+    /// The meter is appended to the authority's `MeterIndex`, which is
+    /// created on first use and grows by one entry per meter.
+    /// 
+    /// # Arguments
+    /// * `price_per_call` - Price in USDC smallest units (e.g., 50000 = $0.05)
+    /// * `category` - Category enum for this meter (must match agent's allowed_category)
+    /// * `merchant_wallet_id` - Identifier for the merchant's Circle wallet
+    /// * `requires_zk` - Whether this meter requires ZK-checked policies
+    /// * `kind` - Per-call pricing or a flat periodic subscription (fixed once created)
+    /// * `free_calls` - Calls per agent that may be authorized for free
+    /// * `name` - Display name of the API (1-32 bytes of UTF-8)
+    /// * `endpoint_hash` - Hash of the endpoint URL the meter charges for
+    /// * `referrer_wallet_id` - Circle wallet of the integrator who onboarded the merchant
+    ///   (empty without a referrer; fixed once created)
+    /// * `referrer_bps` - Referrer's share of each payment, at most `MAX_REFERRER_BPS`
+    /// * `min_proof_version` - Oldest circuit version accepted in proofs (0 = any)
+    /// * `accepted_mint` - Token the meter settles in (None = the Config's USDC mint)
+    pub fn create_meter(
+        ctx: Context<CreateMeter>,
+        price_per_call: u64,
+        category: u8,
+        merchant_wallet_id: String,
+        requires_zk: bool,
+        kind: MeterKind,
+        free_calls: u16,
+        name: String,
+        endpoint_hash: [u8; 32],
+        referrer_wallet_id: String,
+        referrer_bps: u16,
+        min_proof_version: u8,
+        accepted_mint: Option<Pubkey>,
+    ) -> Result<()> {
+        let params = MeterParams {
+            price_per_call,
+            category,
+            merchant_wallet_id,
+            requires_zk,
+            kind,
+            free_calls,
+            name,
+            endpoint_hash,
+            referrer_wallet_id,
+            referrer_bps,
+            min_proof_version,
+            accepted_mint,
+        };
+        ctx.accounts.config.check_price(params.price_per_call)?;
+
+        let meter = &mut ctx.accounts.meter;
+        meter.initialize(ctx.accounts.authority.key(), ctx.bumps.meter, &params, &ctx.accounts.config)?;
+        MeterCounters::initialize(&ctx.accounts.meter_counters, meter, ctx.bumps.meter_counters)?;
+
+        MeterIndex::push(
+            &mut ctx.accounts.meter_index,
+            ctx.accounts.authority.key(),
+            ctx.bumps.meter_index,
+            meter.key(),
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )?;
+
+        emit!(meter.created_event(
+            meter.key(),
+            ctx.accounts.meter_id.key(),
+            params,
+            Clock::get()?.slot,
+        ));
+
+        msg!("Meter created: {:?}", ctx.accounts.meter.key());
+        msg!("  price_per_call: {}, category: {}, requires_zk: {}", 
+             price_per_call, category, requires_zk);
+        
+        Ok(())
+    }
+
+    /// Creates a Meter whose address derives from a string id instead of a
+    /// throwaway `meter_id` account.
+    /// 
+    /// Clients derive the address as
+    /// `findProgramAddress(["meter", authority, endpoint_id], program_id)`,
+    /// with `endpoint_id` as its raw ASCII bytes. The id is stored on the
+    /// meter, and later meter instructions take any account as `meter_id`.
+    /// 
+    /// # Arguments
+    /// * `endpoint_id` - The meter's id, 1-32 bytes of ASCII
+    /// * `params` - Same settings as `create_meter`
+    pub fn create_meter_v2(
+        ctx: Context<CreateMeterV2>,
+        endpoint_id: String,
+        params: MeterParams,
+    ) -> Result<()> {
+        require!(
+            !endpoint_id.is_empty() && endpoint_id.len() <= 32 && endpoint_id.is_ascii(),
+            AgentBlinkPayError::InvalidEndpointId
+        );
+
+        ctx.accounts.config.check_price(params.price_per_call)?;
+
+        let meter = &mut ctx.accounts.meter;
+        meter.initialize(ctx.accounts.authority.key(), ctx.bumps.meter, &params, &ctx.accounts.config)?;
+        MeterCounters::initialize(&ctx.accounts.meter_counters, meter, ctx.bumps.meter_counters)?;
+
+        let mut endpoint_id_bytes = [0u8; 32];
+        endpoint_id_bytes[..endpoint_id.len()].copy_from_slice(endpoint_id.as_bytes());
+        meter.endpoint_id = endpoint_id_bytes;
+        meter.endpoint_id_len = endpoint_id.len() as u8;
+
+        MeterIndex::push(
+            &mut ctx.accounts.meter_index,
+            ctx.accounts.authority.key(),
+            ctx.bumps.meter_index,
+            meter.key(),
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )?;
+
+        msg!("Meter created: {:?}, endpoint_id: {}", meter.key(), endpoint_id);
+
+        emit!(meter.created_event(meter.key(), Pubkey::default(), params, Clock::get()?.slot));
+
+        Ok(())
+    }
+
+    /// Creates a Meter with the pricing of an existing meter of the same
+    /// authority, for merchants registering many similar endpoints.
+    /// 
+    /// Copies `price_per_call`, `category`, `requires_zk`, `accepted_mint`
+    /// and the revenue splits, plus the merchant wallet, kind and name the
+    /// new meter can't exist without. Everything else (tiers, free calls, endpoint hash,
+    /// referrer, ...) starts unset; use the usual meter instructions to
+    /// change it. The clone's PDA derives from `new_meter_id` like
+    /// `create_meter`'s does from `meter_id`.
+    /// 
+    /// The copied price is the one the source charges now: a scheduled
+    /// increase that has taken effect counts, one still pending doesn't.
+    /// 
+    /// # Arguments
+    /// * `overrides` - Price and category to use instead of the source's
+    pub fn clone_meter(ctx: Context<CloneMeter>, overrides: CloneMeterOverrides) -> Result<()> {
+        let source = &ctx.accounts.source_meter;
+        let current_slot = Clock::get()?.slot;
+        let params = MeterParams {
+            price_per_call: overrides
+                .price_per_call
+                .unwrap_or(source.effective_price(current_slot)),
+            category: overrides.category.unwrap_or(source.category),
+            merchant_wallet_id: source.merchant_wallet_id_str().to_string(),
+            requires_zk: source.requires_zk,
+            kind: source.kind,
+            free_calls: 0,
+            name: source.name_str().to_string(),
+            endpoint_hash: [0; 32],
+            referrer_wallet_id: String::new(),
+            referrer_bps: 0,
+            min_proof_version: 0,
+            accepted_mint: Some(source.accepted_mint),
+        };
+        ctx.accounts.config.check_price(params.price_per_call)?;
+
+        let meter = &mut ctx.accounts.meter;
+        meter.initialize(ctx.accounts.authority.key(), ctx.bumps.meter, &params, &ctx.accounts.config)?;
+        MeterCounters::initialize(&ctx.accounts.meter_counters, meter, ctx.bumps.meter_counters)?;
+        meter.splits = source.splits;
+
+        MeterIndex::push(
+            &mut ctx.accounts.meter_index,
+            ctx.accounts.authority.key(),
+            ctx.bumps.meter_index,
+            meter.key(),
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )?;
+
+        msg!("Meter cloned: {:?} from {:?}", meter.key(), source.key());
+
+        emit!(meter.created_event(
+            meter.key(),
+            ctx.accounts.new_meter_id.key(),
+            params,
+            current_slot,
+        ));
+
+        Ok(())
+    }
+
+    /// Updates the pricing and metadata fields of an existing Meter.
+    /// Signed by the meter's authority or its operator.
+    /// 
+    /// Price increases are timelocked: they are stored as `pending_price` and
+    /// take effect `min_price_delay_slots` (from the Config) later, so agents
+    /// can react before paying more. Decreases apply immediately and cancel
+    /// any pending increase. `MeterPriceScheduled` is emitted for every price
+    /// change.
+    /// 
+    /// Outstanding authorizations keep the `amount` and price they were
+    /// issued at; `MeterPaid` flags a price that changed since. A category
+    /// change instead voids them: recording checks that the authorization's
+    /// category still matches the meter's and fails with `CategoryMismatch`.
+    /// 
+    /// # Arguments
+    /// * `price_per_call` - New price in USDC smallest units
+    /// * `category` - New category enum for this meter
+    /// * `requires_zk` - Whether this meter requires ZK-checked policies
+    /// * `free_calls` - Calls per agent that may be authorized for free
+    /// * `name` - Display name of the API (1-32 bytes of UTF-8)
+    /// * `endpoint_hash` - Hash of the endpoint URL the meter charges for
+    /// * `max_amount_per_payment` - Largest authorization accepted (0 = the price only)
+    /// * `min_proof_version` - Oldest circuit version accepted in proofs (0 = any)
+    pub fn update_meter(
+        ctx: Context<UpdateMeterSettings>,
+        price_per_call: u64,
+        category: u8,
+        requires_zk: bool,
+        free_calls: u16,
+        name: String,
+        endpoint_hash: [u8; 32],
+        max_amount_per_payment: u64,
+        min_proof_version: u8,
+    ) -> Result<()> {
+        ctx.accounts.config.check_price(price_per_call)?;
+        Category::try_from(category)?;
+
+        let current_slot = Clock::get()?.slot;
+        let delay = ctx.accounts.config.min_price_delay_slots;
+        let meter = &mut ctx.accounts.meter;
+        meter.settle_price(current_slot);
+
+        let old_price = meter.price_per_call;
+        let effective_slot = if price_per_call > old_price {
+            current_slot.saturating_add(delay)
+        } else {
+            current_slot
+        };
+
+        let event = MeterUpdated {
+            meter: meter.key(),
+            old_price_per_call: old_price,
+            new_price_per_call: price_per_call,
+            old_category: meter.category,
+            new_category: category,
+            old_requires_zk: meter.requires_zk,
+            new_requires_zk: requires_zk,
+            old_free_calls: meter.free_calls,
+            new_free_calls: free_calls,
+            old_name: meter.name_str().to_string(),
+            new_name: name.clone(),
+            old_endpoint_hash: meter.endpoint_hash,
+            new_endpoint_hash: endpoint_hash,
+            old_max_amount_per_payment: meter.max_amount_per_payment,
+            new_max_amount_per_payment: max_amount_per_payment,
+            old_min_proof_version: meter.min_proof_version,
+            new_min_proof_version: min_proof_version,
+            slot: current_slot,
+        };
+
+        if effective_slot > current_slot {
+            meter.pending_price = price_per_call;
+            meter.price_effective_slot = effective_slot;
+        } else {
+            meter.price_per_call = price_per_call;
+            meter.pending_price = 0;
+            meter.price_effective_slot = 0;
+        }
+        meter.category = category;
+        meter.requires_zk = requires_zk;
+        meter.free_calls = free_calls;
+        meter.set_name(&name)?;
+        meter.endpoint_hash = endpoint_hash;
+        meter.max_amount_per_payment = max_amount_per_payment;
+        meter.min_proof_version = min_proof_version;
+
+        msg!("Meter updated: {:?}", meter.key());
+        msg!("  price_per_call: {}, category: {}, requires_zk: {}, free_calls: {}",
+             price_per_call, category, requires_zk, free_calls);
+
+        if price_per_call != old_price {
+            emit!(MeterPriceScheduled {
+                meter: meter.key(),
+                old_price,
+                new_price: price_per_call,
+                effective_slot,
+                slot: current_slot,
+            });
+        }
+        emit!(event);
+
+        Ok(())
+    }
+
+    /// Replaces a meter's volume pricing tiers.
+    /// 
+    /// A tier applies once an agent has `min_calls` recorded payments to the
+    /// meter; the highest applicable tier wins. Tiers with `price == 0` are
+    /// unset, and an agent below every set tier pays `price_per_call`. While
+    /// any tier is set, authorizations must be for exactly the tier price.
+    /// 
+    /// Tiers are discounts: a set tier's price may not exceed the meter's
+    /// effective price (`TierAboveMeterPrice`), so raising what agents pay
+    /// still goes through `update_meter`'s timelock.
+    /// 
+    /// # Arguments
+    /// * `tiers` - The new tiers; set tiers must have increasing `min_calls`
+    pub fn set_meter_tiers(ctx: Context<UpdateMeter>, tiers: [PriceTier; 4]) -> Result<()> {
+        let current_slot = Clock::get()?.slot;
+        let price = ctx.accounts.meter.effective_price(current_slot);
+        let mut last_min_calls = None;
+        for tier in tiers.iter().filter(|tier| tier.is_set()) {
+            require!(
+                last_min_calls.map_or(true, |last| tier.min_calls > last),
+                AgentBlinkPayError::InvalidPriceTiers
+            );
+            require!(tier.price <= price, AgentBlinkPayError::TierAboveMeterPrice);
+            last_min_calls = Some(tier.min_calls);
+        }
+
+        let meter = &mut ctx.accounts.meter;
+        meter.tiers = tiers;
+
+        msg!("Meter tiers set: {:?}", meter.key());
+
+        emit!(MeterTiersUpdated {
+            meter: meter.key(),
+            tiers,
+            slot: current_slot,
+        });
+
+        Ok(())
+    }
+
+    /// Replaces a meter's revenue splits.
+    /// 
+    /// Each set split (`bps != 0`) sends its share of every payment to an
+    /// extra Circle wallet; the primary `merchant_wallet_id` gets the rest,
+    /// so split shares plus the primary's implicit share total 10000 bps.
+    /// On meters with a referrer, splits divide what is left after the
+    /// referrer's share. Per-recipient amounts are reported in `MeterPaid`.
+    /// 
+    /// # Arguments
+    /// * `splits` - The new splits; unset entries must have `bps == 0`
+    pub fn set_meter_splits(ctx: Context<UpdateMeter>, splits: [RevenueSplit; 3]) -> Result<()> {
+        let mut total_bps: u16 = 0;
+        for split in splits.iter().filter(|split| split.is_set()) {
+            require!(
+                split.wallet_id_len as usize <= split.wallet_id.len(),
+                AgentBlinkPayError::InvalidRevenueSplits
+            );
+            check_wallet_id(&split.wallet_id[..split.wallet_id_len as usize])?;
+            total_bps = total_bps
+                .checked_add(split.bps)
+                .filter(|bps| *bps <= MAX_BPS)
+                .ok_or(AgentBlinkPayError::InvalidRevenueSplits)?;
+        }
+
+        let meter = &mut ctx.accounts.meter;
+        meter.splits = splits;
+
+        msg!("Meter splits set: meter={:?}, split_bps={}", meter.key(), total_bps);
+
+        emit!(MeterSplitsUpdated {
+            meter: meter.key(),
+            splits,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Caps how many payments a meter accepts per window of slots, across
+    /// all agents. Over-limit `record_meter_payment` and
+    /// `record_subscription_call` fail with `MeterRateLimited`.
+    /// 
+    /// The window itself lives in the meter's `MeterCounters`, which only
+    /// recording write-locks. A window already in progress carries over.
+    /// 
+    /// # Arguments
+    /// * `max_calls_per_window` - Calls allowed per window (0 = unlimited)
+    /// * `window_slots` - Window length in slots; must be non-zero when limited
+    pub fn set_meter_rate_limit(
+        ctx: Context<UpdateMeter>,
+        max_calls_per_window: u32,
+        window_slots: u32,
+    ) -> Result<()> {
+        require!(
+            max_calls_per_window == 0 || window_slots > 0,
+            AgentBlinkPayError::InvalidRateLimit
+        );
+
+        let meter = &mut ctx.accounts.meter;
+        let old_max_calls_per_window = meter.max_calls_per_window;
+        let old_window_slots = meter.window_slots;
+        meter.max_calls_per_window = max_calls_per_window;
+        meter.window_slots = window_slots;
+
+        msg!("Meter rate limit set: meter={:?}, max_calls_per_window={}, window_slots={}",
+             meter.key(), max_calls_per_window, window_slots);
+
+        emit!(MeterRateLimitUpdated {
+            meter: meter.key(),
+            old_max_calls_per_window,
+            new_max_calls_per_window: max_calls_per_window,
+            old_window_slots,
+            new_window_slots: window_slots,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Stops new payments to a meter, e.g. during maintenance.
+    /// Signed by the meter's authority or its operator.
+    /// 
+    /// Authorizations issued before the pause can still be recorded until
+    /// they expire, so in-flight calls settle. Pausing an already paused meter keeps the
+    /// original `paused_at_slot`.
+    pub fn pause_meter(ctx: Context<OperateMeter>) -> Result<()> {
+        let meter = &mut ctx.accounts.meter;
+        let current_slot = Clock::get()?.slot;
+
+        if meter.active {
+            meter.active = false;
+            meter.paused_at_slot = current_slot;
+        }
+
+        msg!("Meter paused: {:?}, since slot {}", meter.key(), meter.paused_at_slot);
+
+        emit!(MeterStatusChanged {
+            meter: meter.key(),
+            active: false,
+            slot: current_slot,
+        });
+
+        Ok(())
+    }
+
+    /// Resumes payments to a paused meter. Signed by the meter's authority
+    /// or its operator.
+    pub fn unpause_meter(ctx: Context<OperateMeter>) -> Result<()> {
+        let meter = &mut ctx.accounts.meter;
+
+        meter.active = true;
+        meter.paused_at_slot = 0;
+
+        msg!("Meter unpaused: {:?}", meter.key());
+
+        emit!(MeterStatusChanged {
+            meter: meter.key(),
+            active: true,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Points a meter's payments at a new Circle wallet, e.g. after the old
+    /// one was compromised. The meter keeps its address.
+    /// 
+    /// The meter must be paused so no new authorizations are issued while
+    /// the off-chain service switches routing on `MerchantWalletUpdated`.
+    /// 
+    /// # Arguments
+    /// * `new_wallet_id` - The new Circle wallet id (same rules as `create_meter`)
+    pub fn update_merchant_wallet(ctx: Context<UpdateMeter>, new_wallet_id: String) -> Result<()> {
+        let meter = &mut ctx.accounts.meter;
+        require!(!meter.active, AgentBlinkPayError::MeterNotPaused);
+
+        let old_wallet_id = meter.merchant_wallet_id_str().to_string();
+        meter.set_merchant_wallet_id(&new_wallet_id)?;
+
+        msg!("Merchant wallet updated: meter={:?}, {} -> {}",
+             meter.key(), old_wallet_id, new_wallet_id);
+
+        emit!(MerchantWalletUpdated {
+            meter: meter.key(),
+            old_wallet_id,
+            new_wallet_id,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Closes a Meter and sends its rent to `recipient`.
+    /// 
+    /// The meter must have been paused (`pause_meter`) for more than
+    /// `MAX_AUTHORIZATION_TTL_SLOTS`, so every authorization against it has
+    /// expired or been used by the time it disappears.
+    /// 
+    /// The creator's `MeterIndex` address is always required, so the meter
+    /// can't be closed while still listed there. Meters created before the
+    /// index existed may have none, in which case there's nothing to update.
+    /// A meter with a `circuit_id` also needs that circuit's `VerifyingKey`
+    /// or `HonkVerifyingKey`, whose meter count it leaves.
+    pub fn close_meter(ctx: Context<CloseMeter>) -> Result<()> {
+        let meter = &ctx.accounts.meter;
+        let current_slot = Clock::get()?.slot;
+
+        require!(meter.paused_at_slot != 0, AgentBlinkPayError::MeterNotPaused);
+        require!(
+            current_slot
+                > meter
+                    .paused_at_slot
+                    .saturating_add(MAX_AUTHORIZATION_TTL_SLOTS)
+                    .saturating_add(MAX_GRACE_SLOTS as u64),
+            AgentBlinkPayError::MeterPausedTooRecently
+        );
+
+        let index_info = &ctx.accounts.meter_index;
+        if index_info.owner == &crate::ID {
+            let mut meter_index = MeterIndex::try_deserialize(&mut &index_info.try_borrow_data()?[..])?;
+            meter_index.remove(&meter.key());
+
+            let mut data = index_info.try_borrow_mut_data()?;
+            let mut writer: &mut [u8] = &mut data[..];
+            meter_index.try_serialize(&mut writer)?;
+        }
+        if meter.circuit_id != 0 {
+            let meter_count = circuit_meter_count(
+                ctx.accounts.verifying_key.as_mut(),
+                ctx.accounts.honk_verifying_key.as_mut(),
+            )?;
+            *meter_count = meter_count.saturating_sub(1);
+        }
+
+        msg!("Meter closed: {:?}, rent to {:?}",
+             meter.key(), ctx.accounts.recipient.key());
+
+        emit!(MeterClosed {
+            meter: meter.key(),
+            authority: meter.authority,
+            recipient: ctx.accounts.recipient.key(),
+            slot: current_slot,
+        });
+
+        Ok(())
+    }
+
+    /// Starts a two-step transfer of meter authority by nominating `new_authority`.
+    /// 
+    /// Authority only changes once `new_authority` signs `accept_meter_authority`.
+    /// Nominating `Pubkey::default()` cancels a pending transfer. The current
+    /// authority keeps full control until acceptance. The meter's address
+    /// stays derived from its creator, so it doesn't move.
+    /// 
+    /// # Arguments
+    /// * `new_authority` - Key that must accept the transfer
+    pub fn transfer_meter_authority(
+        ctx: Context<UpdateMeter>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let meter = &mut ctx.accounts.meter;
+
+        meter.pending_authority = if new_authority == Pubkey::default() {
+            None
+        } else {
+            Some(new_authority)
+        };
+
+        msg!("Meter authority transfer started: meter={:?}, pending_authority={:?}",
+             meter.key(), meter.pending_authority);
+
+        emit!(MeterAuthorityTransferStarted {
+            meter: meter.key(),
+            authority: meter.authority,
+            pending_authority: new_authority,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Completes a meter authority transfer. Must be signed by the pending authority.
+    /// Clears the meter's operator.
+    pub fn accept_meter_authority(ctx: Context<AcceptMeterAuthority>) -> Result<()> {
+        let meter = &mut ctx.accounts.meter;
+        let new_authority = ctx.accounts.new_authority.key();
+
+        require!(
+            meter.pending_authority == Some(new_authority),
+            AgentBlinkPayError::NotPendingAuthority
+        );
+
+        let previous_authority = meter.authority;
+        meter.authority = new_authority;
+        meter.pending_authority = None;
+        // The operator was the previous authority's choice
+        meter.operator = None;
+
+        msg!("Meter authority transferred: meter={:?}, {:?} -> {:?}",
+             meter.key(), previous_authority, new_authority);
+
+        emit!(MeterAuthorityTransferred {
+            meter: meter.key(),
+            previous_authority,
+            new_authority,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Appoints or removes the meter's operator. Authority only.
+    /// 
+    /// The operator is a key, e.g. a backend hot key, that may sign
+    /// `update_meter`, `pause_meter` and `unpause_meter`. Everything else,
+    /// including this instruction, stays with the authority.
+    /// 
+    /// # Arguments
+    /// * `operator` - The new operator, or None to remove it
+    pub fn set_meter_operator(ctx: Context<UpdateMeter>, operator: Option<Pubkey>) -> Result<()> {
+        let meter = &mut ctx.accounts.meter;
+        let old_operator = meter.operator;
+        meter.operator = operator;
+
+        msg!("Meter operator set: meter={:?}, operator={:?}", meter.key(), operator);
+
+        emit!(MeterOperatorChanged {
+            meter: meter.key(),
+            old_operator,
+            new_operator: operator,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Turns a meter's agent allowlist on or off.
+    /// 
+    /// While enabled, only agents with a `MeterAgentAccess` record for the
+    /// meter can authorize payments or subscribe to it.
+    /// 
+    /// # Arguments
+    /// * `enabled` - Whether the allowlist is enforced
+    pub fn set_meter_allowlist(ctx: Context<UpdateMeter>, enabled: bool) -> Result<()> {
+        let meter = &mut ctx.accounts.meter;
+        meter.allowlist_enabled = enabled;
+
+        msg!("Meter allowlist set: meter={:?}, enabled={}", meter.key(), enabled);
+
+        emit!(MeterAllowlistChanged {
+            meter: meter.key(),
+            enabled,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Chooses how a per-call meter's payments settle (see `settlement_modes`).
+    /// 
+    /// In escrow mode, authorizing moves the amount from the agent's vault
+    /// into an escrow owned by the authorization, and recording pays the
+    /// merchant from it. Authorizations keep the mode they were issued
+    /// under, so switching doesn't strand escrowed funds.
+    /// 
+    /// # Arguments
+    /// * `settlement_mode` - One of the `settlement_modes` constants
+    pub fn set_meter_settlement_mode(ctx: Context<UpdateMeter>, settlement_mode: u8) -> Result<()> {
+        let meter = &mut ctx.accounts.meter;
+        require!(
+            settlement_mode <= settlement_modes::ESCROW,
+            AgentBlinkPayError::InvalidSettlementMode
+        );
+        require!(
+            settlement_mode == settlement_modes::EVENT_ONLY || meter.kind == MeterKind::PerCall,
+            AgentBlinkPayError::WrongMeterKind
+        );
+        meter.settlement_mode = settlement_mode;
+
+        msg!("Meter settlement mode set: meter={:?}, mode={}", meter.key(), settlement_mode);
+
+        emit!(MeterSettlementModeChanged {
+            meter: meter.key(),
+            settlement_mode,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Opts a meter out of (or back into) payments authorized by a policy
+    /// oracle attestation. While set, `authorize_payment_attested` fails
+    /// with `AttestationRejectedByMeter`; proofs and, unless the meter has
+    /// `requires_zk`, `authorize_payment_simple` are unaffected.
+    /// 
+    /// # Arguments
+    /// * `reject` - Whether attested payments are refused
+    pub fn set_meter_reject_attested(ctx: Context<UpdateMeter>, reject: bool) -> Result<()> {
+        let meter = &mut ctx.accounts.meter;
+        meter.reject_attested = reject;
+
+        msg!("Meter attestations set: meter={:?}, reject={}", meter.key(), reject);
+
+        emit!(MeterRejectAttestedChanged {
+            meter: meter.key(),
+            reject_attested: reject,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Pins the circuit proofs paying this meter must be for, or unpins it
+    /// with 0. Proofs for another circuit fail with `ProofCircuitMismatch`.
+    /// 
+    /// The circuit must be a payment circuit with a verifying key
+    /// installed, passed as `verifying_key` (or `honk_verifying_key` for an
+    /// UltraHonk circuit); the meter's current circuit's key goes in
+    /// `old_verifying_key` (`old_honk_verifying_key`). Both keys count the
+    /// meters using them, so a key can't be removed from under a meter.
+    /// 
+    /// # Arguments
+    /// * `circuit_id` - `circuit_ids::PAYMENT_POLICY` or `DAILY_LIMIT`, or 0
+    pub fn set_meter_circuit(ctx: Context<SetMeterCircuit>, circuit_id: u8) -> Result<()> {
+        let meter = &mut ctx.accounts.meter;
+        let old_circuit_id = meter.circuit_id;
+        require!(
+            circuit_id == 0 || is_payment_circuit(circuit_id),
+            AgentBlinkPayError::InvalidCircuit
+        );
+
+        if circuit_id != old_circuit_id {
+            if old_circuit_id != 0 {
+                let meter_count = circuit_meter_count(
+                    ctx.accounts.old_verifying_key.as_mut(),
+                    ctx.accounts.old_honk_verifying_key.as_mut(),
+                )?;
+                *meter_count = meter_count.saturating_sub(1);
+            }
+            if circuit_id != 0 {
+                let meter_count = circuit_meter_count(
+                    ctx.accounts.verifying_key.as_mut(),
+                    ctx.accounts.honk_verifying_key.as_mut(),
+                )?;
+                *meter_count = meter_count
+                    .checked_add(1)
+                    .ok_or(AgentBlinkPayError::MathOverflow)?;
+            }
+            meter.circuit_id = circuit_id;
+        }
+
+        msg!("Meter circuit set: meter={:?}, circuit={}", meter.key(), circuit_id);
+
+        emit!(MeterCircuitChanged {
+            meter: meter.key(),
+            old_circuit_id,
+            new_circuit_id: circuit_id,
+            slot: Clock::get()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Lets an agent pay a meter by creating its `MeterAgentAccess` record.
+    /// Only takes effect while the meter's allowlist is enabled.
+    pub fn grant_meter_access(ctx: Context<GrantMeterAccess>) -> Result<()> {
+        let access = &mut ctx.accounts.meter_access;
+
+        access.meter = ctx.accounts.meter.key();
+        access.agent = ctx.accounts.agent.key();
+        access.bump = ctx.bumps.meter_access;
+
+        msg!("Meter access granted: meter={:?}, agent={:?}", access.meter, access.agent);
+
+        Ok(())
+    }
+
+    /// Withdraws an agent's access to a meter by closing its `MeterAgentAccess`
+    /// record. Rent goes back to the meter authority.
+    pub fn revoke_meter_access(ctx: Context<RevokeMeterAccess>) -> Result<()> {
+        msg!("Meter access revoked: meter={:?}, agent={:?}",
+             ctx.accounts.meter.key(), ctx.accounts.agent.key());
+
+        Ok(())
+    }
+
+    /// Grows a Meter created by an older program version to the current
+    /// `Meter::LEN`. New fields are zero-initialized, except `active`, which
+    /// is set for meters that predate it. Safe to call on an account that is
+    /// already up to date.
+    pub fn migrate_meter(ctx: Context<MigrateMeter>) -> Result<()> {
+        let meter_info = ctx.accounts.meter.to_account_info();
+        let old_len = meter_info.data_len();
+
+        grow_account(
+            &meter_info,
+            Meter::DISCRIMINATOR,
+            Meter::LEN,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )?;
+
+        // Meters from before the active flag were all live
+        if old_len <= Meter::ACTIVE_OFFSET {
+            meter_info.try_borrow_mut_data()?[Meter::ACTIVE_OFFSET] = 1;
+        }
+
+        // Meters from before the creator field are still held by their creator
+        if old_len <= Meter::CREATOR_OFFSET {
+            meter_info.try_borrow_mut_data()?[Meter::CREATOR_OFFSET..Meter::CREATOR_OFFSET + 32]
+                .copy_from_slice(ctx.accounts.authority.key().as_ref());
+        }
+
+        msg!("Meter migrated: {:?}", meter_info.key());
+
+        Ok(())
+    }
+
+    /// Grows a MeterUsage created by an older program version to the
+    /// current `MeterUsage::LEN`. Anyone may pay; safe to call on an account
+    /// that is already up to date.
+    pub fn migrate_meter_usage(ctx: Context<MigrateMeterUsage>) -> Result<()> {
+        let usage_info = ctx.accounts.meter_usage.to_account_info();
+        let old_len = usage_info.data_len();
+
+        grow_account(
+            &usage_info,
+            MeterUsage::DISCRIMINATOR,
+            MeterUsage::LEN,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+
+        if old_len <= MeterUsage::VERSION_OFFSET {
+            usage_info.try_borrow_mut_data()?[MeterUsage::VERSION_OFFSET] = MeterUsage::VERSION;
+        }
+
+        msg!("Meter usage migrated: {:?}", usage_info.key());
+
+        Ok(())
+    }
+
+    /// Rewrites an Authorization created by an older program version into
+    /// the current layout, which starts with `version` and `kind`, and grows
+    /// it to `Authorization::LEN`. New fields are zero-initialized. Anyone
+    /// may pay; safe to call on an account that is already up to date.
+    /// 
+    /// # Arguments
+    /// * `nonce` - The nonce of the authorization to migrate
+    pub fn migrate_authorization(ctx: Context<MigrateAuthorization>, _nonce: u64) -> Result<()> {
+        let auth_info = ctx.accounts.authorization.to_account_info();
+
+        let migrated = {
+            let data = auth_info.try_borrow_data()?;
+            require!(
+                data.len() >= 8 && data[..8] == Authorization::DISCRIMINATOR,
+                AgentBlinkPayError::InvalidAccountData
+            );
+            // Every older layout is shorter than the current one
+            if data.len() >= Authorization::LEN {
+                return Ok(());
+            }
+            Authorization::migrate_layout(&data)
+        };
+
+        grow_account(
+            &auth_info,
+            Authorization::DISCRIMINATOR,
+            Authorization::LEN,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+        auth_info.try_borrow_mut_data()?.copy_from_slice(&migrated);
+
+        msg!("Authorization migrated: {:?}", auth_info.key());
+
+        Ok(())
+    }
+
+    /// Creates the `MeterCounters` of a meter created before the counters
+    /// were split out of the Meter, carrying over the totals and rate limit
+    /// window stored on it. Anyone may pay. Such a meter can't record
+    /// payments until this has run.
+    pub fn init_meter_counters(ctx: Context<InitMeterCounters>) -> Result<()> {
+        MeterCounters::initialize(
+            &ctx.accounts.meter_counters,
+            &ctx.accounts.meter,
+            ctx.bumps.meter_counters,
+        )?;
+
+        msg!("Meter counters initialized: {:?}", ctx.accounts.meter.key());
+
+        Ok(())
+    }
+
+    /// Verifies a Groth16 proof against a `VerifyingKey` account.
+    /// 
+    /// Called by this program via CPI (`verifier_program`), and usable by
+    /// any other program the same way. The proof is its header, whose
+    /// version must match the key's `circuit_version` and whose scheme must
+    /// be `proof_schemes::GROTH16`, followed by the `GROTH16_PROOF_LEN`
+    /// bytes of A, B and C; the public inputs are
+    /// 32-byte big-endian field elements in the circuit's order (see
+    /// `public_inputs`). Fails with `ProofTooSmall` or `ProofTooLarge`
+    /// outside MIN_PROOF_LEN and MAX_PROOF_LEN, with `MalformedProof` if
+    /// the proof can't be decoded and with `InvalidProof` if it doesn't
+    /// verify, logging why the proof was rejected.
+    /// 
+    /// # Arguments
+    /// * `proof` - Proof header and points
+    /// * `public_inputs` - The circuit's public inputs, concatenated
+    pub fn verify_proof(
+        ctx: Context<VerifyProof>,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+    ) -> Result<()> {
+        let vk = &ctx.accounts.verifying_key;
+        check_proof_len(&proof)?;
+        require!(public_inputs.len() % 32 == 0, AgentBlinkPayError::InvalidInputs);
+        let inputs: Vec<[u8; 32]> = public_inputs
+            .chunks_exact(32)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect();
+        verify_groth16_proof(vk, &proof, &inputs)?;
+
+        msg!(
+            "Verifier: proof valid for circuit {} v{}",
+            vk.circuit_id,
+            vk.circuit_version
+        );
+        Ok(())
+    }
+
+    /// Runs one stage of a payment proof verification spread over
+    /// PROOF_VERIFICATION_STAGES transactions, for when the pairing check
+    /// and the authorization's account inits don't fit one transaction's
+    /// compute budget. `finalize_authorization` then mints the
+    /// authorization from the verified `PendingVerification`.
+    /// 
+    /// Each stage appends its `proof_chunk` to the proof held by the
+    /// pending verification, then:
+    /// - stage 0 creates the pending verification and binds it to the
+    ///   payment: the agent, meter and nonce (its address), the policy,
+    ///   amount and category. It also checks the policy commitment.
+    /// - stage 1 checks the proof header and the verifying key as
+    ///   `authorize_payment_with_proof` does, then folds the payment's
+    ///   public inputs into vk_x.
+    /// - stage 2 requires the whole proof and runs the pairing check.
+    /// 
+    /// Stages must run in order (`InvalidVerificationStage`), repeat the
+    /// parameters bound at stage 0 (`PendingVerificationMismatch`), and run
+    /// within PENDING_VERIFICATION_TTL_SLOTS of stage 0
+    /// (`PendingVerificationExpired`). Only the built-in Groth16 verifier
+    /// can be staged: with `external_verifier` set, stage 0 fails with
+    /// `StagedVerificationUnsupported`.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies the payment is under.
+    ///   Unused by the handler, but needed to derive the `agent_policy` PDA.
+    /// * `amount` - Amount the authorization will be for
+    /// * `category` - Category of the payment
+    /// * `nonce` - Nonce of the authorization to be created
+    /// * `stage_index` - The stage to run, from 0
+    /// * `proof_chunk` - Next bytes of the proof (header and points); may be empty
+    pub fn verify_proof_stage(
+        ctx: Context<VerifyProofStage>,
+        _policy_id: u16,
+        amount: u64,
+        category: u8,
+        nonce: u64,
+        stage_index: u8,
+        proof_chunk: Vec<u8>,
+    ) -> Result<()> {
+        require!(
+            stage_index < PROOF_VERIFICATION_STAGES,
+            AgentBlinkPayError::InvalidVerificationStage
+        );
+        let current_slot = Clock::get()?.slot;
+        let policy = &ctx.accounts.agent_policy;
+        let meter = &ctx.accounts.meter;
+        let vk = &ctx.accounts.verifying_key;
+        let pending = &mut ctx.accounts.pending_verification;
+
+        if stage_index == 0 {
+            // Freshly created by init_if_needed; an existing one is in flight
+            require!(
+                pending.agent == Pubkey::default(),
+                AgentBlinkPayError::InvalidVerificationStage
+            );
+            require!(
+                !ctx.accounts.config.external_verifier,
+                AgentBlinkPayError::StagedVerificationUnsupported
+            );
+            check_policy_commitment(policy)?;
+            pending.agent = ctx.accounts.agent.key();
+            pending.policy = policy.key();
+            pending.meter = meter.key();
+            pending.nonce = nonce;
+            pending.amount = amount;
+            pending.category = category;
+            pending.created_at_slot = current_slot;
+            pending.expires_at_slot = current_slot.saturating_add(PENDING_VERIFICATION_TTL_SLOTS);
+            pending.rent_payer = ctx.accounts.payer.key();
+            pending.bump = ctx.bumps.pending_verification;
+        } else {
+            require!(
+                pending.next_stage == stage_index,
+                AgentBlinkPayError::InvalidVerificationStage
+            );
+            require!(
+                !pending.is_expired(current_slot),
+                AgentBlinkPayError::PendingVerificationExpired
+            );
+            pending.check_payment(&policy.key(), amount, category)?;
+        }
+        pending.append_proof(&proof_chunk)?;
+
+        match stage_index {
+            0 => {}
+            1 => {
+                let header = ProofHeader::parse(pending.proof())?;
+                let circuit_id = meter.proof_circuit(pending.proof())?;
+                policy.check_payment_circuit(circuit_id)?;
+                require!(vk.circuit_id == circuit_id, AgentBlinkPayError::WrongVerifyingKey);
+                require!(
+                    header.scheme == proof_schemes::GROTH16,
+                    AgentBlinkPayError::UnsupportedProofScheme
+                );
+                if header.version != vk.circuit_version {
+                    return Err(reject_proof(ProofRejection::VersionMismatch));
+                }
+                meter.check_proof_version(pending.proof())?;
+
+                let public_inputs = payment_public_inputs(
+                    policy,
+                    circuit_id,
+                    amount,
+                    category,
+                    meter.key(),
+                    nonce,
+                    current_slot,
+                );
+                pending.vk_x = groth16_vk_x(vk, &public_inputs)?;
+                pending.circuit_id = circuit_id;
+                pending.verifying_key = vk.key();
+                pending.spent_today = policy.spent_today_at(current_slot);
+            }
+            _ => {
+                require_keys_eq!(
+                    vk.key(),
+                    pending.verifying_key,
+                    AgentBlinkPayError::WrongVerifyingKey
+                );
+                let header = ProofHeader::parse(pending.proof())?;
+                groth16_pairing(vk, header.payload, &pending.vk_x)?;
+            }
+        }
+        pending.next_stage = stage_index + 1;
+
+        msg!("Proof stage {} of {} done: agent={:?}, meter={:?}, nonce={}",
+             stage_index + 1, PROOF_VERIFICATION_STAGES, pending.agent, pending.meter, nonce);
+
+        Ok(())
+    }
+
+    /// Verifies a payment proof ahead of its authorization and records it
+    /// in a `ProofReceipt`, for provers that run before the agent sends the
+    /// payment. `authorize_payment_with_proof` then consumes the receipt in
+    /// place of the proof, skipping the verification.
+    /// 
+    /// The proof is checked as `authorize_payment_with_proof` checks it,
+    /// against the circuit its version names, but with the public inputs
+    /// given rather than derived from a payment: 32-byte big-endian field
+    /// elements as for `verify_proof`, which must hash to
+    /// `public_inputs_hash` (see `public_inputs::hash`, `InvalidInputs`).
+    /// The receipt is bound to that hash, the agent, the policy and the
+    /// circuit's key, and expires PROOF_RECEIPT_TTL_SLOTS later. A proof
+    /// that fails verification is rejected in permissive mode too.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies the proof is for (only
+    ///   used to derive the `agent_policy` PDA)
+    /// * `public_inputs_hash` - SHA-256 of `public_inputs`
+    /// * `public_inputs` - The proof's public inputs, concatenated
+    /// * `proof` - Proof header and payload
+    pub fn submit_proof_receipt(
+        ctx: Context<SubmitProofReceipt>,
+        _policy_id: u16,
+        public_inputs_hash: [u8; 32],
+        public_inputs: Vec<u8>,
+        proof: Vec<u8>,
+    ) -> Result<()> {
+        require!(public_inputs.len() % 32 == 0, AgentBlinkPayError::InvalidInputs);
+        let inputs: Vec<[u8; 32]> = public_inputs
+            .chunks_exact(32)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect();
+        require!(
+            public_inputs::hash(&inputs) == public_inputs_hash,
+            AgentBlinkPayError::InvalidInputs
+        );
+
+        let policy = &ctx.accounts.agent_policy;
+        let circuit_id = payment_circuit(proof_version(&proof))
+            .ok_or(AgentBlinkPayError::UnsupportedProofVersion)?;
+        let verifying_key = CircuitKey::from_accounts(
+            ctx.accounts.verifying_key.as_ref(),
+            ctx.accounts.honk_verifying_key.as_ref(),
+        )?;
+        let verified = verify_circuit_proof(
+            policy,
+            circuit_id,
+            &inputs,
+            &proof,
+            &ctx.accounts.config,
+            ctx.accounts.verifier_program.to_account_info(),
+            verifying_key,
+        )?;
+        require!(verified, AgentBlinkPayError::InvalidProof);
+
+        let current_slot = Clock::get()?.slot;
+        let receipt = &mut ctx.accounts.proof_receipt;
+        receipt.agent = ctx.accounts.agent.key();
+        receipt.policy = policy.key();
+        receipt.public_inputs_hash = public_inputs_hash;
+        receipt.proof_commitment = solana_program::hash::hash(&proof).to_bytes();
+        receipt.proof_version = proof_version(&proof);
+        receipt.circuit_id = circuit_id;
+        receipt.verifying_key = verifying_key.key();
+        receipt.circuit_version = verifying_key.circuit_version();
+        let verification = verifying_key.verification(&ctx.accounts.config, verified);
+        receipt.verification_method = verification.method;
+        receipt.verifier_ref = verification.verifier_ref;
+        receipt.created_at_slot = current_slot;
+        receipt.expires_at_slot = current_slot.saturating_add(PROOF_RECEIPT_TTL_SLOTS);
+        receipt.rent_payer = ctx.accounts.payer.key();
+        receipt.bump = ctx.bumps.proof_receipt;
+
+        msg!("Proof receipt submitted: agent={:?}, circuit={}, expires_at_slot={}",
+             receipt.agent, circuit_id, receipt.expires_at_slot);
+
+        Ok(())
+    }
+
+    /// Authorizes a payment by verifying a ZK proof of policy compliance.
+    /// 
+    /// This instruction enforces that the payment adheres to the agent's policy using
+    /// Zero-Knowledge Proofs (via a Verifier CPI).
+    /// 
+    /// Public Inputs to the ZK Circuit:
+    /// - policy_hash (from AgentPolicy account)
+    /// - amount (from instruction args)
+    /// - category (from instruction args)
+    /// 
+    /// The Proof proves:
+    /// "I know a private policy P where hash(P) == policy_hash, AND
+    ///  amount <= P.max_per_tx AND category == P.allowed_category"
+    /// 
+    /// `amount <= max_per_tx` is also checked on-chain before the verifier
+    /// is called, so the limit holds whatever verifier program is passed.
+    /// 
+    /// An `amount` of 0 uses one of the meter's `free_calls` for this agent
+    /// and is rejected once they are used up. Paid amounts must be at least
+    /// the meter's current price and may not exceed that price or its
+    /// `max_amount_per_payment`, whichever is larger; a declared `tip` comes
+    /// on top of the price.
+    /// Meters with a `min_proof_version` reject proofs whose version byte
+    /// (the first byte of `proof`) is older.
+    /// 
+    /// A proof let through unverified by permissive mode counts as no proof:
+    /// it fails with `ZkRequiredByPolicy` or `ZkRequiredByMeter` where
+    /// `authorize_payment_simple` would. The same goes for every other
+    /// instruction that takes a proof.
+    /// 
+    /// A `quantity` above 1 authorizes that many calls at once, each
+    /// consumed by its own `record_meter_payment`. Such a batch must cost
+    /// exactly `quantity` times the meter's per-call price (free calls and
+    /// tiers only apply to single calls), and the budgets and proof cover
+    /// the whole batch. The expiry applies to all calls in it.
+    /// 
+    /// Program-owned agents: if the policy was created for a PDA
+    /// (`agent_is_pda`), the owning program calls this instruction (and
+    /// `record_meter_payment`) via CPI and signs for the agent with
+    /// `invoke_signed`. Policies of keypair agents never accept a PDA signer.
+    /// 
+    /// The proof is spent by creating the `Nullifier` PDA of its public
+    /// inputs (see `payment_nullifier`), so no proof of the same agent,
+    /// meter, nonce, amount and policy hash can authorize again, even after
+    /// the authorization is closed; a reuse fails with `ProofAlreadyUsed`.
+    /// 
+    /// A proof verified ahead of time by `submit_proof_receipt` is passed as
+    /// the context's `proof_receipt` instead, with an empty `proof`. The
+    /// payment's public inputs, encoded as for the verifier, must hash to
+    /// the receipt's `public_inputs_hash` (`ProofReceiptMismatch`), the
+    /// circuit's key must not have changed since (`ProofReceiptStale`), and
+    /// the receipt must be unexpired (`ProofReceiptExpired`). The receipt is closed to `payer`,
+    /// which must be the account that paid for it, and the payment's
+    /// nullifier is spent as for an inline proof.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies to evaluate the payment under
+    /// * `amount` - Amount to authorize in USDC smallest units
+    /// * `quantity` - Number of calls the authorization covers (at least 1)
+    /// * `category` - Category of this payment
+    /// * `nonce` - Unique identifier to prevent replay attacks
+    /// * `expires_at` - Slot (or with `expiry_kind` UNIX, unix timestamp) after
+    ///   which this authorization expires; must be in the future and at most
+    ///   `MAX_AUTHORIZATION_TTL_SLOTS` (or the Config's
+    ///   `max_authorization_ttl_secs`) ahead
+    /// * `proof` - ZK proof bytes (empty with a `proof_receipt`)
+    /// * `request_id` - Caller's id for the request being paid, e.g. the x402
+    ///   payment id; echoed in the events (all zero = none)
+    /// * `expiry_kind` - How `expires_at` is measured (see `expiry_kinds`)
+    /// * `consumer` - The only key that may record the payment, e.g. a chosen
+    ///   facilitator (`Pubkey::default()` = anyone)
+    /// * `hash_lock` - SHA-256 of a secret the merchant must reveal to be paid,
+    ///   for pay-on-delivery: `record_meter_payment` then needs the preimage
+    ///   (all zero = no lock). Unrevealed, the authorization just expires.
+    /// * `tip` - Deliberate overpayment for priority treatment, included in
+    ///   `amount` (0 = none). A tipped authorization covers one call and
+    ///   `amount` must be exactly the meter's price plus `tip`; `MeterPaid`
+    ///   reports the tip part of each charge separately.
+    pub fn authorize_payment_with_proof(
+        ctx: Context<AuthorizePayment>,
+        policy_id: u16,
+        amount: u64,
+        quantity: u16,
+        category: u8,
+        nonce: u64,
+        expires_at: u64,
+        proof: Vec<u8>,
+        request_id: [u8; 32],
+        expiry_kind: u8,
+        consumer: Pubkey,
+        hash_lock: [u8; 32],
+        tip: u64,
+    ) -> Result<()> {
+        let proof = if ctx.accounts.proof_receipt.is_some() {
+            require!(proof.is_empty(), AgentBlinkPayError::ProofReceiptMismatch);
+            PaymentProof::Receipt
+        } else {
+            PaymentProof::Inline(proof)
+        };
+        authorize_payment(
+            ctx,
+            policy_id,
+            amount,
+            quantity,
+            category,
+            nonce,
+            expires_at,
+            proof,
+            request_id,
+            expiry_kind,
+            consumer,
+            hash_lock,
+            tip,
+        )
+    }
+
+    /// Authorizes a payment without a ZK proof, for meters and policies that
+    /// don't require one.
+    /// 
+    /// Performs every on-chain check of `authorize_payment_with_proof`
+    /// (budgets, `max_per_tx`, category, frozen, meter price and so on) and
+    /// creates the same `Authorization`, with `zk_verified` false. Fails with
+    /// `ZkRequiredByPolicy` if the policy has `always_require_zk` set and
+    /// `ZkRequiredByMeter` if the meter has `requires_zk` set. The context's
+    /// `verifier_program` is not used.
+    /// 
+    /// # Arguments
+    /// * Same as `authorize_payment_with_proof`, without `proof`, `hash_lock`
+    ///   and `tip`
+    pub fn authorize_payment_simple(
+        ctx: Context<AuthorizePayment>,
+        policy_id: u16,
+        amount: u64,
+        quantity: u16,
+        category: u8,
+        nonce: u64,
+        expires_at: u64,
+        request_id: [u8; 32],
+        expiry_kind: u8,
+        consumer: Pubkey,
+    ) -> Result<()> {
+        authorize_payment(
+            ctx,
+            policy_id,
+            amount,
+            quantity,
+            category,
+            nonce,
+            expires_at,
+            PaymentProof::None,
+            request_id,
+            expiry_kind,
+            consumer,
+            [0; 32],
+            0,
+        )
+    }
+
+    /// Authorizes a payment on an attestation by the Config's
+    /// `policy_oracle` instead of a proof, for agents without a prover.
+    /// 
+    /// The oracle signs `ATTESTATION_DOMAIN || agent || meter || amount ||
+    /// category || nonce || expires_at_slot` (integers little-endian, the
+    /// slot expiry the authorization gets) off-chain, and the transaction
+    /// checks the signature with an Ed25519 program instruction right
+    /// before this one; the context's `instructions` sysvar is required to
+    /// find it. A missing, malformed or mismatched check fails with
+    /// `InvalidAttestation`. Otherwise it behaves like
+    /// `authorize_payment_simple`, including `ZkRequiredByPolicy` and
+    /// `ZkRequiredByMeter`, and marks the authorization `attested`. Fails
+    /// with `AttestationsDisabled` while no oracle is configured and
+    /// `AttestationRejectedByMeter` for meters with `reject_attested` set.
+    /// 
+    /// # Arguments
+    /// * Same as `authorize_payment_simple`
+    pub fn authorize_payment_attested(
+        ctx: Context<AuthorizePayment>,
+        policy_id: u16,
+        amount: u64,
+        quantity: u16,
+        category: u8,
+        nonce: u64,
+        expires_at: u64,
+        request_id: [u8; 32],
+        expiry_kind: u8,
+        consumer: Pubkey,
+    ) -> Result<()> {
+        authorize_payment(
+            ctx,
+            policy_id,
+            amount,
+            quantity,
+            category,
+            nonce,
+            expires_at,
+            PaymentProof::Attested,
+            request_id,
+            expiry_kind,
+            consumer,
+            [0; 32],
+            0,
+        )
+    }
+
+    /// Authorizes a payment whose proof was verified ahead of time by
+    /// `verify_proof_stage`, consuming its `PendingVerification`.
+    /// 
+    /// Creates the same `Authorization` as `authorize_payment_with_proof`,
+    /// with every check but the proof itself, and spends the payment's
+    /// `Nullifier`. The pending verification (the context's
+    /// `pending_verification`) must have completed all stages
+    /// (`ProofNotVerified`), be unexpired (`PendingVerificationExpired`) and
+    /// be bound to this policy, amount and category
+    /// (`PendingVerificationMismatch`); its agent, meter and nonce are
+    /// fixed by its address. For daily limit proofs the policy's
+    /// `spent_today` must not have moved since stage 1
+    /// (`PendingInputsChanged`). The pending verification is closed to
+    /// `payer`, which must be the account that paid for it.
+    /// 
+    /// # Arguments
+    /// * Same as `authorize_payment_with_proof`, without `proof`
+    pub fn finalize_authorization(
+        ctx: Context<AuthorizePayment>,
+        policy_id: u16,
+        amount: u64,
+        quantity: u16,
+        category: u8,
+        nonce: u64,
+        expires_at: u64,
+        request_id: [u8; 32],
+        expiry_kind: u8,
+        consumer: Pubkey,
+        hash_lock: [u8; 32],
+        tip: u64,
+    ) -> Result<()> {
+        authorize_payment(
+            ctx,
+            policy_id,
+            amount,
+            quantity,
+            category,
+            nonce,
+            expires_at,
+            PaymentProof::Staged,
+            request_id,
+            expiry_kind,
+            consumer,
+            hash_lock,
+            tip,
+        )
+    }
+
+    /// Like `authorize_payment_with_proof`, but the nonce is taken from the
+    /// (agent, meter) pair's `NonceCounter`, created on first use, instead
+    /// of being chosen by the client.
+    /// 
+    /// The authorization PDA is derived from the counter's current
+    /// `next_nonce`, which is then incremented, and the nonce used is
+    /// reported in `AuthorizationCreated`. A transaction built against a
+    /// stale counter fails the seeds check and can simply be rebuilt. Clients
+    /// that precompute addresses keep using the manual-nonce path; mixing
+    /// both on one pair can collide with a manually chosen nonce.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies to evaluate the payment under
+    /// * `amount` - Amount to authorize in USDC smallest units
+    /// * `quantity` - Number of calls the authorization covers (at least 1)
+    /// * `category` - Category of this payment
+    /// * `expires_at_slot` - Slot after which this authorization expires; must be
+    ///   in the future and at most `MAX_AUTHORIZATION_TTL_SLOTS` ahead
+    /// * `proof` - ZK proof bytes
+    /// * `request_id` - Caller's id for the request being paid (all zero = none)
+    pub fn authorize_payment_auto_nonce(
+        ctx: Context<AuthorizePaymentAutoNonce>,
+        policy_id: u16,
+        amount: u64,
+        quantity: u16,
+        category: u8,
+        expires_at_slot: u64,
+        proof: Vec<u8>,
+        request_id: [u8; 32],
+    ) -> Result<()> {
+        let policy = &ctx.accounts.agent_policy;
+        let meter = &ctx.accounts.meter;
+        let current_slot = Clock::get()?.slot;
+
+        let counter = &mut ctx.accounts.nonce_counter;
+        if counter.meter == Pubkey::default() {
+            // Freshly created by init_if_needed
+            counter.agent = ctx.accounts.agent.key();
+            counter.meter = meter.key();
+            counter.bump = ctx.bumps.nonce_counter;
+        }
+        let nonce = counter.next_nonce;
+        counter.next_nonce = nonce
+            .checked_add(1)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        let payment = AuthParams { amount, category, nonce, expires_at_slot, request_id };
+
+        let usage = &mut ctx.accounts.meter_usage;
+        if usage.meter == Pubkey::default() {
+            // Freshly created by init_if_needed
+            usage.meter = meter.key();
+            usage.agent = ctx.accounts.agent.key();
+            usage.bump = ctx.bumps.meter_usage;
+            usage.version = MeterUsage::VERSION;
+        }
+
+        check_payment(policy, meter, usage, &payment, quantity, 0, current_slot)?;
+        require!(
+            meter.settlement_mode == settlement_modes::EVENT_ONLY,
+            AgentBlinkPayError::EscrowNotSupported
+        );
+        require!(
+            !policy.enforce_meter_allowlist || ctx.accounts.allowed_meter.is_some(),
+            AgentBlinkPayError::MeterNotAllowed
+        );
+        require!(
+            !meter.allowlist_enabled || ctx.accounts.meter_access.is_some(),
+            AgentBlinkPayError::AgentNotAllowedByMeter
+        );
+        require!(
+            ctx.accounts.denied_meter.owner != &crate::ID,
+            AgentBlinkPayError::MeterDenied
+        );
+
+        meter.check_proof_version(&proof)?;
+        let version = proof_version(&proof);
+        let verifying_key = CircuitKey::Groth16(&ctx.accounts.verifying_key);
+        let verified = verify_policy_proof(
+            policy,
+            meter.proof_circuit(&proof)?,
+            amount,
+            category,
+            meter.key(),
+            nonce,
+            &proof,
+            current_slot,
+            &ctx.accounts.config,
+            ctx.accounts.verifier_program.to_account_info(),
+            verifying_key,
+        )?;
+        if !verified {
+            check_unproven_payment(policy, meter)?;
+        }
+        spend_nullifier(
+            &ctx.accounts.nullifier.to_account_info(),
+            payment_nullifier(
+                b"auth",
+                &ctx.accounts.agent.key(),
+                &meter.key(),
+                nonce,
+                amount,
+                &policy.policy_hash,
+            ),
+            ctx.accounts.agent.key(),
+            ctx.accounts.authorization.key(),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        let auth = &mut ctx.accounts.authorization;
+        auth.set_inner(payment.to_authorization(
+            ctx.accounts.agent.key(),
+            meter,
+            policy,
+            quantity,
+            current_slot,
+        ));
+        auth.bump = ctx.bumps.authorization;
+        auth.rent_payer = ctx.accounts.payer.key();
+        auth.set_verification(verifying_key.verification(&ctx.accounts.config, verified));
+        auth.proof_version = version;
+
+        emit!(auth.created_event(auth.key(), current_slot));
+
+        msg!("Payment authorized: agent={:?}, meter={:?}, amount={}, quantity={}, nonce={} (auto), policy_id={}",
+             auth.agent, auth.meter, amount, quantity, nonce, policy_id);
+
+        Ok(())
+    }
+
+    /// Authorizes up to `MAX_AUTHORIZATION_BATCH` single-call payments to one
+    /// meter in one transaction, e.g. an agent's next few planned calls.
+    /// 
+    /// Each entry is checked exactly like `authorize_payment_with_proof` with
+    /// a quantity of 1 and needs its own proof, `proofs[i]` for
+    /// `payments[i]`. The budgets must also cover the batch as a whole.
+    /// 
+    /// Instead, `proofs` may hold one aggregated proof
+    /// (`proof_schemes::GROTH16_BATCH`) whose `batch_count` is the number of
+    /// entries (else `BatchCountMismatch`, before any authorization is
+    /// created). It covers every entry with one pairing check, which keeps
+    /// larger batches within the compute budget (`verify_batch_policy_proof`).
+    /// 
+    /// `remaining_accounts` carries one writable `(authorization, nullifier)`
+    /// pair per entry, in the same order as `payments`: the authorization
+    /// PDA (["auth", agent, meter, nonce]) and the entry's `Nullifier`,
+    /// spent as in `authorize_payment_with_proof` whether the entry has its
+    /// own proof or shares the aggregated one. The batch is atomic: any invalid entry
+    /// aborts the whole instruction. Each authorization emits its own
+    /// `AuthorizationCreated`.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies to evaluate the payments under
+    /// * `payments` - One entry per authorization
+    /// * `proofs` - One ZK proof per entry, or one aggregated proof
+    pub fn authorize_payments_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AuthorizePaymentsBatch<'info>>,
+        policy_id: u16,
+        payments: Vec<AuthParams>,
+        proofs: Vec<Vec<u8>>,
+    ) -> Result<()> {
+        require!(!payments.is_empty(), AgentBlinkPayError::EmptyBatch);
+        require!(payments.len() <= MAX_AUTHORIZATION_BATCH, AgentBlinkPayError::BatchTooLarge);
+        let aggregated = match proofs.as_slice() {
+            [proof] if proof.get(1) == Some(&proof_schemes::GROTH16_BATCH) => {
+                Some(AggregatedProof::parse(proof)?)
+            }
+            _ => None,
+        };
+        match &aggregated {
+            Some(proof) => require!(
+                proof.batch_count() == payments.len(),
+                AgentBlinkPayError::BatchCountMismatch
+            ),
+            None => require!(
+                proofs.len() == payments.len(),
+                AgentBlinkPayError::BatchProofsMismatch
+            ),
+        }
+        require!(
+            ctx.remaining_accounts.len() == payments.len() * 2,
+            AgentBlinkPayError::BatchAccountsMismatch
+        );
+
+        let policy = &ctx.accounts.agent_policy;
+        let meter = &ctx.accounts.meter;
+        let agent = ctx.accounts.agent.key();
+        let meter_key = meter.key();
+        let payer = ctx.accounts.payer.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let current_slot = Clock::get()?.slot;
+
+        let total = payments
+            .iter()
+            .try_fold(0u64, |total, payment| total.checked_add(payment.amount))
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        require!(total <= policy.remaining_budget(), AgentBlinkPayError::BudgetExceeded);
+        require!(
+            total <= policy.remaining_today(current_slot),
+            AgentBlinkPayError::DailyLimitExceeded
+        );
+        require!(
+            !policy.enforce_meter_allowlist || ctx.accounts.allowed_meter.is_some(),
+            AgentBlinkPayError::MeterNotAllowed
+        );
+        require!(
+            !meter.allowlist_enabled || ctx.accounts.meter_access.is_some(),
+            AgentBlinkPayError::AgentNotAllowedByMeter
+        );
+        require!(
+            ctx.accounts.denied_meter.owner != &crate::ID,
+            AgentBlinkPayError::MeterDenied
+        );
+
+        let usage = &mut ctx.accounts.meter_usage;
+        if usage.meter == Pubkey::default() {
+            // Freshly created by init_if_needed
+            usage.meter = meter_key;
+            usage.agent = agent;
+            usage.bump = ctx.bumps.meter_usage;
+            usage.version = MeterUsage::VERSION;
+        }
+
+        require!(
+            meter.settlement_mode == settlement_modes::EVENT_ONLY,
+            AgentBlinkPayError::EscrowNotSupported
+        );
+        let verifying_key = CircuitKey::Groth16(&ctx.accounts.verifying_key);
+        // An aggregated proof covers every entry and is checked once, up front
+        let batch_verified = match &aggregated {
+            Some(proof) => {
+                meter.check_proof_version(&proofs[0])?;
+                let verified = verify_batch_policy_proof(
+                    policy,
+                    meter.proof_circuit(&proofs[0])?,
+                    &payments,
+                    meter_key,
+                    proof,
+                    current_slot,
+                    &ctx.accounts.config,
+                    &ctx.accounts.verifying_key,
+                )?;
+                Some((verified, proof.version))
+            }
+            None => None,
+        };
+        for (i, (payment, accounts)) in payments
+            .iter()
+            .zip(ctx.remaining_accounts.chunks(2))
+            .enumerate()
+        {
+            let auth_info = &accounts[0];
+            check_payment(policy, meter, usage, payment, 1, 0, current_slot)?;
+            let (verified, version) = match batch_verified {
+                Some(batch_verified) => batch_verified,
+                None => {
+                    let proof = &proofs[i];
+                    meter.check_proof_version(proof)?;
+                    let verified = verify_policy_proof(
+                        policy,
+                        meter.proof_circuit(proof)?,
+                        payment.amount,
+                        payment.category,
+                        meter.key(),
+                        payment.nonce,
+                        proof,
+                        current_slot,
+                        &ctx.accounts.config,
+                        ctx.accounts.verifier_program.to_account_info(),
+                        verifying_key,
+                    )?;
+                    (verified, proof_version(proof))
+                }
+            };
+            if !verified {
+                check_unproven_payment(policy, meter)?;
+            }
+
+            let nonce_bytes = payment.nonce.to_le_bytes();
+            let (expected_auth, bump) = Pubkey::find_program_address(
+                &[b"auth", agent.as_ref(), meter_key.as_ref(), &nonce_bytes],
+                ctx.program_id,
+            );
+            require_keys_eq!(
+                *auth_info.key,
+                expected_auth,
+                AgentBlinkPayError::InvalidAuthorizationAccount
+            );
+            require!(auth_info.is_writable, AgentBlinkPayError::InvalidAuthorizationAccount);
+            if !auth_info.data_is_empty() {
+                msg!("Nonce {} already used by authorization {:?}", payment.nonce, auth_info.key);
+                return err!(AgentBlinkPayError::NonceAlreadyUsed);
+            }
+
+            create_pda_account(
+                &payer,
+                auth_info,
+                Authorization::LEN,
+                &[b"auth", agent.as_ref(), meter_key.as_ref(), &nonce_bytes, &[bump]],
+                &system_program,
+            )?;
+            spend_nullifier(
+                &accounts[1],
+                payment_nullifier(
+                    b"auth",
+                    &agent,
+                    &meter_key,
+                    payment.nonce,
+                    payment.amount,
+                    &policy.policy_hash,
+                ),
+                agent,
+                *auth_info.key,
+                &payer,
+                &system_program,
+            )?;
+
+            let mut auth = payment.to_authorization(agent, meter, policy, 1, current_slot);
+            auth.bump = bump;
+            auth.rent_payer = payer.key();
+            auth.set_verification(verifying_key.verification(&ctx.accounts.config, verified));
+            auth.proof_version = version;
+
+            let mut data = auth_info.try_borrow_mut_data()?;
+            let mut writer: &mut [u8] = &mut data[..];
+            auth.try_serialize(&mut writer)?;
+
+            emit!(auth.created_event(*auth_info.key, current_slot));
+        }
+
+        msg!("Payments authorized: agent={:?}, meter={:?}, count={}, total={}, policy_id={}",
+             agent, meter_key, payments.len(), total, policy_id);
+
+        Ok(())
+    }
+
+    /// Authorizes a streaming payment whose spendable amount grows by
+    /// `rate_per_slot` every slot, for long-running jobs such as a streamed
+    /// LLM response.
+    /// 
+    /// `amount` is the cap and is checked exactly like a single-call
+    /// `authorize_payment_with_proof`, so the stream as a whole stays within
+    /// the budgets and the meter's price bounds. Unlocking starts at the
+    /// authorization slot; `record_meter_payment` may then consume up to
+    /// `min(amount, rate_per_slot * elapsed slots)` in total, in as many
+    /// records as the merchant likes.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies to evaluate the payment under
+    /// * `amount` - Most the stream can ever pay, in USDC smallest units
+    /// * `rate_per_slot` - Amount unlocked per slot (1 to `amount`)
+    /// * `category` - Category of this payment
+    /// * `nonce` - Unique identifier to prevent replay attacks
+    /// * `expires_at_slot` - Slot after which this authorization expires; must be
+    ///   in the future and at most `MAX_AUTHORIZATION_TTL_SLOTS` ahead
+    /// * `proof` - ZK proof bytes
+    /// * `request_id` - Caller's id for the request being paid, e.g. the x402
+    ///   payment id; echoed in the events (all zero = none)
+    pub fn authorize_streaming_payment(
+        ctx: Context<AuthorizeStreamingPayment>,
+        policy_id: u16,
+        amount: u64,
+        rate_per_slot: u64,
+        category: u8,
+        nonce: u64,
+        expires_at_slot: u64,
+        proof: Vec<u8>,
+        request_id: [u8; 32],
+    ) -> Result<()> {
+        let policy = &ctx.accounts.agent_policy;
+        let meter = &ctx.accounts.meter;
+        let current_slot = Clock::get()?.slot;
+        let payment = AuthParams { amount, category, nonce, expires_at_slot, request_id };
+
+        check_nonce_unused(
+            &ctx.accounts.authorization.agent,
+            &ctx.accounts.authorization.key(),
+            nonce,
+        )?;
+
+        let usage = &mut ctx.accounts.meter_usage;
+        if usage.meter == Pubkey::default() {
+            // Freshly created by init_if_needed
+            usage.meter = meter.key();
+            usage.agent = ctx.accounts.agent.key();
+            usage.bump = ctx.bumps.meter_usage;
+            usage.version = MeterUsage::VERSION;
+        }
+
+        require!(
+            rate_per_slot > 0 && rate_per_slot <= amount,
+            AgentBlinkPayError::InvalidStreamRate
+        );
+        check_payment(policy, meter, usage, &payment, 1, 0, current_slot)?;
+        require!(
+            meter.settlement_mode == settlement_modes::EVENT_ONLY,
+            AgentBlinkPayError::EscrowNotSupported
+        );
+        require!(
+            !policy.enforce_meter_allowlist || ctx.accounts.allowed_meter.is_some(),
+            AgentBlinkPayError::MeterNotAllowed
+        );
+        require!(
+            !meter.allowlist_enabled || ctx.accounts.meter_access.is_some(),
+            AgentBlinkPayError::AgentNotAllowedByMeter
+        );
+        require!(
+            ctx.accounts.denied_meter.owner != &crate::ID,
+            AgentBlinkPayError::MeterDenied
+        );
+
+        meter.check_proof_version(&proof)?;
+        let version = proof_version(&proof);
+        let verifying_key = CircuitKey::Groth16(&ctx.accounts.verifying_key);
+        let verified = verify_policy_proof(
+            policy,
+            meter.proof_circuit(&proof)?,
+            amount,
+            category,
+            meter.key(),
+            nonce,
+            &proof,
+            current_slot,
+            &ctx.accounts.config,
+            ctx.accounts.verifier_program.to_account_info(),
+            verifying_key,
+        )?;
+        if !verified {
+            check_unproven_payment(policy, meter)?;
+        }
+        spend_nullifier(
+            &ctx.accounts.nullifier.to_account_info(),
+            payment_nullifier(
+                b"auth",
+                &ctx.accounts.agent.key(),
+                &meter.key(),
+                nonce,
+                amount,
+                &policy.policy_hash,
+            ),
+            ctx.accounts.agent.key(),
+            ctx.accounts.authorization.key(),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        let auth = &mut ctx.accounts.authorization;
+        auth.set_inner(payment.to_authorization(
+            ctx.accounts.agent.key(),
+            meter,
+            policy,
+            1,
+            current_slot,
+        ));
+        auth.bump = ctx.bumps.authorization;
+        auth.rent_payer = ctx.accounts.payer.key();
+        auth.kind = authorization_kinds::STREAMING;
+        auth.rate_per_slot = rate_per_slot;
+        auth.start_slot = current_slot;
+        auth.set_verification(verifying_key.verification(&ctx.accounts.config, verified));
+        auth.proof_version = version;
+
+        emit!(auth.created_event(auth.key(), current_slot));
+
+        msg!("Streaming payment authorized: agent={:?}, meter={:?}, cap={}, rate_per_slot={}, nonce={}, policy_id={}",
+             auth.agent, auth.meter, amount, rate_per_slot, nonce, policy_id);
+
+        Ok(())
+    }
+
+    /// Authorizes up to `total_amount` of payments to one meter with a
+    /// single proof; `record_budget_payment` then draws it down call by call.
+    /// 
+    /// The proof comes from the budget circuit: its public input is the
+    /// total, checked against the policy's remaining lifetime budget rather
+    /// than `max_per_tx` (see `verify_budget_proof`). `max_per_tx` and the
+    /// meter's payment cap apply to each draw instead. The total must also
+    /// fit today's remaining daily limit and cover at least one call. The
+    /// budget's `Nullifier` is spent as in `authorize_payment_with_proof`.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies to evaluate the budget under
+    /// * `total_amount` - Most all draws together may pay, in USDC smallest units
+    /// * `category` - Category of the payments
+    /// * `nonce` - Unique identifier to prevent replay attacks
+    /// * `expires_at_slot` - Slot after which no more draws are accepted; must be
+    ///   in the future and at most `MAX_AUTHORIZATION_TTL_SLOTS` ahead
+    /// * `proof` - Budget circuit proof bytes
+    pub fn authorize_budget_with_proof(
+        ctx: Context<AuthorizeBudget>,
+        policy_id: u16,
+        total_amount: u64,
+        category: u8,
+        nonce: u64,
+        expires_at_slot: u64,
+        proof: Vec<u8>,
+    ) -> Result<()> {
+        let policy = &ctx.accounts.agent_policy;
+        let meter = &ctx.accounts.meter;
+        let current_slot = Clock::get()?.slot;
+
+        check_nonce_unused(
+            &ctx.accounts.budget_authorization.agent,
+            &ctx.accounts.budget_authorization.key(),
+            nonce,
+        )?;
+
+        let usage = &mut ctx.accounts.meter_usage;
+        if usage.meter == Pubkey::default() {
+            // Freshly created by init_if_needed
+            usage.meter = meter.key();
+            usage.agent = ctx.accounts.agent.key();
+            usage.bump = ctx.bumps.meter_usage;
+            usage.version = MeterUsage::VERSION;
+        }
+
+        require!(!policy.frozen, AgentBlinkPayError::PolicyFrozen);
+        require!(!policy.is_paused(current_slot), AgentBlinkPayError::PolicyPaused);
+        require!(
+            total_amount <= policy.remaining_budget(),
+            AgentBlinkPayError::BudgetExceeded
+        );
+        require!(
+            total_amount <= policy.remaining_today(current_slot),
+            AgentBlinkPayError::DailyLimitExceeded
+        );
+        require!(meter.active, AgentBlinkPayError::MeterInactive);
+        require!(meter.kind == MeterKind::PerCall, AgentBlinkPayError::WrongMeterKind);
+        require!(
+            meter.settlement_mode == settlement_modes::EVENT_ONLY,
+            AgentBlinkPayError::EscrowNotSupported
+        );
+        require!(
+            Category::same(meter.category, category)?,
+            AgentBlinkPayError::CategoryMismatch
+        );
+        require!(
+            policy.accepts_mint(&meter.accepted_mint),
+            AgentBlinkPayError::MintMismatch
+        );
+        require!(
+            total_amount >= meter.effective_price(current_slot).max(1),
+            AgentBlinkPayError::AmountBelowMeterPrice
+        );
+        require!(
+            expires_at_slot > current_slot
+                && expires_at_slot <= current_slot.saturating_add(MAX_AUTHORIZATION_TTL_SLOTS),
+            AgentBlinkPayError::InvalidExpiry
+        );
+        require!(
+            !policy.enforce_meter_allowlist || ctx.accounts.allowed_meter.is_some(),
+            AgentBlinkPayError::MeterNotAllowed
+        );
+        require!(
+            !meter.allowlist_enabled || ctx.accounts.meter_access.is_some(),
+            AgentBlinkPayError::AgentNotAllowedByMeter
+        );
+        require!(
+            ctx.accounts.denied_meter.owner != &crate::ID,
+            AgentBlinkPayError::MeterDenied
+        );
+
+        meter.check_proof_version(&proof)?;
+        let verified = verify_budget_proof(
+            policy,
+            total_amount,
+            category,
+            meter.key(),
+            nonce,
+            &proof,
+            &ctx.accounts.config,
+            ctx.accounts.verifier_program.to_account_info(),
+            CircuitKey::Groth16(&ctx.accounts.verifying_key),
+        )?;
+        if !verified {
+            check_unproven_payment(policy, meter)?;
+        }
+        spend_nullifier(
+            &ctx.accounts.nullifier.to_account_info(),
+            payment_nullifier(
+                b"budget_auth",
+                &ctx.accounts.agent.key(),
+                &meter.key(),
+                nonce,
+                total_amount,
+                &policy.policy_hash,
+            ),
+            ctx.accounts.agent.key(),
+            ctx.accounts.budget_authorization.key(),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        let budget = &mut ctx.accounts.budget_authorization;
+        budget.agent = ctx.accounts.agent.key();
+        budget.meter = meter.key();
+        budget.policy_id = policy_id;
+        budget.nonce = nonce;
+        budget.mint = meter.accepted_mint;
+        budget.category = category;
+        budget.total_amount = total_amount;
+        budget.remaining = total_amount;
+        budget.expires_at_slot = expires_at_slot;
+        budget.created_at_slot = current_slot;
+        budget.price_at_authorization = meter.effective_price(current_slot);
+        budget.policy_hash_at_auth = policy.policy_hash;
+        budget.rent_payer = ctx.accounts.payer.key();
+        budget.bump = ctx.bumps.budget_authorization;
+
+        emit!(BudgetAuthorizationCreated {
+            budget_authorization: budget.key(),
+            agent: budget.agent,
+            meter: budget.meter,
+            nonce,
+            policy_id,
+            total_amount,
+            category,
+            expires_at_slot,
+            slot: current_slot,
+        });
+
+        msg!("Budget authorized: agent={:?}, meter={:?}, total={}, nonce={}, policy_id={}",
+             budget.agent, budget.meter, total_amount, nonce, policy_id);
+
+        Ok(())
+    }
+
+    /// Draws one payment from a budget authorization and emits `MeterPaid`
+    /// for it.
+    /// 
+    /// Like `record_meter_payment`, anyone may submit it, and the policy
+    /// must not have been frozen, paused or replaced since the budget was
+    /// authorized. Each draw is 1 to `remaining`, at most `max_per_tx` and
+    /// within the meter's payment cap, and is charged to the policy's
+    /// budgets when recorded. Draws are accepted until `expires_at_slot`
+    /// plus the Config's grace window.
+    /// 
+    /// # Arguments
+    /// * `nonce` - The nonce of the budget authorization
+    /// * `amount` - Amount to charge for this call
+    pub fn record_budget_payment(
+        ctx: Context<RecordBudgetPayment>,
+        nonce: u64,
+        amount: u64,
+    ) -> Result<()> {
+        let budget = &mut ctx.accounts.budget_authorization;
+        let meter = &ctx.accounts.meter;
+        let policy = &mut ctx.accounts.agent_policy;
+        let clock = Clock::get()?;
+        let current_slot = clock.slot;
+
+        require!(
+            !budget.is_expired_after(current_slot, ctx.accounts.config.grace_slots),
+            AgentBlinkPayError::AuthorizationExpired
+        );
+        let recorded_in_grace = budget.is_expired_after(current_slot, 0);
+        require!(!policy.frozen, AgentBlinkPayError::PolicyFrozen);
+        require!(!policy.is_paused(current_slot), AgentBlinkPayError::PolicyPaused);
+        require!(
+            budget.policy_hash_at_auth == policy.policy_hash,
+            AgentBlinkPayError::PolicyChangedSinceAuthorization
+        );
+        require!(
+            meter.active || budget.expires_at_slot >= meter.paused_at_slot,
+            AgentBlinkPayError::MeterInactive
+        );
+        require!(
+            Category::same(budget.category, meter.category)?,
+            AgentBlinkPayError::CategoryMismatch
+        );
+        require!(
+            amount > 0 && amount <= budget.remaining,
+            AgentBlinkPayError::InvalidConsumeAmount
+        );
+        require!(amount <= policy.max_per_tx, AgentBlinkPayError::AmountExceedsMax);
+
+        let usage = &mut ctx.accounts.meter_usage;
+        require!(
+            amount <= meter.max_payment(usage.calls, current_slot),
+            AgentBlinkPayError::AmountExceedsMeterCap
+        );
+
+        // Same bookkeeping as record_meter_payment
+        let mut counters = ctx.accounts.meter_counters.load_mut()?;
+        counters.record_call(meter, amount, current_slot)?;
+        policy.charge(amount, current_slot)?;
+        usage.calls = usage
+            .calls
+            .checked_add(1)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        usage.volume = usage
+            .volume
+            .checked_add(amount)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        if usage.first_call_slot == 0 {
+            usage.first_call_slot = current_slot;
+        }
+
+        budget.remaining -= amount;
+        let (referrer_amount, primary_amount, split_amounts) = meter.split_amounts(amount);
+        let current_price = meter.effective_price(current_slot);
+
+        emit!(MeterPaid {
+            agent: budget.agent,
+            meter: budget.meter,
+            recorded_by: ctx.accounts.recorder.key(),
+            amount,
+            mint: budget.mint,
+            category: budget.category,
+            nonce,
+            policy_id: budget.policy_id,
+            meter_total_calls: counters.total_calls,
+            meter_total_volume: counters.total_volume,
+            free_call: false,
+            referrer_amount,
+            primary_amount,
+            split_amounts,
+            agent_calls: usage.calls,
+            agent_volume: usage.volume,
+            agent_first_call_slot: usage.first_call_slot,
+            amount_remaining: budget.remaining,
+            authorized_amount: budget.total_amount,
+            price_at_authorization: budget.price_at_authorization,
+            current_price,
+            price_changed: current_price != budget.price_at_authorization,
+            request_id: [0; 32],
+            settled_on_chain: false,
+            recorded_in_grace,
+            settlement_retry: false,
+            units: 0,
+            tip: 0,
+            verification_method: verification_methods::NONE,
+            verifier_ref: Pubkey::default(),
+            slot: current_slot,
+        });
+
+        msg!("Budget payment recorded: agent={:?}, meter={:?}, amount={}, nonce={}, remaining={}",
+             budget.agent, budget.meter, amount, nonce, budget.remaining);
+
+        Ok(())
+    }
+
+    /// Closes a budget authorization that is spent or past its expiry (and
+    /// grace window), returning its rent to the account that paid for it.
+    /// 
+    /// Permissionless, like `close_authorization`; emits
+    /// `AuthorizationClosed` with reason USED or EXPIRED.
+    pub fn close_budget_authorization(ctx: Context<CloseBudgetAuthorization>) -> Result<()> {
+        let budget = &ctx.accounts.budget_authorization;
+        let current_slot = Clock::get()?.slot;
+
+        let reason = budget
+            .close_reason(current_slot, &ctx.accounts.config)
+            .ok_or(AgentBlinkPayError::AuthorizationStillLive)?;
+
+        emit!(AuthorizationClosed {
+            agent: budget.agent,
+            meter: budget.meter,
+            nonce: budget.nonce,
+            reason,
+            slot: current_slot,
+        });
+
+        msg!("Budget authorization closed: agent={:?}, meter={:?}, nonce={}, reason={}",
+             budget.agent, budget.meter, budget.nonce, reason);
+
+        Ok(())
+    }
+
+    /// Authorizes one payment to each of up to `MAX_BUNDLE_METERS` meters
+    /// with a single proof, for workflows that call a fixed pipeline of APIs.
+    /// 
+    /// `remaining_accounts` carries one `(meter, denied_meter)` pair per
+    /// entry of `amounts`, in the same order; `denied_meter` is the
+    /// ["denied", agent_policy, meter] address, as in `authorize_payment_with_proof`.
+    /// Each meter must be active, per-call, event-only, in the policy's
+    /// category and mint, and appear once; each amount must cover the
+    /// meter's price and stay within `max_per_tx`. Allowlisted policies and
+    /// meters aren't supported here and need individual authorizations.
+    /// 
+    /// The proof is a budget circuit proof (see `verify_budget_proof`) over
+    /// the bundle's total, which must also fit the remaining budget and
+    /// today's daily limit, and its `Nullifier` is spent as in
+    /// `authorize_payment_with_proof`. Each slice is consumed by
+    /// `record_bundle_payment`.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies to evaluate the bundle under
+    /// * `amounts` - Amount for each meter, in USDC smallest units
+    /// * `nonce` - Unique identifier to prevent replay attacks
+    /// * `expires_at_slot` - Slot after which no slice can be recorded; must be
+    ///   in the future and at most `MAX_AUTHORIZATION_TTL_SLOTS` ahead
+    /// * `proof` - Budget circuit proof bytes
+    pub fn authorize_bundle_with_proof<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AuthorizeBundle<'info>>,
+        policy_id: u16,
+        amounts: Vec<u64>,
+        nonce: u64,
+        expires_at_slot: u64,
+        proof: Vec<u8>,
+    ) -> Result<()> {
+        require!(!amounts.is_empty(), AgentBlinkPayError::EmptyBatch);
+        require!(amounts.len() <= MAX_BUNDLE_METERS, AgentBlinkPayError::BatchTooLarge);
+        require!(
+            ctx.remaining_accounts.len() == amounts.len() * 2,
+            AgentBlinkPayError::BatchAccountsMismatch
+        );
+
+        let policy = &ctx.accounts.agent_policy;
+        let agent = ctx.accounts.agent.key();
+        let current_slot = Clock::get()?.slot;
+
+        check_nonce_unused(
+            &ctx.accounts.bundle_authorization.agent,
+            &ctx.accounts.bundle_authorization.key(),
+            nonce,
+        )?;
+
+        let total = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        require!(!policy.frozen, AgentBlinkPayError::PolicyFrozen);
+        require!(!policy.is_paused(current_slot), AgentBlinkPayError::PolicyPaused);
+        require!(!policy.enforce_meter_allowlist, AgentBlinkPayError::MeterNotAllowed);
+        require!(total <= policy.remaining_budget(), AgentBlinkPayError::BudgetExceeded);
+        require!(
+            total <= policy.remaining_today(current_slot),
+            AgentBlinkPayError::DailyLimitExceeded
+        );
+        require!(
+            expires_at_slot > current_slot
+                && expires_at_slot <= current_slot.saturating_add(MAX_AUTHORIZATION_TTL_SLOTS),
+            AgentBlinkPayError::InvalidExpiry
+        );
+
+        let bundle = &mut ctx.accounts.bundle_authorization;
+        let mut any_meter_requires_zk = false;
+        for (i, (amount, accounts)) in amounts.iter().zip(ctx.remaining_accounts.chunks(2)).enumerate() {
+            let meter = Account::<Meter>::try_from(&accounts[0])?;
+            let denied_info = &accounts[1];
+
+            let (expected_denied, _) = Pubkey::find_program_address(
+                &[b"denied", policy.key().as_ref(), meter.key().as_ref()],
+                ctx.program_id,
+            );
+            require_keys_eq!(
+                *denied_info.key,
+                expected_denied,
+                AgentBlinkPayError::InvalidBundleMeter
+            );
+            require!(denied_info.owner != &crate::ID, AgentBlinkPayError::MeterDenied);
+            require!(
+                !bundle.meters[..i].contains(&meter.key()),
+                AgentBlinkPayError::InvalidBundleMeter
+            );
+
+            require!(meter.active, AgentBlinkPayError::MeterInactive);
+            require!(meter.kind == MeterKind::PerCall, AgentBlinkPayError::WrongMeterKind);
+            require!(!meter.allowlist_enabled, AgentBlinkPayError::AgentNotAllowedByMeter);
+            require!(
+                meter.settlement_mode == settlement_modes::EVENT_ONLY,
+                AgentBlinkPayError::EscrowNotSupported
+            );
+            require!(
+                Category::same(meter.category, policy.allowed_category)?,
+                AgentBlinkPayError::CategoryMismatch
+            );
+            require!(
+                policy.accepts_mint(&meter.accepted_mint),
+                AgentBlinkPayError::MintMismatch
+            );
+            meter.check_proof_version(&proof)?;
+
+            let price = meter.effective_price(current_slot);
+            require!(*amount >= price.max(1), AgentBlinkPayError::AmountBelowMeterPrice);
+            require!(*amount <= policy.max_per_tx, AgentBlinkPayError::AmountExceedsMax);
+
+            bundle.meters[i] = meter.key();
+            bundle.mints[i] = meter.accepted_mint;
+            bundle.amounts[i] = *amount;
+            bundle.remaining[i] = *amount;
+            bundle.prices_at_authorization[i] = price;
+            any_meter_requires_zk |= meter.requires_zk;
+        }
+
+        let verified = verify_budget_proof(
+            policy,
+            total,
+            policy.allowed_category,
+            // A bundle spans several meters, so it binds none; the nonce
+            // still ties the proof to this bundle
+            Pubkey::default(),
+            nonce,
+            &proof,
+            &ctx.accounts.config,
+            ctx.accounts.verifier_program.to_account_info(),
+            CircuitKey::Groth16(&ctx.accounts.verifying_key),
+        )?;
+        if !verified {
+            require!(!policy.requires_proof(), AgentBlinkPayError::ZkRequiredByPolicy);
+            require!(!any_meter_requires_zk, AgentBlinkPayError::ZkRequiredByMeter);
+        }
+        spend_nullifier(
+            &ctx.accounts.nullifier.to_account_info(),
+            payment_nullifier(
+                b"bundle_auth",
+                &agent,
+                &Pubkey::default(),
+                nonce,
+                total,
+                &policy.policy_hash,
+            ),
+            agent,
+            bundle.key(),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        bundle.agent = agent;
+        bundle.policy_id = policy_id;
+        bundle.nonce = nonce;
+        bundle.category = policy.allowed_category;
+        bundle.meter_count = amounts.len() as u8;
+        bundle.expires_at_slot = expires_at_slot;
+        bundle.created_at_slot = current_slot;
+        bundle.policy_hash_at_auth = policy.policy_hash;
+        bundle.rent_payer = ctx.accounts.payer.key();
+        bundle.bump = ctx.bumps.bundle_authorization;
+
+        emit!(BundleAuthorizationCreated {
+            bundle_authorization: bundle.key(),
+            agent,
+            nonce,
+            policy_id,
+            meters: bundle.meters[..amounts.len()].to_vec(),
+            amounts: amounts.clone(),
+            expires_at_slot,
+            slot: current_slot,
+        });
+
+        msg!("Bundle authorized: agent={:?}, meters={}, total={}, nonce={}, policy_id={}",
+             agent, amounts.len(), total, nonce, policy_id);
+
+        Ok(())
+    }
+
+    /// Records the slice of a bundle authorization for one of its meters and
+    /// emits `MeterPaid` for it, like `record_meter_payment` does for a
+    /// single authorization.
+    /// 
+    /// Anyone may submit it; the payer funds the agent's `MeterUsage` for
+    /// the meter if it doesn't exist yet. The whole slice is charged at once.
+    /// Slices are accepted until `expires_at_slot` plus the Config's grace
+    /// window, and the policy must not have been frozen, paused or replaced
+    /// since the bundle was authorized.
+    /// 
+    /// # Arguments
+    /// * `nonce` - The nonce of the bundle authorization
+    /// * `meter_index` - Position of the meter in the bundle
+    pub fn record_bundle_payment(
+        ctx: Context<RecordBundlePayment>,
+        nonce: u64,
+        meter_index: u8,
+    ) -> Result<()> {
+        let bundle = &mut ctx.accounts.bundle_authorization;
+        let meter = &ctx.accounts.meter;
+        let clock = Clock::get()?;
+        let current_slot = clock.slot;
+        let i = meter_index as usize;
+
+        require!(
+            meter_index < bundle.meter_count && bundle.meters[i] == meter.key(),
+            AgentBlinkPayError::InvalidBundleMeter
+        );
+        require!(bundle.remaining[i] > 0, AgentBlinkPayError::AuthorizationUsed);
+        require!(
+            !bundle.is_expired_after(current_slot, ctx.accounts.config.grace_slots),
+            AgentBlinkPayError::AuthorizationExpired
+        );
+        let recorded_in_grace = bundle.is_expired_after(current_slot, 0);
+
+        let policy = &mut ctx.accounts.agent_policy;
+        require!(!policy.frozen, AgentBlinkPayError::PolicyFrozen);
+        require!(!policy.is_paused(current_slot), AgentBlinkPayError::PolicyPaused);
+        require!(
+            bundle.policy_hash_at_auth == policy.policy_hash,
+            AgentBlinkPayError::PolicyChangedSinceAuthorization
+        );
+        require!(
+            meter.active || bundle.expires_at_slot >= meter.paused_at_slot,
+            AgentBlinkPayError::MeterInactive
+        );
+        require!(
+            Category::same(bundle.category, meter.category)?,
+            AgentBlinkPayError::CategoryMismatch
+        );
+
+        let usage = &mut ctx.accounts.meter_usage;
+        if usage.meter == Pubkey::default() {
+            // Freshly created by init_if_needed
+            usage.meter = meter.key();
+            usage.agent = ctx.accounts.agent.key();
+            usage.bump = ctx.bumps.meter_usage;
+            usage.version = MeterUsage::VERSION;
+        }
+
+        let amount = bundle.remaining[i];
+        require!(
+            amount <= meter.max_payment(usage.calls, current_slot),
+            AgentBlinkPayError::AmountExceedsMeterCap
+        );
+
+        // Same bookkeeping as record_meter_payment
+        let mut counters = ctx.accounts.meter_counters.load_mut()?;
+        counters.record_call(meter, amount, current_slot)?;
+        policy.charge(amount, current_slot)?;
+        usage.calls = usage
+            .calls
+            .checked_add(1)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        usage.volume = usage
+            .volume
+            .checked_add(amount)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        if usage.first_call_slot == 0 {
+            usage.first_call_slot = current_slot;
+        }
+
+        bundle.remaining[i] = 0;
+        let (referrer_amount, primary_amount, split_amounts) = meter.split_amounts(amount);
+        let current_price = meter.effective_price(current_slot);
+        let price_at_authorization = bundle.prices_at_authorization[i];
+
+        emit!(MeterPaid {
+            agent: bundle.agent,
+            meter: meter.key(),
+            recorded_by: ctx.accounts.recorder.key(),
+            amount,
+            mint: bundle.mints[i],
+            category: bundle.category,
+            nonce,
+            policy_id: bundle.policy_id,
+            meter_total_calls: counters.total_calls,
+            meter_total_volume: counters.total_volume,
+            free_call: false,
+            referrer_amount,
+            primary_amount,
+            split_amounts,
+            agent_calls: usage.calls,
+            agent_volume: usage.volume,
+            agent_first_call_slot: usage.first_call_slot,
+            amount_remaining: 0,
+            authorized_amount: bundle.amounts[i],
+            price_at_authorization,
+            current_price,
+            price_changed: current_price != price_at_authorization,
+            request_id: [0; 32],
+            settled_on_chain: false,
+            recorded_in_grace,
+            settlement_retry: false,
+            units: 0,
+            tip: 0,
+            verification_method: verification_methods::NONE,
+            verifier_ref: Pubkey::default(),
+            slot: current_slot,
+        });
+
+        msg!("Bundle payment recorded: agent={:?}, meter={:?}, amount={}, nonce={}, index={}",
+             bundle.agent, meter.key(), amount, nonce, meter_index);
+
+        Ok(())
+    }
+
+    /// Closes a bundle authorization whose slices are all recorded or that
+    /// is past its expiry (and grace window), returning its rent to the
+    /// account that paid for it.
+    /// 
+    /// Permissionless, like `close_authorization`; emits
+    /// `AuthorizationClosed` (with a default meter) and reason USED or EXPIRED.
+    pub fn close_bundle_authorization(ctx: Context<CloseBundleAuthorization>) -> Result<()> {
+        let bundle = &ctx.accounts.bundle_authorization;
+        let current_slot = Clock::get()?.slot;
+
+        let reason = bundle
+            .close_reason(current_slot, &ctx.accounts.config)
+            .ok_or(AgentBlinkPayError::AuthorizationStillLive)?;
+
+        emit!(AuthorizationClosed {
+            agent: bundle.agent,
+            meter: Pubkey::default(),
+            nonce: bundle.nonce,
+            reason,
+            slot: current_slot,
+        });
+
+        msg!("Bundle authorization closed: agent={:?}, nonce={}, reason={}",
+             bundle.agent, bundle.nonce, reason);
+
+        Ok(())
+    }
+
+    /// Opens a unidirectional payment channel from the agent to a meter,
+    /// for call rates too high for a transaction per call.
+    /// 
+    /// The agent then pays per call off-chain by signing vouchers: the
+    /// message `CHANNEL_VOUCHER_DOMAIN || channel || opened_at_slot ||
+    /// cumulative_amount` (both u64 LE), each with a higher cumulative
+    /// amount than the last. `opened_at_slot` ties vouchers to this opening
+    /// of the channel, so they can't be replayed after it is closed and
+    /// reopened at the same address. The
+    /// meter authority submits the latest one with `settle_channel` whenever
+    /// it likes. Nothing is reserved up front; the policy is charged as
+    /// vouchers are settled, and `deposit_cap` bounds what they can claim.
+    /// 
+    /// Opening is checked like `authorize_payment_simple`: no proof, so ZK
+    /// meters and policies can't open channels, and the agent must be a
+    /// keypair since it signs vouchers itself.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies settles are charged to
+    /// * `deposit_cap` - Most the vouchers can claim in total
+    /// * `expires_at_slot` - Slot after which no more vouchers are settled; must
+    ///   be in the future and at most `MAX_AUTHORIZATION_TTL_SLOTS` ahead
+    pub fn open_channel(
+        ctx: Context<OpenChannel>,
+        policy_id: u16,
+        deposit_cap: u64,
+        expires_at_slot: u64,
+    ) -> Result<()> {
+        let policy = &ctx.accounts.agent_policy;
+        let meter = &ctx.accounts.meter;
+        let current_slot = Clock::get()?.slot;
+
+        let usage = &mut ctx.accounts.meter_usage;
+        if usage.meter == Pubkey::default() {
+            // Freshly created by init_if_needed
+            usage.meter = meter.key();
+            usage.agent = ctx.accounts.agent.key();
+            usage.bump = ctx.bumps.meter_usage;
+            usage.version = MeterUsage::VERSION;
+        }
+
+        require!(!policy.agent_is_pda, AgentBlinkPayError::AgentMustBeKeypair);
+        require!(!policy.frozen, AgentBlinkPayError::PolicyFrozen);
+        require!(!policy.is_paused(current_slot), AgentBlinkPayError::PolicyPaused);
+        require!(!policy.requires_proof(), AgentBlinkPayError::ZkRequiredByPolicy);
+        require!(
+            deposit_cap <= policy.remaining_budget(),
+            AgentBlinkPayError::BudgetExceeded
+        );
+        require!(meter.active, AgentBlinkPayError::MeterInactive);
+        require!(!meter.requires_zk, AgentBlinkPayError::ZkRequiredByMeter);
+        require!(meter.kind == MeterKind::PerCall, AgentBlinkPayError::WrongMeterKind);
+        require!(
+            meter.settlement_mode == settlement_modes::EVENT_ONLY,
+            AgentBlinkPayError::EscrowNotSupported
+        );
+        require!(
+            Category::same(meter.category, policy.allowed_category)?,
+            AgentBlinkPayError::CategoryMismatch
+        );
+        require!(
+            policy.accepts_mint(&meter.accepted_mint),
+            AgentBlinkPayError::MintMismatch
+        );
+        require!(
+            deposit_cap >= meter.effective_price(current_slot).max(1),
+            AgentBlinkPayError::AmountBelowMeterPrice
+        );
+        require!(
+            expires_at_slot > current_slot
+                && expires_at_slot <= current_slot.saturating_add(MAX_AUTHORIZATION_TTL_SLOTS),
+            AgentBlinkPayError::InvalidExpiry
+        );
+        require!(
+            !policy.enforce_meter_allowlist || ctx.accounts.allowed_meter.is_some(),
+            AgentBlinkPayError::MeterNotAllowed
+        );
+        require!(
+            !meter.allowlist_enabled || ctx.accounts.meter_access.is_some(),
+            AgentBlinkPayError::AgentNotAllowedByMeter
+        );
+        require!(
+            ctx.accounts.denied_meter.owner != &crate::ID,
+            AgentBlinkPayError::MeterDenied
+        );
+
+        let channel = &mut ctx.accounts.channel;
+        channel.agent = ctx.accounts.agent.key();
+        channel.meter = meter.key();
+        channel.policy_id = policy_id;
+        channel.mint = meter.accepted_mint;
+        channel.category = meter.category;
+        channel.deposit_cap = deposit_cap;
+        channel.claimed = 0;
+        channel.expires_at_slot = expires_at_slot;
+        channel.opened_at_slot = current_slot;
+        channel.price_at_open = meter.effective_price(current_slot);
+        channel.policy_hash_at_open = policy.policy_hash;
+        channel.rent_payer = ctx.accounts.payer.key();
+        channel.bump = ctx.bumps.channel;
+
+        emit!(ChannelOpened {
+            channel: channel.key(),
+            agent: channel.agent,
+            meter: channel.meter,
+            policy_id,
+            deposit_cap,
+            expires_at_slot,
+            slot: current_slot,
+        });
+
+        msg!("Channel opened: agent={:?}, meter={:?}, deposit_cap={}, expires_at_slot={}",
+             channel.agent, channel.meter, deposit_cap, expires_at_slot);
+
+        Ok(())
+    }
+
+    /// Settles the latest voucher of a channel, emitting `MeterPaid` for
+    /// the amount above what was already claimed.
+    /// 
+    /// Signed by the meter authority. The agent's ed25519 signature over the
+    /// voucher is checked by the Ed25519 program in the instruction right
+    /// before this one; this instruction reads it back through the
+    /// instructions sysvar (see `verify_channel_voucher`). Vouchers are
+    /// cumulative, so only the newest needs settling and older ones are
+    /// rejected. Settles are accepted until the expiry plus the Config's
+    /// grace window, and the policy must not have been frozen, paused or
+    /// replaced since the channel was opened.
+    /// 
+    /// # Arguments
+    /// * `cumulative_amount` - Total the voucher pays over the channel's lifetime
+    /// * `agent_signature` - The agent's signature over the voucher, as passed
+    ///   to the Ed25519 program
+    pub fn settle_channel(
+        ctx: Context<SettleChannel>,
+        cumulative_amount: u64,
+        agent_signature: [u8; 64],
+    ) -> Result<()> {
+        let channel = &mut ctx.accounts.channel;
+        let clock = Clock::get()?;
+        let current_slot = clock.slot;
+
+        verify_channel_voucher(
+            &ctx.accounts.instructions,
+            &channel.key(),
+            &channel.agent,
+            channel.opened_at_slot,
+            cumulative_amount,
+            &agent_signature,
+        )?;
+
+        require!(
+            !channel.is_expired_after(current_slot, ctx.accounts.config.grace_slots),
+            AgentBlinkPayError::AuthorizationExpired
+        );
+        let recorded_in_grace = channel.is_expired_after(current_slot, 0);
+        require!(
+            cumulative_amount > channel.claimed,
+            AgentBlinkPayError::StaleChannelVoucher
+        );
+        require!(
+            cumulative_amount <= channel.deposit_cap,
+            AgentBlinkPayError::ChannelCapExceeded
+        );
+
+        let policy = &mut ctx.accounts.agent_policy;
+        require!(!policy.frozen, AgentBlinkPayError::PolicyFrozen);
+        require!(!policy.is_paused(current_slot), AgentBlinkPayError::PolicyPaused);
+        require!(
+            channel.policy_hash_at_open == policy.policy_hash,
+            AgentBlinkPayError::PolicyChangedSinceAuthorization
+        );
+
+        let meter = &ctx.accounts.meter;
+        require!(
+            meter.active || channel.expires_at_slot >= meter.paused_at_slot,
+            AgentBlinkPayError::MeterInactive
+        );
+        require!(
+            Category::same(channel.category, meter.category)?,
+            AgentBlinkPayError::CategoryMismatch
+        );
+
+        let amount = cumulative_amount - channel.claimed;
+
+        // Same bookkeeping as record_meter_payment, once per settle
+        let mut counters = ctx.accounts.meter_counters.load_mut()?;
+        counters.record_call(meter, amount, current_slot)?;
+        policy.charge(amount, current_slot)?;
+        let usage = &mut ctx.accounts.meter_usage;
+        usage.calls = usage
+            .calls
+            .checked_add(1)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        usage.volume = usage
+            .volume
+            .checked_add(amount)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        if usage.first_call_slot == 0 {
+            usage.first_call_slot = current_slot;
+        }
+
+        channel.claimed = cumulative_amount;
+        let (referrer_amount, primary_amount, split_amounts) = meter.split_amounts(amount);
+        let current_price = meter.effective_price(current_slot);
+
+        emit!(MeterPaid {
+            agent: channel.agent,
+            meter: channel.meter,
+            recorded_by: ctx.accounts.authority.key(),
+            amount,
+            mint: channel.mint,
+            category: channel.category,
+            nonce: 0,
+            policy_id: channel.policy_id,
+            meter_total_calls: counters.total_calls,
+            meter_total_volume: counters.total_volume,
+            free_call: false,
+            referrer_amount,
+            primary_amount,
+            split_amounts,
+            agent_calls: usage.calls,
+            agent_volume: usage.volume,
+            agent_first_call_slot: usage.first_call_slot,
+            amount_remaining: channel.deposit_cap - channel.claimed,
+            authorized_amount: channel.deposit_cap,
+            price_at_authorization: channel.price_at_open,
+            current_price,
+            price_changed: current_price != channel.price_at_open,
+            request_id: [0; 32],
+            settled_on_chain: false,
+            recorded_in_grace,
+            settlement_retry: false,
+            units: 0,
+            tip: 0,
+            verification_method: verification_methods::NONE,
+            verifier_ref: Pubkey::default(),
+            slot: current_slot,
+        });
+
+        msg!("Channel settled: agent={:?}, meter={:?}, amount={}, claimed={}",
+             channel.agent, channel.meter, amount, channel.claimed);
+
+        Ok(())
+    }
+
+    /// Closes a channel past its expiry and grace window, or one whose
+    /// whole cap was claimed, returning its rent to the account that paid
+    /// for it. Whatever the vouchers didn't claim was never charged, so the
+    /// agent's budget keeps it.
+    /// 
+    /// Permissionless, like `close_authorization`. The meter authority
+    /// should settle the latest voucher before the grace window ends. Not
+    /// allowed in the slot the channel was opened in, so a reopened channel
+    /// always gets a new `opened_at_slot`.
+    pub fn close_channel(ctx: Context<CloseChannel>) -> Result<()> {
+        let channel = &ctx.accounts.channel;
+        let current_slot = Clock::get()?.slot;
+
+        require!(
+            channel.claimed == channel.deposit_cap
+                || channel.is_expired_after(current_slot, ctx.accounts.config.grace_slots),
+            AgentBlinkPayError::AuthorizationStillLive
+        );
+        require!(
+            current_slot > channel.opened_at_slot,
+            AgentBlinkPayError::AuthorizationStillLive
+        );
+
+        emit!(ChannelClosed {
+            channel: channel.key(),
+            agent: channel.agent,
+            meter: channel.meter,
+            claimed: channel.claimed,
+            deposit_cap: channel.deposit_cap,
+            slot: current_slot,
+        });
+
+        msg!("Channel closed: agent={:?}, meter={:?}, claimed={} of {}",
+             channel.agent, channel.meter, channel.claimed, channel.deposit_cap);
+
+        Ok(())
+    }
+
+    /// Records a meter payment by consuming an authorization.
+    /// 
+    /// Anyone may submit it (the agent, the merchant or an x402 facilitator
+    /// settling for it): the authorization already carries the agent's
+    /// consent, and the PDA seeds tie the agent, meter and policy accounts
+    /// to it. The signer is reported as `recorded_by`. An authorization
+    /// bound to a `consumer` can only be recorded by that key.
+    /// 
+    /// This consumes one call of the authorization and emits a MeterPaid
+    /// event for the amount charged. The off-chain Circle service listens
+    /// for this event to execute the actual USDC transfer.
+    /// 
+    /// With `consume_all` the call is charged its share of the authorized
+    /// amount, as before partial consumption existed: all of it for a
+    /// single call, `amount / quantity` per call of a batch. Otherwise the
+    /// call is charged `amount`, which may be anything from 1 up to the
+    /// authorization's `amount_remaining`, so e.g. a $1.00 authorization
+    /// can pay a $0.30 call and keep $0.70 for later ones. The authorization
+    /// is marked used once both its amount and its calls are consumed; an
+    /// expired one forfeits whatever is left.
+    /// 
+    /// Amounts follow the price the authorization was issued at. If the
+    /// meter has been repriced since, `MeterPaid` reports both prices and
+    /// sets `price_changed`, so settlement can tell it from a short payment.
+    /// 
+    /// A streaming authorization can only be consumed up to what it has
+    /// unlocked so far (see `authorize_streaming_payment`); `consume_all`
+    /// charges everything unlocked and not yet consumed, and asking for more
+    /// fails with `AmountNotYetUnlocked`.
+    /// 
+    /// The amount is added to the policy's `lifetime_spent`. When that reaches
+    /// `total_budget` the policy freezes itself and emits `BudgetExhausted`.
+    /// It is also added to `spent_today`, which must stay within `daily_limit`.
+    /// 
+    /// With `alert_threshold_bps` set, crossing that fraction of either limit
+    /// emits `SpendThresholdCrossed` (at most once per daily window).
+    /// 
+    /// The meter's totals and rate limit window are updated in its
+    /// `MeterCounters`. Only that account is write-locked, so payments to
+    /// the same meter are serialized within a block while the Meter itself
+    /// stays read-only and authorizations against it run in parallel.
+    /// 
+    /// # Arguments
+    /// * `nonce` - The nonce of the authorization to consume
+    /// * `amount` - Amount to charge for this call (ignored with `consume_all`)
+    /// * `consume_all` - Charge the call's full share of the authorization
+    /// * `external_id` - Caller's id for this record (all zero = none). Once
+    ///   the authorization is used, a retry with the id of the record that
+    ///   used it is a no-op
+    /// * `preimage` - Secret whose SHA-256 is the authorization's `hash_lock`
+    ///   (at most `MAX_PREIMAGE_LEN` bytes; ignored without a lock)
+    /// * `create_receipt` - Also keep a `Receipt` of the payment, funded by
+    ///   `payer`; later records of the same authorization add to it
+    pub fn record_meter_payment(
+        ctx: Context<RecordPayment>,
+        nonce: u64,
+        amount: u64,
+        consume_all: bool,
+        external_id: [u8; 16],
+        preimage: Vec<u8>,
+        create_receipt: bool,
+    ) -> Result<()> {
+        let auth = &mut ctx.accounts.authorization;
+
+        // Validate authorization is not revoked
+        auth.require_kind(&[authorization_kinds::STANDARD, authorization_kinds::STREAMING])?;
+        require!(!auth.revoked, AgentBlinkPayError::AuthorizationRevoked);
+        require!(
+            auth.consumer == Pubkey::default() || auth.consumer == ctx.accounts.recorder.key(),
+            AgentBlinkPayError::UnauthorizedConsumer
+        );
+
+        // Hash-locked (pay-on-delivery): only the revealed secret releases payment
+        require!(preimage.len() <= MAX_PREIMAGE_LEN, AgentBlinkPayError::PreimageTooLong);
+        require!(
+            auth.hash_lock == [0; 32]
+                || solana_program::hash::hash(&preimage).to_bytes() == auth.hash_lock,
+            AgentBlinkPayError::InvalidPreimage
+        );
+        
+        // Validate authorization has not expired. Records that land up to
+        // `grace_slots` late (e.g. under congestion) still go through, flagged.
+        let clock = Clock::get()?;
+        let current_slot = clock.slot;
+        require!(
+            !auth.is_expired_after(&clock, ctx.accounts.config.grace_slots),
+            AgentBlinkPayError::AuthorizationExpired
+        );
+        let recorded_in_grace = auth.is_expired(&clock);
+
+        // Idempotent retry (e.g. after a client timeout) of the record that
+        // used the authorization up: succeed without charging or emitting
+        if auth.used && external_id != [0; 16] && external_id == auth.external_id {
+            msg!("Record already applied: nonce={}", nonce);
+            return Ok(());
+        }
+        require!(!auth.used, AgentBlinkPayError::AuthorizationUsed);
+        
+        // A freeze or pause also stops authorizations issued before it, and
+        // so does replacing the policy the authorization was checked against
+        let policy = &mut ctx.accounts.agent_policy;
+        require!(!policy.frozen, AgentBlinkPayError::PolicyFrozen);
+        require!(!policy.is_paused(current_slot), AgentBlinkPayError::PolicyPaused);
+        require!(
+            auth.policy_hash_at_auth == [0; 32] || auth.policy_hash_at_auth == policy.policy_hash,
+            AgentBlinkPayError::PolicyChangedSinceAuthorization
+        );
+
+        // Grace period: a paused meter still settles authorizations that
+        // were live when it was paused (authorize rejects inactive meters,
+        // so no later ones exist). close_meter waits for all of them.
+        let meter = &ctx.accounts.meter;
+        require!(
+            meter.active || auth.expires_at_slot >= meter.paused_at_slot,
+            AgentBlinkPayError::MeterInactive
+        );
+
+        let amount = if consume_all {
+            auth.next_call_amount(current_slot)
+        } else {
+            require!(
+                amount > 0 && amount <= auth.amount_remaining,
+                AgentBlinkPayError::InvalidConsumeAmount
+            );
+            amount
+        };
+        // Streams have no free calls: nothing unlocked yet means nothing to record
+        require!(
+            amount <= auth.claimable(current_slot)
+                && (amount > 0 || auth.kind != authorization_kinds::STREAMING),
+            AgentBlinkPayError::AmountNotYetUnlocked
+        );
+
+        // A settlement retry (see report_settlement_failure) only re-emits
+        // the payment; it was charged and counted the first time
+        let settlement_retry = auth.settlement_failed;
+        let mut counters = ctx.accounts.meter_counters.load_mut()?;
+        let usage = &mut ctx.accounts.meter_usage;
+        if !settlement_retry {
+            // Meter-wide backpressure and merchant-side usage statistics
+            counters.record_call(meter, amount, current_slot)?;
+
+            // Charge the budgets (may emit alerts and auto-freeze)
+            policy.charge(amount, current_slot)?;
+
+            // Per-agent counters; the call count selects the price tier
+            usage.calls = usage
+                .calls
+                .checked_add(1)
+                .ok_or(AgentBlinkPayError::MathOverflow)?;
+            usage.volume = usage
+                .volume
+                .checked_add(amount)
+                .ok_or(AgentBlinkPayError::MathOverflow)?;
+            if usage.first_call_slot == 0 {
+                usage.first_call_slot = current_slot;
+            }
+        }
+
+        // Mark as used once every call and all of the amount are consumed
+        let tip = auth.tip_in(auth.consumed(), amount);
+        auth.last_recorded_slot = current_slot;
+        auth.external_id = external_id;
+        auth.amount_remaining -= amount;
+        auth.calls_remaining = auth.calls_remaining.saturating_sub(1);
+        auth.used = auth.amount_remaining == 0 && auth.calls_remaining == 0;
+        let (referrer_amount, primary_amount, split_amounts) = meter.split_amounts(amount);
+        let current_price = meter.effective_price(current_slot);
+
+        // Escrowed authorizations pay the merchant on-chain
+        let settled_on_chain = auth.escrow != Pubkey::default();
+        if settled_on_chain {
+            let (escrow, merchant_token_account, token_program) = match (
+                &ctx.accounts.escrow,
+                &ctx.accounts.merchant_token_account,
+                &ctx.accounts.token_program,
+            ) {
+                (Some(escrow), Some(merchant), Some(token_program)) => (escrow, merchant, token_program),
+                _ => return err!(AgentBlinkPayError::EscrowAccountsMissing),
+            };
+            // The last call also sweeps anything else sent to the escrow
+            let release = if auth.used { escrow.amount } else { amount };
+            transfer_from_escrow(auth, escrow, merchant_token_account, token_program, release, auth.used)?;
+            auth.settled_from_escrow = true;
+            if auth.used {
+                auth.escrow = Pubkey::default();
+            }
+        }
+
+        // Optional proof of purchase for other programs, outliving the authorization
+        if create_receipt {
+            let (receipt_info, payer, system_program) = match (
+                &ctx.accounts.receipt,
+                &ctx.accounts.payer,
+                &ctx.accounts.system_program,
+            ) {
+                (Some(receipt), Some(payer), Some(system_program)) => (
+                    receipt.to_account_info(),
+                    payer.to_account_info(),
+                    system_program.to_account_info(),
+                ),
+                _ => return err!(AgentBlinkPayError::ReceiptAccountsMissing),
+            };
+            let (meter_key, agent_key, nonce_bytes) = (meter.key(), auth.agent, nonce.to_le_bytes());
+            let (expected_receipt, bump) = Pubkey::find_program_address(
+                &[b"receipt", meter_key.as_ref(), agent_key.as_ref(), &nonce_bytes],
+                ctx.program_id,
+            );
+            require_keys_eq!(
+                *receipt_info.key,
+                expected_receipt,
+                AgentBlinkPayError::InvalidReceiptAccount
+            );
+
+            let mut receipt = if receipt_info.data_is_empty() {
+                create_pda_account(
+                    &payer,
+                    &receipt_info,
+                    Receipt::LEN,
+                    &[b"receipt", meter_key.as_ref(), agent_key.as_ref(), &nonce_bytes, &[bump]],
+                    &system_program,
+                )?;
+                Receipt {
+                    request_id: auth.request_id,
+                    rent_payer: payer.key(),
+                    bump,
+                    ..Default::default()
+                }
+            } else {
+                require_keys_eq!(
+                    *receipt_info.owner,
+                    crate::ID,
+                    AgentBlinkPayError::InvalidReceiptAccount
+                );
+                Receipt::try_deserialize(&mut &receipt_info.try_borrow_data()?[..])?
+            };
+            if !settlement_retry {
+                receipt.amount = receipt
+                    .amount
+                    .checked_add(amount)
+                    .ok_or(AgentBlinkPayError::MathOverflow)?;
+            }
+            receipt.slot = current_slot;
+
+            let mut data = receipt_info.try_borrow_mut_data()?;
+            let mut writer: &mut [u8] = &mut data[..];
+            receipt.try_serialize(&mut writer)?;
+        }
+        
+        // Emit the payment event
+        // Off-chain services (Circle integration) listen for this event
+        // to trigger the actual USDC transfer, unless it was settled on-chain
+        emit!(MeterPaid {
+            agent: auth.agent,
+            meter: auth.meter,
+            recorded_by: ctx.accounts.recorder.key(),
+            amount,
+            mint: auth.mint,
+            category: auth.category,
+            nonce: nonce,
+            policy_id: auth.policy_id,
+            meter_total_calls: counters.total_calls,
+            meter_total_volume: counters.total_volume,
+            free_call: amount == 0,
+            referrer_amount,
+            primary_amount,
+            split_amounts,
+            agent_calls: usage.calls,
+            agent_volume: usage.volume,
+            agent_first_call_slot: usage.first_call_slot,
+            amount_remaining: auth.amount_remaining,
+            authorized_amount: auth.amount,
+            price_at_authorization: auth.price_at_authorization,
+            current_price,
+            price_changed: current_price != auth.price_at_authorization,
+            request_id: auth.request_id,
+            settled_on_chain,
+            recorded_in_grace,
+            settlement_retry,
+            units: 0,
+            tip,
+            verification_method: auth.verification_method,
+            verifier_ref: auth.verifier_ref,
+            slot: current_slot,
+        });
+        
+        msg!("Payment recorded: agent={:?}, meter={:?}, amount={}, nonce={}, calls_remaining={}",
+             auth.agent, auth.meter, amount, nonce, auth.calls_remaining);
+
+        Ok(())
+    }
+
+    /// Records a usage-based payment: `units` (tokens, bytes, ...) times the
+    /// meter's per-call price, for APIs whose cost is only known after the
+    /// call. The authorization's `amount` is the cap.
+    /// 
+    /// Signed by the meter's authority, the source of truth for usage. The
+    /// price is the one the authorization was issued at, so a reprice
+    /// between authorize and record can't raise the charge. Only single-call,
+    /// untipped, non-escrowed standard authorizations without a hash lock
+    /// qualify; a streaming one fails with `WrongAuthorizationKind`. The
+    /// authorization is marked used; the uncharged part of the cap is
+    /// released. Otherwise checked and booked like `record_meter_payment`,
+    /// and `MeterPaid` carries `units` alongside the charged `amount`.
+    /// 
+    /// # Arguments
+    /// * `nonce` - The nonce of the authorization to consume
+    /// * `units` - Units consumed by the call (at least 1)
+    pub fn record_metered_usage(
+        ctx: Context<RecordMeteredUsage>,
+        nonce: u64,
+        units: u64,
+    ) -> Result<()> {
+        let auth = &mut ctx.accounts.authorization;
+        let clock = Clock::get()?;
+        let current_slot = clock.slot;
+
+        require!(!auth.revoked, AgentBlinkPayError::AuthorizationRevoked);
+        require!(!auth.used, AgentBlinkPayError::AuthorizationUsed);
+        require!(
+            auth.consumer == Pubkey::default() || auth.consumer == ctx.accounts.authority.key(),
+            AgentBlinkPayError::UnauthorizedConsumer
+        );
+        require!(auth.hash_lock == [0; 32], AgentBlinkPayError::InvalidPreimage);
+        require!(auth.escrow == Pubkey::default(), AgentBlinkPayError::EscrowNotSupported);
+        auth.require_kind(&[authorization_kinds::STANDARD])?;
+        require!(
+            auth.calls_remaining == 1 && auth.tip == 0,
+            AgentBlinkPayError::NotMeteredUsageAuthorization
+        );
+        require!(
+            !auth.is_expired_after(&clock, ctx.accounts.config.grace_slots),
+            AgentBlinkPayError::AuthorizationExpired
+        );
+        let recorded_in_grace = auth.is_expired(&clock);
+
+        let policy = &mut ctx.accounts.agent_policy;
+        require!(!policy.frozen, AgentBlinkPayError::PolicyFrozen);
+        require!(!policy.is_paused(current_slot), AgentBlinkPayError::PolicyPaused);
+        require!(
+            auth.policy_hash_at_auth == [0; 32] || auth.policy_hash_at_auth == policy.policy_hash,
+            AgentBlinkPayError::PolicyChangedSinceAuthorization
+        );
+
+        let meter = &ctx.accounts.meter;
+        require!(
+            meter.active || auth.expires_at_slot >= meter.paused_at_slot,
+            AgentBlinkPayError::MeterInactive
+        );
+
+        require!(units > 0, AgentBlinkPayError::InvalidUnits);
+        let amount = units
+            .checked_mul(auth.price_at_authorization)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        require!(
+            amount <= auth.amount_remaining,
+            AgentBlinkPayError::UsageExceedsAuthorization
+        );
+
+        // As in record_meter_payment, a settlement retry was already booked
+        let settlement_retry = auth.settlement_failed;
+        let mut counters = ctx.accounts.meter_counters.load_mut()?;
+        let usage = &mut ctx.accounts.meter_usage;
+        if !settlement_retry {
+            counters.record_call(meter, amount, current_slot)?;
+            policy.charge(amount, current_slot)?;
+            usage.calls = usage
+                .calls
+                .checked_add(1)
+                .ok_or(AgentBlinkPayError::MathOverflow)?;
+            usage.volume = usage
+                .volume
+                .checked_add(amount)
+                .ok_or(AgentBlinkPayError::MathOverflow)?;
+            if usage.first_call_slot == 0 {
+                usage.first_call_slot = current_slot;
+            }
+        }
+
+        auth.last_recorded_slot = current_slot;
+        auth.amount_remaining = 0;
+        auth.calls_remaining = 0;
+        auth.used = true;
+        let (referrer_amount, primary_amount, split_amounts) = meter.split_amounts(amount);
+        let current_price = meter.effective_price(current_slot);
+
+        emit!(MeterPaid {
+            agent: auth.agent,
+            meter: auth.meter,
+            recorded_by: ctx.accounts.authority.key(),
+            amount,
+            mint: auth.mint,
+            category: auth.category,
+            nonce,
+            policy_id: auth.policy_id,
+            meter_total_calls: counters.total_calls,
+            meter_total_volume: counters.total_volume,
+            free_call: amount == 0,
+            referrer_amount,
+            primary_amount,
+            split_amounts,
+            agent_calls: usage.calls,
+            agent_volume: usage.volume,
+            agent_first_call_slot: usage.first_call_slot,
+            amount_remaining: 0,
+            authorized_amount: auth.amount,
+            price_at_authorization: auth.price_at_authorization,
+            current_price,
+            price_changed: current_price != auth.price_at_authorization,
+            request_id: auth.request_id,
+            settled_on_chain: false,
+            recorded_in_grace,
+            settlement_retry,
+            units,
+            tip: 0,
+            verification_method: auth.verification_method,
+            verifier_ref: auth.verifier_ref,
+            slot: current_slot,
+        });
+
+        msg!("Metered usage recorded: agent={:?}, meter={:?}, units={}, amount={}, nonce={}",
+             auth.agent, auth.meter, units, amount, nonce);
+
+        Ok(())
+    }
+
+    /// Closes a receipt the agent no longer needs, returning its rent to the
+    /// account that funded it. Signed by the agent.
+    /// 
+    /// # Arguments
+    /// * `nonce` - The nonce of the authorization the receipt is for
+    pub fn close_receipt(ctx: Context<CloseReceipt>, nonce: u64) -> Result<()> {
+        msg!("Receipt closed: agent={:?}, meter={:?}, nonce={}",
+             ctx.accounts.agent.key(), ctx.accounts.meter.key(), nonce);
+
+        Ok(())
+    }
+
+    /// Cancels an authorization before it is (fully) consumed. Signed by the
+    /// agent or its policy owner.
+    /// 
+    /// The authorization is kept, marked `revoked`, so a later
+    /// `record_meter_payment` fails with `AuthorizationRevoked` rather than
+    /// `AuthorizationUsed` and merchants can tell a cancellation from a
+    /// replay. Its rent is reclaimed with `close_authorization`.
+    /// 
+    /// # Arguments
+    /// * `nonce` - The nonce of the authorization to revoke
+    pub fn revoke_authorization(ctx: Context<RevokeAuthorization>, nonce: u64) -> Result<()> {
+        let auth = &mut ctx.accounts.authorization;
+        require!(!auth.revoked, AgentBlinkPayError::AuthorizationRevoked);
+        require!(!auth.used, AgentBlinkPayError::AuthorizationUsed);
+
+        auth.revoked = true;
+
+        emit!(AuthorizationRevoked {
+            agent: auth.agent,
+            meter: auth.meter,
+            nonce,
+            calls_remaining: auth.calls_remaining,
+            revoked_by: ctx.accounts.authority.key(),
+            slot: Clock::get()?.slot,
+        });
+
+        msg!("Authorization revoked: agent={:?}, meter={:?}, nonce={}",
+             auth.agent, auth.meter, nonce);
+
+        Ok(())
+    }
+
+    /// Closes an authorization that can no longer be consumed, returning its
+    /// rent to the account that paid for it.
+    /// 
+    /// Permissionless: the lamports always go to the recorded `rent_payer`,
+    /// so anyone may crank it. Only used, revoked or expired authorizations
+    /// can be closed; a live one fails with `AuthorizationStillLive`, and one
+    /// still holding escrow with `EscrowStillFunded` until
+    /// `cancel_escrowed_authorization` has returned it. A recorded one also
+    /// stays until the Config's dispute and refund windows have passed.
+    pub fn close_authorization(ctx: Context<CloseAuthorization>) -> Result<()> {
+        let auth = &ctx.accounts.authorization;
+        let clock = Clock::get()?;
+        let current_slot = clock.slot;
+
+        require!(auth.escrow == Pubkey::default(), AgentBlinkPayError::EscrowStillFunded);
+        let reason = auth
+            .close_reason(&clock, &ctx.accounts.config)
+            .ok_or(AgentBlinkPayError::AuthorizationStillLive)?;
+
+        emit!(AuthorizationClosed {
+            agent: auth.agent,
+            meter: auth.meter,
+            nonce: auth.nonce,
+            reason,
+            slot: current_slot,
+        });
+
+        msg!("Authorization closed: agent={:?}, meter={:?}, nonce={}, reason={}",
+             auth.agent, auth.meter, auth.nonce, reason);
+
+        Ok(())
+    }
+
+    /// Closes a proof's `Nullifier`, returning its rent to the account that
+    /// paid for it.
+    /// 
+    /// Permissionless like `close_authorization`. The nullifier's
+    /// authorization (an `Authorization`, `BudgetAuthorization` or
+    /// `BundleAuthorization`) must be terminal: already closed, or closable
+    /// by its close instruction. Otherwise fails with `AuthorizationStillLive`.
+    pub fn close_nullifier(ctx: Context<CloseNullifier>) -> Result<()> {
+        let auth_info = ctx.accounts.authorization.to_account_info();
+        if auth_info.owner == &crate::ID {
+            let data = auth_info.try_borrow_data()?;
+            let clock = Clock::get()?;
+            let config = &ctx.accounts.config;
+            let close_reason = if data.starts_with(&BudgetAuthorization::DISCRIMINATOR) {
+                BudgetAuthorization::try_deserialize(&mut &data[..])?
+                    .close_reason(clock.slot, config)
+            } else if data.starts_with(&BundleAuthorization::DISCRIMINATOR) {
+                BundleAuthorization::try_deserialize(&mut &data[..])?
+                    .close_reason(clock.slot, config)
+            } else {
+                Authorization::try_deserialize(&mut &data[..])?.close_reason(&clock, config)
+            };
+            require!(close_reason.is_some(), AgentBlinkPayError::AuthorizationStillLive);
+        }
+
+        msg!("Nullifier closed: agent={:?}, authorization={:?}",
+             ctx.accounts.nullifier.agent, ctx.accounts.nullifier.authorization);
+
+        Ok(())
+    }
+
+    /// Closes an expired `PendingVerification` that was never finalized,
+    /// returning its rent to the account that paid for it.
+    /// 
+    /// Permissionless like `close_authorization`. Fails with
+    /// `PendingVerificationLive` until PENDING_VERIFICATION_TTL_SLOTS have
+    /// passed since its stage 0.
+    pub fn close_pending_verification(ctx: Context<ClosePendingVerification>) -> Result<()> {
+        let pending = &ctx.accounts.pending_verification;
+        require!(
+            pending.is_expired(Clock::get()?.slot),
+            AgentBlinkPayError::PendingVerificationLive
+        );
+
+        msg!("Pending verification closed: agent={:?}, meter={:?}, nonce={}",
+             pending.agent, pending.meter, pending.nonce);
+
+        Ok(())
+    }
+
+    /// Closes an expired `ProofReceipt` that was never consumed, returning
+    /// its rent to the account that paid for it.
+    /// 
+    /// Permissionless like `close_pending_verification`. Fails with
+    /// `ProofReceiptLive` until PROOF_RECEIPT_TTL_SLOTS have passed since
+    /// it was submitted.
+    pub fn close_proof_receipt(ctx: Context<CloseProofReceipt>) -> Result<()> {
+        let receipt = &ctx.accounts.proof_receipt;
+        require!(
+            receipt.is_expired(Clock::get()?.slot),
+            AgentBlinkPayError::ProofReceiptLive
+        );
+
+        msg!("Proof receipt closed: agent={:?}", receipt.agent);
+
+        Ok(())
+    }
+
+    /// Closes many dead authorizations at once, returning each one's rent to
+    /// its recorded `rent_payer`.
+    /// 
+    /// `remaining_accounts` holds up to `MAX_SWEEP_AUTHORIZATIONS` writable
+    /// (authorization, rent_payer) pairs. Entries that don't qualify are
+    /// skipped rather than failing the sweep: accounts that aren't
+    /// Authorizations of this program, live authorizations (see
+    /// `close_authorization`) and mismatched or read-only rent payers.
+    /// Permissionless; emits a single `AuthorizationsSwept`.
+    pub fn sweep_expired_authorizations<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SweepAuthorizations<'info>>,
+    ) -> Result<()> {
+        let entries = ctx.remaining_accounts;
+        require!(
+            !entries.is_empty() && entries.len() % 2 == 0,
+            AgentBlinkPayError::BatchAccountsMismatch
+        );
+        require!(
+            entries.len() / 2 <= MAX_SWEEP_AUTHORIZATIONS,
+            AgentBlinkPayError::BatchTooLarge
+        );
+        let clock = Clock::get()?;
+        let config = &ctx.accounts.config;
+
+        let mut count: u16 = 0;
+        for pair in entries.chunks(2) {
+            let (auth_info, rent_payer) = (&pair[0], &pair[1]);
+            if !auth_info.is_writable || !rent_payer.is_writable {
+                continue;
+            }
+            // Checks the owner and discriminator; a closed or foreign
+            // account fails here
+            let auth = match Account::<Authorization>::try_from(auth_info) {
+                Ok(auth) => auth,
+                Err(_) => continue,
+            };
+            if auth.rent_payer != *rent_payer.key || auth.close_reason(&clock, config).is_none() {
+                continue;
+            }
+
+            close_account(auth_info, rent_payer)?;
+            count += 1;
+        }
+
+        emit!(AuthorizationsSwept {
+            count,
+            slot: clock.slot,
+        });
+
+        msg!("Authorizations swept: {} of {}", count, entries.len() / 2);
+
+        Ok(())
+    }
+
+    /// Creates an agent's vault for `mint`: a token account at
+    /// ["vault", agent, mint] that is its own authority, so only this
+    /// program moves funds out of it.
+    /// 
+    /// The agent funds it with a plain token transfer. Escrow-mode meters
+    /// draw each authorization's amount from it.
+    pub fn open_agent_vault(ctx: Context<OpenAgentVault>) -> Result<()> {
+        emit!(AgentVaultOpened {
+            agent: ctx.accounts.agent.key(),
+            mint: ctx.accounts.mint.key(),
+            vault: ctx.accounts.vault.key(),
+            slot: Clock::get()?.slot,
+        });
+
+        msg!("Agent vault opened: agent={:?}, mint={:?}",
+             ctx.accounts.agent.key(), ctx.accounts.mint.key());
+
+        Ok(())
+    }
+
+    /// Moves funds out of the agent's vault. Signed by the agent.
+    /// 
+    /// Only the vault's free balance can be withdrawn: escrowed amounts
+    /// already sit in their authorizations' escrow accounts.
+    /// 
+    /// # Arguments
+    /// * `amount` - Amount to withdraw (smallest units of the vault's mint)
+    pub fn withdraw_from_vault(ctx: Context<WithdrawFromVault>, amount: u64) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        require!(amount <= vault.amount, AgentBlinkPayError::VaultInsufficientFunds);
+
+        let agent = ctx.accounts.agent.key();
+        let bump = [ctx.bumps.vault];
+        let seeds: &[&[u8]] = &[b"vault", agent.as_ref(), vault.mint.as_ref(), &bump];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: vault.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: vault.to_account_info(),
+                },
+                &[seeds],
+            ),
+            amount,
+        )?;
+
+        emit!(VaultWithdrawn {
+            agent,
+            mint: vault.mint,
+            amount,
+            slot: Clock::get()?.slot,
+        });
+
+        msg!("Vault withdrawal: agent={:?}, amount={}", agent, amount);
+
+        Ok(())
+    }
+
+    /// Returns an expired or revoked authorization's escrow to the agent's
+    /// vault and closes the escrow account.
+    /// 
+    /// Permissionless: the funds can only go back to the vault they came
+    /// from. The escrow's rent goes to the authorization, and from there to
+    /// its `rent_payer` on close_authorization.
+    pub fn cancel_escrowed_authorization(ctx: Context<CancelEscrowedAuthorization>) -> Result<()> {
+        let auth = &mut ctx.accounts.authorization;
+        let clock = Clock::get()?;
+
+        require!(auth.escrow != Pubkey::default(), AgentBlinkPayError::NotEscrowed);
+        require!(
+            auth.revoked || auth.is_expired_after(&clock, ctx.accounts.config.grace_slots),
+            AgentBlinkPayError::AuthorizationStillLive
+        );
+
+        let refunded = ctx.accounts.escrow.amount;
+        transfer_from_escrow(
+            auth,
+            &ctx.accounts.escrow,
+            &ctx.accounts.vault,
+            &ctx.accounts.token_program,
+            refunded,
+            true,
+        )?;
+        auth.escrow = Pubkey::default();
+
+        emit!(EscrowRefunded {
+            authorization: auth.key(),
+            agent: auth.agent,
+            meter: auth.meter,
+            nonce: auth.nonce,
+            amount: refunded,
+            slot: clock.slot,
+        });
+
+        msg!("Escrow refunded: agent={:?}, meter={:?}, nonce={}, amount={}",
+             auth.agent, auth.meter, auth.nonce, refunded);
+
+        Ok(())
+    }
+
+    /// Records that the Circle service has moved the funds for a used
+    /// authorization. Signed by the Config's `settlement_authority`.
+    /// 
+    /// # Arguments
+    /// * `nonce` - The nonce of the settled authorization
+    /// * `settlement_ref` - The Circle transfer id (or a hash of it)
+    pub fn confirm_settlement(
+        ctx: Context<ReportSettlement>,
+        nonce: u64,
+        settlement_ref: [u8; 32],
+    ) -> Result<()> {
+        let auth = &mut ctx.accounts.authorization;
+        require!(auth.used, AgentBlinkPayError::AuthorizationNotUsed);
+        require!(!auth.settled, AgentBlinkPayError::SettlementAlreadyConfirmed);
+
+        auth.settled = true;
+        auth.settlement_ref = settlement_ref;
+
+        emit!(SettlementConfirmed {
+            authorization: auth.key(),
+            agent: auth.agent,
+            meter: auth.meter,
+            nonce,
+            amount: auth.amount,
+            settlement_ref,
+            retried: auth.settlement_failed,
+            slot: Clock::get()?.slot,
+        });
+
+        msg!("Settlement confirmed: agent={:?}, meter={:?}, nonce={}",
+             auth.agent, auth.meter, nonce);
+
+        Ok(())
+    }
+
+    /// Reports that the Circle transfer for a used authorization failed,
+    /// re-opening it so it can be recorded once more. Signed by the Config's
+    /// `settlement_authority`.
+    /// 
+    /// The whole amount becomes recordable again as a single call, until
+    /// `SETTLEMENT_RETRY_WINDOW_SLOTS` from now. The retry re-emits
+    /// `MeterPaid` without charging the policy a second time. Only one
+    /// failure may be reported per authorization, and none for one paid
+    /// out on-chain from escrow (`SettledOnChain`).
+    /// 
+    /// # Arguments
+    /// * `nonce` - The nonce of the authorization whose settlement failed
+    /// * `reason_code` - Settlement service's failure code, passed through to the event
+    pub fn report_settlement_failure(
+        ctx: Context<ReportSettlement>,
+        nonce: u64,
+        reason_code: u8,
+    ) -> Result<()> {
+        let auth = &mut ctx.accounts.authorization;
+        let current_slot = Clock::get()?.slot;
+        require!(auth.used, AgentBlinkPayError::AuthorizationNotUsed);
+        require!(!auth.settled, AgentBlinkPayError::SettlementAlreadyConfirmed);
+        require!(!auth.settlement_failed, AgentBlinkPayError::SettlementRetryUsed);
+        require!(!auth.settled_from_escrow, AgentBlinkPayError::SettledOnChain);
+
+        auth.settlement_failed = true;
+        auth.used = false;
+        // The retry is a new record, not a replay of the last one
+        auth.external_id = [0; 16];
+        auth.amount_remaining = auth.amount;
+        auth.calls_remaining = 1;
+        auth.expiry_kind = expiry_kinds::SLOT;
+        auth.expires_at_slot = current_slot.saturating_add(SETTLEMENT_RETRY_WINDOW_SLOTS);
+
+        emit!(SettlementFailed {
+            authorization: auth.key(),
+            agent: auth.agent,
+            meter: auth.meter,
+            nonce,
+            reason_code,
+            retry_expires_at_slot: auth.expires_at_slot,
+            slot: current_slot,
+        });
+
+        msg!("Settlement failed: agent={:?}, meter={:?}, nonce={}, reason={}",
+             auth.agent, auth.meter, nonce, reason_code);
+
+        Ok(())
+    }
+
+    /// Disputes a recorded payment, e.g. a call that was charged but not
+    /// served. Signed by the agent or the meter's authority, who pays the
+    /// `Dispute` account's rent.
+    /// 
+    /// Must be opened within the Config's `dispute_window_slots` of the
+    /// authorization's last record. Marks the authorization `disputed` so
+    /// settlement services hold the payout until `resolve_dispute`.
+    /// 
+    /// # Arguments
+    /// * `nonce` - The nonce of the disputed authorization
+    /// * `reason_code` - Caller-defined reason, passed through to the event
+    pub fn open_dispute(ctx: Context<OpenDispute>, nonce: u64, reason_code: u8) -> Result<()> {
+        let auth = &mut ctx.accounts.authorization;
+        let opener = ctx.accounts.opener.key();
+        let current_slot = Clock::get()?.slot;
+
+        require!(
+            opener == auth.agent || opener == ctx.accounts.meter.authority,
+            AgentBlinkPayError::Unauthorized
+        );
+        require!(auth.last_recorded_slot != 0, AgentBlinkPayError::NothingRecorded);
+        let window = ctx.accounts.config.dispute_window_slots;
+        require!(
+            window > 0 && current_slot <= auth.last_recorded_slot.saturating_add(window),
+            AgentBlinkPayError::DisputeWindowClosed
+        );
+
+        auth.disputed = true;
+
+        let dispute = &mut ctx.accounts.dispute;
+        dispute.authorization = auth.key();
+        dispute.opened_by = opener;
+        dispute.reason_code = reason_code;
+        dispute.opened_at_slot = current_slot;
+        dispute.bump = ctx.bumps.dispute;
+
+        emit!(DisputeOpened {
+            dispute: dispute.key(),
+            authorization: auth.key(),
+            agent: auth.agent,
+            meter: auth.meter,
+            nonce,
+            opened_by: opener,
+            reason_code,
+            slot: current_slot,
+        });
+
+        msg!("Dispute opened: agent={:?}, meter={:?}, nonce={}, by={:?}",
+             auth.agent, auth.meter, nonce, opener);
+
+        Ok(())
+    }
+
+    /// Rules on an open dispute and closes it, returning its rent to the
+    /// party that opened it. Signed by the config admin.
+    /// 
+    /// Clears the authorization's `disputed` flag; settlement services act
+    /// on the outcome reported by `DisputeResolved`.
+    /// 
+    /// # Arguments
+    /// * `outcome` - One of the `dispute_outcomes` constants
+    pub fn resolve_dispute(ctx: Context<ResolveDispute>, outcome: u8) -> Result<()> {
+        require!(
+            outcome <= dispute_outcomes::REFUNDED,
+            AgentBlinkPayError::InvalidDisputeOutcome
+        );
+
+        let auth = &mut ctx.accounts.authorization;
+        auth.disputed = false;
+
+        emit!(DisputeResolved {
+            dispute: ctx.accounts.dispute.key(),
+            authorization: auth.key(),
+            agent: auth.agent,
+            meter: auth.meter,
+            nonce: auth.nonce,
+            outcome,
+            resolved_by: ctx.accounts.admin.key(),
+            slot: Clock::get()?.slot,
+        });
+
+        msg!("Dispute resolved: agent={:?}, meter={:?}, nonce={}, outcome={}",
+             auth.agent, auth.meter, auth.nonce, outcome);
+
+        Ok(())
+    }
+
+    /// Refunds part or all of a used authorization to the agent. Signed by
+    /// the meter's authority.
+    /// 
+    /// Only emits `RefundIssued`, which the Circle service executes as a
+    /// transfer back from the merchant's wallet; the policy's budgets are
+    /// not credited back. Refunds accumulate in `refunded_amount` and can't
+    /// exceed the authorization's amount. Must be issued within the
+    /// Config's `refund_window_slots` of the last record, and not while the
+    /// payment is disputed.
+    /// 
+    /// # Arguments
+    /// * `nonce` - The nonce of the refunded authorization
+    /// * `amount` - Amount to refund (smallest units of the authorization's mint)
+    pub fn issue_refund(ctx: Context<IssueRefund>, nonce: u64, amount: u64) -> Result<()> {
+        let auth = &mut ctx.accounts.authorization;
+        let meter = &ctx.accounts.meter;
+        let current_slot = Clock::get()?.slot;
+
+        require!(auth.used, AgentBlinkPayError::AuthorizationNotUsed);
+        require!(!auth.disputed, AgentBlinkPayError::PaymentDisputed);
+        let window = ctx.accounts.config.refund_window_slots;
+        require!(
+            window > 0 && current_slot <= auth.last_recorded_slot.saturating_add(window),
+            AgentBlinkPayError::RefundWindowClosed
+        );
+        let refunded_amount = auth
+            .refunded_amount
+            .checked_add(amount)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        require!(
+            amount > 0 && refunded_amount <= auth.amount,
+            AgentBlinkPayError::RefundExceedsAmount
+        );
+
+        auth.refunded_amount = refunded_amount;
+
+        emit!(RefundIssued {
+            authorization: auth.key(),
+            agent: auth.agent,
+            meter: auth.meter,
+            nonce,
+            amount,
+            refunded_amount,
+            mint: auth.mint,
+            merchant_wallet_id: meter.merchant_wallet_id_str().to_string(),
+            slot: current_slot,
+        });
+
+        msg!("Refund issued: agent={:?}, meter={:?}, nonce={}, amount={}, total={}",
+             auth.agent, auth.meter, nonce, amount, refunded_amount);
+
+        Ok(())
+    }
+
+    /// Pays a subscription meter's fee, starting or extending the agent's
+    /// access by one period.
+    /// 
+    /// Renewing before expiry extends from the current `expires_at_slot`,
+    /// so no paid time is lost. The fee is charged against the policy like a
+    /// per-call payment and emitted as a `MeterPaid` (nonce 0) so the Circle
+    /// service settles it. There is no proof on this path, so meters or
+    /// policies that require ZK are rejected.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies pays the fee
+    pub fn subscribe(ctx: Context<Subscribe>, policy_id: u16) -> Result<()> {
+        let policy = &mut ctx.accounts.agent_policy;
+        let meter = &ctx.accounts.meter;
+        let current_slot = Clock::get()?.slot;
+
+        let (period_slots, fee) = match meter.kind {
+            MeterKind::Subscription { period_slots, fee } => (period_slots, fee),
+            MeterKind::PerCall => return err!(AgentBlinkPayError::WrongMeterKind),
+        };
+
+        require!(!policy.frozen, AgentBlinkPayError::PolicyFrozen);
+        require!(!policy.is_paused(current_slot), AgentBlinkPayError::PolicyPaused);
+        require!(meter.active, AgentBlinkPayError::MeterInactive);
+        require!(
+            Category::same(meter.category, policy.allowed_category)?,
+            AgentBlinkPayError::CategoryMismatch
+        );
+        require!(
+            policy.accepts_mint(&meter.accepted_mint),
+            AgentBlinkPayError::MintMismatch
+        );
+        require!(fee <= policy.max_per_tx, AgentBlinkPayError::AmountExceedsMax);
+        require!(!policy.requires_proof(), AgentBlinkPayError::ZkRequiredByPolicy);
+        require!(!meter.requires_zk, AgentBlinkPayError::ZkRequiredByMeter);
+        require!(
+            !policy.enforce_meter_allowlist || ctx.accounts.allowed_meter.is_some(),
+            AgentBlinkPayError::MeterNotAllowed
+        );
+        require!(
+            !meter.allowlist_enabled || ctx.accounts.meter_access.is_some(),
+            AgentBlinkPayError::AgentNotAllowedByMeter
+        );
+        require!(
+            ctx.accounts.denied_meter.owner != &crate::ID,
+            AgentBlinkPayError::MeterDenied
+        );
+
+        policy.charge(fee, current_slot)?;
+
+        let mut counters = ctx.accounts.meter_counters.load_mut()?;
+        counters.total_volume = counters
+            .total_volume
+            .checked_add(fee)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+
+        let sub = &mut ctx.accounts.subscription;
+        if sub.meter == Pubkey::default() {
+            // Freshly created by init_if_needed
+            sub.meter = meter.key();
+            sub.agent = ctx.accounts.agent.key();
+            sub.bump = ctx.bumps.subscription;
+        }
+        sub.policy_id = policy_id;
+        sub.expires_at_slot = sub
+            .expires_at_slot
+            .max(current_slot)
+            .checked_add(period_slots)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+
+        let (referrer_amount, primary_amount, split_amounts) = meter.split_amounts(fee);
+        emit!(MeterPaid {
+            agent: sub.agent,
+            meter: sub.meter,
+            recorded_by: ctx.accounts.agent.key(),
+            amount: fee,
+            mint: meter.accepted_mint,
+            category: meter.category,
+            nonce: 0,
+            policy_id,
+            meter_total_calls: counters.total_calls,
+            meter_total_volume: counters.total_volume,
+            free_call: false,
+            referrer_amount,
+            primary_amount,
+            split_amounts,
+            agent_calls: 0,
+            agent_volume: 0,
+            agent_first_call_slot: 0,
+            amount_remaining: 0,
+            authorized_amount: fee,
+            price_at_authorization: 0,
+            current_price: 0,
+            price_changed: false,
+            request_id: [0; 32],
+            settled_on_chain: false,
+            recorded_in_grace: false,
+            settlement_retry: false,
+            units: 0,
+            tip: 0,
+            verification_method: verification_methods::NONE,
+            verifier_ref: Pubkey::default(),
+            slot: current_slot,
+        });
+
+        emit!(Subscribed {
+            agent: sub.agent,
+            meter: sub.meter,
+            policy_id,
+            fee,
+            expires_at_slot: sub.expires_at_slot,
+            slot: current_slot,
+        });
+
+        msg!("Subscribed: agent={:?}, meter={:?}, expires_at_slot={}",
+             sub.agent, sub.meter, sub.expires_at_slot);
+
+        Ok(())
+    }
+
+    /// Records one call to a subscription meter.
+    /// 
+    /// No authorization is needed: the call is covered as long as the
+    /// agent's subscription hasn't expired. Emits a zero-amount `MeterPaid`
+    /// (nonce 0) so usage shows up alongside per-call payments. A paused
+    /// meter still serves subscriptions that were live when it was paused,
+    /// like authorizations in `record_meter_payment`.
+    pub fn record_subscription_call(ctx: Context<RecordSubscriptionCall>) -> Result<()> {
+        let sub = &ctx.accounts.subscription;
+        let current_slot = Clock::get()?.slot;
+
+        require!(
+            current_slot <= sub.expires_at_slot,
+            AgentBlinkPayError::SubscriptionExpired
+        );
+
+        let meter = &ctx.accounts.meter;
+        require!(
+            meter.active || sub.expires_at_slot >= meter.paused_at_slot,
+            AgentBlinkPayError::MeterInactive
+        );
+        let mut counters = ctx.accounts.meter_counters.load_mut()?;
+        counters.record_call(meter, 0, current_slot)?;
+
+        emit!(MeterPaid {
+            agent: sub.agent,
+            meter: sub.meter,
+            recorded_by: ctx.accounts.agent.key(),
+            amount: 0,
+            mint: meter.accepted_mint,
+            category: meter.category,
+            nonce: 0,
+            policy_id: sub.policy_id,
+            meter_total_calls: counters.total_calls,
+            meter_total_volume: counters.total_volume,
+            free_call: false,
+            referrer_amount: 0,
+            primary_amount: 0,
+            split_amounts: [0; 3],
+            agent_calls: 0,
+            agent_volume: 0,
+            agent_first_call_slot: 0,
+            amount_remaining: 0,
+            authorized_amount: 0,
+            price_at_authorization: 0,
+            current_price: 0,
+            price_changed: false,
+            request_id: [0; 32],
+            settled_on_chain: false,
+            recorded_in_grace: false,
+            settlement_retry: false,
+            units: 0,
+            tip: 0,
+            verification_method: verification_methods::NONE,
+            verifier_ref: Pubkey::default(),
+            slot: current_slot,
+        });
+
+        msg!("Subscription call recorded: agent={:?}, meter={:?}", sub.agent, sub.meter);
+
+        Ok(())
+    }
+    /// Buys prepaid credit on a per-call meter.
+    /// 
+    /// The purchase is checked like `authorize_payment_with_proof` (including
+    /// the ZK proof) and charged against the policy immediately. The Circle
+    /// service settles it from `CreditsPurchased`; calls are then drawn down
+    /// with `consume_credit` without a per-call authorization.
+    /// 
+    /// # Arguments
+    /// * `policy_id` - Which of the agent's policies pays for the credit
+    /// * `amount` - Credit to buy in USDC smallest units
+    /// * `proof` - ZK proof bytes
+    pub fn buy_credits(
+        ctx: Context<BuyCredits>,
+        policy_id: u16,
+        amount: u64,
+        proof: Vec<u8>,
+    ) -> Result<()> {
+        let policy = &ctx.accounts.agent_policy;
+        let meter = &ctx.accounts.meter;
+        let current_slot = Clock::get()?.slot;
+
+        require!(!policy.frozen, AgentBlinkPayError::PolicyFrozen);
+        require!(!policy.is_paused(current_slot), AgentBlinkPayError::PolicyPaused);
+        require!(meter.active, AgentBlinkPayError::MeterInactive);
+        require!(meter.kind == MeterKind::PerCall, AgentBlinkPayError::WrongMeterKind);
+        require!(
+            meter.settlement_mode == settlement_modes::EVENT_ONLY,
+            AgentBlinkPayError::EscrowNotSupported
+        );
+        require!(
+            Category::same(meter.category, policy.allowed_category)?,
+            AgentBlinkPayError::CategoryMismatch
+        );
+        require!(
+            policy.accepts_mint(&meter.accepted_mint),
+            AgentBlinkPayError::MintMismatch
+        );
+        require!(
+            !policy.enforce_meter_allowlist || ctx.accounts.allowed_meter.is_some(),
+            AgentBlinkPayError::MeterNotAllowed
+        );
+        require!(
+            !meter.allowlist_enabled || ctx.accounts.meter_access.is_some(),
+            AgentBlinkPayError::AgentNotAllowedByMeter
+        );
+        require!(
+            ctx.accounts.denied_meter.owner != &crate::ID,
+            AgentBlinkPayError::MeterDenied
+        );
+
+        meter.check_proof_version(&proof)?;
+        let verified = verify_policy_proof(
+            policy,
+            meter.proof_circuit(&proof)?,
+            amount,
+            meter.category,
+            meter.key(),
+            // Credit purchases take no nonce
+            0,
+            &proof,
+            current_slot,
+            &ctx.accounts.config,
+            ctx.accounts.verifier_program.to_account_info(),
+            CircuitKey::Groth16(&ctx.accounts.verifying_key),
+        )?;
+        if !verified {
+            check_unproven_payment(policy, meter)?;
+        }
+
+        // Budget and daily limit checks (may emit alerts and auto-freeze)
+        ctx.accounts.agent_policy.charge(amount, current_slot)?;
+
+        let credit = &mut ctx.accounts.credit;
+        if credit.meter == Pubkey::default() {
+            // Freshly created by init_if_needed
+            credit.meter = ctx.accounts.meter.key();
+            credit.agent = ctx.accounts.agent.key();
+            credit.bump = ctx.bumps.credit;
+        }
+        credit.policy_id = policy_id;
+        credit.remaining = credit
+            .remaining
+            .checked_add(amount)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+
+        emit!(CreditsPurchased {
+            agent: credit.agent,
+            meter: credit.meter,
+            policy_id,
+            amount,
+            remaining: credit.remaining,
+            slot: current_slot,
+        });
+
+        msg!("Credits purchased: agent={:?}, meter={:?}, amount={}, remaining={}",
+             credit.agent, credit.meter, amount, credit.remaining);
+
+        Ok(())
+    }
+
+    /// Draws down an agent's prepaid credit for `units` calls at the meter's
+    /// effective per-call price. Signed by the meter authority (the
+    /// merchant's facilitator).
+    /// 
+    /// # Arguments
+    /// * `units` - Number of calls served
+    pub fn consume_credit(ctx: Context<ConsumeCredit>, units: u64) -> Result<()> {
+        let current_slot = Clock::get()?.slot;
+        let amount = units
+            .checked_mul(ctx.accounts.meter.effective_price(current_slot))
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+
+        let credit = &mut ctx.accounts.credit;
+        credit.remaining = credit
+            .remaining
+            .checked_sub(amount)
+            .ok_or(AgentBlinkPayError::InsufficientCredits)?;
+
+        emit!(CreditConsumed {
+            agent: credit.agent,
+            meter: credit.meter,
+            units,
+            amount,
+            remaining: credit.remaining,
+            slot: current_slot,
+        });
+
+        Ok(())
+    }
+
+    /// Gives up an agent's unused credit so the off-chain side can return
+    /// the funds. The policy's budgets are not credited back.
+    pub fn refund_credits(ctx: Context<RefundCredits>) -> Result<()> {
+        let credit = &mut ctx.accounts.credit;
+        let amount = credit.remaining;
+        require!(amount > 0, AgentBlinkPayError::InsufficientCredits);
+        credit.remaining = 0;
+
+        emit!(CreditsRefunded {
+            agent: credit.agent,
+            meter: credit.meter,
+            policy_id: credit.policy_id,
+            amount,
+            slot: Clock::get()?.slot,
+        });
+
+        msg!("Credits refunded: agent={:?}, meter={:?}, amount={}",
+             credit.agent, credit.meter, amount);
+
+        Ok(())
+    }
+}
+
+// =============================================================================
+// ACCOUNT MIGRATION HELPERS
+// =============================================================================
+
+/// Closes a program-owned account, sending its lamports to `destination`.
+fn close_account<'info>(
+    account: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+) -> Result<()> {
+    let dest_starting_lamports = destination.lamports();
+    **destination.lamports.borrow_mut() = dest_starting_lamports
+        .checked_add(account.lamports())
+        .ok_or(AgentBlinkPayError::MathOverflow)?;
+    **account.lamports.borrow_mut() = 0;
+
+    account.assign(&system_program::ID);
+    account.realloc(0, false)?;
+
+    Ok(())
+}
+
+/// Creates a rent-exempt, program-owned PDA of `space` bytes, funded by `payer`.
+/// 
+/// Used where accounts can't be declared with `init` because they arrive via
+/// `remaining_accounts`. Mirrors Anchor's `init`, including the case where the
+/// address was pre-funded with lamports.
+fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    new_account: &AccountInfo<'info>,
+    space: usize,
+    signer_seeds: &[&[u8]],
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let required_lamports = Rent::get()?.minimum_balance(space);
+    let current_lamports = new_account.lamports();
+
+    if current_lamports == 0 {
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::CreateAccount {
+                    from: payer.clone(),
+                    to: new_account.clone(),
+                },
+                &[signer_seeds],
+            ),
+            required_lamports,
+            space as u64,
+            &crate::ID,
+        )?;
+    } else {
+        let top_up = required_lamports.saturating_sub(current_lamports);
+        if top_up > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    system_program::Transfer {
+                        from: payer.clone(),
+                        to: new_account.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        system_program::allocate(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::Allocate {
+                    account_to_allocate: new_account.clone(),
+                },
+                &[signer_seeds],
+            ),
+            space as u64,
+        )?;
+        system_program::assign(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::Assign {
+                    account_to_assign: new_account.clone(),
+                },
+                &[signer_seeds],
+            ),
+            &crate::ID,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Reallocates a program-owned account to `new_len`, topping up rent from `payer`.
+/// 
+/// The account is only checked for the expected discriminator, since an account
+/// in an older layout can't be deserialized into the current struct. The added
+/// bytes are zeroed. No-op if the account is already at least `new_len` bytes.
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    discriminator: [u8; 8],
+    new_len: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    {
+        let data = account.try_borrow_data()?;
+        require!(
+            data.len() >= 8 && data[..8] == discriminator,
+            AgentBlinkPayError::InvalidAccountData
+        );
+        if data.len() >= new_len {
+            return Ok(());
+        }
+    }
+
+    let required_lamports = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(account.lamports());
+    if required_lamports > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            required_lamports,
+        )?;
+    }
+
+    account.realloc(new_len, true)?;
+
+    Ok(())
+}
+
+// =============================================================================
+// VALIDATION HELPERS
+// =============================================================================
+
+/// Rejects Circle wallet ids the off-chain lookup can't handle: empty ids
+/// and ids with anything but printable, non-space ASCII.
+fn check_wallet_id(wallet_id: &[u8]) -> Result<()> {
+    require!(
+        !wallet_id.is_empty() && wallet_id.iter().all(u8::is_ascii_graphic),
+        AgentBlinkPayError::InvalidMerchantWalletId
+    );
+    Ok(())
+}
+
+/// Fails with `NonceAlreadyUsed` if the authorization PDA for `nonce` held
+/// an authorization before this instruction, so the agent can tell a
+/// nonce collision from other failures and retry with a fresh nonce.
+/// 
+/// `existing_agent` is the `agent` stored in the account, which is only
+/// unset when `init_if_needed` created it in this instruction.
+fn check_nonce_unused(existing_agent: &Pubkey, authorization: &Pubkey, nonce: u64) -> Result<()> {
+    if *existing_agent != Pubkey::default() {
+        msg!("Nonce {} already used by authorization {:?}", nonce, authorization);
+        return err!(AgentBlinkPayError::NonceAlreadyUsed);
+    }
+    Ok(())
+}
+
+/// Nullifier of a payment proof: the hash of the public inputs that bind
+/// it to one payment (agent, meter, nonce, amount and policy hash), under
+/// the seed prefix of the authorization it creates (`b"auth"`,
+/// `b"budget_auth"` or `b"bundle_auth"`, whose nonces are independent).
+/// Derived from the statement rather than the proof bytes, so a fresh
+/// proof of a closed authorization's payment is spent as well.
+fn payment_nullifier(
+    domain: &[u8],
+    agent: &Pubkey,
+    meter: &Pubkey,
+    nonce: u64,
+    amount: u64,
+    policy_hash: &[u8; 32],
+) -> [u8; 32] {
+    solana_program::hash::hashv(&[
+        domain,
+        agent.as_ref(),
+        meter.as_ref(),
+        &nonce.to_le_bytes(),
+        &amount.to_le_bytes(),
+        &policy_hash[..],
+    ])
+    .to_bytes()
+}
+
+/// Creates the `Nullifier` PDA for `nullifier`, so the proof can't
+/// authorize again even after its authorization is closed and the nonce
+/// reused. Fails with `ProofAlreadyUsed` if it already exists.
+/// 
+/// Created by hand rather than with `init` because authorize_payment_simple
+/// (sharing the context) takes no proof and spends no nullifier, and the
+/// batch passes one per entry.
+fn spend_nullifier<'info>(
+    nullifier_info: &AccountInfo<'info>,
+    nullifier: [u8; 32],
+    agent: Pubkey,
+    authorization: Pubkey,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let (expected, bump) =
+        Pubkey::find_program_address(&[b"nullifier", nullifier.as_ref()], &crate::ID);
+    require_keys_eq!(
+        *nullifier_info.key,
+        expected,
+        AgentBlinkPayError::InvalidNullifierAccount
+    );
+    if nullifier_info.owner == &crate::ID {
+        msg!("Proof already used: nullifier {:?}", nullifier_info.key);
+        return err!(AgentBlinkPayError::ProofAlreadyUsed);
+    }
+
+    create_pda_account(
+        payer,
+        nullifier_info,
+        Nullifier::LEN,
+        &[b"nullifier", nullifier.as_ref(), &[bump]],
+        system_program,
+    )?;
+    let record = Nullifier {
+        nullifier,
+        agent,
+        authorization,
+        rent_payer: payer.key(),
+        bump,
+    };
+    let mut data = nullifier_info.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data[..];
+    record.try_serialize(&mut writer)?;
+
+    Ok(())
+}
+
+/// Rejects a payment without a verified proof (none, attested, or let
+/// through by permissive mode) if the policy or the meter requires one.
+fn check_unproven_payment(policy: &AgentPolicy, meter: &Meter) -> Result<()> {
+    require!(!policy.requires_proof(), AgentBlinkPayError::ZkRequiredByPolicy);
+    require!(!meter.requires_zk, AgentBlinkPayError::ZkRequiredByMeter);
+    Ok(())
+}
+
+/// Checks one payment against the agent's policy and the meter: everything
+/// the authorize instructions verify besides the allowlists and the proof.
+/// A single call of `amount` 0 reserves one of the meter's free calls in
+/// `usage`.
+fn check_payment(
+    policy: &AgentPolicy,
+    meter: &Meter,
+    usage: &mut MeterUsage,
+    payment: &AuthParams,
+    quantity: u16,
+    tip: u64,
+    current_slot: u64,
+) -> Result<()> {
+    let amount = payment.amount;
+
+    require!(!policy.frozen, AgentBlinkPayError::PolicyFrozen);
+    require!(!policy.is_paused(current_slot), AgentBlinkPayError::PolicyPaused);
+    require!(
+        amount <= policy.remaining_budget(),
+        AgentBlinkPayError::BudgetExceeded
+    );
+    require!(
+        amount <= policy.remaining_today(current_slot),
+        AgentBlinkPayError::DailyLimitExceeded
+    );
+    require!(amount <= policy.max_per_tx, AgentBlinkPayError::AmountExceedsMax);
+    require!(meter.active, AgentBlinkPayError::MeterInactive);
+    require!(meter.kind == MeterKind::PerCall, AgentBlinkPayError::WrongMeterKind);
+    require!(
+        Category::same(meter.category, payment.category)?,
+        AgentBlinkPayError::CategoryMismatch
+    );
+    require!(
+        policy.accepts_mint(&meter.accepted_mint),
+        AgentBlinkPayError::MintMismatch
+    );
+    require!(quantity > 0, AgentBlinkPayError::InvalidQuantity);
+
+    if tip > 0 {
+        // A tip rides on exactly one call at its current price
+        require!(
+            quantity == 1
+                && amount.checked_sub(tip) == Some(meter.price_for(usage.calls, current_slot)),
+            AgentBlinkPayError::AmountNotPricePlusTip
+        );
+    } else if quantity > 1 {
+        let batch_price = (quantity as u64)
+            .checked_mul(meter.effective_price(current_slot))
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        require!(amount == batch_price, AgentBlinkPayError::AmountNotQuantityPrice);
+    } else if amount == 0 {
+        // Free calls are reserved when authorized, so in-flight
+        // authorizations can't exceed the allowance
+        require!(
+            usage.free_calls_used < meter.free_calls,
+            AgentBlinkPayError::FreeCallsExhausted
+        );
+        usage.free_calls_used += 1;
+    } else if meter.has_tiers() {
+        require!(
+            amount == meter.price_for(usage.calls, current_slot),
+            AgentBlinkPayError::AmountNotTierPrice
+        );
+    } else {
+        require!(
+            amount >= meter.effective_price(current_slot),
+            AgentBlinkPayError::AmountBelowMeterPrice
+        );
+    }
+
+    // Defense in depth: a meter never accepts far more than it charges,
+    // even when the policy would allow it. Only a declared tip may exceed it.
+    require!(
+        (amount - tip) / quantity as u64 <= meter.max_payment(usage.calls, current_slot),
+        AgentBlinkPayError::AmountExceedsMeterCap
+    );
+
+    // An authorization must be live for at least one more slot and at
+    // most MAX_AUTHORIZATION_TTL_SLOTS
+    require!(
+        payment.expires_at_slot > current_slot
+            && payment.expires_at_slot <= current_slot.saturating_add(MAX_AUTHORIZATION_TTL_SLOTS),
+        AgentBlinkPayError::InvalidExpiry
+    );
+
+    Ok(())
+}
+
+// =============================================================================
+// AUTHORIZATION HELPER
+// =============================================================================
+
+/// How the payment passed to `authorize_payment` is proven.
+enum PaymentProof {
+    /// No proof (authorize_payment_simple)
+    None,
+    /// A proof verified by this instruction (authorize_payment_with_proof)
+    Inline(Vec<u8>),
+    /// A proof already verified by verify_proof_stage, held by the
+    /// context's `pending_verification` (finalize_authorization)
+    Staged,
+    /// An Ed25519 attestation by the Config's `policy_oracle`, checked by
+    /// the preceding instruction (authorize_payment_attested)
+    Attested,
+    /// A proof verified by submit_proof_receipt, held by the context's
+    /// `proof_receipt` (authorize_payment_with_proof)
+    Receipt,
+}
+
+/// Shared body of `authorize_payment_with_proof`, `finalize_authorization`,
+/// `authorize_payment_simple` and `authorize_payment_attested`, by how the
+/// payment is proven.
+fn authorize_payment(
+    ctx: Context<AuthorizePayment>,
+    policy_id: u16,
+    amount: u64,
+    quantity: u16,
+    category: u8,
+    nonce: u64,
+    expires_at: u64,
+    proof: PaymentProof,
+    request_id: [u8; 32],
+    expiry_kind: u8,
+    consumer: Pubkey,
+    hash_lock: [u8; 32],
+    tip: u64,
+) -> Result<()> {
+    let policy = &ctx.accounts.agent_policy;
+    let meter = &ctx.accounts.meter;
+    let clock = Clock::get()?;
+    let current_slot = clock.slot;
+
+    // A wall-clock authorization also gets the longest slot expiry, so
+    // the slot-based guarantees of pause_meter and close_meter still hold
+    let (expires_at_slot, expires_at_unix) = match expiry_kind {
+        expiry_kinds::SLOT => (expires_at, 0),
+        expiry_kinds::UNIX => {
+            let expires_at_unix =
+                i64::try_from(expires_at).map_err(|_| AgentBlinkPayError::InvalidExpiry)?;
+            let max_ttl = ctx.accounts.config.max_authorization_ttl_secs as i64;
+            require!(
+                expires_at_unix > clock.unix_timestamp
+                    && expires_at_unix <= clock.unix_timestamp.saturating_add(max_ttl),
+                AgentBlinkPayError::InvalidExpiry
+            );
+            (current_slot.saturating_add(MAX_AUTHORIZATION_TTL_SLOTS), expires_at_unix)
+        }
+        _ => return err!(AgentBlinkPayError::InvalidExpiryKind),
+    };
+    let payment = AuthParams { amount, category, nonce, expires_at_slot, request_id };
+
+    check_nonce_unused(
+        &ctx.accounts.authorization.agent,
+        &ctx.accounts.authorization.key(),
+        nonce,
+    )?;
+
+    let usage = &mut ctx.accounts.meter_usage;
+    if usage.meter == Pubkey::default() {
+        // Freshly created by init_if_needed
+        usage.meter = meter.key();
+        usage.agent = ctx.accounts.agent.key();
+        usage.bump = ctx.bumps.meter_usage;
+        usage.version = MeterUsage::VERSION;
+    }
+
+    // 1. Basic Checks
+    check_payment(policy, meter, usage, &payment, quantity, tip, current_slot)?;
+    require!(
+        !policy.enforce_meter_allowlist || ctx.accounts.allowed_meter.is_some(),
+        AgentBlinkPayError::MeterNotAllowed
+    );
+    require!(
+        !meter.allowlist_enabled || ctx.accounts.meter_access.is_some(),
+        AgentBlinkPayError::AgentNotAllowedByMeter
+    );
+    // The denial PDA's address is checked by the context; it must not exist
+    require!(
+        ctx.accounts.denied_meter.owner != &crate::ID,
+        AgentBlinkPayError::MeterDenied
+    );
+    
+    // 2-4. Commitment check and verifier CPI. A verified proof satisfies
+    // both `policy.always_require_zk` and `meter.requires_zk`; without one
+    // (attested, or let through by permissive mode) neither may be set. A
+    // proof let through by permissive mode leaves `zk_verified` false and
+    // is recorded as `verification_methods::PERMISSIVE`.
+    let attested = matches!(proof, PaymentProof::Attested);
+    // A receipt is closed with the context, so it may only be passed to be
+    // consumed
+    require!(
+        ctx.accounts.proof_receipt.is_none() || matches!(proof, PaymentProof::Receipt),
+        AgentBlinkPayError::ProofReceiptMismatch
+    );
+    let (verification, version, proven) = match proof {
+        PaymentProof::Inline(proof) => {
+            msg!("ZK Verification: Calling External Verifier via CPI... (required: {})",
+                 policy.requires_zk_for(meter));
+            meter.check_proof_version(&proof)?;
+            let verifying_key = CircuitKey::from_accounts(
+                ctx.accounts.verifying_key.as_ref(),
+                ctx.accounts.honk_verifying_key.as_ref(),
+            )?;
+            let version = proof_version(&proof);
+            let verified = verify_policy_proof(
+                policy,
+                meter.proof_circuit(&proof)?,
+                amount,
+                category,
+                meter.key(),
+                nonce,
+                &proof,
+                current_slot,
+                &ctx.accounts.config,
+                ctx.accounts.verifier_program.to_account_info(),
+                verifying_key,
+            )?;
+            if !verified {
+                check_unproven_payment(policy, meter)?;
+            }
+            (
+                verifying_key.verification(&ctx.accounts.config, verified),
+                version,
+                true,
+            )
+        }
+        PaymentProof::Staged => {
+            let pending = ctx
+                .accounts
+                .pending_verification
+                .as_ref()
+                .ok_or(AgentBlinkPayError::PendingVerificationMissing)?;
+            pending.check_finalizable(policy, amount, category, current_slot)?;
+            meter.check_proof_version(pending.proof())?;
+            meter.proof_circuit(pending.proof())?;
+            msg!("ZK Verification: proof verified in stages (required: {})",
+                 policy.requires_zk_for(meter));
+            (
+                Verification {
+                    method: verification_methods::EMBEDDED,
+                    verifier_ref: pending.verifying_key,
+                },
+                proof_version(pending.proof()),
+                true,
+            )
+        }
+        PaymentProof::Receipt => {
+            let receipt = ctx
+                .accounts
+                .proof_receipt
+                .as_ref()
+                .ok_or(AgentBlinkPayError::ProofReceiptMismatch)?;
+            let verifying_key = CircuitKey::from_accounts(
+                ctx.accounts.verifying_key.as_ref(),
+                ctx.accounts.honk_verifying_key.as_ref(),
+            )?;
+            receipt.check_consumable(
+                policy,
+                meter,
+                verifying_key,
+                amount,
+                category,
+                nonce,
+                current_slot,
+            )?;
+            msg!("ZK Verification: proof verified by receipt (required: {})",
+                 policy.requires_zk_for(meter));
+            (
+                Verification {
+                    method: receipt.verification_method,
+                    verifier_ref: receipt.verifier_ref,
+                },
+                receipt.proof_version,
+                true,
+            )
+        }
+        PaymentProof::None => {
+            check_unproven_payment(policy, meter)?;
+            (Verification::NONE, 0, false)
+        }
+        PaymentProof::Attested => {
+            let oracle = ctx.accounts.config.policy_oracle;
+            require_keys_neq!(oracle, Pubkey::default(), AgentBlinkPayError::AttestationsDisabled);
+            require!(!meter.reject_attested, AgentBlinkPayError::AttestationRejectedByMeter);
+            check_unproven_payment(policy, meter)?;
+            let instructions = ctx
+                .accounts
+                .instructions
+                .as_ref()
+                .ok_or(AgentBlinkPayError::InvalidAttestation)?;
+            verify_attestation(
+                instructions,
+                &oracle,
+                &ctx.accounts.agent.key(),
+                &meter.key(),
+                &payment,
+            )?;
+            msg!("Payment attested by policy oracle {:?}", oracle);
+            (
+                Verification {
+                    method: verification_methods::ATTESTATION,
+                    verifier_ref: oracle,
+                },
+                0,
+                false,
+            )
+        }
+    };
+    if proven {
+        let nullifier = payment_nullifier(
+            b"auth",
+            &ctx.accounts.agent.key(),
+            &meter.key(),
+            nonce,
+            amount,
+            &policy.policy_hash,
+        );
+        let nullifier_info = ctx
+            .accounts
+            .nullifier
+            .as_ref()
+            .ok_or(AgentBlinkPayError::InvalidNullifierAccount)?
+            .to_account_info();
+        spend_nullifier(
+            &nullifier_info,
+            nullifier,
+            ctx.accounts.agent.key(),
+            ctx.accounts.authorization.key(),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+    }
+
+    // 5. Escrow mode: lock the amount up front
+    let escrow = if meter.settlement_mode == settlement_modes::ESCROW && amount > 0 {
+        fund_escrow(&ctx.accounts, amount)?
+    } else {
+        Pubkey::default()
+    };
+
+    // 6. Create Authorization
+    let auth = &mut ctx.accounts.authorization;
+    auth.set_inner(payment.to_authorization(
+        ctx.accounts.agent.key(),
+        meter,
+        policy,
+        quantity,
+        current_slot,
+    ));
+    auth.bump = ctx.bumps.authorization;
+    auth.rent_payer = ctx.accounts.payer.key();
+    auth.expiry_kind = expiry_kind;
+    auth.expires_at_unix = expires_at_unix;
+    auth.consumer = consumer;
+    auth.hash_lock = hash_lock;
+    auth.tip = tip;
+    auth.set_verification(verification);
+    auth.proof_version = version;
+    auth.attested = attested;
+    auth.escrow = escrow;
+
+    emit!(auth.created_event(auth.key(), current_slot));
+    
+    msg!("Payment authorized: agent={:?}, meter={:?}, amount={}, quantity={}, nonce={}, policy_id={}",
+         auth.agent, auth.meter, amount, quantity, nonce, policy_id);
+    
+    Ok(())
+}
+
+// =============================================================================
+// ESCROW HELPERS
+// =============================================================================
+
+/// Creates the authorization's escrow (its associated token account for the
+/// meter's mint) and moves `amount` into it from the agent's vault.
+/// Returns the escrow's address.
+/// 
+/// Anyone can create an ATA ahead of the authorization, so an existing
+/// escrow is accepted, but only once it's checked to be a token account of
+/// the mint owned by the authorization.
+fn fund_escrow(accounts: &AuthorizePayment, amount: u64) -> Result<Pubkey> {
+    let (vault, escrow, mint, token_program, associated_token_program) = match (
+        &accounts.vault,
+        &accounts.escrow,
+        &accounts.mint,
+        &accounts.token_program,
+        &accounts.associated_token_program,
+    ) {
+        (Some(vault), Some(escrow), Some(mint), Some(token_program), Some(ata_program)) => {
+            (vault, escrow, mint, token_program, ata_program)
+        }
+        _ => return err!(AgentBlinkPayError::EscrowAccountsMissing),
+    };
+    let authorization = accounts.authorization.key();
+    require!(
+        escrow.key() == associated_token::get_associated_token_address(&authorization, &mint.key()),
+        AgentBlinkPayError::InvalidEscrowAccount
+    );
+    require!(amount <= vault.amount, AgentBlinkPayError::VaultInsufficientFunds);
+
+    associated_token::create_idempotent(CpiContext::new(
+        associated_token_program.to_account_info(),
+        associated_token::Create {
+            payer: accounts.payer.to_account_info(),
+            associated_token: escrow.to_account_info(),
+            authority: accounts.authorization.to_account_info(),
+            mint: mint.to_account_info(),
+            system_program: accounts.system_program.to_account_info(),
+            token_program: token_program.to_account_info(),
+        },
+    ))?;
+    require_keys_eq!(*escrow.owner, token_program.key(), AgentBlinkPayError::InvalidEscrowAccount);
+    let escrow_account = TokenAccount::try_deserialize(&mut &escrow.try_borrow_data()?[..])?;
+    require_keys_eq!(escrow_account.owner, authorization, AgentBlinkPayError::InvalidEscrowAccount);
+    require_keys_eq!(escrow_account.mint, mint.key(), AgentBlinkPayError::InvalidEscrowAccount);
+
+    // The context checked the vault's address, so only the bump is needed
+    let agent = accounts.agent.key();
+    let mint_key = mint.key();
+    let (_, bump) =
+        Pubkey::find_program_address(&[b"vault", agent.as_ref(), mint_key.as_ref()], &crate::ID);
+    let bump = [bump];
+    let seeds: &[&[u8]] = &[b"vault", agent.as_ref(), mint_key.as_ref(), &bump];
+    token::transfer(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            token::Transfer {
+                from: vault.to_account_info(),
+                to: escrow.to_account_info(),
+                authority: vault.to_account_info(),
+            },
+            &[seeds],
+        ),
+        amount,
+    )?;
+
+    Ok(escrow.key())
+}
+
+/// Moves `amount` out of an authorization's escrow, signed by the
+/// authorization PDA. With `close`, also closes the (then empty) escrow,
+/// crediting its rent to the authorization.
+fn transfer_from_escrow<'info>(
+    auth: &Account<'info, Authorization>,
+    escrow: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+    close: bool,
+) -> Result<()> {
+    let nonce = auth.nonce.to_le_bytes();
+    let bump = [auth.bump];
+    let seeds: &[&[u8]] = &[b"auth", auth.agent.as_ref(), auth.meter.as_ref(), &nonce, &bump];
+
+    if amount > 0 {
+        token::transfer(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                token::Transfer {
+                    from: escrow.to_account_info(),
+                    to: to.to_account_info(),
+                    authority: auth.to_account_info(),
+                },
+                &[seeds],
+            ),
+            amount,
+        )?;
+    }
+    if close {
+        token::close_account(CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            token::CloseAccount {
+                account: escrow.to_account_info(),
+                destination: auth.to_account_info(),
+                authority: auth.to_account_info(),
+            },
+            &[seeds],
+        ))?;
+    }
+
+    Ok(())
+}
+
+// =============================================================================
+// ED25519 SIGNATURE HELPERS
+// =============================================================================
+
+/// The signature by `signer` over `message` checked by the instruction
+/// before the current one, if that is an Ed25519 program instruction
+/// checking one.
+/// 
+/// The Ed25519 program fails the transaction if a signature it carries is
+/// invalid, so finding one there means it was verified.
+fn preceding_ed25519_signature(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<Option<[u8; 64]>> {
+    use solana_program::sysvar::instructions::{
+        load_current_index_checked, load_instruction_at_checked,
+    };
+
+    let current_index = load_current_index_checked(instructions)?;
+    if current_index == 0 {
+        return Ok(None);
+    }
+    let ix = load_instruction_at_checked(current_index as usize - 1, instructions)?;
+    if ix.program_id != solana_program::ed25519_program::ID {
+        return Ok(None);
+    }
+    Ok(ed25519_signature(&ix.data, signer, message))
+}
+
+/// The signature by `signer` over `message` in the data of an Ed25519
+/// program instruction, if that is what it checks.
+/// 
+/// The data must be one signature header of seven u16 offsets after a
+/// two-byte count; the instruction indices must be `u16::MAX` so the key,
+/// signature and message are read from that instruction itself and can't
+/// point elsewhere. Any other shape, including offsets past the end of the
+/// data, gives `None`.
+fn ed25519_signature(data: &[u8], signer: &Pubkey, message: &[u8]) -> Option<[u8; 64]> {
+    if data.len() < 16 || data[0] != 1 {
+        return None;
+    }
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let (signature_offset, signature_ix) = (read_u16(2) as usize, read_u16(4));
+    let (public_key_offset, public_key_ix) = (read_u16(6) as usize, read_u16(8));
+    let (message_offset, message_size, message_ix) =
+        (read_u16(10) as usize, read_u16(12) as usize, read_u16(14));
+    if signature_ix != u16::MAX || public_key_ix != u16::MAX || message_ix != u16::MAX {
+        return None;
+    }
+
+    let field = |offset: usize, len: usize| data.get(offset..offset + len);
+    if field(public_key_offset, 32)? != signer.as_ref()
+        || message_size != message.len()
+        || field(message_offset, message_size)? != message
+    {
+        return None;
+    }
+    field(signature_offset, 64)?.try_into().ok()
+}
+
+/// Checks that the instruction before the current one is an Ed25519
+/// program instruction verifying `signature` by `agent` over the voucher
+/// for `cumulative_amount` on the opening of `channel` at `opened_at_slot`.
+fn verify_channel_voucher(
+    instructions: &AccountInfo,
+    channel: &Pubkey,
+    agent: &Pubkey,
+    opened_at_slot: u64,
+    cumulative_amount: u64,
+    signature: &[u8; 64],
+) -> Result<()> {
+    let mut message = Vec::with_capacity(CHANNEL_VOUCHER_DOMAIN.len() + 48);
+    message.extend_from_slice(CHANNEL_VOUCHER_DOMAIN);
+    message.extend_from_slice(channel.as_ref());
+    message.extend_from_slice(&opened_at_slot.to_le_bytes());
+    message.extend_from_slice(&cumulative_amount.to_le_bytes());
+
+    require!(
+        preceding_ed25519_signature(instructions, agent, &message)? == Some(*signature),
+        AgentBlinkPayError::InvalidChannelVoucher
+    );
+
+    Ok(())
+}
+
+/// Checks that the instruction before the current one is an Ed25519
+/// program instruction verifying `oracle`'s attestation of `payment` by
+/// `agent` to `meter` (see `ATTESTATION_DOMAIN`).
+fn verify_attestation(
+    instructions: &AccountInfo,
+    oracle: &Pubkey,
+    agent: &Pubkey,
+    meter: &Pubkey,
+    payment: &AuthParams,
+) -> Result<()> {
+    let mut message = Vec::with_capacity(ATTESTATION_DOMAIN.len() + 89);
+    message.extend_from_slice(ATTESTATION_DOMAIN);
+    message.extend_from_slice(agent.as_ref());
+    message.extend_from_slice(meter.as_ref());
+    message.extend_from_slice(&payment.amount.to_le_bytes());
+    message.push(payment.category);
+    message.extend_from_slice(&payment.nonce.to_le_bytes());
+    message.extend_from_slice(&payment.expires_at_slot.to_le_bytes());
+
+    require!(
+        preceding_ed25519_signature(instructions, oracle, &message)?.is_some(),
+        AgentBlinkPayError::InvalidAttestation
+    );
+
+    Ok(())
+}
+
+// =============================================================================
+// ZK VERIFICATION HELPER
+// =============================================================================
+
+/// Circuit version a proof was generated for, from its first byte.
+/// Empty proofs report version 0.
+fn proof_version(proof: &[u8]) -> u8 {
+    proof.first().copied().unwrap_or(0)
+}
+
+/// A proof argument split into its header and payload:
+/// `[version: u8][scheme: u8][payload...]`.
+struct ProofHeader<'a> {
+    version: u8,
+    scheme: u8,
+    payload: &'a [u8],
+}
+
+impl<'a> ProofHeader<'a> {
+    /// Splits off the header. Fails with `MalformedProof` if the proof is
+    /// shorter than PROOF_HEADER_LEN.
+    fn parse(proof: &'a [u8]) -> Result<Self> {
+        require!(proof.len() >= PROOF_HEADER_LEN, AgentBlinkPayError::MalformedProof);
+        Ok(Self {
+            version: proof[0],
+            scheme: proof[1],
+            payload: &proof[PROOF_HEADER_LEN..],
+        })
+    }
+}
+
+/// Fails with `ProofTooSmall` or `ProofTooLarge` unless the proof argument
+/// is within MIN_PROOF_LEN and MAX_PROOF_LEN.
+fn check_proof_len(proof: &[u8]) -> Result<()> {
+    require!(proof.len() >= MIN_PROOF_LEN, AgentBlinkPayError::ProofTooSmall);
+    require!(proof.len() <= MAX_PROOF_LEN, AgentBlinkPayError::ProofTooLarge);
+    Ok(())
+}
+
+/// Verifies a ZK proof that the payment complies with the agent's policy.
+/// 
+/// # Arguments
+/// * `policy` - The agent's policy, whose `policy_hash` commits to the limits
+/// * `circuit_id` - The proof's circuit, from `Meter::proof_circuit`
+/// * `amount` - The payment amount (public input)
+/// * `category` - The payment category (public input)
+/// * `meter` - The meter the payment is authorized for (public input)
+/// * `nonce` - The authorization's nonce (public input)
+/// * `proof` - The ZK proof bytes generated by the Noir prover
+/// * `current_slot` - Slot at which the policy's `spent_today` is read
+/// * `config` - Global config, which selects the verifier
+/// * `verifier_program` - `Config::verifier`: this program or the external verifier
+/// * `verifying_key` - The `VerifyingKey` of `circuit_id`
+/// 
+/// Version 2 proofs are for the daily limit circuit, whose public inputs
+/// add the policy's `spent_today` to the payment policy inputs: it proves
+/// that `spent_today + amount` stays under a private daily limit. Version 3
+/// proofs are for the meter allowlist circuit, which adds the policy's
+/// `allowlist_root` and proves the meter is in its tree; a policy with a
+/// root takes no other payment proofs (`AgentPolicy::check_payment_circuit`).
+/// 
+/// # Returns
+/// * `Ok(true)` if proof is valid
+/// * `Ok(false)` if it failed its checks in permissive mode (`verify_circuit_proof`)
+/// * `Err(InvalidProof)` if proof verification fails
+fn verify_policy_proof<'info>(
+    policy: &AgentPolicy,
+    circuit_id: u8,
+    amount: u64,
+    category: u8,
+    meter: Pubkey,
+    nonce: u64,
+    proof: &[u8],
+    current_slot: u64,
+    config: &Config,
+    verifier_program: AccountInfo<'info>,
+    verifying_key: CircuitKey<'_, 'info>,
+) -> Result<bool> {
+    policy.check_payment_circuit(circuit_id)?;
+    let public_inputs =
+        payment_public_inputs(policy, circuit_id, amount, category, meter, nonce, current_slot);
+    verify_circuit_proof(
+        policy,
+        circuit_id,
+        &public_inputs,
+        proof,
+        config,
+        verifier_program,
+        verifying_key,
+    )
+}
+
+/// Public inputs of a payment proof for `circuit_id`: the payment policy
+/// inputs, then for the daily limit circuit what the agent has spent today
+/// and for the meter allowlist circuit the policy's allowlist root.
+fn payment_public_inputs(
+    policy: &AgentPolicy,
+    circuit_id: u8,
+    amount: u64,
+    category: u8,
+    meter: Pubkey,
+    nonce: u64,
+    current_slot: u64,
+) -> Vec<[u8; 32]> {
+    let mut public_inputs = PaymentPolicyInputs {
+        amount,
+        category,
+        policy_hash: policy.policy_hash,
+        meter: meter.to_bytes(),
+        nonce,
+    }
+    .to_field_elements()
+    .to_vec();
+    match circuit_id {
+        circuit_ids::DAILY_LIMIT => {
+            public_inputs.push(public_inputs::field_element(policy.spent_today_at(current_slot)));
+        }
+        circuit_ids::METER_ALLOWLIST => public_inputs.push(policy.allowlist_root),
+        _ => {}
+    }
+    public_inputs
+}
+
+/// Public inputs of an aggregated proof for `circuit_id`: each payment's
+/// `payment_public_inputs`, concatenated in the order of `payments`.
+fn batch_public_inputs(
+    policy: &AgentPolicy,
+    circuit_id: u8,
+    payments: &[AuthParams],
+    meter: Pubkey,
+    current_slot: u64,
+) -> Vec<[u8; 32]> {
+    payments
+        .iter()
+        .flat_map(|payment| {
+            payment_public_inputs(
+                policy,
+                circuit_id,
+                payment.amount,
+                payment.category,
+                meter,
+                payment.nonce,
+                current_slot,
+            )
+        })
+        .collect()
+}
+
+/// Verifies an aggregated proof that every payment of a batch complies
+/// with the agent's policy, for `authorize_payments_batch`.
+/// 
+/// The proof holds one Groth16 proof of `circuit_id` per payment, in the
+/// order of `payments`, and is checked against `batch_public_inputs` with
+/// a single pairing (`verify_groth16_batch`), so a proof moved to another
+/// entry fails. The check always runs in-process with the built-in
+/// verifier: with `external_verifier` enabled aggregated proofs fail with
+/// `UnsupportedProofScheme`. The enforcement mode applies as in
+/// `verify_circuit_proof`.
+/// 
+/// # Returns
+/// * `Ok(true)` if every proof is valid
+/// * `Ok(false)` if the proof failed its checks in permissive mode
+/// * `Err(InvalidProof)` if verification fails
+fn verify_batch_policy_proof(
+    policy: &AgentPolicy,
+    circuit_id: u8,
+    payments: &[AuthParams],
+    meter: Pubkey,
+    proof: &AggregatedProof,
+    current_slot: u64,
+    config: &Config,
+    verifying_key: &VerifyingKey,
+) -> Result<bool> {
+    policy.check_payment_circuit(circuit_id)?;
+    require!(!config.external_verifier, AgentBlinkPayError::UnsupportedProofScheme);
+    require!(
+        verifying_key.circuit_id == circuit_id,
+        AgentBlinkPayError::WrongVerifyingKey
+    );
+    let public_inputs = batch_public_inputs(policy, circuit_id, payments, meter, current_slot);
+    let result = check_policy_commitment(policy)
+        .and_then(|()| verify_groth16_batch(verifying_key, proof, &public_inputs));
+    enforce_proof_check(policy, circuit_id, config, result)
+}
+
+/// Circuit a payment proof is verified against, by proof version. Version 1
+/// is the payment policy circuit; version 2 the daily limit circuit and
+/// version 3 the meter allowlist circuit, whose keys are only installed on
+/// deployments that use them.
+fn payment_circuit(version: u8) -> Option<u8> {
+    match version {
+        1 => Some(circuit_ids::PAYMENT_POLICY),
+        2 => Some(circuit_ids::DAILY_LIMIT),
+        3 => Some(circuit_ids::METER_ALLOWLIST),
+        _ => None,
+    }
+}
+
+/// True for circuits `payment_circuit` maps a proof version to, the ones a
+/// meter can pin.
+fn is_payment_circuit(circuit_id: u8) -> bool {
+    matches!(
+        circuit_id,
+        circuit_ids::PAYMENT_POLICY | circuit_ids::DAILY_LIMIT | circuit_ids::METER_ALLOWLIST
+    )
+}
+
+/// Verifies a budget circuit proof for `authorize_budget_with_proof`.
+/// 
+/// The budget circuit extends the per-payment circuit's public inputs with
+/// a limit: its amount input is the budget's total and the limit is the
+/// policy's remaining lifetime budget, so it proves that the whole budget
+/// fits what the agent may still spend. Per-call limits are enforced on
+/// each draw by `record_budget_payment`.
+fn verify_budget_proof<'info>(
+    policy: &AgentPolicy,
+    total_amount: u64,
+    category: u8,
+    meter: Pubkey,
+    nonce: u64,
+    proof: &[u8],
+    config: &Config,
+    verifier_program: AccountInfo<'info>,
+    verifying_key: CircuitKey<'_, 'info>,
+) -> Result<bool> {
+    policy.check_payment_circuit(circuit_ids::BUDGET)?;
+    let mut public_inputs = PaymentPolicyInputs {
+        amount: total_amount,
+        category,
+        policy_hash: policy.policy_hash,
+        meter: meter.to_bytes(),
+        nonce,
+    }
+    .to_field_elements()
+    .to_vec();
+    public_inputs.push(public_inputs::field_element(policy.remaining_budget()));
+    verify_circuit_proof(
+        policy,
+        circuit_ids::BUDGET,
+        &public_inputs,
+        proof,
+        config,
+        verifier_program,
+        verifying_key,
+    )
+}
+
+/// A circuit's registered key, of whichever scheme.
+#[derive(Clone, Copy)]
+enum CircuitKey<'a, 'info> {
+    Groth16(&'a Account<'info, VerifyingKey>),
+    UltraHonk(&'a Account<'info, HonkVerifyingKey>),
+}
+
+impl<'a, 'info> CircuitKey<'a, 'info> {
+    /// The key passed as `verifying_key` or `honk_verifying_key`. Fails
+    /// with `VerifyingKeyMissing` if neither was.
+    fn from_accounts(
+        verifying_key: Option<&'a Account<'info, VerifyingKey>>,
+        honk_verifying_key: Option<&'a Account<'info, HonkVerifyingKey>>,
+    ) -> Result<Self> {
+        match (verifying_key, honk_verifying_key) {
+            (Some(vk), _) => Ok(Self::Groth16(vk)),
+            (None, Some(vk)) => Ok(Self::UltraHonk(vk)),
+            (None, None) => err!(AgentBlinkPayError::VerifyingKeyMissing),
+        }
+    }
+
+    /// The key's account.
+    fn key(&self) -> Pubkey {
+        match self {
+            Self::Groth16(vk) => vk.key(),
+            Self::UltraHonk(vk) => vk.key(),
+        }
+    }
+
+    fn circuit_id(&self) -> u8 {
+        match self {
+            Self::Groth16(vk) => vk.circuit_id,
+            Self::UltraHonk(vk) => vk.circuit_id,
+        }
+    }
+
+    fn circuit_version(&self) -> u8 {
+        match self {
+            Self::Groth16(vk) => vk.circuit_version,
+            Self::UltraHonk(vk) => vk.circuit_version,
+        }
+    }
+
+    /// The one scheme the circuit's proofs may use (see `proof_schemes`).
+    fn scheme(&self) -> u8 {
+        match self {
+            Self::Groth16(_) => proof_schemes::GROTH16,
+            Self::UltraHonk(_) => proof_schemes::ULTRA_HONK,
+        }
+    }
+
+    /// Program the proof must go to: the Config's verifier for Groth16,
+    /// the key's Honk verifier for UltraHonk.
+    fn verifier(&self, config: &Config) -> Pubkey {
+        match self {
+            Self::Groth16(_) => config.verifier(),
+            Self::UltraHonk(vk) => vk.verifier_program,
+        }
+    }
+
+    /// How `verify_circuit_proof` checked a proof against this key, given
+    /// whether it was verified: by the built-in Groth16 verifier (recorded
+    /// as this key) or by CPI to a verifier program, or not at all under
+    /// permissive mode.
+    fn verification(&self, config: &Config, verified: bool) -> Verification {
+        let (method, verifier_ref) = match self {
+            Self::Groth16(vk) if !config.external_verifier => {
+                (verification_methods::EMBEDDED, vk.key())
+            }
+            _ => (verification_methods::CPI, self.verifier(config)),
+        };
+        Verification {
+            method: if verified { method } else { verification_methods::PERMISSIVE },
+            verifier_ref,
+        }
+    }
+}
+
+/// How an authorization's payment was checked, as recorded in its
+/// `verification_method` and `verifier_ref`.
+#[derive(Clone, Copy)]
+struct Verification {
+    method: u8,
+    verifier_ref: Pubkey,
+}
+
+impl Verification {
+    /// Neither a proof nor an attestation.
+    const NONE: Self = Self {
+        method: verification_methods::NONE,
+        verifier_ref: Pubkey::new_from_array([0; 32]),
+    };
+
+    /// Whether a proof was verified.
+    fn zk_verified(&self) -> bool {
+        matches!(self.method, verification_methods::EMBEDDED | verification_methods::CPI)
+    }
+}
+
+/// Shared body of the proof checks: the proof header, the policy
+/// commitment, then the verifier for the proof's scheme.
+/// 
+/// Proofs of a version outside SUPPORTED_PROOF_VERSIONS fail with
+/// `UnsupportedProofVersion`, and of an unknown scheme with
+/// `UnsupportedProofScheme`. `verifying_key` must be the key of
+/// `circuit_id` (`WrongVerifyingKey`), and the proof must be of the key's
+/// scheme (`ProofSchemeMismatch`): the circuit's registry entry, not the
+/// proof, picks the verifier. Groth16 proofs go by CPI to this program's
+/// `verify_proof`, against `verifying_key`, unless the Config enables
+/// `external_verifier`. The proof then goes to the Config's
+/// `verifier_program`, which holds its own key: the instruction data is the
+/// proof payload followed by the public inputs, and no accounts. UltraHonk
+/// proofs go the same way to the `HonkVerifyingKey`'s verifier program.
+/// `invoke` errors become `InvalidProof`; a verifier that rejects the proof
+/// aborts the transaction with its own error.
+/// 
+/// Returns whether the proof was verified. Under
+/// `enforcement_modes::PERMISSIVE` a proof that fails its checks is logged,
+/// reported with `ProofCheckSkipped` and let through (`Ok(false)`); the
+/// proof must still be within MIN_PROOF_LEN and MAX_PROOF_LEN, and the
+/// verifying key and verifier program the right accounts.
+/// Permissive mode checks Groth16 proofs in-process rather than by CPI,
+/// since a failed CPI aborts the transaction; for the same reason an
+/// external or Honk verifier that rejects the proof still aborts it.
+fn verify_circuit_proof<'info>(
+    policy: &AgentPolicy,
+    circuit_id: u8,
+    public_inputs: &[[u8; 32]],
+    proof: &[u8],
+    config: &Config,
+    verifier_program: AccountInfo<'info>,
+    verifying_key: CircuitKey<'_, 'info>,
+) -> Result<bool> {
+    check_proof_len(proof)?;
+    require!(
+        verifying_key.circuit_id() == circuit_id,
+        AgentBlinkPayError::WrongVerifyingKey
+    );
+    if let Some(&scheme) = proof.get(1) {
+        require!(
+            !is_proof_scheme(scheme) || scheme == verifying_key.scheme(),
+            AgentBlinkPayError::ProofSchemeMismatch
+        );
+    }
+    require_keys_eq!(
+        verifier_program.key(),
+        verifying_key.verifier(config),
+        AgentBlinkPayError::VerifierProgramMismatch
+    );
+
+    let permissive = config.enforcement_mode == enforcement_modes::PERMISSIVE;
+    let result = check_circuit_proof(
+        policy,
+        public_inputs,
+        proof,
+        config,
+        verifier_program,
+        verifying_key,
+        permissive,
+    );
+    enforce_proof_check(policy, circuit_id, config, result)
+}
+
+/// Applies the Config's enforcement mode to the result of a proof's
+/// checks: `Ok(true)` if they passed; under `enforcement_modes::PERMISSIVE`
+/// a failure is logged, reported with `ProofCheckSkipped` and let through
+/// (`Ok(false)`), otherwise it is returned.
+fn enforce_proof_check(
+    policy: &AgentPolicy,
+    circuit_id: u8,
+    config: &Config,
+    result: Result<()>,
+) -> Result<bool> {
+    let permissive = config.enforcement_mode == enforcement_modes::PERMISSIVE;
+    match result {
+        Ok(()) => {
+            msg!("ZK Verifier returned success.");
+            Ok(true)
+        }
+        Err(err) if permissive => {
+            msg!("Warning: proof check failed ({}), proceeding in permissive mode", err);
+            let error_code = match &err {
+                Error::AnchorError(e) => e.error_code_number,
+                Error::ProgramError(_) => 0,
+            };
+            emit!(ProofCheckSkipped {
+                agent: policy.agent_pubkey,
+                circuit_id,
+                error_code,
+                slot: Clock::get()?.slot,
+            });
+            Ok(false)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// The checks of `verify_circuit_proof` that the enforcement mode applies
+/// to. `in_process` runs the built-in Groth16 verifier directly instead of
+/// by self-CPI.
+fn check_circuit_proof<'info>(
+    policy: &AgentPolicy,
+    public_inputs: &[[u8; 32]],
+    proof: &[u8],
+    config: &Config,
+    verifier_program: AccountInfo<'info>,
+    verifying_key: CircuitKey<'_, 'info>,
+    in_process: bool,
+) -> Result<()> {
+    let header = ProofHeader::parse(proof)?;
+    require!(
+        SUPPORTED_PROOF_VERSIONS.contains(&header.version),
+        AgentBlinkPayError::UnsupportedProofVersion
+    );
+
+    check_policy_commitment(policy)?;
+
+    require!(
+        header.scheme == verifying_key.scheme(),
+        AgentBlinkPayError::UnsupportedProofScheme
+    );
+
+    match verifying_key {
+        CircuitKey::UltraHonk(vk) => {
+            if header.version != vk.circuit_version {
+                return Err(reject_proof(ProofRejection::VersionMismatch));
+            }
+            invoke_external_verifier(verifier_program, header.payload, public_inputs)?;
+        }
+        CircuitKey::Groth16(_) if config.external_verifier => {
+            invoke_external_verifier(verifier_program, header.payload, public_inputs)?;
+        }
+        CircuitKey::Groth16(vk) if in_process => {
+            verify_groth16_proof(vk, proof, public_inputs)?;
+        }
+        CircuitKey::Groth16(vk) => {
+            // CPI Call to Verifier Instruction
+            // We call `verify_proof` on *this* program (Self-CPI).
+            let cpi_accounts = agent_blink_pay::cpi::accounts::VerifyProof {
+                verifying_key: vk.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(verifier_program, cpi_accounts);
+
+            agent_blink_pay::cpi::verify_proof(cpi_ctx, proof.to_vec(), public_inputs.concat())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends a proof payload and its public inputs to a verifier program that
+/// holds the circuit's key (the Config's external verifier or a Honk
+/// verifier), with no accounts.
+fn invoke_external_verifier(
+    verifier_program: AccountInfo<'_>,
+    payload: &[u8],
+    public_inputs: &[[u8; 32]],
+) -> Result<()> {
+    let instruction = solana_program::instruction::Instruction {
+        program_id: verifier_program.key(),
+        accounts: vec![],
+        data: [payload, &public_inputs.concat()].concat(),
+    };
+    solana_program::program::invoke(&instruction, &[verifier_program])
+        .map_err(|_| error!(AgentBlinkPayError::InvalidProof))
+}
+
+/// True for the schemes in `proof_schemes`.
+fn is_proof_scheme(scheme: u8) -> bool {
+    matches!(
+        scheme,
+        proof_schemes::GROTH16 | proof_schemes::ULTRA_HONK | proof_schemes::GROTH16_BATCH
+    )
+}
+
+/// Commitment Check (Policy Integrity)
+/// Ensure the stored policy hash matches the claimed parameters.
+/// This ensures the inputs we pass to the Verifier are indeed the Agent's Policy.
+fn check_policy_commitment(policy: &AgentPolicy) -> Result<()> {
+    let salt = b"BlinkPay";
+    let computed_hash = solana_program::hash::hashv(&[
+        &policy.max_per_tx.to_le_bytes(),
+        &[policy.allowed_category],
+        salt
+    ]);
+    require!(
+        policy.policy_hash == computed_hash.to_bytes(),
+        AgentBlinkPayError::InvalidProof
+    );
+    Ok(())
+}
+
+// =============================================================================
+// GROTH16 VERIFIER
+// =============================================================================
+
+/// BN254 base field modulus, big-endian.
+const BN254_FIELD_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x97, 0x81, 0x6a, 0x91, 0x68, 0x71, 0xca, 0x8d, 0x3c, 0x20, 0x8c, 0x16, 0xd8, 0x7c, 0xfd, 0x47,
+];
+
+/// BN254 scalar field modulus, big-endian; public inputs must be below it.
+const BN254_SCALAR_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91, 0x43, 0xe1, 0xf5, 0x93, 0xf0, 0x00, 0x00, 0x01,
+];
+
+/// Why a proof was rejected, logged with the error: `MalformedProof` for
+/// the proofs that can't be decoded, `InvalidProof` for the others.
+#[derive(Debug)]
+enum ProofRejection {
+    /// Wrong length, or public inputs that don't fit the circuit
+    MalformedProof,
+    /// A proof point is not on the curve or not in its subgroup
+    MalformedPoint,
+    /// The points are valid but the pairing check fails
+    PairingFailed,
+    /// The proof's version byte is not the key's `circuit_version`
+    VersionMismatch,
+}
+
+fn reject_proof(reason: ProofRejection) -> Error {
+    let error = match reason {
+        ProofRejection::MalformedProof | ProofRejection::MalformedPoint => {
+            AgentBlinkPayError::MalformedProof
+        }
+        ProofRejection::PairingFailed | ProofRejection::VersionMismatch => {
+            AgentBlinkPayError::InvalidProof
+        }
+    };
+    msg!("{}: {:?}", error.name(), reason);
+    error!(error)
+}
+
+/// Checks a whole proof argument, header then Groth16 points, against `vk`:
+/// the body of `verify_proof`, also run in-process in permissive mode.
+fn verify_groth16_proof(vk: &VerifyingKey, proof: &[u8], public_inputs: &[[u8; 32]]) -> Result<()> {
+    let header = ProofHeader::parse(proof)
+        .map_err(|_| reject_proof(ProofRejection::MalformedProof))?;
+    if header.version != vk.circuit_version {
+        return Err(reject_proof(ProofRejection::VersionMismatch));
+    }
+    require!(
+        header.scheme == proof_schemes::GROTH16,
+        AgentBlinkPayError::UnsupportedProofScheme
+    );
+    verify_groth16(vk, header.payload, public_inputs)
+}
+
+/// Checks a Groth16 proof (A, B, C) against `vk` and `public_inputs`:
+/// e(-A, B) · e(alpha, beta) · e(vk_x, gamma) · e(C, delta) == 1, where
+/// vk_x = ic[0] + Σ public_inputs[i] · ic[i + 1].
+fn verify_groth16(vk: &VerifyingKey, proof: &[u8], public_inputs: &[[u8; 32]]) -> Result<()> {
+    let vk_x = groth16_vk_x(vk, public_inputs)?;
+    groth16_pairing(vk, proof, &vk_x)
+}
+
+/// vk_x = ic[0] + Σ public_inputs[i] · ic[i + 1], the public inputs folded
+/// into one G1 point. The first half of `verify_groth16`, run in its own
+/// stage by `verify_proof_stage`.
+fn groth16_vk_x(vk: &VerifyingKey, public_inputs: &[[u8; 32]]) -> Result<[u8; 64]> {
+    if public_inputs.len() + 1 != vk.ic.len() {
+        return Err(reject_proof(ProofRejection::MalformedProof));
+    }
+
+    let mut vk_x = vk.ic[0].to_vec();
+    for (input, ic) in public_inputs.iter().zip(&vk.ic[1..]) {
+        if input[..] >= BN254_SCALAR_MODULUS[..] {
+            return Err(reject_proof(ProofRejection::MalformedProof));
+        }
+        let term = alt_bn128_multiplication(&[&ic[..], &input[..]].concat())
+            .map_err(|_| reject_proof(ProofRejection::MalformedPoint))?;
+        vk_x = alt_bn128_addition(&[&vk_x[..], &term[..]].concat())
+            .map_err(|_| reject_proof(ProofRejection::MalformedPoint))?;
+    }
+    vk_x.try_into()
+        .map_err(|_| reject_proof(ProofRejection::MalformedPoint))
+}
+
+/// The pairing check of a Groth16 proof (A, B, C) given its `vk_x`.
+fn groth16_pairing(vk: &VerifyingKey, proof: &[u8], vk_x: &[u8; 64]) -> Result<()> {
+    if proof.len() != GROTH16_PROOF_LEN {
+        return Err(reject_proof(ProofRejection::MalformedProof));
+    }
+    let (a, rest) = proof.split_at(64);
+    let (b, c) = rest.split_at(128);
+
+    let pairing_input = [
+        &negate_g1(a)?[..],
+        b,
+        &vk.alpha_g1[..],
+        &vk.beta_g2[..],
+        &vk_x[..],
+        &vk.gamma_g2[..],
+        c,
+        &vk.delta_g2[..],
+    ]
+    .concat();
+    check_pairing(&pairing_input)
+}
+
+/// Fails with `PairingFailed` unless the product of the pairings of the
+/// (G1, G2) pairs in `pairing_input` is 1.
+fn check_pairing(pairing_input: &[u8]) -> Result<()> {
+    let result = alt_bn128_pairing(pairing_input)
+        .map_err(|_| reject_proof(ProofRejection::MalformedPoint))?;
+
+    // The syscall returns 1 as a 32-byte big-endian integer on success
+    if result.len() != 32 || result[31] != 1 || result[..31].iter().any(|byte| *byte != 0) {
+        return Err(reject_proof(ProofRejection::PairingFailed));
+    }
+    Ok(())
+}
+
+/// Domain separator of the coefficients `verify_groth16_batch` derives.
+const BATCH_CHALLENGE_DOMAIN: &[u8] = b"agent_blink_pay:groth16_batch";
+
+/// An aggregated proof argument (`proof_schemes::GROTH16_BATCH`):
+/// `[version: u8][scheme: u8][batch_count: u8]`, then `batch_count`
+/// Groth16 payloads, one per batch entry in order.
+struct AggregatedProof<'a> {
+    version: u8,
+    payload: &'a [u8],
+}
+
+impl<'a> AggregatedProof<'a> {
+    /// Splits off the header. Fails with `MalformedProof` unless the
+    /// payload holds exactly `batch_count` Groth16 payloads, 1 to
+    /// MAX_AUTHORIZATION_BATCH of them.
+    fn parse(proof: &'a [u8]) -> Result<Self> {
+        let header = ProofHeader::parse(proof)?;
+        require!(
+            header.scheme == proof_schemes::GROTH16_BATCH,
+            AgentBlinkPayError::UnsupportedProofScheme
+        );
+        let (&batch_count, payload) = header
+            .payload
+            .split_first()
+            .ok_or_else(|| reject_proof(ProofRejection::MalformedProof))?;
+        let batch_count = batch_count as usize;
+        if batch_count == 0
+            || batch_count > MAX_AUTHORIZATION_BATCH
+            || payload.len() != batch_count * GROTH16_PROOF_LEN
+        {
+            return Err(reject_proof(ProofRejection::MalformedProof));
+        }
+        Ok(Self {
+            version: header.version,
+            payload,
+        })
+    }
+
+    /// Number of Groth16 proofs it holds, one per batch entry.
+    fn batch_count(&self) -> usize {
+        self.payload.len() / GROTH16_PROOF_LEN
+    }
+
+    /// The Groth16 payloads, in entry order.
+    fn proofs(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.payload.chunks_exact(GROTH16_PROOF_LEN)
+    }
+}
+
+/// Checks the Groth16 proofs of an aggregated proof against `vk` with one
+/// pairing, `public_inputs` holding each proof's inputs in turn.
+/// 
+/// With coefficients r_i (r_0 = 1), the proofs pass together if
+/// Π e(-r_i·A_i, B_i) · e(Σ r_i·alpha, beta) · e(Σ r_i·vk_x_i, gamma) ·
+/// e(Σ r_i·C_i, delta) == 1, which, except with negligible probability,
+/// only holds if each proof's own check does. The r_i are 120-bit values
+/// derived from the proofs and public inputs, so no prover can pick
+/// proofs whose errors cancel out. The pairing takes batch_count + 3 pairs
+/// instead of 4 per proof.
+fn verify_groth16_batch(
+    vk: &VerifyingKey,
+    proof: &AggregatedProof,
+    public_inputs: &[[u8; 32]],
+) -> Result<()> {
+    if proof.version != vk.circuit_version {
+        return Err(reject_proof(ProofRejection::VersionMismatch));
+    }
+    let inputs_per_proof = vk.num_public_inputs();
+    if inputs_per_proof == 0 || public_inputs.len() != inputs_per_proof * proof.batch_count() {
+        return Err(reject_proof(ProofRejection::MalformedProof));
+    }
+
+    let seed = solana_program::hash::hashv(&[
+        BATCH_CHALLENGE_DOMAIN,
+        proof.payload,
+        &public_inputs.concat(),
+    ]);
+    let mut pairing_input = Vec::with_capacity((proof.batch_count() + 3) * 192);
+    let mut coefficient_sum = 0u128;
+    let mut vk_x_sum = [0u8; 64];
+    let mut c_sum = [0u8; 64];
+    for (i, (groth16, inputs)) in proof
+        .proofs()
+        .zip(public_inputs.chunks_exact(inputs_per_proof))
+        .enumerate()
+    {
+        let coefficient = batch_coefficient(&seed, i);
+        coefficient_sum += coefficient;
+        let (a, rest) = groth16.split_at(64);
+        let (b, c) = rest.split_at(128);
+
+        let vk_x = groth16_vk_x(vk, inputs)?;
+        pairing_input.extend_from_slice(&g1_scale(&negate_g1(a)?, coefficient)?);
+        pairing_input.extend_from_slice(b);
+        vk_x_sum = g1_add(&vk_x_sum, &g1_scale(&vk_x, coefficient)?)?;
+        c_sum = g1_add(&c_sum, &g1_scale(c, coefficient)?)?;
+    }
+    for (g1, g2) in [
+        (g1_scale(&vk.alpha_g1, coefficient_sum)?, &vk.beta_g2),
+        (vk_x_sum, &vk.gamma_g2),
+        (c_sum, &vk.delta_g2),
+    ] {
+        pairing_input.extend_from_slice(&g1);
+        pairing_input.extend_from_slice(g2);
+    }
+    check_pairing(&pairing_input)
+}
+
+/// Coefficient r_i of the `index`th proof of an aggregated proof: 1 for
+/// the first, then the top 120 bits of SHA-256(seed || index), so that
+/// their sum fits a u128.
+fn batch_coefficient(seed: &solana_program::hash::Hash, index: usize) -> u128 {
+    if index == 0 {
+        return 1;
+    }
+    let hash = solana_program::hash::hashv(&[seed.as_ref(), &(index as u64).to_le_bytes()]);
+    let mut high = [0u8; 16];
+    high.copy_from_slice(&hash.as_ref()[..16]);
+    u128::from_be_bytes(high) >> 8
+}
+
+/// k·P for a G1 point P.
+fn g1_scale(point: &[u8], scalar: u128) -> Result<[u8; 64]> {
+    let mut input = [0u8; 96];
+    input[..64].copy_from_slice(point);
+    if scalar == 1 {
+        return Ok(input[..64].try_into().unwrap());
+    }
+    input[80..].copy_from_slice(&scalar.to_be_bytes());
+    alt_bn128_multiplication(&input)
+        .ok()
+        .and_then(|product| product.try_into().ok())
+        .ok_or_else(|| reject_proof(ProofRejection::MalformedPoint))
+}
+
+/// P + Q for G1 points P and Q (all zero is the point at infinity).
+fn g1_add(p: &[u8; 64], q: &[u8; 64]) -> Result<[u8; 64]> {
+    alt_bn128_addition(&[&p[..], &q[..]].concat())
+        .ok()
+        .and_then(|sum| sum.try_into().ok())
+        .ok_or_else(|| reject_proof(ProofRejection::MalformedPoint))
+}
+
+/// -P for a G1 point (x, y): (x, p - y), with the point at infinity (all
+/// zero) its own negation.
+fn negate_g1(point: &[u8]) -> Result<[u8; 64]> {
+    let mut negated = [0u8; 64];
+    negated.copy_from_slice(point);
+    let y = &point[32..];
+    if y.iter().all(|byte| *byte == 0) {
+        return Ok(negated);
+    }
+    if y >= &BN254_FIELD_MODULUS[..] {
+        return Err(reject_proof(ProofRejection::MalformedPoint));
+    }
+
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let mut digit = BN254_FIELD_MODULUS[i] as i16 - y[i] as i16 - borrow;
+        borrow = (digit < 0) as i16;
+        if digit < 0 {
+            digit += 256;
+        }
+        negated[32 + i] = digit as u8;
+    }
+    Ok(negated)
+}
+
+// =============================================================================
+// ACCOUNT STRUCTURES & CONTEXTS
+// =============================================================================
+
+/// Context for verify_proof instruction.
+#[derive(Accounts)]
+pub struct VerifyProof<'info> {
+    /// The key to verify against (PDA: ["vk", circuit_id])
+    pub verifying_key: Account<'info, VerifyingKey>,
+}
+
+/// Context for verify_proof_stage instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16, amount: u64, category: u8, nonce: u64)]
+pub struct VerifyProofStage<'info> {
+    /// The agent the payment will be authorized for
+    pub agent: Signer<'info>,
+
+    /// The agent's policy account
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent.key().is_on_curve() || agent_policy.agent_is_pda
+            @ AgentBlinkPayError::AgentMustBeKeypair,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// The meter to be paid
+    pub meter: Account<'info, Meter>,
+
+    /// The verification in progress (PDA: ["pending", agent, meter, nonce]),
+    /// created by stage 0
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = PendingVerification::LEN,
+        seeds = [
+            b"pending",
+            agent.key().as_ref(),
+            meter.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump
+    )]
+    pub pending_verification: Account<'info, PendingVerification>,
+
+    /// The key of the proof's circuit (PDA: ["vk", circuit_id]); unused by
+    /// stage 0
+    pub verifying_key: Account<'info, VerifyingKey>,
+
+    /// Global config (PDA: ["config"]), which must select the built-in verifier
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// Account paying for the transaction and, at stage 0, the pending
+    /// verification's rent
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for submit_proof_receipt instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16, public_inputs_hash: [u8; 32])]
+pub struct SubmitProofReceipt<'info> {
+    /// The agent the proof will pay for
+    pub agent: Signer<'info>,
+
+    /// The agent's policy account
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent.key().is_on_curve() || agent_policy.agent_is_pda
+            @ AgentBlinkPayError::AgentMustBeKeypair,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// The receipt to create (PDA: ["proof_receipt", agent, public_inputs_hash])
+    #[account(
+        init,
+        payer = payer,
+        space = ProofReceipt::LEN,
+        seeds = [b"proof_receipt", agent.key().as_ref(), public_inputs_hash.as_ref()],
+        bump
+    )]
+    pub proof_receipt: Account<'info, ProofReceipt>,
+
+    /// Global config (PDA: ["config"])
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// The verifier the proof's circuit uses, as for authorize_payment_with_proof
+    /// CHECK: Compared against the key's verifier before the CPI
+    pub verifier_program: AccountInfo<'info>,
+
+    /// The key of the proof's circuit (PDA: ["vk", circuit_id])
+    pub verifying_key: Option<Account<'info, VerifyingKey>>,
+
+    /// The UltraHonk key of the proof's circuit (PDA: ["honk_vk",
+    /// circuit_id]), in place of `verifying_key` for UltraHonk circuits
+    pub honk_verifying_key: Option<Account<'info, HonkVerifyingKey>>,
+
+    /// Account paying for the receipt, refunded when it is consumed or closed
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Agent's spending policy account.
+/// 
+/// PDA seeds: ["policy", agent_pubkey, policy_id (u16 LE)]
+/// 
+/// An agent can hold several policies (e.g. one per project); each payment
+/// picks the one it is evaluated under. `DEFAULT_POLICY_ID` (0) is the
+/// policy used by single-policy clients.
+/// 
+/// This defines what an agent is allowed to spend on and how much.
+/// The policy_hash is a commitment used as a public input to ZK proofs,
+/// allowing verification without revealing the full policy details.
+#[account]
+#[derive(Default)]
+pub struct AgentPolicy {
+    /// The agent's public key (payment identity)
+    pub agent_pubkey: Pubkey,
+    
+    /// Hash commitment to the full policy (ZK public input)
+    /// Computed as: hash(max_per_tx || allowed_category || other_fields)
+    pub policy_hash: [u8; 32],
+    
+    /// Category of spending allowed (e.g., 1 = AI_API, 2 = CATAN_ACTION)
+    pub allowed_category: u8,
+    
+    /// Maximum allowed spend per transaction (USDC smallest units)
+    /// e.g., 500000 = 0.5 USDC
+    pub max_per_tx: u64,
+    
+    /// If true, agent cannot authorize any payments
+    pub frozen: bool,
+    
+    /// PDA bump seed
+    pub bump: u8,
+
+    /// Lifetime spend cap (USDC smallest units), 0 = unlimited
+    pub total_budget: u64,
+
+    /// Total amount recorded against this policy so far
+    pub lifetime_spent: u64,
+
+    /// Why the policy is frozen (see `freeze_reasons`)
+    pub frozen_reason: u8,
+
+    /// Optional second key allowed to pause the agent (default = none)
+    pub guardian: Pubkey,
+
+    /// Payments are rejected while current_slot <= paused_until_slot
+    pub paused_until_slot: u64,
+
+    /// Which of the agent's policies this is (part of the PDA seeds)
+    pub policy_id: u16,
+
+    /// Failed authorizations reported by watchers since the last successful payment
+    pub failed_auth_attempts: u16,
+
+    /// Slot of the most recent reported failure
+    pub last_failed_slot: u64,
+
+    /// Key that administers this policy. Default means the agent itself,
+    /// which is the case for policies created before owners existed.
+    pub owner: Pubkey,
+
+    /// If true, payments require an `AllowedMeter` record for the meter
+    pub enforce_meter_allowlist: bool,
+
+    /// Nominated owner awaiting `accept_policy_ownership`
+    pub pending_owner: Option<Pubkey>,
+
+    /// If true, every payment must go through proof verification, even for
+    /// meters with `requires_zk == false`
+    pub always_require_zk: bool,
+
+    /// Hash of the off-chain, human-readable policy document. Purely a
+    /// commitment for humans; never part of the ZK public inputs.
+    pub metadata_hash: [u8; 32],
+
+    /// Spend cap per `SLOTS_PER_DAY` window (USDC smallest units), 0 = unlimited
+    pub daily_limit: u64,
+
+    /// Amount recorded in the current window
+    pub spent_today: u64,
+
+    /// Slot at which the current window started
+    pub day_start_slot: u64,
+
+    /// Fraction of a limit, in basis points, at which `SpendThresholdCrossed`
+    /// is emitted (e.g. 8000 = 80%), 0 = disabled
+    pub alert_threshold_bps: u16,
+
+    /// Whether the daily alert already fired in the current window
+    pub alert_emitted: bool,
+
+    /// True if the agent is a program-derived address (off the ed25519
+    /// curve) that signs via CPI. Set when the policy is created.
+    pub agent_is_pda: bool,
+
+    /// If set, only meters settling in this mint can be paid
+    pub restrict_mint: Option<Pubkey>,
+
+    /// Account that paid the rent; receives it back when the policy is
+    /// closed. Unset on policies migrated from before this field.
+    pub rent_payer: Pubkey,
+
+    /// Root of a private meter allowlist's Merkle tree (see
+    /// zk/meter_allowlist), a public input of meter allowlist proofs. If
+    /// set, every payment needs one; all zero = none.
+    pub allowlist_root: [u8; 32],
+}
+
+impl AgentPolicy {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // agent_pubkey
+        32 +                    // policy_hash
+        1 +                     // allowed_category
+        8 +                     // max_per_tx
+        1 +                     // frozen
+        1 +                     // bump
+        8 +                     // total_budget
+        8 +                     // lifetime_spent
+        1 +                     // frozen_reason
+        32 +                    // guardian
+        8 +                     // paused_until_slot
+        2 +                     // policy_id
+        2 +                     // failed_auth_attempts
+        8 +                     // last_failed_slot
+        32 +                    // owner
+        1 +                     // enforce_meter_allowlist
+        1 + 32 +                // pending_owner
+        1 +                     // always_require_zk
+        32 +                    // metadata_hash
+        8 +                     // daily_limit
+        8 +                     // spent_today
+        8 +                     // day_start_slot
+        2 +                     // alert_threshold_bps
+        1 +                     // alert_emitted
+        1 +                     // agent_is_pda
+        1 + 32 +                // restrict_mint
+        32 +                    // rent_payer
+        32;                     // allowlist_root
+
+    /// True if a budget is set and it has been fully spent.
+    pub fn budget_exhausted(&self) -> bool {
+        self.total_budget != 0 && self.lifetime_spent >= self.total_budget
+    }
+
+    /// The key that administers this policy.
+    pub fn effective_owner(&self) -> Pubkey {
+        if self.owner == Pubkey::default() {
+            self.agent_pubkey
+        } else {
+            self.owner
+        }
+    }
+
+    /// True if `key` administers this policy.
+    pub fn is_owner(&self, key: &Pubkey) -> bool {
+        *key == self.effective_owner()
+    }
+
+    /// Where the policy's rent goes when it is closed: the recorded
+    /// `rent_payer`, or the owner for policies migrated without one.
+    pub fn rent_refund_destination(&self) -> Pubkey {
+        if self.rent_payer == Pubkey::default() {
+            self.effective_owner()
+        } else {
+            self.rent_payer
+        }
+    }
+
+    /// Applies the owner-controlled fields shared by `set_policy` and
+    /// `set_policies_batch`.
+    fn apply_params(&mut self, params: &PolicyParams) -> Result<()> {
+        require!(
+            params.alert_threshold_bps <= MAX_BPS,
+            AgentBlinkPayError::InvalidAlertThreshold
+        );
+        Category::try_from(params.allowed_category)?;
+        if let Some(salt) = params.policy_salt {
+            require!(
+                params.policy_hash
+                    == policy_hash::compute(params.max_per_tx, params.allowed_category, &salt),
+                AgentBlinkPayError::PolicyHashMismatch
+            );
+        }
+        require!(params.allowlist_root[0] == 0, AgentBlinkPayError::InvalidAllowlistRoot);
+
+        self.policy_id = params.policy_id;
+        self.policy_hash = params.policy_hash;
+        self.allowed_category = params.allowed_category;
+        self.max_per_tx = params.max_per_tx;
+        self.total_budget = params.total_budget;
+        self.enforce_meter_allowlist = params.enforce_meter_allowlist;
+        self.always_require_zk = params.always_require_zk;
+        self.metadata_hash = params.metadata_hash;
+        self.daily_limit = params.daily_limit;
+        self.alert_threshold_bps = params.alert_threshold_bps;
+        self.restrict_mint = params.restrict_mint;
+        self.allowlist_root = params.allowlist_root;
+
+        // An explicit freeze always wins. Unfreezing only sticks if the
+        // (possibly raised) budget is no longer exhausted.
+        if params.frozen {
+            self.frozen = true;
+            self.frozen_reason = freeze_reasons::MANUAL;
+        } else if self.budget_exhausted() {
+            self.frozen = true;
+            self.frozen_reason = freeze_reasons::BUDGET_EXHAUSTED;
+        } else {
+            // Unfreezing after a tripped circuit breaker also re-arms it
+            if self.frozen_reason == freeze_reasons::CIRCUIT_BREAKER {
+                self.failed_auth_attempts = 0;
+            }
+            self.frozen = false;
+            self.frozen_reason = freeze_reasons::NONE;
+        }
+
+        Ok(())
+    }
+
+    fn updated_event(&self, slot: u64) -> PolicyUpdated {
+        PolicyUpdated {
+            agent_pubkey: self.agent_pubkey,
+            policy_id: self.policy_id,
+            policy_hash: self.policy_hash,
+            allowed_category: self.allowed_category,
+            max_per_tx: self.max_per_tx,
+            frozen: self.frozen,
+            total_budget: self.total_budget,
+            enforce_meter_allowlist: self.enforce_meter_allowlist,
+            always_require_zk: self.always_require_zk,
+            metadata_hash: self.metadata_hash,
+            daily_limit: self.daily_limit,
+            alert_threshold_bps: self.alert_threshold_bps,
+            restrict_mint: self.restrict_mint,
+            allowlist_root: self.allowlist_root,
+            slot,
+        }
+    }
+
+    /// True if payments to `meter` under this policy must carry a verified proof.
+    /// 
+    /// Any authorization path that skips proof verification must reject
+    /// when this is true (`ZkRequiredByPolicy` if it's the policy's doing).
+    pub fn requires_zk_for(&self, meter: &Meter) -> bool {
+        self.requires_proof() || meter.requires_zk
+    }
+
+    /// True if every payment under this policy must carry a verified proof:
+    /// it has `always_require_zk` set, or a private meter allowlist that
+    /// only a proof can check.
+    pub fn requires_proof(&self) -> bool {
+        self.always_require_zk || self.has_allowlist_root()
+    }
+
+    /// True if the policy has a private meter allowlist.
+    pub fn has_allowlist_root(&self) -> bool {
+        self.allowlist_root != [0; 32]
+    }
+
+    /// Rejects a payment proof of `circuit_id` under this policy: with an
+    /// allowlist root only meter allowlist proofs, which check it, and
+    /// without one none of them (`AllowlistCircuitMismatch`).
+    pub fn check_payment_circuit(&self, circuit_id: u8) -> Result<()> {
+        require!(
+            self.has_allowlist_root() == (circuit_id == circuit_ids::METER_ALLOWLIST),
+            AgentBlinkPayError::AllowlistCircuitMismatch
+        );
+        Ok(())
+    }
+
+    /// True unless the policy restricts payments to a mint other than `mint`.
+    pub fn accepts_mint(&self, mint: &Pubkey) -> bool {
+        self.restrict_mint.is_none() || self.restrict_mint == Some(*mint)
+    }
+
+    /// True while a pause set by `pause_policy` is in effect.
+    pub fn is_paused(&self, current_slot: u64) -> bool {
+        current_slot <= self.paused_until_slot
+    }
+
+    /// Amount that can still be spent before the budget is exhausted.
+    pub fn remaining_budget(&self) -> u64 {
+        if self.total_budget == 0 {
+            u64::MAX
+        } else {
+            self.total_budget.saturating_sub(self.lifetime_spent)
+        }
+    }
+
+    /// True once the current daily window has run its course.
+    fn spend_window_elapsed(&self, current_slot: u64) -> bool {
+        current_slot >= self.day_start_slot.saturating_add(SLOTS_PER_DAY)
+    }
+
+    /// Starts a new daily window if the current one has elapsed.
+    pub fn roll_spend_window(&mut self, current_slot: u64) {
+        if self.spend_window_elapsed(current_slot) {
+            self.spent_today = 0;
+            self.day_start_slot = current_slot;
+            self.alert_emitted = false;
+        }
+    }
+
+    /// Charges `amount` against the lifetime budget and the daily window.
+    /// 
+    /// Shared by every path that spends from a policy. Emits
+    /// `SpendThresholdCrossed` / `BudgetExhausted` (auto-freezing the policy)
+    /// as limits are approached or reached.
+    pub fn charge(&mut self, amount: u64, current_slot: u64) -> Result<()> {
+        // Charge the lifetime budget
+        require!(
+            amount <= self.remaining_budget(),
+            AgentBlinkPayError::BudgetExceeded
+        );
+        let previous_lifetime_spent = self.lifetime_spent;
+        self.lifetime_spent = self
+            .lifetime_spent
+            .checked_add(amount)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+
+        // Charge the daily window
+        self.roll_spend_window(current_slot);
+        require!(
+            amount <= self.remaining_today(current_slot),
+            AgentBlinkPayError::DailyLimitExceeded
+        );
+        self.spent_today = self
+            .spent_today
+            .checked_add(amount)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+
+        // A successful payment resets the circuit breaker
+        self.failed_auth_attempts = 0;
+
+        // Early warnings before the agent hits a cap
+        if self.past_alert_threshold(self.spent_today, self.daily_limit)
+            && !self.alert_emitted
+        {
+            self.alert_emitted = true;
+            emit!(SpendThresholdCrossed {
+                agent_pubkey: self.agent_pubkey,
+                policy_id: self.policy_id,
+                limit_type: limit_types::DAILY,
+                spent: self.spent_today,
+                limit: self.daily_limit,
+                slot: current_slot,
+            });
+        }
+        if self.past_alert_threshold(self.lifetime_spent, self.total_budget)
+            && !self.past_alert_threshold(previous_lifetime_spent, self.total_budget)
+        {
+            emit!(SpendThresholdCrossed {
+                agent_pubkey: self.agent_pubkey,
+                policy_id: self.policy_id,
+                limit_type: limit_types::LIFETIME,
+                spent: self.lifetime_spent,
+                limit: self.total_budget,
+                slot: current_slot,
+            });
+        }
+
+        // Auto-freeze once the lifetime budget is used up
+        if self.budget_exhausted() && !self.frozen {
+            self.frozen = true;
+            self.frozen_reason = freeze_reasons::BUDGET_EXHAUSTED;
+
+            emit!(BudgetExhausted {
+                agent_pubkey: self.agent_pubkey,
+                policy_id: self.policy_id,
+                total_budget: self.total_budget,
+                lifetime_spent: self.lifetime_spent,
+                slot: current_slot,
+            });
+
+            msg!("Budget exhausted, policy frozen: agent={:?}", self.agent_pubkey);
+        }
+
+        Ok(())
+    }
+
+    /// True if `spent` has reached `alert_threshold_bps` of `limit`.
+    /// Always false when the threshold or the limit is 0.
+    pub fn past_alert_threshold(&self, spent: u64, limit: u64) -> bool {
+        self.alert_threshold_bps != 0
+            && limit != 0
+            && spent as u128 * MAX_BPS as u128
+                >= limit as u128 * self.alert_threshold_bps as u128
+    }
+
+    /// Amount spent in the daily window at `current_slot`: 0 once the
+    /// window has elapsed, even before `roll_spend_window` resets it.
+    pub fn spent_today_at(&self, current_slot: u64) -> u64 {
+        if self.spend_window_elapsed(current_slot) {
+            0
+        } else {
+            self.spent_today
+        }
+    }
+
+    /// Amount that can still be spent in the daily window at `current_slot`.
+    pub fn remaining_today(&self, current_slot: u64) -> u64 {
+        if self.daily_limit == 0 {
+            u64::MAX
+        } else if self.spend_window_elapsed(current_slot) {
+            self.daily_limit
+        } else {
+            self.daily_limit.saturating_sub(self.spent_today)
+        }
+    }
+}
+
+/// Owner-controlled policy fields, as passed to `set_policy` and to each
+/// entry of `set_policies_batch`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PolicyParams {
+    /// Which of the agent's policies to set (0 = default)
+    pub policy_id: u16,
+
+    /// Commitment to the full policy (used as ZK public input)
+    pub policy_hash: [u8; 32],
+
+    /// Category of spending allowed (e.g., AI_API = 1)
+    pub allowed_category: u8,
+
+    /// Maximum spend per transaction in smallest USDC units
+    pub max_per_tx: u64,
+
+    /// If true, agent cannot authorize any payments
+    pub frozen: bool,
+
+    /// Lifetime spend cap in USDC smallest units (0 = unlimited)
+    pub total_budget: u64,
+
+    /// If true, only meters with an `AllowedMeter` record can be paid
+    pub enforce_meter_allowlist: bool,
+
+    /// If true, every payment needs a verified proof, whatever the meter says
+    pub always_require_zk: bool,
+
+    /// Hash of the human-readable policy document (not a ZK input)
+    pub metadata_hash: [u8; 32],
+
+    /// Spend cap per `SLOTS_PER_DAY` window (0 = unlimited)
+    pub daily_limit: u64,
+
+    /// Warn once spend passes this fraction of a limit (0 = off)
+    pub alert_threshold_bps: u16,
+
+    /// Only pay meters settling in this mint (None = any)
+    pub restrict_mint: Option<Pubkey>,
+
+    /// If set, `policy_hash` must equal
+    /// `policy_hash::compute(max_per_tx, allowed_category, salt)`, the
+    /// circuits' commitment, or the call fails with `PolicyHashMismatch`.
+    /// The salt becomes public in the transaction. None = not checked.
+    pub policy_salt: Option<[u8; 32]>,
+
+    /// Root of the agent's private meter allowlist (see
+    /// `circuit_ids::METER_ALLOWLIST`). If set, every payment needs a
+    /// version 3 proof that the meter is in the tree. It is a field
+    /// element, so its first byte must be 0 (`InvalidAllowlistRoot`).
+    /// All zero = no allowlist.
+    pub allowlist_root: [u8; 32],
+}
+
+/// Meter account for a paywalled API endpoint.
+/// 
+/// PDA seeds: ["meter", authority, meter_id]
+/// 
+/// Created when an API provider registers their endpoint through the
+/// "Register API" flow in the dashboard.
+#[account]
+pub struct Meter {
+    /// Authority that can update this meter
+    pub authority: Pubkey,
+    
+    /// Price per call in USDC smallest units (e.g., 50000 = $0.05)
+    pub price_per_call: u64,
+    
+    /// Category enum (must match agent's allowed_category)
+    pub category: u8,
+    
+    /// Merchant's Circle wallet ID (for off-chain USDC transfers)
+    pub merchant_wallet_id: [u8; 64],
+    
+    /// Actual length of merchant_wallet_id
+    pub merchant_wallet_id_len: u8,
+    
+    /// Whether this meter requires ZK-checked policies
+    pub requires_zk: bool,
+    
+    /// PDA bump seed
+    pub bump: u8,
+
+    /// Slot at which the meter was paused, 0 while it is active
+    pub paused_at_slot: u64,
+
+    /// False while paused: new authorizations are rejected
+    pub active: bool,
+
+    /// Legacy: payments recorded before the counters moved to
+    /// `MeterCounters`; copied there by `init_meter_counters`
+    pub total_calls: u64,
+
+    /// Legacy: volume recorded before the counters moved to `MeterCounters`
+    pub total_volume: u64,
+
+    /// Volume pricing tiers (price == 0 = unset)
+    pub tiers: [PriceTier; 4], // MAX_PRICE_TIERS
+
+    /// How the meter charges: per call, or a flat fee per period
+    pub kind: MeterKind,
+
+    /// Calls per agent that may be authorized with amount 0
+    pub free_calls: u16,
+
+    /// Display name of the API (UTF-8, `name_len` bytes used)
+    pub name: [u8; 32],
+
+    /// Actual length of name
+    pub name_len: u8,
+
+    /// Hash of the endpoint URL this meter charges for
+    pub endpoint_hash: [u8; 32],
+
+    /// Authority that created the meter. The PDA stays derived from this key
+    /// after the authority is transferred.
+    pub creator: Pubkey,
+
+    /// Authority nominated by `transfer_meter_authority`, awaiting acceptance
+    pub pending_authority: Option<Pubkey>,
+
+    /// Only agents with a `MeterAgentAccess` record may pay this meter
+    pub allowlist_enabled: bool,
+
+    /// Recorded calls allowed per window across all agents (0 = unlimited)
+    pub max_calls_per_window: u32,
+
+    /// Rate limit window length in slots
+    pub window_slots: u32,
+
+    /// Legacy: rate limit window start, now kept in `MeterCounters`
+    pub window_start_slot: u64,
+
+    /// Legacy: rate limit window count, now kept in `MeterCounters`
+    pub calls_in_window: u32,
+
+    /// Scheduled price increase, applied from `price_effective_slot`
+    pub pending_price: u64,
+
+    /// Slot `pending_price` takes effect at (0 = nothing scheduled)
+    pub price_effective_slot: u64,
+
+    /// PDA seed of create_meter_v2 meters (ASCII, `endpoint_id_len` bytes used)
+    pub endpoint_id: [u8; 32],
+
+    /// Actual length of endpoint_id (0 = created by create_meter)
+    pub endpoint_id_len: u8,
+
+    /// Extra recipients of each payment; the primary wallet gets the rest
+    pub splits: [RevenueSplit; 3], // MAX_REVENUE_SPLITS
+
+    /// Largest authorization accepted if above the price (0 = the price only)
+    pub max_amount_per_payment: u64,
+
+    /// Circle wallet of the integrator who onboarded the merchant
+    pub referrer_wallet_id: [u8; 64],
+    pub referrer_wallet_id_len: u8,
+
+    /// Referrer's share of each payment, in bps (0 = no referrer)
+    pub referrer_bps: u16,
+
+    /// Oldest circuit version accepted in proofs paying this meter (0 = any)
+    pub min_proof_version: u8,
+
+    /// Key that may update, pause and unpause the meter besides the authority
+    pub operator: Option<Pubkey>,
+
+    /// Token the meter settles in; transferred on-chain only in escrow mode
+    pub accepted_mint: Pubkey,
+
+    /// Event-only or escrow settlement (see `settlement_modes`)
+    pub settlement_mode: u8,
+
+    /// Circuit proofs paying this meter must be for (see `circuit_ids`;
+    /// 0 = any payment circuit, by proof version). Set by `set_meter_circuit`
+    pub circuit_id: u8,
+
+    /// Refuse payments authorized by a policy oracle attestation instead of
+    /// a proof. Set by `set_meter_reject_attested`
+    pub reject_attested: bool,
+}
+
+// The [u8; 64] wallet ids don't implement Default, so it can't be derived
+impl Default for Meter {
+    fn default() -> Self {
+        Self {
+            authority: Pubkey::default(),
+            price_per_call: 0,
+            category: 0,
+            merchant_wallet_id: [0; 64],
+            merchant_wallet_id_len: 0,
+            requires_zk: false,
+            bump: 0,
+            paused_at_slot: 0,
+            active: false,
+            total_calls: 0,
+            total_volume: 0,
+            tiers: Default::default(),
+            kind: MeterKind::default(),
+            free_calls: 0,
+            name: [0; 32],
+            name_len: 0,
+            endpoint_hash: [0; 32],
+            creator: Pubkey::default(),
+            pending_authority: None,
+            allowlist_enabled: false,
+            max_calls_per_window: 0,
+            window_slots: 0,
+            window_start_slot: 0,
+            calls_in_window: 0,
+            pending_price: 0,
+            price_effective_slot: 0,
+            endpoint_id: [0; 32],
+            endpoint_id_len: 0,
+            splits: Default::default(),
+            max_amount_per_payment: 0,
+            referrer_wallet_id: [0; 64],
+            referrer_wallet_id_len: 0,
+            referrer_bps: 0,
+            min_proof_version: 0,
+            operator: None,
+            accepted_mint: Pubkey::default(),
+            settlement_mode: 0,
+            circuit_id: 0,
+            reject_attested: false,
+        }
+    }
+}
+
+impl Meter {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // authority
+        8 +                     // price_per_call
+        1 +                     // category
+        64 +                    // merchant_wallet_id
+        1 +                     // merchant_wallet_id_len
+        1 +                     // requires_zk
+        1 +                     // bump
+        8 +                     // paused_at_slot
+        1 +                     // active
+        8 +                     // total_calls
+        8 +                     // total_volume
+        16 * MAX_PRICE_TIERS +  // tiers
+        1 + 16 +                // kind
+        2 +                     // free_calls
+        32 +                    // name
+        1 +                     // name_len
+        32 +                    // endpoint_hash
+        32 +                    // creator
+        1 + 32 +                // pending_authority
+        1 +                     // allowlist_enabled
+        4 +                     // max_calls_per_window
+        4 +                     // window_slots
+        8 +                     // window_start_slot
+        4 +                     // calls_in_window
+        8 +                     // pending_price
+        8 +                     // price_effective_slot
+        32 +                    // endpoint_id
+        1 +                     // endpoint_id_len
+        67 * MAX_REVENUE_SPLITS + // splits
+        8 +                     // max_amount_per_payment
+        64 +                    // referrer_wallet_id
+        1 +                     // referrer_wallet_id_len
+        2 +                     // referrer_bps
+        1 +                     // min_proof_version
+        1 + 32 +                // operator
+        32 +                    // accepted_mint
+        1 +                     // settlement_mode
+        1 +                     // circuit_id
+        1;                      // reject_attested
+
+    /// Byte offset of `active`, used by `migrate_meter`.
+    pub const ACTIVE_OFFSET: usize = 8 + 32 + 8 + 1 + 64 + 1 + 1 + 1 + 8;
+
+    /// Byte offset of `creator`, used by `migrate_meter`.
+    pub const CREATOR_OFFSET: usize =
+        Self::ACTIVE_OFFSET + 1 + 8 + 8 + 16 * MAX_PRICE_TIERS + 1 + 16 + 2 + 32 + 1 + 32;
+
+    /// Sets up a freshly created meter.
+    fn initialize(
+        &mut self,
+        authority: Pubkey,
+        bump: u8,
+        params: &MeterParams,
+        config: &Config,
+    ) -> Result<()> {
+        if let MeterKind::Subscription { period_slots, .. } = params.kind {
+            require!(period_slots > 0, AgentBlinkPayError::InvalidSubscriptionTerms);
+        }
+
+        self.authority = authority;
+        self.creator = authority;
+        self.price_per_call = params.price_per_call;
+        Category::try_from(params.category)?;
+        self.category = params.category;
+        self.requires_zk = params.requires_zk;
+        self.bump = bump;
+        self.active = true;
+        self.kind = params.kind;
+        self.free_calls = params.free_calls;
+        self.set_name(&params.name)?;
+        self.endpoint_hash = params.endpoint_hash;
+        self.set_referrer(&params.referrer_wallet_id, params.referrer_bps)?;
+        self.min_proof_version = params.min_proof_version;
+        self.accepted_mint = params.accepted_mint.unwrap_or(config.usdc_mint);
+
+        self.set_merchant_wallet_id(&params.merchant_wallet_id)
+    }
+
+    /// Validates and stores the referral terms. Only called at creation.
+    fn set_referrer(&mut self, wallet_id: &str, bps: u16) -> Result<()> {
+        require!(bps <= MAX_REFERRER_BPS, AgentBlinkPayError::InvalidReferral);
+        if bps == 0 {
+            require!(wallet_id.is_empty(), AgentBlinkPayError::InvalidReferral);
+            return Ok(());
+        }
+        require!(
+            !wallet_id.is_empty() && wallet_id.len() <= 64,
+            AgentBlinkPayError::InvalidReferral
+        );
+        check_wallet_id(wallet_id.as_bytes())?;
+
+        let id_bytes = wallet_id.as_bytes();
+        self.referrer_wallet_id[..id_bytes.len()].copy_from_slice(id_bytes);
+        self.referrer_wallet_id_len = id_bytes.len() as u8;
+        self.referrer_bps = bps;
+
+        Ok(())
+    }
+
+    /// Validates and stores the merchant's Circle wallet id.
+    pub fn set_merchant_wallet_id(&mut self, wallet_id: &str) -> Result<()> {
+        require!(wallet_id.len() <= 64, AgentBlinkPayError::MerchantWalletIdTooLong);
+        check_wallet_id(wallet_id.as_bytes())?;
+
+        // Store merchant_wallet_id as fixed-size array
+        let mut wallet_id_bytes = [0u8; 64];
+        let id_bytes = wallet_id.as_bytes();
+        wallet_id_bytes[..id_bytes.len()].copy_from_slice(id_bytes);
+        self.merchant_wallet_id = wallet_id_bytes;
+        self.merchant_wallet_id_len = id_bytes.len() as u8;
+
+        Ok(())
+    }
+
+    fn created_event(
+        &self,
+        meter: Pubkey,
+        meter_id: Pubkey,
+        params: MeterParams,
+        slot: u64,
+    ) -> MeterCreated {
+        MeterCreated {
+            meter,
+            meter_id,
+            endpoint_id: self.endpoint_id_str().to_string(),
+            authority: self.authority,
+            price_per_call: params.price_per_call,
+            category: params.category,
+            requires_zk: params.requires_zk,
+            merchant_wallet_id: self.merchant_wallet_id_str().to_string(),
+            name: params.name,
+            endpoint_hash: params.endpoint_hash,
+            referrer_wallet_id: params.referrer_wallet_id,
+            referrer_bps: params.referrer_bps,
+            accepted_mint: self.accepted_mint,
+            slot,
+        }
+    }
+
+    /// The last PDA seed: the stored endpoint id for create_meter_v2
+    /// meters, else the `meter_id` account's key.
+    pub fn pda_seed<'a>(&'a self, meter_id: &'a Pubkey) -> &'a [u8] {
+        if self.endpoint_id_len > 0 {
+            &self.endpoint_id[..(self.endpoint_id_len as usize).min(32)]
+        } else {
+            meter_id.as_ref()
+        }
+    }
+
+    /// The merchant's Circle wallet id.
+    pub fn merchant_wallet_id_str(&self) -> &str {
+        let len = (self.merchant_wallet_id_len as usize).min(self.merchant_wallet_id.len());
+        std::str::from_utf8(&self.merchant_wallet_id[..len]).unwrap_or_default()
+    }
+
+    /// The endpoint id (empty for meters created by create_meter).
+    pub fn endpoint_id_str(&self) -> &str {
+        let len = (self.endpoint_id_len as usize).min(self.endpoint_id.len());
+        std::str::from_utf8(&self.endpoint_id[..len]).unwrap_or_default()
+    }
+
+    /// Stores `name`, which must be 1-32 bytes. Strings are UTF-8 by
+    /// construction, so only the length needs checking.
+    pub fn set_name(&mut self, name: &str) -> Result<()> {
+        require!(
+            !name.is_empty() && name.len() <= 32,
+            AgentBlinkPayError::InvalidMeterName
+        );
+
+        let mut name_bytes = [0u8; 32];
+        name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+        self.name = name_bytes;
+        self.name_len = name.len() as u8;
+
+        Ok(())
+    }
+
+    /// The display name (empty for meters created before names existed).
+    pub fn name_str(&self) -> &str {
+        let len = (self.name_len as usize).min(self.name.len());
+        std::str::from_utf8(&self.name[..len]).unwrap_or_default()
+    }
+
+    /// True if at least one pricing tier is set.
+    pub fn has_tiers(&self) -> bool {
+        self.tiers.iter().any(PriceTier::is_set)
+    }
+
+    /// Price per call for an agent with `calls` recorded payments: the
+    /// highest set tier it has reached, else the effective per-call price.
+    /// A tier never charges more than the effective price, which a price
+    /// cut can bring below tiers set earlier.
+    pub fn price_for(&self, calls: u64, current_slot: u64) -> u64 {
+        let price = self.effective_price(current_slot);
+        self.tiers
+            .iter()
+            .filter(|tier| tier.is_set() && tier.min_calls <= calls)
+            .max_by_key(|tier| tier.min_calls)
+            .map_or(price, |tier| tier.price.min(price))
+    }
+
+    /// Divides `amount` into the referrer's share, the primary wallet's
+    /// share and the set splits' shares, in that order. The referrer is
+    /// paid first and the splits divide what is left. All shares are
+    /// floored; the remainder goes to the primary wallet.
+    pub fn split_amounts(&self, amount: u64) -> (u64, u64, [u64; 3]) {
+        // bps <= MAX_BPS, so no share ever exceeds the amount it is taken from
+        let share_of = |amount: u64, bps: u16| {
+            (amount as u128 * bps as u128 / MAX_BPS as u128) as u64
+        };
+
+        let referrer_amount = share_of(amount, self.referrer_bps);
+        let merchant_amount = amount - referrer_amount;
+        let mut split_amounts = [0u64; 3];
+        let mut primary_amount = merchant_amount;
+        for (share, split) in split_amounts.iter_mut().zip(self.splits.iter()) {
+            if split.is_set() {
+                *share = share_of(merchant_amount, split.bps);
+                primary_amount -= *share;
+            }
+        }
+        (referrer_amount, primary_amount, split_amounts)
+    }
+
+    /// True if `signer` is the meter's authority or its operator.
+    pub fn is_managed_by(&self, signer: &Pubkey) -> bool {
+        *signer == self.authority || self.operator == Some(*signer)
+    }
+
+    /// Rejects proofs generated for a circuit older than `min_proof_version`.
+    pub fn check_proof_version(&self, proof: &[u8]) -> Result<()> {
+        require!(
+            proof_version(proof) >= self.min_proof_version,
+            AgentBlinkPayError::ProofVersionTooOld
+        );
+        Ok(())
+    }
+
+    /// Circuit a payment proof is verified against: the one its version
+    /// names (`payment_circuit`), which must be the meter's `circuit_id`
+    /// when it has one.
+    pub fn proof_circuit(&self, proof: &[u8]) -> Result<u8> {
+        let circuit_id = payment_circuit(proof_version(proof))
+            .ok_or(AgentBlinkPayError::UnsupportedProofVersion)?;
+        require!(
+            self.circuit_id == 0 || self.circuit_id == circuit_id,
+            AgentBlinkPayError::ProofCircuitMismatch
+        );
+        Ok(circuit_id)
+    }
+
+    /// Largest amount an agent with `calls` recorded payments may authorize.
+    pub fn max_payment(&self, calls: u64, current_slot: u64) -> u64 {
+        self.price_for(calls, current_slot).max(self.max_amount_per_payment)
+    }
+
+    /// The per-call price at `current_slot`, including a scheduled change
+    /// whose effective slot has passed.
+    pub fn effective_price(&self, current_slot: u64) -> u64 {
+        if self.price_effective_slot != 0 && current_slot >= self.price_effective_slot {
+            self.pending_price
+        } else {
+            self.price_per_call
+        }
+    }
+
+    /// Folds a scheduled price change that has taken effect into `price_per_call`.
+    pub fn settle_price(&mut self, current_slot: u64) {
+        self.price_per_call = self.effective_price(current_slot);
+        if self.price_effective_slot != 0 && current_slot >= self.price_effective_slot {
+            self.pending_price = 0;
+            self.price_effective_slot = 0;
+        }
+    }
+}
+
+/// A meter's hot counters, split out of the Meter so that recording a
+/// payment write-locks only this small account.
+/// 
+/// PDA seeds: ["counters", meter_pubkey]
+/// 
+/// Zero-copy: the record path touches it on every call, so it is read and
+/// written in place instead of being (de)serialized.
+#[account(zero_copy)]
+pub struct MeterCounters {
+    /// The counted meter
+    pub meter: Pubkey,
+
+    /// Number of payments recorded against the meter
+    pub total_calls: u64,
+
+    /// Sum of all payments recorded against the meter (USDC smallest units)
+    pub total_volume: u64,
+
+    /// Slot the current rate limit window started at
+    pub window_start_slot: u64,
+
+    /// Calls recorded in the current rate limit window
+    pub calls_in_window: u32,
+
+    /// PDA bump seed
+    pub bump: u8,
+
+    /// Explicit padding to the 8-byte alignment of the struct
+    pub _padding: [u8; 3],
+}
+
+impl MeterCounters {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // meter
+        8 +                     // total_calls
+        8 +                     // total_volume
+        8 +                     // window_start_slot
+        4 +                     // calls_in_window
+        1 +                     // bump
+        3;                      // _padding
+
+    /// Fills in a just-created counters account, carrying over whatever
+    /// the meter had counted itself (nothing, for new meters).
+    fn initialize(
+        loader: &AccountLoader<MeterCounters>,
+        meter: &Account<Meter>,
+        bump: u8,
+    ) -> Result<()> {
+        let mut counters = loader.load_init()?;
+        counters.meter = meter.key();
+        counters.total_calls = meter.total_calls;
+        counters.total_volume = meter.total_volume;
+        counters.window_start_slot = meter.window_start_slot;
+        counters.calls_in_window = meter.calls_in_window;
+        counters.bump = bump;
+        Ok(())
+    }
+
+    /// Counts one recorded call of `amount` against the meter's rate limit
+    /// and totals.
+    pub fn record_call(&mut self, meter: &Meter, amount: u64, current_slot: u64) -> Result<()> {
+        self.count_windowed_call(meter, current_slot)?;
+        self.total_calls = self
+            .total_calls
+            .checked_add(1)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        self.total_volume = self
+            .total_volume
+            .checked_add(amount)
+            .ok_or(AgentBlinkPayError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Counts one recorded call against the rate limit, starting a new
+    /// window once the current one has elapsed.
+    fn count_windowed_call(&mut self, meter: &Meter, current_slot: u64) -> Result<()> {
+        if meter.max_calls_per_window == 0 {
+            return Ok(());
+        }
+
+        if current_slot.saturating_sub(self.window_start_slot) >= meter.window_slots as u64 {
+            self.window_start_slot = current_slot;
+            self.calls_in_window = 0;
+        }
+
+        require!(
+            self.calls_in_window < meter.max_calls_per_window,
+            AgentBlinkPayError::MeterRateLimited
+        );
+        self.calls_in_window += 1;
+
+        Ok(())
+    }
+}
+
+/// Meter settings, as passed to `create_meter_v2`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MeterParams {
+    /// Price in USDC smallest units (e.g., 50000 = $0.05)
+    pub price_per_call: u64,
+
+    /// Category enum for this meter (must match agent's allowed_category)
+    pub category: u8,
+
+    /// Identifier for the merchant's Circle wallet
+    pub merchant_wallet_id: String,
+
+    /// Whether this meter requires ZK-checked policies
+    pub requires_zk: bool,
+
+    /// Per-call pricing or a flat periodic subscription (fixed once created)
+    pub kind: MeterKind,
+
+    /// Calls per agent that may be authorized for free
+    pub free_calls: u16,
+
+    /// Display name of the API (1-32 bytes of UTF-8)
+    pub name: String,
+
+    /// Hash of the endpoint URL the meter charges for
+    pub endpoint_hash: [u8; 32],
+
+    /// Circle wallet of the integrator who onboarded the merchant (empty without a referrer)
+    pub referrer_wallet_id: String,
+
+    /// Referrer's share of each payment, at most MAX_REFERRER_BPS (0 = no referrer)
+    pub referrer_bps: u16,
+
+    /// Oldest circuit version accepted in proofs paying this meter (0 = any)
+    pub min_proof_version: u8,
+
+    /// Token the meter settles in (None = the Config's usdc_mint)
+    pub accepted_mint: Option<Pubkey>,
+}
+
+/// Settings `clone_meter` applies instead of the source meter's.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct CloneMeterOverrides {
+    /// Price in USDC smallest units (None = the source's price)
+    pub price_per_call: Option<u64>,
+
+    /// Category enum (None = the source's category)
+    pub category: Option<u8>,
+}
+
+/// One payment of `authorize_payments_batch`, same fields as the
+/// `authorize_payment_with_proof` arguments.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AuthParams {
+    pub amount: u64,
+    pub category: u8,
+    pub nonce: u64,
+    pub expires_at_slot: u64,
+    pub request_id: [u8; 32],
+}
+
+impl AuthParams {
+    /// The Authorization issued for this payment. `bump` and `rent_payer`
+    /// are left for the caller to fill in.
+    fn to_authorization(
+        &self,
+        agent: Pubkey,
+        meter: &Account<Meter>,
+        policy: &AgentPolicy,
+        quantity: u16,
+        current_slot: u64,
+    ) -> Authorization {
+        Authorization {
+            version: Authorization::VERSION,
+            agent,
+            meter: meter.key(),
+            amount: self.amount,
+            category: self.category,
+            nonce: self.nonce,
+            expires_at_slot: self.expires_at_slot,
+            policy_id: policy.policy_id,
+            mint: meter.accepted_mint,
+            quantity,
+            calls_remaining: quantity,
+            created_at_slot: current_slot,
+            amount_remaining: self.amount,
+            price_at_authorization: meter.effective_price(current_slot),
+            request_id: self.request_id,
+            policy_hash_at_auth: policy.policy_hash,
+            ..Default::default()
+        }
+    }
+}
+
+/// How a Meter charges agents.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeterKind {
+    /// Every call needs an authorization for the call price
+    #[default]
+    PerCall,
+
+    /// `fee` buys unlimited calls for `period_slots` (see `subscribe`)
+    Subscription { period_slots: u64, fee: u64 },
+}
+
+/// One volume pricing tier of a Meter.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceTier {
+    /// Recorded calls by the agent from which this tier applies
+    pub min_calls: u64,
+
+    /// Price per call in this tier (USDC smallest units), 0 = unset
+    pub price: u64,
+}
+
+impl PriceTier {
+    /// False for placeholder tiers (price == 0).
+    pub fn is_set(&self) -> bool {
+        self.price != 0
+    }
+}
+
+/// An extra recipient of a Meter's revenue.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct RevenueSplit {
+    /// Circle wallet receiving this share
+    pub wallet_id: [u8; 64],
+
+    /// Actual length of wallet_id
+    pub wallet_id_len: u8,
+
+    /// Share of each payment in basis points, 0 = unset
+    pub bps: u16,
+}
+
+// [u8; 64] doesn't implement Default, so it can't be derived
+impl Default for RevenueSplit {
+    fn default() -> Self {
+        Self {
+            wallet_id: [0; 64],
+            wallet_id_len: 0,
+            bps: 0,
+        }
+    }
+}
+
+impl RevenueSplit {
+    /// False for placeholder splits (bps == 0).
+    pub fn is_set(&self) -> bool {
+        self.bps != 0
+    }
+}
+
+/// Authorization (payment ticket) account.
+/// 
+/// PDA seeds: ["auth", agent_pubkey, meter_pubkey, nonce]
+/// 
+/// Created by authorize_payment_with_proof after ZK verification.
+/// Consumed by record_meter_payment to emit the payment event.
+/// One-time use, expires after expires_at_slot.
+/// Streaming authorizations (authorize_streaming_payment) unlock their
+/// amount gradually instead.
+/// 
+/// `version` and `kind` lead the layout so every variant can be told apart
+/// before the rest is read. Accounts in an older layout don't deserialize
+/// until `migrate_authorization` has rewritten them.
+#[account]
+#[derive(Default, InitSpace)]
+pub struct Authorization {
+    /// Layout version (see `Authorization::VERSION`)
+    pub version: u8,
+
+    /// Which flow issued the authorization (see `authorization_kinds`);
+    /// instructions for another flow reject it with `WrongAuthorizationKind`
+    pub kind: u8,
+
+    /// The agent making the payment
+    pub agent: Pubkey,
+    
+    /// The meter being paid
+    pub meter: Pubkey,
+    
+    /// Amount approved (USDC smallest units)
+    pub amount: u64,
+    
+    /// Category of this payment
+    pub category: u8,
+    
+    /// Unique nonce to prevent replay attacks
+    pub nonce: u64,
+    
+    /// Slot after which this authorization is invalid
+    pub expires_at_slot: u64,
+    
+    /// Whether this authorization has been consumed
+    pub used: bool,
+    
+    /// PDA bump seed
+    pub bump: u8,
+
+    /// The agent policy this authorization was issued under
+    pub policy_id: u16,
+
+    /// Token the payment settles in (the meter's accepted_mint when issued)
+    pub mint: Pubkey,
+
+    /// Number of calls `amount` pays for
+    pub quantity: u16,
+
+    /// Calls not yet consumed by record_meter_payment
+    pub calls_remaining: u16,
+
+    /// Slot the authorization was issued at
+    pub created_at_slot: u64,
+
+    /// Account that paid the rent; receives it back on close_authorization
+    pub rent_payer: Pubkey,
+
+    /// Cancelled by the agent or policy owner via revoke_authorization
+    pub revoked: bool,
+
+    /// Part of `amount` not yet charged by record_meter_payment
+    pub amount_remaining: u64,
+
+    /// Streaming only: amount unlocked per slot since `start_slot`
+    pub rate_per_slot: u64,
+
+    /// Streaming only: slot unlocking starts from
+    pub start_slot: u64,
+
+    /// The meter's per-call price when the authorization was issued
+    pub price_at_authorization: u64,
+
+    /// Caller-supplied request id, e.g. the x402 payment id (all zero = none)
+    pub request_id: [u8; 32],
+
+    /// Whether the authorization also expires by wall clock (see `expiry_kinds`)
+    pub expiry_kind: u8,
+
+    /// Unix timestamp after which a wall-clock authorization is invalid
+    /// (0 for slot expiry). `expires_at_slot` still applies as a backstop.
+    pub expires_at_unix: i64,
+
+    /// The only key that may record payments against it (default = anyone)
+    pub consumer: Pubkey,
+
+    /// Issued after verifying a ZK proof (false for authorize_payment_simple)
+    pub zk_verified: bool,
+
+    /// Escrow token account holding the unreleased amount (default = not
+    /// escrowed, or already paid out or refunded)
+    pub escrow: Pubkey,
+
+    /// The settlement authority confirmed the off-chain transfer
+    pub settled: bool,
+
+    /// Settlement service's reference for the transfer (set with `settled`)
+    pub settlement_ref: [u8; 32],
+
+    /// A failed settlement was reported and the authorization re-opened
+    /// for its one retry
+    pub settlement_failed: bool,
+
+    /// The merchant was paid on-chain from the escrow, so there is no
+    /// off-chain transfer to retry
+    pub settled_from_escrow: bool,
+
+    /// Slot of the latest record_meter_payment (0 = never recorded)
+    pub last_recorded_slot: u64,
+
+    /// A dispute is open (see `Dispute`); settlement should wait for it
+    pub disputed: bool,
+
+    /// Total refunded by the merchant through issue_refund
+    pub refunded_amount: u64,
+
+    /// The policy's `policy_hash` when issued; recording fails once the
+    /// policy is replaced (all zero for authorizations predating it)
+    pub policy_hash_at_auth: [u8; 32],
+
+    /// `external_id` of the latest successful record (all zero = none)
+    pub external_id: [u8; 16],
+
+    /// SHA-256 of the secret that must be revealed to record (all zero = none)
+    pub hash_lock: [u8; 32],
+
+    /// Part of `amount` the agent added as a tip (0 = none); charged last
+    pub tip: u64,
+
+    /// Version of the proof it was issued on (0 = issued without a proof)
+    pub proof_version: u8,
+
+    /// Issued on a policy oracle attestation (authorize_payment_attested)
+    /// rather than a proof
+    pub attested: bool,
+
+    /// How the payment was checked (see `verification_methods`); NONE for
+    /// authorizations issued before this field was added
+    pub verification_method: u8,
+
+    /// What checked it: the VerifyingKey, the verifier program or the
+    /// policy oracle (default address for NONE)
+    pub verifier_ref: Pubkey,
+}
+
+impl Authorization {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+
+    /// Current layout version
+    pub const VERSION: u8 = 2;
+
+    /// Length of the version 1 layout, the first with `version`. Later
+    /// versions only append fields.
+    pub const VERSION_1_LEN: usize = 459;
+
+    /// Offset of `kind` in the layouts before `version`, which had no
+    /// version and kept `kind` after `amount_remaining`
+    pub const LEGACY_KIND_OFFSET: usize = 8 + 32 + 32 + 8 + 1 + 8 + 8 + 1 + 1 + 2 + 32 + 2 + 2 + 8 + 32 + 1 + 8;
+
+    /// Records how the payment was checked.
+    fn set_verification(&mut self, verification: Verification) {
+        self.zk_verified = verification.zk_verified();
+        self.verification_method = verification.method;
+        self.verifier_ref = verification.verifier_ref;
+    }
+
+    /// Fails with `WrongAuthorizationKind` unless `kind` is one of `kinds`.
+    pub fn require_kind(&self, kinds: &[u8]) -> Result<()> {
+        require!(kinds.contains(&self.kind), AgentBlinkPayError::WrongAuthorizationKind);
+        Ok(())
+    }
+
+    /// Rewrites the data of an authorization in an older layout (an older
+    /// `version`, or no `version` and `kind` at `LEGACY_KIND_OFFSET` or
+    /// absent) into the current one. Fields the old layout didn't have yet
+    /// are zeroed.
+    pub fn migrate_layout(data: &[u8]) -> Vec<u8> {
+        let mut migrated = Vec::with_capacity(Self::LEN);
+        if data.len() >= Self::VERSION_1_LEN {
+            migrated.extend_from_slice(data);
+            migrated[8] = Self::VERSION;
+            migrated.resize(Self::LEN, 0);
+            return migrated;
+        }
+        migrated.extend_from_slice(&data[..8]);
+        migrated.push(Self::VERSION);
+        if data.len() > Self::LEGACY_KIND_OFFSET {
+            migrated.push(data[Self::LEGACY_KIND_OFFSET]);
+            migrated.extend_from_slice(&data[8..Self::LEGACY_KIND_OFFSET]);
+            migrated.extend_from_slice(&data[Self::LEGACY_KIND_OFFSET + 1..]);
+        } else {
+            // Authorizations from before `kind` were all standard
+            migrated.push(authorization_kinds::STANDARD);
+            migrated.extend_from_slice(&data[8..]);
+        }
+        migrated.resize(Self::LEN, 0);
+        migrated
+    }
+
+    /// Why the authorization may be closed (a `close_reasons` constant), or
+    /// None while it can still be consumed (including the Config's grace
+    /// window), still holds escrow, is disputed, or was recorded recently
+    /// enough to still be disputed or refunded.
+    pub fn close_reason(&self, clock: &Clock, config: &Config) -> Option<u8> {
+        if self.escrow != Pubkey::default() || self.disputed || self.in_review(clock.slot, config) {
+            None
+        } else if self.revoked {
+            Some(close_reasons::REVOKED)
+        } else if self.used {
+            Some(close_reasons::USED)
+        } else if self.is_expired_after(clock, config.grace_slots) {
+            Some(close_reasons::EXPIRED)
+        } else {
+            None
+        }
+    }
+
+    /// True while the last record is within the Config's dispute or refund
+    /// window, so the account must stay for `open_dispute`, `issue_refund`
+    /// and `report_settlement_failure`.
+    pub fn in_review(&self, current_slot: u64, config: &Config) -> bool {
+        let window = config.dispute_window_slots.max(config.refund_window_slots);
+        window > 0
+            && self.last_recorded_slot != 0
+            && current_slot <= self.last_recorded_slot.saturating_add(window)
+    }
+
+    /// True once the authorization is past its expiry.
+    pub fn is_expired(&self, clock: &Clock) -> bool {
+        self.is_expired_after(clock, 0)
+    }
+
+    /// True once the authorization is past its expiry plus `grace_slots`,
+    /// and can no longer be consumed. The grace only extends the slot
+    /// expiry; a wall-clock expiry is exact.
+    pub fn is_expired_after(&self, clock: &Clock, grace_slots: u16) -> bool {
+        clock.slot > self.expires_at_slot.saturating_add(grace_slots as u64)
+            || (self.expiry_kind == expiry_kinds::UNIX && clock.unix_timestamp > self.expires_at_unix)
+    }
+
+    /// The share of `amount` paid by each recorded call.
+    pub fn per_call_amount(&self) -> u64 {
+        self.amount / self.quantity.max(1) as u64
+    }
+
+    /// What a `consume_all` record charges: the per-call share, or
+    /// everything left on the last call. A stream charges all it has
+    /// unlocked and not yet consumed.
+    pub fn next_call_amount(&self, current_slot: u64) -> u64 {
+        if self.kind == authorization_kinds::STREAMING {
+            self.claimable(current_slot)
+        } else if self.calls_remaining > 1 {
+            self.per_call_amount().min(self.amount_remaining)
+        } else {
+            self.amount_remaining
+        }
+    }
+
+    /// Amount charged so far.
+    pub fn consumed(&self) -> u64 {
+        self.amount - self.amount_remaining
+    }
+
+    /// The tip part of a charge of `amount` made after `consumed` was
+    /// already charged. The price is charged first, the tip last.
+    pub fn tip_in(&self, consumed: u64, amount: u64) -> u64 {
+        let price_left = (self.amount - self.tip).saturating_sub(consumed);
+        amount.saturating_sub(price_left).min(self.tip)
+    }
+
+    /// Most that may have been consumed by `current_slot`: all of `amount`,
+    /// or for a stream `rate_per_slot` per elapsed slot up to `amount`.
+    pub fn unlocked(&self, current_slot: u64) -> u64 {
+        if self.kind != authorization_kinds::STREAMING {
+            return self.amount;
+        }
+        let elapsed = current_slot.saturating_sub(self.start_slot);
+        self.rate_per_slot.saturating_mul(elapsed).min(self.amount)
+    }
+
+    /// Amount the next record may still charge at `current_slot`.
+    pub fn claimable(&self, current_slot: u64) -> u64 {
+        self.unlocked(current_slot).saturating_sub(self.consumed())
+    }
+
+    fn created_event(&self, authorization: Pubkey, slot: u64) -> AuthorizationCreated {
+        AuthorizationCreated {
+            authorization,
+            agent: self.agent,
+            meter: self.meter,
+            nonce: self.nonce,
+            policy_id: self.policy_id,
+            amount: self.amount,
+            quantity: self.quantity,
+            category: self.category,
+            expires_at_slot: self.expires_at_slot,
+            kind: self.kind,
+            rate_per_slot: self.rate_per_slot,
+            zk_verified: self.zk_verified,
+            attested: self.attested,
+            verification_method: self.verification_method,
+            verifier_ref: self.verifier_ref,
+            request_id: self.request_id,
+            expiry_kind: self.expiry_kind,
+            expires_at_unix: self.expires_at_unix,
+            consumer: self.consumer,
+            escrow: self.escrow,
+            hash_lock: self.hash_lock,
+            slot,
+        }
+    }
+}
+
+/// Call and volume counters for one (agent, meter) pair.
+/// 
+/// PDA seeds: ["usage", meter_pubkey, agent_pubkey]
+/// 
+/// Created on the agent's first authorization against the meter (every
+/// recorded payment has one) and updated by `record_meter_payment`; selects
+/// the meter's price tier, tracks the agent's use of the meter's free calls
+/// and gives merchants per-agent analytics.
+/// 
+/// New fields are carved out of `reserved` and bump `version`, so the
+/// account can grow without another realloc migration.
+#[account]
+#[derive(Default)]
+pub struct MeterUsage {
+    /// The meter being counted
+    pub meter: Pubkey,
+
+    /// The paying agent
+    pub agent: Pubkey,
+
+    /// Payments recorded by this agent against this meter
+    pub calls: u64,
+
+    /// PDA bump seed
+    pub bump: u8,
+
+    /// Free calls authorized so far (see `Meter::free_calls`)
+    pub free_calls_used: u16,
+
+    /// Layout version (see `MeterUsage::VERSION`)
+    pub version: u8,
+
+    /// Amount recorded by this agent against this meter
+    pub volume: u64,
+
+    /// Slot of the agent's first recorded payment (0 = none yet)
+    pub first_call_slot: u64,
+
+    /// Space for future fields
+    pub reserved: [u8; 32],
+}
+
+impl MeterUsage {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // meter
+        32 +                    // agent
+        8 +                     // calls
+        1 +                     // bump
+        2 +                     // free_calls_used
+        1 +                     // version
+        8 +                     // volume
+        8 +                     // first_call_slot
+        32;                     // reserved
+
+    /// Current layout version.
+    pub const VERSION: u8 = 1;
+
+    /// Byte offset of `version`, used by `migrate_meter_usage`.
+    pub const VERSION_OFFSET: usize = 8 + 32 + 32 + 8 + 1 + 2;
+}
+
+/// An agent's subscription to a subscription meter.
+/// 
+/// PDA seeds: ["sub", meter_pubkey, agent_pubkey]
+#[account]
+#[derive(Default)]
+pub struct Subscription {
+    /// The subscription meter
+    pub meter: Pubkey,
+
+    /// The subscribed agent
+    pub agent: Pubkey,
+
+    /// Policy that paid the most recent fee
+    pub policy_id: u16,
+
+    /// Calls are covered while current_slot <= expires_at_slot
+    pub expires_at_slot: u64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Subscription {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // meter
+        32 +                    // agent
+        2 +                     // policy_id
+        8 +                     // expires_at_slot
+        1;                      // bump
+}
+
+/// Allowlist record for one (policy, meter) pair.
+/// 
+/// PDA seeds: ["allowed", agent_policy, meter_pubkey]
+/// 
+/// Its existence is what matters: policies with `enforce_meter_allowlist`
+/// only accept payments to meters that have one.
+#[account]
+#[derive(Default)]
+pub struct AllowedMeter {
+    /// The agent this entry applies to
+    pub agent: Pubkey,
+
+    /// The policy this entry belongs to
+    pub policy: Pubkey,
+
+    /// The allowed meter
+    pub meter: Pubkey,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl AllowedMeter {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // agent
+        32 +                    // policy
+        32 +                    // meter
+        1;                      // bump
+}
+
+/// Next nonce for `authorize_payment_auto_nonce`, per (agent, meter) pair.
+/// 
+/// PDA seeds: ["nonce", agent_pubkey, meter_pubkey]
+/// 
+/// Created on the pair's first auto-nonce authorization.
+#[account]
+#[derive(Default)]
+pub struct NonceCounter {
+    /// The authorizing agent
+    pub agent: Pubkey,
+
+    /// The meter being paid
+    pub meter: Pubkey,
+
+    /// Nonce the next auto-nonce authorization will use
+    pub next_nonce: u64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl NonceCounter {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // agent
+        32 +                    // meter
+        8 +                     // next_nonce
+        1;                      // bump
+}
+
+/// Budget authorization: one proof covering many payments to a meter.
+/// 
+/// PDA seeds: ["budget_auth", agent_pubkey, meter_pubkey, nonce]
+/// 
+/// Created by authorize_budget_with_proof, drawn down by
+/// record_budget_payment and closed by close_budget_authorization.
+#[account]
+#[derive(Default)]
+pub struct BudgetAuthorization {
+    /// The agent making the payments
+    pub agent: Pubkey,
+
+    /// The meter being paid
+    pub meter: Pubkey,
+
+    /// The agent policy the budget was issued under
+    pub policy_id: u16,
+
+    /// Unique nonce to prevent replay attacks
+    pub nonce: u64,
+
+    /// Token the payments settle in (the meter's accepted_mint when issued)
+    pub mint: Pubkey,
+
+    /// Category of the payments
+    pub category: u8,
+
+    /// Most all draws together may pay
+    pub total_amount: u64,
+
+    /// Part of `total_amount` not yet drawn
+    pub remaining: u64,
+
+    /// Slot after which no more draws are accepted (plus the grace window)
+    pub expires_at_slot: u64,
+
+    /// Slot the budget was authorized at
+    pub created_at_slot: u64,
+
+    /// The meter's per-call price when the budget was authorized
+    pub price_at_authorization: u64,
+
+    /// The policy's `policy_hash` when authorized
+    pub policy_hash_at_auth: [u8; 32],
+
+    /// Account that paid the rent; receives it back on close
+    pub rent_payer: Pubkey,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl BudgetAuthorization {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // agent
+        32 +                    // meter
+        2 +                     // policy_id
+        8 +                     // nonce
+        32 +                    // mint
+        1 +                     // category
+        8 +                     // total_amount
+        8 +                     // remaining
+        8 +                     // expires_at_slot
+        8 +                     // created_at_slot
+        8 +                     // price_at_authorization
+        32 +                    // policy_hash_at_auth
+        32 +                    // rent_payer
+        1;                      // bump
+
+    /// Why the budget can be closed now (see `close_reasons`), or None
+    /// while it can still be drawn from.
+    pub fn close_reason(&self, current_slot: u64, config: &Config) -> Option<u8> {
+        if self.remaining == 0 {
+            Some(close_reasons::USED)
+        } else if self.is_expired_after(current_slot, config.grace_slots) {
+            Some(close_reasons::EXPIRED)
+        } else {
+            None
+        }
+    }
+
+    /// True once `grace_slots` past `expires_at_slot`.
+    pub fn is_expired_after(&self, current_slot: u64, grace_slots: u16) -> bool {
+        current_slot > self.expires_at_slot.saturating_add(grace_slots as u64)
+    }
+}
+
+/// Proof that an agent paid a meter, for other programs to check without
+/// an event indexer.
+/// 
+/// PDA seeds: ["receipt", meter_pubkey, agent_pubkey, nonce (u64 LE)],
+/// with `nonce` the authorization's. A program gating access derives the
+/// address from those seeds, checks this program owns it and deserializes
+/// it (or reads `amount` at byte 8 and `slot` at byte 16, little endian).
+/// 
+/// Created by record_meter_payment with `create_receipt` and closed by
+/// close_receipt; it is not touched when the authorization is closed.
+#[account]
+#[derive(Default)]
+pub struct Receipt {
+    /// Total recorded against the authorization with a receipt requested
+    pub amount: u64,
+
+    /// Slot of the latest such record
+    pub slot: u64,
+
+    /// The authorization's `request_id` (all zero = none)
+    pub request_id: [u8; 32],
+
+    /// Account that paid the rent; receives it back on close
+    pub rent_payer: Pubkey,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Receipt {
+    pub const LEN: usize = 8 +  // discriminator
+        8 +                     // amount
+        8 +                     // slot
+        32 +                    // request_id
+        32 +                    // rent_payer
+        1;                      // bump
+}
+
+/// Bundle authorization: one payment to each of up to `MAX_BUNDLE_METERS`
+/// meters under one proof.
+/// 
+/// PDA seeds: ["bundle_auth", agent_pubkey, nonce]
+/// 
+/// Created by authorize_bundle_with_proof, consumed slice by slice by
+/// record_bundle_payment and closed by close_bundle_authorization. Unused
+/// entries past `meter_count` are zero.
+#[account]
+#[derive(Default)]
+pub struct BundleAuthorization {
+    /// The agent making the payments
+    pub agent: Pubkey,
+
+    /// The agent policy the bundle was issued under
+    pub policy_id: u16,
+
+    /// Unique nonce to prevent replay attacks
+    pub nonce: u64,
+
+    /// The policy's category, shared by every meter in the bundle
+    pub category: u8,
+
+    /// Number of meters in the bundle
+    pub meter_count: u8,
+
+    /// The meters, in authorization order
+    pub meters: [Pubkey; MAX_BUNDLE_METERS],
+
+    /// Token each meter settles in (its accepted_mint when issued)
+    pub mints: [Pubkey; MAX_BUNDLE_METERS],
+
+    /// Amount authorized for each meter
+    pub amounts: [u64; MAX_BUNDLE_METERS],
+
+    /// Amount not yet recorded for each meter (0 once recorded)
+    pub remaining: [u64; MAX_BUNDLE_METERS],
+
+    /// Each meter's per-call price when the bundle was authorized
+    pub prices_at_authorization: [u64; MAX_BUNDLE_METERS],
+
+    /// Slot after which no slice can be recorded (plus the grace window)
+    pub expires_at_slot: u64,
+
+    /// Slot the bundle was authorized at
+    pub created_at_slot: u64,
+
+    /// The policy's `policy_hash` when authorized
+    pub policy_hash_at_auth: [u8; 32],
+
+    /// Account that paid the rent; receives it back on close
+    pub rent_payer: Pubkey,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl BundleAuthorization {
+    pub const LEN: usize = 8 +                  // discriminator
+        32 +                                    // agent
+        2 +                                     // policy_id
+        8 +                                     // nonce
+        1 +                                     // category
+        1 +                                     // meter_count
+        32 * MAX_BUNDLE_METERS +                // meters
+        32 * MAX_BUNDLE_METERS +                // mints
+        8 * MAX_BUNDLE_METERS +                 // amounts
+        8 * MAX_BUNDLE_METERS +                 // remaining
+        8 * MAX_BUNDLE_METERS +                 // prices_at_authorization
+        8 +                                     // expires_at_slot
+        8 +                                     // created_at_slot
+        32 +                                    // policy_hash_at_auth
+        32 +                                    // rent_payer
+        1;                                      // bump
+
+    /// Why the bundle can be closed now (see `close_reasons`), or None
+    /// while a slice can still be recorded.
+    pub fn close_reason(&self, current_slot: u64, config: &Config) -> Option<u8> {
+        if self.remaining.iter().all(|remaining| *remaining == 0) {
+            Some(close_reasons::USED)
+        } else if self.is_expired_after(current_slot, config.grace_slots) {
+            Some(close_reasons::EXPIRED)
+        } else {
+            None
+        }
+    }
+
+    /// True once `grace_slots` past `expires_at_slot`.
+    pub fn is_expired_after(&self, current_slot: u64, grace_slots: u16) -> bool {
+        current_slot > self.expires_at_slot.saturating_add(grace_slots as u64)
+    }
+}
+
+/// Unidirectional payment channel from an agent to a meter.
+/// 
+/// PDA seeds: ["channel", agent_pubkey, meter_pubkey]
+/// 
+/// Created by open_channel, advanced by settle_channel and closed by
+/// close_channel; one open channel per (agent, meter) pair.
+#[account]
+#[derive(Default)]
+pub struct Channel {
+    /// The agent paying through the channel (signs the vouchers)
+    pub agent: Pubkey,
+
+    /// The meter being paid
+    pub meter: Pubkey,
+
+    /// The agent policy settles are charged to
+    pub policy_id: u16,
+
+    /// Token the payments settle in (the meter's accepted_mint when opened)
+    pub mint: Pubkey,
+
+    /// The meter's category when opened
+    pub category: u8,
+
+    /// Most the vouchers can claim in total
+    pub deposit_cap: u64,
+
+    /// Cumulative amount of the latest settled voucher; only increases
+    pub claimed: u64,
+
+    /// Slot after which no more vouchers are settled (plus the grace window)
+    pub expires_at_slot: u64,
+
+    /// Slot the channel was opened at; part of every voucher, so vouchers
+    /// of an earlier channel at the same address don't verify
+    pub opened_at_slot: u64,
+
+    /// The meter's per-call price when opened
+    pub price_at_open: u64,
+
+    /// The policy's `policy_hash` when opened
+    pub policy_hash_at_open: [u8; 32],
+
+    /// Account that paid the rent; receives it back on close
+    pub rent_payer: Pubkey,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Channel {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // agent
+        32 +                    // meter
+        2 +                     // policy_id
+        32 +                    // mint
+        1 +                     // category
+        8 +                     // deposit_cap
+        8 +                     // claimed
+        8 +                     // expires_at_slot
+        8 +                     // opened_at_slot
+        8 +                     // price_at_open
+        32 +                    // policy_hash_at_open
+        32 +                    // rent_payer
+        1;                      // bump
+
+    /// True once `grace_slots` past `expires_at_slot`.
+    pub fn is_expired_after(&self, current_slot: u64, grace_slots: u16) -> bool {
+        current_slot > self.expires_at_slot.saturating_add(grace_slots as u64)
+    }
+}
+
+/// An open dispute over a recorded payment.
+/// 
+/// PDA seeds: ["dispute", authorization_pubkey]
+/// 
+/// Created by open_dispute and closed by resolve_dispute.
+#[account]
+#[derive(Default)]
+pub struct Dispute {
+    /// The disputed authorization
+    pub authorization: Pubkey,
+
+    /// The agent or meter authority who opened it; receives the rent back
+    pub opened_by: Pubkey,
+
+    /// Caller-defined reason
+    pub reason_code: u8,
+
+    /// Slot the dispute was opened at
+    pub opened_at_slot: u64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Dispute {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // authorization
+        32 +                    // opened_by
+        1 +                     // reason_code
+        8 +                     // opened_at_slot
+        1;                      // bump
+}
+
+/// Meter-side allowlist record for one (meter, agent) pair.
+/// 
+/// PDA seeds: ["access", meter_pubkey, agent_pubkey]
+/// 
+/// Created by the meter authority. Meters with `allowlist_enabled` only
+/// accept payments from agents that have one.
+#[account]
+#[derive(Default)]
+pub struct MeterAgentAccess {
+    /// The meter granting access
+    pub meter: Pubkey,
+
+    /// The agent allowed to pay the meter
+    pub agent: Pubkey,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl MeterAgentAccess {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // meter
+        32 +                    // agent
+        1;                      // bump
+}
+
+/// An agent's prepaid balance on a per-call meter.
+/// 
+/// PDA seeds: ["credit", meter_pubkey, agent_pubkey]
+#[account]
+#[derive(Default)]
+pub struct Credit {
+    /// The meter the credit can be spent on
+    pub meter: Pubkey,
+
+    /// The agent holding the credit
+    pub agent: Pubkey,
+
+    /// Policy that paid for the most recent purchase
+    pub policy_id: u16,
+
+    /// Unspent credit in USDC smallest units
+    pub remaining: u64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Credit {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // meter
+        32 +                    // agent
+        2 +                     // policy_id
+        8 +                     // remaining
+        1;                      // bump
+}
+
+/// The meters created by one authority, so clients can list them with a
+/// single account fetch instead of `getProgramAccounts`.
+/// 
+/// PDA seeds: ["meter_index", authority_pubkey]
+/// 
+/// Keyed by the creating authority: meters stay listed under their creator
+/// after `transfer_meter_authority`. Meters created before the index
+/// existed are not listed. The account grows one entry at a time up to
+/// `MAX_METERS_PER_AUTHORITY` and never shrinks.
 #[account]
 #[derive(Default)]
-pub struct AgentPolicy {
-    /// The agent's public key (payment identity)
+pub struct MeterIndex {
+    /// Authority whose meters are listed
+    pub authority: Pubkey,
+
+    /// Number of listed meters (always meters.len())
+    pub count: u32,
+
+    /// Listed meters, in no particular order
+    pub meters: Vec<Pubkey>,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl MeterIndex {
+    /// Account size needed to list `meters` meters.
+    pub const fn space_for(meters: usize) -> usize {
+        8 +                     // discriminator
+        32 +                    // authority
+        4 +                     // count
+        4 + 32 * meters +       // meters
+        1                       // bump
+    }
+
+    /// Appends `meter`, growing the account by one entry if needed.
+    /// Initializes the index on first use.
+    fn push<'info>(
+        index: &mut Account<'info, MeterIndex>,
+        authority: Pubkey,
+        bump: u8,
+        meter: Pubkey,
+        payer: &Signer<'info>,
+        system_program: &Program<'info, System>,
+    ) -> Result<()> {
+        if index.authority == Pubkey::default() {
+            // Freshly created by init_if_needed
+            index.authority = authority;
+            index.bump = bump;
+        }
+        require!(
+            index.meters.len() < MAX_METERS_PER_AUTHORITY,
+            AgentBlinkPayError::MeterIndexFull
+        );
+
+        // A fresh account is created with room for one entry and has no
+        // discriminator yet, so only grow when that room is used up
+        let new_len = Self::space_for(index.meters.len() + 1);
+        let index_info = index.to_account_info();
+        if index_info.data_len() < new_len {
+            grow_account(&index_info, MeterIndex::DISCRIMINATOR, new_len, payer, system_program)?;
+        }
+
+        index.meters.push(meter);
+        index.count = index.meters.len() as u32;
+
+        Ok(())
+    }
+
+    /// Removes `meter` if listed.
+    pub fn remove(&mut self, meter: &Pubkey) {
+        if let Some(position) = self.meters.iter().position(|listed| listed == meter) {
+            self.meters.swap_remove(position);
+            self.count = self.meters.len() as u32;
+        }
+    }
+}
+
+/// Denylist record for one (policy, meter) pair.
+/// 
+/// PDA seeds: ["denied", agent_policy, meter_pubkey]
+/// 
+/// While it exists, the agent can't authorize payments to the meter under
+/// that policy.
+#[account]
+#[derive(Default)]
+pub struct DeniedMeter {
+    /// The agent this entry applies to
+    pub agent: Pubkey,
+
+    /// The policy this entry belongs to
+    pub policy: Pubkey,
+
+    /// The banned meter
+    pub meter: Pubkey,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl DeniedMeter {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // agent
+        32 +                    // policy
+        32 +                    // meter
+        1;                      // bump
+}
+
+/// Tombstone of an agent key retired by `rotate_agent_key`.
+/// 
+/// PDA seeds: ["retired", agent_pubkey]
+/// 
+/// While it exists, no policy can be created for the key, so a leaked key
+/// can't re-create the policy PDA it was rotated away from. Never closed.
+#[account]
+#[derive(Default)]
+pub struct RetiredAgent {
+    /// The retired agent key
+    pub agent: Pubkey,
+
+    /// The key its latest rotated policy moved to
+    pub new_agent: Pubkey,
+
+    /// Slot of the first rotation
+    pub retired_at_slot: u64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl RetiredAgent {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // agent
+        32 +                    // new_agent
+        8 +                     // retired_at_slot
+        1;                      // bump
+}
+
+/// Global program configuration.
+/// 
+/// PDA seeds: ["config"]
+/// 
+/// A single account holding program-wide settings, created once by
+/// `initialize_config` and managed by `admin`.
+#[account]
+#[derive(Default)]
+pub struct Config {
+    /// Key allowed to update this account
+    pub admin: Pubkey,
+
+    /// Keys allowed to report failed authorizations (default = empty slot)
+    pub watchers: [Pubkey; 4], // MAX_WATCHERS
+
+    /// Reported failures after which a policy auto-freezes (0 = disabled)
+    pub auto_freeze_threshold: u16,
+
+    /// PDA bump seed
+    pub bump: u8,
+
+    /// Delay before a meter price increase takes effect
+    pub min_price_delay_slots: u64,
+
+    /// Lowest `price_per_call` a meter may be created or updated with
+    pub min_price_per_call: u64,
+
+    /// Highest `price_per_call` a meter may be created or updated with (0 = unbounded)
+    pub max_price_per_call: u64,
+
+    /// Mint meters settle in unless created with another `accepted_mint`
+    pub usdc_mint: Pubkey,
+
+    /// Longest wall-clock TTL of an authorization, in seconds (0 = wall-clock
+    /// expiry disabled)
+    pub max_authorization_ttl_secs: u32,
+
+    /// Slots past `expires_at_slot` in which an authorization may still be
+    /// recorded, flagged `recorded_in_grace` (at most MAX_GRACE_SLOTS)
+    pub grace_slots: u16,
+
+    /// Key of the Circle service that confirms settlements (default = none)
+    pub settlement_authority: Pubkey,
+
+    /// Slots after an authorization's last record in which it may be
+    /// disputed (0 = disputes disabled)
+    pub dispute_window_slots: u64,
+
+    /// Slots after an authorization's last record in which its merchant
+    /// may refund it (0 = refunds disabled)
+    pub refund_window_slots: u64,
+
+    /// External (Sunspot-generated) verifier program, used when
+    /// `external_verifier` is set
+    pub verifier_program: Pubkey,
+
+    /// Verify proofs by CPI to `verifier_program` instead of this
+    /// program's `verify_proof`
+    pub external_verifier: bool,
+
+    /// What a failed proof does (see `enforcement_modes`); once STRICT it
+    /// can't be relaxed
+    pub enforcement_mode: u8,
+
+    /// Key whose Ed25519 attestations `authorize_payment_attested` accepts
+    /// in place of a proof (default = attestations disabled)
+    pub policy_oracle: Pubkey,
+}
+
+impl Config {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // admin
+        32 * MAX_WATCHERS +     // watchers
+        2 +                     // auto_freeze_threshold
+        1 +                     // bump
+        8 +                     // min_price_delay_slots
+        8 +                     // min_price_per_call
+        8 +                     // max_price_per_call
+        32 +                    // usdc_mint
+        4 +                     // max_authorization_ttl_secs
+        2 +                     // grace_slots
+        32 +                    // settlement_authority
+        8 +                     // dispute_window_slots
+        8 +                     // refund_window_slots
+        32 +                    // verifier_program
+        1 +                     // external_verifier
+        1 +                     // enforcement_mode
+        32;                     // policy_oracle
+
+    /// Byte offset of `enforcement_mode`, used by `migrate_config`.
+    pub const ENFORCEMENT_MODE_OFFSET: usize =
+        8 + 32 + 32 * MAX_WATCHERS + 2 + 1 + 8 + 8 + 8 + 32 + 4 + 2 + 32 + 8 + 8 + 32 + 1;
+
+    /// True if `key` is one of the configured watchers.
+    pub fn is_watcher(&self, key: &Pubkey) -> bool {
+        *key != Pubkey::default() && self.watchers.contains(key)
+    }
+
+    /// Program that verifies proofs: the external verifier when enabled,
+    /// otherwise this program.
+    pub fn verifier(&self) -> Pubkey {
+        if self.external_verifier {
+            self.verifier_program
+        } else {
+            crate::ID
+        }
+    }
+
+    fn apply(&mut self, params: &ConfigParams) -> Result<()> {
+        require!(
+            params.grace_slots <= MAX_GRACE_SLOTS,
+            AgentBlinkPayError::GraceSlotsTooLarge
+        );
+        require!(
+            !params.external_verifier || params.verifier_program != Pubkey::default(),
+            AgentBlinkPayError::VerifierProgramMissing
+        );
+        require!(
+            params.enforcement_mode <= enforcement_modes::STRICT,
+            AgentBlinkPayError::InvalidEnforcementMode
+        );
+        require!(
+            self.enforcement_mode != enforcement_modes::STRICT
+                || params.enforcement_mode == enforcement_modes::STRICT,
+            AgentBlinkPayError::EnforcementModeLocked
+        );
+
+        self.watchers = params.watchers;
+        self.auto_freeze_threshold = params.auto_freeze_threshold;
+        self.min_price_delay_slots = params.min_price_delay_slots;
+        self.min_price_per_call = params.min_price_per_call;
+        self.max_price_per_call = params.max_price_per_call;
+        self.usdc_mint = params.usdc_mint;
+        self.max_authorization_ttl_secs = params.max_authorization_ttl_secs;
+        self.grace_slots = params.grace_slots;
+        self.settlement_authority = params.settlement_authority;
+        self.dispute_window_slots = params.dispute_window_slots;
+        self.refund_window_slots = params.refund_window_slots;
+        self.verifier_program = params.verifier_program;
+        self.external_verifier = params.external_verifier;
+        self.enforcement_mode = params.enforcement_mode;
+        self.policy_oracle = params.policy_oracle;
+
+        Ok(())
+    }
+
+    /// Rejects meter prices outside the configured bounds (inclusive).
+    pub fn check_price(&self, price_per_call: u64) -> Result<()> {
+        require!(
+            price_per_call >= self.min_price_per_call
+                && (self.max_price_per_call == 0 || price_per_call <= self.max_price_per_call),
+            AgentBlinkPayError::PriceOutOfBounds
+        );
+        Ok(())
+    }
+}
+
+/// Groth16 verifying key of one circuit.
+/// 
+/// PDA seeds: ["vk", circuit_id]
+/// 
+/// Set by the Config admin with `set_verifying_key`. Points are encoded as
+/// the alt_bn128 syscalls take them (G2 coordinates imaginary part first).
+/// The account is sized for MAX_VK_PUBLIC_INPUTS, so a key with fewer
+/// inputs can later be replaced by one with more.
+#[account]
+pub struct VerifyingKey {
+    /// The circuit this key verifies (see `circuit_ids`)
+    pub circuit_id: u8,
+
+    /// Version byte proofs for this key must start with
+    pub circuit_version: u8,
+
+    /// Frozen for good; `set_verifying_key` rejects further updates
+    pub locked: bool,
+
+    /// PDA bump seed
+    pub bump: u8,
+
+    pub alpha_g1: [u8; 64],
+    pub beta_g2: [u8; 128],
+    pub gamma_g2: [u8; 128],
+    pub delta_g2: [u8; 128],
+
+    /// The constant term, then one point per public input
+    pub ic: Vec<[u8; 64]>,
+
+    /// Meters whose `circuit_id` is this circuit; the key can't be removed
+    /// while any are left
+    pub meter_count: u32,
+}
+
+// Arrays over 32 elements don't implement Default, so it can't be derived
+impl Default for VerifyingKey {
+    fn default() -> Self {
+        Self {
+            circuit_id: 0,
+            circuit_version: 0,
+            locked: false,
+            bump: 0,
+            alpha_g1: [0; 64],
+            beta_g2: [0; 128],
+            gamma_g2: [0; 128],
+            delta_g2: [0; 128],
+            ic: Vec::new(),
+            meter_count: 0,
+        }
+    }
+}
+
+impl VerifyingKey {
+    pub const LEN: usize = 8 +  // discriminator
+        1 +                     // circuit_id
+        1 +                     // circuit_version
+        1 +                     // locked
+        1 +                     // bump
+        64 +                    // alpha_g1
+        128 * 3 +               // beta_g2, gamma_g2, delta_g2
+        4 + 64 * (MAX_VK_PUBLIC_INPUTS + 1) + // ic
+        4;                      // meter_count
+
+    /// Number of public inputs the circuit takes.
+    pub fn num_public_inputs(&self) -> usize {
+        self.ic.len().saturating_sub(1)
+    }
+}
+
+/// A circuit's UltraHonk verifier, installed by the Config admin.
+/// 
+/// PDA seeds: ["honk_vk", circuit_id]
+/// 
+/// UltraHonk proofs are verified by CPI to a Sunspot-generated Honk
+/// verifier program, which embeds the circuit's key; this account names
+/// that program. A circuit has either this or a Groth16 `VerifyingKey`,
+/// never both, so the registry and not the proof decides how a circuit's
+/// proofs are verified.
+#[account]
+#[derive(Default)]
+pub struct HonkVerifyingKey {
+    /// The circuit this key verifies (see `circuit_ids`)
+    pub circuit_id: u8,
+
+    /// Version byte proofs for this key must start with
+    pub circuit_version: u8,
+
+    /// Frozen for good; `set_honk_verifying_key` rejects further updates
+    pub locked: bool,
+
+    /// PDA bump seed
+    pub bump: u8,
+
+    /// The Honk verifier program generated for the circuit
+    pub verifier_program: Pubkey,
+
+    /// SHA-256 of the verifying key `verifier_program` embeds, for audits
+    pub vk_hash: [u8; 32],
+
+    /// Meters whose `circuit_id` is this circuit; the key can't be removed
+    /// while any are left
+    pub meter_count: u32,
+}
+
+impl HonkVerifyingKey {
+    pub const LEN: usize = 8 +  // discriminator
+        1 +                     // circuit_id
+        1 +                     // circuit_version
+        1 +                     // locked
+        1 +                     // bump
+        32 +                    // verifier_program
+        32 +                    // vk_hash
+        4;                      // meter_count
+}
+
+/// `meter_count` of a circuit's key, whichever scheme it is registered
+/// with. Fails with `VerifyingKeyMissing` if neither key was passed.
+fn circuit_meter_count<'a, 'info>(
+    verifying_key: Option<&'a mut Account<'info, VerifyingKey>>,
+    honk_verifying_key: Option<&'a mut Account<'info, HonkVerifyingKey>>,
+) -> Result<&'a mut u32> {
+    match (verifying_key, honk_verifying_key) {
+        (Some(vk), _) => Ok(&mut vk.meter_count),
+        (None, Some(vk)) => Ok(&mut vk.meter_count),
+        (None, None) => err!(AgentBlinkPayError::VerifyingKeyMissing),
+    }
+}
+
+/// A payment statement spent by one of the proof-taking authorize
+/// instructions.
+/// 
+/// PDA seeds: ["nullifier", nullifier]
+/// 
+/// Its existence is what matters: a second authorization proving the same
+/// public inputs fails with `ProofAlreadyUsed`, whatever the proof bytes.
+/// Closable with `close_nullifier` once its authorization is terminal.
+#[account]
+#[derive(Default)]
+pub struct Nullifier {
+    /// `payment_nullifier` of the payment's public inputs
+    pub nullifier: [u8; 32],
+
+    /// The agent that spent the proof
+    pub agent: Pubkey,
+
+    /// The authorization the proof created (an `Authorization`,
+    /// `BudgetAuthorization` or `BundleAuthorization`)
+    pub authorization: Pubkey,
+
+    /// Account that paid the rent, refunded on close
+    pub rent_payer: Pubkey,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Nullifier {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // nullifier
+        32 +                    // agent
+        32 +                    // authorization
+        32 +                    // rent_payer
+        1;                      // bump
+}
+
+ 
+/// A payment proof being verified across transactions by
+/// `verify_proof_stage`, consumed by `finalize_authorization`.
+/// 
+/// PDA seeds: ["pending", agent, meter, nonce (u64 LE)]
+/// 
+/// Stage 0 binds it to the payment: its address fixes the agent, meter and
+/// nonce, and the policy, amount and category are stored for the later
+/// stages and finalize_authorization to match. Anyone may close it with
+/// `close_pending_verification` once `expires_at_slot` has passed.
+#[account]
+pub struct PendingVerification {
+    /// The agent paying
+    pub agent: Pubkey,
+
+    /// The agent's policy the proof is checked against
+    pub policy: Pubkey,
+
+    /// The meter being paid
+    pub meter: Pubkey,
+
+    /// Nonce of the authorization to be created
+    pub nonce: u64,
+
+    /// Amount of the authorization (public input)
+    pub amount: u64,
+
+    /// Category of the payment (public input)
+    pub category: u8,
+
+    /// Next stage to run; PROOF_VERIFICATION_STAGES once the proof is verified
+    pub next_stage: u8,
+
+    /// Proof received so far, header then Groth16 points
+    /// (PROOF_HEADER_LEN + GROTH16_PROOF_LEN bytes)
+    pub proof: [u8; 258],
+
+    /// Bytes of `proof` received so far
+    pub proof_len: u16,
+
+    /// Circuit of the proof, set by stage 1 (see `circuit_ids`)
+    pub circuit_id: u8,
+
+    /// The VerifyingKey stage 1 folded the public inputs with
+    pub verifying_key: Pubkey,
+
+    /// The public inputs folded into one G1 point by stage 1
+    pub vk_x: [u8; 64],
+
+    /// The policy's spent_today when stage 1 ran, a public input of daily
+    /// limit proofs
+    pub spent_today: u64,
+
+    /// Slot of stage 0
+    pub created_at_slot: u64,
+
+    /// Last slot the remaining stages and finalize_authorization may run
+    pub expires_at_slot: u64,
+
+    /// Account that paid the rent, refunded on close
+    pub rent_payer: Pubkey,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl PendingVerification {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // agent
+        32 +                    // policy
+        32 +                    // meter
+        8 +                     // nonce
+        8 +                     // amount
+        1 +                     // category
+        1 +                     // next_stage
+        258 +                   // proof
+        2 +                     // proof_len
+        1 +                     // circuit_id
+        32 +                    // verifying_key
+        64 +                    // vk_x
+        8 +                     // spent_today
+        8 +                     // created_at_slot
+        8 +                     // expires_at_slot
+        32 +                    // rent_payer
+        1;                      // bump
+
+    /// The proof bytes received so far.
+    pub fn proof(&self) -> &[u8] {
+        &self.proof[..self.proof_len as usize]
+    }
+
+    /// Appends the next chunk of the proof. Fails with `ProofTooLarge` past
+    /// the length of a Groth16 proof and its header.
+    pub fn append_proof(&mut self, chunk: &[u8]) -> Result<()> {
+        let start = self.proof_len as usize;
+        let end = start
+            .checked_add(chunk.len())
+            .filter(|end| *end <= self.proof.len())
+            .ok_or(AgentBlinkPayError::ProofTooLarge)?;
+        self.proof[start..end].copy_from_slice(chunk);
+        self.proof_len = end as u16;
+        Ok(())
+    }
+
+    /// Whether every stage has run, so the proof is verified.
+    pub fn is_verified(&self) -> bool {
+        self.next_stage == PROOF_VERIFICATION_STAGES
+    }
+
+    pub fn is_expired(&self, current_slot: u64) -> bool {
+        current_slot > self.expires_at_slot
+    }
+
+    /// Rejects a stage or finalization for another payment than the one
+    /// bound at stage 0.
+    pub fn check_payment(&self, policy: &Pubkey, amount: u64, category: u8) -> Result<()> {
+        require!(
+            self.policy == *policy && self.amount == amount && self.category == category,
+            AgentBlinkPayError::PendingVerificationMismatch
+        );
+        Ok(())
+    }
+
+    /// Checks that the verified proof can authorize this payment now.
+    pub fn check_finalizable(
+        &self,
+        policy: &Account<AgentPolicy>,
+        amount: u64,
+        category: u8,
+        current_slot: u64,
+    ) -> Result<()> {
+        require!(self.is_verified(), AgentBlinkPayError::ProofNotVerified);
+        require!(
+            !self.is_expired(current_slot),
+            AgentBlinkPayError::PendingVerificationExpired
+        );
+        self.check_payment(&policy.key(), amount, category)?;
+        require!(
+            self.circuit_id != circuit_ids::DAILY_LIMIT
+                || policy.spent_today_at(current_slot) == self.spent_today,
+            AgentBlinkPayError::PendingInputsChanged
+        );
+        Ok(())
+    }
+}
+
+/// A payment proof verified by `submit_proof_receipt`, for
+/// `authorize_payment_with_proof` to consume without verifying it again.
+/// 
+/// PDA seeds: ["proof_receipt", agent, public_inputs_hash]
+/// 
+/// Bound to the SHA-256 of the proof's public inputs: a payment can use it
+/// only if its own public inputs, encoded as for the verifier, hash the
+/// same. Single-use: consuming it closes it and spends the payment's
+/// nullifier. Anyone may close it with `close_proof_receipt` once
+/// `expires_at_slot` has passed.
+#[account]
+pub struct ProofReceipt {
+    /// The agent the proof pays for
+    pub agent: Pubkey,
+
+    /// The agent's policy the proof was checked against
+    pub policy: Pubkey,
+
+    /// SHA-256 of the proof's public inputs (see `public_inputs::hash`)
+    pub public_inputs_hash: [u8; 32],
+
+    /// SHA-256 of the proof
+    pub proof_commitment: [u8; 32],
+
+    /// Version byte of the proof
+    pub proof_version: u8,
+
+    /// Circuit the proof was verified against (see `circuit_ids`)
+    pub circuit_id: u8,
+
+    /// The VerifyingKey or HonkVerifyingKey the proof was verified with
+    pub verifying_key: Pubkey,
+
+    /// The key's circuit_version at verification
+    pub circuit_version: u8,
+
+    /// How the proof was verified, copied to the authorization that
+    /// consumes the receipt (see `verification_methods`)
+    pub verification_method: u8,
+
+    /// What verified it (see `Authorization::verifier_ref`)
+    pub verifier_ref: Pubkey,
+
+    /// Slot of submit_proof_receipt
+    pub created_at_slot: u64,
+
+    /// Last slot the receipt may be consumed
+    pub expires_at_slot: u64,
+
+    /// Account that paid the rent, refunded on close
+    pub rent_payer: Pubkey,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl ProofReceipt {
+    pub const LEN: usize = 8 +  // discriminator
+        32 +                    // agent
+        32 +                    // policy
+        32 +                    // public_inputs_hash
+        32 +                    // proof_commitment
+        1 +                     // proof_version
+        1 +                     // circuit_id
+        32 +                    // verifying_key
+        1 +                     // circuit_version
+        1 +                     // verification_method
+        32 +                    // verifier_ref
+        8 +                     // created_at_slot
+        8 +                     // expires_at_slot
+        32 +                    // rent_payer
+        1;                      // bump
+
+    pub fn is_expired(&self, current_slot: u64) -> bool {
+        current_slot > self.expires_at_slot
+    }
+
+    /// Checks that the receipt's proof can authorize this payment now: the
+    /// meter accepts its version and circuit, its key is still the
+    /// circuit's, and the payment's public inputs are the ones it was
+    /// verified with.
+    #[allow(clippy::too_many_arguments)]
+    fn check_consumable(
+        &self,
+        policy: &Account<AgentPolicy>,
+        meter: &Account<Meter>,
+        verifying_key: CircuitKey,
+        amount: u64,
+        category: u8,
+        nonce: u64,
+        current_slot: u64,
+    ) -> Result<()> {
+        require!(
+            !self.is_expired(current_slot),
+            AgentBlinkPayError::ProofReceiptExpired
+        );
+        require_keys_eq!(self.policy, policy.key(), AgentBlinkPayError::ProofReceiptMismatch);
+        let version = [self.proof_version];
+        meter.check_proof_version(&version)?;
+        let circuit_id = meter.proof_circuit(&version)?;
+        require!(
+            circuit_id == self.circuit_id
+                && verifying_key.key() == self.verifying_key
+                && verifying_key.circuit_version() == self.circuit_version,
+            AgentBlinkPayError::ProofReceiptStale
+        );
+        policy.check_payment_circuit(circuit_id)?;
+        check_policy_commitment(policy)?;
+
+        let public_inputs = payment_public_inputs(
+            policy,
+            circuit_id,
+            amount,
+            category,
+            meter.key(),
+            nonce,
+            current_slot,
+        );
+        require!(
+            public_inputs::hash(&public_inputs) == self.public_inputs_hash,
+            AgentBlinkPayError::ProofReceiptMismatch
+        );
+        Ok(())
+    }
+}
+
+/// Settings passed to `initialize_config` / `update_config`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ConfigParams {
+    /// Watcher keys (use `Pubkey::default()` for unused slots)
+    pub watchers: [Pubkey; 4], // MAX_WATCHERS
+
+    /// Reported failures after which a policy auto-freezes (0 = disabled)
+    pub auto_freeze_threshold: u16,
+
+    /// Delay before a meter price increase takes effect
+    pub min_price_delay_slots: u64,
+
+    /// Lowest `price_per_call` a meter may be created or updated with
+    pub min_price_per_call: u64,
+
+    /// Highest `price_per_call` a meter may be created or updated with (0 = unbounded)
+    pub max_price_per_call: u64,
+
+    /// Mint meters settle in unless created with another `accepted_mint`
+    pub usdc_mint: Pubkey,
+
+    /// Longest wall-clock TTL of an authorization, in seconds (0 = wall-clock
+    /// expiry disabled)
+    pub max_authorization_ttl_secs: u32,
+
+    /// Slots an expired authorization may still be recorded in
+    pub grace_slots: u16,
+
+    /// Key allowed to confirm or fail settlements (default = none)
+    pub settlement_authority: Pubkey,
+
+    /// Slots after a record in which it may be disputed (0 = disabled)
+    pub dispute_window_slots: u64,
+
+    /// Slots after a record in which it may be refunded (0 = disabled)
+    pub refund_window_slots: u64,
+
+    /// External verifier program (default = none)
+    pub verifier_program: Pubkey,
+
+    /// Verify proofs with `verifier_program` instead of the embedded verifier
+    pub external_verifier: bool,
+
+    /// `enforcement_modes` value; STRICT can't be changed back
+    pub enforcement_mode: u8,
+
+    /// Policy oracle signing payment attestations (default = none)
+    pub policy_oracle: Pubkey,
+}
+
+/// A verifying key, as passed to `set_verifying_key`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct VerifyingKeyParams {
+    /// Version byte proofs for this key must start with
+    pub circuit_version: u8,
+
+    pub alpha_g1: [u8; 64],
+    pub beta_g2: [u8; 128],
+    pub gamma_g2: [u8; 128],
+    pub delta_g2: [u8; 128],
+
+    /// The constant term, then one point per public input (at most
+    /// MAX_VK_PUBLIC_INPUTS inputs)
+    pub ic: Vec<[u8; 64]>,
+
+    /// Freeze the key so it can never be updated again
+    pub locked: bool,
+}
+
+/// An UltraHonk verifier, as passed to `set_honk_verifying_key`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct HonkVerifyingKeyParams {
+    /// Version byte proofs for this key must start with
+    pub circuit_version: u8,
+
+    /// The Honk verifier program generated for the circuit
+    pub verifier_program: Pubkey,
+
+    /// SHA-256 of the verifying key the program embeds
+    pub vk_hash: [u8; 32],
+
+    /// Freeze the key so it can never be updated again
+    pub locked: bool,
+}
+
+// =============================================================================
+// INSTRUCTION CONTEXTS
+// =============================================================================
+
+/// Context for set_policy instruction.
+#[derive(Accounts)]
+#[instruction(params: PolicyParams)]
+pub struct SetPolicy<'info> {
+    /// The policy owner (becomes the owner when the policy is created).
+    /// Either a keypair or a PDA signing via `invoke_signed`.
+    /// CHECK: Only needs to have signed; compared against the policy owner
+    #[account(signer @ AgentBlinkPayError::OwnerSignatureMissing)]
+    pub owner: UncheckedAccount<'info>,
+
+    /// The agent whose policy is being set; must sign when the policy is
+    /// created
+    /// CHECK: Used for PDA derivation; is_signer checked on creation
+    pub agent: UncheckedAccount<'info>,
+    
+    /// The policy account (PDA: ["policy", agent, policy_id])
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = AgentPolicy::LEN,
+        seeds = [b"policy", agent.key().as_ref(), &params.policy_id.to_le_bytes()],
+        bump
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// Tombstone address of the agent key (PDA: ["retired", agent]).
+    /// Always required; a policy can't be created while it exists.
+    /// CHECK: Address is re-derived from seeds; the handler only checks its owner
+    #[account(seeds = [b"retired", agent.key().as_ref()], bump)]
+    pub retired_agent: UncheckedAccount<'info>,
+    
+    /// Account paying for the transaction
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for set_policies_batch instruction.
+/// 
+/// The `(agent, agent_policy, retired_agent)` triples are passed via
+/// `remaining_accounts`.
+#[derive(Accounts)]
+pub struct SetPoliciesBatch<'info> {
+    /// The organization admin that owns every policy in the batch.
+    /// Either a keypair or a PDA signing via `invoke_signed`.
+    /// CHECK: Only needs to have signed; compared against each policy owner
+    #[account(signer @ AgentBlinkPayError::OwnerSignatureMissing)]
+    pub owner: UncheckedAccount<'info>,
+
+    /// Account paying for newly created policies
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for extend_budget instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct ExtendBudget<'info> {
+    /// The policy owner.
+    /// Either a keypair or a PDA signing via `invoke_signed`.
+    /// CHECK: Only needs to have signed; compared against the policy owner
+    #[account(signer @ AgentBlinkPayError::OwnerSignatureMissing)]
+    pub owner: UncheckedAccount<'info>,
+
+    /// The agent whose budget is being extended
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The policy account (PDA: ["policy", agent, policy_id])
+    #[account(
+        mut,
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent_policy.is_owner(&owner.key()) @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+}
+
+/// Context for set_guardian instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct SetGuardian<'info> {
+    /// The policy owner
+    pub owner: Signer<'info>,
+
+    /// The agent whose guardian is being set
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The policy account (PDA: ["policy", agent, policy_id])
+    #[account(
+        mut,
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent_policy.is_owner(&owner.key()) @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+}
+
+/// Context for pause_policy instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct PausePolicy<'info> {
+    /// The policy owner or the policy's guardian
+    #[account(
+        constraint = agent_policy.is_owner(&authority.key())
+            || (agent_policy.guardian != Pubkey::default()
+                && authority.key() == agent_policy.guardian)
+            @ AgentBlinkPayError::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    /// The agent being paused
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The policy account (PDA: ["policy", agent, policy_id])
+    #[account(
+        mut,
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+}
+
+/// Context for report_auth_failure instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct ReportAuthFailure<'info> {
+    /// A watcher listed in the Config
+    #[account(
+        constraint = config.is_watcher(&watcher.key()) @ AgentBlinkPayError::Unauthorized
+    )]
+    pub watcher: Signer<'info>,
+
+    /// Global config (PDA: ["config"])
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The agent whose authorization failed
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The policy account (PDA: ["policy", agent, policy_id])
+    #[account(
+        mut,
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+}
+
+/// Context for allow_meter instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct AllowMeter<'info> {
+    /// The policy owner
+    pub owner: Signer<'info>,
+
+    /// The agent whose allowlist is being changed
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The policy account (PDA: ["policy", agent, policy_id])
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent_policy.is_owner(&owner.key()) @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// The meter being allowed
+    pub meter: Account<'info, Meter>,
+
+    /// The allowlist record (PDA: ["allowed", agent_policy, meter])
+    #[account(
+        init,
+        payer = payer,
+        space = AllowedMeter::LEN,
+        seeds = [b"allowed", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump
+    )]
+    pub allowed_meter: Account<'info, AllowedMeter>,
+
+    /// Account paying for the record
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for disallow_meter instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct DisallowMeter<'info> {
+    /// The policy owner (receives the record's rent)
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// The agent whose allowlist is being changed
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The policy account (PDA: ["policy", agent, policy_id])
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent_policy.is_owner(&owner.key()) @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// The meter being removed
+    /// CHECK: Only used for PDA derivation, so closed meters can be removed too
+    pub meter: UncheckedAccount<'info>,
+
+    /// The allowlist record (PDA: ["allowed", agent_policy, meter])
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"allowed", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump = allowed_meter.bump,
+    )]
+    pub allowed_meter: Account<'info, AllowedMeter>,
+}
+
+/// Context for deny_meter instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct DenyMeter<'info> {
+    /// The policy owner
+    pub owner: Signer<'info>,
+
+    /// The agent whose denylist is being changed
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The policy account (PDA: ["policy", agent, policy_id])
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent_policy.is_owner(&owner.key()) @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// The meter being banned
+    pub meter: Account<'info, Meter>,
+
+    /// The denylist record (PDA: ["denied", agent_policy, meter])
+    #[account(
+        init,
+        payer = payer,
+        space = DeniedMeter::LEN,
+        seeds = [b"denied", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump
+    )]
+    pub denied_meter: Account<'info, DeniedMeter>,
+
+    /// Account paying for the record
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for undeny_meter instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct UndenyMeter<'info> {
+    /// The policy owner (receives the record's rent)
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// The agent whose denylist is being changed
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The policy account (PDA: ["policy", agent, policy_id])
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent_policy.is_owner(&owner.key()) @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// The banned meter
+    /// CHECK: Only used for PDA derivation, so closed meters can be undenied too
+    pub meter: UncheckedAccount<'info>,
+
+    /// The denylist record (PDA: ["denied", agent_policy, meter])
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"denied", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump = denied_meter.bump,
+    )]
+    pub denied_meter: Account<'info, DeniedMeter>,
+}
+
+/// Context for transfer_policy_ownership instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct TransferPolicyOwnership<'info> {
+    /// The current policy owner
+    pub owner: Signer<'info>,
+
+    /// The agent whose policy is being transferred
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The policy account (PDA: ["policy", agent, policy_id])
+    #[account(
+        mut,
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent_policy.is_owner(&owner.key()) @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+}
+
+/// Context for accept_policy_ownership instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct AcceptPolicyOwnership<'info> {
+    /// The nominated owner
+    pub new_owner: Signer<'info>,
+
+    /// The agent whose policy is being transferred
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The policy account (PDA: ["policy", agent, policy_id])
+    #[account(
+        mut,
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+}
+
+/// Context for rotate_agent_key instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16, new_agent: Pubkey)]
+pub struct RotateAgentKey<'info> {
+    /// The policy owner
+    pub owner: Signer<'info>,
+
+    /// The agent key being retired
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The current policy account (PDA: ["policy", agent, policy_id]),
+    /// closed to its rent payer
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent_policy.is_owner(&owner.key()) @ AgentBlinkPayError::Unauthorized,
+        constraint = agent_policy.rent_refund_destination() == rent_payer.key()
+            @ AgentBlinkPayError::RentPayerMismatch,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// The policy account for the new key (PDA: ["policy", new_agent, policy_id])
+    #[account(
+        init,
+        payer = payer,
+        space = AgentPolicy::LEN,
+        seeds = [b"policy", new_agent.as_ref(), &policy_id.to_le_bytes()],
+        bump
+    )]
+    pub new_agent_policy: Account<'info, AgentPolicy>,
+
+    /// The new agent key, signing (directly or via invoke_signed) for the
+    /// policy created at its address
+    #[account(address = new_agent @ AgentBlinkPayError::AgentSignatureMissing)]
+    pub new_agent_key: Signer<'info>,
+
+    /// Tombstone of the retired key (PDA: ["retired", agent]), created on
+    /// its first rotation
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = RetiredAgent::LEN,
+        seeds = [b"retired", agent.key().as_ref()],
+        bump
+    )]
+    pub retired_agent: Account<'info, RetiredAgent>,
+
+    /// Receives the old policy's rent
+    /// CHECK: Must be the old policy's `rent_refund_destination()`
+    #[account(mut)]
+    pub rent_payer: UncheckedAccount<'info>,
+
+    /// Pays for the new account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for reset_spend_window instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct ResetSpendWindow<'info> {
+    /// The policy owner
+    pub owner: Signer<'info>,
+
+    /// The global config admin, co-signing the reset
+    pub admin: Signer<'info>,
+
+    /// Global config (PDA: ["config"])
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The agent whose spend window is reset
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The agent's policy account (PDA: ["policy", agent, policy_id])
+    #[account(
+        mut,
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent_policy.is_owner(&owner.key()) @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+}
+
+/// Context for initialize_config instruction.
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    /// Becomes the Config admin; must be the program's upgrade authority
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// This program, to locate its ProgramData account
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ AgentBlinkPayError::Unauthorized)]
+    pub program: Program<'info, crate::program::AgentBlinkPay>,
+
+    /// The program's ProgramData; its upgrade authority gates Config creation
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ AgentBlinkPayError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    /// Global config (PDA: ["config"])
+    #[account(
+        init,
+        payer = admin,
+        space = Config::LEN,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for update_config instruction.
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    /// The current Config admin
+    pub admin: Signer<'info>,
+
+    /// Global config (PDA: ["config"])
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+/// Context for migrate_config instruction.
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    /// The Config admin; pays the extra rent
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// Global config (PDA: ["config"])
+    /// CHECK: May still be in an older layout, so it can't be loaded as
+    /// `Config`. Ownership is checked here, the discriminator and admin in
+    /// the handler.
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        owner = crate::ID,
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for set_verifying_key instruction.
+#[derive(Accounts)]
+#[instruction(circuit_id: u8)]
+pub struct SetVerifyingKey<'info> {
+    /// The Config admin; pays for the key account when it is created
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// Global config (PDA: ["config"])
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The circuit's key (PDA: ["vk", circuit_id])
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = VerifyingKey::LEN,
+        seeds = [b"vk", &[circuit_id]],
+        bump
+    )]
+    pub verifying_key: Account<'info, VerifyingKey>,
+
+    /// The circuit's UltraHonk key (PDA: ["honk_vk", circuit_id]), which
+    /// must not exist
+    /// CHECK: Only checked to be empty
+    #[account(seeds = [b"honk_vk", &[circuit_id]], bump)]
+    pub honk_verifying_key: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for set_honk_verifying_key instruction.
+#[derive(Accounts)]
+#[instruction(circuit_id: u8)]
+pub struct SetHonkVerifyingKey<'info> {
+    /// The Config admin; pays for the key account when it is created
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// Global config (PDA: ["config"])
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The circuit's UltraHonk key (PDA: ["honk_vk", circuit_id])
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = HonkVerifyingKey::LEN,
+        seeds = [b"honk_vk", &[circuit_id]],
+        bump
+    )]
+    pub honk_verifying_key: Account<'info, HonkVerifyingKey>,
+
+    /// The circuit's Groth16 key (PDA: ["vk", circuit_id]), which must not
+    /// exist
+    /// CHECK: Only checked to be empty
+    #[account(seeds = [b"vk", &[circuit_id]], bump)]
+    pub groth16_verifying_key: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for remove_verifying_key instruction.
+#[derive(Accounts)]
+#[instruction(circuit_id: u8)]
+pub struct RemoveVerifyingKey<'info> {
+    /// The Config admin; receives the key account's rent
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// Global config (PDA: ["config"])
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The circuit's key (PDA: ["vk", circuit_id])
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"vk", &[circuit_id]],
+        bump = verifying_key.bump,
+    )]
+    pub verifying_key: Account<'info, VerifyingKey>,
+}
+
+/// Context for remove_honk_verifying_key instruction.
+#[derive(Accounts)]
+#[instruction(circuit_id: u8)]
+pub struct RemoveHonkVerifyingKey<'info> {
+    /// The Config admin; receives the key account's rent
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// Global config (PDA: ["config"])
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The circuit's UltraHonk key (PDA: ["honk_vk", circuit_id])
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"honk_vk", &[circuit_id]],
+        bump = honk_verifying_key.bump,
+    )]
+    pub honk_verifying_key: Account<'info, HonkVerifyingKey>,
+}
+
+/// Context for migrate_policy instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct MigratePolicy<'info> {
+    /// The agent whose policy is being migrated
+    pub agent: Signer<'info>,
+
+    /// The policy account (PDA: ["policy", agent, policy_id])
+    /// CHECK: May still be in an older layout, so it can't be loaded as
+    /// `AgentPolicy`. Ownership is checked here, the discriminator in the handler.
+    #[account(
+        mut,
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump,
+        owner = crate::ID,
+    )]
+    pub agent_policy: UncheckedAccount<'info>,
+
+    /// Account paying for the extra rent
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for migrate_legacy_policy instruction.
+#[derive(Accounts)]
+pub struct MigrateLegacyPolicy<'info> {
+    /// The agent whose policy is being moved
+    pub agent: Signer<'info>,
+
+    /// The pre-policy_id policy account (PDA: ["policy", agent])
+    /// CHECK: May be in any older layout, so it can't be loaded as
+    /// `AgentPolicy`. Ownership is checked here, the discriminator in the handler.
+    #[account(
+        mut,
+        seeds = [b"policy", agent.key().as_ref()],
+        bump,
+        owner = crate::ID,
+    )]
+    pub legacy_policy: UncheckedAccount<'info>,
+
+    /// The new default policy account (PDA: ["policy", agent, 0u16])
+    #[account(
+        init,
+        payer = payer,
+        space = AgentPolicy::LEN,
+        seeds = [
+            b"policy",
+            agent.key().as_ref(),
+            &DEFAULT_POLICY_ID.to_le_bytes()
+        ],
+        bump
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// Pays for the new account and receives the legacy account's rent
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for create_meter instruction.
+#[derive(Accounts)]
+#[instruction(price_per_call: u64, category: u8, merchant_wallet_id: String)]
+pub struct CreateMeter<'info> {
+    /// Authority creating and controlling this meter
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    /// Unique identifier for this meter (e.g., API endpoint hash)
+    /// CHECK: This is just used for PDA derivation
+    pub meter_id: AccountInfo<'info>,
+    
+    /// The meter account (PDA: ["meter", authority, meter_id])
+    #[account(
+        init,
+        payer = authority,
+        space = Meter::LEN,
+        seeds = [b"meter", authority.key().as_ref(), meter_id.key().as_ref()],
+        bump
+    )]
+    pub meter: Account<'info, Meter>,
+    
+    /// The meter's counters (PDA: ["counters", meter])
+    #[account(
+        init,
+        payer = authority,
+        space = MeterCounters::LEN,
+        seeds = [b"counters", meter.key().as_ref()],
+        bump
+    )]
+    pub meter_counters: AccountLoader<'info, MeterCounters>,
+
+    /// Global config (PDA: ["config"]), for the price bounds
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The authority's meter list (PDA: ["meter_index", authority])
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = MeterIndex::space_for(1),
+        seeds = [b"meter_index", authority.key().as_ref()],
+        bump
+    )]
+    pub meter_index: Account<'info, MeterIndex>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for create_meter_v2 instruction.
+#[derive(Accounts)]
+#[instruction(endpoint_id: String)]
+pub struct CreateMeterV2<'info> {
+    /// Authority creating and controlling this meter
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The meter account (PDA: ["meter", authority, endpoint_id])
+    #[account(
+        init,
+        payer = authority,
+        space = Meter::LEN,
+        seeds = [b"meter", authority.key().as_ref(), endpoint_id.as_bytes()],
+        bump
+    )]
+    pub meter: Account<'info, Meter>,
+
+    /// The meter's counters (PDA: ["counters", meter])
+    #[account(
+        init,
+        payer = authority,
+        space = MeterCounters::LEN,
+        seeds = [b"counters", meter.key().as_ref()],
+        bump
+    )]
+    pub meter_counters: AccountLoader<'info, MeterCounters>,
+
+    /// Global config (PDA: ["config"]), for the price bounds
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The authority's meter list (PDA: ["meter_index", authority])
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = MeterIndex::space_for(1),
+        seeds = [b"meter_index", authority.key().as_ref()],
+        bump
+    )]
+    pub meter_index: Account<'info, MeterIndex>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for clone_meter instruction.
+#[derive(Accounts)]
+pub struct CloneMeter<'info> {
+    /// Authority of the source meter, creating and controlling the clone
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The meter whose settings are copied
+    #[account(has_one = authority @ AgentBlinkPayError::Unauthorized)]
+    pub source_meter: Account<'info, Meter>,
+
+    /// Unique identifier for the new meter
+    /// CHECK: This is just used for PDA derivation
+    pub new_meter_id: AccountInfo<'info>,
+
+    /// The new meter account (PDA: ["meter", authority, new_meter_id])
+    #[account(
+        init,
+        payer = authority,
+        space = Meter::LEN,
+        seeds = [b"meter", authority.key().as_ref(), new_meter_id.key().as_ref()],
+        bump
+    )]
+    pub meter: Account<'info, Meter>,
+
+    /// The meter's counters (PDA: ["counters", meter])
+    #[account(
+        init,
+        payer = authority,
+        space = MeterCounters::LEN,
+        seeds = [b"counters", meter.key().as_ref()],
+        bump
+    )]
+    pub meter_counters: AccountLoader<'info, MeterCounters>,
+
+    /// Global config (PDA: ["config"]), for the price bounds
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The authority's meter list (PDA: ["meter_index", authority])
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = MeterIndex::space_for(1),
+        seeds = [b"meter_index", authority.key().as_ref()],
+        bump
+    )]
+    pub meter_index: Account<'info, MeterIndex>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for subscribe instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct Subscribe<'info> {
+    /// The subscribing agent
+    pub agent: Signer<'info>,
+
+    /// The policy paying the fee
+    #[account(
+        mut,
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent.key().is_on_curve() || agent_policy.agent_is_pda
+            @ AgentBlinkPayError::AgentMustBeKeypair,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// The subscription meter
+    pub meter: Account<'info, Meter>,
+
+    /// The meter's counters (PDA: ["counters", meter])
+    #[account(
+        mut,
+        seeds = [b"counters", meter.key().as_ref()],
+        bump = meter_counters.load()?.bump,
+    )]
+    pub meter_counters: AccountLoader<'info, MeterCounters>,
+
+    /// Allowlist record for this policy/meter pair (PDA: ["allowed", agent_policy, meter]).
+    /// Required when the policy has `enforce_meter_allowlist` set.
+    #[account(
+        seeds = [b"allowed", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump = allowed_meter.bump,
+    )]
+    pub allowed_meter: Option<Account<'info, AllowedMeter>>,
+
+    /// Denylist record address for this policy/meter pair (PDA: ["denied", agent_policy, meter]).
+    /// CHECK: Address is re-derived from seeds; the handler only checks its owner
+    #[account(
+        seeds = [b"denied", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump,
+    )]
+    pub denied_meter: UncheckedAccount<'info>,
+
+    /// Meter-side access record for this agent (PDA: ["access", meter, agent]).
+    /// Required when the meter has `allowlist_enabled` set.
+    #[account(
+        seeds = [b"access", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_access.bump,
+    )]
+    pub meter_access: Option<Account<'info, MeterAgentAccess>>,
+
+    /// The agent's subscription (PDA: ["sub", meter, agent])
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = Subscription::LEN,
+        seeds = [b"sub", meter.key().as_ref(), agent.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    /// Account paying for the subscription account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for record_subscription_call instruction.
+#[derive(Accounts)]
+pub struct RecordSubscriptionCall<'info> {
+    /// The subscribed agent
+    pub agent: Signer<'info>,
+
+    /// The subscription meter
+    pub meter: Account<'info, Meter>,
+
+    /// The meter's counters (PDA: ["counters", meter])
+    #[account(
+        mut,
+        seeds = [b"counters", meter.key().as_ref()],
+        bump = meter_counters.load()?.bump,
+    )]
+    pub meter_counters: AccountLoader<'info, MeterCounters>,
+
+    /// The agent's subscription (PDA: ["sub", meter, agent])
+    #[account(
+        seeds = [b"sub", meter.key().as_ref(), agent.key().as_ref()],
+        bump = subscription.bump,
+    )]
+    pub subscription: Account<'info, Subscription>,
+}
+
+/// Context for buy_credits instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct BuyCredits<'info> {
+    /// The agent buying credit
+    pub agent: Signer<'info>,
+
+    /// The policy paying for the credit
+    #[account(
+        mut,
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent.key().is_on_curve() || agent_policy.agent_is_pda
+            @ AgentBlinkPayError::AgentMustBeKeypair,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// The per-call meter the credit is for
+    pub meter: Account<'info, Meter>,
+
+    /// Allowlist record for this policy/meter pair (PDA: ["allowed", agent_policy, meter]).
+    /// Required when the policy has `enforce_meter_allowlist` set.
+    #[account(
+        seeds = [b"allowed", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump = allowed_meter.bump,
+    )]
+    pub allowed_meter: Option<Account<'info, AllowedMeter>>,
+
+    /// Denylist record address for this policy/meter pair (PDA: ["denied", agent_policy, meter]).
+    /// CHECK: Address is re-derived from seeds; the handler only checks its owner
+    #[account(
+        seeds = [b"denied", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump,
+    )]
+    pub denied_meter: UncheckedAccount<'info>,
+
+    /// Meter-side access record for this agent (PDA: ["access", meter, agent]).
+    /// Required when the meter has `allowlist_enabled` set.
+    #[account(
+        seeds = [b"access", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_access.bump,
+    )]
+    pub meter_access: Option<Account<'info, MeterAgentAccess>>,
+
+    /// The agent's credit balance (PDA: ["credit", meter, agent])
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = Credit::LEN,
+        seeds = [b"credit", meter.key().as_ref(), agent.key().as_ref()],
+        bump
+    )]
+    pub credit: Account<'info, Credit>,
+
+    /// Account paying for the credit account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// Global config (PDA: ["config"]), selects the verifier
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// The verifier the Config selects: this program, or its external
+    /// `verifier_program`
+    /// CHECK: Compared against `Config::verifier` before the CPI
+    pub verifier_program: AccountInfo<'info>,
+
+    /// The key of the proof's circuit (PDA: ["vk", circuit_id]), checked
+    /// against the proof version before verifying
+    pub verifying_key: Account<'info, VerifyingKey>,
+}
+
+/// Context for consume_credit instruction.
+#[derive(Accounts)]
+pub struct ConsumeCredit<'info> {
+    /// Current authority of the meter
+    pub authority: Signer<'info>,
+
+    /// The meter the credit is spent on
+    #[account(has_one = authority @ AgentBlinkPayError::Unauthorized)]
+    pub meter: Account<'info, Meter>,
+
+    /// The agent whose credit is spent
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The agent's credit balance (PDA: ["credit", meter, agent])
+    #[account(
+        mut,
+        seeds = [b"credit", meter.key().as_ref(), agent.key().as_ref()],
+        bump = credit.bump,
+    )]
+    pub credit: Account<'info, Credit>,
+}
+
+/// Context for refund_credits instruction.
+#[derive(Accounts)]
+pub struct RefundCredits<'info> {
+    /// The agent giving up its credit
+    pub agent: Signer<'info>,
+
+    /// The meter the credit is for
+    /// CHECK: Only used for PDA derivation, so credit on closed meters can be refunded
+    pub meter: UncheckedAccount<'info>,
+
+    /// The agent's credit balance (PDA: ["credit", meter, agent])
+    #[account(
+        mut,
+        seeds = [b"credit", meter.key().as_ref(), agent.key().as_ref()],
+        bump = credit.bump,
+    )]
+    pub credit: Account<'info, Credit>,
+}
+
+/// Context for update_meter instruction.
+#[derive(Accounts)]
+pub struct UpdateMeterSettings<'info> {
+    /// Current authority of the meter, or its operator
+    pub authority: Signer<'info>,
+
+    /// The meter's identifier, as passed to create_meter (ignored for
+    /// create_meter_v2 meters, whose id is stored on the meter)
+    /// CHECK: This is just used for PDA derivation
+    pub meter_id: AccountInfo<'info>,
+
+    /// The meter account (PDA: ["meter", creator, meter_id or endpoint_id])
+    #[account(
+        mut,
+        seeds = [b"meter", meter.creator.as_ref(), meter.pda_seed(meter_id.key)],
+        bump = meter.bump,
+        constraint = meter.is_managed_by(authority.key) @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub meter: Account<'info, Meter>,
+
+    /// Global config (PDA: ["config"]), for the price bounds and increase delay
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+/// Context for the meter authority's management instructions: set_meter_tiers,
+/// set_meter_rate_limit, set_meter_splits, update_merchant_wallet,
+/// set_meter_allowlist, set_meter_operator and transfer_meter_authority.
+#[derive(Accounts)]
+pub struct UpdateMeter<'info> {
+    /// Current authority of the meter
+    pub authority: Signer<'info>,
+
+    /// The meter's identifier, as passed to create_meter (ignored for
+    /// create_meter_v2 meters, whose id is stored on the meter)
+    /// CHECK: This is just used for PDA derivation
+    pub meter_id: AccountInfo<'info>,
+
+    /// The meter account (PDA: ["meter", creator, meter_id or endpoint_id])
+    #[account(
+        mut,
+        seeds = [b"meter", meter.creator.as_ref(), meter.pda_seed(meter_id.key)],
+        bump = meter.bump,
+        constraint = meter.authority == authority.key() || meter.operator != Some(authority.key()) @ AgentBlinkPayError::UnauthorizedOperator,
+        constraint = meter.authority == authority.key() @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub meter: Account<'info, Meter>,
+}
+
+/// Context for set_meter_circuit instruction.
+#[derive(Accounts)]
+#[instruction(circuit_id: u8)]
+pub struct SetMeterCircuit<'info> {
+    /// Current authority of the meter
+    pub authority: Signer<'info>,
+
+    /// The meter's identifier, as passed to create_meter (ignored for
+    /// create_meter_v2 meters, whose id is stored on the meter)
+    /// CHECK: This is just used for PDA derivation
+    pub meter_id: AccountInfo<'info>,
+
+    /// The meter account (PDA: ["meter", creator, meter_id or endpoint_id])
+    #[account(
+        mut,
+        seeds = [b"meter", meter.creator.as_ref(), meter.pda_seed(meter_id.key)],
+        bump = meter.bump,
+        constraint = meter.authority == authority.key() || meter.operator != Some(authority.key()) @ AgentBlinkPayError::UnauthorizedOperator,
+        constraint = meter.authority == authority.key() @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub meter: Account<'info, Meter>,
+
+    /// The key of the meter's current circuit (PDA: ["vk", meter.circuit_id]);
+    /// required when it has one, unless it is an UltraHonk circuit
+    #[account(
+        mut,
+        seeds = [b"vk", &[meter.circuit_id]],
+        bump = old_verifying_key.bump,
+    )]
+    pub old_verifying_key: Option<Account<'info, VerifyingKey>>,
+
+    /// The UltraHonk key of the meter's current circuit (PDA: ["honk_vk",
+    /// meter.circuit_id]), in place of `old_verifying_key`
+    #[account(
+        mut,
+        seeds = [b"honk_vk", &[meter.circuit_id]],
+        bump = old_honk_verifying_key.bump,
+    )]
+    pub old_honk_verifying_key: Option<Account<'info, HonkVerifyingKey>>,
+
+    /// The key of the new circuit (PDA: ["vk", circuit_id]); required
+    /// unless `circuit_id` is 0 or an UltraHonk circuit
+    #[account(
+        mut,
+        seeds = [b"vk", &[circuit_id]],
+        bump = verifying_key.bump,
+    )]
+    pub verifying_key: Option<Account<'info, VerifyingKey>>,
+
+    /// The UltraHonk key of the new circuit (PDA: ["honk_vk", circuit_id]),
+    /// in place of `verifying_key`
+    #[account(
+        mut,
+        seeds = [b"honk_vk", &[circuit_id]],
+        bump = honk_verifying_key.bump,
+    )]
+    pub honk_verifying_key: Option<Account<'info, HonkVerifyingKey>>,
+}
+
+/// Context for pause_meter and unpause_meter, which the meter's operator
+/// may sign as well.
+#[derive(Accounts)]
+pub struct OperateMeter<'info> {
+    /// Current authority of the meter, or its operator
+    pub authority: Signer<'info>,
+
+    /// The meter's identifier, as passed to create_meter (ignored for
+    /// create_meter_v2 meters, whose id is stored on the meter)
+    /// CHECK: This is just used for PDA derivation
+    pub meter_id: AccountInfo<'info>,
+
+    /// The meter account (PDA: ["meter", creator, meter_id or endpoint_id])
+    #[account(
+        mut,
+        seeds = [b"meter", meter.creator.as_ref(), meter.pda_seed(meter_id.key)],
+        bump = meter.bump,
+        constraint = meter.is_managed_by(authority.key) @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub meter: Account<'info, Meter>,
+}
+
+/// Context for accept_meter_authority instruction.
+#[derive(Accounts)]
+pub struct AcceptMeterAuthority<'info> {
+    /// The nominated authority
+    pub new_authority: Signer<'info>,
+
+    /// The meter's identifier, as passed to create_meter (ignored for
+    /// create_meter_v2 meters, whose id is stored on the meter)
+    /// CHECK: This is just used for PDA derivation
+    pub meter_id: AccountInfo<'info>,
+
+    /// The meter account (PDA: ["meter", creator, meter_id or endpoint_id])
+    #[account(
+        mut,
+        seeds = [b"meter", meter.creator.as_ref(), meter.pda_seed(meter_id.key)],
+        bump = meter.bump,
+    )]
+    pub meter: Account<'info, Meter>,
+}
+
+/// Context for grant_meter_access instruction.
+#[derive(Accounts)]
+pub struct GrantMeterAccess<'info> {
+    /// Current authority of the meter
+    pub authority: Signer<'info>,
+
+    /// The meter's identifier, as passed to create_meter (ignored for
+    /// create_meter_v2 meters, whose id is stored on the meter)
+    /// CHECK: This is just used for PDA derivation
+    pub meter_id: AccountInfo<'info>,
+
+    /// The meter account (PDA: ["meter", creator, meter_id or endpoint_id])
+    #[account(
+        seeds = [b"meter", meter.creator.as_ref(), meter.pda_seed(meter_id.key)],
+        bump = meter.bump,
+        has_one = authority @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub meter: Account<'info, Meter>,
+
+    /// The agent being granted access
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The access record (PDA: ["access", meter, agent])
+    #[account(
+        init,
+        payer = payer,
+        space = MeterAgentAccess::LEN,
+        seeds = [b"access", meter.key().as_ref(), agent.key().as_ref()],
+        bump
+    )]
+    pub meter_access: Account<'info, MeterAgentAccess>,
+
+    /// Account paying for the record
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for revoke_meter_access instruction.
+#[derive(Accounts)]
+pub struct RevokeMeterAccess<'info> {
+    /// Current authority of the meter (receives the record's rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The meter's identifier, as passed to create_meter (ignored for
+    /// create_meter_v2 meters, whose id is stored on the meter)
+    /// CHECK: This is just used for PDA derivation
+    pub meter_id: AccountInfo<'info>,
+
+    /// The meter account (PDA: ["meter", creator, meter_id or endpoint_id])
+    #[account(
+        seeds = [b"meter", meter.creator.as_ref(), meter.pda_seed(meter_id.key)],
+        bump = meter.bump,
+        has_one = authority @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub meter: Account<'info, Meter>,
+
+    /// The agent losing access
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The access record (PDA: ["access", meter, agent])
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"access", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_access.bump,
+    )]
+    pub meter_access: Account<'info, MeterAgentAccess>,
+}
+
+/// Context for close_meter instruction.
+#[derive(Accounts)]
+pub struct CloseMeter<'info> {
+    /// Current authority of the meter
+    pub authority: Signer<'info>,
+
+    /// The meter's identifier, as passed to create_meter (ignored for
+    /// create_meter_v2 meters, whose id is stored on the meter)
+    /// CHECK: This is just used for PDA derivation
+    pub meter_id: AccountInfo<'info>,
+
+    /// The meter account (PDA: ["meter", creator, meter_id or endpoint_id])
+    #[account(
+        mut,
+        close = recipient,
+        seeds = [b"meter", meter.creator.as_ref(), meter.pda_seed(meter_id.key)],
+        bump = meter.bump,
+        constraint = meter.authority == authority.key() || meter.operator != Some(authority.key()) @ AgentBlinkPayError::UnauthorizedOperator,
+        constraint = meter.authority == authority.key() @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub meter: Account<'info, Meter>,
+
+    /// Receives the meter's rent
+    /// CHECK: Any account chosen by the authority
+    #[account(mut)]
+    pub recipient: UncheckedAccount<'info>,
+
+    /// The creator's meter list (PDA: ["meter_index", creator]). Always
+    /// required; updated by the handler when it exists
+    /// CHECK: Address is re-derived from seeds; the handler checks its owner
+    /// before deserializing it
+    #[account(mut, seeds = [b"meter_index", meter.creator.as_ref()], bump)]
+    pub meter_index: UncheckedAccount<'info>,
+
+    /// The key of the meter's circuit (PDA: ["vk", meter.circuit_id]);
+    /// required when the meter has one, unless it is an UltraHonk circuit
+    #[account(
+        mut,
+        seeds = [b"vk", &[meter.circuit_id]],
+        bump = verifying_key.bump,
+    )]
+    pub verifying_key: Option<Account<'info, VerifyingKey>>,
+
+    /// The UltraHonk key of the meter's circuit (PDA: ["honk_vk",
+    /// meter.circuit_id]), in place of `verifying_key`
+    #[account(
+        mut,
+        seeds = [b"honk_vk", &[meter.circuit_id]],
+        bump = honk_verifying_key.bump,
+    )]
+    pub honk_verifying_key: Option<Account<'info, HonkVerifyingKey>>,
+}
+
+/// Context for migrate_meter_usage instruction.
+#[derive(Accounts)]
+pub struct MigrateMeterUsage<'info> {
+    /// Pays the extra rent
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The counted meter
+    /// CHECK: Only used for PDA derivation
+    pub meter: UncheckedAccount<'info>,
+
+    /// The counted agent
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The usage account (PDA: ["usage", meter, agent])
+    /// CHECK: May still be in an older layout, so it can't be loaded as
+    /// `MeterUsage`. Ownership is checked here, the discriminator in the handler.
+    #[account(
+        mut,
+        seeds = [b"usage", meter.key().as_ref(), agent.key().as_ref()],
+        bump,
+        owner = crate::ID,
+    )]
+    pub meter_usage: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for migrate_authorization instruction.
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct MigrateAuthorization<'info> {
+    /// Pays the extra rent
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The paying agent
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The paid meter
+    /// CHECK: Only used for PDA derivation
+    pub meter: UncheckedAccount<'info>,
+
+    /// The authorization (PDA: ["auth", agent, meter, nonce])
+    /// CHECK: May still be in an older layout, so it can't be loaded as
+    /// `Authorization`. Ownership is checked here, the discriminator in the handler.
+    #[account(
+        mut,
+        seeds = [b"auth", agent.key().as_ref(), meter.key().as_ref(), &nonce.to_le_bytes()],
+        bump,
+        owner = crate::ID,
+    )]
+    pub authorization: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for init_meter_counters instruction.
+#[derive(Accounts)]
+pub struct InitMeterCounters<'info> {
+    /// Pays the rent
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The meter whose counters are created
+    pub meter: Account<'info, Meter>,
+
+    /// The meter's counters (PDA: ["counters", meter])
+    #[account(
+        init,
+        payer = payer,
+        space = MeterCounters::LEN,
+        seeds = [b"counters", meter.key().as_ref()],
+        bump
+    )]
+    pub meter_counters: AccountLoader<'info, MeterCounters>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for migrate_meter instruction.
+#[derive(Accounts)]
+pub struct MigrateMeter<'info> {
+    /// Authority that controls the meter; pays the extra rent
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The meter's identifier, as passed to create_meter
+    /// CHECK: This is just used for PDA derivation
+    pub meter_id: AccountInfo<'info>,
+
+    /// The meter account (PDA: ["meter", authority, meter_id])
+    /// CHECK: May still be in an older layout, so it can't be loaded as
+    /// `Meter`. Ownership is checked here, the discriminator in the handler.
+    #[account(
+        mut,
+        seeds = [b"meter", authority.key().as_ref(), meter_id.key().as_ref()],
+        bump,
+        owner = crate::ID,
+    )]
+    pub meter: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for authorize_payment_with_proof and authorize_payment_simple instructions.
+#[derive(Accounts)]
+#[instruction(policy_id: u16, amount: u64, quantity: u16, category: u8, nonce: u64)]
+pub struct AuthorizePayment<'info> {
+    /// The agent authorizing the payment. An ed25519 keypair, or a PDA
+    /// signing via CPI if its policy was created for one.
+    pub agent: Signer<'info>,
+    
+    /// The agent's policy account
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent.key().is_on_curve() || agent_policy.agent_is_pda
+            @ AgentBlinkPayError::AgentMustBeKeypair,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+    
+    /// The meter being paid
+    pub meter: Account<'info, Meter>,
+
+    /// Allowlist record for this policy/meter pair (PDA: ["allowed", agent_policy, meter]).
+    /// Required when the policy has `enforce_meter_allowlist` set.
+    #[account(
+        seeds = [b"allowed", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump = allowed_meter.bump,
+    )]
+    pub allowed_meter: Option<Account<'info, AllowedMeter>>,
+
+    /// Denylist record address for this policy/meter pair (PDA: ["denied", agent_policy, meter]).
+    /// Always required so the check can't be skipped; payment is rejected if it exists.
+    /// CHECK: Address is re-derived from seeds; the handler only checks its owner
+    #[account(
+        seeds = [b"denied", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump,
+    )]
+    pub denied_meter: UncheckedAccount<'info>,
+
+    /// Meter-side access record for this agent (PDA: ["access", meter, agent]).
+    /// Required when the meter has `allowlist_enabled` set.
+    #[account(
+        seeds = [b"access", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_access.bump,
+    )]
+    pub meter_access: Option<Account<'info, MeterAgentAccess>>,
+
+    /// The agent's call counter for this meter (PDA: ["usage", meter, agent])
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MeterUsage::LEN,
+        seeds = [b"usage", meter.key().as_ref(), agent.key().as_ref()],
+        bump
+    )]
+    pub meter_usage: Account<'info, MeterUsage>,
+    
+    /// The authorization account (PDA: ["auth", agent, meter, nonce]).
+    /// `init_if_needed` so a reused nonce fails with `NonceAlreadyUsed`
+    /// in the handler rather than inside the system program.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = Authorization::LEN,
+        seeds = [
+            b"auth",
+            agent.key().as_ref(),
+            meter.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump
+    )]
+    pub authorization: Account<'info, Authorization>,
+    
+    /// Global config (PDA: ["config"]), for the wall-clock TTL bound
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// Account paying for the transaction
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+
+    /// The verifier the Config selects: this program, or its external
+    /// `verifier_program` (unused by authorize_payment_simple)
+    /// CHECK: Compared against `Config::verifier` before the CPI
+    pub verifier_program: AccountInfo<'info>,
+
+    /// The key of the proof's circuit (PDA: ["vk", circuit_id]), checked
+    /// against the proof version; only needed with a proof
+    pub verifying_key: Option<Account<'info, VerifyingKey>>,
+
+    /// The UltraHonk key of the proof's circuit (PDA: ["honk_vk",
+    /// circuit_id]), in place of `verifying_key` for UltraHonk circuits
+    pub honk_verifying_key: Option<Account<'info, HonkVerifyingKey>>,
+
+    /// The payment's nullifier (PDA: ["nullifier", payment_nullifier(b"auth",
+    /// agent, meter, nonce, amount, policy_hash)]), created by the handler;
+    /// only needed with a proof
+    /// CHECK: Address is checked against the payment's nullifier
+    #[account(mut)]
+    pub nullifier: Option<UncheckedAccount<'info>>,
+
+    /// finalize_authorization only: the proof's verification (PDA:
+    /// ["pending", agent, meter, nonce]), closed to `payer`
+    #[account(
+        mut,
+        close = payer,
+        seeds = [
+            b"pending",
+            agent.key().as_ref(),
+            meter.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump = pending_verification.bump,
+        constraint = pending_verification.rent_payer == payer.key()
+            @ AgentBlinkPayError::RentPayerMismatch,
+    )]
+    pub pending_verification: Option<Account<'info, PendingVerification>>,
+
+    /// authorize_payment_attested only: the instructions sysvar, to find
+    /// the Ed25519 attestation check
+    /// CHECK: Address is checked against the sysvar id
+    #[account(address = solana_program::sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+
+    /// authorize_payment_with_proof only: a proof verified ahead of time
+    /// (PDA: ["proof_receipt", agent, public_inputs_hash]), closed to `payer`
+    #[account(
+        mut,
+        close = payer,
+        constraint = proof_receipt.agent == agent.key()
+            @ AgentBlinkPayError::ProofReceiptMismatch,
+        constraint = proof_receipt.rent_payer == payer.key()
+            @ AgentBlinkPayError::RentPayerMismatch,
+    )]
+    pub proof_receipt: Option<Account<'info, ProofReceipt>>,
+
+    /// Escrow mode only: the agent's vault (PDA: ["vault", agent, mint])
+    #[account(
+        mut,
+        seeds = [b"vault", agent.key().as_ref(), meter.accepted_mint.as_ref()],
+        bump,
+    )]
+    pub vault: Option<Account<'info, TokenAccount>>,
+
+    /// Escrow mode only: the authorization's associated token account,
+    /// created by the handler unless it already exists
+    /// CHECK: Address is checked against the authorization's ATA
+    #[account(mut)]
+    pub escrow: Option<UncheckedAccount<'info>>,
+
+    /// Escrow mode only: the meter's accepted mint
+    #[account(address = meter.accepted_mint @ AgentBlinkPayError::MintMismatch)]
+    pub mint: Option<Account<'info, Mint>>,
+
+    pub token_program: Option<Program<'info, Token>>,
+
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+}
+
+/// Context for authorize_streaming_payment instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16, amount: u64, rate_per_slot: u64, category: u8, nonce: u64)]
+pub struct AuthorizeStreamingPayment<'info> {
+    /// The agent authorizing the stream. An ed25519 keypair, or a PDA
+    /// signing via CPI if its policy was created for one.
+    pub agent: Signer<'info>,
+    
+    /// The agent's policy account
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent.key().is_on_curve() || agent_policy.agent_is_pda
+            @ AgentBlinkPayError::AgentMustBeKeypair,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+    
+    /// The meter being paid
+    pub meter: Account<'info, Meter>,
+
+    /// Allowlist record for this policy/meter pair (PDA: ["allowed", agent_policy, meter]).
+    /// Required when the policy has `enforce_meter_allowlist` set.
+    #[account(
+        seeds = [b"allowed", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump = allowed_meter.bump,
+    )]
+    pub allowed_meter: Option<Account<'info, AllowedMeter>>,
+
+    /// Denylist record address for this policy/meter pair (PDA: ["denied", agent_policy, meter]).
+    /// Always required so the check can't be skipped; payment is rejected if it exists.
+    /// CHECK: Address is re-derived from seeds; the handler only checks its owner
+    #[account(
+        seeds = [b"denied", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump,
+    )]
+    pub denied_meter: UncheckedAccount<'info>,
+
+    /// Meter-side access record for this agent (PDA: ["access", meter, agent]).
+    /// Required when the meter has `allowlist_enabled` set.
+    #[account(
+        seeds = [b"access", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_access.bump,
+    )]
+    pub meter_access: Option<Account<'info, MeterAgentAccess>>,
+
+    /// The agent's call counter for this meter (PDA: ["usage", meter, agent])
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MeterUsage::LEN,
+        seeds = [b"usage", meter.key().as_ref(), agent.key().as_ref()],
+        bump
+    )]
+    pub meter_usage: Account<'info, MeterUsage>,
+    
+    /// The authorization account (PDA: ["auth", agent, meter, nonce]).
+    /// `init_if_needed` so a reused nonce fails with `NonceAlreadyUsed`
+    /// in the handler rather than inside the system program.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = Authorization::LEN,
+        seeds = [
+            b"auth",
+            agent.key().as_ref(),
+            meter.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump
+    )]
+    pub authorization: Account<'info, Authorization>,
+    
+    /// Account paying for the transaction
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+
+    /// Global config (PDA: ["config"]), selects the verifier
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// The verifier the Config selects: this program, or its external
+    /// `verifier_program`
+    /// CHECK: Compared against `Config::verifier` before the CPI
+    pub verifier_program: AccountInfo<'info>,
+
+    /// The key of the proof's circuit (PDA: ["vk", circuit_id]), checked
+    /// against the proof version before verifying
+    pub verifying_key: Account<'info, VerifyingKey>,
+
+    /// The payment's nullifier (PDA: ["nullifier", payment_nullifier(
+    /// b"auth", agent, meter, nonce, amount, policy_hash)]), created by the
+    /// handler
+    /// CHECK: Address is checked against the payment's nullifier
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+}
+
+/// Context for authorize_budget_with_proof instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16, total_amount: u64, category: u8, nonce: u64)]
+pub struct AuthorizeBudget<'info> {
+    /// The agent authorizing the budget. An ed25519 keypair, or a PDA
+    /// signing via CPI if its policy was created for one.
+    pub agent: Signer<'info>,
+    
+    /// The agent's policy account
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent.key().is_on_curve() || agent_policy.agent_is_pda
+            @ AgentBlinkPayError::AgentMustBeKeypair,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+    
+    /// The meter being paid
+    pub meter: Account<'info, Meter>,
+
+    /// Allowlist record for this policy/meter pair (PDA: ["allowed", agent_policy, meter]).
+    /// Required when the policy has `enforce_meter_allowlist` set.
+    #[account(
+        seeds = [b"allowed", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump = allowed_meter.bump,
+    )]
+    pub allowed_meter: Option<Account<'info, AllowedMeter>>,
+
+    /// Denylist record address for this policy/meter pair (PDA: ["denied", agent_policy, meter]).
+    /// Always required so the check can't be skipped; payment is rejected if it exists.
+    /// CHECK: Address is re-derived from seeds; the handler only checks its owner
+    #[account(
+        seeds = [b"denied", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump,
+    )]
+    pub denied_meter: UncheckedAccount<'info>,
+
+    /// Meter-side access record for this agent (PDA: ["access", meter, agent]).
+    /// Required when the meter has `allowlist_enabled` set.
+    #[account(
+        seeds = [b"access", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_access.bump,
+    )]
+    pub meter_access: Option<Account<'info, MeterAgentAccess>>,
+
+    /// The agent's call counter for this meter (PDA: ["usage", meter, agent])
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MeterUsage::LEN,
+        seeds = [b"usage", meter.key().as_ref(), agent.key().as_ref()],
+        bump
+    )]
+    pub meter_usage: Account<'info, MeterUsage>,
+    
+    /// The budget authorization (PDA: ["budget_auth", agent, meter, nonce]).
+    /// `init_if_needed` so a reused nonce fails with `NonceAlreadyUsed`.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = BudgetAuthorization::LEN,
+        seeds = [
+            b"budget_auth",
+            agent.key().as_ref(),
+            meter.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump
+    )]
+    pub budget_authorization: Account<'info, BudgetAuthorization>,
+    
+    /// Account paying for the transaction
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+
+    /// Global config (PDA: ["config"]), selects the verifier
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// The verifier the Config selects: this program, or its external
+    /// `verifier_program`
+    /// CHECK: Compared against `Config::verifier` before the CPI
+    pub verifier_program: AccountInfo<'info>,
+
+    /// The budget circuit's key (PDA: ["vk", BUDGET])
+    #[account(
+        seeds = [b"vk", &[circuit_ids::BUDGET]],
+        bump = verifying_key.bump,
+    )]
+    pub verifying_key: Account<'info, VerifyingKey>,
+
+    /// The budget's nullifier (PDA: ["nullifier", payment_nullifier(
+    /// b"budget_auth", agent, meter, nonce, total_amount, policy_hash)]),
+    /// created by the handler
+    /// CHECK: Address is checked against the payment's nullifier
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+}
+
+/// Context for record_budget_payment instruction.
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct RecordBudgetPayment<'info> {
+    /// Whoever submits the record: the agent, the meter's authority or a
+    /// relayer. The budget authorization carries the agent's consent.
+    pub recorder: Signer<'info>,
+
+    /// The agent making the payment
+    /// CHECK: Only used for PDA derivation; the policy, budget and usage
+    /// seeds all bind it to the budget's agent
+    pub agent: UncheckedAccount<'info>,
+    
+    /// The meter being paid
+    pub meter: Account<'info, Meter>,
+
+    /// The meter's counters (PDA: ["counters", meter])
+    #[account(
+        mut,
+        seeds = [b"counters", meter.key().as_ref()],
+        bump = meter_counters.load()?.bump,
+    )]
+    pub meter_counters: AccountLoader<'info, MeterCounters>,
+
+    /// The policy the budget was issued under
+    #[account(
+        mut,
+        seeds = [
+            b"policy",
+            agent.key().as_ref(),
+            &budget_authorization.policy_id.to_le_bytes()
+        ],
+        bump = agent_policy.bump,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+    
+    /// The budget authorization to draw from
+    #[account(
+        mut,
+        seeds = [
+            b"budget_auth",
+            agent.key().as_ref(),
+            meter.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump = budget_authorization.bump,
+    )]
+    pub budget_authorization: Account<'info, BudgetAuthorization>,
+
+    /// The agent's call counter for this meter (PDA: ["usage", meter, agent])
+    #[account(
+        mut,
+        seeds = [b"usage", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_usage.bump,
+    )]
+    pub meter_usage: Account<'info, MeterUsage>,
+
+    /// Global config (PDA: ["config"]), for the grace window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+/// Context for close_budget_authorization instruction.
+#[derive(Accounts)]
+pub struct CloseBudgetAuthorization<'info> {
+    /// The budget authorization to close, if spent or expired
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = rent_payer @ AgentBlinkPayError::RentPayerMismatch,
+    )]
+    pub budget_authorization: Account<'info, BudgetAuthorization>,
+
+    /// Receives the budget authorization's rent
+    /// CHECK: Must be the payer recorded on the budget authorization
+    #[account(mut)]
+    pub rent_payer: UncheckedAccount<'info>,
+
+    /// Global config (PDA: ["config"]), for the grace window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+/// Context for authorize_bundle_with_proof instruction.
+/// (meter, denied_meter) pairs are passed via `remaining_accounts`.
+#[derive(Accounts)]
+#[instruction(policy_id: u16, amounts: Vec<u64>, nonce: u64)]
+pub struct AuthorizeBundle<'info> {
+    /// The agent authorizing the bundle. An ed25519 keypair, or a PDA
+    /// signing via CPI if its policy was created for one.
+    pub agent: Signer<'info>,
+    
+    /// The agent's policy account
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent.key().is_on_curve() || agent_policy.agent_is_pda
+            @ AgentBlinkPayError::AgentMustBeKeypair,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// The bundle authorization (PDA: ["bundle_auth", agent, nonce]).
+    /// `init_if_needed` so a reused nonce fails with `NonceAlreadyUsed`.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = BundleAuthorization::LEN,
+        seeds = [b"bundle_auth", agent.key().as_ref(), &nonce.to_le_bytes()],
+        bump
+    )]
+    pub bundle_authorization: Account<'info, BundleAuthorization>,
+    
+    /// Account paying for the transaction
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+
+    /// Global config (PDA: ["config"]), selects the verifier
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// The verifier the Config selects: this program, or its external
+    /// `verifier_program`
+    /// CHECK: Compared against `Config::verifier` before the CPI
+    pub verifier_program: AccountInfo<'info>,
+
+    /// The budget circuit's key (PDA: ["vk", BUDGET])
+    #[account(
+        seeds = [b"vk", &[circuit_ids::BUDGET]],
+        bump = verifying_key.bump,
+    )]
+    pub verifying_key: Account<'info, VerifyingKey>,
+
+    /// The bundle's nullifier (PDA: ["nullifier", payment_nullifier(
+    /// b"bundle_auth", agent, Pubkey::default(), nonce, sum of amounts,
+    /// policy_hash)]), created by the handler
+    /// CHECK: Address is checked against the payment's nullifier
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+}
+
+/// Context for record_bundle_payment instruction.
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct RecordBundlePayment<'info> {
+    /// Whoever submits the record: the agent, the meter's authority or a
+    /// relayer. The bundle authorization carries the agent's consent.
+    pub recorder: Signer<'info>,
+
+    /// The agent making the payment
+    /// CHECK: Only used for PDA derivation; the policy, bundle and usage
+    /// seeds all bind it to the bundle's agent
+    pub agent: UncheckedAccount<'info>,
+    
+    /// The meter being paid; must be in the bundle at `meter_index`
+    pub meter: Account<'info, Meter>,
+
+    /// The meter's counters (PDA: ["counters", meter])
+    #[account(
+        mut,
+        seeds = [b"counters", meter.key().as_ref()],
+        bump = meter_counters.load()?.bump,
+    )]
+    pub meter_counters: AccountLoader<'info, MeterCounters>,
+
+    /// The policy the bundle was issued under
+    #[account(
+        mut,
+        seeds = [
+            b"policy",
+            agent.key().as_ref(),
+            &bundle_authorization.policy_id.to_le_bytes()
+        ],
+        bump = agent_policy.bump,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+    
+    /// The bundle authorization to record against
+    #[account(
+        mut,
+        seeds = [b"bundle_auth", agent.key().as_ref(), &nonce.to_le_bytes()],
+        bump = bundle_authorization.bump,
+    )]
+    pub bundle_authorization: Account<'info, BundleAuthorization>,
+
+    /// The agent's call counter for this meter (PDA: ["usage", meter, agent])
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MeterUsage::LEN,
+        seeds = [b"usage", meter.key().as_ref(), agent.key().as_ref()],
+        bump
+    )]
+    pub meter_usage: Account<'info, MeterUsage>,
+
+    /// Global config (PDA: ["config"]), for the grace window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// Pays for the usage account if it doesn't exist yet
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for close_bundle_authorization instruction.
+#[derive(Accounts)]
+pub struct CloseBundleAuthorization<'info> {
+    /// The bundle authorization to close, if spent or expired
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = rent_payer @ AgentBlinkPayError::RentPayerMismatch,
+    )]
+    pub bundle_authorization: Account<'info, BundleAuthorization>,
+
+    /// Receives the bundle authorization's rent
+    /// CHECK: Must be the payer recorded on the bundle authorization
+    #[account(mut)]
+    pub rent_payer: UncheckedAccount<'info>,
+
+    /// Global config (PDA: ["config"]), for the grace window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+/// Context for open_channel instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct OpenChannel<'info> {
+    /// The agent opening the channel; must be a keypair, as it signs vouchers
+    pub agent: Signer<'info>,
+    
+    /// The agent's policy account
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+    
+    /// The meter being paid
+    pub meter: Account<'info, Meter>,
+
+    /// Allowlist record for this policy/meter pair (PDA: ["allowed", agent_policy, meter]).
+    /// Required when the policy has `enforce_meter_allowlist` set.
+    #[account(
+        seeds = [b"allowed", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump = allowed_meter.bump,
+    )]
+    pub allowed_meter: Option<Account<'info, AllowedMeter>>,
+
+    /// Denylist record address for this policy/meter pair (PDA: ["denied", agent_policy, meter]).
+    /// Always required so the check can't be skipped; opening is rejected if it exists.
+    /// CHECK: Address is re-derived from seeds; the handler only checks its owner
+    #[account(
+        seeds = [b"denied", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump,
+    )]
+    pub denied_meter: UncheckedAccount<'info>,
+
+    /// Meter-side access record for this agent (PDA: ["access", meter, agent]).
+    /// Required when the meter has `allowlist_enabled` set.
+    #[account(
+        seeds = [b"access", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_access.bump,
+    )]
+    pub meter_access: Option<Account<'info, MeterAgentAccess>>,
+
+    /// The agent's call counter for this meter (PDA: ["usage", meter, agent])
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MeterUsage::LEN,
+        seeds = [b"usage", meter.key().as_ref(), agent.key().as_ref()],
+        bump
+    )]
+    pub meter_usage: Account<'info, MeterUsage>,
+    
+    /// The channel (PDA: ["channel", agent, meter])
+    #[account(
+        init,
+        payer = payer,
+        space = Channel::LEN,
+        seeds = [b"channel", agent.key().as_ref(), meter.key().as_ref()],
+        bump
+    )]
+    pub channel: Account<'info, Channel>,
+    
+    /// Account paying for the transaction
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for settle_channel instruction.
+#[derive(Accounts)]
+pub struct SettleChannel<'info> {
+    /// Current authority of the meter, submitting the voucher
+    pub authority: Signer<'info>,
+
+    /// The agent paying through the channel
+    /// CHECK: Only used for PDA derivation; the policy, channel and usage
+    /// seeds all bind it to the channel's agent
+    pub agent: UncheckedAccount<'info>,
+    
+    /// The meter being paid
+    #[account(
+        constraint = meter.authority == authority.key() @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub meter: Account<'info, Meter>,
+
+    /// The meter's counters (PDA: ["counters", meter])
+    #[account(
+        mut,
+        seeds = [b"counters", meter.key().as_ref()],
+        bump = meter_counters.load()?.bump,
+    )]
+    pub meter_counters: AccountLoader<'info, MeterCounters>,
+
+    /// The policy settles are charged to
+    #[account(
+        mut,
+        seeds = [b"policy", agent.key().as_ref(), &channel.policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+    
+    /// The channel being settled (PDA: ["channel", agent, meter])
+    #[account(
+        mut,
+        seeds = [b"channel", agent.key().as_ref(), meter.key().as_ref()],
+        bump = channel.bump,
+    )]
+    pub channel: Account<'info, Channel>,
+
+    /// The agent's call counter for this meter (PDA: ["usage", meter, agent])
+    #[account(
+        mut,
+        seeds = [b"usage", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_usage.bump,
+    )]
+    pub meter_usage: Account<'info, MeterUsage>,
+
+    /// Global config (PDA: ["config"]), for the grace window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// The instructions sysvar, to find the Ed25519 voucher check
+    /// CHECK: Address is checked against the sysvar id
+    #[account(address = solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+/// Context for close_channel instruction.
+#[derive(Accounts)]
+pub struct CloseChannel<'info> {
+    /// The channel to close, if expired or fully claimed
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = rent_payer @ AgentBlinkPayError::RentPayerMismatch,
+    )]
+    pub channel: Account<'info, Channel>,
+
+    /// Receives the channel's rent
+    /// CHECK: Must be the payer recorded on the channel
+    #[account(mut)]
+    pub rent_payer: UncheckedAccount<'info>,
+
+    /// Global config (PDA: ["config"]), for the grace window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+/// Context for authorize_payment_auto_nonce instruction.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct AuthorizePaymentAutoNonce<'info> {
+    /// The agent authorizing the payment. An ed25519 keypair, or a PDA
+    /// signing via CPI if its policy was created for one.
+    pub agent: Signer<'info>,
+    
+    /// The agent's policy account
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent.key().is_on_curve() || agent_policy.agent_is_pda
+            @ AgentBlinkPayError::AgentMustBeKeypair,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+    
+    /// The meter being paid
+    pub meter: Account<'info, Meter>,
+
+    /// Allowlist record for this policy/meter pair (PDA: ["allowed", agent_policy, meter]).
+    /// Required when the policy has `enforce_meter_allowlist` set.
+    #[account(
+        seeds = [b"allowed", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump = allowed_meter.bump,
+    )]
+    pub allowed_meter: Option<Account<'info, AllowedMeter>>,
+
+    /// Denylist record address for this policy/meter pair (PDA: ["denied", agent_policy, meter]).
+    /// Always required so the check can't be skipped; payment is rejected if it exists.
+    /// CHECK: Address is re-derived from seeds; the handler only checks its owner
+    #[account(
+        seeds = [b"denied", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump,
+    )]
+    pub denied_meter: UncheckedAccount<'info>,
+
+    /// Meter-side access record for this agent (PDA: ["access", meter, agent]).
+    /// Required when the meter has `allowlist_enabled` set.
+    #[account(
+        seeds = [b"access", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_access.bump,
+    )]
+    pub meter_access: Option<Account<'info, MeterAgentAccess>>,
+
+    /// The agent's call counter for this meter (PDA: ["usage", meter, agent])
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MeterUsage::LEN,
+        seeds = [b"usage", meter.key().as_ref(), agent.key().as_ref()],
+        bump
+    )]
+    pub meter_usage: Account<'info, MeterUsage>,
+    
+    /// The pair's nonce counter (PDA: ["nonce", agent, meter])
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = NonceCounter::LEN,
+        seeds = [b"nonce", agent.key().as_ref(), meter.key().as_ref()],
+        bump
+    )]
+    pub nonce_counter: Account<'info, NonceCounter>,
+
+    /// The authorization account (PDA: ["auth", agent, meter, next_nonce])
+    #[account(
+        init,
+        payer = payer,
+        space = Authorization::LEN,
+        seeds = [
+            b"auth",
+            agent.key().as_ref(),
+            meter.key().as_ref(),
+            &nonce_counter.next_nonce.to_le_bytes()
+        ],
+        bump
+    )]
+    pub authorization: Account<'info, Authorization>,
+    
+    /// Account paying for the transaction
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+
+    /// Global config (PDA: ["config"]), selects the verifier
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// The verifier the Config selects: this program, or its external
+    /// `verifier_program`
+    /// CHECK: Compared against `Config::verifier` before the CPI
+    pub verifier_program: AccountInfo<'info>,
+
+    /// The key of the proof's circuit (PDA: ["vk", circuit_id]), checked
+    /// against the proof version before verifying
+    pub verifying_key: Account<'info, VerifyingKey>,
+
+    /// The payment's nullifier (PDA: ["nullifier", payment_nullifier(
+    /// b"auth", agent, meter, nonce, amount, policy_hash)]), created by the
+    /// handler
+    /// CHECK: Address is checked against the payment's nullifier
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+}
+
+/// Context for authorize_payments_batch instruction. The authorization PDAs
+/// are passed via `remaining_accounts`.
+#[derive(Accounts)]
+#[instruction(policy_id: u16)]
+pub struct AuthorizePaymentsBatch<'info> {
+    /// The agent authorizing the payments. An ed25519 keypair, or a PDA
+    /// signing via CPI if its policy was created for one.
+    pub agent: Signer<'info>,
+
+    /// The agent's policy account
+    #[account(
+        seeds = [b"policy", agent.key().as_ref(), &policy_id.to_le_bytes()],
+        bump = agent_policy.bump,
+        constraint = agent.key().is_on_curve() || agent_policy.agent_is_pda
+            @ AgentBlinkPayError::AgentMustBeKeypair,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// The meter being paid
+    pub meter: Account<'info, Meter>,
+
+    /// Allowlist record for this policy/meter pair (PDA: ["allowed", agent_policy, meter]).
+    /// Required when the policy has `enforce_meter_allowlist` set.
+    #[account(
+        seeds = [b"allowed", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump = allowed_meter.bump,
+    )]
+    pub allowed_meter: Option<Account<'info, AllowedMeter>>,
+
+    /// Denylist record address for this policy/meter pair (PDA: ["denied", agent_policy, meter]).
+    /// CHECK: Address is re-derived from seeds; the handler only checks its owner
+    #[account(
+        seeds = [b"denied", agent_policy.key().as_ref(), meter.key().as_ref()],
+        bump,
+    )]
+    pub denied_meter: UncheckedAccount<'info>,
+
+    /// Meter-side access record for this agent (PDA: ["access", meter, agent]).
+    /// Required when the meter has `allowlist_enabled` set.
+    #[account(
+        seeds = [b"access", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_access.bump,
+    )]
+    pub meter_access: Option<Account<'info, MeterAgentAccess>>,
+
+    /// The agent's call counter for this meter (PDA: ["usage", meter, agent])
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MeterUsage::LEN,
+        seeds = [b"usage", meter.key().as_ref(), agent.key().as_ref()],
+        bump
+    )]
+    pub meter_usage: Account<'info, MeterUsage>,
+
+    /// Account paying for the transaction and the authorizations
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// Global config (PDA: ["config"]), selects the verifier
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// The verifier the Config selects: this program, or its external
+    /// `verifier_program`
+    /// CHECK: Compared against `Config::verifier` before the CPI
+    pub verifier_program: AccountInfo<'info>,
+
+    /// The key of the proof's circuit (PDA: ["vk", circuit_id]), checked
+    /// against the proof version before verifying
+    pub verifying_key: Account<'info, VerifyingKey>,
+}
+
+/// Context for revoke_authorization instruction.
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct RevokeAuthorization<'info> {
+    /// The agent or the owner of the policy the authorization was issued under
+    pub authority: Signer<'info>,
+
+    /// The policy the authorization was issued under
+    #[account(
+        seeds = [
+            b"policy",
+            authorization.agent.as_ref(),
+            &authorization.policy_id.to_le_bytes()
+        ],
+        bump = agent_policy.bump,
+        constraint = authority.key() == authorization.agent
+            || agent_policy.is_owner(&authority.key()) @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+
+    /// The authorization to revoke (PDA: ["auth", agent, meter, nonce])
+    #[account(
+        mut,
+        seeds = [
+            b"auth",
+            authorization.agent.as_ref(),
+            authorization.meter.as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump = authorization.bump,
+    )]
+    pub authorization: Account<'info, Authorization>,
+}
+
+/// Context for close_authorization instruction.
+#[derive(Accounts)]
+pub struct CloseAuthorization<'info> {
+    /// The authorization to close, if used or expired
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = rent_payer @ AgentBlinkPayError::RentPayerMismatch,
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    /// Receives the authorization's rent
+    /// CHECK: Must be the payer recorded on the authorization
+    #[account(mut)]
+    pub rent_payer: UncheckedAccount<'info>,
+
+    /// Global config (PDA: ["config"]), for the grace window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+/// Context for close_nullifier instruction.
+#[derive(Accounts)]
+pub struct CloseNullifier<'info> {
+    /// The nullifier to close
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = rent_payer @ AgentBlinkPayError::RentPayerMismatch,
+        has_one = authorization @ AgentBlinkPayError::InvalidAuthorizationAccount,
+    )]
+    pub nullifier: Account<'info, Nullifier>,
+
+    /// The nullifier's authorization, which must be closed or closable
+    /// CHECK: Must be the authorization recorded on the nullifier
+    pub authorization: UncheckedAccount<'info>,
+
+    /// Receives the nullifier's rent
+    /// CHECK: Must be the payer recorded on the nullifier
+    #[account(mut)]
+    pub rent_payer: UncheckedAccount<'info>,
+
+    /// Global config (PDA: ["config"]), for the grace window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+/// Context for close_pending_verification instruction.
+#[derive(Accounts)]
+pub struct ClosePendingVerification<'info> {
+    /// The expired pending verification to close
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = rent_payer @ AgentBlinkPayError::RentPayerMismatch,
+    )]
+    pub pending_verification: Account<'info, PendingVerification>,
+
+    /// Receives the pending verification's rent
+    /// CHECK: Must be the payer recorded on the pending verification
+    #[account(mut)]
+    pub rent_payer: UncheckedAccount<'info>,
+}
+
+/// Context for close_proof_receipt instruction.
+#[derive(Accounts)]
+pub struct CloseProofReceipt<'info> {
+    /// The expired proof receipt to close
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = rent_payer @ AgentBlinkPayError::RentPayerMismatch,
+    )]
+    pub proof_receipt: Account<'info, ProofReceipt>,
+
+    /// Receives the proof receipt's rent
+    /// CHECK: Must be the payer recorded on the proof receipt
+    #[account(mut)]
+    pub rent_payer: UncheckedAccount<'info>,
+}
+
+/// Context for sweep_expired_authorizations instruction. The
+/// (authorization, rent_payer) pairs are passed via `remaining_accounts`.
+#[derive(Accounts)]
+pub struct SweepAuthorizations<'info> {
+    /// Anyone; only pays the transaction fee
+    pub cranker: Signer<'info>,
+
+    /// Global config (PDA: ["config"]), for the grace window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+/// Context for confirm_settlement and report_settlement_failure instructions.
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct ReportSettlement<'info> {
+    /// The Config's settlement authority
+    pub settlement_authority: Signer<'info>,
+
+    /// Global config (PDA: ["config"])
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.settlement_authority == settlement_authority.key()
+            @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The agent that made the payment
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// The meter that was paid
+    /// CHECK: Only used for PDA derivation
+    pub meter: UncheckedAccount<'info>,
+
+    /// The authorization being settled (PDA: ["auth", agent, meter, nonce])
+    #[account(
+        mut,
+        seeds = [
+            b"auth",
+            agent.key().as_ref(),
+            meter.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump = authorization.bump,
+    )]
+    pub authorization: Account<'info, Authorization>,
+}
+
+/// Context for open_dispute instruction.
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct OpenDispute<'info> {
+    /// The agent or the meter's authority; pays the dispute's rent
+    #[account(mut)]
+    pub opener: Signer<'info>,
+
+    /// The meter that was paid
+    #[account(address = authorization.meter)]
+    pub meter: Account<'info, Meter>,
+
+    /// The disputed authorization (PDA: ["auth", agent, meter, nonce])
+    #[account(
+        mut,
+        seeds = [
+            b"auth",
+            authorization.agent.as_ref(),
+            meter.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump = authorization.bump,
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    /// The dispute (PDA: ["dispute", authorization])
+    #[account(
+        init,
+        payer = opener,
+        space = Dispute::LEN,
+        seeds = [b"dispute", authorization.key().as_ref()],
+        bump
+    )]
+    pub dispute: Account<'info, Dispute>,
+
+    /// Global config (PDA: ["config"]), for the dispute window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for resolve_dispute instruction.
+#[derive(Accounts)]
+pub struct ResolveDispute<'info> {
+    /// The Config admin
+    pub admin: Signer<'info>,
+
+    /// Global config (PDA: ["config"])
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The disputed authorization
+    #[account(mut)]
+    pub authorization: Account<'info, Authorization>,
+
+    /// The dispute to close (PDA: ["dispute", authorization])
+    #[account(
+        mut,
+        close = opened_by,
+        seeds = [b"dispute", authorization.key().as_ref()],
+        bump = dispute.bump,
+        has_one = authorization,
+        has_one = opened_by,
+    )]
+    pub dispute: Account<'info, Dispute>,
+
+    /// Receives the dispute's rent
+    /// CHECK: Must be the opener recorded on the dispute
+    #[account(mut)]
+    pub opened_by: UncheckedAccount<'info>,
+}
+
+/// Context for issue_refund instruction.
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct IssueRefund<'info> {
+    /// Current authority of the meter
+    pub authority: Signer<'info>,
+
+    /// The meter that was paid
+    #[account(
+        constraint = meter.authority == authority.key() @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub meter: Account<'info, Meter>,
+
+    /// The refunded authorization (PDA: ["auth", agent, meter, nonce])
+    #[account(
+        mut,
+        seeds = [
+            b"auth",
+            authorization.agent.as_ref(),
+            meter.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump = authorization.bump,
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    /// Global config (PDA: ["config"]), for the refund window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+/// Context for record_metered_usage instruction.
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct RecordMeteredUsage<'info> {
+    /// Current authority of the meter, reporting the usage
+    pub authority: Signer<'info>,
+
+    /// The agent making the payment
+    /// CHECK: Only used for PDA derivation; the policy, authorization and
+    /// usage seeds all bind it to the authorization's agent
+    pub agent: UncheckedAccount<'info>,
+    
+    /// The meter being paid
+    #[account(
+        constraint = meter.authority == authority.key() @ AgentBlinkPayError::Unauthorized,
+    )]
+    pub meter: Account<'info, Meter>,
+
+    /// The meter's counters (PDA: ["counters", meter])
+    #[account(
+        mut,
+        seeds = [b"counters", meter.key().as_ref()],
+        bump = meter_counters.load()?.bump,
+    )]
+    pub meter_counters: AccountLoader<'info, MeterCounters>,
+
+    /// The policy the authorization was issued under
+    #[account(
+        mut,
+        seeds = [
+            b"policy",
+            agent.key().as_ref(),
+            &authorization.policy_id.to_le_bytes()
+        ],
+        bump = agent_policy.bump,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+    
+    /// The authorization to consume
+    #[account(
+        mut,
+        seeds = [
+            b"auth",
+            agent.key().as_ref(),
+            meter.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump = authorization.bump,
+        constraint = Category::same(authorization.category, meter.category).unwrap_or(false)
+            @ AgentBlinkPayError::CategoryMismatch,
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    /// The agent's call counter for this meter (PDA: ["usage", meter, agent])
+    #[account(
+        mut,
+        seeds = [b"usage", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_usage.bump,
+    )]
+    pub meter_usage: Account<'info, MeterUsage>,
+
+    /// Global config (PDA: ["config"]), for the grace window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+/// Context for record_meter_payment instruction.
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct RecordPayment<'info> {
+    /// Whoever submits the record: the agent, the meter's authority or a
+    /// relayer. The authorization already carries the agent's consent.
+    pub recorder: Signer<'info>,
+
+    /// The agent making the payment
+    /// CHECK: Only used for PDA derivation; the policy, authorization and
+    /// usage seeds all bind it to the authorization's agent
+    pub agent: UncheckedAccount<'info>,
+    
+    /// The meter being paid
+    pub meter: Account<'info, Meter>,
+
+    /// The meter's counters (PDA: ["counters", meter])
+    #[account(
+        mut,
+        seeds = [b"counters", meter.key().as_ref()],
+        bump = meter_counters.load()?.bump,
+    )]
+    pub meter_counters: AccountLoader<'info, MeterCounters>,
+
+    /// The policy the authorization was issued under (charged against the
+    /// lifetime budget)
+    #[account(
+        mut,
+        seeds = [
+            b"policy",
+            agent.key().as_ref(),
+            &authorization.policy_id.to_le_bytes()
+        ],
+        bump = agent_policy.bump,
+    )]
+    pub agent_policy: Account<'info, AgentPolicy>,
+    
+    /// The authorization to consume
+    #[account(
+        mut,
+        seeds = [
+            b"auth",
+            agent.key().as_ref(),
+            meter.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump = authorization.bump,
+        constraint = authorization.agent == agent.key(),
+        constraint = authorization.meter == meter.key(),
+        constraint = Category::same(authorization.category, meter.category).unwrap_or(false)
+            @ AgentBlinkPayError::CategoryMismatch,
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    /// The agent's call counter for this meter (PDA: ["usage", meter, agent])
+    #[account(
+        mut,
+        seeds = [b"usage", meter.key().as_ref(), agent.key().as_ref()],
+        bump = meter_usage.bump,
+    )]
+    pub meter_usage: Account<'info, MeterUsage>,
+
+    /// Global config (PDA: ["config"]), for the grace window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// Escrowed authorizations only: the escrow to pay out of
+    #[account(
+        mut,
+        address = authorization.escrow @ AgentBlinkPayError::InvalidEscrowAccount,
+    )]
+    pub escrow: Option<Account<'info, TokenAccount>>,
+
+    /// Escrowed authorizations only: the meter authority's token account
+    /// receiving the payment
+    #[account(
+        mut,
+        constraint = merchant_token_account.mint == authorization.mint
+            @ AgentBlinkPayError::MintMismatch,
+        constraint = merchant_token_account.owner == meter.authority
+            @ AgentBlinkPayError::InvalidEscrowAccount,
+    )]
+    pub merchant_token_account: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Option<Program<'info, Token>>,
+
+    /// With `create_receipt` only: the receipt
+    /// (PDA: ["receipt", meter, agent, nonce])
+    /// CHECK: Address is re-derived in the handler; created there if empty
+    #[account(mut)]
+    pub receipt: Option<UncheckedAccount<'info>>,
+
+    /// With `create_receipt` only: funds a new receipt
+    #[account(mut)]
+    pub payer: Option<Signer<'info>>,
+
+    pub system_program: Option<Program<'info, System>>,
+}
+
+/// Context for close_receipt instruction.
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct CloseReceipt<'info> {
+    /// The agent the receipt was issued to
+    pub agent: Signer<'info>,
+
+    /// The meter that was paid
+    /// CHECK: Only used for PDA derivation, so receipts of closed meters can be closed too
+    pub meter: UncheckedAccount<'info>,
+
+    /// The receipt (PDA: ["receipt", meter, agent, nonce])
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [b"receipt", meter.key().as_ref(), agent.key().as_ref(), &nonce.to_le_bytes()],
+        bump = receipt.bump,
+        has_one = rent_payer @ AgentBlinkPayError::RentPayerMismatch,
+    )]
+    pub receipt: Account<'info, Receipt>,
+
+    /// Receives the receipt's rent
+    /// CHECK: Must be the payer recorded on the receipt
+    #[account(mut)]
+    pub rent_payer: UncheckedAccount<'info>,
+}
+
+/// Context for open_agent_vault instruction.
+#[derive(Accounts)]
+pub struct OpenAgentVault<'info> {
+    /// The agent the vault belongs to
+    /// CHECK: Only used for PDA derivation
+    pub agent: UncheckedAccount<'info>,
+
+    /// Token the vault holds
+    pub mint: Account<'info, Mint>,
+
+    /// The vault (PDA: ["vault", agent, mint]), its own token authority
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"vault", agent.key().as_ref(), mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = vault,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    /// Account paying for the vault's rent
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for withdraw_from_vault instruction.
+#[derive(Accounts)]
+pub struct WithdrawFromVault<'info> {
+    /// The agent the vault belongs to
+    pub agent: Signer<'info>,
+
+    /// The agent's vault (PDA: ["vault", agent, mint])
+    #[account(
+        mut,
+        seeds = [b"vault", agent.key().as_ref(), vault.mint.as_ref()],
+        bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    /// Receives the withdrawn tokens
+    #[account(mut, token::mint = vault.mint)]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Context for cancel_escrowed_authorization instruction.
+#[derive(Accounts)]
+pub struct CancelEscrowedAuthorization<'info> {
+    /// Anyone; only pays the transaction fee
+    pub cranker: Signer<'info>,
+
+    /// The expired or revoked authorization
+    #[account(
+        mut,
+        seeds = [
+            b"auth",
+            authorization.agent.as_ref(),
+            authorization.meter.as_ref(),
+            &authorization.nonce.to_le_bytes()
+        ],
+        bump = authorization.bump,
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    /// The authorization's escrow
+    #[account(
+        mut,
+        address = authorization.escrow @ AgentBlinkPayError::InvalidEscrowAccount,
+    )]
+    pub escrow: Account<'info, TokenAccount>,
+
+    /// The vault the escrow was funded from (PDA: ["vault", agent, mint])
+    #[account(
+        mut,
+        seeds = [b"vault", authorization.agent.as_ref(), authorization.mint.as_ref()],
+        bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    /// Global config (PDA: ["config"]), for the grace window
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+// =============================================================================
+// EVENTS
+// =============================================================================
+
+/// Emitted when a meter payment is recorded.
+/// 
+/// Off-chain services (specifically the Circle integration service)
+/// listen for this event to trigger USDC transfers from the agent's
+/// Circle wallet to the merchant's Circle wallet.
+#[event]
+pub struct MeterPaid {
+    /// The agent who made the payment
+    pub agent: Pubkey,
+    
+    /// The meter that was paid
+    pub meter: Pubkey,
+
+    /// Signer of the instruction that recorded the payment (the agent
+    /// itself on the subscription paths)
+    pub recorded_by: Pubkey,
+    
+    /// Amount paid (smallest units of `mint`)
+    pub amount: u64,
+
+    /// Token the payment settles in
+    pub mint: Pubkey,
+    
+    /// Category of the payment
+    pub category: u8,
+    
+    /// Unique nonce for this payment
+    pub nonce: u64,
+
+    /// The agent policy the payment was authorized under
+    pub policy_id: u16,
+
+    /// Calls recorded against the meter so far, including this one
+    pub meter_total_calls: u64,
+
+    /// Volume recorded against the meter so far, including this one
+    pub meter_total_volume: u64,
+
+    /// True if the call was covered by the meter's free allowance
+    pub free_call: bool,
+
+    /// Part of `amount` owed to the meter's referrer (0 without a referrer)
+    pub referrer_amount: u64,
+
+    /// Part of `amount` owed to the meter's primary wallet
+    pub primary_amount: u64,
+
+    /// Part of `amount` owed to each of the meter's revenue splits
+    pub split_amounts: [u64; 3], // MAX_REVENUE_SPLITS
+
+    /// The agent's recorded calls to this meter, including this one
+    /// (0 for subscription meters, which keep no per-agent usage)
+    pub agent_calls: u64,
+
+    /// The agent's recorded volume on this meter, including this payment
+    pub agent_volume: u64,
+
+    /// Slot of the agent's first recorded payment to this meter
+    pub agent_first_call_slot: u64,
+
+    /// Amount left on the authorization after this payment (0 for
+    /// subscription meters)
+    pub amount_remaining: u64,
+
+    /// Total amount of the authorization (the fee when subscribing, 0 for
+    /// subscription calls)
+    pub authorized_amount: u64,
+
+    /// Meter price when the authorization was issued (0 for subscription
+    /// meters)
+    pub price_at_authorization: u64,
+
+    /// Meter price when the payment was recorded (0 for subscription meters)
+    pub current_price: u64,
+
+    /// The meter was repriced between authorization and this payment, so
+    /// `amount` follows the old price
+    pub price_changed: bool,
+
+    /// Request id given when the payment was authorized (all zero = none,
+    /// and for subscription meters)
+    pub request_id: [u8; 32],
+
+    /// Paid out of the authorization's escrow, so the Circle service must
+    /// not transfer it again
+    pub settled_on_chain: bool,
+
+    /// Recorded after the authorization's `expires_at_slot`, within the
+    /// Config's grace window (always false for subscription meters)
+    pub recorded_in_grace: bool,
+
+    /// Re-emitted after `report_settlement_failure`; budgets and counters
+    /// were already charged by the original record
+    pub settlement_retry: bool,
+
+    /// Usage units behind `amount` when recorded by `record_metered_usage`
+    /// (0 otherwise)
+    pub units: u64,
+
+    /// Part of `amount` the agent added as a tip, to be routed like the
+    /// rest of the payment or to a tip wallet (0 = none)
+    pub tip: u64,
+
+    /// How the authorization behind the payment was checked (see
+    /// `verification_methods`; NONE for payments not recorded against an
+    /// Authorization)
+    pub verification_method: u8,
+
+    /// What checked it (see `Authorization::verifier_ref`)
+    pub verifier_ref: Pubkey,
+    
+    /// Slot when payment was recorded
+    pub slot: u64,
+}
+
+/// Emitted when a meter is registered, so indexers don't have to poll
+/// for new Meter accounts.
+#[event]
+pub struct MeterCreated {
+    pub meter: Pubkey,
+    /// Identifier the meter PDA was derived from (default for create_meter_v2)
+    pub meter_id: Pubkey,
+    /// String id the meter PDA was derived from (empty for create_meter)
+    pub endpoint_id: String,
+    pub authority: Pubkey,
+    pub price_per_call: u64,
+    pub category: u8,
+    pub requires_zk: bool,
+    pub merchant_wallet_id: String,
+    pub name: String,
+    pub endpoint_hash: [u8; 32],
+    pub referrer_wallet_id: String,
+    pub referrer_bps: u16,
+    pub accepted_mint: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when a meter's authority changes its pricing or metadata fields.
+#[event]
+pub struct MeterUpdated {
+    pub meter: Pubkey,
+    pub old_price_per_call: u64,
+    pub new_price_per_call: u64,
+    pub old_category: u8,
+    pub new_category: u8,
+    pub old_requires_zk: bool,
+    pub new_requires_zk: bool,
+    pub old_free_calls: u16,
+    pub new_free_calls: u16,
+    pub old_name: String,
+    pub new_name: String,
+    pub old_endpoint_hash: [u8; 32],
+    pub new_endpoint_hash: [u8; 32],
+    pub old_max_amount_per_payment: u64,
+    pub new_max_amount_per_payment: u64,
+    pub old_min_proof_version: u8,
+    pub new_min_proof_version: u8,
+    pub slot: u64,
+}
+
+/// Emitted when a meter's pricing tiers are replaced.
+#[event]
+pub struct MeterTiersUpdated {
+    pub meter: Pubkey,
+    pub tiers: [PriceTier; 4], // MAX_PRICE_TIERS
+    pub slot: u64,
+}
+
+/// Emitted when a meter's rate limit is changed.
+#[event]
+pub struct MeterRateLimitUpdated {
+    pub meter: Pubkey,
+    pub old_max_calls_per_window: u32,
+    pub new_max_calls_per_window: u32,
+    pub old_window_slots: u32,
+    pub new_window_slots: u32,
+    pub slot: u64,
+}
+
+/// Emitted when an agent starts or renews a subscription.
+#[event]
+pub struct Subscribed {
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub policy_id: u16,
+    pub fee: u64,
+    pub expires_at_slot: u64,
+    pub slot: u64,
+}
+
+/// Emitted when a meter is paused or unpaused.
+#[event]
+pub struct MeterStatusChanged {
+    pub meter: Pubkey,
+    pub active: bool,
+    pub slot: u64,
+}
+
+/// Emitted when a meter is closed, so indexers can mark the endpoint dead.
+#[event]
+pub struct MeterClosed {
+    pub meter: Pubkey,
+    pub authority: Pubkey,
+    pub recipient: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when an agent's policy is created or updated.
+#[event]
+pub struct PolicyUpdated {
+    pub agent_pubkey: Pubkey,
+    pub policy_id: u16,
+    pub policy_hash: [u8; 32],
+    pub allowed_category: u8,
+    pub max_per_tx: u64,
+    pub frozen: bool,
+    pub total_budget: u64,
+    pub enforce_meter_allowlist: bool,
+    pub always_require_zk: bool,
+    pub metadata_hash: [u8; 32],
+    pub daily_limit: u64,
+    pub alert_threshold_bps: u16,
+    pub restrict_mint: Option<Pubkey>,
+    pub allowlist_root: [u8; 32],
+    pub slot: u64,
+}
+
+/// Emitted when a payment uses up the last of a policy's lifetime budget
+/// and the policy is frozen automatically.
+#[event]
+pub struct BudgetExhausted {
+    pub agent_pubkey: Pubkey,
+    pub policy_id: u16,
+    pub total_budget: u64,
+    pub lifetime_spent: u64,
+    pub slot: u64,
+}
+
+/// Emitted when an agent is paused by its owner or guardian.
+#[event]
+pub struct PolicyPaused {
+    pub agent_pubkey: Pubkey,
+    pub policy_id: u16,
+    pub paused_by: Pubkey,
+    pub paused_until_slot: u64,
+    pub slot: u64,
+}
+
+/// Emitted when watcher-reported failures freeze a policy.
+#[event]
+pub struct CircuitBreakerTripped {
+    pub agent_pubkey: Pubkey,
+    pub policy_id: u16,
+    pub failed_auth_attempts: u16,
+    pub reported_by: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when a policy owner nominates a new owner (default pubkey = cancelled).
+#[event]
+pub struct OwnershipTransferStarted {
+    pub agent_pubkey: Pubkey,
+    pub policy_id: u16,
+    pub owner: Pubkey,
+    pub pending_owner: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when the nominated owner accepts a policy.
+#[event]
+pub struct OwnershipTransferred {
+    pub agent_pubkey: Pubkey,
+    pub policy_id: u16,
+    pub previous_owner: Pubkey,
+    pub new_owner: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when a meter authority nominates a new authority (default pubkey = cancelled).
+#[event]
+pub struct MeterAuthorityTransferStarted {
+    pub meter: Pubkey,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when the nominated authority accepts a meter.
+#[event]
+pub struct MeterAuthorityTransferred {
+    pub meter: Pubkey,
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when a meter's price changes. Increases take effect at
+/// `effective_slot`; decreases take effect immediately (`effective_slot == slot`).
+#[event]
+pub struct MeterPriceScheduled {
+    pub meter: Pubkey,
+    pub old_price: u64,
+    pub new_price: u64,
+    pub effective_slot: u64,
+    pub slot: u64,
+}
+
+/// Emitted when a meter's primary Circle wallet changes.
+#[event]
+pub struct MerchantWalletUpdated {
+    pub meter: Pubkey,
+    pub old_wallet_id: String,
+    pub new_wallet_id: String,
+    pub slot: u64,
+}
+
+/// Emitted when a meter's revenue splits are replaced.
+#[event]
+pub struct MeterSplitsUpdated {
+    pub meter: Pubkey,
+    pub splits: [RevenueSplit; 3], // MAX_REVENUE_SPLITS
+    pub slot: u64,
+}
+
+/// Emitted when a meter's authority appoints or removes its operator.
+#[event]
+pub struct MeterOperatorChanged {
+    pub meter: Pubkey,
+    pub old_operator: Option<Pubkey>,
+    pub new_operator: Option<Pubkey>,
+    pub slot: u64,
+}
+
+/// Emitted when a meter's agent allowlist is turned on or off.
+#[event]
+pub struct MeterAllowlistChanged {
+    pub meter: Pubkey,
+    pub enabled: bool,
+    pub slot: u64,
+}
+
+/// Emitted when an agent buys prepaid credit; the Circle service settles `amount`.
+#[event]
+pub struct CreditsPurchased {
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub policy_id: u16,
+    pub amount: u64,
+    pub remaining: u64,
+    pub slot: u64,
+}
+
+/// Emitted when a meter authority draws down an agent's credit.
+#[event]
+pub struct CreditConsumed {
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub units: u64,
+    pub amount: u64,
+    pub remaining: u64,
+    pub slot: u64,
+}
+
+/// Emitted when an agent gives up its unused credit; the Circle service returns `amount`.
+#[event]
+pub struct CreditsRefunded {
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub policy_id: u16,
+    pub amount: u64,
+    pub slot: u64,
+}
+
+/// Emitted when a policy is moved to a new agent key.
+#[event]
+pub struct AgentKeyRotated {
+    pub old_agent: Pubkey,
+    pub new_agent: Pubkey,
+    pub policy_id: u16,
+    pub slot: u64,
+}
+
+/// Emitted the first time a payment pushes spend past the policy's
+/// `alert_threshold_bps` of a limit (see `limit_types`).
+#[event]
+pub struct SpendThresholdCrossed {
+    pub agent_pubkey: Pubkey,
+    pub policy_id: u16,
+    pub limit_type: u8,
+    pub spent: u64,
+    pub limit: u64,
+    pub slot: u64,
+}
+
+/// Emitted when `reset_spend_window` zeros a policy's daily window.
+/// Carries the values it replaced for the audit trail.
+#[event]
+pub struct SpendWindowReset {
+    pub agent_pubkey: Pubkey,
+    pub policy_id: u16,
+    pub old_spent_today: u64,
+    pub old_day_start_slot: u64,
+    pub reset_by_owner: Pubkey,
+    pub reset_by_admin: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when the global Config is created or updated.
+#[event]
+pub struct ConfigUpdated {
+    pub admin: Pubkey,
+    pub watchers: [Pubkey; 4], // MAX_WATCHERS
+    pub auto_freeze_threshold: u16,
+    pub min_price_delay_slots: u64,
+    pub min_price_per_call: u64,
+    pub max_price_per_call: u64,
+    pub usdc_mint: Pubkey,
+    pub max_authorization_ttl_secs: u32,
+    pub grace_slots: u16,
+    pub settlement_authority: Pubkey,
+    pub dispute_window_slots: u64,
+    pub refund_window_slots: u64,
+    pub verifier_program: Pubkey,
+    pub external_verifier: bool,
+    pub enforcement_mode: u8,
+    pub policy_oracle: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when the admin sets a circuit's verifying key.
+#[event]
+pub struct VerifyingKeySet {
+    pub circuit_id: u8,
+    pub circuit_version: u8,
+    pub num_public_inputs: u8,
+    pub locked: bool,
+    pub admin: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when the admin sets a circuit's UltraHonk verifier.
+#[event]
+pub struct HonkVerifyingKeySet {
+    pub circuit_id: u8,
+    pub circuit_version: u8,
+    pub verifier_program: Pubkey,
+    pub vk_hash: [u8; 32],
+    pub locked: bool,
+    pub admin: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when the admin removes a circuit's verifying key.
+#[event]
+pub struct VerifyingKeyRemoved {
+    pub circuit_id: u8,
+    /// Scheme of the removed key (see `proof_schemes`)
+    pub scheme: u8,
+    pub admin: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when permissive enforcement lets a proof that failed its checks
+/// through.
+#[event]
+pub struct ProofCheckSkipped {
+    pub agent: Pubkey,
+    pub circuit_id: u8,
+    /// Error the check failed with (0 if it wasn't a program error code)
+    pub error_code: u32,
+    pub slot: u64,
+}
+
+/// Emitted when the owner tops up a policy's lifetime budget.
+#[event]
+pub struct BudgetExtended {
     pub agent_pubkey: Pubkey,
+    pub policy_id: u16,
+    pub total_budget: u64,
+    pub lifetime_spent: u64,
+    pub unfrozen: bool,
+    pub slot: u64,
+}
+
+/// Emitted for every authorization issued, singly or in a batch, so
+/// facilitators can track authorizations without parsing `msg!` logs.
+#[event]
+pub struct AuthorizationCreated {
+    /// The Authorization PDA
+    pub authorization: Pubkey,
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub nonce: u64,
+    pub policy_id: u16,
+    pub amount: u64,
+    pub quantity: u16,
+    pub category: u8,
+    pub expires_at_slot: u64,
+    /// One of the `authorization_kinds` constants
+    pub kind: u8,
+    /// Streaming only (0 otherwise)
+    pub rate_per_slot: u64,
+    /// Whether a ZK proof was verified (false for authorize_payment_simple)
+    pub zk_verified: bool,
+    /// Whether a policy oracle attestation stood in for the proof
+    pub attested: bool,
+    /// One of the `verification_methods` constants
+    pub verification_method: u8,
+    /// What checked the payment (see `Authorization::verifier_ref`)
+    pub verifier_ref: Pubkey,
+    /// Caller-supplied request id (all zero = none)
+    pub request_id: [u8; 32],
+    /// One of the `expiry_kinds` constants
+    pub expiry_kind: u8,
+    /// Wall-clock expiry (0 for slot expiry)
+    pub expires_at_unix: i64,
+    /// The only key that may record the payment (default = anyone)
+    pub consumer: Pubkey,
+    /// Escrow token account funded for it (default = event-only settlement)
+    pub escrow: Pubkey,
+    /// SHA-256 the merchant must reveal the preimage of (all zero = none)
+    pub hash_lock: [u8; 32],
+    pub slot: u64,
+}
+
+/// Emitted when the agent or policy owner cancels an authorization.
+#[event]
+pub struct AuthorizationRevoked {
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub nonce: u64,
+    pub calls_remaining: u16,
+    pub revoked_by: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when a used, revoked or expired authorization is closed and its rent
+/// returned. `reason` is one of the `close_reasons` constants.
+#[event]
+pub struct AuthorizationClosed {
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub nonce: u64,
+    pub reason: u8,
+    pub slot: u64,
+}
+
+/// Emitted when a meter switches between event-only and escrow settlement.
+#[event]
+pub struct MeterSettlementModeChanged {
+    pub meter: Pubkey,
+    /// One of the `settlement_modes` constants
+    pub settlement_mode: u8,
+    pub slot: u64,
+}
+
+/// Emitted when a meter starts or stops refusing attested payments.
+#[event]
+pub struct MeterRejectAttestedChanged {
+    pub meter: Pubkey,
+    pub reject_attested: bool,
+    pub slot: u64,
+}
+
+/// Emitted when a meter's circuit is set or cleared.
+#[event]
+pub struct MeterCircuitChanged {
+    pub meter: Pubkey,
+    /// 0 = none (see `circuit_ids`)
+    pub old_circuit_id: u8,
+    pub new_circuit_id: u8,
+    pub slot: u64,
+}
+
+/// Emitted when an agent's token vault is created.
+#[event]
+pub struct AgentVaultOpened {
+    pub agent: Pubkey,
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when an agent withdraws from its vault.
+#[event]
+pub struct VaultWithdrawn {
+    pub agent: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub slot: u64,
+}
+
+/// Emitted when an expired or revoked authorization's escrow is returned
+/// to the agent's vault.
+#[event]
+pub struct EscrowRefunded {
+    pub authorization: Pubkey,
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub nonce: u64,
+    /// Amount returned to the vault
+    pub amount: u64,
+    pub slot: u64,
+}
+
+/// Emitted when the settlement authority confirms an authorization's
+/// off-chain transfer.
+#[event]
+pub struct SettlementConfirmed {
+    pub authorization: Pubkey,
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub nonce: u64,
+    pub amount: u64,
+    pub settlement_ref: [u8; 32],
+    /// Settled on the retry after a reported failure
+    pub retried: bool,
+    pub slot: u64,
+}
+
+/// Emitted when the settlement authority reports a failed transfer and the
+/// authorization is re-opened for a retry.
+#[event]
+pub struct SettlementFailed {
+    pub authorization: Pubkey,
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub nonce: u64,
+    /// Settlement service's failure code
+    pub reason_code: u8,
+    /// Last slot the retry may be recorded in
+    pub retry_expires_at_slot: u64,
+    pub slot: u64,
+}
+
+/// Emitted when an agent or meter authority disputes a recorded payment.
+#[event]
+pub struct DisputeOpened {
+    pub dispute: Pubkey,
+    pub authorization: Pubkey,
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub nonce: u64,
+    pub opened_by: Pubkey,
+    pub reason_code: u8,
+    pub slot: u64,
+}
+
+/// Emitted when the config admin rules on a dispute.
+#[event]
+pub struct DisputeResolved {
+    pub dispute: Pubkey,
+    pub authorization: Pubkey,
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub nonce: u64,
+    /// One of the `dispute_outcomes` constants
+    pub outcome: u8,
+    pub resolved_by: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when a merchant refunds a payment; the Circle service transfers
+/// `amount` from `merchant_wallet_id` back to the agent.
+#[event]
+pub struct RefundIssued {
+    pub authorization: Pubkey,
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub nonce: u64,
+    /// Amount refunded by this call
+    pub amount: u64,
+    /// Total refunded on the authorization, including this call
+    pub refunded_amount: u64,
+    pub mint: Pubkey,
+    /// The meter's Circle wallet the refund is paid from
+    pub merchant_wallet_id: String,
+    pub slot: u64,
+}
+
+/// Emitted when a bundle authorization is created.
+#[event]
+pub struct BundleAuthorizationCreated {
+    pub bundle_authorization: Pubkey,
+    pub agent: Pubkey,
+    pub nonce: u64,
+    pub policy_id: u16,
+    pub meters: Vec<Pubkey>,
+    pub amounts: Vec<u64>,
+    pub expires_at_slot: u64,
+    pub slot: u64,
+}
+
+/// Emitted when an agent opens a payment channel.
+#[event]
+pub struct ChannelOpened {
+    pub channel: Pubkey,
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub policy_id: u16,
+    pub deposit_cap: u64,
+    pub expires_at_slot: u64,
+    pub slot: u64,
+}
+
+/// Emitted when a payment channel is closed.
+#[event]
+pub struct ChannelClosed {
+    pub channel: Pubkey,
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    /// Total settled over the channel's lifetime
+    pub claimed: u64,
+    pub deposit_cap: u64,
+    pub slot: u64,
+}
+
+/// Emitted when a budget authorization is created.
+#[event]
+pub struct BudgetAuthorizationCreated {
+    pub budget_authorization: Pubkey,
+    pub agent: Pubkey,
+    pub meter: Pubkey,
+    pub nonce: u64,
+    pub policy_id: u16,
+    pub total_amount: u64,
+    pub category: u8,
+    pub expires_at_slot: u64,
+    pub slot: u64,
+}
+
+/// Emitted once per `sweep_expired_authorizations` call.
+#[event]
+pub struct AuthorizationsSwept {
+    /// Authorizations closed (skipped entries aren't counted)
+    pub count: u16,
+    pub slot: u64,
+}
+
+// =============================================================================
+// ERRORS
+// =============================================================================
+
+/// Custom errors for AgentBlinkPay program.
+#[error_code]
+pub enum AgentBlinkPayError {
+    /// Agent's policy is frozen - no payments allowed
+    #[msg("Agent policy is frozen")]
+    PolicyFrozen,
     
-    /// Hash commitment to the full policy (ZK public input)
-    /// Computed as: hash(max_per_tx || allowed_category || other_fields)
-    pub policy_hash: [u8; 32],
+    /// Payment amount exceeds the policy's max_per_tx limit
+    #[msg("Amount exceeds maximum allowed per transaction")]
+    AmountExceedsMax,
     
-    /// Category of spending allowed (e.g., 1 = AI_API, 2 = CATAN_ACTION)
-    pub allowed_category: u8,
+    /// Authorization has already been used (replay attempt)
+    #[msg("Authorization has already been used")]
+    AuthorizationUsed,
+    
+    /// Authorization has expired (current_slot > expires_at_slot)
+    #[msg("Authorization has expired")]
+    AuthorizationExpired,
+    
+    /// Payment category doesn't match meter category
+    #[msg("Category mismatch between payment and meter")]
+    CategoryMismatch,
+    
+    /// ZK proof verification failed
+    #[msg("Invalid ZK proof")]
+    InvalidProof,
+
+    /// Proof that can't be decoded: a short header, a payload of the wrong
+    /// length, a point off the curve or a public input outside the field.
+    /// Unlike InvalidProof, resubmitting the same bytes can never succeed
+    #[msg("Malformed ZK proof")]
+    MalformedProof,
+
+    /// Proof argument shorter than MIN_PROOF_LEN
+    #[msg("Proof is shorter than MIN_PROOF_LEN")]
+    ProofTooSmall,
+
+    /// Proof argument longer than MAX_PROOF_LEN
+    #[msg("Proof is longer than MAX_PROOF_LEN")]
+    ProofTooLarge,
+    
+    /// Merchant wallet ID is too long (max 64 bytes)
+    #[msg("Merchant wallet ID too long (max 64 bytes)")]
+    MerchantWalletIdTooLong,
+
+    #[msg("Invalid Public Inputs for Verifier")]
+    InvalidInputs,
+
+    /// Payment would push lifetime_spent past total_budget
+    #[msg("Payment exceeds the remaining lifetime budget")]
+    BudgetExceeded,
+
+    /// extend_budget called on a policy without a budget
+    #[msg("Policy budget is unlimited; set one with set_policy first")]
+    BudgetUnlimited,
+
+    /// Checked arithmetic overflowed
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+
+    /// Agent's policy is temporarily paused (current_slot <= paused_until_slot)
+    #[msg("Agent policy is paused")]
+    PolicyPaused,
+
+    /// Signer is neither the expected owner nor a delegated key
+    #[msg("Signer is not authorized for this action")]
+    Unauthorized,
+
+    /// A policy was created without the agent's signature
+    #[msg("Agent must sign to create its policy")]
+    AgentSignatureMissing,
+
+    /// A policy was created for an agent key retired by rotate_agent_key
+    #[msg("Agent key was rotated away and can't get new policies")]
+    AgentKeyRetired,
+
+    /// Batch instruction called with no entries
+    #[msg("Batch must contain at least one entry")]
+    EmptyBatch,
+
+    /// Batch instruction called with too many entries
+    #[msg("Batch exceeds the maximum size")]
+    BatchTooLarge,
+
+    /// remaining_accounts doesn't line up with the batch entries
+    #[msg("Number of remaining accounts does not match the batch entries")]
+    BatchAccountsMismatch,
+
+    /// Passed policy account isn't the expected PDA or isn't writable
+    #[msg("Policy account does not match the expected PDA")]
+    InvalidPolicyAccount,
+
+    /// Policy is in allowlist mode and the meter has no AllowedMeter record
+    #[msg("Meter is not on the agent's allowlist")]
+    MeterNotAllowed,
+
+    /// Agent has a DeniedMeter record for this meter
+    #[msg("Meter is on the agent's denylist")]
+    MeterDenied,
+
+    /// accept_policy_ownership signed by a key that isn't the pending owner
+    #[msg("Signer is not the pending owner")]
+    NotPendingOwner,
+
+    /// Policy has always_require_zk set or an allowlist root, and the payment
+    /// path skips proof verification
+    #[msg("Agent policy requires a ZK proof for every payment")]
+    ZkRequiredByPolicy,
+
+    /// Payment would push spent_today past daily_limit
+    #[msg("Payment exceeds the remaining daily limit")]
+    DailyLimitExceeded,
+
+    /// alert_threshold_bps above 100%
+    #[msg("Alert threshold must be at most 10000 bps")]
+    InvalidAlertThreshold,
+
+    /// Owner account passed to an owner instruction did not sign
+    #[msg("Policy owner must sign (directly or via invoke_signed)")]
+    OwnerSignatureMissing,
+
+    /// A program-derived address signed as the agent of a keypair policy
+    #[msg("Agent must be an ed25519 keypair for this policy")]
+    AgentMustBeKeypair,
+
+    /// No longer returned (superseded by InvalidExpiry); kept so later
+    /// error codes don't shift
+    #[msg("Authorization lifetime exceeds the maximum TTL")]
+    AuthorizationTtlTooLong,
+
+    /// close_meter or update_merchant_wallet called on a meter that isn't paused
+    #[msg("Meter must be paused first")]
+    MeterNotPaused,
+
+    /// close_meter called before the pause outlived every possible authorization
+    #[msg("Meter has not been paused for the maximum authorization TTL yet")]
+    MeterPausedTooRecently,
+
+    /// Meter is paused and doesn't accept new authorizations
+    #[msg("Meter is paused")]
+    MeterInactive,
+
+    /// Set pricing tiers are not in strictly increasing min_calls order
+    #[msg("Pricing tiers must have increasing min_calls")]
+    InvalidPriceTiers,
+
+    /// set_meter_tiers with a tier priced above the meter's effective price
+    #[msg("Tier price exceeds the meter's price per call")]
+    TierAboveMeterPrice,
+
+    /// Meter has pricing tiers and the amount isn't the agent's tier price
+    #[msg("Amount does not match the meter's tier price for this agent")]
+    AmountNotTierPrice,
+
+    /// Subscription meter created with a zero-length period
+    #[msg("Subscription period must be at least one slot")]
+    InvalidSubscriptionTerms,
+
+    /// Per-call instruction used on a subscription meter, or vice versa
+    #[msg("Instruction does not match the meter's kind")]
+    WrongMeterKind,
+
+    /// Meter has requires_zk set and the payment path skips proof verification
+    #[msg("Meter requires a ZK proof for every payment")]
+    ZkRequiredByMeter,
+
+    /// authorize_payment_attested while the Config has no policy_oracle
+    #[msg("Payment attestations are disabled")]
+    AttestationsDisabled,
+
+    /// authorize_payment_attested on a meter with reject_attested set
+    #[msg("Meter does not accept attested payments")]
+    AttestationRejectedByMeter,
+
+    /// authorize_payment_attested without a matching Ed25519 attestation
+    /// check by the policy oracle before it
+    #[msg("Policy oracle attestation missing or does not match")]
+    InvalidAttestation,
+
+    /// record_subscription_call after the subscription ran out
+    #[msg("Subscription has expired")]
+    SubscriptionExpired,
+
+    /// Zero-amount authorization without free calls left on the meter
+    #[msg("No free calls left for this agent on this meter")]
+    FreeCallsExhausted,
+
+    /// Meter name empty or longer than 32 bytes
+    #[msg("Meter name must be 1-32 bytes")]
+    InvalidMeterName,
+
+    /// accept_meter_authority signed by a key that isn't the pending authority
+    #[msg("Signer is not the pending meter authority")]
+    NotPendingAuthority,
+
+    /// Meter has its allowlist enabled and no MeterAgentAccess was supplied
+    #[msg("Agent is not on this meter's allowlist")]
+    AgentNotAllowedByMeter,
+
+    /// max_calls_per_window set with a zero-length window
+    #[msg("Rate limit window must be at least one slot")]
+    InvalidRateLimit,
+
+    /// Meter already recorded max_calls_per_window calls in this window
+    #[msg("Meter rate limit reached, retry in a later slot window")]
+    MeterRateLimited,
+
+    /// create_meter_v2 endpoint_id empty, longer than 32 bytes or not ASCII
+    #[msg("Endpoint id must be 1-32 ASCII bytes")]
+    InvalidEndpointId,
+
+    /// price_per_call below the Config's min_price_per_call or above its max_price_per_call
+    #[msg("Price per call is outside the configured bounds")]
+    PriceOutOfBounds,
+
+    /// Revenue split wallet id too long, or split shares above 10000 bps
+    #[msg("Revenue split shares may total at most 10000 bps")]
+    InvalidRevenueSplits,
+
+    /// Authority's MeterIndex already lists MAX_METERS_PER_AUTHORITY meters
+    #[msg("Authority has reached the maximum number of meters")]
+    MeterIndexFull,
+
+    /// record_meter_payment signed by someone other than the authorization's consumer
+    #[msg("Only the authorization's consumer may record this payment")]
+    UnauthorizedConsumer,
+
+    /// expiry_kind is not one of the `expiry_kinds` constants
+    #[msg("Unknown expiry kind")]
+    InvalidExpiryKind,
+
+    /// Streaming authorization with a rate_per_slot of 0 or above its amount
+    #[msg("Rate per slot must be between 1 and the authorized amount")]
+    InvalidStreamRate,
+
+    /// Streaming authorization consumed beyond what has unlocked so far
+    #[msg("Amount exceeds what the stream has unlocked so far")]
+    AmountNotYetUnlocked,
+
+    /// set_meter_settlement_mode with a value outside `settlement_modes`
+    #[msg("Unknown settlement mode")]
+    InvalidSettlementMode,
+
+    /// Escrow-mode payment without the vault, escrow, mint or token program accounts
+    #[msg("Escrow settlement requires the vault, escrow and token accounts")]
+    EscrowAccountsMissing,
+
+    /// Escrow account isn't the authorization's, or the payee isn't the meter authority's
+    #[msg("Escrow or merchant token account does not match")]
+    InvalidEscrowAccount,
+
+    /// Streaming, batch, auto-nonce or credit payment to an escrow-mode meter
+    #[msg("This payment path does not support escrow-mode meters")]
+    EscrowNotSupported,
+
+    /// Authorize or withdraw above the vault's balance
+    #[msg("Agent vault balance is too low")]
+    VaultInsufficientFunds,
+
+    /// close_authorization before cancel_escrowed_authorization returned its escrow
+    #[msg("Authorization still holds escrowed funds")]
+    EscrowStillFunded,
+
+    /// cancel_escrowed_authorization on an authorization without open escrow
+    #[msg("Authorization has no escrow to return")]
+    NotEscrowed,
+
+    /// Config grace_slots above MAX_GRACE_SLOTS
+    #[msg("Grace window exceeds MAX_GRACE_SLOTS")]
+    GraceSlotsTooLarge,
+
+    /// Settlement reported for an authorization that isn't fully consumed
+    #[msg("Authorization has not been fully recorded yet")]
+    AuthorizationNotUsed,
+
+    /// confirm_settlement or report_settlement_failure after a confirmation
+    #[msg("Settlement has already been confirmed")]
+    SettlementAlreadyConfirmed,
+
+    /// report_settlement_failure on an authorization that already had its retry
+    #[msg("Settlement retry has already been used")]
+    SettlementRetryUsed,
+
+    /// report_settlement_failure on an authorization paid out from escrow
+    #[msg("Authorization was settled on-chain from escrow")]
+    SettledOnChain,
+
+    /// open_dispute on an authorization with no recorded payment
+    #[msg("Authorization has no recorded payment to dispute")]
+    NothingRecorded,
+
+    /// open_dispute later than dispute_window_slots after the last record
+    #[msg("Dispute window has closed")]
+    DisputeWindowClosed,
+
+    /// resolve_dispute with a value outside `dispute_outcomes`
+    #[msg("Unknown dispute outcome")]
+    InvalidDisputeOutcome,
+
+    /// issue_refund while a dispute is open on the payment
+    #[msg("Payment is disputed")]
+    PaymentDisputed,
+
+    /// issue_refund later than refund_window_slots after the last record
+    #[msg("Refund window has closed")]
+    RefundWindowClosed,
+
+    /// issue_refund of 0, or taking total refunds above the authorization's amount
+    #[msg("Refunds would exceed the authorization's amount")]
+    RefundExceedsAmount,
+
+    /// record_meter_payment after the policy's policy_hash changed
+    #[msg("Policy has changed since the payment was authorized")]
+    PolicyChangedSinceAuthorization,
+
+    /// Refund account passed to a close doesn't match the recorded rent payer
+    #[msg("Refund account does not match the account's rent payer")]
+    RentPayerMismatch,
+
+    /// An authorization for this agent, meter and nonce already exists
+    #[msg("Nonce already used for this agent and meter; pick a new nonce")]
+    NonceAlreadyUsed,
+
+    /// record_meter_payment preimage doesn't hash to the authorization's hash_lock
+    #[msg("Preimage does not match the authorization's hash lock")]
+    InvalidPreimage,
+
+    /// record_meter_payment preimage longer than MAX_PREIMAGE_LEN
+    #[msg("Preimage exceeds MAX_PREIMAGE_LEN bytes")]
+    PreimageTooLong,
+
+    /// record_metered_usage with 0 units
+    #[msg("Units must be at least 1")]
+    InvalidUnits,
+
+    /// record_metered_usage charge above the authorization's cap
+    #[msg("Metered usage exceeds the authorized amount")]
+    UsageExceedsAuthorization,
+
+    /// record_metered_usage on a multi-call or tipped authorization
+    #[msg("Only single-call standard authorizations can record metered usage")]
+    NotMeteredUsageAuthorization,
+
+    /// record_meter_payment with create_receipt but no receipt, payer or system program
+    #[msg("Receipt, payer and system program accounts are required for a receipt")]
+    ReceiptAccountsMissing,
+
+    /// Receipt account isn't the ["receipt", meter, agent, nonce] PDA
+    #[msg("Invalid receipt account")]
+    InvalidReceiptAccount,
+
+    /// Bundle meter that is repeated, doesn't match its denylist address,
+    /// or isn't in the bundle at the given index
+    #[msg("Invalid or duplicate bundle meter")]
+    InvalidBundleMeter,
+
+    /// settle_channel without a matching Ed25519 voucher check before it
+    #[msg("Channel voucher signature missing or does not match")]
+    InvalidChannelVoucher,
+
+    /// settle_channel voucher not above what the channel already claimed
+    #[msg("Channel voucher is not newer than the last settled one")]
+    StaleChannelVoucher,
+
+    /// settle_channel voucher above the channel's deposit_cap
+    #[msg("Channel voucher exceeds the deposit cap")]
+    ChannelCapExceeded,
+
+    /// Authorization issued by a flow the instruction doesn't handle
+    #[msg("Authorization kind is not valid for this instruction")]
+    WrongAuthorizationKind,
+
+    /// set_verifying_key on a key that was locked
+    #[msg("Verifying key is locked")]
+    VerifyingKeyLocked,
+
+    /// Setting a circuit's key of one scheme while it has one of the other
+    #[msg("Circuit already has a verifying key of another scheme")]
+    CircuitSchemeConflict,
+
+    /// remove_verifying_key while meters still use the circuit
+    #[msg("Verifying key is still used by meters")]
+    VerifyingKeyInUse,
+
+    /// set_verifying_key without an ic point or with more than MAX_VK_PUBLIC_INPUTS inputs
+    #[msg("Verifying key has an invalid number of public inputs")]
+    InvalidVerifyingKey,
+
+    /// authorize_payment_with_proof without the circuit's verifying key account
+    #[msg("Verifying key account is required to verify a proof")]
+    VerifyingKeyMissing,
+
+    /// Verifier program other than the one the Config selects
+    #[msg("Verifier program does not match the Config")]
+    VerifierProgramMismatch,
+
+    /// Config enabling external_verifier without a verifier_program
+    #[msg("External verifier enabled without a verifier program")]
+    VerifierProgramMissing,
+
+    /// Config enforcement_mode that isn't one of `enforcement_modes`
+    #[msg("Unknown enforcement mode")]
+    InvalidEnforcementMode,
+
+    /// update_config relaxing a STRICT enforcement_mode
+    #[msg("Strict enforcement can't be turned off")]
+    EnforcementModeLocked,
+
+    /// Proof header version outside SUPPORTED_PROOF_VERSIONS
+    #[msg("Proof version is not supported")]
+    UnsupportedProofVersion,
+
+    /// Proof header scheme that isn't one of `proof_schemes`
+    #[msg("Proof scheme is not supported")]
+    UnsupportedProofScheme,
+
+    /// Verifying key of another circuit than the proof's
+    #[msg("Verifying key is not for the proof's circuit")]
+    WrongVerifyingKey,
+
+    /// Proof of another scheme than its circuit's registered key
+    #[msg("Proof scheme does not match the circuit's verifying key")]
+    ProofSchemeMismatch,
+
+    /// Proof for another circuit than the meter's circuit_id
+    #[msg("Proof is not for the meter's circuit")]
+    ProofCircuitMismatch,
+
+    /// set_meter_circuit with a circuit that isn't a payment circuit
+    #[msg("Meters can only use payment circuits")]
+    InvalidCircuit,
+
+    /// A proof-taking authorize instruction whose payment's Nullifier exists
+    #[msg("Proof was already used for this payment")]
+    ProofAlreadyUsed,
+
+    /// Nullifier account missing or not the payment's nullifier PDA
+    #[msg("Nullifier account does not match the payment")]
+    InvalidNullifierAccount,
+
+    /// set_policy with a salt that doesn't reproduce the given policy_hash
+    #[msg("Policy hash does not match the policy and salt")]
+    PolicyHashMismatch,
+
+    /// set_policy with an allowlist root that isn't a field element (first byte set)
+    #[msg("Allowlist root must be a field element")]
+    InvalidAllowlistRoot,
+
+    /// Meter allowlist proof for a policy without an allowlist root, or
+    /// another payment proof for one with a root
+    #[msg("Proof circuit does not match the policy's meter allowlist")]
+    AllowlistCircuitMismatch,
+
+    /// verify_proof_stage out of order, past the last stage, or stage 0 again
+    #[msg("Proof verification stage is out of order")]
+    InvalidVerificationStage,
+
+    /// verify_proof_stage while the Config selects an external verifier
+    #[msg("Staged verification needs the built-in verifier")]
+    StagedVerificationUnsupported,
+
+    /// A stage or finalize_authorization past the pending verification's expiry
+    #[msg("Pending verification has expired")]
+    PendingVerificationExpired,
+
+    /// close_pending_verification before the pending verification expired
+    #[msg("Pending verification has not expired yet")]
+    PendingVerificationLive,
+
+    /// Policy, amount or category differ from those bound at stage 0
+    #[msg("Parameters do not match the pending verification")]
+    PendingVerificationMismatch,
+
+    /// finalize_authorization without its pending verification account
+    #[msg("Pending verification account is required")]
+    PendingVerificationMissing,
+
+    /// finalize_authorization before every stage has run
+    #[msg("Proof has not completed verification")]
+    ProofNotVerified,
+
+    /// The policy's spent_today changed since a daily limit proof's stage 1
+    #[msg("Public inputs changed since the proof was verified")]
+    PendingInputsChanged,
+
+    /// Proof receipt of another agent or policy, whose public inputs hash
+    /// differs from the payment's, or passed along with a proof
+    #[msg("Proof receipt does not match the payment")]
+    ProofReceiptMismatch,
+
+    /// Proof receipt consumed after its expires_at_slot
+    #[msg("Proof receipt has expired")]
+    ProofReceiptExpired,
+
+    /// close_proof_receipt before the receipt expired
+    #[msg("Proof receipt has not expired yet")]
+    ProofReceiptLive,
+
+    /// The circuit's verifying key was replaced or upgraded since the receipt's proof was verified
+    #[msg("Proof receipt was verified against an outdated key")]
+    ProofReceiptStale,
+
+    /// Tipped authorization that isn't one call at the meter's price plus the tip
+    #[msg("Amount must be the meter's price plus the tip, for a single call")]
+    AmountNotPricePlusTip,
+
+    /// record_meter_payment amount of 0 or above the authorization's amount_remaining
+    #[msg("Amount must be between 1 and the authorization's remaining amount")]
+    InvalidConsumeAmount,
+
+    /// authorize_payments_batch called with a different number of proofs than payments
+    #[msg("Number of proofs does not match the batch entries")]
+    BatchProofsMismatch,
+
+    /// Aggregated proof whose batch_count isn't the number of batch entries
+    #[msg("Aggregated proof's batch_count does not match the batch entries")]
+    BatchCountMismatch,
+
+    /// Passed authorization account isn't the expected PDA, isn't writable or already exists
+    #[msg("Authorization account does not match the expected PDA")]
+    InvalidAuthorizationAccount,
+
+    /// Authorization was cancelled with revoke_authorization
+    #[msg("Authorization has been revoked")]
+    AuthorizationRevoked,
+
+    /// close_authorization on an authorization that is unused, unrevoked and unexpired
+    #[msg("Authorization is still live and can't be closed")]
+    AuthorizationStillLive,
+
+    /// expires_at_slot not after the current slot, or further out than
+    /// MAX_AUTHORIZATION_TTL_SLOTS
+    #[msg("Authorization expiry must be within the next MAX_AUTHORIZATION_TTL_SLOTS slots")]
+    InvalidExpiry,
+
+    /// Paid authorization below the meter's per-call price
+    #[msg("Amount is below the meter's price per call")]
+    AmountBelowMeterPrice,
+
+    /// Authorization for zero calls
+    #[msg("Quantity must be at least 1")]
+    InvalidQuantity,
+
+    /// Multi-call authorization whose amount isn't quantity x price_per_call
+    #[msg("Amount must be quantity times the meter's price per call")]
+    AmountNotQuantityPrice,
+
+    /// Meter settles in a mint other than the policy's restrict_mint
+    #[msg("Meter's mint is not allowed by the policy")]
+    MintMismatch,
+
+    /// Category is not one of the `categories` constants
+    #[msg("Unknown category")]
+    InvalidCategory,
+
+    /// Meter operator signed an authority-only meter instruction
+    #[msg("Meter operators can't perform this action")]
+    UnauthorizedOperator,
+
+    /// Proof generated for a circuit older than the meter's min_proof_version
+    #[msg("Proof version is older than the meter accepts")]
+    ProofVersionTooOld,
+
+    /// Referrer share above MAX_REFERRER_BPS, or a referrer wallet id that
+    /// is invalid or set without a share
+    #[msg("Invalid referrer: share must be at most 1000 bps with a valid wallet id")]
+    InvalidReferral,
+
+    /// consume_credit for more than the remaining credit, or refund with none left
+    #[msg("Insufficient prepaid credit")]
+    InsufficientCredits,
+
+    /// Wallet id empty or containing whitespace, control or non-ASCII characters
+    #[msg("Merchant wallet id must be non-empty printable ASCII without whitespace")]
+    InvalidMerchantWalletId,
+
+    /// Authorization above both the meter's price and its max_amount_per_payment
+    #[msg("Amount exceeds the meter's per-payment cap")]
+    AmountExceedsMeterCap,
+
+    /// Account discriminator doesn't match the expected account type
+    #[msg("Account data does not match the expected account type")]
+    InvalidAccountData,
+}
+
+// =============================================================================
+// CATEGORY CONSTANTS (for reference)
+// =============================================================================
+
+/// Category constants for spending classification.
+/// These are stored as u8 in accounts for space efficiency.
+pub mod categories {
+    use anchor_lang::prelude::*;
+
+    /// AI/ML inference APIs (e.g., OpenAI, Anthropic)
+    #[constant]
+    pub const AI_API: u8 = 1;
     
-    /// Maximum allowed spend per transaction (USDC smallest units)
-    /// e.g., 500000 = 0.5 USDC
-    pub max_per_tx: u64,
+    /// Data feeds and market data
+    #[constant]
+    pub const DATA_FEED: u8 = 2;
     
-    /// If true, agent cannot authorize any payments
-    pub frozen: bool,
+    /// General tools and utilities
+    #[constant]
+    pub const TOOL: u8 = 3;
     
-    /// PDA bump seed
-    pub bump: u8,
+    /// Game actions (e.g., Catan demo)
+    #[constant]
+    pub const CATAN_ACTION: u8 = 4;
 }
 
-impl AgentPolicy {
-    pub const LEN: usize = 8 +  // discriminator
-        32 +                    // agent_pubkey
-        32 +                    // policy_hash
-        1 +                     // allowed_category
-        8 +                     // max_per_tx
-        1 +                     // frozen
-        1;                      // bump
+/// The known spending categories. Accounts and instructions carry them
+/// as u8; convert with `Category::try_from` to validate, so adding a
+/// category only takes a new constant and variant here.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Category {
+    AiApi = categories::AI_API,
+    DataFeed = categories::DATA_FEED,
+    Tool = categories::TOOL,
+    CatanAction = categories::CATAN_ACTION,
 }
 
-/// Meter account for a paywalled API endpoint.
-/// 
-/// PDA seeds: ["meter", authority, meter_id]
-/// 
-/// Created when an API provider registers their endpoint through the
-/// "Register API" flow in the dashboard.
-#[account]
-#[derive(Default)]
-pub struct Meter {
-    /// Authority that can update this meter
-    pub authority: Pubkey,
-    
-    /// Price per call in USDC smallest units (e.g., 50000 = $0.05)
-    pub price_per_call: u64,
-    
-    /// Category enum (must match agent's allowed_category)
-    pub category: u8,
-    
-    /// Merchant's Circle wallet ID (for off-chain USDC transfers)
-    pub merchant_wallet_id: [u8; 64],
-    
-    /// Actual length of merchant_wallet_id
-    pub merchant_wallet_id_len: u8,
-    
-    /// Whether this meter requires ZK-checked policies
-    pub requires_zk: bool,
-    
-    /// PDA bump seed
-    pub bump: u8,
+impl TryFrom<u8> for Category {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            categories::AI_API => Ok(Self::AiApi),
+            categories::DATA_FEED => Ok(Self::DataFeed),
+            categories::TOOL => Ok(Self::Tool),
+            categories::CATAN_ACTION => Ok(Self::CatanAction),
+            _ => err!(AgentBlinkPayError::InvalidCategory),
+        }
+    }
 }
 
-impl Meter {
-    pub const LEN: usize = 8 +  // discriminator
-        32 +                    // authority
-        8 +                     // price_per_call
-        1 +                     // category
-        64 +                    // merchant_wallet_id
-        1 +                     // merchant_wallet_id_len
-        1 +                     // requires_zk
-        1;                      // bump
+impl Category {
+    /// True if the stored categories `a` and `b` are the same known category.
+    pub fn same(a: u8, b: u8) -> Result<bool> {
+        Ok(Self::try_from(a)? == Self::try_from(b)?)
+    }
 }
 
-/// Authorization (payment ticket) account.
-/// 
-/// PDA seeds: ["auth", agent_pubkey, meter_pubkey, nonce]
-/// 
-/// Created by authorize_payment_with_proof after ZK verification.
-/// Consumed by record_meter_payment to emit the payment event.
-/// One-time use, expires after expires_at_slot.
-#[account]
-#[derive(Default)]
-pub struct Authorization {
-    /// The agent making the payment
-    pub agent: Pubkey,
-    
-    /// The meter being paid
-    pub meter: Pubkey,
-    
-    /// Amount approved (USDC smallest units)
-    pub amount: u64,
-    
-    /// Category of this payment
-    pub category: u8,
-    
-    /// Unique nonce to prevent replay attacks
-    pub nonce: u64,
-    
-    /// Slot after which this authorization is invalid
-    pub expires_at_slot: u64,
-    
-    /// Whether this authorization has been consumed
-    pub used: bool,
-    
-    /// PDA bump seed
-    pub bump: u8,
+// =============================================================================
+// FREEZE REASONS
+// =============================================================================
+
+/// Why an AgentPolicy is frozen, stored as `AgentPolicy::frozen_reason`.
+/// Lets automatic unfreezes avoid clearing a freeze the owner set by hand.
+pub mod freeze_reasons {
+    /// Not frozen
+    pub const NONE: u8 = 0;
+
+    /// Frozen explicitly via set_policy
+    pub const MANUAL: u8 = 1;
+
+    /// Frozen automatically when lifetime_spent reached total_budget
+    pub const BUDGET_EXHAUSTED: u8 = 2;
+
+    /// Frozen automatically after too many reported authorization failures
+    pub const CIRCUIT_BREAKER: u8 = 3;
 }
 
-impl Authorization {
-    pub const LEN: usize = 8 +  // discriminator
-        32 +                    // agent
-        32 +                    // meter
-        8 +                     // amount
-        1 +                     // category
-        8 +                     // nonce
-        8 +                     // expires_at_slot
-        1 +                     // used
-        1;                      // bump
+// =============================================================================
+// LIMIT TYPES
+// =============================================================================
+
+/// Which spend limit a `SpendThresholdCrossed` event refers to.
+pub mod limit_types {
+    /// `daily_limit`, checked against `spent_today`
+    pub const DAILY: u8 = 0;
+
+    /// `total_budget`, checked against `lifetime_spent`
+    pub const LIFETIME: u8 = 1;
+}
+
+// =============================================================================
+// AUTHORIZATION KINDS
+// =============================================================================
+
+/// Values of `Authorization::kind`.
+pub mod authorization_kinds {
+    /// Fixed amount, spendable at once (authorize_payment_with_proof)
+    pub const STANDARD: u8 = 0;
+
+    /// Amount unlocked per slot (authorize_streaming_payment)
+    pub const STREAMING: u8 = 1;
+}
+
+// =============================================================================
+// VERIFICATION METHODS
+// =============================================================================
+
+/// How an authorization's payment was checked, recorded in its
+/// `verification_method`; its `verifier_ref` names what checked it.
+pub mod verification_methods {
+    /// No proof or attestation (authorize_payment_simple); no verifier_ref
+    pub const NONE: u8 = 0;
+
+    /// A proof checked by this program's Groth16 verifier, in one
+    /// instruction or in stages; verifier_ref is the VerifyingKey
+    pub const EMBEDDED: u8 = 1;
+
+    /// A proof checked by CPI to the Config's external verifier or a Honk
+    /// verifier; verifier_ref is that program
+    pub const CPI: u8 = 2;
+
+    /// A policy oracle attestation (authorize_payment_attested);
+    /// verifier_ref is the oracle's key
+    pub const ATTESTATION: u8 = 3;
+
+    /// A proof that failed its checks, let through by permissive
+    /// enforcement mode; verifier_ref is what it failed against
+    pub const PERMISSIVE: u8 = 4;
+}
+
+// =============================================================================
+// CIRCUIT IDS
+// =============================================================================
+
+/// Circuits `verify_proof` knows a verifying key for.
+pub mod circuit_ids {
+    /// zk/payment_policy: public inputs (amount, category, policy_hash,
+    /// meter, nonce)
+    pub const PAYMENT_POLICY: u8 = 1;
+
+    /// Budget circuit: the payment policy inputs plus the remaining budget
+    pub const BUDGET: u8 = 2;
+
+    /// zk/daily_limit: the payment policy inputs plus the policy's
+    /// spent_today, proving the day's spend stays under a private limit.
+    /// Proven by version 2 payment proofs.
+    pub const DAILY_LIMIT: u8 = 3;
+
+    /// zk/meter_allowlist: the payment policy inputs plus the policy's
+    /// allowlist_root, proving the meter is on a private allowlist. Proven
+    /// by version 3 payment proofs.
+    pub const METER_ALLOWLIST: u8 = 4;
+}
+
+// =============================================================================
+// PROOF SCHEMES
+// =============================================================================
+
+/// Proof systems, as named by the second byte of a proof's header.
+pub mod proof_schemes {
+    /// Groth16 over BN254; payload is A, B and C (GROTH16_PROOF_LEN bytes)
+    pub const GROTH16: u8 = 1;
+
+    /// UltraHonk; payload is the proof as the circuit's Honk verifier
+    /// program takes it (see `HonkVerifyingKey`)
+    pub const ULTRA_HONK: u8 = 2;
+
+    /// Groth16 proofs of one circuit for the entries of an
+    /// `authorize_payments_batch`, checked together; the header adds a
+    /// batch_count and the payload is that many Groth16 payloads, in entry
+    /// order (AGGREGATED_PROOF_HEADER_LEN)
+    pub const GROTH16_BATCH: u8 = 3;
+}
+
+// =============================================================================
+// ENFORCEMENT MODES
+// =============================================================================
+
+/// What a proof that fails verification does, per `Config::enforcement_mode`.
+pub mod enforcement_modes {
+    /// Log the failure, emit `ProofCheckSkipped` and authorize anyway, with
+    /// `zk_verified` false; for rolling out new circuits
+    pub const PERMISSIVE: u8 = 0;
+    /// Reject the authorization. Can't be relaxed once set
+    pub const STRICT: u8 = 1;
+}
+
+// =============================================================================
+// PUBLIC INPUTS
+// =============================================================================
+
+/// Canonical encoding of circuit public inputs, shared with the Noir
+/// circuits in zk/ and any off-chain prover.
+/// 
+/// Each public input is a BN254 scalar field element, encoded as
+/// FIELD_ELEMENT_LEN bytes, big-endian. Integers are zero-extended on the
+/// left. A 32-byte hash can exceed the field modulus, so `policy_hash` is
+/// split into two elements holding its first and last HASH_HALF_LEN bytes,
+/// each right-aligned. The meter is bound by its SHA-256 hash with the
+/// first byte cleared, which fits one element. Golden vectors for this layout are in
+/// tests/fixtures/groth16_fixtures.json (`public_input_vectors`).
+pub mod public_inputs {
+    /// Bytes per encoded field element
+    pub const FIELD_ELEMENT_LEN: usize = 32;
+
+    /// Bytes of `policy_hash` carried by each of its two field elements
+    pub const HASH_HALF_LEN: usize = 16;
+
+    /// Position of each payment policy circuit input
+    pub const AMOUNT_INDEX: usize = 0;
+    pub const CATEGORY_INDEX: usize = 1;
+    pub const POLICY_HASH_HIGH_INDEX: usize = 2;
+    pub const POLICY_HASH_LOW_INDEX: usize = 3;
+    pub const METER_INDEX: usize = 4;
+    pub const NONCE_INDEX: usize = 5;
+
+    /// Number of payment policy circuit inputs
+    pub const PAYMENT_POLICY_INPUT_COUNT: usize = 6;
+
+    /// Position of the budget circuit's extra input, the policy's remaining
+    /// budget, after the payment policy inputs
+    pub const REMAINING_BUDGET_INDEX: usize = 6;
+
+    /// Position of the daily limit circuit's extra input, the policy's
+    /// spent_today, after the payment policy inputs
+    pub const SPENT_TODAY_INDEX: usize = 6;
+
+    /// Position of the meter allowlist circuit's extra input, the policy's
+    /// allowlist_root, after the payment policy inputs
+    pub const ALLOWLIST_ROOT_INDEX: usize = 6;
+
+    pub type FieldElement = [u8; FIELD_ELEMENT_LEN];
+
+    /// Public inputs of the payment policy circuit (zk/payment_policy).
+    /// `meter` and `nonce` tie a proof to the authorization it was made
+    /// for, so it can't be replayed against another meter or nonce.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct PaymentPolicyInputs {
+        pub amount: u64,
+        pub category: u8,
+        pub policy_hash: [u8; 32],
+        pub meter: [u8; 32],
+        pub nonce: u64,
+    }
+
+    impl PaymentPolicyInputs {
+        /// The inputs in circuit order, as the verifier takes them.
+        pub fn to_field_elements(&self) -> [FieldElement; PAYMENT_POLICY_INPUT_COUNT] {
+            let mut elements = [[0u8; FIELD_ELEMENT_LEN]; PAYMENT_POLICY_INPUT_COUNT];
+            elements[AMOUNT_INDEX] = field_element(self.amount);
+            elements[CATEGORY_INDEX] = field_element(self.category as u64);
+            elements[POLICY_HASH_HIGH_INDEX][FIELD_ELEMENT_LEN - HASH_HALF_LEN..]
+                .copy_from_slice(&self.policy_hash[..HASH_HALF_LEN]);
+            elements[POLICY_HASH_LOW_INDEX][FIELD_ELEMENT_LEN - HASH_HALF_LEN..]
+                .copy_from_slice(&self.policy_hash[HASH_HALF_LEN..]);
+            elements[METER_INDEX] = meter_field_element(&self.meter);
+            elements[NONCE_INDEX] = field_element(self.nonce);
+            elements
+        }
+    }
+
+    /// `meter` as a field element: its SHA-256 hash, first byte cleared.
+    pub fn meter_field_element(meter: &[u8; 32]) -> FieldElement {
+        let mut element = anchor_lang::solana_program::hash::hash(meter).to_bytes();
+        element[0] = 0;
+        element
+    }
+
+    /// `value` as a field element.
+    pub fn field_element(value: u64) -> FieldElement {
+        let mut element = [0u8; FIELD_ELEMENT_LEN];
+        element[FIELD_ELEMENT_LEN - 8..].copy_from_slice(&value.to_be_bytes());
+        element
+    }
+
+    /// SHA-256 of the inputs in circuit order, concatenated: what a
+    /// `ProofReceipt` is bound to.
+    pub fn hash(elements: &[FieldElement]) -> [u8; 32] {
+        anchor_lang::solana_program::hash::hash(&elements.concat()).to_bytes()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn hex(element: &FieldElement) -> String {
+            element.iter().map(|byte| format!("{:02x}", byte)).collect()
+        }
+
+        fn hash_from_hex(hash: &str) -> [u8; 32] {
+            let mut bytes = [0u8; 32];
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&hash[2 * i..2 * i + 2], 16).unwrap();
+            }
+            bytes
+        }
+
+        fn check(inputs: PaymentPolicyInputs, expected: [&str; PAYMENT_POLICY_INPUT_COUNT]) {
+            let elements = inputs.to_field_elements();
+            for (element, expected) in elements.iter().zip(expected) {
+                assert_eq!(hex(element), expected);
+            }
+        }
+
+        // Same vectors as `public_input_vectors` in groth16_fixtures.json
+
+        #[test]
+        fn encodes_fixture_policy() {
+            check(
+                PaymentPolicyInputs {
+                    amount: 50_000,
+                    category: 1,
+                    policy_hash: hash_from_hex(
+                        "764a6b2e60899e159f7af59d17b3cda0b37e1e2f59f7a4c75bd2ab95c4584d32",
+                    ),
+                    meter: hash_from_hex(
+                        "1d41a4cc8714f4baf697c5c80698fb710346b5e9fce48013cf3bc978b9f3f273",
+                    ),
+                    nonce: 1,
+                },
+                [
+                    "000000000000000000000000000000000000000000000000000000000000c350",
+                    "0000000000000000000000000000000000000000000000000000000000000001",
+                    "00000000000000000000000000000000764a6b2e60899e159f7af59d17b3cda0",
+                    "00000000000000000000000000000000b37e1e2f59f7a4c75bd2ab95c4584d32",
+                    "0010d69f76e921814f0b2d8b9cfa702e219040cb0b7e7a251c56e99894ab7741",
+                    "0000000000000000000000000000000000000000000000000000000000000001",
+                ],
+            );
+        }
+
+        #[test]
+        fn encodes_maximum_values() {
+            check(
+                PaymentPolicyInputs {
+                    amount: u64::MAX,
+                    category: u8::MAX,
+                    policy_hash: [0xff; 32],
+                    meter: [0xff; 32],
+                    nonce: u64::MAX,
+                },
+                [
+                    "000000000000000000000000000000000000000000000000ffffffffffffffff",
+                    "00000000000000000000000000000000000000000000000000000000000000ff",
+                    "00000000000000000000000000000000ffffffffffffffffffffffffffffffff",
+                    "00000000000000000000000000000000ffffffffffffffffffffffffffffffff",
+                    "009613760f72635fbdb44a5a0a63c39f12af30f950a6ee5c971be188e89c4051",
+                    "000000000000000000000000000000000000000000000000ffffffffffffffff",
+                ],
+            );
+        }
+
+        #[test]
+        fn splits_hash_in_byte_order() {
+            let mut policy_hash = [0u8; 32];
+            for (i, byte) in policy_hash.iter_mut().enumerate() {
+                *byte = i as u8;
+            }
+            check(
+                PaymentPolicyInputs {
+                    amount: 0,
+                    category: 0,
+                    policy_hash,
+                    meter: [0; 32],
+                    nonce: 0,
+                },
+                [
+                    "0000000000000000000000000000000000000000000000000000000000000000",
+                    "0000000000000000000000000000000000000000000000000000000000000000",
+                    "00000000000000000000000000000000000102030405060708090a0b0c0d0e0f",
+                    "00000000000000000000000000000000101112131415161718191a1b1c1d1e1f",
+                    "00687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925",
+                    "0000000000000000000000000000000000000000000000000000000000000000",
+                ],
+            );
+        }
+
+        #[test]
+        fn hashes_elements_in_order() {
+            let amount = field_element(1);
+            let nonce = field_element(2);
+            let mut concatenated = [0u8; 2 * FIELD_ELEMENT_LEN];
+            concatenated[FIELD_ELEMENT_LEN - 1] = 1;
+            concatenated[2 * FIELD_ELEMENT_LEN - 1] = 2;
+            assert_eq!(
+                hash(&[amount, nonce]),
+                anchor_lang::solana_program::hash::hash(&concatenated).to_bytes()
+            );
+            assert_ne!(hash(&[amount, nonce]), hash(&[nonce, amount]));
+        }
+    }
 }
 
 // =============================================================================
-// INSTRUCTION CONTEXTS
+// POLICY HASH
 // =============================================================================
 
-/// Context for set_policy instruction.
-#[derive(Accounts)]
-pub struct SetPolicy<'info> {
-    /// The agent whose policy is being set
-    pub agent: Signer<'info>,
-    
-    /// The policy account (PDA: ["policy", agent])
-    #[account(
-        init_if_needed,
-        payer = payer,
-        space = AgentPolicy::LEN,
-        seeds = [b"policy", agent.key().as_ref()],
-        bump
-    )]
-    pub agent_policy: Account<'info, AgentPolicy>,
-    
-    /// Account paying for the transaction
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    
-    pub system_program: Program<'info, System>,
-}
+/// The policy commitment the Noir circuits in zk/ prove against, for
+/// backends and client crates that need to compute `policy_hash`.
+/// 
+/// The circuits' `compute_policy_hash` hashes a POLICY_PREIMAGE_LEN-byte
+/// preimage with SHA-256: `max_per_tx` (u64 LE), `allowed_category`, then
+/// the first SALT_BYTES_USED bytes of the 32-byte salt. The last salt byte
+/// is ignored. On-chain this runs on the sol_sha256 syscall. Golden vectors
+/// are in tests/fixtures/groth16_fixtures.json (`policy_hash_vectors`);
+/// the circuit's `test_policy_hash_golden_vector` checks one of them.
+pub mod policy_hash {
+    /// Bytes hashed into the commitment
+    pub const POLICY_PREIMAGE_LEN: usize = 40;
 
-/// Context for create_meter instruction.
-#[derive(Accounts)]
-#[instruction(price_per_call: u64, category: u8, merchant_wallet_id: String)]
-pub struct CreateMeter<'info> {
-    /// Authority creating and controlling this meter
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    
-    /// Unique identifier for this meter (e.g., API endpoint hash)
-    /// CHECK: This is just used for PDA derivation
-    pub meter_id: AccountInfo<'info>,
-    
-    /// The meter account (PDA: ["meter", authority, meter_id])
-    #[account(
-        init,
-        payer = authority,
-        space = Meter::LEN,
-        seeds = [b"meter", authority.key().as_ref(), meter_id.key().as_ref()],
-        bump
-    )]
-    pub meter: Account<'info, Meter>,
-    
-    pub system_program: Program<'info, System>,
-}
+    /// Bytes of the salt the preimage takes
+    pub const SALT_BYTES_USED: usize = 31;
 
-/// Context for authorize_payment_with_proof instruction.
-#[derive(Accounts)]
-#[instruction(amount: u64, category: u8, nonce: u64)]
-pub struct AuthorizePayment<'info> {
-    /// The agent authorizing the payment
-    pub agent: Signer<'info>,
-    
-    /// The agent's policy account
-    #[account(
-        seeds = [b"policy", agent.key().as_ref()],
-        bump = agent_policy.bump,
-    )]
-    pub agent_policy: Account<'info, AgentPolicy>,
-    
-    /// The meter being paid
-    pub meter: Account<'info, Meter>,
-    
-    /// The authorization account (PDA: ["auth", agent, meter, nonce])
-    #[account(
-        init,
-        payer = payer,
-        space = Authorization::LEN,
-        seeds = [
-            b"auth",
-            agent.key().as_ref(),
-            meter.key().as_ref(),
-            &nonce.to_le_bytes()
-        ],
-        bump
-    )]
-    pub authorization: Account<'info, Authorization>,
-    
-    /// Account paying for the transaction
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    
-    pub system_program: Program<'info, System>,
+    /// SHA-256(max_per_tx LE || allowed_category || salt[..SALT_BYTES_USED])
+    pub fn compute(max_per_tx: u64, allowed_category: u8, salt: &[u8; 32]) -> [u8; 32] {
+        let mut preimage = [0u8; POLICY_PREIMAGE_LEN];
+        preimage[..8].copy_from_slice(&max_per_tx.to_le_bytes());
+        preimage[8] = allowed_category;
+        preimage[9..].copy_from_slice(&salt[..SALT_BYTES_USED]);
+        anchor_lang::solana_program::hash::hash(&preimage).to_bytes()
+    }
 
-    /// The Verifier Program to call via CPI
-    /// CHECK: We manually trust the caller to pass the correct program ID, or we hardcode it.
-    /// For Simulation, this is likely THIS program ID.
-    pub verifier_program: AccountInfo<'info>,
-}
+    #[cfg(test)]
+    mod tests {
+        use super::*;
 
-/// Context for record_meter_payment instruction.
-#[derive(Accounts)]
-#[instruction(nonce: u64)]
-pub struct RecordPayment<'info> {
-    /// The agent making the payment
-    pub agent: Signer<'info>,
-    
-    /// The meter being paid
-    pub meter: Account<'info, Meter>,
-    
-    /// The authorization to consume
-    #[account(
-        mut,
-        seeds = [
-            b"auth",
-            agent.key().as_ref(),
-            meter.key().as_ref(),
-            &nonce.to_le_bytes()
-        ],
-        bump = authorization.bump,
-        constraint = authorization.agent == agent.key(),
-        constraint = authorization.meter == meter.key(),
-    )]
-    pub authorization: Account<'info, Authorization>,
+        fn hex(hash: &[u8; 32]) -> String {
+            hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+        }
+
+        // Same vectors as `policy_hash_vectors` in groth16_fixtures.json
+
+        #[test]
+        fn hashes_circuit_test_policy() {
+            assert_eq!(
+                hex(&compute(1_000_000, 1, &[1; 32])),
+                "187b2c072f5797d9244c61626b3dd297b2de448e6a9d605d6c8b0397d3289129"
+            );
+        }
+
+        #[test]
+        fn hashes_maximum_values() {
+            assert_eq!(
+                hex(&compute(u64::MAX, u8::MAX, &[0xff; 32])),
+                "6ecd0f0bd7cf53c56d2129820911a26f815949eee418ca46b4f3d7a80cd969a7"
+            );
+        }
+
+        #[test]
+        fn ignores_last_salt_byte() {
+            let mut salt = [0u8; 32];
+            for (i, byte) in salt.iter_mut().enumerate() {
+                *byte = i as u8;
+            }
+            let expected = "7646986cf75070093536fe885fa576dcd682c9e08df12f6114d0228684fde03c";
+            assert_eq!(hex(&compute(0, 0, &salt)), expected);
+            salt[31] = 0xff;
+            assert_eq!(hex(&compute(0, 0, &salt)), expected);
+        }
+    }
 }
 
 // =============================================================================
-// EVENTS
+// EXPIRY KINDS
 // =============================================================================
 
-/// Emitted when a meter payment is recorded.
-/// 
-/// Off-chain services (specifically the Circle integration service)
-/// listen for this event to trigger USDC transfers from the agent's
-/// Circle wallet to the merchant's Circle wallet.
-#[event]
-pub struct MeterPaid {
-    /// The agent who made the payment
-    pub agent: Pubkey,
-    
-    /// The meter that was paid
-    pub meter: Pubkey,
-    
-    /// Amount paid (USDC smallest units)
-    pub amount: u64,
-    
-    /// Category of the payment
-    pub category: u8,
-    
-    /// Unique nonce for this payment
-    pub nonce: u64,
-    
-    /// Slot when payment was recorded
-    pub slot: u64,
+/// Values of `Authorization::expiry_kind`.
+pub mod expiry_kinds {
+    /// Expires after `expires_at_slot`
+    pub const SLOT: u8 = 0;
+
+    /// Also expires after `expires_at_unix` (Clock::unix_timestamp)
+    pub const UNIX: u8 = 1;
 }
 
-/// Emitted when an agent's policy is created or updated.
-#[event]
-pub struct PolicyUpdated {
-    pub agent_pubkey: Pubkey,
-    pub policy_hash: [u8; 32],
-    pub allowed_category: u8,
-    pub max_per_tx: u64,
-    pub frozen: bool,
-    pub slot: u64,
+// =============================================================================
+// CLOSE REASONS
+// =============================================================================
+
+/// Why an Authorization was closed, as reported by `AuthorizationClosed`.
+pub mod close_reasons {
+    /// Every call in it was recorded
+    pub const USED: u8 = 0;
+
+    /// It expired before being fully consumed
+    pub const EXPIRED: u8 = 1;
+
+    /// It was cancelled with revoke_authorization
+    pub const REVOKED: u8 = 2;
 }
 
 // =============================================================================
-// ERRORS
+// SETTLEMENT MODES
 // =============================================================================
 
-/// Custom errors for AgentBlinkPay program.
-#[error_code]
-pub enum AgentBlinkPayError {
-    /// Agent's policy is frozen - no payments allowed
-    #[msg("Agent policy is frozen")]
-    PolicyFrozen,
-    
-    /// Payment amount exceeds the policy's max_per_tx limit
-    #[msg("Amount exceeds maximum allowed per transaction")]
-    AmountExceedsMax,
-    
-    /// Authorization has already been used (replay attempt)
-    #[msg("Authorization has already been used")]
-    AuthorizationUsed,
-    
-    /// Authorization has expired (current_slot > expires_at_slot)
-    #[msg("Authorization has expired")]
-    AuthorizationExpired,
-    
-    /// Payment category doesn't match meter category
-    #[msg("Category mismatch between payment and meter")]
-    CategoryMismatch,
-    
-    /// ZK proof verification failed
-    #[msg("Invalid ZK proof")]
-    InvalidProof,
-    
-    /// Merchant wallet ID is too long (max 64 bytes)
-    #[msg("Merchant wallet ID too long (max 64 bytes)")]
-    MerchantWalletIdTooLong,
+/// How a meter's payments move funds, as set by `set_meter_settlement_mode`.
+pub mod settlement_modes {
+    /// MeterPaid events only; the Circle service transfers the funds
+    pub const EVENT_ONLY: u8 = 0;
 
-    #[msg("Invalid Public Inputs for Verifier")]
-    InvalidInputs,
+    /// Authorizing escrows the amount from the agent's vault and recording
+    /// pays the merchant's token account on-chain
+    pub const ESCROW: u8 = 1;
 }
 
 // =============================================================================
-// CATEGORY CONSTANTS (for reference)
+// DISPUTE OUTCOMES
 // =============================================================================
 
-/// Category constants for spending classification.
-/// These are stored as u8 in accounts for space efficiency.
-pub mod categories {
-    /// AI/ML inference APIs (e.g., OpenAI, Anthropic)
-    pub const AI_API: u8 = 1;
-    
-    /// Data feeds and market data
-    pub const DATA_FEED: u8 = 2;
-    
-    /// General tools and utilities
-    pub const TOOL: u8 = 3;
-    
-    /// Game actions (e.g., Catan demo)
-    pub const CATAN_ACTION: u8 = 4;
+/// How resolve_dispute ruled, as reported by `DisputeResolved`.
+pub mod dispute_outcomes {
+    /// The call was served; the payment stands
+    pub const UPHELD: u8 = 0;
+
+    /// The call wasn't served; the agent is owed a refund
+    pub const REFUNDED: u8 = 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNER: Pubkey = Pubkey::new_from_array([7; 32]);
+    const MESSAGE: &[u8] = b"agent_blink_pay:attestation test";
+
+    /// Ed25519 program data checking one signature, laid out like
+    /// `Ed25519Program.createInstructionWithPublicKey`: header, key,
+    /// signature, message
+    fn instruction_data() -> Vec<u8> {
+        let mut data = vec![1, 0];
+        for field in [48u16, u16::MAX, 16, u16::MAX, 112, MESSAGE.len() as u16, u16::MAX] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(SIGNER.as_ref());
+        data.extend_from_slice(&[9; 64]);
+        data.extend_from_slice(MESSAGE);
+        data
+    }
+
+    fn set_u16(data: &mut [u8], at: usize, value: u16) {
+        data[at..at + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn finds_signature() {
+        assert_eq!(ed25519_signature(&instruction_data(), &SIGNER, MESSAGE), Some([9; 64]));
+    }
+
+    #[test]
+    fn rejects_other_signer_or_message() {
+        let data = instruction_data();
+        assert_eq!(ed25519_signature(&data, &Pubkey::new_from_array([8; 32]), MESSAGE), None);
+        assert_eq!(ed25519_signature(&data, &SIGNER, b"agent_blink_pay:attestation tesT"), None);
+        assert_eq!(ed25519_signature(&data, &SIGNER, &MESSAGE[..MESSAGE.len() - 1]), None);
+    }
+
+    #[test]
+    fn rejects_malformed_header() {
+        let data = instruction_data();
+        assert_eq!(ed25519_signature(&data[..15], &SIGNER, MESSAGE), None);
+        assert_eq!(ed25519_signature(&[], &SIGNER, MESSAGE), None);
+
+        let mut two_signatures = data.clone();
+        two_signatures[0] = 2;
+        assert_eq!(ed25519_signature(&two_signatures, &SIGNER, MESSAGE), None);
+    }
+
+    #[test]
+    fn rejects_fields_in_other_instructions() {
+        for at in [4, 8, 14] {
+            let mut data = instruction_data();
+            set_u16(&mut data, at, 0);
+            assert_eq!(ed25519_signature(&data, &SIGNER, MESSAGE), None);
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_offsets() {
+        let data = instruction_data();
+        assert_eq!(ed25519_signature(&data[..data.len() - 1], &SIGNER, MESSAGE), None);
+
+        for at in [2, 6, 10] {
+            let mut data = instruction_data();
+            set_u16(&mut data, at, u16::MAX);
+            assert_eq!(ed25519_signature(&data, &SIGNER, MESSAGE), None);
+        }
+    }
+
+    #[test]
+    fn bounds_proof_length() {
+        let too_small = || Err(AgentBlinkPayError::ProofTooSmall.into());
+        assert_eq!(check_proof_len(&[]), too_small());
+        assert_eq!(check_proof_len(&[1; MIN_PROOF_LEN - 1]), too_small());
+        assert_eq!(check_proof_len(&[1; MIN_PROOF_LEN]), Ok(()));
+        assert_eq!(check_proof_len(&[1; MAX_PROOF_LEN]), Ok(()));
+        assert_eq!(
+            check_proof_len(&[1; MAX_PROOF_LEN + 1]),
+            Err(AgentBlinkPayError::ProofTooLarge.into())
+        );
+    }
+
+    #[test]
+    fn parses_proof_header() {
+        let malformed: Error = AgentBlinkPayError::MalformedProof.into();
+        assert_eq!(ProofHeader::parse(&[1]).err(), Some(malformed));
+
+        let header = ProofHeader::parse(&[2, 1]).unwrap();
+        assert_eq!((header.version, header.scheme), (2, 1));
+        assert!(header.payload.is_empty());
+    }
+
+    #[test]
+    fn tells_malformed_from_invalid_proofs() {
+        let malformed = || Error::from(AgentBlinkPayError::MalformedProof);
+        let invalid = || Error::from(AgentBlinkPayError::InvalidProof);
+        assert_eq!(reject_proof(ProofRejection::MalformedProof), malformed());
+        assert_eq!(reject_proof(ProofRejection::MalformedPoint), malformed());
+        assert_eq!(reject_proof(ProofRejection::PairingFailed), invalid());
+        assert_eq!(reject_proof(ProofRejection::VersionMismatch), invalid());
+    }
+
+    #[test]
+    fn matches_proof_circuit_to_allowlist() {
+        let mismatch = || Err(AgentBlinkPayError::AllowlistCircuitMismatch.into());
+        let mut policy = AgentPolicy::default();
+        assert_eq!(policy.check_payment_circuit(circuit_ids::PAYMENT_POLICY), Ok(()));
+        assert_eq!(policy.check_payment_circuit(circuit_ids::METER_ALLOWLIST), mismatch());
+        assert!(!policy.requires_proof());
+
+        policy.allowlist_root[31] = 1;
+        assert_eq!(policy.check_payment_circuit(circuit_ids::METER_ALLOWLIST), Ok(()));
+        assert_eq!(policy.check_payment_circuit(circuit_ids::PAYMENT_POLICY), mismatch());
+        assert_eq!(policy.check_payment_circuit(circuit_ids::BUDGET), mismatch());
+        assert!(policy.requires_proof());
+    }
+
+    #[test]
+    fn records_verification_on_authorization() {
+        let verifier_ref = Pubkey::new_unique();
+        let mut auth = Authorization::default();
+        for (method, zk_verified) in [
+            (verification_methods::EMBEDDED, true),
+            (verification_methods::CPI, true),
+            (verification_methods::ATTESTATION, false),
+            (verification_methods::PERMISSIVE, false),
+        ] {
+            auth.set_verification(Verification { method, verifier_ref });
+            assert_eq!(auth.zk_verified, zk_verified);
+            assert_eq!(auth.verification_method, method);
+            assert_eq!(auth.verifier_ref, verifier_ref);
+        }
+
+        auth.set_verification(Verification::NONE);
+        assert!(!auth.zk_verified);
+        assert_eq!(auth.verification_method, verification_methods::NONE);
+        assert_eq!(auth.verifier_ref, Pubkey::default());
+    }
+
+    #[test]
+    fn concatenates_batch_inputs_in_entry_order() {
+        let policy = AgentPolicy::default();
+        let meter = Pubkey::new_unique();
+        let payment = |nonce| AuthParams {
+            amount: 10 * nonce,
+            category: 1,
+            nonce,
+            expires_at_slot: 0,
+            request_id: [0; 32],
+        };
+        let payments = [payment(1), payment(2), payment(3)];
+
+        let inputs = batch_public_inputs(&policy, circuit_ids::PAYMENT_POLICY, &payments, meter, 0);
+        let per_payment = public_inputs::PAYMENT_POLICY_INPUT_COUNT;
+        assert_eq!(inputs.len(), payments.len() * per_payment);
+        for (chunk, payment) in inputs.chunks(per_payment).zip(&payments) {
+            let expected = payment_public_inputs(
+                &policy,
+                circuit_ids::PAYMENT_POLICY,
+                payment.amount,
+                payment.category,
+                meter,
+                payment.nonce,
+                0,
+            );
+            assert_eq!(chunk, &expected[..]);
+        }
+    }
+
+    #[test]
+    fn parses_aggregated_proof_headers() {
+        let aggregated = |batch_count: u8, proofs: usize| {
+            let mut proof = vec![1, proof_schemes::GROTH16_BATCH, batch_count];
+            proof.resize(AGGREGATED_PROOF_HEADER_LEN + proofs * GROTH16_PROOF_LEN, 7);
+            proof
+        };
+        let proof = aggregated(2, 2);
+        let parsed = AggregatedProof::parse(&proof).unwrap();
+        assert_eq!(parsed.version, 1);
+        assert_eq!(parsed.batch_count(), 2);
+        assert_eq!(parsed.proofs().count(), 2);
+
+        let malformed = || Err(AgentBlinkPayError::MalformedProof.into());
+        let parse = |proof: Vec<u8>| AggregatedProof::parse(&proof).map(|parsed| parsed.batch_count());
+        assert_eq!(parse(aggregated(2, 3)), malformed());
+        assert_eq!(parse(aggregated(0, 0)), malformed());
+        let too_many = MAX_AUTHORIZATION_BATCH + 1;
+        assert_eq!(parse(aggregated(too_many as u8, too_many)), malformed());
+        assert_eq!(parse(vec![1, proof_schemes::GROTH16_BATCH]), malformed());
+
+        let mut single = aggregated(1, 1);
+        single[1] = proof_schemes::GROTH16;
+        assert_eq!(parse(single), Err(AgentBlinkPayError::UnsupportedProofScheme.into()));
+    }
+
+    #[test]
+    fn appends_allowlist_root_to_its_circuit_inputs() {
+        let policy = AgentPolicy {
+            allowlist_root: [3; 32],
+            ..AgentPolicy::default()
+        };
+        let inputs = |circuit_id| {
+            payment_public_inputs(&policy, circuit_id, 1, 1, Pubkey::default(), 1, 0)
+        };
+        let allowlist = inputs(circuit_ids::METER_ALLOWLIST);
+        assert_eq!(allowlist.len(), public_inputs::PAYMENT_POLICY_INPUT_COUNT + 1);
+        assert_eq!(allowlist[public_inputs::ALLOWLIST_ROOT_INDEX], [3; 32]);
+        assert_eq!(
+            inputs(circuit_ids::PAYMENT_POLICY).len(),
+            public_inputs::PAYMENT_POLICY_INPUT_COUNT
+        );
+    }
 }
@@ -0,0 +1,369 @@
+//! CPI Caller - test-only Solana program
+//!
+//! Stands in for a multisig or any other program that administers
+//! AgentBlinkPay policies, or pays as an agent, through a PDA. Only used by
+//! the integration tests.
+//!
+//! ## Instructions
+//! - `set_policy_as_pda`: Call `set_policy` with this program's owner PDA as the owner
+//! - `set_policy_for_agent_pda`: Call `set_policy` signed by this program's agent PDA
+//! - `authorize_as_pda`: Call `authorize_payment_with_proof` as this program's agent PDA
+//! - `record_as_pda`: Call `record_meter_payment` as this program's agent PDA
+
+use anchor_lang::prelude::*;
+use agent_blink_pay::program::AgentBlinkPay;
+use agent_blink_pay::PolicyParams;
+
+declare_id!("2Gcekwt7YQMkP8DqtHJo2njoV4AZqByRSaVUUu2ns42j");
+
+#[program]
+pub mod cpi_caller {
+    use super::*;
+
+    /// Sets an agent's policy with the PDA ["owner"] of this program as owner.
+    /// 
+    /// The PDA signs via `invoke_signed`, which is how a multisig vault
+    /// would call `set_policy`.
+    /// 
+    /// Optional policy features are left off (unlimited budget and daily
+    /// limit, no allowlist, no alerts, any mint).
+    /// 
+    /// # Arguments
+    /// * `policy_id`, `policy_hash`, `allowed_category`, `max_per_tx`, `frozen` -
+    ///   Forwarded to `set_policy`
+    pub fn set_policy_as_pda(
+        ctx: Context<SetPolicyAsPda>,
+        policy_id: u16,
+        policy_hash: [u8; 32],
+        allowed_category: u8,
+        max_per_tx: u64,
+        frozen: bool,
+    ) -> Result<()> {
+        let bump = [ctx.bumps.owner_pda];
+        let signer_seeds: &[&[&[u8]]] = &[&[b"owner", &bump]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.agent_blink_pay_program.to_account_info(),
+            agent_blink_pay::cpi::accounts::SetPolicy {
+                owner: ctx.accounts.owner_pda.to_account_info(),
+                agent: ctx.accounts.agent.to_account_info(),
+                agent_policy: ctx.accounts.agent_policy.to_account_info(),
+                retired_agent: ctx.accounts.retired_agent.to_account_info(),
+                payer: ctx.accounts.payer.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+            },
+            signer_seeds,
+        );
+
+        agent_blink_pay::cpi::set_policy(
+            cpi_ctx,
+            PolicyParams {
+                policy_id,
+                policy_hash,
+                allowed_category,
+                max_per_tx,
+                frozen,
+                total_budget: 0,
+                enforce_meter_allowlist: false,
+                always_require_zk: false,
+                metadata_hash: [0u8; 32],
+                daily_limit: 0,
+                alert_threshold_bps: 0,
+                restrict_mint: None,
+                policy_salt: None,
+                allowlist_root: [0u8; 32],
+            },
+        )
+    }
+
+    /// Sets the policy of the PDA ["agent"] of this program, owned by `owner`.
+    /// 
+    /// The agent PDA signs via `invoke_signed`, which `set_policy` requires
+    /// when it creates the policy. Optional policy features are left off, as
+    /// in `set_policy_as_pda`.
+    /// 
+    /// # Arguments
+    /// * `policy_id`, `policy_hash`, `allowed_category`, `max_per_tx`, `frozen` -
+    ///   Forwarded to `set_policy`
+    pub fn set_policy_for_agent_pda(
+        ctx: Context<SetPolicyForAgentPda>,
+        policy_id: u16,
+        policy_hash: [u8; 32],
+        allowed_category: u8,
+        max_per_tx: u64,
+        frozen: bool,
+    ) -> Result<()> {
+        let bump = [ctx.bumps.agent_pda];
+        let signer_seeds: &[&[&[u8]]] = &[&[b"agent", &bump]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.agent_blink_pay_program.to_account_info(),
+            agent_blink_pay::cpi::accounts::SetPolicy {
+                owner: ctx.accounts.owner.to_account_info(),
+                agent: ctx.accounts.agent_pda.to_account_info(),
+                agent_policy: ctx.accounts.agent_policy.to_account_info(),
+                retired_agent: ctx.accounts.retired_agent.to_account_info(),
+                payer: ctx.accounts.payer.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+            },
+            signer_seeds,
+        );
+
+        agent_blink_pay::cpi::set_policy(
+            cpi_ctx,
+            PolicyParams {
+                policy_id,
+                policy_hash,
+                allowed_category,
+                max_per_tx,
+                frozen,
+                total_budget: 0,
+                enforce_meter_allowlist: false,
+                always_require_zk: false,
+                metadata_hash: [0u8; 32],
+                daily_limit: 0,
+                alert_threshold_bps: 0,
+                restrict_mint: None,
+                policy_salt: None,
+                allowlist_root: [0u8; 32],
+            },
+        )
+    }
+
+    /// Authorizes a payment with the PDA ["agent"] of this program as agent.
+    /// 
+    /// # Arguments
+    /// * Same as `authorize_payment_with_proof`
+    pub fn authorize_as_pda(
+        ctx: Context<AuthorizeAsPda>,
+        policy_id: u16,
+        amount: u64,
+        quantity: u16,
+        category: u8,
+        nonce: u64,
+        expires_at: u64,
+        proof: Vec<u8>,
+        request_id: [u8; 32],
+        expiry_kind: u8,
+        consumer: Pubkey,
+        hash_lock: [u8; 32],
+        tip: u64,
+    ) -> Result<()> {
+        let bump = [ctx.bumps.agent_pda];
+        let signer_seeds: &[&[&[u8]]] = &[&[b"agent", &bump]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.agent_blink_pay_program.to_account_info(),
+            agent_blink_pay::cpi::accounts::AuthorizePayment {
+                agent: ctx.accounts.agent_pda.to_account_info(),
+                agent_policy: ctx.accounts.agent_policy.to_account_info(),
+                meter: ctx.accounts.meter.to_account_info(),
+                allowed_meter: None,
+                denied_meter: ctx.accounts.denied_meter.to_account_info(),
+                meter_access: None,
+                meter_usage: ctx.accounts.meter_usage.to_account_info(),
+                authorization: ctx.accounts.authorization.to_account_info(),
+                config: ctx.accounts.config.to_account_info(),
+                payer: ctx.accounts.payer.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                verifier_program: ctx.accounts.agent_blink_pay_program.to_account_info(),
+                verifying_key: Some(ctx.accounts.verifying_key.to_account_info()),
+                honk_verifying_key: None,
+                nullifier: Some(ctx.accounts.nullifier.to_account_info()),
+                pending_verification: None,
+                instructions: None,
+                proof_receipt: None,
+                vault: None,
+                escrow: None,
+                mint: None,
+                token_program: None,
+                associated_token_program: None,
+            },
+            signer_seeds,
+        );
+
+        agent_blink_pay::cpi::authorize_payment_with_proof(
+            cpi_ctx,
+            policy_id,
+            amount,
+            quantity,
+            category,
+            nonce,
+            expires_at,
+            proof,
+            request_id,
+            expiry_kind,
+            consumer,
+            hash_lock,
+            tip,
+        )
+    }
+
+    /// Consumes an authorization issued to the PDA ["agent"] of this program.
+    /// 
+    /// # Arguments
+    /// * `nonce` - The nonce of the authorization to consume
+    pub fn record_as_pda(ctx: Context<RecordAsPda>, nonce: u64) -> Result<()> {
+        let bump = [ctx.bumps.agent_pda];
+        let signer_seeds: &[&[&[u8]]] = &[&[b"agent", &bump]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.agent_blink_pay_program.to_account_info(),
+            agent_blink_pay::cpi::accounts::RecordPayment {
+                recorder: ctx.accounts.agent_pda.to_account_info(),
+                agent: ctx.accounts.agent_pda.to_account_info(),
+                meter: ctx.accounts.meter.to_account_info(),
+                meter_counters: ctx.accounts.meter_counters.to_account_info(),
+                agent_policy: ctx.accounts.agent_policy.to_account_info(),
+                authorization: ctx.accounts.authorization.to_account_info(),
+                meter_usage: ctx.accounts.meter_usage.to_account_info(),
+                config: ctx.accounts.config.to_account_info(),
+                escrow: None,
+                merchant_token_account: None,
+                token_program: None,
+                receipt: None,
+                payer: None,
+                system_program: None,
+            },
+            signer_seeds,
+        );
+
+        // Charge the authorization's full share, as before partial consumption
+        agent_blink_pay::cpi::record_meter_payment(cpi_ctx, nonce, 0, true, [0; 16], Vec::new(), false)
+    }
+}
+
+/// Context for set_policy_as_pda instruction.
+#[derive(Accounts)]
+pub struct SetPolicyAsPda<'info> {
+    /// This program's owner PDA (PDA: ["owner"])
+    /// CHECK: Only used as a CPI signer
+    #[account(seeds = [b"owner"], bump)]
+    pub owner_pda: UncheckedAccount<'info>,
+
+    /// The agent whose policy is being set; signs so the policy can be created
+    pub agent: Signer<'info>,
+
+    /// The policy account, validated by AgentBlinkPay
+    /// CHECK: Seeds and owner are checked by the callee
+    #[account(mut)]
+    pub agent_policy: UncheckedAccount<'info>,
+
+    /// The agent's tombstone address, validated by AgentBlinkPay
+    /// CHECK: Seeds and owner are checked by the callee
+    pub retired_agent: UncheckedAccount<'info>,
+
+    /// Account paying for the policy account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    pub agent_blink_pay_program: Program<'info, AgentBlinkPay>,
+}
+
+/// Context for set_policy_for_agent_pda instruction.
+#[derive(Accounts)]
+pub struct SetPolicyForAgentPda<'info> {
+    /// This program's agent PDA (PDA: ["agent"])
+    /// CHECK: Only used as a CPI signer
+    #[account(seeds = [b"agent"], bump)]
+    pub agent_pda: UncheckedAccount<'info>,
+
+    /// Owner of the policy
+    pub owner: Signer<'info>,
+
+    /// The policy account, validated by AgentBlinkPay
+    /// CHECK: Seeds and owner are checked by the callee
+    #[account(mut)]
+    pub agent_policy: UncheckedAccount<'info>,
+
+    /// The agent's tombstone address, validated by AgentBlinkPay
+    /// CHECK: Seeds and owner are checked by the callee
+    pub retired_agent: UncheckedAccount<'info>,
+
+    /// Account paying for the policy account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    pub agent_blink_pay_program: Program<'info, AgentBlinkPay>,
+}
+
+/// Context for authorize_as_pda instruction.
+#[derive(Accounts)]
+pub struct AuthorizeAsPda<'info> {
+    /// This program's agent PDA (PDA: ["agent"])
+    /// CHECK: Only used as a CPI signer
+    #[account(seeds = [b"agent"], bump)]
+    pub agent_pda: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by AgentBlinkPay
+    pub agent_policy: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by AgentBlinkPay
+    pub meter: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by AgentBlinkPay
+    pub denied_meter: UncheckedAccount<'info>,
+
+    /// CHECK: Validated (and possibly created) by AgentBlinkPay
+    #[account(mut)]
+    pub meter_usage: UncheckedAccount<'info>,
+
+    /// CHECK: Created by AgentBlinkPay
+    #[account(mut)]
+    pub authorization: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by AgentBlinkPay
+    pub config: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by AgentBlinkPay
+    pub verifying_key: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by AgentBlinkPay
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Account paying for the authorization
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    pub agent_blink_pay_program: Program<'info, AgentBlinkPay>,
+}
+
+/// Context for record_as_pda instruction.
+#[derive(Accounts)]
+pub struct RecordAsPda<'info> {
+    /// This program's agent PDA (PDA: ["agent"])
+    /// CHECK: Only used as a CPI signer
+    #[account(seeds = [b"agent"], bump)]
+    pub agent_pda: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by AgentBlinkPay
+    pub meter: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by AgentBlinkPay
+    #[account(mut)]
+    pub meter_counters: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by AgentBlinkPay
+    #[account(mut)]
+    pub agent_policy: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by AgentBlinkPay
+    #[account(mut)]
+    pub authorization: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by AgentBlinkPay
+    #[account(mut)]
+    pub meter_usage: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by AgentBlinkPay
+    pub config: UncheckedAccount<'info>,
+
+    pub agent_blink_pay_program: Program<'info, AgentBlinkPay>,
+}
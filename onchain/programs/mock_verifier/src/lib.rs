@@ -0,0 +1,36 @@
+//! Mock Verifier - test-only Solana program
+//!
+//! Stands in for the Sunspot-generated verifier program that AgentBlinkPay
+//! calls when the Config enables `external_verifier`, so the integration
+//! tests can exercise the CPI path without real proving. Only used by the
+//! integration tests.
+//!
+//! Like a Sunspot verifier it is a plain (non-Anchor) program taking no
+//! accounts; its instruction data is the proof points followed by the
+//! public inputs. It accepts the proof if the first byte is
+//! `ACCEPT_MAGIC` and rejects it otherwise.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+
+declare_id!("DUURAM5mYBrePqiCb9x2Ai2dgcjtLpi471LME5yqpWjj");
+
+/// First proof byte that makes the mock accept.
+pub const ACCEPT_MAGIC: u8 = 0xA5;
+
+#[cfg(not(feature = "no-entrypoint"))]
+anchor_lang::solana_program::entrypoint!(process_instruction);
+
+pub fn process_instruction(
+    _program_id: &Pubkey,
+    _accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    if instruction_data.first() == Some(&ACCEPT_MAGIC) {
+        msg!("Mock verifier: proof accepted");
+        Ok(())
+    } else {
+        msg!("Mock verifier: proof rejected");
+        Err(ProgramError::InvalidInstructionData)
+    }
+}
@@ -1,9 +1,22 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { AgentBlinkPay } from "../target/types/agent_blink_pay";
-import { Keypair, PublicKey, SystemProgram } from "@solana/web3.js";
+import { CpiCaller } from "../target/types/cpi_caller";
+import { Ed25519Program, Keypair, PublicKey, SYSVAR_INSTRUCTIONS_PUBKEY, SystemProgram } from "@solana/web3.js";
+import {
+    ASSOCIATED_TOKEN_PROGRAM_ID,
+    TOKEN_PROGRAM_ID,
+    createAccount,
+    createMint,
+    getAccount,
+    getAssociatedTokenAddressSync,
+    getOrCreateAssociatedTokenAccount,
+    mintTo,
+} from "@solana/spl-token";
 import { expect } from "chai";
 import crypto from "crypto";
+import fs from "fs";
+import path from "path";
 
 describe("agent_blink_pay", () => {
     // Configure the client to use the local cluster.
@@ -15,11 +28,14 @@ describe("agent_blink_pay", () => {
     // Test keypairs
     const agentKeypair = Keypair.generate();
     const meterIdKeypair = Keypair.generate();
+    const watcherKeypair = Keypair.generate();
 
     // PDAs
     let policyPda: PublicKey;
     let meterPda: PublicKey;
     let authPda: PublicKey;
+    let configPda: PublicKey;
+    let deniedMeterPda: PublicKey;
 
     // Test constants
     const policyHash = Array.from(crypto.createHash('sha256').update('test_policy').digest());
@@ -28,11 +44,125 @@ describe("agent_blink_pay", () => {
     const pricePerCall = new anchor.BN(50000); // 0.05 USDC
     const merchantWalletId = "test_merchant_wallet_123";
     const testNonce = new anchor.BN(Date.now());
+    const unlimitedBudget = new anchor.BN(0);
+    const policyId = 0; // DEFAULT_POLICY_ID
+    const emptyMetadataHash = Array(32).fill(0);
+    const unlimitedDailyLimit = new anchor.BN(0);
+    const noAlertThreshold = 0;
+    const perCallKind = { perCall: {} };
+    const noFreeCalls = 0;
+    const meterName = "Test API";
+    const endpointHash = Array(32).fill(0);
+    const noMeterCap = new anchor.BN(0);
+    const noReferrer = "";
+    const noReferrerBps = 0;
+    const anyProofVersion = 0;
+    const singleCall = 1;
+    const fullAmount = new anchor.BN(0); // ignored with consumeAll
+    const consumeAll = true;
+    const noRequestId = Array(32).fill(0);
+    const noExternalId = Array(16).fill(0);
+    const noHashLock = Array(32).fill(0);
+    const noTip = new anchor.BN(0);
+    const noPreimage = Buffer.alloc(0);
+    const noReceipt = false;
+    const expireBySlot = 0; // expiry_kinds::SLOT
+    const anyConsumer = PublicKey.default;
+    const defaultMint = null; // the Config's usdc_mint
+    const noMintRestriction = null;
+    const noPolicySalt = null; // set_policy doesn't recompute policy_hash
+    const noAllowlistRoot = Array(32).fill(0);
+    const usdcMint = new PublicKey("4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU");
+    const paymentPolicyCircuit = 1; // circuit_ids::PAYMENT_POLICY
+    const budgetCircuit = 2; // circuit_ids::BUDGET
+    const dailyLimitCircuit = 3; // circuit_ids::DAILY_LIMIT
+    const meterAllowlistCircuit = 4; // circuit_ids::METER_ALLOWLIST
+    const permissiveEnforcement = 0; // enforcement_modes::PERMISSIVE
+    const strictEnforcement = 1; // enforcement_modes::STRICT
+    const verificationNone = 0; // verification_methods::NONE
+    const verificationEmbedded = 1; // verification_methods::EMBEDDED
+    const verificationCpi = 2; // verification_methods::CPI
+    const verificationAttestation = 3; // verification_methods::ATTESTATION
+    const verificationPermissive = 4; // verification_methods::PERMISSIVE
+    const [programDataPda] = PublicKey.findProgramAddressSync(
+        [program.programId.toBuffer()],
+        new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+
+    // Development verifying keys and proofs (tests/fixtures/groth16_fixtures.py)
+    const groth16Fixtures = JSON.parse(
+        fs.readFileSync(path.join(__dirname, "fixtures", "groth16_fixtures.json"), "utf8")
+    );
+
+    // UltraHonk test proofs, checked by programs/mock_verifier
+    const honkFixtures = JSON.parse(
+        fs.readFileSync(path.join(__dirname, "fixtures", "honk_fixtures.json"), "utf8")
+    );
+
+    // The fixture proofs commit to this meter and nonce
+    const fixtureMeterAuthority = Keypair.fromSeed(Buffer.from(groth16Fixtures.meter_authority_seed, "hex"));
+    const fixtureMeterId = new PublicKey(Buffer.from(groth16Fixtures.meter_id, "hex"));
+    const fixtureMeterPda = new PublicKey(Buffer.from(groth16Fixtures.meter, "hex"));
+    const fixtureNonce = new anchor.BN(groth16Fixtures.nonce);
+
+    // set_policy params for the test agent, with per-test overrides
+    const policyParams = (overrides: object = {}) => ({
+        policyId,
+        policyHash,
+        allowedCategory,
+        maxPerTx,
+        frozen: false,
+        totalBudget: unlimitedBudget,
+        enforceMeterAllowlist: false,
+        alwaysRequireZk: false,
+        metadataHash: emptyMetadataHash,
+        dailyLimit: unlimitedDailyLimit,
+        alertThresholdBps: noAlertThreshold,
+        restrictMint: noMintRestriction,
+        policySalt: noPolicySalt,
+        allowlistRoot: noAllowlistRoot,
+        ...overrides,
+    });
+
+    // Global config: one watcher, circuit breaker trips after 3 failures
+    const configParams = (overrides: object = {}) => ({
+        watchers: [
+            watcherKeypair.publicKey,
+            PublicKey.default,
+            PublicKey.default,
+            PublicKey.default,
+        ],
+        autoFreezeThreshold: 3,
+        minPriceDelaySlots: new anchor.BN(0),
+        minPricePerCall: new anchor.BN(0),
+        maxPricePerCall: new anchor.BN(0),
+        usdcMint,
+        maxAuthorizationTtlSecs: 600,
+        graceSlots: 0,
+        settlementAuthority: PublicKey.default,
+        disputeWindowSlots: new anchor.BN(0),
+        refundWindowSlots: new anchor.BN(0),
+        verifierProgram: PublicKey.default,
+        externalVerifier: false,
+        enforcementMode: strictEnforcement,
+        policyOracle: PublicKey.default,
+        ...overrides,
+    });
+
+    const updateConfig = (overrides: object = {}) =>
+        program.methods
+            .updateConfig(configParams(overrides))
+            .accounts({ admin: provider.wallet.publicKey, config: configPda })
+            .rpc();
 
     before(async () => {
         // Derive PDAs
         [policyPda] = PublicKey.findProgramAddressSync(
-            [Buffer.from("policy"), agentKeypair.publicKey.toBuffer()],
+            [
+                Buffer.from("policy"),
+                agentKeypair.publicKey.toBuffer(),
+                new anchor.BN(policyId).toArrayLike(Buffer, 'le', 2)
+            ],
             program.programId
         );
 
@@ -55,6 +185,59 @@ describe("agent_blink_pay", () => {
             program.programId
         );
 
+        [deniedMeterPda] = PublicKey.findProgramAddressSync(
+            [Buffer.from("denied"), policyPda.toBuffer(), meterPda.toBuffer()],
+            program.programId
+        );
+
+        [configPda] = PublicKey.findProgramAddressSync(
+            [Buffer.from("config")],
+            program.programId
+        );
+
+        // Permissive until the enforcement mode tests latch it to strict
+        await program.methods
+            .initializeConfig(configParams({ enforcementMode: permissiveEnforcement }))
+            .accounts({
+                admin: provider.wallet.publicKey,
+                program: program.programId,
+                programData: programDataPda,
+                config: configPda,
+                systemProgram: SystemProgram.programId,
+            })
+            .rpc();
+
+        await setVerifyingKey(paymentPolicyCircuit, vkParams(groth16Fixtures.keys.payment_policy));
+        await setVerifyingKey(budgetCircuit, vkParams(groth16Fixtures.keys.budget));
+
+        const fixtureMeterSig = await provider.connection.requestAirdrop(
+            fixtureMeterAuthority.publicKey,
+            anchor.web3.LAMPORTS_PER_SOL
+        );
+        await provider.connection.confirmTransaction(fixtureMeterSig);
+        expect(
+            PublicKey.findProgramAddressSync(
+                [Buffer.from("meter"), fixtureMeterAuthority.publicKey.toBuffer(), fixtureMeterId.toBuffer()],
+                program.programId
+            )[0].toBase58()
+        ).to.equal(fixtureMeterPda.toBase58());
+        await program.methods
+            .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+            .accounts({
+                authority: fixtureMeterAuthority.publicKey,
+                meterId: fixtureMeterId,
+                meter: fixtureMeterPda,
+                meterCounters: countersPdaFor(fixtureMeterPda),
+                config: configPda,
+                meterIndex: PublicKey.findProgramAddressSync(
+                    [Buffer.from("meter_index"), fixtureMeterAuthority.publicKey.toBuffer()],
+                    program.programId
+                )[0],
+                systemProgram: SystemProgram.programId,
+            })
+            .signers([fixtureMeterAuthority])
+            .rpc();
+
         // Airdrop SOL to agent for fees
         const sig = await provider.connection.requestAirdrop(
             agentKeypair.publicKey,
@@ -63,16 +246,402 @@ describe("agent_blink_pay", () => {
         await provider.connection.confirmTransaction(sig);
     });
 
+    // Helpers shared by the feature tests below
+    const authPdaFor = (
+        nonce: anchor.BN,
+        agent: PublicKey = agentKeypair.publicKey,
+        meter: PublicKey = meterPda
+    ) =>
+        PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("auth"),
+                agent.toBuffer(),
+                meter.toBuffer(),
+                nonce.toArrayLike(Buffer, 'le', 8)
+            ],
+            program.programId
+        )[0];
+
+    const agentPolicyPdaFor = (agent: PublicKey) =>
+        PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("policy"),
+                agent.toBuffer(),
+                new anchor.BN(policyId).toArrayLike(Buffer, 'le', 2)
+            ],
+            program.programId
+        )[0];
+
+    const retiredPdaFor = (agent: PublicKey) =>
+        PublicKey.findProgramAddressSync([Buffer.from("retired"), agent.toBuffer()], program.programId)[0];
+
+    // Allowlist and denylist records are keyed by the agent's policy
+    const deniedPdaFor = (meter: PublicKey, agent: PublicKey = agentKeypair.publicKey) =>
+        PublicKey.findProgramAddressSync(
+            [Buffer.from("denied"), agentPolicyPdaFor(agent).toBuffer(), meter.toBuffer()],
+            program.programId
+        )[0];
+
+    const countersPdaFor = (meter: PublicKey) =>
+        PublicKey.findProgramAddressSync(
+            [Buffer.from("counters"), meter.toBuffer()],
+            program.programId
+        )[0];
+
+    const usagePdaFor = (meter: PublicKey = meterPda, agent: PublicKey = agentKeypair.publicKey) =>
+        PublicKey.findProgramAddressSync(
+            [Buffer.from("usage"), meter.toBuffer(), agent.toBuffer()],
+            program.programId
+        )[0];
+
+    const vkPdaFor = (circuitId: number) =>
+        PublicKey.findProgramAddressSync(
+            [Buffer.from("vk"), Buffer.from([circuitId])],
+            program.programId
+        )[0];
+
+    const honkVkPdaFor = (circuitId: number) =>
+        PublicKey.findProgramAddressSync(
+            [Buffer.from("honk_vk"), Buffer.from([circuitId])],
+            program.programId
+        )[0];
+
+    // Nullifier PDA of a payment, from its public inputs (payment_nullifier
+    // in lib.rs); `domain` is the seed prefix of the authorization it creates
+    const nullifierPdaFor = async (
+        agent: PublicKey,
+        meter: PublicKey,
+        nonce: anchor.BN,
+        amount: anchor.BN,
+        policy: PublicKey,
+        domain: string = "auth"
+    ) => {
+        const { policyHash } = await program.account.agentPolicy.fetch(policy);
+        const nullifier = crypto
+            .createHash("sha256")
+            .update(Buffer.concat([
+                Buffer.from(domain),
+                agent.toBuffer(),
+                meter.toBuffer(),
+                nonce.toArrayLike(Buffer, "le", 8),
+                amount.toArrayLike(Buffer, "le", 8),
+                Buffer.from(policyHash),
+            ]))
+            .digest();
+        return PublicKey.findProgramAddressSync([Buffer.from("nullifier"), nullifier], program.programId)[0];
+    };
+
+    const vkParams = (key: any, circuitVersion: number = 1, locked: boolean = false) => ({
+        circuitVersion,
+        alphaG1: [...Buffer.from(key.alpha_g1, "hex")],
+        betaG2: [...Buffer.from(key.beta_g2, "hex")],
+        gammaG2: [...Buffer.from(key.gamma_g2, "hex")],
+        deltaG2: [...Buffer.from(key.delta_g2, "hex")],
+        ic: key.ic.map((point: string) => [...Buffer.from(point, "hex")]),
+        locked,
+    });
+
+    const setVerifyingKey = (circuitId: number, params: ReturnType<typeof vkParams>) =>
+        program.methods
+            .setVerifyingKey(circuitId, params)
+            .accounts({
+                admin: provider.wallet.publicKey,
+                config: configPda,
+                verifyingKey: vkPdaFor(circuitId),
+                honkVerifyingKey: honkVkPdaFor(circuitId),
+                systemProgram: SystemProgram.programId,
+            })
+            .rpc();
+
+    const [meterIndexPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("meter_index"), provider.wallet.publicKey.toBuffer()],
+        program.programId
+    );
+
+    const authorize = async (
+        nonce: anchor.BN,
+        amount: anchor.BN,
+        meter: PublicKey = meterPda,
+        meterAccess: PublicKey | null = null,
+        proof: Buffer = Buffer.alloc(64),
+        ttlSlots: number = 100,
+        hashLock: number[] = noHashLock,
+        tip: anchor.BN = noTip
+    ) => {
+        const currentSlot = await provider.connection.getSlot();
+        await program.methods
+            .authorizePaymentWithProof(
+                policyId,
+                amount,
+                singleCall,
+                allowedCategory,
+                nonce,
+                new anchor.BN(currentSlot + ttlSlots),
+                [...proof],
+                noRequestId,
+                expireBySlot,
+                anyConsumer,
+                hashLock,
+                tip
+            )
+            .accounts({
+                agent: agentKeypair.publicKey,
+                agentPolicy: policyPda,
+                meter,
+                allowedMeter: null,
+                deniedMeter: deniedPdaFor(meter),
+                meterAccess,
+                meterUsage: usagePdaFor(meter),
+                authorization: authPdaFor(nonce, agentKeypair.publicKey, meter),
+                config: configPda,
+                payer: provider.wallet.publicKey,
+                systemProgram: SystemProgram.programId,
+                verifierProgram: program.programId,
+                verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                nullifier: await nullifierPdaFor(agentKeypair.publicKey, meter, nonce, amount, policyPda),
+                vault: null,
+                escrow: null,
+                mint: null,
+                tokenProgram: null,
+                associatedTokenProgram: null,
+            })
+            .signers([agentKeypair])
+            .rpc();
+    };
+
+    // Authorizes a fixture payment with `proof`, for the ZK suites whose
+    // agents each hold a policy under the fixture policy hash
+    const authorizeWithProof = async (
+        agent: Keypair,
+        nonce: anchor.BN,
+        proof: Buffer,
+        overrides: {
+            amount?: anchor.BN,
+            meter?: PublicKey,
+            expiresAtSlot?: anchor.BN,
+            verifierProgram?: PublicKey,
+            verifyingKey?: PublicKey | null,
+            honkVerifyingKey?: PublicKey | null,
+            proofReceipt?: PublicKey,
+            commitment?: anchor.web3.Commitment,
+        } = {}
+    ) => {
+        const amount = overrides.amount ?? new anchor.BN(groth16Fixtures.amount);
+        const meter = overrides.meter ?? fixtureMeterPda;
+        const agentPolicy = agentPolicyPdaFor(agent.publicKey);
+        const expiresAtSlot =
+            overrides.expiresAtSlot ?? new anchor.BN((await provider.connection.getSlot()) + 100);
+        return program.methods
+            .authorizePaymentWithProof(
+                policyId,
+                amount,
+                singleCall,
+                groth16Fixtures.category,
+                nonce,
+                expiresAtSlot,
+                [...proof],
+                noRequestId,
+                expireBySlot,
+                anyConsumer,
+                noHashLock,
+                noTip
+            )
+            .accounts({
+                agent: agent.publicKey,
+                agentPolicy,
+                meter,
+                allowedMeter: null,
+                deniedMeter: deniedPdaFor(meter, agent.publicKey),
+                meterAccess: null,
+                meterUsage: usagePdaFor(meter, agent.publicKey),
+                authorization: authPdaFor(nonce, agent.publicKey, meter),
+                config: configPda,
+                payer: provider.wallet.publicKey,
+                systemProgram: SystemProgram.programId,
+                verifierProgram: overrides.verifierProgram ?? program.programId,
+                verifyingKey:
+                    overrides.verifyingKey === undefined ? vkPdaFor(paymentPolicyCircuit) : overrides.verifyingKey,
+                honkVerifyingKey: overrides.honkVerifyingKey ?? null,
+                nullifier: await nullifierPdaFor(agent.publicKey, meter, nonce, amount, agentPolicy),
+                proofReceipt: overrides.proofReceipt ?? null,
+                vault: null,
+                escrow: null,
+                mint: null,
+                tokenProgram: null,
+                associatedTokenProgram: null,
+            })
+            .signers([agent])
+            .rpc(overrides.commitment ? { commitment: overrides.commitment } : undefined);
+    };
+
+    // Expects `action` to fail with `code`, and with `log` among the
+    // program logs when given
+    const expectError = async (action: () => Promise<unknown>, code: string, log?: string) => {
+        try {
+            await action();
+            expect.fail(`Should have thrown ${code} error`);
+        } catch (err: any) {
+            expect(err.error.errorCode.code).to.equal(code);
+            if (log !== undefined) {
+                expect(err.logs.join("\n")).to.include(log);
+            }
+        }
+    };
+
+    const record = async (
+        nonce: anchor.BN,
+        meter: PublicKey = meterPda,
+        externalId: number[] = noExternalId,
+        preimage: Buffer = noPreimage
+    ) =>
+        program.methods
+            .recordMeterPayment(nonce, fullAmount, consumeAll, externalId, preimage, noReceipt)
+            .accounts({
+                recorder: agentKeypair.publicKey,
+                agent: agentKeypair.publicKey,
+                meter,
+                meterCounters: countersPdaFor(meter),
+                agentPolicy: policyPda,
+                authorization: authPdaFor(nonce, agentKeypair.publicKey, meter),
+                meterUsage: usagePdaFor(meter),
+                config: configPda,
+                escrow: null,
+                merchantTokenAccount: null,
+                tokenProgram: null,
+                receipt: null,
+                payer: null,
+                systemProgram: null,
+            })
+            .signers([agentKeypair])
+            .rpc({ commitment: "confirmed" });
+
+    const setPolicyFlags = async (flags: {
+        frozen?: boolean,
+        totalBudget?: anchor.BN,
+        enforceMeterAllowlist?: boolean,
+        alwaysRequireZk?: boolean,
+        metadataHash?: number[],
+        dailyLimit?: anchor.BN,
+        alertThresholdBps?: number,
+        restrictMint?: PublicKey | null,
+        policySalt?: number[] | null,
+        allowlistRoot?: number[],
+    } = {}) => {
+        await program.methods
+            .setPolicy(policyParams(flags))
+            .accounts({
+                owner: agentKeypair.publicKey,
+                agent: agentKeypair.publicKey,
+                agentPolicy: policyPda,
+                retiredAgent: retiredPdaFor(agentKeypair.publicKey),
+                payer: provider.wallet.publicKey,
+                systemProgram: SystemProgram.programId,
+            })
+            .signers([agentKeypair])
+            .rpc();
+    };
+
+    // =========================================================================
+    // TEST 92: Enforcement mode (runs first: strict can't be turned off)
+    // =========================================================================
+    describe("enforcement mode", () => {
+        const zkAgent = Keypair.generate();
+        // A valid proof, but not for these nonces: the pairing check fails
+        const proof = Buffer.concat([Buffer.from([1, 1]), Buffer.from(groth16Fixtures.payment_proof, "hex")]);
+
+        const setFixturePolicy = (alwaysRequireZk: boolean) =>
+            program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                    allowedCategory: groth16Fixtures.category,
+                    maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                    alwaysRequireZk,
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+
+        before(async () => {
+            await setFixturePolicy(false);
+        });
+
+        it("lets a failed proof through in permissive mode and reports it", async () => {
+            const nonce = fixtureNonce.addn(1);
+            const signature = await authorizeWithProof(zkAgent, nonce, proof, { commitment: "confirmed" });
+
+            const auth = await program.account.authorization.fetch(
+                authPdaFor(nonce, zkAgent.publicKey, fixtureMeterPda)
+            );
+            expect(auth.zkVerified).to.equal(false);
+            expect(auth.verificationMethod).to.equal(verificationPermissive);
+            expect(auth.verifierRef.toBase58()).to.equal(vkPdaFor(paymentPolicyCircuit).toBase58());
+
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const skipped = [...parser.parseLogs(tx!.meta!.logMessages!)].find(
+                (e) => e.name === "ProofCheckSkipped"
+            );
+            expect(skipped).to.not.be.undefined;
+            expect(skipped!.data.agent.toBase58()).to.equal(zkAgent.publicKey.toBase58());
+            expect(skipped!.data.circuitId).to.equal(paymentPolicyCircuit);
+            expect(tx!.meta!.logMessages!.join("\n")).to.include("PairingFailed");
+        });
+
+        it("doesn't let a failed proof through where a proof is required", async () => {
+            await setFixturePolicy(true);
+            try {
+                await expectError(
+                    () => authorizeWithProof(zkAgent, fixtureNonce.addn(3), proof),
+                    "ZkRequiredByPolicy"
+                );
+            } finally {
+                await setFixturePolicy(false);
+            }
+        });
+
+        it("rejects a failed proof once strict", async () => {
+            await updateConfig({ enforcementMode: strictEnforcement });
+
+            await expectError(() => authorizeWithProof(zkAgent, fixtureNonce.addn(2), proof), "InvalidProof");
+        });
+
+        it("can't be switched back to permissive", async () => {
+            await expectError(
+                () => updateConfig({ enforcementMode: permissiveEnforcement }),
+                "EnforcementModeLocked"
+            );
+
+            const config = await program.account.config.fetch(configPda);
+            expect(config.enforcementMode).to.equal(strictEnforcement);
+        });
+
+        it("rejects an unknown mode", async () => {
+            await expectError(() => updateConfig({ enforcementMode: 2 }), "InvalidEnforcementMode");
+        });
+    });
+
     // =========================================================================
     // TEST 1: set_policy creates AgentPolicy PDA correctly
     // =========================================================================
     describe("set_policy", () => {
         it("creates AgentPolicy PDA with correct values", async () => {
             await program.methods
-                .setPolicy(policyHash, allowedCategory, maxPerTx, false)
+                .setPolicy(policyParams())
                 .accounts({
+                    owner: agentKeypair.publicKey,
                     agent: agentKeypair.publicKey,
                     agentPolicy: policyPda,
+                    retiredAgent: retiredPdaFor(agentKeypair.publicKey),
                     payer: provider.wallet.publicKey,
                     systemProgram: SystemProgram.programId,
                 })
@@ -90,10 +659,12 @@ describe("agent_blink_pay", () => {
 
         it("can freeze an agent by setting frozen=true", async () => {
             await program.methods
-                .setPolicy(policyHash, allowedCategory, maxPerTx, true)
+                .setPolicy(policyParams({ frozen: true }))
                 .accounts({
+                    owner: agentKeypair.publicKey,
                     agent: agentKeypair.publicKey,
                     agentPolicy: policyPda,
+                    retiredAgent: retiredPdaFor(agentKeypair.publicKey),
                     payer: provider.wallet.publicKey,
                     systemProgram: SystemProgram.programId,
                 })
@@ -103,6 +674,37 @@ describe("agent_blink_pay", () => {
             const policy = await program.account.agentPolicy.fetch(policyPda);
             expect(policy.frozen).to.equal(true);
         });
+
+        it("rejects creating another agent's policy without its signature", async () => {
+            const victim = Keypair.generate();
+            const squatter = Keypair.generate();
+            const [victimPolicyPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("policy"),
+                    victim.publicKey.toBuffer(),
+                    new anchor.BN(policyId).toArrayLike(Buffer, 'le', 2)
+                ],
+                program.programId
+            );
+            try {
+                await program.methods
+                    .setPolicy(policyParams())
+                    .accounts({
+                        owner: squatter.publicKey,
+                        agent: victim.publicKey,
+                        agentPolicy: victimPolicyPda,
+                        retiredAgent: retiredPdaFor(victim.publicKey),
+                        payer: provider.wallet.publicKey,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .signers([squatter])
+                    .rpc();
+                expect.fail("Should have thrown AgentSignatureMissing error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AgentSignatureMissing");
+            }
+            expect(await provider.connection.getAccountInfo(victimPolicyPda)).to.equal(null);
+        });
     });
 
     // =========================================================================
@@ -111,11 +713,14 @@ describe("agent_blink_pay", () => {
     describe("create_meter", () => {
         it("creates Meter PDA with correct values", async () => {
             await program.methods
-                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false)
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
                 .accounts({
                     authority: provider.wallet.publicKey,
                     meterId: meterIdKeypair.publicKey,
                     meter: meterPda,
+                    meterCounters: countersPdaFor(meterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
                     systemProgram: SystemProgram.programId,
                 })
                 .rpc();
@@ -127,6 +732,45 @@ describe("agent_blink_pay", () => {
             expect(meter.category).to.equal(allowedCategory);
             expect(meter.requiresZk).to.equal(false);
         });
+
+        it("emits MeterCreated with the meter_id and wallet id", async () => {
+            const eventMeterId = Keypair.generate();
+            const [eventMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    eventMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            const signature = await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: eventMeterId.publicKey,
+                    meter: eventMeterPda,
+                    meterCounters: countersPdaFor(eventMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc({ commitment: "confirmed" });
+
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const events = [...parser.parseLogs(tx!.meta!.logMessages!)];
+            const created = events.find((e) => e.name === "MeterCreated");
+
+            expect(created).to.not.be.undefined;
+            expect(created!.data.meter.toBase58()).to.equal(eventMeterPda.toBase58());
+            expect(created!.data.meterId.toBase58()).to.equal(eventMeterId.publicKey.toBase58());
+            expect(created!.data.authority.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+            expect(created!.data.merchantWalletId).to.equal(merchantWalletId);
+            expect(created!.data.pricePerCall.toNumber()).to.equal(pricePerCall.toNumber());
+        });
     });
 
     // =========================================================================
@@ -136,10 +780,12 @@ describe("agent_blink_pay", () => {
         it("fails when agent policy is frozen", async () => {
             // Ensure policy is frozen
             await program.methods
-                .setPolicy(policyHash, allowedCategory, maxPerTx, true) // frozen = true
+                .setPolicy(policyParams({ frozen: true }))
                 .accounts({
+                    owner: agentKeypair.publicKey,
                     agent: agentKeypair.publicKey,
                     agentPolicy: policyPda,
+                    retiredAgent: retiredPdaFor(agentKeypair.publicKey),
                     payer: provider.wallet.publicKey,
                     systemProgram: SystemProgram.programId,
                 })
@@ -153,19 +799,36 @@ describe("agent_blink_pay", () => {
             try {
                 await program.methods
                     .authorizePaymentWithProof(
+                        policyId,
                         new anchor.BN(50000), // amount
+                        singleCall,
                         allowedCategory,
                         testNonce,
                         expiresAtSlot,
-                        [...proof]
+                        [...proof],
+                        noRequestId,
+                        expireBySlot,
+                        anyConsumer,
+                        noHashLock,
+                        noTip
                     )
                     .accounts({
                         agent: agentKeypair.publicKey,
                         agentPolicy: policyPda,
                         meter: meterPda,
+                        allowedMeter: null,
+                        deniedMeter: deniedMeterPda,
+                        meterAccess: null,
+                        meterUsage: usagePdaFor(),
                         authorization: authPda,
+                        config: configPda,
                         payer: provider.wallet.publicKey,
                         systemProgram: SystemProgram.programId,
+                        vault: null,
+                        escrow: null,
+                        mint: null,
+                        tokenProgram: null,
+                        associatedTokenProgram: null,
                     })
                     .signers([agentKeypair])
                     .rpc();
@@ -179,10 +842,12 @@ describe("agent_blink_pay", () => {
         it("fails when amount exceeds max_per_tx", async () => {
             // Unfreeze first
             await program.methods
-                .setPolicy(policyHash, allowedCategory, maxPerTx, false)
+                .setPolicy(policyParams())
                 .accounts({
+                    owner: agentKeypair.publicKey,
                     agent: agentKeypair.publicKey,
                     agentPolicy: policyPda,
+                    retiredAgent: retiredPdaFor(agentKeypair.publicKey),
                     payer: provider.wallet.publicKey,
                     systemProgram: SystemProgram.programId,
                 })
@@ -208,19 +873,36 @@ describe("agent_blink_pay", () => {
             try {
                 await program.methods
                     .authorizePaymentWithProof(
+                        policyId,
                         new anchor.BN(2000000), // 2 USDC > max 1 USDC
+                        singleCall,
                         allowedCategory,
                         badNonce,
                         expiresAtSlot,
-                        [...proof]
+                        [...proof],
+                        noRequestId,
+                        expireBySlot,
+                        anyConsumer,
+                        noHashLock,
+                        noTip
                     )
                     .accounts({
                         agent: agentKeypair.publicKey,
                         agentPolicy: policyPda,
                         meter: meterPda,
+                        allowedMeter: null,
+                        deniedMeter: deniedMeterPda,
+                        meterAccess: null,
+                        meterUsage: usagePdaFor(),
                         authorization: badAuthPda,
+                        config: configPda,
                         payer: provider.wallet.publicKey,
                         systemProgram: SystemProgram.programId,
+                        vault: null,
+                        escrow: null,
+                        mint: null,
+                        tokenProgram: null,
+                        associatedTokenProgram: null,
                     })
                     .signers([agentKeypair])
                     .rpc();
@@ -250,19 +932,36 @@ describe("agent_blink_pay", () => {
 
             await program.methods
                 .authorizePaymentWithProof(
+                    policyId,
                     new anchor.BN(50000), // Valid amount
+                    singleCall,
                     allowedCategory,
                     goodNonce,
                     expiresAtSlot,
-                    [...proof]
+                    [...proof],
+                    noRequestId,
+                    expireBySlot,
+                    anyConsumer,
+                    noHashLock,
+                    noTip
                 )
                 .accounts({
                     agent: agentKeypair.publicKey,
                     agentPolicy: policyPda,
                     meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedMeterPda,
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(),
                     authorization: goodAuthPda,
+                    config: configPda,
                     payer: provider.wallet.publicKey,
                     systemProgram: SystemProgram.programId,
+                    vault: null,
+                    escrow: null,
+                    mint: null,
+                    tokenProgram: null,
+                    associatedTokenProgram: null,
                 })
                 .signers([agentKeypair])
                 .rpc();
@@ -301,19 +1000,36 @@ describe("agent_blink_pay", () => {
 
             await program.methods
                 .authorizePaymentWithProof(
+                    policyId,
                     new anchor.BN(50000),
+                    singleCall,
                     allowedCategory,
                     paymentNonce,
                     expiresAtSlot,
-                    [...proof]
+                    [...proof],
+                    noRequestId,
+                    expireBySlot,
+                    anyConsumer,
+                    noHashLock,
+                    noTip
                 )
                 .accounts({
                     agent: agentKeypair.publicKey,
                     agentPolicy: policyPda,
                     meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedMeterPda,
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(),
                     authorization: paymentAuthPda,
+                    config: configPda,
                     payer: provider.wallet.publicKey,
                     systemProgram: SystemProgram.programId,
+                    vault: null,
+                    escrow: null,
+                    mint: null,
+                    tokenProgram: null,
+                    associatedTokenProgram: null,
                 })
                 .signers([agentKeypair])
                 .rpc();
@@ -331,11 +1047,22 @@ describe("agent_blink_pay", () => {
             });
 
             await program.methods
-                .recordMeterPayment(paymentNonce)
+                .recordMeterPayment(paymentNonce, fullAmount, consumeAll, noExternalId, noPreimage, noReceipt)
                 .accounts({
+                    recorder: agentKeypair.publicKey,
                     agent: agentKeypair.publicKey,
                     meter: meterPda,
+                    meterCounters: countersPdaFor(meterPda),
+                    agentPolicy: policyPda,
                     authorization: paymentAuthPda,
+                    meterUsage: usagePdaFor(),
+                    config: configPda,
+                    escrow: null,
+                    merchantTokenAccount: null,
+                    tokenProgram: null,
+                    receipt: null,
+                    payer: null,
+                    systemProgram: null,
                 })
                 .signers([agentKeypair])
                 .rpc();
@@ -354,11 +1081,22 @@ describe("agent_blink_pay", () => {
             // Using the same auth from above (already used)
             try {
                 await program.methods
-                    .recordMeterPayment(paymentNonce)
+                    .recordMeterPayment(paymentNonce, fullAmount, consumeAll, noExternalId, noPreimage, noReceipt)
                     .accounts({
+                        recorder: agentKeypair.publicKey,
                         agent: agentKeypair.publicKey,
                         meter: meterPda,
+                        meterCounters: countersPdaFor(meterPda),
+                        agentPolicy: policyPda,
                         authorization: paymentAuthPda,
+                        meterUsage: usagePdaFor(),
+                        config: configPda,
+                        escrow: null,
+                        merchantTokenAccount: null,
+                        tokenProgram: null,
+                        receipt: null,
+                        payer: null,
+                        systemProgram: null,
                     })
                     .signers([agentKeypair])
                     .rpc();
@@ -388,39 +1126,67 @@ describe("agent_blink_pay", () => {
             );
 
             const currentSlot = await provider.connection.getSlot();
-            // Set expires_at_slot to current slot (will be expired by the time we record)
-            const expiresAtSlot = new anchor.BN(currentSlot);
+            // Expire a couple of slots out (authorize needs a future slot)
+            const expiresAtSlot = new anchor.BN(currentSlot + 2);
             const proof = Buffer.alloc(64);
 
             await program.methods
                 .authorizePaymentWithProof(
+                    policyId,
                     new anchor.BN(50000),
+                    singleCall,
                     allowedCategory,
                     expiredNonce,
                     expiresAtSlot,
-                    [...proof]
+                    [...proof],
+                    noRequestId,
+                    expireBySlot,
+                    anyConsumer,
+                    noHashLock,
+                    noTip
                 )
                 .accounts({
                     agent: agentKeypair.publicKey,
                     agentPolicy: policyPda,
                     meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedMeterPda,
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(),
                     authorization: expiredAuthPda,
+                    config: configPda,
                     payer: provider.wallet.publicKey,
                     systemProgram: SystemProgram.programId,
+                    vault: null,
+                    escrow: null,
+                    mint: null,
+                    tokenProgram: null,
+                    associatedTokenProgram: null,
                 })
                 .signers([agentKeypair])
                 .rpc();
 
-            // Wait a bit to ensure slot advances
-            await new Promise(resolve => setTimeout(resolve, 1000));
+            // Wait a bit to ensure the slot advances past the expiry
+            await new Promise(resolve => setTimeout(resolve, 3000));
 
             try {
                 await program.methods
-                    .recordMeterPayment(expiredNonce)
+                    .recordMeterPayment(expiredNonce, fullAmount, consumeAll, noExternalId, noPreimage, noReceipt)
                     .accounts({
+                        recorder: agentKeypair.publicKey,
                         agent: agentKeypair.publicKey,
                         meter: meterPda,
+                        meterCounters: countersPdaFor(meterPda),
+                        agentPolicy: policyPda,
                         authorization: expiredAuthPda,
+                        meterUsage: usagePdaFor(),
+                        config: configPda,
+                        escrow: null,
+                        merchantTokenAccount: null,
+                        tokenProgram: null,
+                        receipt: null,
+                        payer: null,
+                        systemProgram: null,
                     })
                     .signers([agentKeypair])
                     .rpc();
@@ -431,4 +1197,8331 @@ describe("agent_blink_pay", () => {
             }
         });
     });
+
+    // =========================================================================
+    // TEST 6: total_budget auto-freezes the policy once spent
+    // =========================================================================
+    describe("total_budget", () => {
+        const setBudget = async (frozen: boolean, budget: anchor.BN) => {
+            await program.methods
+                .setPolicy(policyParams({ frozen, totalBudget: budget }))
+                .accounts({
+                    owner: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    retiredAgent: retiredPdaFor(agentKeypair.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        after(async () => {
+            await setBudget(false, unlimitedBudget);
+        });
+
+        it("freezes when lifetime_spent reaches total_budget and unfreezes on extend_budget", async () => {
+            const current = await program.account.agentPolicy.fetch(policyPda);
+            await setBudget(false, current.lifetimeSpent.add(pricePerCall));
+
+            const nonce = new anchor.BN(Date.now() + 300);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+
+            let policy = await program.account.agentPolicy.fetch(policyPda);
+            expect(policy.frozen).to.equal(true);
+            expect(policy.frozenReason).to.equal(2); // BUDGET_EXHAUSTED
+
+            await program.methods
+                .extendBudget(policyId, pricePerCall)
+                .accounts({
+                    owner: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            policy = await program.account.agentPolicy.fetch(policyPda);
+            expect(policy.frozen).to.equal(false);
+            expect(policy.frozenReason).to.equal(0);
+        });
+
+        it("does not clear a manual freeze on extend_budget", async () => {
+            const current = await program.account.agentPolicy.fetch(policyPda);
+            await setBudget(true, current.lifetimeSpent.add(pricePerCall));
+
+            await program.methods
+                .extendBudget(policyId, pricePerCall)
+                .accounts({
+                    owner: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            const policy = await program.account.agentPolicy.fetch(policyPda);
+            expect(policy.frozen).to.equal(true);
+            expect(policy.frozenReason).to.equal(1); // MANUAL
+        });
+    });
+
+    // =========================================================================
+    // TEST 7: pause_policy blocks payments until the pause lapses
+    // =========================================================================
+    describe("pause_policy", () => {
+        const guardian = Keypair.generate();
+
+        it("rejects authorize and record while paused, then lapses on its own", async () => {
+            await program.methods
+                .setGuardian(policyId, guardian.publicKey)
+                .accounts({
+                    owner: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            // Issue an authorization before the pause
+            const inFlightNonce = new anchor.BN(Date.now() + 400);
+            await authorize(inFlightNonce, pricePerCall);
+
+            await program.methods
+                .pausePolicy(policyId, new anchor.BN(5))
+                .accounts({
+                    authority: guardian.publicKey,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                })
+                .signers([guardian])
+                .rpc();
+
+            try {
+                await authorize(new anchor.BN(Date.now() + 401), pricePerCall);
+                expect.fail("Should have thrown PolicyPaused error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("PolicyPaused");
+            }
+
+            try {
+                await record(inFlightNonce);
+                expect.fail("Should have thrown PolicyPaused error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("PolicyPaused");
+            }
+
+            // Wait for the pause to lapse
+            await new Promise(resolve => setTimeout(resolve, 4000));
+
+            await record(inFlightNonce);
+            const auth = await program.account.authorization.fetch(authPdaFor(inFlightNonce));
+            expect(auth.used).to.equal(true);
+        });
+
+        it("rejects pause from an unrelated key", async () => {
+            const stranger = Keypair.generate();
+            try {
+                await program.methods
+                    .pausePolicy(policyId, new anchor.BN(5))
+                    .accounts({
+                        authority: stranger.publicKey,
+                        agent: agentKeypair.publicKey,
+                        agentPolicy: policyPda,
+                    })
+                    .signers([stranger])
+                    .rpc();
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 8: watcher-reported failures trip the circuit breaker
+    // =========================================================================
+    describe("circuit breaker", () => {
+        const reportFailure = async (watcher: Keypair) => {
+            await program.methods
+                .reportAuthFailure(policyId)
+                .accounts({
+                    watcher: watcher.publicKey,
+                    config: configPda,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                })
+                .signers([watcher])
+                .rpc();
+        };
+
+        it("only lets the upgrade authority initialize the Config", async () => {
+            const impostor = Keypair.generate();
+            const sig = await provider.connection.requestAirdrop(impostor.publicKey, anchor.web3.LAMPORTS_PER_SOL);
+            await provider.connection.confirmTransaction(sig);
+
+            try {
+                await program.methods
+                    .initializeConfig({
+                        watchers: [
+                            watcherKeypair.publicKey,
+                            PublicKey.default,
+                            PublicKey.default,
+                            PublicKey.default,
+                        ],
+                        autoFreezeThreshold: 3,
+                    })
+                    .accounts({
+                        admin: impostor.publicKey,
+                        program: program.programId,
+                        programData: programDataPda,
+                        config: configPda,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .signers([impostor])
+                    .rpc();
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+
+        it("rejects reports from keys that are not watchers", async () => {
+            try {
+                await reportFailure(Keypair.generate());
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+
+        it("freezes the policy at the threshold and resets on unfreeze", async () => {
+            for (let i = 0; i < 3; i++) {
+                await reportFailure(watcherKeypair);
+            }
+
+            let policy = await program.account.agentPolicy.fetch(policyPda);
+            expect(policy.failedAuthAttempts).to.equal(3);
+            expect(policy.frozen).to.equal(true);
+            expect(policy.frozenReason).to.equal(3); // CIRCUIT_BREAKER
+
+            try {
+                await authorize(new anchor.BN(Date.now() + 500), pricePerCall);
+                expect.fail("Should have thrown PolicyFrozen error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("PolicyFrozen");
+            }
+
+            await program.methods
+                .setPolicy(policyParams())
+                .accounts({
+                    owner: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    retiredAgent: retiredPdaFor(agentKeypair.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            policy = await program.account.agentPolicy.fetch(policyPda);
+            expect(policy.frozen).to.equal(false);
+            expect(policy.failedAuthAttempts).to.equal(0);
+        });
+    });
+
+    // =========================================================================
+    // TEST 9: set_policies_batch provisions several agents atomically
+    // =========================================================================
+    describe("set_policies_batch", () => {
+        const orgAdmin = Keypair.generate();
+        const agents = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+        const policyPdaFor = (agent: PublicKey) =>
+            PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("policy"),
+                    agent.toBuffer(),
+                    new anchor.BN(policyId).toArrayLike(Buffer, 'le', 2)
+                ],
+                program.programId
+            )[0];
+        const paramsFor = (frozen: boolean) => policyParams({ frozen });
+        // Agents sign when their policy is created
+        const remainingFor = (keys: PublicKey[], agentsSign = false) =>
+            keys.flatMap(agent => [
+                { pubkey: agent, isSigner: agentsSign, isWritable: false },
+                { pubkey: policyPdaFor(agent), isSigner: false, isWritable: true },
+                { pubkey: retiredPdaFor(agent), isSigner: false, isWritable: false },
+            ]);
+
+        it("rejects creating a policy the agent hasn't signed for", async () => {
+            const keys = agents.map(a => a.publicKey);
+            try {
+                await program.methods
+                    .setPoliciesBatch(keys.map(() => paramsFor(false)))
+                    .accounts({
+                        owner: orgAdmin.publicKey,
+                        payer: provider.wallet.publicKey,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .remainingAccounts(remainingFor(keys))
+                    .signers([orgAdmin])
+                    .rpc();
+                expect.fail("Should have thrown AgentSignatureMissing error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AgentSignatureMissing");
+            }
+        });
+
+        it("creates every policy in the batch owned by the admin", async () => {
+            const keys = agents.map(a => a.publicKey);
+            await program.methods
+                .setPoliciesBatch(keys.map(() => paramsFor(false)))
+                .accounts({
+                    owner: orgAdmin.publicKey,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .remainingAccounts(remainingFor(keys, true))
+                .signers([orgAdmin, ...agents])
+                .rpc();
+
+            for (const agent of keys) {
+                const policy = await program.account.agentPolicy.fetch(policyPdaFor(agent));
+                expect(policy.agentPubkey.toBase58()).to.equal(agent.toBase58());
+                expect(policy.owner.toBase58()).to.equal(orgAdmin.publicKey.toBase58());
+            }
+        });
+
+        it("aborts the whole batch when one entry is not owned by the signer", async () => {
+            // agentKeypair's policy is owned by the agent itself
+            const keys = [agents[0].publicKey, agentKeypair.publicKey];
+            try {
+                await program.methods
+                    .setPoliciesBatch(keys.map(() => paramsFor(true)))
+                    .accounts({
+                        owner: orgAdmin.publicKey,
+                        payer: provider.wallet.publicKey,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .remainingAccounts(remainingFor(keys))
+                    .signers([orgAdmin])
+                    .rpc();
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+
+            const policy = await program.account.agentPolicy.fetch(policyPdaFor(agents[0].publicKey));
+            expect(policy.frozen).to.equal(false);
+        });
+    });
+
+    // =========================================================================
+    // TEST 10: enforce_meter_allowlist requires an AllowedMeter record
+    // =========================================================================
+    describe("meter allowlist", () => {
+        let allowedMeterPda: PublicKey;
+
+        const setAllowlistMode = async (enforce: boolean) => {
+            await program.methods
+                .setPolicy(policyParams({ enforceMeterAllowlist: enforce }))
+                .accounts({
+                    owner: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    retiredAgent: retiredPdaFor(agentKeypair.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        before(async () => {
+            [allowedMeterPda] = PublicKey.findProgramAddressSync(
+                [Buffer.from("allowed"), policyPda.toBuffer(), meterPda.toBuffer()],
+                program.programId
+            );
+            await setAllowlistMode(true);
+        });
+
+        after(async () => {
+            await setAllowlistMode(false);
+        });
+
+        it("rejects a meter without an AllowedMeter record", async () => {
+            try {
+                await authorize(new anchor.BN(Date.now() + 600), pricePerCall);
+                expect.fail("Should have thrown MeterNotAllowed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("MeterNotAllowed");
+            }
+        });
+
+        it("accepts the meter once allowed", async () => {
+            await program.methods
+                .allowMeter(policyId)
+                .accounts({
+                    owner: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: allowedMeterPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            const nonce = new anchor.BN(Date.now() + 601);
+            const currentSlot = await provider.connection.getSlot();
+            await program.methods
+                .authorizePaymentWithProof(
+                    policyId,
+                    pricePerCall,
+                    singleCall,
+                    allowedCategory,
+                    nonce,
+                    new anchor.BN(currentSlot + 100),
+                    [...Buffer.alloc(64)],
+                    noRequestId,
+                    expireBySlot,
+                    anyConsumer,
+                    noHashLock,
+                    noTip
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: allowedMeterPda,
+                    deniedMeter: deniedMeterPda,
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(),
+                    authorization: authPdaFor(nonce),
+                    config: configPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    nullifier: await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, pricePerCall, policyPda),
+                    vault: null,
+                    escrow: null,
+                    mint: null,
+                    tokenProgram: null,
+                    associatedTokenProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.amount.toNumber()).to.equal(pricePerCall.toNumber());
+        });
+
+        it("rejects the meter again after disallow_meter", async () => {
+            await program.methods
+                .disallowMeter(policyId)
+                .accounts({
+                    owner: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: allowedMeterPda,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            try {
+                await authorize(new anchor.BN(Date.now() + 602), pricePerCall);
+                expect.fail("Should have thrown MeterNotAllowed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("MeterNotAllowed");
+            }
+        });
+
+        it("doesn't let the owner of another policy lift the ban", async () => {
+            // A second policy on the same agent, owned by someone else
+            const otherOwner = Keypair.generate();
+            const otherPolicyId = 1;
+            const [otherPolicyPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("policy"),
+                    agentKeypair.publicKey.toBuffer(),
+                    new anchor.BN(otherPolicyId).toArrayLike(Buffer, 'le', 2)
+                ],
+                program.programId
+            );
+            await program.methods
+                .setPolicy(policyParams({ policyId: otherPolicyId }))
+                .accounts({
+                    owner: otherOwner.publicKey,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: otherPolicyPda,
+                    retiredAgent: retiredPdaFor(agentKeypair.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .remainingAccounts([{ pubkey: agentKeypair.publicKey, isSigner: true, isWritable: false }])
+                .signers([otherOwner, agentKeypair])
+                .rpc();
+
+            await program.methods
+                .denyMeter(policyId)
+                .accounts({
+                    ...denyAccounts(),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            try {
+                await program.methods
+                    .undenyMeter(otherPolicyId)
+                    .accounts({
+                        ...denyAccounts(),
+                        owner: otherOwner.publicKey,
+                        agentPolicy: otherPolicyPda,
+                    })
+                    .signers([otherOwner])
+                    .rpc();
+                expect.fail("Should have thrown ConstraintSeeds error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("ConstraintSeeds");
+            }
+
+            await program.methods
+                .undenyMeter(policyId)
+                .accounts(denyAccounts())
+                .signers([agentKeypair])
+                .rpc();
+        });
+    });
+
+    // =========================================================================
+    // TEST 11: DeniedMeter records block payments to a meter
+    // =========================================================================
+    describe("meter denylist", () => {
+        const denyAccounts = () => ({
+            owner: agentKeypair.publicKey,
+            agent: agentKeypair.publicKey,
+            agentPolicy: policyPda,
+            meter: meterPda,
+            deniedMeter: deniedMeterPda,
+        });
+
+        it("rejects a denied meter and accepts it again after undeny", async () => {
+            await program.methods
+                .denyMeter(policyId)
+                .accounts({
+                    ...denyAccounts(),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            try {
+                await authorize(new anchor.BN(Date.now() + 700), pricePerCall);
+                expect.fail("Should have thrown MeterDenied error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("MeterDenied");
+            }
+
+            await program.methods
+                .undenyMeter(policyId)
+                .accounts(denyAccounts())
+                .signers([agentKeypair])
+                .rpc();
+
+            const nonce = new anchor.BN(Date.now() + 701);
+            await authorize(nonce, pricePerCall);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.used).to.equal(false);
+        });
+
+        it("rejects a forged denial account with the wrong seeds", async () => {
+            const nonce = new anchor.BN(Date.now() + 702);
+            const currentSlot = await provider.connection.getSlot();
+            try {
+                await program.methods
+                    .authorizePaymentWithProof(
+                        policyId,
+                        pricePerCall,
+                        singleCall,
+                        allowedCategory,
+                        nonce,
+                        new anchor.BN(currentSlot + 100),
+                        [...Buffer.alloc(64)],
+                        noRequestId,
+                        expireBySlot,
+                        anyConsumer,
+                        noHashLock,
+                        noTip
+                    )
+                    .accounts({
+                        agent: agentKeypair.publicKey,
+                        agentPolicy: policyPda,
+                        meter: meterPda,
+                        allowedMeter: null,
+                        // Empty account that isn't the denial PDA for this pair
+                        deniedMeter: Keypair.generate().publicKey,
+                        meterUsage: usagePdaFor(),
+                        authorization: authPdaFor(nonce),
+                        config: configPda,
+                        payer: provider.wallet.publicKey,
+                        systemProgram: SystemProgram.programId,
+                        verifierProgram: program.programId,
+                        verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                        nullifier: await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, pricePerCall, policyPda),
+                        vault: null,
+                        escrow: null,
+                        mint: null,
+                        tokenProgram: null,
+                        associatedTokenProgram: null,
+                    })
+                    .signers([agentKeypair])
+                    .rpc();
+                expect.fail("Should have thrown ConstraintSeeds error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("ConstraintSeeds");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 12: two-step policy ownership transfer
+    // =========================================================================
+    describe("policy ownership transfer", () => {
+        const agent = Keypair.generate();
+        const newOwner = Keypair.generate();
+        let agentPolicyPda: PublicKey;
+
+        const setPolicyAs = async (owner: Keypair) => {
+            await program.methods
+                .setPolicy(policyParams())
+                .accounts({
+                    owner: owner.publicKey,
+                    agent: agent.publicKey,
+                    agentPolicy: agentPolicyPda,
+                    retiredAgent: retiredPdaFor(agent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([owner])
+                .rpc();
+        };
+
+        before(async () => {
+            [agentPolicyPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("policy"),
+                    agent.publicKey.toBuffer(),
+                    new anchor.BN(policyId).toArrayLike(Buffer, 'le', 2)
+                ],
+                program.programId
+            );
+            await setPolicyAs(agent);
+        });
+
+        it("only the nominated key can accept, and the old owner loses control", async () => {
+            await program.methods
+                .transferPolicyOwnership(policyId, newOwner.publicKey)
+                .accounts({
+                    owner: agent.publicKey,
+                    agent: agent.publicKey,
+                    agentPolicy: agentPolicyPda,
+                })
+                .signers([agent])
+                .rpc();
+
+            let policy = await program.account.agentPolicy.fetch(agentPolicyPda);
+            expect(policy.pendingOwner.toBase58()).to.equal(newOwner.publicKey.toBase58());
+
+            const impostor = Keypair.generate();
+            try {
+                await program.methods
+                    .acceptPolicyOwnership(policyId)
+                    .accounts({
+                        newOwner: impostor.publicKey,
+                        agent: agent.publicKey,
+                        agentPolicy: agentPolicyPda,
+                    })
+                    .signers([impostor])
+                    .rpc();
+                expect.fail("Should have thrown NotPendingOwner error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("NotPendingOwner");
+            }
+
+            await program.methods
+                .acceptPolicyOwnership(policyId)
+                .accounts({
+                    newOwner: newOwner.publicKey,
+                    agent: agent.publicKey,
+                    agentPolicy: agentPolicyPda,
+                })
+                .signers([newOwner])
+                .rpc();
+
+            policy = await program.account.agentPolicy.fetch(agentPolicyPda);
+            expect(policy.owner.toBase58()).to.equal(newOwner.publicKey.toBase58());
+            expect(policy.pendingOwner).to.equal(null);
+
+            try {
+                await setPolicyAs(agent);
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+            await setPolicyAs(newOwner);
+        });
+    });
+
+    // =========================================================================
+    // TEST 13: always_require_zk vs meter.requires_zk
+    // =========================================================================
+    describe("always_require_zk", () => {
+        const zkMeterId = Keypair.generate();
+        let zkMeterPda: PublicKey;
+
+        before(async () => {
+            [zkMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    zkMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, true, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: zkMeterId.publicKey,
+                    meter: zkMeterPda,
+                    meterCounters: countersPdaFor(zkMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        after(async () => {
+            await setPolicyFlags();
+        });
+
+        for (const policyZk of [false, true]) {
+            for (const meterZk of [false, true]) {
+                it(`verifies the proof with policy=${policyZk}, meter=${meterZk}`, async () => {
+                    await setPolicyFlags({ alwaysRequireZk: policyZk });
+                    const meter = meterZk ? zkMeterPda : meterPda;
+                    const nonce = new anchor.BN(Date.now() + 800);
+
+                    await authorize(nonce, pricePerCall, meter);
+
+                    const auth = await program.account.authorization.fetch(
+                        authPdaFor(nonce, agentKeypair.publicKey, meter)
+                    );
+                    expect(auth.amount.toNumber()).to.equal(pricePerCall.toNumber());
+                });
+            }
+        }
+    });
+
+    // =========================================================================
+    // TEST 14: metadata_hash is stored independently of the ZK policy_hash
+    // =========================================================================
+    describe("metadata_hash", () => {
+        after(async () => {
+            await setPolicyFlags();
+        });
+
+        it("stores the document hash without affecting authorization", async () => {
+            const metadataHash = Array.from(
+                crypto.createHash('sha256').update('may buy weather data up to $10/day').digest()
+            );
+            await setPolicyFlags({ metadataHash });
+
+            const policy = await program.account.agentPolicy.fetch(policyPda);
+            expect(policy.metadataHash).to.deep.equal(metadataHash);
+            expect(policy.policyHash).to.deep.equal(policyHash);
+
+            const nonce = new anchor.BN(Date.now() + 900);
+            await authorize(nonce, pricePerCall);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.used).to.equal(false);
+        });
+    });
+
+    // =========================================================================
+    // TEST 15: rotate_agent_key moves the policy and strands old authorizations
+    // =========================================================================
+    describe("rotate_agent_key", () => {
+        const oldAgent = Keypair.generate();
+        const newAgent = Keypair.generate();
+
+        const policyPdaFor = (agent: PublicKey) =>
+            PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("policy"),
+                    agent.toBuffer(),
+                    new anchor.BN(policyId).toArrayLike(Buffer, 'le', 2)
+                ],
+                program.programId
+            )[0];
+
+        it("copies policy state to the new key and closes the old PDA", async () => {
+            const oldPolicyPda = policyPdaFor(oldAgent.publicKey);
+            const newPolicyPda = policyPdaFor(newAgent.publicKey);
+
+            await program.methods
+                .setPolicy(policyParams({ totalBudget: new anchor.BN(5000000) }))
+                .accounts({
+                    owner: oldAgent.publicKey,
+                    agent: oldAgent.publicKey,
+                    agentPolicy: oldPolicyPda,
+                    retiredAgent: retiredPdaFor(oldAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([oldAgent])
+                .rpc();
+
+            const spentNonce = new anchor.BN(Date.now() + 1000);
+            const pendingNonce = new anchor.BN(Date.now() + 1001);
+            const currentSlot = await provider.connection.getSlot();
+            for (const nonce of [spentNonce, pendingNonce]) {
+                await program.methods
+                    .authorizePaymentWithProof(
+                        policyId,
+                        pricePerCall,
+                        singleCall,
+                        allowedCategory,
+                        nonce,
+                        new anchor.BN(currentSlot + 100),
+                        [...Buffer.alloc(64)],
+                        noRequestId,
+                        expireBySlot,
+                        anyConsumer,
+                        noHashLock,
+                        noTip
+                    )
+                    .accounts({
+                        agent: oldAgent.publicKey,
+                        agentPolicy: oldPolicyPda,
+                        meter: meterPda,
+                        allowedMeter: null,
+                        deniedMeter: deniedPdaFor(meterPda, oldAgent.publicKey),
+                        meterAccess: null,
+                        meterUsage: usagePdaFor(meterPda, oldAgent.publicKey),
+                        authorization: authPdaFor(nonce, oldAgent.publicKey),
+                        config: configPda,
+                        payer: provider.wallet.publicKey,
+                        systemProgram: SystemProgram.programId,
+                        verifierProgram: program.programId,
+                        verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                        nullifier: await nullifierPdaFor(oldAgent.publicKey, meterPda, nonce, pricePerCall, oldPolicyPda),
+                        vault: null,
+                        escrow: null,
+                        mint: null,
+                        tokenProgram: null,
+                        associatedTokenProgram: null,
+                    })
+                    .signers([oldAgent])
+                    .rpc();
+            }
+
+            const recordAsOldAgent = (nonce: anchor.BN) =>
+                program.methods
+                    .recordMeterPayment(nonce, fullAmount, consumeAll, noExternalId, noPreimage, noReceipt)
+                    .accounts({
+                        recorder: oldAgent.publicKey,
+                        agent: oldAgent.publicKey,
+                        meter: meterPda,
+                        meterCounters: countersPdaFor(meterPda),
+                        agentPolicy: oldPolicyPda,
+                        authorization: authPdaFor(nonce, oldAgent.publicKey),
+                        meterUsage: usagePdaFor(meterPda, oldAgent.publicKey),
+                        config: configPda,
+                        escrow: null,
+                        merchantTokenAccount: null,
+                        tokenProgram: null,
+                        receipt: null,
+                        payer: null,
+                        systemProgram: null,
+                    })
+                    .signers([oldAgent])
+                    .rpc();
+            await recordAsOldAgent(spentNonce);
+
+            const oldPolicy = await program.account.agentPolicy.fetch(oldPolicyPda);
+            expect(oldPolicy.rentPayer.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+
+            const rotate = (rentPayer: PublicKey, newAgentKey: Keypair = newAgent) =>
+                program.methods
+                    .rotateAgentKey(policyId, newAgent.publicKey)
+                    .accounts({
+                        owner: oldAgent.publicKey,
+                        agent: oldAgent.publicKey,
+                        agentPolicy: oldPolicyPda,
+                        newAgentPolicy: newPolicyPda,
+                        newAgentKey: newAgentKey.publicKey,
+                        retiredAgent: retiredPdaFor(oldAgent.publicKey),
+                        rentPayer,
+                        payer: provider.wallet.publicKey,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .signers(newAgentKey === oldAgent ? [oldAgent] : [oldAgent, newAgentKey])
+                    .rpc();
+
+            try {
+                await rotate(Keypair.generate().publicKey);
+                expect.fail("Should have thrown RentPayerMismatch error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("RentPayerMismatch");
+            }
+            try {
+                // The old key signing in the new key's place doesn't count
+                await rotate(provider.wallet.publicKey, oldAgent);
+                expect.fail("Should have thrown AgentSignatureMissing error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AgentSignatureMissing");
+            }
+            await rotate(provider.wallet.publicKey);
+
+            const policy = await program.account.agentPolicy.fetch(newPolicyPda);
+            expect(policy.agentPubkey.toBase58()).to.equal(newAgent.publicKey.toBase58());
+            expect(policy.lifetimeSpent.toNumber()).to.equal(pricePerCall.toNumber());
+            expect(policy.totalBudget.toNumber()).to.equal(5000000);
+            expect(await provider.connection.getAccountInfo(oldPolicyPda)).to.equal(null);
+
+            try {
+                await recordAsOldAgent(pendingNonce);
+                expect.fail("Old authorization should be unusable after rotation");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AccountNotInitialized");
+            }
+
+            const retired = await program.account.retiredAgent.fetch(retiredPdaFor(oldAgent.publicKey));
+            expect(retired.agent.toBase58()).to.equal(oldAgent.publicKey.toBase58());
+            expect(retired.newAgent.toBase58()).to.equal(newAgent.publicKey.toBase58());
+        });
+
+        it("rejects re-creating a policy for the retired key", async () => {
+            try {
+                await program.methods
+                    .setPolicy(policyParams())
+                    .accounts({
+                        owner: oldAgent.publicKey,
+                        agent: oldAgent.publicKey,
+                        agentPolicy: policyPdaFor(oldAgent.publicKey),
+                        retiredAgent: retiredPdaFor(oldAgent.publicKey),
+                        payer: provider.wallet.publicKey,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .signers([oldAgent])
+                    .rpc();
+                expect.fail("Should have thrown AgentKeyRetired error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AgentKeyRetired");
+            }
+            expect(await provider.connection.getAccountInfo(policyPdaFor(oldAgent.publicKey))).to.equal(null);
+        });
+    });
+
+    // =========================================================================
+    // TEST 16: daily limit and reset_spend_window
+    // =========================================================================
+    describe("daily limit", () => {
+        after(async () => {
+            await setPolicyFlags();
+        });
+
+        const resetWindow = (signers: Keypair[], admin: PublicKey) =>
+            program.methods
+                .resetSpendWindow(policyId)
+                .accounts({
+                    owner: agentKeypair.publicKey,
+                    admin,
+                    config: configPda,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                })
+                .signers(signers)
+                .rpc();
+
+        it("rejects spend past the daily limit until the window is reset", async () => {
+            // Start from a clean window so earlier tests' spend doesn't count
+            await resetWindow([agentKeypair], provider.wallet.publicKey);
+            await setPolicyFlags({ dailyLimit: pricePerCall });
+
+            const nonce = new anchor.BN(Date.now() + 1100);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+
+            let policy = await program.account.agentPolicy.fetch(policyPda);
+            expect(policy.spentToday.toNumber()).to.equal(pricePerCall.toNumber());
+
+            try {
+                await authorize(new anchor.BN(Date.now() + 1101), pricePerCall);
+                expect.fail("Should have thrown DailyLimitExceeded error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("DailyLimitExceeded");
+            }
+
+            await resetWindow([agentKeypair], provider.wallet.publicKey);
+
+            policy = await program.account.agentPolicy.fetch(policyPda);
+            expect(policy.spentToday.toNumber()).to.equal(0);
+            expect(policy.dailyLimit.toNumber()).to.equal(pricePerCall.toNumber());
+            expect(policy.frozen).to.equal(false);
+
+            await authorize(new anchor.BN(Date.now() + 1102), pricePerCall);
+        });
+
+        it("rejects a reset without the config admin", async () => {
+            const notAdmin = Keypair.generate();
+            try {
+                await resetWindow([agentKeypair, notAdmin], notAdmin.publicKey);
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 17: alert_threshold_bps early warning
+    // =========================================================================
+    describe("spend alert threshold", () => {
+        after(async () => {
+            await setPolicyFlags();
+        });
+
+        it("flags the daily window once spend passes the threshold", async () => {
+            await program.methods
+                .resetSpendWindow(policyId)
+                .accounts({
+                    owner: agentKeypair.publicKey,
+                    admin: provider.wallet.publicKey,
+                    config: configPda,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                })
+                .signers([agentKeypair])
+                .rpc();
+            await setPolicyFlags({
+                dailyLimit: pricePerCall.muln(4),
+                alertThresholdBps: 5000,
+            });
+
+            const dailyAlerts: number[] = [];
+            const listener = program.addEventListener("SpendThresholdCrossed", (event) => {
+                if (event.limitType === 0) {
+                    dailyAlerts.push(event.spent.toNumber());
+                }
+            });
+
+            const pay = async (offset: number) => {
+                const nonce = new anchor.BN(Date.now() + offset);
+                await authorize(nonce, pricePerCall);
+                await record(nonce);
+                return program.account.agentPolicy.fetch(policyPda);
+            };
+
+            let policy = await pay(1200);
+            expect(policy.alertEmitted).to.equal(false);
+
+            policy = await pay(1201);
+            expect(policy.alertEmitted).to.equal(true);
+
+            policy = await pay(1202);
+            expect(policy.alertEmitted).to.equal(true);
+
+            await new Promise(resolve => setTimeout(resolve, 1000));
+            program.removeEventListener(listener);
+
+            // Event listener may not fire in test env; if it does, only once
+            expect(dailyAlerts.length).to.be.at.most(1);
+        });
+
+        it("rejects thresholds above 100%", async () => {
+            try {
+                await setPolicyFlags({ alertThresholdBps: 10001 });
+                expect.fail("Should have thrown InvalidAlertThreshold error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidAlertThreshold");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 18: a PDA of another program can own a policy via CPI
+    // =========================================================================
+    describe("PDA policy owner", () => {
+        const cpiCaller = anchor.workspace.CpiCaller as Program<CpiCaller>;
+        const agent = Keypair.generate();
+        let ownerPda: PublicKey;
+        let agentPolicyPda: PublicKey;
+
+        const setPolicyViaCpi = async (frozen: boolean) => {
+            await cpiCaller.methods
+                .setPolicyAsPda(policyId, policyHash, allowedCategory, maxPerTx, frozen)
+                .accounts({
+                    ownerPda,
+                    agent: agent.publicKey,
+                    agentPolicy: agentPolicyPda,
+                    retiredAgent: retiredPdaFor(agent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    agentBlinkPayProgram: program.programId,
+                })
+                .signers([agent])
+                .rpc();
+        };
+
+        before(async () => {
+            [ownerPda] = PublicKey.findProgramAddressSync(
+                [Buffer.from("owner")],
+                cpiCaller.programId
+            );
+            [agentPolicyPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("policy"),
+                    agent.publicKey.toBuffer(),
+                    new anchor.BN(policyId).toArrayLike(Buffer, 'le', 2)
+                ],
+                program.programId
+            );
+        });
+
+        it("creates and freezes a policy signed by the caller's PDA", async () => {
+            await setPolicyViaCpi(false);
+
+            let policy = await program.account.agentPolicy.fetch(agentPolicyPda);
+            expect(policy.owner.toBase58()).to.equal(ownerPda.toBase58());
+            expect(policy.frozen).to.equal(false);
+
+            await setPolicyViaCpi(true);
+            policy = await program.account.agentPolicy.fetch(agentPolicyPda);
+            expect(policy.frozen).to.equal(true);
+        });
+
+        it("rejects the agent key once the PDA owns the policy", async () => {
+            try {
+                await program.methods
+                    .setPolicy(policyParams())
+                    .accounts({
+                        owner: agent.publicKey,
+                        agent: agent.publicKey,
+                        agentPolicy: agentPolicyPda,
+                        retiredAgent: retiredPdaFor(agent.publicKey),
+                        payer: provider.wallet.publicKey,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .signers([agent])
+                    .rpc();
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 19: a PDA of another program can pay as the agent via CPI
+    // =========================================================================
+    describe("PDA agent", () => {
+        const cpiCaller = anchor.workspace.CpiCaller as Program<CpiCaller>;
+        let agentPda: PublicKey;
+        let agentPolicyPda: PublicKey;
+
+        before(async () => {
+            [agentPda] = PublicKey.findProgramAddressSync(
+                [Buffer.from("agent")],
+                cpiCaller.programId
+            );
+            [agentPolicyPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("policy"),
+                    agentPda.toBuffer(),
+                    new anchor.BN(policyId).toArrayLike(Buffer, 'le', 2)
+                ],
+                program.programId
+            );
+
+            // The agent PDA has to sign for its policy to be created
+            await cpiCaller.methods
+                .setPolicyForAgentPda(policyId, policyHash, allowedCategory, maxPerTx, false)
+                .accounts({
+                    agentPda,
+                    owner: provider.wallet.publicKey,
+                    agentPolicy: agentPolicyPda,
+                    retiredAgent: retiredPdaFor(agentPda),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    agentBlinkPayProgram: program.programId,
+                })
+                .rpc();
+        });
+
+        it("marks the policy as PDA-owned at creation", async () => {
+            const policy = await program.account.agentPolicy.fetch(agentPolicyPda);
+            expect(policy.agentIsPda).to.equal(true);
+
+            const keypairPolicy = await program.account.agentPolicy.fetch(policyPda);
+            expect(keypairPolicy.agentIsPda).to.equal(false);
+        });
+
+        it("authorizes and records a payment through the caller program", async () => {
+            const nonce = new anchor.BN(Date.now() + 1300);
+            const authorization = authPdaFor(nonce, agentPda);
+            const currentSlot = await provider.connection.getSlot();
+
+            await cpiCaller.methods
+                .authorizeAsPda(
+                    policyId,
+                    pricePerCall,
+                    singleCall,
+                    allowedCategory,
+                    nonce,
+                    new anchor.BN(currentSlot + 100),
+                    Buffer.alloc(64),
+                    noRequestId,
+                    expireBySlot,
+                    anyConsumer,
+                    noHashLock,
+                    noTip
+                )
+                .accounts({
+                    agentPda,
+                    agentPolicy: agentPolicyPda,
+                    meter: meterPda,
+                    deniedMeter: deniedPdaFor(meterPda, agentPda),
+                    meterUsage: usagePdaFor(meterPda, agentPda),
+                    authorization,
+                    config: configPda,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    nullifier: await nullifierPdaFor(agentPda, meterPda, nonce, pricePerCall, agentPolicyPda),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    agentBlinkPayProgram: program.programId,
+                })
+                .rpc();
+
+            await cpiCaller.methods
+                .recordAsPda(nonce)
+                .accounts({
+                    agentPda,
+                    meter: meterPda,
+                    meterCounters: countersPdaFor(meterPda),
+                    agentPolicy: agentPolicyPda,
+                    authorization,
+                    meterUsage: usagePdaFor(meterPda, agentPda),
+                    config: configPda,
+                    agentBlinkPayProgram: program.programId,
+                })
+                .rpc();
+
+            const auth = await program.account.authorization.fetch(authorization);
+            expect(auth.agent.toBase58()).to.equal(agentPda.toBase58());
+            expect(auth.used).to.equal(true);
+        });
+    });
+
+    // =========================================================================
+    // TEST 20: update_meter
+    // =========================================================================
+    describe("update_meter", () => {
+        const updMeterId = Keypair.generate();
+        let updMeterPda: PublicKey;
+
+        before(async () => {
+            [updMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    updMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: updMeterId.publicKey,
+                    meter: updMeterPda,
+                    meterCounters: countersPdaFor(updMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("lets the authority change price, category and requires_zk", async () => {
+            await program.methods
+                .updateMeter(pricePerCall.muln(2), 2, true, noFreeCalls, meterName, endpointHash, noMeterCap, anyProofVersion)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: updMeterId.publicKey,
+                    meter: updMeterPda,
+                    config: configPda,
+                })
+                .rpc();
+
+            const meter = await program.account.meter.fetch(updMeterPda);
+            expect(meter.pricePerCall.toNumber()).to.equal(pricePerCall.toNumber() * 2);
+            expect(meter.category).to.equal(2);
+            expect(meter.requiresZk).to.equal(true);
+        });
+
+        it("rejects anyone else", async () => {
+            const stranger = Keypair.generate();
+            try {
+                await program.methods
+                    .updateMeter(new anchor.BN(1), allowedCategory, false, noFreeCalls, meterName, endpointHash, noMeterCap, anyProofVersion)
+                    .accounts({
+                        authority: stranger.publicKey,
+                        meterId: updMeterId.publicKey,
+                        meter: updMeterPda,
+                        config: configPda,
+                    })
+                    .signers([stranger])
+                    .rpc();
+                expect.fail("Should have rejected a non-authority signer");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 21: close_meter and the authorization TTL cap
+    // =========================================================================
+    describe("close_meter", () => {
+        it("refuses to close an active meter", async () => {
+            try {
+                await program.methods
+                    .closeMeter()
+                    .accounts({
+                        authority: provider.wallet.publicKey,
+                        meterId: meterIdKeypair.publicKey,
+                        meter: meterPda,
+                        recipient: provider.wallet.publicKey,
+                        meterIndex: meterIndexPda,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown MeterNotPaused error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("MeterNotPaused");
+            }
+        });
+
+        it("requires the creator's meter index", async () => {
+            try {
+                await program.methods
+                    .closeMeter()
+                    .accounts({
+                        authority: provider.wallet.publicKey,
+                        meterId: meterIdKeypair.publicKey,
+                        meter: meterPda,
+                        recipient: provider.wallet.publicKey,
+                        meterIndex: Keypair.generate().publicKey,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown ConstraintSeeds error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("ConstraintSeeds");
+            }
+        });
+
+        it("rejects authorizations that outlive the maximum TTL", async () => {
+            const nonce = new anchor.BN(Date.now() + 1400);
+            const currentSlot = await provider.connection.getSlot();
+            try {
+                await program.methods
+                    .authorizePaymentWithProof(
+                        policyId,
+                        pricePerCall,
+                        singleCall,
+                        allowedCategory,
+                        nonce,
+                        new anchor.BN(currentSlot + 100000),
+                        [...Buffer.alloc(64)],
+                        noRequestId,
+                        expireBySlot,
+                        anyConsumer,
+                        noHashLock,
+                        noTip
+                    )
+                    .accounts({
+                        agent: agentKeypair.publicKey,
+                        agentPolicy: policyPda,
+                        meter: meterPda,
+                        allowedMeter: null,
+                        deniedMeter: deniedMeterPda,
+                        meterAccess: null,
+                        meterUsage: usagePdaFor(),
+                        authorization: authPdaFor(nonce),
+                        config: configPda,
+                        payer: provider.wallet.publicKey,
+                        systemProgram: SystemProgram.programId,
+                        verifierProgram: program.programId,
+                        verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                        nullifier: await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, pricePerCall, policyPda),
+                        vault: null,
+                        escrow: null,
+                        mint: null,
+                        tokenProgram: null,
+                        associatedTokenProgram: null,
+                    })
+                    .signers([agentKeypair])
+                    .rpc();
+                expect.fail("Should have thrown InvalidExpiry error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidExpiry");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 22: pause_meter / unpause_meter
+    // =========================================================================
+    describe("meter pause", () => {
+        const meterAuthorityAccounts = () => ({
+            authority: provider.wallet.publicKey,
+            meterId: meterIdKeypair.publicKey,
+            meter: meterPda,
+        });
+
+        after(async () => {
+            await program.methods.unpauseMeter().accounts(meterAuthorityAccounts()).rpc();
+        });
+
+        it("rejects new authorizations but settles in-flight ones", async () => {
+            const inFlight = new anchor.BN(Date.now() + 1500);
+            await authorize(inFlight, pricePerCall);
+
+            await program.methods.pauseMeter().accounts(meterAuthorityAccounts()).rpc();
+
+            let meter = await program.account.meter.fetch(meterPda);
+            expect(meter.active).to.equal(false);
+            expect(meter.pausedAtSlot.toNumber()).to.be.greaterThan(0);
+
+            try {
+                await authorize(new anchor.BN(Date.now() + 1501), pricePerCall);
+                expect.fail("Should have thrown MeterInactive error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("MeterInactive");
+            }
+
+            await record(inFlight);
+            const auth = await program.account.authorization.fetch(authPdaFor(inFlight));
+            expect(auth.used).to.equal(true);
+
+            try {
+                await program.methods
+                    .closeMeter()
+                    .accounts({ ...meterAuthorityAccounts(), recipient: provider.wallet.publicKey, meterIndex: meterIndexPda })
+                    .rpc();
+                expect.fail("Should have thrown MeterPausedTooRecently error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("MeterPausedTooRecently");
+            }
+
+            await program.methods.unpauseMeter().accounts(meterAuthorityAccounts()).rpc();
+            meter = await program.account.meter.fetch(meterPda);
+            expect(meter.active).to.equal(true);
+            expect(meter.pausedAtSlot.toNumber()).to.equal(0);
+
+            await authorize(new anchor.BN(Date.now() + 1502), pricePerCall);
+        });
+
+        it("settles an authorization issued the slot before the pause", async () => {
+            const lastCall = new anchor.BN(Date.now() + 1503);
+            await authorize(lastCall, pricePerCall);
+            const authorizedAt = await provider.connection.getSlot();
+
+            await program.methods.pauseMeter().accounts(meterAuthorityAccounts()).rpc();
+            const meter = await program.account.meter.fetch(meterPda);
+            expect(meter.pausedAtSlot.toNumber()).to.be.greaterThan(authorizedAt - 1);
+
+            await record(lastCall);
+            const auth = await program.account.authorization.fetch(authPdaFor(lastCall));
+            expect(auth.used).to.equal(true);
+            expect(auth.expiresAtSlot.toNumber()).to.be.at.least(meter.pausedAtSlot.toNumber());
+
+            await program.methods.unpauseMeter().accounts(meterAuthorityAccounts()).rpc();
+        });
+    });
+
+    // =========================================================================
+    // TEST 23: per-meter usage statistics
+    // =========================================================================
+    describe("meter usage statistics", () => {
+        it("counts calls and volume on each recorded payment", async () => {
+            const initial = await program.account.meterCounters.fetch(countersPdaFor(meterPda));
+
+            const nonce = new anchor.BN(Date.now() + 1600);
+            await authorize(nonce, pricePerCall);
+
+            // Authorizing alone doesn't count as usage
+            let counters = await program.account.meterCounters.fetch(countersPdaFor(meterPda));
+            expect(counters.totalCalls.toNumber()).to.equal(initial.totalCalls.toNumber());
+
+            await record(nonce);
+
+            counters = await program.account.meterCounters.fetch(countersPdaFor(meterPda));
+            expect(counters.totalCalls.toNumber()).to.equal(initial.totalCalls.toNumber() + 1);
+            expect(counters.totalVolume.toNumber()).to.equal(
+                initial.totalVolume.toNumber() + pricePerCall.toNumber()
+            );
+        });
+    });
+
+    // =========================================================================
+    // TEST 24: tiered volume pricing
+    // =========================================================================
+    describe("meter pricing tiers", () => {
+        const tierMeterId = Keypair.generate();
+        let tierMeterPda: PublicKey;
+        const unsetTier = { minCalls: new anchor.BN(0), price: new anchor.BN(0) };
+        const discountPrice = pricePerCall.divn(2);
+
+        const setTiers = (tiers: { minCalls: anchor.BN, price: anchor.BN }[]) =>
+            program.methods
+                .setMeterTiers(tiers)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: tierMeterId.publicKey,
+                    meter: tierMeterPda,
+                })
+                .rpc();
+
+        before(async () => {
+            [tierMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    tierMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: tierMeterId.publicKey,
+                    meter: tierMeterPda,
+                    meterCounters: countersPdaFor(tierMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("charges price_per_call until the agent reaches a discount tier", async () => {
+            await setTiers([
+                { minCalls: new anchor.BN(1), price: discountPrice },
+                unsetTier,
+                unsetTier,
+                unsetTier,
+            ]);
+
+            // No recorded calls yet: base price, and nothing else
+            try {
+                await authorize(new anchor.BN(Date.now() + 1700), discountPrice, tierMeterPda);
+                expect.fail("Should have thrown AmountNotTierPrice error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AmountNotTierPrice");
+            }
+            const first = new anchor.BN(Date.now() + 1701);
+            await authorize(first, pricePerCall, tierMeterPda);
+            await record(first, tierMeterPda);
+
+            const usage = await program.account.meterUsage.fetch(usagePdaFor(tierMeterPda));
+            expect(usage.calls.toNumber()).to.equal(1);
+
+            // One recorded call: discount tier
+            await authorize(new anchor.BN(Date.now() + 1702), discountPrice, tierMeterPda);
+        });
+
+        it("rejects tiers that are out of order", async () => {
+            try {
+                await setTiers([
+                    { minCalls: new anchor.BN(10), price: discountPrice },
+                    { minCalls: new anchor.BN(5), price: discountPrice },
+                    unsetTier,
+                    unsetTier,
+                ]);
+                expect.fail("Should have thrown InvalidPriceTiers error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidPriceTiers");
+            }
+        });
+
+        it("rejects tiers priced above the meter's price", async () => {
+            try {
+                await setTiers([
+                    { minCalls: new anchor.BN(1), price: pricePerCall.muln(10) },
+                    unsetTier,
+                    unsetTier,
+                    unsetTier,
+                ]);
+                expect.fail("Should have thrown TierAboveMeterPrice error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("TierAboveMeterPrice");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 25: subscription meters
+    // =========================================================================
+    describe("subscription meters", () => {
+        const createSubscriptionMeter = async (periodSlots: number) => {
+            const meterId = Keypair.generate();
+            const [meter] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    meterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, {
+                    subscription: { periodSlots: new anchor.BN(periodSlots), fee: pricePerCall },
+                }, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: meterId.publicKey,
+                    meter,
+                    meterCounters: countersPdaFor(meter),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+            return meter;
+        };
+
+        const subPdaFor = (meter: PublicKey) =>
+            PublicKey.findProgramAddressSync(
+                [Buffer.from("sub"), meter.toBuffer(), agentKeypair.publicKey.toBuffer()],
+                program.programId
+            )[0];
+
+        const subscribe = async (meter: PublicKey) => {
+            await program.methods
+                .subscribe(policyId)
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter,
+                    meterCounters: countersPdaFor(meter),
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(meter),
+                    meterAccess: null,
+                    subscription: subPdaFor(meter),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        const recordCall = async (meter: PublicKey) => {
+            await program.methods
+                .recordSubscriptionCall()
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    meter,
+                    meterCounters: countersPdaFor(meter),
+                    subscription: subPdaFor(meter),
+                })
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        let longMeter: PublicKey;
+        let shortMeter: PublicKey;
+
+        before(async () => {
+            longMeter = await createSubscriptionMeter(10000);
+            shortMeter = await createSubscriptionMeter(2);
+        });
+
+        it("rejects the per-call path on a subscription meter", async () => {
+            try {
+                await authorize(new anchor.BN(Date.now() + 1800), pricePerCall, longMeter);
+                expect.fail("Should have thrown WrongMeterKind error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("WrongMeterKind");
+            }
+        });
+
+        it("extends an early renewal from the current expiry", async () => {
+            const spentBefore = (await program.account.agentPolicy.fetch(policyPda)).lifetimeSpent;
+
+            await subscribe(longMeter);
+            const first = await program.account.subscription.fetch(subPdaFor(longMeter));
+
+            await subscribe(longMeter);
+            const renewed = await program.account.subscription.fetch(subPdaFor(longMeter));
+            expect(renewed.expiresAtSlot.toNumber()).to.equal(first.expiresAtSlot.toNumber() + 10000);
+
+            const policy = await program.account.agentPolicy.fetch(policyPda);
+            expect(policy.lifetimeSpent.toNumber()).to.equal(
+                spentBefore.toNumber() + 2 * pricePerCall.toNumber()
+            );
+
+            await recordCall(longMeter);
+            const counters = await program.account.meterCounters.fetch(countersPdaFor(longMeter));
+            expect(counters.totalCalls.toNumber()).to.equal(1);
+        });
+
+        it("rejects calls once the subscription has expired", async () => {
+            await subscribe(shortMeter);
+
+            // Wait for the 2-slot period to run out
+            await new Promise(resolve => setTimeout(resolve, 2000));
+
+            try {
+                await recordCall(shortMeter);
+                expect.fail("Should have thrown SubscriptionExpired error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("SubscriptionExpired");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 26: free calls per agent
+    // =========================================================================
+    describe("meter free calls", () => {
+        const freeMeterId = Keypair.generate();
+        let freeMeterPda: PublicKey;
+
+        before(async () => {
+            [freeMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    freeMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, 2, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: freeMeterId.publicKey,
+                    meter: freeMeterPda,
+                    meterCounters: countersPdaFor(freeMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("accepts zero-amount calls until the allowance is used up", async () => {
+            const free = new anchor.BN(Date.now() + 1900);
+            await authorize(free, new anchor.BN(0), freeMeterPda);
+            await record(free, freeMeterPda);
+
+            // An in-flight authorization also counts against the allowance
+            await authorize(new anchor.BN(Date.now() + 1901), new anchor.BN(0), freeMeterPda);
+
+            const usage = await program.account.meterUsage.fetch(usagePdaFor(freeMeterPda));
+            expect(usage.freeCallsUsed).to.equal(2);
+            expect(usage.calls.toNumber()).to.equal(1);
+
+            try {
+                await authorize(new anchor.BN(Date.now() + 1902), new anchor.BN(0), freeMeterPda);
+                expect.fail("Should have thrown FreeCallsExhausted error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("FreeCallsExhausted");
+            }
+
+            await authorize(new anchor.BN(Date.now() + 1903), pricePerCall, freeMeterPda);
+        });
+    });
+
+    // =========================================================================
+    // TEST 27: meter name and endpoint hash
+    // =========================================================================
+    describe("meter metadata", () => {
+        it("stores the name and endpoint hash and lets the authority change them", async () => {
+            const metaMeterId = Keypair.generate();
+            const [metaMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    metaMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            const hash = Array(32).fill(7);
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, "Weather API", hash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: metaMeterId.publicKey,
+                    meter: metaMeterPda,
+                    meterCounters: countersPdaFor(metaMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+
+            let meter = await program.account.meter.fetch(metaMeterPda);
+            expect(Buffer.from(meter.name.slice(0, meter.nameLen)).toString()).to.equal("Weather API");
+            expect(meter.endpointHash).to.deep.equal(hash);
+
+            await program.methods
+                .updateMeter(pricePerCall, allowedCategory, false, noFreeCalls, "Wetter API ☀", endpointHash, noMeterCap, anyProofVersion)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: metaMeterId.publicKey,
+                    meter: metaMeterPda,
+                    config: configPda,
+                })
+                .rpc();
+
+            meter = await program.account.meter.fetch(metaMeterPda);
+            expect(Buffer.from(meter.name.slice(0, meter.nameLen)).toString()).to.equal("Wetter API ☀");
+            expect(meter.endpointHash).to.deep.equal(endpointHash);
+
+            try {
+                await program.methods
+                    .updateMeter(pricePerCall, allowedCategory, false, noFreeCalls, "", endpointHash, noMeterCap, anyProofVersion)
+                    .accounts({
+                        authority: provider.wallet.publicKey,
+                        meterId: metaMeterId.publicKey,
+                        meter: metaMeterPda,
+                        config: configPda,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown InvalidMeterName error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidMeterName");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 28: two-step meter authority transfer
+    // =========================================================================
+    describe("meter authority transfer", () => {
+        const xferMeterId = Keypair.generate();
+        const newAuthority = Keypair.generate();
+        let xferMeterPda: PublicKey;
+
+        const updateAs = (authority: Keypair | null, price: anchor.BN) => {
+            const builder = program.methods
+                .updateMeter(price, allowedCategory, false, noFreeCalls, meterName, endpointHash, noMeterCap, anyProofVersion)
+                .accounts({
+                    authority: authority ? authority.publicKey : provider.wallet.publicKey,
+                    meterId: xferMeterId.publicKey,
+                    meter: xferMeterPda,
+                    config: configPda,
+                });
+            return authority ? builder.signers([authority]).rpc() : builder.rpc();
+        };
+
+        before(async () => {
+            [xferMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    xferMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: xferMeterId.publicKey,
+                    meter: xferMeterPda,
+                    meterCounters: countersPdaFor(xferMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("keeps the old authority in control until the transfer is accepted", async () => {
+            await program.methods
+                .transferMeterAuthority(newAuthority.publicKey)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: xferMeterId.publicKey,
+                    meter: xferMeterPda,
+                })
+                .rpc();
+
+            const meter = await program.account.meter.fetch(xferMeterPda);
+            expect(meter.pendingAuthority.toBase58()).to.equal(newAuthority.publicKey.toBase58());
+
+            await updateAs(null, pricePerCall.muln(3));
+
+            try {
+                await updateAs(newAuthority, pricePerCall);
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+
+        it("rejects acceptance by anyone but the nominee", async () => {
+            const stranger = Keypair.generate();
+            try {
+                await program.methods
+                    .acceptMeterAuthority()
+                    .accounts({
+                        newAuthority: stranger.publicKey,
+                        meterId: xferMeterId.publicKey,
+                        meter: xferMeterPda,
+                    })
+                    .signers([stranger])
+                    .rpc();
+                expect.fail("Should have thrown NotPendingAuthority error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("NotPendingAuthority");
+            }
+        });
+
+        it("hands control to the new authority on acceptance", async () => {
+            await program.methods
+                .acceptMeterAuthority()
+                .accounts({
+                    newAuthority: newAuthority.publicKey,
+                    meterId: xferMeterId.publicKey,
+                    meter: xferMeterPda,
+                })
+                .signers([newAuthority])
+                .rpc();
+
+            const meter = await program.account.meter.fetch(xferMeterPda);
+            expect(meter.authority.toBase58()).to.equal(newAuthority.publicKey.toBase58());
+            expect(meter.pendingAuthority).to.be.null;
+
+            await updateAs(newAuthority, pricePerCall);
+
+            try {
+                await updateAs(null, pricePerCall.muln(2));
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 29: per-meter agent allowlist
+    // =========================================================================
+    describe("meter agent allowlist", () => {
+        const privateMeterId = Keypair.generate();
+        let privateMeterPda: PublicKey;
+        let accessPda: PublicKey;
+
+        const meterAccounts = () => ({
+            authority: provider.wallet.publicKey,
+            meterId: privateMeterId.publicKey,
+            meter: privateMeterPda,
+            agent: agentKeypair.publicKey,
+            meterAccess: accessPda,
+        });
+
+        before(async () => {
+            [privateMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    privateMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            [accessPda] = PublicKey.findProgramAddressSync(
+                [Buffer.from("access"), privateMeterPda.toBuffer(), agentKeypair.publicKey.toBuffer()],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: privateMeterId.publicKey,
+                    meter: privateMeterPda,
+                    meterCounters: countersPdaFor(privateMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("accepts any agent while the allowlist is off", async () => {
+            await authorize(new anchor.BN(Date.now() + 2900), pricePerCall, privateMeterPda);
+        });
+
+        it("requires an access record once the allowlist is on", async () => {
+            await program.methods
+                .setMeterAllowlist(true)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: privateMeterId.publicKey,
+                    meter: privateMeterPda,
+                })
+                .rpc();
+
+            try {
+                await authorize(new anchor.BN(Date.now() + 2901), pricePerCall, privateMeterPda);
+                expect.fail("Should have thrown AgentNotAllowedByMeter error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AgentNotAllowedByMeter");
+            }
+
+            await program.methods
+                .grantMeterAccess()
+                .accounts({
+                    ...meterAccounts(),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+
+            await authorize(new anchor.BN(Date.now() + 2902), pricePerCall, privateMeterPda, accessPda);
+        });
+
+        it("rejects the agent again after access is revoked", async () => {
+            await program.methods
+                .revokeMeterAccess()
+                .accounts(meterAccounts())
+                .rpc();
+
+            try {
+                await authorize(new anchor.BN(Date.now() + 2903), pricePerCall, privateMeterPda);
+                expect.fail("Should have thrown AgentNotAllowedByMeter error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AgentNotAllowedByMeter");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 30: meter-wide rate limit
+    // =========================================================================
+    describe("meter rate limit", () => {
+        const limitedMeterId = Keypair.generate();
+        let limitedMeterPda: PublicKey;
+
+        const setRateLimit = (maxCalls: number, windowSlots: number) =>
+            program.methods
+                .setMeterRateLimit(maxCalls, windowSlots)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: limitedMeterId.publicKey,
+                    meter: limitedMeterPda,
+                })
+                .rpc({ commitment: "confirmed" });
+
+        before(async () => {
+            [limitedMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    limitedMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: limitedMeterId.publicKey,
+                    meter: limitedMeterPda,
+                    meterCounters: countersPdaFor(limitedMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("rejects a limit with a zero-length window", async () => {
+            try {
+                await setRateLimit(1, 0);
+                expect.fail("Should have thrown InvalidRateLimit error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidRateLimit");
+            }
+        });
+
+        it("emits the old and new limit", async () => {
+            const signature = await setRateLimit(1, 10_000);
+
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const updated = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "MeterRateLimitUpdated");
+            expect(updated!.data.oldMaxCallsPerWindow).to.equal(0);
+            expect(updated!.data.newMaxCallsPerWindow).to.equal(1);
+            expect(updated!.data.oldWindowSlots).to.equal(0);
+            expect(updated!.data.newWindowSlots).to.equal(10_000);
+        });
+
+        it("rejects recorded calls over the limit within a window", async () => {
+            await setRateLimit(1, 10_000);
+
+            const first = new anchor.BN(Date.now() + 3000);
+            const second = new anchor.BN(Date.now() + 3001);
+            await authorize(first, pricePerCall, limitedMeterPda);
+            await authorize(second, pricePerCall, limitedMeterPda);
+            await record(first, limitedMeterPda);
+
+            try {
+                await record(second, limitedMeterPda);
+                expect.fail("Should have thrown MeterRateLimited error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("MeterRateLimited");
+            }
+
+            const counters = await program.account.meterCounters.fetch(countersPdaFor(limitedMeterPda));
+            expect(counters.callsInWindow).to.equal(1);
+
+            // Lifting the limit lets the pending authorization settle
+            await setRateLimit(0, 0);
+            await record(second, limitedMeterPda);
+        });
+    });
+
+    // =========================================================================
+    // TEST 31: meter price change timelock
+    // =========================================================================
+    describe("meter price timelock", () => {
+        const timelockMeterId = Keypair.generate();
+        let timelockMeterPda: PublicKey;
+
+        const setPriceDelay = (slots: number) =>
+            program.methods
+                .updateConfig(configParams({ minPriceDelaySlots: new anchor.BN(slots) }))
+                .accounts({
+                    admin: provider.wallet.publicKey,
+                    config: configPda,
+                })
+                .rpc();
+
+        const setPrice = (price: anchor.BN) =>
+            program.methods
+                .updateMeter(price, allowedCategory, false, noFreeCalls, meterName, endpointHash, noMeterCap, anyProofVersion)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: timelockMeterId.publicKey,
+                    meter: timelockMeterPda,
+                    config: configPda,
+                })
+                .rpc();
+
+        before(async () => {
+            [timelockMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    timelockMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: timelockMeterId.publicKey,
+                    meter: timelockMeterPda,
+                    meterCounters: countersPdaFor(timelockMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+            await setPriceDelay(1_000);
+        });
+
+        after(async () => {
+            await setPriceDelay(0);
+        });
+
+        it("schedules price increases instead of applying them", async () => {
+            const slot = await provider.connection.getSlot();
+            await setPrice(pricePerCall.muln(100));
+
+            const meter = await program.account.meter.fetch(timelockMeterPda);
+            expect(meter.pricePerCall.toNumber()).to.equal(pricePerCall.toNumber());
+            expect(meter.pendingPrice.toNumber()).to.equal(pricePerCall.toNumber() * 100);
+            expect(meter.priceEffectiveSlot.toNumber()).to.be.at.least(slot + 1_000);
+        });
+
+        it("applies decreases immediately and drops the pending increase", async () => {
+            await setPrice(pricePerCall.divn(2));
+
+            const meter = await program.account.meter.fetch(timelockMeterPda);
+            expect(meter.pricePerCall.toNumber()).to.equal(pricePerCall.toNumber() / 2);
+            expect(meter.pendingPrice.toNumber()).to.equal(0);
+            expect(meter.priceEffectiveSlot.toNumber()).to.equal(0);
+        });
+    });
+
+    // =========================================================================
+    // TEST 32: create_meter_v2 with a string endpoint id
+    // =========================================================================
+    describe("create_meter_v2", () => {
+        const v2MeterPdaFor = (endpointId: string) =>
+            PublicKey.findProgramAddressSync(
+                [Buffer.from("meter"), provider.wallet.publicKey.toBuffer(), Buffer.from(endpointId)],
+                program.programId
+            )[0];
+
+        const meterParams = {
+            pricePerCall,
+            category: allowedCategory,
+            merchantWalletId,
+            requiresZk: false,
+            kind: perCallKind,
+            freeCalls: noFreeCalls,
+            name: meterName,
+            endpointHash,
+            referrerWalletId: noReferrer,
+            referrerBps: noReferrerBps,
+            minProofVersion: anyProofVersion,
+            acceptedMint: defaultMint,
+        };
+
+        it("derives the meter from the endpoint id and stores it", async () => {
+            const endpointId = `weather-${Date.now() % 1_000_000}`;
+            const v2MeterPda = v2MeterPdaFor(endpointId);
+            await program.methods
+                .createMeterV2(endpointId, meterParams)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meter: v2MeterPda,
+                    meterCounters: countersPdaFor(v2MeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+
+            let meter = await program.account.meter.fetch(v2MeterPda);
+            expect(Buffer.from(meter.endpointId.slice(0, meter.endpointIdLen)).toString()).to.equal(endpointId);
+            expect(meter.pricePerCall.toNumber()).to.equal(pricePerCall.toNumber());
+
+            // Management instructions ignore meter_id for v2 meters
+            await program.methods
+                .pauseMeter()
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: v2MeterPda,
+                    meter: v2MeterPda,
+                })
+                .rpc();
+
+            meter = await program.account.meter.fetch(v2MeterPda);
+            expect(meter.active).to.equal(false);
+        });
+
+        it("rejects endpoint ids that aren't ASCII", async () => {
+            try {
+                await program.methods
+                    .createMeterV2("wétter", meterParams)
+                    .accounts({
+                        authority: provider.wallet.publicKey,
+                        meter: v2MeterPdaFor("wétter"),
+                        meterCounters: countersPdaFor(v2MeterPdaFor("wétter")),
+                        config: configPda,
+                        meterIndex: meterIndexPda,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown InvalidEndpointId error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidEndpointId");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 33: Config price bounds for meters
+    // =========================================================================
+    describe("meter price bounds", () => {
+        const minPrice = new anchor.BN(1_000);
+        const maxPrice = new anchor.BN(1_000_000);
+
+        const createWithPrice = async (price: anchor.BN) => {
+            const boundsMeterId = Keypair.generate();
+            const [boundsMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    boundsMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(price, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: boundsMeterId.publicKey,
+                    meter: boundsMeterPda,
+                    meterCounters: countersPdaFor(boundsMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+            return { boundsMeterId, boundsMeterPda };
+        };
+
+        before(async () => {
+            await updateConfig({ minPricePerCall: minPrice, maxPricePerCall: maxPrice });
+        });
+
+        after(async () => {
+            await updateConfig({});
+        });
+
+        it("accepts prices exactly at the bounds", async () => {
+            await createWithPrice(minPrice);
+            await createWithPrice(maxPrice);
+        });
+
+        it("rejects prices just outside the bounds", async () => {
+            for (const price of [minPrice.subn(1), maxPrice.addn(1)]) {
+                try {
+                    await createWithPrice(price);
+                    expect.fail("Should have thrown PriceOutOfBounds error");
+                } catch (err: any) {
+                    expect(err.error.errorCode.code).to.equal("PriceOutOfBounds");
+                }
+            }
+        });
+
+        it("applies the bounds to update_meter too", async () => {
+            const { boundsMeterId, boundsMeterPda } = await createWithPrice(minPrice);
+            try {
+                await program.methods
+                    .updateMeter(maxPrice.addn(1), allowedCategory, false, noFreeCalls, meterName, endpointHash, noMeterCap, anyProofVersion)
+                    .accounts({
+                        authority: provider.wallet.publicKey,
+                        meterId: boundsMeterId.publicKey,
+                        meter: boundsMeterPda,
+                        config: configPda,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown PriceOutOfBounds error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("PriceOutOfBounds");
+            }
+        });
+
+        it("treats a zero max as unbounded", async () => {
+            await updateConfig({ minPricePerCall: minPrice });
+            await createWithPrice(maxPrice.muln(1_000));
+        });
+    });
+
+    // =========================================================================
+    // TEST 34: revenue splits
+    // =========================================================================
+    describe("meter revenue splits", () => {
+        const splitMeterId = Keypair.generate();
+        let splitMeterPda: PublicKey;
+
+        const split = (walletId: string, bps: number) => {
+            const walletIdBytes = Buffer.alloc(64);
+            walletIdBytes.write(walletId);
+            return { walletId: [...walletIdBytes], walletIdLen: walletId.length, bps };
+        };
+        const unsetSplit = { walletId: Array(64).fill(0), walletIdLen: 0, bps: 0 };
+
+        const setSplits = (splits: object[]) =>
+            program.methods
+                .setMeterSplits(splits)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: splitMeterId.publicKey,
+                    meter: splitMeterPda,
+                })
+                .rpc();
+
+        before(async () => {
+            [splitMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    splitMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: splitMeterId.publicKey,
+                    meter: splitMeterPda,
+                    meterCounters: countersPdaFor(splitMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("rejects splits totalling more than 10000 bps", async () => {
+            try {
+                await setSplits([split("market", 6000), split("affiliate", 4001), unsetSplit]);
+                expect.fail("Should have thrown InvalidRevenueSplits error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidRevenueSplits");
+            }
+        });
+
+        it("reports floored split amounts with the remainder to the primary", async () => {
+            await setSplits([split("market", 2000), split("affiliate", 3333), unsetSplit]);
+
+            const nonce = new anchor.BN(Date.now() + 3400);
+            await authorize(nonce, pricePerCall, splitMeterPda);
+            const signature = await program.methods
+                .recordMeterPayment(nonce, fullAmount, consumeAll, noExternalId, noPreimage, noReceipt)
+                .accounts({
+                    recorder: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: splitMeterPda,
+                    meterCounters: countersPdaFor(splitMeterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, splitMeterPda),
+                    meterUsage: usagePdaFor(splitMeterPda),
+                    config: configPda,
+                    escrow: null,
+                    merchantTokenAccount: null,
+                    tokenProgram: null,
+                    receipt: null,
+                    payer: null,
+                    systemProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc({ commitment: "confirmed" });
+
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const paid = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "MeterPaid");
+
+            // 50000 * 20% = 10000, 50000 * 33.33% = 16665, primary gets 23335
+            expect(paid!.data.splitAmounts.map((a: anchor.BN) => a.toNumber())).to.deep.equal([10000, 16665, 0]);
+            expect(paid!.data.primaryAmount.toNumber()).to.equal(23335);
+        });
+    });
+
+    // =========================================================================
+    // TEST 35: prepaid credits
+    // =========================================================================
+    describe("prepaid credits", () => {
+        const creditMeterId = Keypair.generate();
+        let creditMeterPda: PublicKey;
+        let creditPda: PublicKey;
+
+        const consume = (units: number) =>
+            program.methods
+                .consumeCredit(new anchor.BN(units))
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meter: creditMeterPda,
+                    agent: agentKeypair.publicKey,
+                    credit: creditPda,
+                })
+                .rpc();
+
+        before(async () => {
+            [creditMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    creditMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            [creditPda] = PublicKey.findProgramAddressSync(
+                [Buffer.from("credit"), creditMeterPda.toBuffer(), agentKeypair.publicKey.toBuffer()],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: creditMeterId.publicKey,
+                    meter: creditMeterPda,
+                    meterCounters: countersPdaFor(creditMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("buys credit against the policy budget", async () => {
+            const initial = await program.account.agentPolicy.fetch(policyPda);
+
+            await program.methods
+                .buyCredits(policyId, pricePerCall.muln(3), [...Buffer.alloc(64)])
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: creditMeterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(creditMeterPda),
+                    meterAccess: null,
+                    credit: creditPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    config: configPda,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            const credit = await program.account.credit.fetch(creditPda);
+            expect(credit.remaining.toNumber()).to.equal(pricePerCall.toNumber() * 3);
+
+            const policy = await program.account.agentPolicy.fetch(policyPda);
+            expect(policy.lifetimeSpent.toNumber())
+                .to.equal(initial.lifetimeSpent.toNumber() + pricePerCall.toNumber() * 3);
+        });
+
+        it("lets the meter authority consume credit until it runs out", async () => {
+            await consume(2);
+
+            let credit = await program.account.credit.fetch(creditPda);
+            expect(credit.remaining.toNumber()).to.equal(pricePerCall.toNumber());
+
+            try {
+                await consume(2);
+                expect.fail("Should have thrown InsufficientCredits error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InsufficientCredits");
+            }
+
+            credit = await program.account.credit.fetch(creditPda);
+            expect(credit.remaining.toNumber()).to.equal(pricePerCall.toNumber());
+        });
+
+        it("rejects consumption signed by anyone but the meter authority", async () => {
+            const stranger = Keypair.generate();
+            try {
+                await program.methods
+                    .consumeCredit(new anchor.BN(1))
+                    .accounts({
+                        authority: stranger.publicKey,
+                        meter: creditMeterPda,
+                        agent: agentKeypair.publicKey,
+                        credit: creditPda,
+                    })
+                    .signers([stranger])
+                    .rpc();
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+
+        it("refunds the unused credit to the agent", async () => {
+            await program.methods
+                .refundCredits()
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    meter: creditMeterPda,
+                    credit: creditPda,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            const credit = await program.account.credit.fetch(creditPda);
+            expect(credit.remaining.toNumber()).to.equal(0);
+        });
+    });
+
+    // =========================================================================
+    // TEST 36: per-agent usage analytics
+    // =========================================================================
+    describe("meter usage analytics", () => {
+        const statsMeterId = Keypair.generate();
+        let statsMeterPda: PublicKey;
+
+        before(async () => {
+            [statsMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    statsMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: statsMeterId.publicKey,
+                    meter: statsMeterPda,
+                    meterCounters: countersPdaFor(statsMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("tracks calls, volume and the first call slot per agent", async () => {
+            const first = new anchor.BN(Date.now() + 3600);
+            await authorize(first, pricePerCall, statsMeterPda);
+
+            let usage = await program.account.meterUsage.fetch(usagePdaFor(statsMeterPda));
+            expect(usage.version).to.equal(1);
+            expect(usage.firstCallSlot.toNumber()).to.equal(0);
+
+            await record(first, statsMeterPda);
+            usage = await program.account.meterUsage.fetch(usagePdaFor(statsMeterPda));
+            const firstCallSlot = usage.firstCallSlot.toNumber();
+            expect(firstCallSlot).to.be.greaterThan(0);
+
+            const second = new anchor.BN(Date.now() + 3601);
+            await authorize(second, pricePerCall, statsMeterPda);
+            await record(second, statsMeterPda);
+
+            usage = await program.account.meterUsage.fetch(usagePdaFor(statsMeterPda));
+            expect(usage.calls.toNumber()).to.equal(2);
+            expect(usage.volume.toNumber()).to.equal(pricePerCall.toNumber() * 2);
+            expect(usage.firstCallSlot.toNumber()).to.equal(firstCallSlot);
+        });
+    });
+
+    // =========================================================================
+    // TEST 37: merchant_wallet_id validation
+    // =========================================================================
+    describe("merchant_wallet_id validation", () => {
+        const createWithWalletId = async (walletId: string) => {
+            const walletMeterId = Keypair.generate();
+            const [walletMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    walletMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, walletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: walletMeterId.publicKey,
+                    meter: walletMeterPda,
+                    meterCounters: countersPdaFor(walletMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+            return walletMeterPda;
+        };
+
+        it("accepts a UUID-style wallet id", async () => {
+            const walletId = "0b6b3c1e-5f0c-4b8e-9a7d-2f1e3c4d5a6b";
+            const walletMeterPda = await createWithWalletId(walletId);
+
+            const meter = await program.account.meter.fetch(walletMeterPda);
+            expect(Buffer.from(meter.merchantWalletId.slice(0, meter.merchantWalletIdLen)).toString())
+                .to.equal(walletId);
+        });
+
+        for (const [label, walletId] of [
+            ["an empty wallet id", ""],
+            ["a wallet id with an embedded NUL", "wallet\u0000id"],
+            ["a wallet id with whitespace", "wallet id"],
+        ]) {
+            it(`rejects ${label}`, async () => {
+                try {
+                    await createWithWalletId(walletId);
+                    expect.fail("Should have thrown InvalidMerchantWalletId error");
+                } catch (err: any) {
+                    expect(err.error.errorCode.code).to.equal("InvalidMerchantWalletId");
+                }
+            });
+        }
+
+        it("keeps the authority in control when it is also the operator", async () => {
+            await program.methods
+                .setMeterOperator(provider.wallet.publicKey)
+                .accounts(opAccounts(provider.wallet.publicKey))
+                .rpc();
+            await program.methods
+                .setMeterOperator(operator.publicKey)
+                .accounts(opAccounts(provider.wallet.publicKey))
+                .rpc();
+
+            const meter = await program.account.meter.fetch(opMeterPda);
+            expect(meter.operator.toBase58()).to.equal(operator.publicKey.toBase58());
+        });
+    });
+
+    // =========================================================================
+    // TEST 38: update_merchant_wallet
+    // =========================================================================
+    describe("update_merchant_wallet", () => {
+        const walletMeterId = Keypair.generate();
+        let walletMeterPda: PublicKey;
+
+        const accounts = () => ({
+            authority: provider.wallet.publicKey,
+            meterId: walletMeterId.publicKey,
+            meter: walletMeterPda,
+        });
+
+        before(async () => {
+            [walletMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    walletMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    ...accounts(),
+                    meterCounters: countersPdaFor(walletMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("requires the meter to be paused", async () => {
+            try {
+                await program.methods.updateMerchantWallet("new_wallet_456").accounts(accounts()).rpc();
+                expect.fail("Should have thrown MeterNotPaused error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("MeterNotPaused");
+            }
+        });
+
+        it("validates and stores the new wallet id", async () => {
+            await program.methods.pauseMeter().accounts(accounts()).rpc();
+
+            try {
+                await program.methods.updateMerchantWallet("new wallet").accounts(accounts()).rpc();
+                expect.fail("Should have thrown InvalidMerchantWalletId error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidMerchantWalletId");
+            }
+
+            await program.methods.updateMerchantWallet("new_wallet_456").accounts(accounts()).rpc();
+
+            const meter = await program.account.meter.fetch(walletMeterPda);
+            expect(Buffer.from(meter.merchantWalletId.slice(0, meter.merchantWalletIdLen)).toString())
+                .to.equal("new_wallet_456");
+        });
+    });
+
+    // =========================================================================
+    // TEST 39: per-payment cap on meters
+    // =========================================================================
+    describe("meter per-payment cap", () => {
+        const capMeterId = Keypair.generate();
+        let capMeterPda: PublicKey;
+
+        before(async () => {
+            [capMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    capMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: capMeterId.publicKey,
+                    meter: capMeterPda,
+                    meterCounters: countersPdaFor(capMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("rejects amounts above the price when no cap is set", async () => {
+            try {
+                await authorize(new anchor.BN(Date.now() + 3900), pricePerCall.muln(2), capMeterPda);
+                expect.fail("Should have thrown AmountExceedsMeterCap error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AmountExceedsMeterCap");
+            }
+        });
+
+        it("accepts amounts up to max_amount_per_payment", async () => {
+            await program.methods
+                .updateMeter(pricePerCall, allowedCategory, false, noFreeCalls, meterName, endpointHash, pricePerCall.muln(3), anyProofVersion)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: capMeterId.publicKey,
+                    meter: capMeterPda,
+                    config: configPda,
+                })
+                .rpc();
+
+            const meter = await program.account.meter.fetch(capMeterPda);
+            expect(meter.maxAmountPerPayment.toNumber()).to.equal(pricePerCall.toNumber() * 3);
+
+            await authorize(new anchor.BN(Date.now() + 3901), pricePerCall.muln(3), capMeterPda);
+            try {
+                await authorize(new anchor.BN(Date.now() + 3902), pricePerCall.muln(3).addn(1), capMeterPda);
+                expect.fail("Should have thrown AmountExceedsMeterCap error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AmountExceedsMeterCap");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 40: meter referrers
+    // =========================================================================
+    describe("meter referrers", () => {
+        const createWithReferrer = async (referrerWalletId: string, referrerBps: number) => {
+            const refMeterId = Keypair.generate();
+            const [refMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    refMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, referrerWalletId, referrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: refMeterId.publicKey,
+                    meter: refMeterPda,
+                    meterCounters: countersPdaFor(refMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+            return refMeterPda;
+        };
+
+        for (const [label, walletId, bps] of [
+            ["a share above 1000 bps", "integrator_1", 1001],
+            ["a wallet id without a share", "integrator_1", 0],
+            ["a share without a wallet id", "", 500],
+        ] as [string, string, number][]) {
+            it(`rejects ${label}`, async () => {
+                try {
+                    await createWithReferrer(walletId, bps);
+                    expect.fail("Should have thrown InvalidReferral error");
+                } catch (err: any) {
+                    expect(err.error.errorCode.code).to.equal("InvalidReferral");
+                }
+            });
+        }
+
+        it("reports the referrer's share in MeterPaid", async () => {
+            const refMeterPda = await createWithReferrer("integrator_1", 500);
+
+            const meter = await program.account.meter.fetch(refMeterPda);
+            expect(meter.referrerBps).to.equal(500);
+            expect(Buffer.from(meter.referrerWalletId.slice(0, meter.referrerWalletIdLen)).toString())
+                .to.equal("integrator_1");
+
+            const nonce = new anchor.BN(Date.now() + 4000);
+            await authorize(nonce, pricePerCall, refMeterPda);
+            const signature = await program.methods
+                .recordMeterPayment(nonce, fullAmount, consumeAll, noExternalId, noPreimage, noReceipt)
+                .accounts({
+                    recorder: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: refMeterPda,
+                    meterCounters: countersPdaFor(refMeterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, refMeterPda),
+                    meterUsage: usagePdaFor(refMeterPda),
+                    config: configPda,
+                    escrow: null,
+                    merchantTokenAccount: null,
+                    tokenProgram: null,
+                    receipt: null,
+                    payer: null,
+                    systemProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc({ commitment: "confirmed" });
+
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const paid = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "MeterPaid");
+
+            // 50000 * 5% = 2500 to the referrer, the rest to the merchant
+            expect(paid!.data.referrerAmount.toNumber()).to.equal(2500);
+            expect(paid!.data.primaryAmount.toNumber()).to.equal(47500);
+        });
+    });
+
+    // =========================================================================
+    // TEST 41: per-authority meter index
+    // =========================================================================
+    describe("meter index", () => {
+        it("lists every meter the authority creates", async () => {
+            const indexBefore = await program.account.meterIndex.fetch(meterIndexPda);
+            expect(indexBefore.authority.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+            expect(indexBefore.count).to.equal(indexBefore.meters.length);
+            expect(indexBefore.meters.map((m: PublicKey) => m.toBase58())).to.include(meterPda.toBase58());
+
+            const indexedMeterId = Keypair.generate();
+            const [indexedMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    indexedMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: indexedMeterId.publicKey,
+                    meter: indexedMeterPda,
+                    meterCounters: countersPdaFor(indexedMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+
+            const index = await program.account.meterIndex.fetch(meterIndexPda);
+            expect(index.count).to.equal(indexBefore.count + 1);
+            expect(index.meters.map((m: PublicKey) => m.toBase58())).to.include(indexedMeterPda.toBase58());
+        });
+    });
+
+    // =========================================================================
+    // TEST 42: minimum proof version on meters
+    // =========================================================================
+    describe("meter minimum proof version", () => {
+        const versionedMeterId = Keypair.generate();
+        let versionedMeterPda: PublicKey;
+
+        const proofWithVersion = (version: number) => {
+            const proof = Buffer.alloc(64);
+            proof[0] = version;
+            return proof;
+        };
+
+        before(async () => {
+            [versionedMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    versionedMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, 2, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: versionedMeterId.publicKey,
+                    meter: versionedMeterPda,
+                    meterCounters: countersPdaFor(versionedMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("rejects proofs below the minimum version", async () => {
+            try {
+                await authorize(new anchor.BN(Date.now() + 4200), pricePerCall, versionedMeterPda, null, proofWithVersion(1));
+                expect.fail("Should have thrown ProofVersionTooOld error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("ProofVersionTooOld");
+            }
+        });
+
+        it("accepts proofs at the minimum version", async () => {
+            await authorize(new anchor.BN(Date.now() + 4201), pricePerCall, versionedMeterPda, null, proofWithVersion(2));
+        });
+
+        it("accepts any version once the minimum is cleared", async () => {
+            await program.methods
+                .updateMeter(pricePerCall, allowedCategory, false, noFreeCalls, meterName, endpointHash, noMeterCap, anyProofVersion)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: versionedMeterId.publicKey,
+                    meter: versionedMeterPda,
+                    config: configPda,
+                })
+                .rpc();
+
+            await authorize(new anchor.BN(Date.now() + 4202), pricePerCall, versionedMeterPda, null, proofWithVersion(0));
+        });
+    });
+
+    // =========================================================================
+    // TEST 43: clone_meter
+    // =========================================================================
+    describe("clone_meter", () => {
+        const sourceMeterId = Keypair.generate();
+        let sourceMeterPda: PublicKey;
+
+        const meterPdaFor = (authority: PublicKey, meterId: PublicKey) =>
+            PublicKey.findProgramAddressSync(
+                [Buffer.from("meter"), authority.toBuffer(), meterId.toBuffer()],
+                program.programId
+            )[0];
+
+        before(async () => {
+            sourceMeterPda = meterPdaFor(provider.wallet.publicKey, sourceMeterId.publicKey);
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, true, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: sourceMeterId.publicKey,
+                    meter: sourceMeterPda,
+                    meterCounters: countersPdaFor(sourceMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+
+            const walletIdBytes = Buffer.alloc(64);
+            walletIdBytes.write("market");
+            const unsetSplit = { walletId: Array(64).fill(0), walletIdLen: 0, bps: 0 };
+            await program.methods
+                .setMeterSplits([{ walletId: [...walletIdBytes], walletIdLen: 6, bps: 1500 }, unsetSplit, unsetSplit])
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: sourceMeterId.publicKey,
+                    meter: sourceMeterPda,
+                })
+                .rpc();
+        });
+
+        it("copies pricing and splits, applying overrides", async () => {
+            const cloneMeterId = Keypair.generate();
+            const cloneMeterPda = meterPdaFor(provider.wallet.publicKey, cloneMeterId.publicKey);
+            await program.methods
+                .cloneMeter({ pricePerCall: pricePerCall.muln(2), category: null })
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    sourceMeter: sourceMeterPda,
+                    newMeterId: cloneMeterId.publicKey,
+                    meter: cloneMeterPda,
+                    meterCounters: countersPdaFor(cloneMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+
+            const clone = await program.account.meter.fetch(cloneMeterPda);
+            expect(clone.pricePerCall.toNumber()).to.equal(pricePerCall.toNumber() * 2);
+            expect(clone.category).to.equal(allowedCategory);
+            expect(clone.requiresZk).to.equal(true);
+            expect(clone.splits[0].bps).to.equal(1500);
+            expect(clone.authority.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+
+            const index = await program.account.meterIndex.fetch(meterIndexPda);
+            expect(index.meters.map((m: PublicKey) => m.toBase58())).to.include(cloneMeterPda.toBase58());
+        });
+
+        it("copies a scheduled price once it has taken effect", async () => {
+            const setPriceDelay = (slots: number) =>
+                program.methods
+                    .updateConfig(configParams({ minPriceDelaySlots: new anchor.BN(slots) }))
+                    .accounts({ admin: provider.wallet.publicKey, config: configPda })
+                    .rpc();
+
+            await setPriceDelay(2);
+            try {
+                await program.methods
+                    .updateMeter(pricePerCall.muln(3), allowedCategory, true, noFreeCalls, meterName, endpointHash, noMeterCap, anyProofVersion)
+                    .accounts({
+                        authority: provider.wallet.publicKey,
+                        meterId: sourceMeterId.publicKey,
+                        meter: sourceMeterPda,
+                        config: configPda,
+                    })
+                    .rpc();
+            } finally {
+                await setPriceDelay(0);
+            }
+            await new Promise(resolve => setTimeout(resolve, 2000));
+
+            const source = await program.account.meter.fetch(sourceMeterPda);
+            expect(source.pricePerCall.toNumber()).to.equal(pricePerCall.toNumber());
+            expect(source.pendingPrice.toNumber()).to.equal(pricePerCall.toNumber() * 3);
+
+            const cloneMeterId = Keypair.generate();
+            const cloneMeterPda = meterPdaFor(provider.wallet.publicKey, cloneMeterId.publicKey);
+            await program.methods
+                .cloneMeter({ pricePerCall: null, category: null })
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    sourceMeter: sourceMeterPda,
+                    newMeterId: cloneMeterId.publicKey,
+                    meter: cloneMeterPda,
+                    meterCounters: countersPdaFor(cloneMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+
+            const clone = await program.account.meter.fetch(cloneMeterPda);
+            expect(clone.pricePerCall.toNumber()).to.equal(pricePerCall.toNumber() * 3);
+        });
+
+        it("rejects a source meter of another authority", async () => {
+            const stranger = Keypair.generate();
+            const cloneMeterId = Keypair.generate();
+            try {
+                await program.methods
+                    .cloneMeter({ pricePerCall: null, category: null })
+                    .accounts({
+                        authority: stranger.publicKey,
+                        sourceMeter: sourceMeterPda,
+                        newMeterId: cloneMeterId.publicKey,
+                        meter: meterPdaFor(stranger.publicKey, cloneMeterId.publicKey),
+                        meterCounters: countersPdaFor(meterPdaFor(stranger.publicKey, cloneMeterId.publicKey)),
+                        config: configPda,
+                        meterIndex: PublicKey.findProgramAddressSync(
+                            [Buffer.from("meter_index"), stranger.publicKey.toBuffer()],
+                            program.programId
+                        )[0],
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .signers([stranger])
+                    .rpc();
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 44: meter operators
+    // =========================================================================
+    describe("meter operators", () => {
+        const opMeterId = Keypair.generate();
+        const operator = Keypair.generate();
+        let opMeterPda: PublicKey;
+
+        const opAccounts = (signer: PublicKey) => ({
+            authority: signer,
+            meterId: opMeterId.publicKey,
+            meter: opMeterPda,
+        });
+
+        before(async () => {
+            [opMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    opMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    ...opAccounts(provider.wallet.publicKey),
+                    meterCounters: countersPdaFor(opMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+            await program.methods
+                .setMeterOperator(operator.publicKey)
+                .accounts(opAccounts(provider.wallet.publicKey))
+                .rpc();
+        });
+
+        it("lets the operator update, pause and unpause the meter", async () => {
+            await program.methods
+                .updateMeter(pricePerCall.divn(2), allowedCategory, false, noFreeCalls, meterName, endpointHash, noMeterCap, anyProofVersion)
+                .accounts({ ...opAccounts(operator.publicKey), config: configPda })
+                .signers([operator])
+                .rpc();
+            await program.methods.pauseMeter().accounts(opAccounts(operator.publicKey)).signers([operator]).rpc();
+            expect((await program.account.meter.fetch(opMeterPda)).active).to.equal(false);
+
+            await program.methods.unpauseMeter().accounts(opAccounts(operator.publicKey)).signers([operator]).rpc();
+            const meter = await program.account.meter.fetch(opMeterPda);
+            expect(meter.active).to.equal(true);
+            expect(meter.pricePerCall.toNumber()).to.equal(pricePerCall.toNumber() / 2);
+        });
+
+        const restricted: [string, () => Promise<string>][] = [
+            ["close_meter", () => program.methods
+                .closeMeter()
+                .accounts({ ...opAccounts(operator.publicKey), recipient: operator.publicKey, meterIndex: meterIndexPda })
+                .signers([operator])
+                .rpc()],
+            ["transfer_meter_authority", () => program.methods
+                .transferMeterAuthority(operator.publicKey)
+                .accounts(opAccounts(operator.publicKey))
+                .signers([operator])
+                .rpc()],
+            ["set_meter_operator", () => program.methods
+                .setMeterOperator(null)
+                .accounts(opAccounts(operator.publicKey))
+                .signers([operator])
+                .rpc()],
+        ];
+        for (const [name, attempt] of restricted) {
+            it(`rejects ${name} signed by the operator`, async () => {
+                try {
+                    await attempt();
+                    expect.fail("Should have thrown UnauthorizedOperator error");
+                } catch (err: any) {
+                    expect(err.error.errorCode.code).to.equal("UnauthorizedOperator");
+                }
+            });
+        }
+    });
+
+    // =========================================================================
+    // TEST 45: category validation
+    // =========================================================================
+    describe("category validation", () => {
+        const unknownCategory = 14;
+
+        it("rejects a policy with an unknown category", async () => {
+            try {
+                await program.methods
+                    .setPolicy(policyParams({ allowedCategory: unknownCategory }))
+                    .accounts({
+                        owner: agentKeypair.publicKey,
+                        agent: agentKeypair.publicKey,
+                        agentPolicy: policyPda,
+                        retiredAgent: retiredPdaFor(agentKeypair.publicKey),
+                        payer: provider.wallet.publicKey,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .signers([agentKeypair])
+                    .rpc();
+                expect.fail("Should have thrown InvalidCategory error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidCategory");
+            }
+        });
+
+        it("rejects a meter with an unknown category", async () => {
+            const badMeterId = Keypair.generate();
+            const [badMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    badMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            try {
+                await program.methods
+                    .createMeter(pricePerCall, unknownCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                    .accounts({
+                        authority: provider.wallet.publicKey,
+                        meterId: badMeterId.publicKey,
+                        meter: badMeterPda,
+                        meterCounters: countersPdaFor(badMeterPda),
+                        config: configPda,
+                        meterIndex: meterIndexPda,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown InvalidCategory error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidCategory");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 46: settlement mints
+    // =========================================================================
+    describe("meter settlement mints", () => {
+        const eurcMint = Keypair.generate().publicKey;
+        const eurcMeterId = Keypair.generate();
+        let eurcMeterPda: PublicKey;
+
+        before(async () => {
+            [eurcMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    eurcMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, eurcMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: eurcMeterId.publicKey,
+                    meter: eurcMeterPda,
+                    meterCounters: countersPdaFor(eurcMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        after(async () => {
+            await setPolicyFlags({});
+        });
+
+        it("defaults meters to the Config's USDC mint", async () => {
+            const meter = await program.account.meter.fetch(meterPda);
+            expect(meter.acceptedMint.toBase58()).to.equal(usdcMint.toBase58());
+
+            const eurcMeter = await program.account.meter.fetch(eurcMeterPda);
+            expect(eurcMeter.acceptedMint.toBase58()).to.equal(eurcMint.toBase58());
+        });
+
+        it("rejects meters outside the policy's restrict_mint", async () => {
+            await setPolicyFlags({ restrictMint: usdcMint });
+
+            try {
+                await authorize(new anchor.BN(Date.now() + 4600), pricePerCall, eurcMeterPda);
+                expect.fail("Should have thrown MintMismatch error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("MintMismatch");
+            }
+
+            const nonce = new anchor.BN(Date.now() + 4601);
+            await authorize(nonce, pricePerCall);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.mint.toBase58()).to.equal(usdcMint.toBase58());
+        });
+    });
+
+    // =========================================================================
+    // TEST 47: meter counters sidecar
+    // =========================================================================
+    describe("meter counters", () => {
+        it("creates the counters alongside the meter", async () => {
+            const counters = await program.account.meterCounters.fetch(countersPdaFor(meterPda));
+            expect(counters.meter.toBase58()).to.equal(meterPda.toBase58());
+            expect(counters.totalCalls.toNumber()).to.be.greaterThan(0);
+        });
+
+        it("records payments without writing the meter", async () => {
+            const before = await provider.connection.getAccountInfo(meterPda);
+            const initial = await program.account.meterCounters.fetch(countersPdaFor(meterPda));
+
+            const nonce = new anchor.BN(Date.now() + 4700);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+
+            const after = await provider.connection.getAccountInfo(meterPda);
+            expect(after.data.equals(before.data)).to.equal(true);
+
+            const counters = await program.account.meterCounters.fetch(countersPdaFor(meterPda));
+            expect(counters.totalCalls.toNumber()).to.equal(initial.totalCalls.toNumber() + 1);
+        });
+
+        it("refuses to initialize counters twice", async () => {
+            try {
+                await program.methods
+                    .initMeterCounters()
+                    .accounts({
+                        payer: provider.wallet.publicKey,
+                        meter: meterPda,
+                        meterCounters: countersPdaFor(meterPda),
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown an account-in-use error");
+            } catch (err: any) {
+                expect(err.toString()).to.include("already in use");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 48: multi-call authorizations
+    // =========================================================================
+    describe("authorization quantity", () => {
+        const batchMeterId = Keypair.generate();
+        let batchMeterPda: PublicKey;
+
+        const authorizeBatch = async (nonce: anchor.BN, amount: anchor.BN, quantity: number) => {
+            const currentSlot = await provider.connection.getSlot();
+            await program.methods
+                .authorizePaymentWithProof(
+                    policyId,
+                    amount,
+                    quantity,
+                    allowedCategory,
+                    nonce,
+                    new anchor.BN(currentSlot + 100),
+                    [...Buffer.alloc(64)],
+                    noRequestId,
+                    expireBySlot,
+                    anyConsumer,
+                    noHashLock,
+                    noTip
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: batchMeterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(batchMeterPda),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(batchMeterPda),
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, batchMeterPda),
+                    config: configPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    nullifier: await nullifierPdaFor(agentKeypair.publicKey, batchMeterPda, nonce, amount, policyPda),
+                    vault: null,
+                    escrow: null,
+                    mint: null,
+                    tokenProgram: null,
+                    associatedTokenProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        before(async () => {
+            [batchMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    batchMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: batchMeterId.publicKey,
+                    meter: batchMeterPda,
+                    meterCounters: countersPdaFor(batchMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("consumes one call per record until the batch is used up", async () => {
+            const nonce = new anchor.BN(Date.now() + 4800);
+            await authorizeBatch(nonce, pricePerCall.muln(3), 3);
+
+            const auth = await program.account.authorization.fetch(
+                authPdaFor(nonce, agentKeypair.publicKey, batchMeterPda)
+            );
+            expect(auth.quantity).to.equal(3);
+            expect(auth.callsRemaining).to.equal(3);
+
+            const spentBefore = (await program.account.agentPolicy.fetch(policyPda)).lifetimeSpent;
+            for (let call = 1; call <= 3; call++) {
+                await record(nonce, batchMeterPda);
+                const consumed = await program.account.authorization.fetch(
+                    authPdaFor(nonce, agentKeypair.publicKey, batchMeterPda)
+                );
+                expect(consumed.callsRemaining).to.equal(3 - call);
+                expect(consumed.used).to.equal(call === 3);
+            }
+
+            const policy = await program.account.agentPolicy.fetch(policyPda);
+            expect(policy.lifetimeSpent.toNumber()).to.equal(
+                spentBefore.toNumber() + 3 * pricePerCall.toNumber()
+            );
+
+            try {
+                await record(nonce, batchMeterPda);
+                expect.fail("Should have thrown AuthorizationUsed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationUsed");
+            }
+        });
+
+        it("rejects a quantity of zero", async () => {
+            try {
+                await authorizeBatch(new anchor.BN(Date.now() + 4801), pricePerCall, 0);
+                expect.fail("Should have thrown InvalidQuantity error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidQuantity");
+            }
+        });
+
+        it("rejects a batch not priced at quantity x price_per_call", async () => {
+            try {
+                await authorizeBatch(new anchor.BN(Date.now() + 4802), pricePerCall.muln(2), 3);
+                expect.fail("Should have thrown AmountNotQuantityPrice error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AmountNotQuantityPrice");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 49: on-chain max_per_tx check
+    // =========================================================================
+    describe("max_per_tx enforcement", () => {
+        // Priced at the policy's limit so the meter cap doesn't interfere
+        const maxMeterId = Keypair.generate();
+        let maxMeterPda: PublicKey;
+
+        before(async () => {
+            [maxMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    maxMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(maxPerTx, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: maxMeterId.publicKey,
+                    meter: maxMeterPda,
+                    meterCounters: countersPdaFor(maxMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("rejects amounts over max_per_tx before calling the verifier", async () => {
+            try {
+                await authorize(new anchor.BN(Date.now() + 4900), maxPerTx.addn(1), maxMeterPda);
+                expect.fail("Should have thrown AmountExceedsMax error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AmountExceedsMax");
+                expect(err.logs.join("\n")).to.not.include("Calling External Verifier");
+            }
+        });
+
+        it("accepts an amount of exactly max_per_tx", async () => {
+            const nonce = new anchor.BN(Date.now() + 4901);
+            await authorize(nonce, maxPerTx, maxMeterPda);
+
+            const auth = await program.account.authorization.fetch(
+                authPdaFor(nonce, agentKeypair.publicKey, maxMeterPda)
+            );
+            expect(auth.amount.toNumber()).to.equal(maxPerTx.toNumber());
+        });
+    });
+
+    // =========================================================================
+    // TEST 50: authorized amount vs meter price
+    // =========================================================================
+    describe("meter price enforcement", () => {
+        it("accepts the exact meter price", async () => {
+            const nonce = new anchor.BN(Date.now() + 5000);
+            await authorize(nonce, pricePerCall);
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.amount.toNumber()).to.equal(pricePerCall.toNumber());
+        });
+
+        it("rejects underpayment", async () => {
+            try {
+                await authorize(new anchor.BN(Date.now() + 5001), new anchor.BN(1));
+                expect.fail("Should have thrown AmountBelowMeterPrice error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AmountBelowMeterPrice");
+            }
+        });
+
+        it("rejects gross overpayment beyond the meter cap", async () => {
+            try {
+                await authorize(new anchor.BN(Date.now() + 5002), pricePerCall.muln(10));
+                expect.fail("Should have thrown AmountExceedsMeterCap error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AmountExceedsMeterCap");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 51: authorization expiry bounds
+    // =========================================================================
+    describe("authorization expiry bounds", () => {
+        const maxTtlSlots = 9_000; // MAX_AUTHORIZATION_TTL_SLOTS
+
+        const authorizeUntil = async (nonce: anchor.BN, expiresAtSlot: number) =>
+            program.methods
+                .authorizePaymentWithProof(
+                    policyId,
+                    pricePerCall,
+                    singleCall,
+                    allowedCategory,
+                    nonce,
+                    new anchor.BN(expiresAtSlot),
+                    [...Buffer.alloc(64)],
+                    noRequestId,
+                    expireBySlot,
+                    anyConsumer,
+                    noHashLock,
+                    noTip
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedMeterPda,
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(),
+                    authorization: authPdaFor(nonce),
+                    config: configPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    nullifier: await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, pricePerCall, policyPda),
+                    vault: null,
+                    escrow: null,
+                    mint: null,
+                    tokenProgram: null,
+                    associatedTokenProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+        it("rejects an expiry at the current slot", async () => {
+            // The transaction lands at or after the fetched slot
+            const currentSlot = await provider.connection.getSlot();
+            try {
+                await authorizeUntil(new anchor.BN(Date.now() + 5100), currentSlot);
+                expect.fail("Should have thrown InvalidExpiry error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidExpiry");
+            }
+        });
+
+        it("accepts an expiry MAX_AUTHORIZATION_TTL_SLOTS ahead and records its creation slot", async () => {
+            const nonce = new anchor.BN(Date.now() + 5101);
+            const currentSlot = await provider.connection.getSlot();
+            await authorizeUntil(nonce, currentSlot + maxTtlSlots);
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.expiresAtSlot.toNumber()).to.equal(currentSlot + maxTtlSlots);
+            expect(auth.createdAtSlot.toNumber()).to.be.at.least(currentSlot);
+        });
+    });
+
+    // =========================================================================
+    // TEST 52: close_authorization
+    // =========================================================================
+    describe("close_authorization", () => {
+        const closeAuthorization = (authorization: PublicKey, rentPayer: PublicKey = provider.wallet.publicKey) =>
+            program.methods
+                .closeAuthorization()
+                .accounts({ authorization, rentPayer, config: configPda })
+                .rpc({ commitment: "confirmed" });
+
+        const closedReason = async (signature: string) => {
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const closed = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "AuthorizationClosed");
+            return closed!.data.reason;
+        };
+
+        it("refuses to close a live authorization", async () => {
+            const nonce = new anchor.BN(Date.now() + 5200);
+            await authorize(nonce, pricePerCall);
+
+            try {
+                await closeAuthorization(authPdaFor(nonce));
+                expect.fail("Should have thrown AuthorizationStillLive error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationStillLive");
+            }
+        });
+
+        it("returns a used authorization's rent to its payer", async () => {
+            const nonce = new anchor.BN(Date.now() + 5201);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.rentPayer.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+
+            try {
+                await closeAuthorization(authPdaFor(nonce), Keypair.generate().publicKey);
+                expect.fail("Should have rejected a rent payer other than the recorded one");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("RentPayerMismatch");
+            }
+
+            const signature = await closeAuthorization(authPdaFor(nonce));
+            expect(await closedReason(signature)).to.equal(0); // close_reasons::USED
+            expect(await provider.connection.getAccountInfo(authPdaFor(nonce))).to.equal(null);
+        });
+
+        it("closes an expired authorization that was never used", async () => {
+            const nonce = new anchor.BN(Date.now() + 5202);
+            await authorize(nonce, pricePerCall, meterPda, null, Buffer.alloc(64), 2);
+            await new Promise(resolve => setTimeout(resolve, 3000));
+
+            const signature = await closeAuthorization(authPdaFor(nonce));
+            expect(await closedReason(signature)).to.equal(1); // close_reasons::EXPIRED
+        });
+    });
+
+    // =========================================================================
+    // TEST 53: revoke_authorization
+    // =========================================================================
+    describe("revoke_authorization", () => {
+        const revoke = (nonce: anchor.BN, authority: Keypair = agentKeypair) =>
+            program.methods
+                .revokeAuthorization(nonce)
+                .accounts({
+                    authority: authority.publicKey,
+                    agentPolicy: policyPda,
+                    authorization: authPdaFor(nonce),
+                })
+                .signers([authority])
+                .rpc();
+
+        it("rejects record_meter_payment after a revoke", async () => {
+            const nonce = new anchor.BN(Date.now() + 5300);
+            await authorize(nonce, pricePerCall);
+            await revoke(nonce);
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.revoked).to.equal(true);
+
+            try {
+                await record(nonce);
+                expect.fail("Should have thrown AuthorizationRevoked error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationRevoked");
+            }
+
+            // The revoked ticket's rent can then be reclaimed
+            await program.methods
+                .closeAuthorization()
+                .accounts({ authorization: authPdaFor(nonce), rentPayer: provider.wallet.publicKey, config: configPda })
+                .rpc();
+        });
+
+        it("rejects a revoke after the payment was recorded", async () => {
+            const nonce = new anchor.BN(Date.now() + 5301);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+
+            try {
+                await revoke(nonce);
+                expect.fail("Should have thrown AuthorizationUsed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationUsed");
+            }
+        });
+
+        it("rejects anyone but the agent or policy owner", async () => {
+            const nonce = new anchor.BN(Date.now() + 5302);
+            await authorize(nonce, pricePerCall);
+
+            try {
+                await revoke(nonce, Keypair.generate());
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 54: authorize_payments_batch
+    // =========================================================================
+    describe("authorize_payments_batch", () => {
+        const authorizeBatch = async (
+            payments: { amount: anchor.BN, nonce: anchor.BN }[],
+            proofLength = 64,
+            withNullifiers = true
+        ) => {
+            const currentSlot = await provider.connection.getSlot();
+            return program.methods
+                .authorizePaymentsBatch(
+                    policyId,
+                    payments.map(({ amount, nonce }) => ({
+                        amount,
+                        category: allowedCategory,
+                        nonce,
+                        expiresAtSlot: new anchor.BN(currentSlot + 100),
+                        requestId: noRequestId,
+                    })),
+                    payments.map(() => [...Buffer.alloc(proofLength)])
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedMeterPda,
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    config: configPda,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                })
+                .remainingAccounts((await Promise.all(payments.map(async ({ amount, nonce }) => [
+                    authPdaFor(nonce),
+                    ...(withNullifiers
+                        ? [await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, amount, policyPda)]
+                        : []),
+                ]))).flat().map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })))
+                .signers([agentKeypair])
+                .rpc({ commitment: "confirmed" });
+        };
+
+        it("creates one authorization and event per entry", async () => {
+            const nonces = [0, 1, 2].map((i) => new anchor.BN(Date.now() + 5400 + i));
+            const signature = await authorizeBatch(nonces.map((nonce) => ({ amount: pricePerCall, nonce })));
+
+            for (const nonce of nonces) {
+                const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+                expect(auth.nonce.toString()).to.equal(nonce.toString());
+                expect(auth.amount.toNumber()).to.equal(pricePerCall.toNumber());
+                expect(auth.used).to.equal(false);
+                expect(auth.verificationMethod).to.equal(verificationEmbedded);
+                expect(auth.verifierRef.toBase58()).to.equal(vkPdaFor(paymentPolicyCircuit).toBase58());
+            }
+            await record(nonces[1]);
+
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const created = [...parser.parseLogs(tx!.meta!.logMessages!)].filter((e) => e.name === "AuthorizationCreated");
+            expect(created.map((e) => e.data.nonce.toString())).to.deep.equal(nonces.map((n) => n.toString()));
+        });
+
+        it("rejects batches over MAX_AUTHORIZATION_BATCH", async () => {
+            const payments = [0, 1, 2, 3, 4, 5].map((i) => ({
+                amount: pricePerCall,
+                nonce: new anchor.BN(Date.now() + 5410 + i),
+            }));
+            try {
+                // Empty proofs and no nullifiers keep the transaction under
+                // the size limit; the batch size is checked first
+                await authorizeBatch(payments, 0, false);
+                expect.fail("Should have thrown BatchTooLarge error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("BatchTooLarge");
+            }
+        });
+
+        it("is atomic when one entry is invalid", async () => {
+            const valid = new anchor.BN(Date.now() + 5420);
+            try {
+                await authorizeBatch([
+                    { amount: pricePerCall, nonce: valid },
+                    { amount: new anchor.BN(1), nonce: new anchor.BN(Date.now() + 5421) },
+                ]);
+                expect.fail("Should have thrown AmountBelowMeterPrice error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AmountBelowMeterPrice");
+            }
+            expect(await provider.connection.getAccountInfo(authPdaFor(valid))).to.equal(null);
+        });
+    });
+
+    // =========================================================================
+    // TEST 55: partial consumption
+    // =========================================================================
+    describe("partial consumption", () => {
+        const allowanceMeterId = Keypair.generate();
+        let allowanceMeterPda: PublicKey;
+        const allowance = pricePerCall.muln(4);
+
+        const recordPartial = (nonce: anchor.BN, amount: anchor.BN) =>
+            program.methods
+                .recordMeterPayment(nonce, amount, false, noExternalId, noPreimage, noReceipt)
+                .accounts({
+                    recorder: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: allowanceMeterPda,
+                    meterCounters: countersPdaFor(allowanceMeterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, allowanceMeterPda),
+                    meterUsage: usagePdaFor(allowanceMeterPda),
+                    config: configPda,
+                    escrow: null,
+                    merchantTokenAccount: null,
+                    tokenProgram: null,
+                    receipt: null,
+                    payer: null,
+                    systemProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc({ commitment: "confirmed" });
+
+        before(async () => {
+            [allowanceMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    allowanceMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: allowanceMeterId.publicKey,
+                    meter: allowanceMeterPda,
+                    meterCounters: countersPdaFor(allowanceMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+            // Allow authorizations above the price so there is something to split
+            await program.methods
+                .updateMeter(pricePerCall, allowedCategory, false, noFreeCalls, meterName, endpointHash, allowance, anyProofVersion)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: allowanceMeterId.publicKey,
+                    meter: allowanceMeterPda,
+                    config: configPda,
+                })
+                .rpc();
+        });
+
+        it("charges part of an authorization and keeps the rest", async () => {
+            const nonce = new anchor.BN(Date.now() + 5500);
+            await authorize(nonce, allowance, allowanceMeterPda);
+
+            const signature = await recordPartial(nonce, pricePerCall);
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const paid = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "MeterPaid");
+            expect(paid!.data.amount.toNumber()).to.equal(pricePerCall.toNumber());
+            expect(paid!.data.amountRemaining.toNumber()).to.equal(allowance.toNumber() - pricePerCall.toNumber());
+            expect(paid!.data.verificationMethod).to.equal(verificationEmbedded);
+            expect(paid!.data.verifierRef.toBase58()).to.equal(vkPdaFor(paymentPolicyCircuit).toBase58());
+
+            let auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, allowanceMeterPda));
+            expect(auth.used).to.equal(false);
+
+            // consume_all takes whatever is left
+            await record(nonce, allowanceMeterPda);
+            auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, allowanceMeterPda));
+            expect(auth.amountRemaining.toNumber()).to.equal(0);
+            expect(auth.used).to.equal(true);
+        });
+
+        it("rejects amounts above what is left", async () => {
+            const nonce = new anchor.BN(Date.now() + 5501);
+            await authorize(nonce, allowance, allowanceMeterPda);
+
+            try {
+                await recordPartial(nonce, allowance.addn(1));
+                expect.fail("Should have thrown InvalidConsumeAmount error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidConsumeAmount");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 56: streaming authorizations
+    // =========================================================================
+    describe("streaming authorizations", () => {
+        const ratePerSlot = new anchor.BN(1_000);
+
+        const authorizeStream = async (nonce: anchor.BN, cap: anchor.BN, rate: anchor.BN) => {
+            const currentSlot = await provider.connection.getSlot();
+            await program.methods
+                .authorizeStreamingPayment(
+                    policyId,
+                    cap,
+                    rate,
+                    allowedCategory,
+                    nonce,
+                    new anchor.BN(currentSlot + 100),
+                    [...Buffer.alloc(64)],
+                    noRequestId
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(meterPda),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(meterPda),
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, meterPda),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    config: configPda,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    nullifier: await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, cap, policyPda),
+                })
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        const recordStream = (nonce: anchor.BN, amount: anchor.BN, all: boolean) =>
+            program.methods
+                .recordMeterPayment(nonce, amount, all, noExternalId, noPreimage, noReceipt)
+                .accounts({
+                    recorder: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: meterPda,
+                    meterCounters: countersPdaFor(meterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, meterPda),
+                    meterUsage: usagePdaFor(meterPda),
+                    config: configPda,
+                    escrow: null,
+                    merchantTokenAccount: null,
+                    tokenProgram: null,
+                    receipt: null,
+                    payer: null,
+                    systemProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc({ commitment: "confirmed" });
+
+        it("rejects consumption that outpaces unlocking", async () => {
+            const nonce = new anchor.BN(Date.now() + 5600);
+            await authorizeStream(nonce, pricePerCall, ratePerSlot);
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, meterPda));
+            expect(auth.kind).to.equal(1);
+            expect(auth.ratePerSlot.toNumber()).to.equal(ratePerSlot.toNumber());
+            expect(auth.verificationMethod).to.equal(verificationEmbedded);
+            expect(auth.verifierRef.toBase58()).to.equal(vkPdaFor(paymentPolicyCircuit).toBase58());
+
+            // Only a few slots have passed, far from the 50 needed for the cap
+            try {
+                await recordStream(nonce, pricePerCall, false);
+                expect.fail("Should have thrown AmountNotYetUnlocked error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AmountNotYetUnlocked");
+            }
+        });
+
+        it("consume_all charges what has unlocked so far", async () => {
+            const nonce = new anchor.BN(Date.now() + 5601);
+            await authorizeStream(nonce, pricePerCall, ratePerSlot);
+            await new Promise((resolve) => setTimeout(resolve, 2000));
+
+            await recordStream(nonce, fullAmount, consumeAll);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, meterPda));
+            const consumed = pricePerCall.toNumber() - auth.amountRemaining.toNumber();
+            expect(consumed).to.be.greaterThan(0);
+            expect(consumed).to.be.lessThan(pricePerCall.toNumber());
+            expect(consumed % ratePerSlot.toNumber()).to.equal(0);
+            expect(auth.used).to.equal(false);
+        });
+
+        it("rejects a rate above the cap", async () => {
+            const nonce = new anchor.BN(Date.now() + 5602);
+            try {
+                await authorizeStream(nonce, pricePerCall, pricePerCall.addn(1));
+                expect.fail("Should have thrown InvalidStreamRate error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidStreamRate");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 57: AuthorizationCreated event
+    // =========================================================================
+    describe("AuthorizationCreated event", () => {
+        it("decodes from the authorize transaction logs", async () => {
+            const nonce = new anchor.BN(Date.now() + 5700);
+            const authPda = authPdaFor(nonce, agentKeypair.publicKey, meterPda);
+            const expiresAtSlot = new anchor.BN((await provider.connection.getSlot()) + 100);
+
+            const signature = await program.methods
+                .authorizePaymentWithProof(
+                    policyId,
+                    pricePerCall,
+                    singleCall,
+                    allowedCategory,
+                    nonce,
+                    expiresAtSlot,
+                    [...Buffer.alloc(64)],
+                    noRequestId,
+                    expireBySlot,
+                    anyConsumer,
+                    noHashLock,
+                    noTip
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(meterPda),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(meterPda),
+                    authorization: authPda,
+                    config: configPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    nullifier: await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, pricePerCall, policyPda),
+                    vault: null,
+                    escrow: null,
+                    mint: null,
+                    tokenProgram: null,
+                    associatedTokenProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc({ commitment: "confirmed" });
+
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const created = [...parser.parseLogs(tx!.meta!.logMessages!)].filter((e) => e.name === "AuthorizationCreated");
+            expect(created).to.have.length(1);
+
+            const event = created[0].data;
+            expect(event.authorization.toBase58()).to.equal(authPda.toBase58());
+            expect(event.agent.toBase58()).to.equal(agentKeypair.publicKey.toBase58());
+            expect(event.meter.toBase58()).to.equal(meterPda.toBase58());
+            expect(event.amount.toNumber()).to.equal(pricePerCall.toNumber());
+            expect(event.category).to.equal(allowedCategory);
+            expect(event.nonce.toString()).to.equal(nonce.toString());
+            expect(event.expiresAtSlot.toString()).to.equal(expiresAtSlot.toString());
+            expect(event.zkVerified).to.equal(true);
+            expect(event.verificationMethod).to.equal(verificationEmbedded);
+            expect(event.verifierRef.toBase58()).to.equal(vkPdaFor(paymentPolicyCircuit).toBase58());
+        });
+    });
+
+    // =========================================================================
+    // TEST 58: anyone may record a payment
+    // =========================================================================
+    describe("record_meter_payment signers", () => {
+        const recordAs = (nonce: anchor.BN, recorder: Keypair | null, agent: PublicKey = agentKeypair.publicKey) =>
+            program.methods
+                .recordMeterPayment(nonce, fullAmount, consumeAll, noExternalId, noPreimage, noReceipt)
+                .accounts({
+                    recorder: recorder ? recorder.publicKey : provider.wallet.publicKey,
+                    agent,
+                    meter: meterPda,
+                    meterCounters: countersPdaFor(meterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, meterPda),
+                    meterUsage: usagePdaFor(meterPda),
+                    config: configPda,
+                    escrow: null,
+                    merchantTokenAccount: null,
+                    tokenProgram: null,
+                    receipt: null,
+                    payer: null,
+                    systemProgram: null,
+                })
+                .signers(recorder ? [recorder] : [])
+                .rpc({ commitment: "confirmed" });
+
+        const recordedBy = async (signature: string) => {
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const paid = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "MeterPaid");
+            return paid!.data.recordedBy.toBase58();
+        };
+
+        it("lets the merchant record", async () => {
+            const nonce = new anchor.BN(Date.now() + 5800);
+            await authorize(nonce, pricePerCall);
+
+            // The provider wallet is the meter's authority
+            const signature = await recordAs(nonce, null);
+            expect(await recordedBy(signature)).to.equal(provider.wallet.publicKey.toBase58());
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, meterPda));
+            expect(auth.used).to.equal(true);
+        });
+
+        it("lets a third party record", async () => {
+            const nonce = new anchor.BN(Date.now() + 5801);
+            await authorize(nonce, pricePerCall);
+
+            const relayer = Keypair.generate();
+            const signature = await recordAs(nonce, relayer);
+            expect(await recordedBy(signature)).to.equal(relayer.publicKey.toBase58());
+        });
+
+        it("rejects an agent account that doesn't match the seeds", async () => {
+            const nonce = new anchor.BN(Date.now() + 5802);
+            await authorize(nonce, pricePerCall);
+
+            try {
+                await recordAs(nonce, null, Keypair.generate().publicKey);
+                expect.fail("Should have thrown ConstraintSeeds error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("ConstraintSeeds");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 59: price snapshot on authorizations
+    // =========================================================================
+    describe("price snapshot", () => {
+        const repricedMeterId = Keypair.generate();
+        let repricedMeterPda: PublicKey;
+
+        const setPrice = (price: anchor.BN) =>
+            program.methods
+                .updateMeter(price, allowedCategory, false, noFreeCalls, meterName, endpointHash, noMeterCap, anyProofVersion)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: repricedMeterId.publicKey,
+                    meter: repricedMeterPda,
+                    config: configPda,
+                })
+                .rpc();
+
+        before(async () => {
+            [repricedMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    repricedMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: repricedMeterId.publicKey,
+                    meter: repricedMeterPda,
+                    meterCounters: countersPdaFor(repricedMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("reports a price change between authorize and record", async () => {
+            const nonce = new anchor.BN(Date.now() + 5900);
+            await authorize(nonce, pricePerCall, repricedMeterPda);
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, repricedMeterPda));
+            expect(auth.priceAtAuthorization.toNumber()).to.equal(pricePerCall.toNumber());
+
+            // Price cuts take effect immediately
+            const newPrice = pricePerCall.divn(2);
+            await setPrice(newPrice);
+
+            const signature = await program.methods
+                .recordMeterPayment(nonce, fullAmount, consumeAll, noExternalId, noPreimage, noReceipt)
+                .accounts({
+                    recorder: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: repricedMeterPda,
+                    meterCounters: countersPdaFor(repricedMeterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, repricedMeterPda),
+                    meterUsage: usagePdaFor(repricedMeterPda),
+                    config: configPda,
+                    escrow: null,
+                    merchantTokenAccount: null,
+                    tokenProgram: null,
+                    receipt: null,
+                    payer: null,
+                    systemProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc({ commitment: "confirmed" });
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const paid = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "MeterPaid");
+
+            // The payment still follows the authorized price
+            expect(paid!.data.amount.toNumber()).to.equal(pricePerCall.toNumber());
+            expect(paid!.data.authorizedAmount.toNumber()).to.equal(pricePerCall.toNumber());
+            expect(paid!.data.priceAtAuthorization.toNumber()).to.equal(pricePerCall.toNumber());
+            expect(paid!.data.currentPrice.toNumber()).to.equal(newPrice.toNumber());
+            expect(paid!.data.priceChanged).to.equal(true);
+        });
+
+        it("leaves price_changed unset when the price held", async () => {
+            const nonce = new anchor.BN(Date.now() + 5901);
+            const price = pricePerCall.divn(2);
+            await authorize(nonce, price, repricedMeterPda);
+
+            const signature = await program.methods
+                .recordMeterPayment(nonce, fullAmount, consumeAll, noExternalId, noPreimage, noReceipt)
+                .accounts({
+                    recorder: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: repricedMeterPda,
+                    meterCounters: countersPdaFor(repricedMeterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, repricedMeterPda),
+                    meterUsage: usagePdaFor(repricedMeterPda),
+                    config: configPda,
+                    escrow: null,
+                    merchantTokenAccount: null,
+                    tokenProgram: null,
+                    receipt: null,
+                    payer: null,
+                    systemProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc({ commitment: "confirmed" });
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const paid = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "MeterPaid");
+            expect(paid!.data.priceChanged).to.equal(false);
+        });
+    });
+
+    // =========================================================================
+    // TEST 60: request ids on authorizations and payments
+    // =========================================================================
+    describe("request ids", () => {
+        const requestId = Array.from({ length: 32 }, (_, i) => i + 1);
+
+        it("carries the request id from authorization to payment", async () => {
+            const nonce = new anchor.BN(Date.now() + 6000);
+            const authPda = authPdaFor(nonce, agentKeypair.publicKey, meterPda);
+            const currentSlot = await provider.connection.getSlot();
+            const authSignature = await program.methods
+                .authorizePaymentWithProof(
+                    policyId,
+                    pricePerCall,
+                    singleCall,
+                    allowedCategory,
+                    nonce,
+                    new anchor.BN(currentSlot + 100),
+                    [...Buffer.alloc(64)],
+                    requestId,
+                    expireBySlot,
+                    anyConsumer,
+                    noHashLock,
+                    noTip
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(meterPda),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(meterPda),
+                    authorization: authPda,
+                    config: configPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    nullifier: await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, pricePerCall, policyPda),
+                    vault: null,
+                    escrow: null,
+                    mint: null,
+                    tokenProgram: null,
+                    associatedTokenProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc({ commitment: "confirmed" });
+
+            // Authorization::LEN
+            const info = await provider.connection.getAccountInfo(authPda);
+            expect(info!.data.length).to.equal(493);
+            const auth = await program.account.authorization.fetch(authPda);
+            expect(auth.requestId).to.deep.equal(requestId);
+
+            const recordSignature = await program.methods
+                .recordMeterPayment(nonce, fullAmount, consumeAll, noExternalId, noPreimage, noReceipt)
+                .accounts({
+                    recorder: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: meterPda,
+                    meterCounters: countersPdaFor(meterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPda,
+                    meterUsage: usagePdaFor(meterPda),
+                    config: configPda,
+                    escrow: null,
+                    merchantTokenAccount: null,
+                    tokenProgram: null,
+                    receipt: null,
+                    payer: null,
+                    systemProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc({ commitment: "confirmed" });
+
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const eventsOf = async (signature: string) => {
+                const tx = await provider.connection.getTransaction(signature, {
+                    commitment: "confirmed",
+                    maxSupportedTransactionVersion: 0,
+                });
+                return [...parser.parseLogs(tx!.meta!.logMessages!)];
+            };
+            const created = (await eventsOf(authSignature)).find((e) => e.name === "AuthorizationCreated");
+            expect(created!.data.requestId).to.deep.equal(requestId);
+            const paid = (await eventsOf(recordSignature)).find((e) => e.name === "MeterPaid");
+            expect(paid!.data.requestId).to.deep.equal(requestId);
+        });
+    });
+
+    // =========================================================================
+    // TEST 61: wall-clock expiry
+    // =========================================================================
+    describe("expiry kinds", () => {
+        const expireByUnix = 1; // expiry_kinds::UNIX
+        const maxTtlSecs = 600; // configParams().maxAuthorizationTtlSecs
+
+        const authorizeExpiring = async (nonce: anchor.BN, expiresAt: number, expiryKind: number) =>
+            program.methods
+                .authorizePaymentWithProof(
+                    policyId,
+                    pricePerCall,
+                    singleCall,
+                    allowedCategory,
+                    nonce,
+                    new anchor.BN(expiresAt),
+                    [...Buffer.alloc(64)],
+                    noRequestId,
+                    expiryKind,
+                    anyConsumer,
+                    noHashLock,
+                    noTip
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(meterPda),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(meterPda),
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, meterPda),
+                    config: configPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    nullifier: await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, pricePerCall, policyPda),
+                    vault: null,
+                    escrow: null,
+                    mint: null,
+                    tokenProgram: null,
+                    associatedTokenProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+        const chainTime = async () => {
+            const slot = await provider.connection.getSlot();
+            return (await provider.connection.getBlockTime(slot))!;
+        };
+
+        const expectInvalidExpiry = async (nonce: anchor.BN, expiresAt: number, expiryKind: number) => {
+            try {
+                await authorizeExpiring(nonce, expiresAt, expiryKind);
+                expect.fail("Should have thrown InvalidExpiry error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidExpiry");
+            }
+        };
+
+        it("bounds slot expiry", async () => {
+            const currentSlot = await provider.connection.getSlot();
+            await expectInvalidExpiry(new anchor.BN(Date.now() + 6100), currentSlot - 1, expireBySlot);
+
+            const nonce = new anchor.BN(Date.now() + 6101);
+            await authorizeExpiring(nonce, currentSlot + 100, expireBySlot);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, meterPda));
+            expect(auth.expiryKind).to.equal(expireBySlot);
+            expect(auth.expiresAtUnix.toNumber()).to.equal(0);
+        });
+
+        it("bounds unix expiry by the Config TTL", async () => {
+            const now = await chainTime();
+            await expectInvalidExpiry(new anchor.BN(Date.now() + 6102), now - 1, expireByUnix);
+            await expectInvalidExpiry(new anchor.BN(Date.now() + 6103), now + maxTtlSecs + 60, expireByUnix);
+
+            const nonce = new anchor.BN(Date.now() + 6104);
+            await authorizeExpiring(nonce, now + 60, expireByUnix);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, meterPda));
+            expect(auth.expiryKind).to.equal(expireByUnix);
+            expect(auth.expiresAtUnix.toNumber()).to.equal(now + 60);
+
+            await record(nonce);
+        });
+
+        it("rejects a payment after the unix expiry", async () => {
+            const nonce = new anchor.BN(Date.now() + 6105);
+            await authorizeExpiring(nonce, (await chainTime()) + 2, expireByUnix);
+
+            // The slot backstop is far off; only the wall clock has passed
+            await new Promise((resolve) => setTimeout(resolve, 4000));
+            try {
+                await record(nonce);
+                expect.fail("Should have thrown AuthorizationExpired error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationExpired");
+            }
+        });
+
+        it("rejects unknown expiry kinds", async () => {
+            try {
+                await authorizeExpiring(new anchor.BN(Date.now() + 6106), (await chainTime()) + 60, 2);
+                expect.fail("Should have thrown InvalidExpiryKind error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidExpiryKind");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 62: authorizations bound to a consumer
+    // =========================================================================
+    describe("consumer-bound authorizations", () => {
+        const facilitator = Keypair.generate();
+
+        const authorizeFor = async (nonce: anchor.BN, consumer: PublicKey) => {
+            const currentSlot = await provider.connection.getSlot();
+            await program.methods
+                .authorizePaymentWithProof(
+                    policyId,
+                    pricePerCall,
+                    singleCall,
+                    allowedCategory,
+                    nonce,
+                    new anchor.BN(currentSlot + 100),
+                    [...Buffer.alloc(64)],
+                    noRequestId,
+                    expireBySlot,
+                    consumer,
+                    noHashLock,
+                    noTip
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(meterPda),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(meterPda),
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, meterPda),
+                    config: configPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    nullifier: await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, pricePerCall, policyPda),
+                    vault: null,
+                    escrow: null,
+                    mint: null,
+                    tokenProgram: null,
+                    associatedTokenProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        const recordBy = (nonce: anchor.BN, recorder: Keypair) =>
+            program.methods
+                .recordMeterPayment(nonce, fullAmount, consumeAll, noExternalId, noPreimage, noReceipt)
+                .accounts({
+                    recorder: recorder.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: meterPda,
+                    meterCounters: countersPdaFor(meterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, meterPda),
+                    meterUsage: usagePdaFor(meterPda),
+                    config: configPda,
+                    escrow: null,
+                    merchantTokenAccount: null,
+                    tokenProgram: null,
+                    receipt: null,
+                    payer: null,
+                    systemProgram: null,
+                })
+                .signers([recorder])
+                .rpc();
+
+        it("lets only the consumer record", async () => {
+            const nonce = new anchor.BN(Date.now() + 6200);
+            await authorizeFor(nonce, facilitator.publicKey);
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, meterPda));
+            expect(auth.consumer.toBase58()).to.equal(facilitator.publicKey.toBase58());
+
+            for (const other of [agentKeypair, Keypair.generate()]) {
+                try {
+                    await recordBy(nonce, other);
+                    expect.fail("Should have thrown UnauthorizedConsumer error");
+                } catch (err: any) {
+                    expect(err.error.errorCode.code).to.equal("UnauthorizedConsumer");
+                }
+            }
+
+            await recordBy(nonce, facilitator);
+        });
+
+        it("lets anyone record without a consumer", async () => {
+            const nonce = new anchor.BN(Date.now() + 6201);
+            await authorizeFor(nonce, anyConsumer);
+            await recordBy(nonce, Keypair.generate());
+        });
+    });
+
+    // =========================================================================
+    // TEST 63: authorize_payment_simple
+    // =========================================================================
+    describe("authorize_payment_simple", () => {
+        const zkMeterId = Keypair.generate();
+        let zkMeterPda: PublicKey;
+
+        const authorizeSimple = async (nonce: anchor.BN, meter: PublicKey) => {
+            const currentSlot = await provider.connection.getSlot();
+            await program.methods
+                .authorizePaymentSimple(
+                    policyId,
+                    pricePerCall,
+                    singleCall,
+                    allowedCategory,
+                    nonce,
+                    new anchor.BN(currentSlot + 100),
+                    noRequestId,
+                    expireBySlot,
+                    anyConsumer
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(meter),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(meter),
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, meter),
+                    config: configPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    verifierProgram: program.programId,
+                    verifyingKey: null,
+                    vault: null,
+                    escrow: null,
+                    mint: null,
+                    tokenProgram: null,
+                    associatedTokenProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        before(async () => {
+            [zkMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    zkMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, true, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: zkMeterId.publicKey,
+                    meter: zkMeterPda,
+                    meterCounters: countersPdaFor(zkMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        after(async () => {
+            await setPolicyFlags();
+        });
+
+        for (const policyZk of [false, true]) {
+            for (const meterZk of [false, true]) {
+                const expected = policyZk ? "ZkRequiredByPolicy" : meterZk ? "ZkRequiredByMeter" : null;
+
+                it(`${expected ? "rejects" : "accepts"} a proof-less payment with policy=${policyZk}, meter=${meterZk}`, async () => {
+                    await setPolicyFlags({ alwaysRequireZk: policyZk });
+                    const meter = meterZk ? zkMeterPda : meterPda;
+                    const nonce = new anchor.BN(Date.now() + 6300);
+
+                    try {
+                        await authorizeSimple(nonce, meter);
+                        if (expected) expect.fail(`Should have thrown ${expected} error`);
+                    } catch (err: any) {
+                        if (!expected) throw err;
+                        expect(err.error.errorCode.code).to.equal(expected);
+                        return;
+                    }
+
+                    const auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, meter));
+                    expect(auth.zkVerified).to.equal(false);
+                    expect(auth.verificationMethod).to.equal(verificationNone);
+                    expect(auth.verifierRef.toBase58()).to.equal(PublicKey.default.toBase58());
+                    await record(nonce, meter);
+                });
+            }
+        }
+
+        it("still accepts proofs on meters that don't require them", async () => {
+            const nonce = new anchor.BN(Date.now() + 6301);
+            await authorize(nonce, pricePerCall);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, meterPda));
+            expect(auth.zkVerified).to.equal(true);
+        });
+    });
+
+    // =========================================================================
+    // TEST 64: auto-assigned nonces
+    // =========================================================================
+    describe("authorize_payment_auto_nonce", () => {
+        let nonceCounterPda: PublicKey;
+
+        before(() => {
+            [nonceCounterPda] = PublicKey.findProgramAddressSync(
+                [Buffer.from("nonce"), agentKeypair.publicKey.toBuffer(), meterPda.toBuffer()],
+                program.programId
+            );
+        });
+
+        const nextNonce = async () => {
+            const info = await provider.connection.getAccountInfo(nonceCounterPda);
+            if (info === null) return new anchor.BN(0);
+            return (await program.account.nonceCounter.fetch(nonceCounterPda)).nextNonce;
+        };
+
+        const authorizeAuto = async (nonce: anchor.BN) => {
+            const currentSlot = await provider.connection.getSlot();
+            return program.methods
+                .authorizePaymentAutoNonce(
+                    policyId,
+                    pricePerCall,
+                    singleCall,
+                    allowedCategory,
+                    new anchor.BN(currentSlot + 100),
+                    [...Buffer.alloc(64)],
+                    noRequestId
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(meterPda),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(meterPda),
+                    nonceCounter: nonceCounterPda,
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, meterPda),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    config: configPda,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    nullifier: await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, pricePerCall, policyPda),
+                })
+                .signers([agentKeypair])
+                .rpc({ commitment: "confirmed" });
+        };
+
+        it("hands out strictly increasing nonces", async () => {
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const nonces: number[] = [];
+
+            for (let i = 0; i < 3; i++) {
+                const signature = await authorizeAuto(await nextNonce());
+                const tx = await provider.connection.getTransaction(signature, {
+                    commitment: "confirmed",
+                    maxSupportedTransactionVersion: 0,
+                });
+                const created = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "AuthorizationCreated");
+                expect(created!.data.verificationMethod).to.equal(verificationEmbedded);
+                nonces.push(created!.data.nonce.toNumber());
+            }
+
+            expect(nonces[1]).to.equal(nonces[0] + 1);
+            expect(nonces[2]).to.equal(nonces[1] + 1);
+            expect((await nextNonce()).toNumber()).to.equal(nonces[2] + 1);
+        });
+
+        it("rejects a transaction built against a stale counter", async () => {
+            const stale = await nextNonce();
+            await authorizeAuto(stale);
+
+            try {
+                await authorizeAuto(stale);
+                expect.fail("Should have thrown ConstraintSeeds error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("ConstraintSeeds");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 65: sweep_expired_authorizations
+    // =========================================================================
+    describe("sweep_expired_authorizations", () => {
+        const sweep = (authorizations: PublicKey[], rentPayer: PublicKey = provider.wallet.publicKey) =>
+            program.methods
+                .sweepExpiredAuthorizations()
+                .accounts({ cranker: provider.wallet.publicKey, config: configPda })
+                .remainingAccounts(authorizations.flatMap((pubkey) => [
+                    { pubkey, isSigner: false, isWritable: true },
+                    { pubkey: rentPayer, isSigner: false, isWritable: true },
+                ]))
+                .rpc({ commitment: "confirmed" });
+
+        const sweptCount = async (signature: string) => {
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const swept = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "AuthorizationsSwept");
+            return swept!.data.count;
+        };
+
+        it("closes dead authorizations and skips the rest", async () => {
+            const base = Date.now() + 6500;
+            const expired = [0, 1].map((i) => new anchor.BN(base + i));
+            const used = new anchor.BN(base + 2);
+            const live = new anchor.BN(base + 3);
+
+            for (const nonce of expired) {
+                await authorize(nonce, pricePerCall, meterPda, null, Buffer.alloc(64), 2);
+            }
+            await authorize(used, pricePerCall);
+            await record(used);
+            await authorize(live, pricePerCall);
+            await new Promise(resolve => setTimeout(resolve, 3000));
+
+            const before = await provider.connection.getBalance(provider.wallet.publicKey);
+            // The policy account is owned by the program but isn't an Authorization
+            const signature = await sweep([...expired, used, live].map((n) => authPdaFor(n)).concat([policyPda]));
+            expect(await sweptCount(signature)).to.equal(3);
+
+            for (const nonce of [...expired, used]) {
+                expect(await provider.connection.getAccountInfo(authPdaFor(nonce))).to.equal(null);
+            }
+            expect(await provider.connection.getAccountInfo(authPdaFor(live))).to.not.equal(null);
+            expect(await provider.connection.getAccountInfo(policyPda)).to.not.equal(null);
+            // Rent of three authorizations came back, minus the fee
+            expect(await provider.connection.getBalance(provider.wallet.publicKey)).to.be.greaterThan(before);
+        });
+
+        it("skips authorizations whose rent payer doesn't match", async () => {
+            const nonce = new anchor.BN(Date.now() + 6510);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+
+            const signature = await sweep([authPdaFor(nonce)], Keypair.generate().publicKey);
+            expect(await sweptCount(signature)).to.equal(0);
+            expect(await provider.connection.getAccountInfo(authPdaFor(nonce))).to.not.equal(null);
+        });
+
+        it("rejects more than MAX_SWEEP_AUTHORIZATIONS entries", async () => {
+            const authorizations = Array.from({ length: 21 }, (_, i) => authPdaFor(new anchor.BN(Date.now() + 6520 + i)));
+            try {
+                await sweep(authorizations);
+                expect.fail("Should have thrown BatchTooLarge error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("BatchTooLarge");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 66: escrow settlement
+    // =========================================================================
+    describe("escrow settlement", () => {
+        const escrowMeterId = Keypair.generate();
+        const payer = (provider.wallet as anchor.Wallet).payer;
+        let escrowMeterPda: PublicKey;
+        let mint: PublicKey;
+        let vaultPda: PublicKey;
+        let merchantAccount: PublicKey;
+
+        const escrowFor = (nonce: anchor.BN) =>
+            getAssociatedTokenAddressSync(mint, authPdaFor(nonce, agentKeypair.publicKey, escrowMeterPda), true);
+
+        const balance = async (account: PublicKey) => Number((await getAccount(provider.connection, account)).amount);
+
+        const authorizeEscrowed = async (nonce: anchor.BN, ttlSlots: number = 100) => {
+            const currentSlot = await provider.connection.getSlot();
+            await program.methods
+                .authorizePaymentWithProof(
+                    policyId,
+                    pricePerCall,
+                    singleCall,
+                    allowedCategory,
+                    nonce,
+                    new anchor.BN(currentSlot + ttlSlots),
+                    [...Buffer.alloc(64)],
+                    noRequestId,
+                    expireBySlot,
+                    anyConsumer,
+                    noHashLock,
+                    noTip
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: escrowMeterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(escrowMeterPda),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(escrowMeterPda),
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, escrowMeterPda),
+                    config: configPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    nullifier: await nullifierPdaFor(agentKeypair.publicKey, escrowMeterPda, nonce, pricePerCall, policyPda),
+                    vault: vaultPda,
+                    escrow: escrowFor(nonce),
+                    mint,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+                })
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        before(async () => {
+            mint = await createMint(provider.connection, payer, provider.wallet.publicKey, null, 6);
+            merchantAccount = await createAccount(provider.connection, payer, mint, provider.wallet.publicKey, Keypair.generate());
+
+            [escrowMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    escrowMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, mint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: escrowMeterId.publicKey,
+                    meter: escrowMeterPda,
+                    meterCounters: countersPdaFor(escrowMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+            await program.methods
+                .setMeterSettlementMode(1)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: escrowMeterId.publicKey,
+                    meter: escrowMeterPda,
+                })
+                .rpc();
+
+            [vaultPda] = PublicKey.findProgramAddressSync(
+                [Buffer.from("vault"), agentKeypair.publicKey.toBuffer(), mint.toBuffer()],
+                program.programId
+            );
+            await program.methods
+                .openAgentVault()
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    mint,
+                    vault: vaultPda,
+                    payer: provider.wallet.publicKey,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+            await mintTo(provider.connection, payer, mint, vaultPda, payer, 1_000_000);
+        });
+
+        it("escrows the amount and pays the merchant on record", async () => {
+            const nonce = new anchor.BN(Date.now() + 6600);
+            await authorizeEscrowed(nonce);
+
+            const authPda = authPdaFor(nonce, agentKeypair.publicKey, escrowMeterPda);
+            const auth = await program.account.authorization.fetch(authPda);
+            expect(auth.escrow.toBase58()).to.equal(escrowFor(nonce).toBase58());
+            expect(await balance(escrowFor(nonce))).to.equal(pricePerCall.toNumber());
+            expect(await balance(vaultPda)).to.equal(1_000_000 - pricePerCall.toNumber());
+
+            const signature = await program.methods
+                .recordMeterPayment(nonce, fullAmount, consumeAll, noExternalId, noPreimage, noReceipt)
+                .accounts({
+                    recorder: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: escrowMeterPda,
+                    meterCounters: countersPdaFor(escrowMeterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPda,
+                    meterUsage: usagePdaFor(escrowMeterPda),
+                    config: configPda,
+                    escrow: escrowFor(nonce),
+                    merchantTokenAccount: merchantAccount,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    receipt: null,
+                    payer: null,
+                    systemProgram: null,
+                })
+                .signers([agentKeypair])
+                .rpc({ commitment: "confirmed" });
+
+            expect(await balance(merchantAccount)).to.equal(pricePerCall.toNumber());
+            expect(await provider.connection.getAccountInfo(escrowFor(nonce))).to.equal(null);
+            const after = await program.account.authorization.fetch(authPda);
+            expect(after.used).to.equal(true);
+            expect(after.escrow.toBase58()).to.equal(PublicKey.default.toBase58());
+            expect(after.settledFromEscrow).to.equal(true);
+
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const paid = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "MeterPaid");
+            expect(paid!.data.settledOnChain).to.equal(true);
+        });
+
+        it("accepts an escrow created ahead of the authorization", async () => {
+            const nonce = new anchor.BN(Date.now() + 6602);
+            const authPda = authPdaFor(nonce, agentKeypair.publicKey, escrowMeterPda);
+            // Anyone can create the ATA first; that mustn't block the payment
+            await getOrCreateAssociatedTokenAccount(provider.connection, payer, mint, authPda, true);
+
+            await authorizeEscrowed(nonce);
+            expect(await balance(escrowFor(nonce))).to.equal(pricePerCall.toNumber());
+        });
+
+        it("rejects a settlement failure report once paid from escrow", async () => {
+            const nonce = new anchor.BN(Date.now() + 6605);
+            await authorizeEscrowed(nonce);
+            const authPda = authPdaFor(nonce, agentKeypair.publicKey, escrowMeterPda);
+            await program.methods
+                .recordMeterPayment(nonce, fullAmount, consumeAll)
+                .accounts({
+                    recorder: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: escrowMeterPda,
+                    meterCounters: countersPdaFor(escrowMeterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPda,
+                    meterUsage: usagePdaFor(escrowMeterPda),
+                    config: configPda,
+                    escrow: escrowFor(nonce),
+                    merchantTokenAccount: merchantAccount,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            await program.methods
+                .updateConfig(configParams({ settlementAuthority: provider.wallet.publicKey }))
+                .accounts({ admin: provider.wallet.publicKey, config: configPda })
+                .rpc();
+            try {
+                await program.methods
+                    .reportSettlementFailure(nonce, 7)
+                    .accounts({
+                        settlementAuthority: provider.wallet.publicKey,
+                        config: configPda,
+                        agent: agentKeypair.publicKey,
+                        meter: escrowMeterPda,
+                        authorization: authPda,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown SettledOnChain error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("SettledOnChain");
+            } finally {
+                await program.methods
+                    .updateConfig(configParams())
+                    .accounts({ admin: provider.wallet.publicKey, config: configPda })
+                    .rpc();
+            }
+
+            const auth = await program.account.authorization.fetch(authPda);
+            expect(auth.used).to.equal(true);
+        });
+
+        it("returns an expired authorization's escrow to the vault", async () => {
+            const nonce = new anchor.BN(Date.now() + 6610);
+            await authorizeEscrowed(nonce, 2);
+            const vaultBefore = await balance(vaultPda);
+            const authPda = authPdaFor(nonce, agentKeypair.publicKey, escrowMeterPda);
+
+            try {
+                await program.methods
+                    .closeAuthorization()
+                    .accounts({ authorization: authPda, rentPayer: provider.wallet.publicKey, config: configPda })
+                    .rpc();
+                expect.fail("Should have thrown EscrowStillFunded error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("EscrowStillFunded");
+            }
+
+            await new Promise(resolve => setTimeout(resolve, 3000));
+            await program.methods
+                .cancelEscrowedAuthorization()
+                .accounts({
+                    cranker: provider.wallet.publicKey,
+                    authorization: authPda,
+                    escrow: escrowFor(nonce),
+                    vault: vaultPda,
+                    config: configPda,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                })
+                .rpc();
+
+            expect(await balance(vaultPda)).to.equal(vaultBefore + pricePerCall.toNumber());
+            expect(await provider.connection.getAccountInfo(escrowFor(nonce))).to.equal(null);
+
+            await program.methods
+                .closeAuthorization()
+                .accounts({ authorization: authPda, rentPayer: provider.wallet.publicKey, config: configPda })
+                .rpc();
+            expect(await provider.connection.getAccountInfo(authPda)).to.equal(null);
+        });
+
+        it("requires the escrow accounts on escrow-mode meters", async () => {
+            try {
+                await authorize(new anchor.BN(Date.now() + 6620), pricePerCall, escrowMeterPda);
+                expect.fail("Should have thrown EscrowAccountsMissing error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("EscrowAccountsMissing");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 67: grace window for late records
+    // =========================================================================
+    describe("record grace window", () => {
+        const graceSlots = 25;
+
+        const updateGrace = (slots: number) =>
+            program.methods
+                .updateConfig(configParams({ graceSlots: slots }))
+                .accounts({
+                    admin: provider.wallet.publicKey,
+                    config: configPda,
+                })
+                .rpc();
+
+        const waitPastSlot = async (slot: number) => {
+            while ((await provider.connection.getSlot()) <= slot) {
+                await new Promise(resolve => setTimeout(resolve, 100));
+            }
+        };
+
+        const recordedInGrace = async (signature: string) => {
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const paid = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "MeterPaid");
+            return paid!.data.recordedInGrace;
+        };
+
+        before(async () => {
+            await updateGrace(graceSlots);
+        });
+
+        after(async () => {
+            await updateGrace(0);
+        });
+
+        it("flags records landing within expires_at_slot + grace_slots", async () => {
+            const nonce = new anchor.BN(Date.now() + 6700);
+            await authorize(nonce, pricePerCall, meterPda, null, Buffer.alloc(64), 2);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+
+            await waitPastSlot(auth.expiresAtSlot.toNumber());
+            const signature = await record(nonce);
+
+            expect(await recordedInGrace(signature)).to.equal(true);
+            expect((await program.account.authorization.fetch(authPdaFor(nonce))).used).to.equal(true);
+        });
+
+        it("doesn't flag records before expiry", async () => {
+            const nonce = new anchor.BN(Date.now() + 6710);
+            await authorize(nonce, pricePerCall);
+            expect(await recordedInGrace(await record(nonce))).to.equal(false);
+        });
+
+        it("rejects records one slot past the grace window", async () => {
+            const nonce = new anchor.BN(Date.now() + 6720);
+            await authorize(nonce, pricePerCall, meterPda, null, Buffer.alloc(64), 2);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+
+            await waitPastSlot(auth.expiresAtSlot.toNumber() + graceSlots);
+            try {
+                await record(nonce);
+                expect.fail("Should have thrown AuthorizationExpired error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationExpired");
+            }
+        });
+
+        it("keeps authorizations open for closing until the grace window ends", async () => {
+            const nonce = new anchor.BN(Date.now() + 6730);
+            await authorize(nonce, pricePerCall, meterPda, null, Buffer.alloc(64), 2);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+
+            await waitPastSlot(auth.expiresAtSlot.toNumber());
+            try {
+                await program.methods
+                    .closeAuthorization()
+                    .accounts({ authorization: authPdaFor(nonce), rentPayer: provider.wallet.publicKey, config: configPda })
+                    .rpc();
+                expect.fail("Should have thrown AuthorizationStillLive error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationStillLive");
+            }
+        });
+
+        it("caps grace_slots at MAX_GRACE_SLOTS", async () => {
+            try {
+                await updateGrace(151);
+                expect.fail("Should have thrown GraceSlotsTooLarge error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("GraceSlotsTooLarge");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 68: settlement confirmation
+    // =========================================================================
+    describe("settlement confirmation", () => {
+        const settlementKeypair = Keypair.generate();
+        const settlementRef = [...crypto.createHash("sha256").update("circle-transfer-1").digest()];
+
+        const settlementAccounts = (nonce: anchor.BN) => ({
+            settlementAuthority: settlementKeypair.publicKey,
+            config: configPda,
+            agent: agentKeypair.publicKey,
+            meter: meterPda,
+            authorization: authPdaFor(nonce),
+        });
+
+        const confirm = (nonce: anchor.BN) =>
+            program.methods
+                .confirmSettlement(nonce, settlementRef)
+                .accounts(settlementAccounts(nonce))
+                .signers([settlementKeypair])
+                .rpc();
+
+        const fail = (nonce: anchor.BN, reasonCode: number = 7) =>
+            program.methods
+                .reportSettlementFailure(nonce, reasonCode)
+                .accounts(settlementAccounts(nonce))
+                .signers([settlementKeypair])
+                .rpc();
+
+        const meterPaid = async (signature: string) => {
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            return [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "MeterPaid")!.data;
+        };
+
+        before(async () => {
+            await program.methods
+                .updateConfig(configParams({ settlementAuthority: settlementKeypair.publicKey }))
+                .accounts({ admin: provider.wallet.publicKey, config: configPda })
+                .rpc();
+        });
+
+        after(async () => {
+            await program.methods
+                .updateConfig(configParams())
+                .accounts({ admin: provider.wallet.publicKey, config: configPda })
+                .rpc();
+        });
+
+        it("confirms a used authorization once", async () => {
+            const nonce = new anchor.BN(Date.now() + 6800);
+            await authorize(nonce, pricePerCall);
+
+            try {
+                await confirm(nonce);
+                expect.fail("Should have thrown AuthorizationNotUsed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationNotUsed");
+            }
+
+            await record(nonce);
+            await confirm(nonce);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.settled).to.equal(true);
+            expect(auth.settlementRef).to.deep.equal(settlementRef);
+
+            try {
+                await confirm(nonce);
+                expect.fail("Should have thrown SettlementAlreadyConfirmed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("SettlementAlreadyConfirmed");
+            }
+        });
+
+        it("only accepts the configured settlement authority", async () => {
+            const nonce = new anchor.BN(Date.now() + 6810);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+
+            try {
+                await program.methods
+                    .confirmSettlement(nonce, settlementRef)
+                    .accounts({ ...settlementAccounts(nonce), settlementAuthority: agentKeypair.publicKey })
+                    .signers([agentKeypair])
+                    .rpc();
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+
+        it("re-opens a failed settlement for one retry without charging again", async () => {
+            const nonce = new anchor.BN(Date.now() + 6820);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+            const spent = (await program.account.agentPolicy.fetch(policyPda)).lifetimeSpent.toNumber();
+
+            await fail(nonce);
+            const reopened = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(reopened.used).to.equal(false);
+            expect(reopened.settlementFailed).to.equal(true);
+            expect(reopened.amountRemaining.toNumber()).to.equal(pricePerCall.toNumber());
+
+            const paid = await meterPaid(await record(nonce));
+            expect(paid.settlementRetry).to.equal(true);
+            expect(paid.amount.toNumber()).to.equal(pricePerCall.toNumber());
+            expect((await program.account.agentPolicy.fetch(policyPda)).lifetimeSpent.toNumber()).to.equal(spent);
+
+            try {
+                await fail(nonce);
+                expect.fail("Should have thrown SettlementRetryUsed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("SettlementRetryUsed");
+            }
+
+            await confirm(nonce);
+            expect((await program.account.authorization.fetch(authPdaFor(nonce))).settled).to.equal(true);
+        });
+    });
+
+    // =========================================================================
+    // TEST 69: payment disputes
+    // =========================================================================
+    describe("payment disputes", () => {
+        const disputeWindowSlots = 20;
+
+        const disputePdaFor = (nonce: anchor.BN) =>
+            PublicKey.findProgramAddressSync(
+                [Buffer.from("dispute"), authPdaFor(nonce).toBuffer()],
+                program.programId
+            )[0];
+
+        const openDispute = (nonce: anchor.BN, opener: Keypair = agentKeypair) =>
+            program.methods
+                .openDispute(nonce, 3)
+                .accounts({
+                    opener: opener.publicKey,
+                    meter: meterPda,
+                    authorization: authPdaFor(nonce),
+                    dispute: disputePdaFor(nonce),
+                    config: configPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([opener])
+                .rpc();
+
+        const updateWindow = (slots: number) =>
+            program.methods
+                .updateConfig(configParams({ disputeWindowSlots: new anchor.BN(slots) }))
+                .accounts({ admin: provider.wallet.publicKey, config: configPda })
+                .rpc();
+
+        before(async () => {
+            await updateWindow(disputeWindowSlots);
+        });
+
+        after(async () => {
+            await updateWindow(0);
+        });
+
+        it("lets the agent dispute a recorded payment and the admin resolve it", async () => {
+            const nonce = new anchor.BN(Date.now() + 6900);
+            await authorize(nonce, pricePerCall);
+
+            try {
+                await openDispute(nonce);
+                expect.fail("Should have thrown NothingRecorded error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("NothingRecorded");
+            }
+
+            await record(nonce);
+            await openDispute(nonce);
+            expect((await program.account.authorization.fetch(authPdaFor(nonce))).disputed).to.equal(true);
+            const dispute = await program.account.dispute.fetch(disputePdaFor(nonce));
+            expect(dispute.openedBy.toBase58()).to.equal(agentKeypair.publicKey.toBase58());
+            expect(dispute.reasonCode).to.equal(3);
+
+            // A disputed authorization keeps its account
+            try {
+                await program.methods
+                    .closeAuthorization()
+                    .accounts({ authorization: authPdaFor(nonce), rentPayer: provider.wallet.publicKey, config: configPda })
+                    .rpc();
+                expect.fail("Should have thrown AuthorizationStillLive error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationStillLive");
+            }
+
+            await program.methods
+                .resolveDispute(1) // REFUNDED
+                .accounts({
+                    admin: provider.wallet.publicKey,
+                    config: configPda,
+                    authorization: authPdaFor(nonce),
+                    dispute: disputePdaFor(nonce),
+                    openedBy: agentKeypair.publicKey,
+                })
+                .rpc();
+            expect((await program.account.authorization.fetch(authPdaFor(nonce))).disputed).to.equal(false);
+            expect(await provider.connection.getAccountInfo(disputePdaFor(nonce))).to.equal(null);
+        });
+
+        it("rejects openers other than the agent and the meter authority", async () => {
+            const nonce = new anchor.BN(Date.now() + 6910);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+
+            const stranger = Keypair.generate();
+            await provider.connection.confirmTransaction(
+                await provider.connection.requestAirdrop(stranger.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+            );
+            try {
+                await openDispute(nonce, stranger);
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+
+        it("fails with DisputeWindowClosed after the window", async () => {
+            const nonce = new anchor.BN(Date.now() + 6920);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            const closeAuthorization = () =>
+                program.methods
+                    .closeAuthorization()
+                    .accounts({ authorization: authPdaFor(nonce), rentPayer: provider.wallet.publicKey, config: configPda })
+                    .rpc();
+
+            // Nobody can close it out from under a dispute while the window is open
+            try {
+                await closeAuthorization();
+                expect.fail("Should have thrown AuthorizationStillLive error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationStillLive");
+            }
+
+            while ((await provider.connection.getSlot()) <= auth.lastRecordedSlot.toNumber() + disputeWindowSlots) {
+                await new Promise(resolve => setTimeout(resolve, 100));
+            }
+            try {
+                await openDispute(nonce);
+                expect.fail("Should have thrown DisputeWindowClosed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("DisputeWindowClosed");
+            }
+
+            await closeAuthorization();
+            expect(await provider.connection.getAccountInfo(authPdaFor(nonce))).to.equal(null);
+        });
+    });
+
+    // =========================================================================
+    // TEST 70: merchant refunds
+    // =========================================================================
+    describe("merchant refunds", () => {
+        const refund = (nonce: anchor.BN, amount: anchor.BN) =>
+            program.methods
+                .issueRefund(nonce, amount)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meter: meterPda,
+                    authorization: authPdaFor(nonce),
+                    config: configPda,
+                })
+                .rpc();
+
+        const updateWindows = (refundSlots: number, disputeSlots: number = 0) =>
+            program.methods
+                .updateConfig(configParams({
+                    refundWindowSlots: new anchor.BN(refundSlots),
+                    disputeWindowSlots: new anchor.BN(disputeSlots),
+                }))
+                .accounts({ admin: provider.wallet.publicKey, config: configPda })
+                .rpc();
+
+        before(async () => {
+            await updateWindows(1000, 1000);
+        });
+
+        after(async () => {
+            await updateWindows(0);
+        });
+
+        it("accumulates partial refunds up to the authorized amount", async () => {
+            const nonce = new anchor.BN(Date.now() + 7000);
+            await authorize(nonce, pricePerCall);
+
+            try {
+                await refund(nonce, new anchor.BN(1));
+                expect.fail("Should have thrown AuthorizationNotUsed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationNotUsed");
+            }
+
+            await record(nonce);
+            const half = pricePerCall.divn(2);
+            await refund(nonce, half);
+            await refund(nonce, half);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.refundedAmount.toNumber()).to.equal(half.muln(2).toNumber());
+
+            try {
+                await refund(nonce, new anchor.BN(1));
+                expect.fail("Should have thrown RefundExceedsAmount error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("RefundExceedsAmount");
+            }
+        });
+
+        it("blocks refunds while the payment is disputed", async () => {
+            const nonce = new anchor.BN(Date.now() + 7010);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+
+            const [disputePda] = PublicKey.findProgramAddressSync(
+                [Buffer.from("dispute"), authPdaFor(nonce).toBuffer()],
+                program.programId
+            );
+            await program.methods
+                .openDispute(nonce, 0)
+                .accounts({
+                    opener: agentKeypair.publicKey,
+                    meter: meterPda,
+                    authorization: authPdaFor(nonce),
+                    dispute: disputePda,
+                    config: configPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            try {
+                await refund(nonce, pricePerCall);
+                expect.fail("Should have thrown PaymentDisputed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("PaymentDisputed");
+            }
+        });
+
+        it("fails once the refund window is disabled", async () => {
+            const nonce = new anchor.BN(Date.now() + 7020);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+            await updateWindows(0);
+
+            try {
+                await refund(nonce, pricePerCall);
+                expect.fail("Should have thrown RefundWindowClosed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("RefundWindowClosed");
+            } finally {
+                await updateWindows(1000, 1000);
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 71: policy snapshot checked at record time
+    // =========================================================================
+    describe("policy changes between authorize and record", () => {
+        afterEach(async () => {
+            await setPolicyFlags({});
+        });
+
+        it("snapshots the policy hash into the authorization", async () => {
+            const nonce = new anchor.BN(Date.now() + 7100);
+            await authorize(nonce, pricePerCall);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.policyHashAtAuth).to.deep.equal(policyHash);
+        });
+
+        it("rejects recording once the policy was replaced", async () => {
+            const nonce = new anchor.BN(Date.now() + 7110);
+            await authorize(nonce, pricePerCall);
+
+            const replacedHash = [...crypto.createHash("sha256").update("replaced policy").digest()];
+            await program.methods
+                .setPolicy(policyParams({ policyHash: replacedHash }))
+                .accounts({
+                    owner: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    retiredAgent: retiredPdaFor(agentKeypair.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+            try {
+                await record(nonce);
+                expect.fail("Should have thrown PolicyChangedSinceAuthorization error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("PolicyChangedSinceAuthorization");
+            }
+        });
+
+        it("rejects recording while the policy is frozen", async () => {
+            const nonce = new anchor.BN(Date.now() + 7120);
+            await authorize(nonce, pricePerCall);
+            await setPolicyFlags({ frozen: true });
+
+            try {
+                await record(nonce);
+                expect.fail("Should have thrown PolicyFrozen error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("PolicyFrozen");
+            }
+
+            await setPolicyFlags({});
+            await record(nonce);
+        });
+    });
+
+    // =========================================================================
+    // TEST 72: idempotent records
+    // =========================================================================
+    describe("record external_id", () => {
+        const externalId = () => [...crypto.randomBytes(16)];
+
+        const emittedEvents = async (signature: string) => {
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            return [...parser.parseLogs(tx!.meta!.logMessages!)];
+        };
+
+        it("treats a retry with the same external_id as a no-op", async () => {
+            const nonce = new anchor.BN(Date.now() + 7200);
+            const id = externalId();
+            await authorize(nonce, pricePerCall);
+            await record(nonce, meterPda, id);
+
+            const usage = await program.account.meterUsage.fetch(usagePdaFor(meterPda));
+            const signature = await record(nonce, meterPda, id);
+
+            expect(await emittedEvents(signature)).to.have.length(0);
+            const after = await program.account.meterUsage.fetch(usagePdaFor(meterPda));
+            expect(after.calls.toNumber()).to.equal(usage.calls.toNumber());
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.externalId).to.deep.equal(id);
+        });
+
+        it("still rejects a different external_id with AuthorizationUsed", async () => {
+            const nonce = new anchor.BN(Date.now() + 7210);
+            await authorize(nonce, pricePerCall);
+            await record(nonce, meterPda, externalId());
+
+            try {
+                await record(nonce, meterPda, externalId());
+                expect.fail("Should have thrown AuthorizationUsed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationUsed");
+            }
+        });
+
+        it("checks expiry before treating a retry as a no-op", async () => {
+            const nonce = new anchor.BN(Date.now() + 7215);
+            const id = externalId();
+            await authorize(nonce, pricePerCall, meterPda, null, Buffer.alloc(64), 2);
+            await record(nonce, meterPda, id);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+
+            while ((await provider.connection.getSlot()) <= auth.expiresAtSlot.toNumber()) {
+                await new Promise(resolve => setTimeout(resolve, 100));
+            }
+            try {
+                await record(nonce, meterPda, id);
+                expect.fail("Should have thrown AuthorizationExpired error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationExpired");
+            }
+        });
+
+        it("doesn't dedupe records without an external_id", async () => {
+            const nonce = new anchor.BN(Date.now() + 7220);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+
+            try {
+                await record(nonce);
+                expect.fail("Should have thrown AuthorizationUsed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationUsed");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 73: budget authorizations
+    // =========================================================================
+    describe("budget authorizations", () => {
+        const budgetPdaFor = (nonce: anchor.BN) =>
+            PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("budget_auth"),
+                    agentKeypair.publicKey.toBuffer(),
+                    meterPda.toBuffer(),
+                    nonce.toArrayLike(Buffer, 'le', 8)
+                ],
+                program.programId
+            )[0];
+
+        const authorizeBudget = async (nonce: anchor.BN, total: anchor.BN, ttlSlots: number = 100) => {
+            const currentSlot = await provider.connection.getSlot();
+            await program.methods
+                .authorizeBudgetWithProof(
+                    policyId,
+                    total,
+                    allowedCategory,
+                    nonce,
+                    new anchor.BN(currentSlot + ttlSlots),
+                    [...Buffer.alloc(64)]
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(meterPda),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(meterPda),
+                    budgetAuthorization: budgetPdaFor(nonce),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    config: configPda,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(budgetCircuit),
+                    nullifier: await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, total, policyPda, "budget_auth"),
+                })
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        const recordBudget = (nonce: anchor.BN, amount: anchor.BN) =>
+            program.methods
+                .recordBudgetPayment(nonce, amount)
+                .accounts({
+                    recorder: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: meterPda,
+                    meterCounters: countersPdaFor(meterPda),
+                    agentPolicy: policyPda,
+                    budgetAuthorization: budgetPdaFor(nonce),
+                    meterUsage: usagePdaFor(meterPda),
+                    config: configPda,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+        const closeBudget = (nonce: anchor.BN) =>
+            program.methods
+                .closeBudgetAuthorization()
+                .accounts({
+                    budgetAuthorization: budgetPdaFor(nonce),
+                    rentPayer: provider.wallet.publicKey,
+                    config: configPda,
+                })
+                .rpc();
+
+        it("draws several payments from one proof", async () => {
+            const nonce = new anchor.BN(Date.now() + 7300);
+            const total = pricePerCall.muln(3);
+            await authorizeBudget(nonce, total);
+
+            let budget = await program.account.budgetAuthorization.fetch(budgetPdaFor(nonce));
+            expect(budget.totalAmount.toString()).to.equal(total.toString());
+            expect(budget.remaining.toString()).to.equal(total.toString());
+
+            const usage = await program.account.meterUsage.fetch(usagePdaFor(meterPda));
+            await recordBudget(nonce, pricePerCall);
+            await recordBudget(nonce, pricePerCall);
+
+            budget = await program.account.budgetAuthorization.fetch(budgetPdaFor(nonce));
+            expect(budget.remaining.toString()).to.equal(pricePerCall.toString());
+            const after = await program.account.meterUsage.fetch(usagePdaFor(meterPda));
+            expect(after.calls.toNumber()).to.equal(usage.calls.toNumber() + 2);
+        });
+
+        it("rejects draws beyond what remains", async () => {
+            const nonce = new anchor.BN(Date.now() + 7310);
+            await authorizeBudget(nonce, pricePerCall.muln(2));
+            await recordBudget(nonce, pricePerCall);
+
+            try {
+                await recordBudget(nonce, pricePerCall.muln(2));
+                expect.fail("Should have thrown InvalidConsumeAmount error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidConsumeAmount");
+            }
+        });
+
+        it("allows a total above max_per_tx but caps each draw", async () => {
+            const nonce = new anchor.BN(Date.now() + 7320);
+            await authorizeBudget(nonce, maxPerTx.muln(2));
+
+            try {
+                await recordBudget(nonce, maxPerTx.addn(1));
+                expect.fail("Should have thrown AmountExceedsMax error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AmountExceedsMax");
+            }
+        });
+
+        it("closes only once spent", async () => {
+            const nonce = new anchor.BN(Date.now() + 7330);
+            await authorizeBudget(nonce, pricePerCall);
+
+            try {
+                await closeBudget(nonce);
+                expect.fail("Should have thrown AuthorizationStillLive error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationStillLive");
+            }
+
+            await recordBudget(nonce, pricePerCall);
+            await closeBudget(nonce);
+            const info = await provider.connection.getAccountInfo(budgetPdaFor(nonce));
+            expect(info).to.be.null;
+        });
+
+        it("rejects draws and allows closing after expiry", async () => {
+            const nonce = new anchor.BN(Date.now() + 7340);
+            await authorizeBudget(nonce, pricePerCall.muln(2), 1);
+            await new Promise(resolve => setTimeout(resolve, 1500));
+
+            try {
+                await recordBudget(nonce, pricePerCall);
+                expect.fail("Should have thrown AuthorizationExpired error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationExpired");
+            }
+
+            await closeBudget(nonce);
+        });
+    });
+
+    // =========================================================================
+    // TEST 74: nonce reuse
+    // =========================================================================
+    describe("nonce reuse", () => {
+        it("rejects a nonce that is still in use with NonceAlreadyUsed", async () => {
+            const nonce = new anchor.BN(Date.now() + 7400);
+            await authorize(nonce, pricePerCall);
+
+            try {
+                await authorize(nonce, pricePerCall);
+                expect.fail("Should have thrown NonceAlreadyUsed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("NonceAlreadyUsed");
+            }
+        });
+
+        it("rejects a used authorization's nonce too", async () => {
+            const nonce = new anchor.BN(Date.now() + 7410);
+            await authorize(nonce, pricePerCall);
+            await record(nonce);
+
+            try {
+                await authorize(nonce, pricePerCall);
+                expect.fail("Should have thrown NonceAlreadyUsed error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("NonceAlreadyUsed");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 75: hash-locked authorizations
+    // =========================================================================
+    describe("hash lock", () => {
+        const secret = crypto.randomBytes(32);
+        const hashLock = [...crypto.createHash("sha256").update(secret).digest()];
+
+        const authorizeLocked = (nonce: anchor.BN) =>
+            authorize(nonce, pricePerCall, meterPda, null, Buffer.alloc(64), 100, hashLock);
+
+        it("stores the lock on the authorization", async () => {
+            const nonce = new anchor.BN(Date.now() + 7500);
+            await authorizeLocked(nonce);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.hashLock).to.deep.equal(hashLock);
+        });
+
+        it("records with the correct preimage", async () => {
+            const nonce = new anchor.BN(Date.now() + 7510);
+            await authorizeLocked(nonce);
+            await record(nonce, meterPda, noExternalId, secret);
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.used).to.equal(true);
+        });
+
+        it("rejects a missing or wrong preimage", async () => {
+            const nonce = new anchor.BN(Date.now() + 7520);
+            await authorizeLocked(nonce);
+
+            for (const preimage of [noPreimage, crypto.randomBytes(32)]) {
+                try {
+                    await record(nonce, meterPda, noExternalId, preimage);
+                    expect.fail("Should have thrown InvalidPreimage error");
+                } catch (err: any) {
+                    expect(err.error.errorCode.code).to.equal("InvalidPreimage");
+                }
+            }
+        });
+
+        it("rejects a preimage over 64 bytes", async () => {
+            const nonce = new anchor.BN(Date.now() + 7530);
+            await authorizeLocked(nonce);
+
+            try {
+                await record(nonce, meterPda, noExternalId, crypto.randomBytes(65));
+                expect.fail("Should have thrown PreimageTooLong error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("PreimageTooLong");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 76: usage-based records
+    // =========================================================================
+    describe("record_metered_usage", () => {
+        const usageMeterId = Keypair.generate();
+        let usageMeterPda: PublicKey;
+        const cap = pricePerCall.muln(5);
+
+        before(async () => {
+            [usageMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    usageMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: usageMeterId.publicKey,
+                    meter: usageMeterPda,
+                    meterCounters: countersPdaFor(usageMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+            await program.methods
+                .updateMeter(pricePerCall, allowedCategory, false, noFreeCalls, meterName, endpointHash, cap, anyProofVersion)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: usageMeterId.publicKey,
+                    meter: usageMeterPda,
+                    config: configPda,
+                })
+                .rpc();
+        });
+
+        const recordUsage = (nonce: anchor.BN, units: anchor.BN, authority: Keypair | null = null) =>
+            program.methods
+                .recordMeteredUsage(nonce, units)
+                .accounts({
+                    authority: authority ? authority.publicKey : provider.wallet.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: usageMeterPda,
+                    meterCounters: countersPdaFor(usageMeterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, usageMeterPda),
+                    meterUsage: usagePdaFor(usageMeterPda),
+                    config: configPda,
+                })
+                .signers(authority ? [authority] : [])
+                .rpc();
+
+        it("charges units times the price and releases the rest of the cap", async () => {
+            const nonce = new anchor.BN(Date.now() + 7600);
+            await authorize(nonce, cap, usageMeterPda);
+            const usage = await program.account.meterUsage.fetch(usagePdaFor(usageMeterPda));
+
+            await recordUsage(nonce, new anchor.BN(3));
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, usageMeterPda));
+            expect(auth.used).to.equal(true);
+            expect(auth.amountRemaining.toNumber()).to.equal(0);
+            const after = await program.account.meterUsage.fetch(usagePdaFor(usageMeterPda));
+            expect(after.volume.toNumber()).to.equal(usage.volume.toNumber() + pricePerCall.toNumber() * 3);
+        });
+
+        it("rejects usage above the cap, zero units and overflow", async () => {
+            const nonce = new anchor.BN(Date.now() + 7610);
+            await authorize(nonce, cap, usageMeterPda);
+
+            for (const [units, code] of [
+                [new anchor.BN(6), "UsageExceedsAuthorization"],
+                [new anchor.BN(0), "InvalidUnits"],
+                [new anchor.BN("18446744073709551615"), "MathOverflow"],
+            ] as [anchor.BN, string][]) {
+                try {
+                    await recordUsage(nonce, units);
+                    expect.fail(`Should have thrown ${code} error`);
+                } catch (err: any) {
+                    expect(err.error.errorCode.code).to.equal(code);
+                }
+            }
+        });
+
+        it("only accepts the meter authority", async () => {
+            const nonce = new anchor.BN(Date.now() + 7620);
+            await authorize(nonce, cap, usageMeterPda);
+
+            try {
+                await recordUsage(nonce, new anchor.BN(1), agentKeypair);
+                expect.fail("Should have thrown Unauthorized error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 77: payment channels
+    // =========================================================================
+    describe("payment channels", () => {
+        const [channelPda] = PublicKey.findProgramAddressSync(
+            [Buffer.from("channel"), agentKeypair.publicKey.toBuffer(), meterPda.toBuffer()],
+            program.programId
+        );
+        const cap = pricePerCall.muln(10);
+
+        const openedAtSlot = async () => (await program.account.channel.fetch(channelPda)).openedAtSlot;
+
+        const voucher = (cumulative: anchor.BN, signer: Keypair, openedAt: anchor.BN) => {
+            const message = Buffer.concat([
+                Buffer.from("agent_blink_pay:channel"),
+                channelPda.toBuffer(),
+                openedAt.toArrayLike(Buffer, 'le', 8),
+                cumulative.toArrayLike(Buffer, 'le', 8),
+            ]);
+            const ix = Ed25519Program.createInstructionWithPrivateKey({
+                privateKey: signer.secretKey,
+                message,
+            });
+            // Signature sits after the 16-byte header and the 32-byte key
+            return { ix, signature: [...ix.data.subarray(48, 112)] };
+        };
+
+        const settle = async (
+            cumulative: anchor.BN,
+            signer: Keypair = agentKeypair,
+            openedAt?: anchor.BN
+        ) => {
+            const { ix, signature } = voucher(cumulative, signer, openedAt ?? await openedAtSlot());
+            return program.methods
+                .settleChannel(cumulative, signature)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: meterPda,
+                    meterCounters: countersPdaFor(meterPda),
+                    agentPolicy: policyPda,
+                    channel: channelPda,
+                    meterUsage: usagePdaFor(meterPda),
+                    config: configPda,
+                    instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+                })
+                .preInstructions([ix])
+                .rpc();
+        };
+
+        const closeChannel = () =>
+            program.methods
+                .closeChannel()
+                .accounts({
+                    channel: channelPda,
+                    rentPayer: provider.wallet.publicKey,
+                    config: configPda,
+                })
+                .rpc();
+
+        const openChannel = async () => {
+            const currentSlot = await provider.connection.getSlot();
+            await program.methods
+                .openChannel(policyId, cap, new anchor.BN(currentSlot + 20))
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(meterPda),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(meterPda),
+                    channel: channelPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        let firstOpenedAt: anchor.BN;
+
+        before(async () => {
+            await openChannel();
+            firstOpenedAt = await openedAtSlot();
+        });
+
+        it("settles the delta of each newer voucher", async () => {
+            const usage = await program.account.meterUsage.fetch(usagePdaFor(meterPda));
+
+            await settle(pricePerCall.muln(2));
+            await settle(pricePerCall.muln(5));
+
+            const channel = await program.account.channel.fetch(channelPda);
+            expect(channel.claimed.toString()).to.equal(pricePerCall.muln(5).toString());
+            const after = await program.account.meterUsage.fetch(usagePdaFor(meterPda));
+            expect(after.volume.toNumber()).to.equal(usage.volume.toNumber() + pricePerCall.toNumber() * 5);
+        });
+
+        it("rejects stale vouchers and vouchers above the cap", async () => {
+            for (const [cumulative, code] of [
+                [pricePerCall.muln(5), "StaleChannelVoucher"],
+                [cap.addn(1), "ChannelCapExceeded"],
+            ] as [anchor.BN, string][]) {
+                try {
+                    await settle(cumulative);
+                    expect.fail(`Should have thrown ${code} error`);
+                } catch (err: any) {
+                    expect(err.error.errorCode.code).to.equal(code);
+                }
+            }
+        });
+
+        it("rejects vouchers not signed by the agent", async () => {
+            try {
+                await settle(pricePerCall.muln(6), Keypair.generate());
+                expect.fail("Should have thrown InvalidChannelVoucher error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidChannelVoucher");
+            }
+        });
+
+        it("closes only after expiry", async () => {
+            try {
+                await closeChannel();
+                expect.fail("Should have thrown AuthorizationStillLive error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AuthorizationStillLive");
+            }
+
+            await new Promise(resolve => setTimeout(resolve, 10000));
+            await closeChannel();
+            expect(await provider.connection.getAccountInfo(channelPda)).to.equal(null);
+        });
+
+        it("rejects the previous channel's vouchers after reopening", async () => {
+            await openChannel();
+            expect((await openedAtSlot()).gt(firstOpenedAt)).to.equal(true);
+
+            // Replays the last voucher settled on the closed channel
+            try {
+                await settle(pricePerCall.muln(5), agentKeypair, firstOpenedAt);
+                expect.fail("Should have thrown InvalidChannelVoucher error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("InvalidChannelVoucher");
+            }
+
+            await settle(pricePerCall.muln(5));
+            const channel = await program.account.channel.fetch(channelPda);
+            expect(channel.claimed.toString()).to.equal(pricePerCall.muln(5).toString());
+        });
+    });
+
+    // =========================================================================
+    // TEST 78: bundle authorizations
+    // =========================================================================
+    describe("bundle authorizations", () => {
+        const secondMeterId = Keypair.generate();
+        let secondMeterPda: PublicKey;
+
+        const bundlePdaFor = (nonce: anchor.BN) =>
+            PublicKey.findProgramAddressSync(
+                [Buffer.from("bundle_auth"), agentKeypair.publicKey.toBuffer(), nonce.toArrayLike(Buffer, 'le', 8)],
+                program.programId
+            )[0];
+
+        before(async () => {
+            [secondMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    secondMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: secondMeterId.publicKey,
+                    meter: secondMeterPda,
+                    meterCounters: countersPdaFor(secondMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        const authorizeBundle = async (nonce: anchor.BN, meters: PublicKey[], amounts: anchor.BN[]) => {
+            const currentSlot = await provider.connection.getSlot();
+            await program.methods
+                .authorizeBundleWithProof(policyId, amounts, nonce, new anchor.BN(currentSlot + 100), [...Buffer.alloc(64)])
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    bundleAuthorization: bundlePdaFor(nonce),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    config: configPda,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(budgetCircuit),
+                    nullifier: await nullifierPdaFor(
+                        agentKeypair.publicKey,
+                        PublicKey.default,
+                        nonce,
+                        amounts.reduce((total, amount) => total.add(amount), new anchor.BN(0)),
+                        policyPda,
+                        "bundle_auth"
+                    ),
+                })
+                .remainingAccounts(meters.flatMap((meter) => [
+                    { pubkey: meter, isSigner: false, isWritable: false },
+                    { pubkey: deniedPdaFor(meter), isSigner: false, isWritable: false },
+                ]))
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        const recordBundle = (nonce: anchor.BN, meter: PublicKey, index: number) =>
+            program.methods
+                .recordBundlePayment(nonce, index)
+                .accounts({
+                    recorder: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter,
+                    meterCounters: countersPdaFor(meter),
+                    agentPolicy: policyPda,
+                    bundleAuthorization: bundlePdaFor(nonce),
+                    meterUsage: usagePdaFor(meter),
+                    config: configPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+        it("authorizes several meters with one proof and records each slice", async () => {
+            const nonce = new anchor.BN(Date.now() + 7800);
+            await authorizeBundle(nonce, [meterPda, secondMeterPda], [pricePerCall, pricePerCall.muln(2)]);
+
+            let bundle = await program.account.bundleAuthorization.fetch(bundlePdaFor(nonce));
+            expect(bundle.meterCount).to.equal(2);
+            expect(bundle.meters[1].toBase58()).to.equal(secondMeterPda.toBase58());
+
+            await recordBundle(nonce, secondMeterPda, 1);
+            await recordBundle(nonce, meterPda, 0);
+
+            bundle = await program.account.bundleAuthorization.fetch(bundlePdaFor(nonce));
+            expect(bundle.remaining.every((remaining: anchor.BN) => remaining.isZero())).to.equal(true);
+
+            await program.methods
+                .closeBundleAuthorization()
+                .accounts({
+                    bundleAuthorization: bundlePdaFor(nonce),
+                    rentPayer: provider.wallet.publicKey,
+                    config: configPda,
+                })
+                .rpc();
+            expect(await provider.connection.getAccountInfo(bundlePdaFor(nonce))).to.equal(null);
+        });
+
+        it("rejects recording a slice twice or for the wrong meter", async () => {
+            const nonce = new anchor.BN(Date.now() + 7810);
+            await authorizeBundle(nonce, [meterPda, secondMeterPda], [pricePerCall, pricePerCall]);
+            await recordBundle(nonce, meterPda, 0);
+
+            for (const [meter, index, code] of [
+                [meterPda, 0, "AuthorizationUsed"],
+                [meterPda, 1, "InvalidBundleMeter"],
+            ] as [PublicKey, number, string][]) {
+                try {
+                    await recordBundle(nonce, meter, index);
+                    expect.fail(`Should have thrown ${code} error`);
+                } catch (err: any) {
+                    expect(err.error.errorCode.code).to.equal(code);
+                }
+            }
+        });
+
+        it("rejects duplicate meters and oversized bundles", async () => {
+            for (const [meters, code] of [
+                [[meterPda, meterPda], "InvalidBundleMeter"],
+                [Array(5).fill(meterPda), "BatchTooLarge"],
+            ] as [PublicKey[], string][]) {
+                try {
+                    await authorizeBundle(new anchor.BN(Date.now() + 7820), meters, meters.map(() => pricePerCall));
+                    expect.fail(`Should have thrown ${code} error`);
+                } catch (err: any) {
+                    expect(err.error.errorCode.code).to.equal(code);
+                }
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 79: receipts
+    // =========================================================================
+    describe("receipts", () => {
+        const receiptPdaFor = (nonce: anchor.BN) =>
+            PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("receipt"),
+                    meterPda.toBuffer(),
+                    agentKeypair.publicKey.toBuffer(),
+                    nonce.toArrayLike(Buffer, 'le', 8)
+                ],
+                program.programId
+            )[0];
+
+        const recordWithReceipt = (nonce: anchor.BN, receipt: PublicKey | null) =>
+            program.methods
+                .recordMeterPayment(nonce, fullAmount, consumeAll, noExternalId, noPreimage, true)
+                .accounts({
+                    recorder: agentKeypair.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: meterPda,
+                    meterCounters: countersPdaFor(meterPda),
+                    agentPolicy: policyPda,
+                    authorization: authPdaFor(nonce),
+                    meterUsage: usagePdaFor(meterPda),
+                    config: configPda,
+                    escrow: null,
+                    merchantTokenAccount: null,
+                    tokenProgram: null,
+                    receipt,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([agentKeypair])
+                .rpc();
+
+        it("keeps a receipt after the authorization is closed", async () => {
+            const nonce = new anchor.BN(Date.now() + 7900);
+            await authorize(nonce, pricePerCall);
+            await recordWithReceipt(nonce, receiptPdaFor(nonce));
+
+            await program.methods
+                .closeAuthorization()
+                .accounts({ authorization: authPdaFor(nonce), rentPayer: provider.wallet.publicKey, config: configPda })
+                .rpc();
+
+            const receipt = await program.account.receipt.fetch(receiptPdaFor(nonce));
+            expect(receipt.amount.toString()).to.equal(pricePerCall.toString());
+            expect(receipt.requestId).to.deep.equal(noRequestId);
+            expect(receipt.rentPayer.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+        });
+
+        it("rejects a receipt request without the receipt account", async () => {
+            const nonce = new anchor.BN(Date.now() + 7910);
+            await authorize(nonce, pricePerCall);
+
+            try {
+                await recordWithReceipt(nonce, null);
+                expect.fail("Should have thrown ReceiptAccountsMissing error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("ReceiptAccountsMissing");
+            }
+        });
+
+        it("lets the agent close the receipt", async () => {
+            const nonce = new anchor.BN(Date.now() + 7920);
+            await authorize(nonce, pricePerCall);
+            await recordWithReceipt(nonce, receiptPdaFor(nonce));
+
+            await program.methods
+                .closeReceipt(nonce)
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    meter: meterPda,
+                    receipt: receiptPdaFor(nonce),
+                    rentPayer: provider.wallet.publicKey,
+                })
+                .signers([agentKeypair])
+                .rpc();
+            expect(await provider.connection.getAccountInfo(receiptPdaFor(nonce))).to.equal(null);
+        });
+    });
+
+    // =========================================================================
+    // TEST 80: record-time category consistency
+    // =========================================================================
+    describe("record-time category check", () => {
+        const recategorizedMeterId = Keypair.generate();
+        let recategorizedMeterPda: PublicKey;
+
+        const setCategory = (category: number) =>
+            program.methods
+                .updateMeter(pricePerCall, category, false, noFreeCalls, meterName, endpointHash, noMeterCap, anyProofVersion)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: recategorizedMeterId.publicKey,
+                    meter: recategorizedMeterPda,
+                    config: configPda,
+                })
+                .rpc();
+
+        before(async () => {
+            [recategorizedMeterPda] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("meter"),
+                    provider.wallet.publicKey.toBuffer(),
+                    recategorizedMeterId.publicKey.toBuffer()
+                ],
+                program.programId
+            );
+            await program.methods
+                .createMeter(pricePerCall, allowedCategory, merchantWalletId, false, perCallKind, noFreeCalls, meterName, endpointHash, noReferrer, noReferrerBps, anyProofVersion, defaultMint)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: recategorizedMeterId.publicKey,
+                    meter: recategorizedMeterPda,
+                    meterCounters: countersPdaFor(recategorizedMeterPda),
+                    config: configPda,
+                    meterIndex: meterIndexPda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+        });
+
+        it("rejects a record after the meter's category changed", async () => {
+            const nonce = new anchor.BN(Date.now() + 8000);
+            await authorize(nonce, pricePerCall, recategorizedMeterPda);
+            await setCategory(2);
+
+            try {
+                await record(nonce, recategorizedMeterPda);
+                expect.fail("Should have thrown CategoryMismatch error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("CategoryMismatch");
+            }
+        });
+
+        it("records once the category is restored", async () => {
+            const nonce = new anchor.BN(Date.now() + 8001);
+            await authorize(nonce, pricePerCall, recategorizedMeterPda);
+            await setCategory(2);
+            await setCategory(allowedCategory);
+
+            await record(nonce, recategorizedMeterPda);
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, recategorizedMeterPda));
+            expect(auth.amountRemaining.toNumber()).to.equal(0);
+        });
+    });
+
+    // =========================================================================
+    // TEST 81: tips
+    // =========================================================================
+    describe("tips", () => {
+        const tip = new anchor.BN(10000);
+
+        it("reports the tip separately in MeterPaid", async () => {
+            const nonce = new anchor.BN(Date.now() + 8100);
+            await authorize(nonce, pricePerCall.add(tip), meterPda, null, Buffer.alloc(64), 100, noHashLock, tip);
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.tip.toNumber()).to.equal(tip.toNumber());
+
+            const signature = await record(nonce);
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+            const paid = [...parser.parseLogs(tx!.meta!.logMessages!)].find((e) => e.name === "MeterPaid");
+            expect(paid!.data.amount.toNumber()).to.equal(pricePerCall.add(tip).toNumber());
+            expect(paid!.data.tip.toNumber()).to.equal(tip.toNumber());
+        });
+
+        it("rejects an amount other than the price plus the tip", async () => {
+            try {
+                await authorize(new anchor.BN(Date.now() + 8110), pricePerCall.add(tip).addn(1), meterPda, null, Buffer.alloc(64), 100, noHashLock, tip);
+                expect.fail("Should have thrown AmountNotPricePlusTip error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AmountNotPricePlusTip");
+            }
+        });
+
+        it("keeps the tip within max_per_tx", async () => {
+            const bigTip = maxPerTx;
+            try {
+                await authorize(new anchor.BN(Date.now() + 8120), pricePerCall.add(bigTip), meterPda, null, Buffer.alloc(64), 100, noHashLock, bigTip);
+                expect.fail("Should have thrown AmountExceedsMax error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("AmountExceedsMax");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 82: authorization layout and kinds
+    // =========================================================================
+    describe("authorization layout", () => {
+        const migrate = (nonce: anchor.BN) =>
+            program.methods
+                .migrateAuthorization(nonce)
+                .accounts({
+                    payer: provider.wallet.publicKey,
+                    agent: agentKeypair.publicKey,
+                    meter: meterPda,
+                    authorization: authPdaFor(nonce),
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+
+        it("starts with the version and kind", async () => {
+            const nonce = new anchor.BN(Date.now() + 8200);
+            await authorize(nonce, pricePerCall);
+
+            const info = await provider.connection.getAccountInfo(authPdaFor(nonce));
+            expect(info!.data[8]).to.equal(2); // Authorization::VERSION
+            expect(info!.data[9]).to.equal(0); // authorization_kinds::STANDARD
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce));
+            expect(auth.version).to.equal(2);
+        });
+
+        it("leaves an up-to-date authorization unchanged when migrated", async () => {
+            const nonce = new anchor.BN(Date.now() + 8210);
+            await authorize(nonce, pricePerCall);
+            const before = await provider.connection.getAccountInfo(authPdaFor(nonce));
+
+            await migrate(nonce);
+
+            const after = await provider.connection.getAccountInfo(authPdaFor(nonce));
+            expect(after!.data.equals(before!.data)).to.equal(true);
+        });
+
+        it("rejects a streaming authorization in the metered usage flow", async () => {
+            const nonce = new anchor.BN(Date.now() + 8220);
+            const currentSlot = await provider.connection.getSlot();
+            await program.methods
+                .authorizeStreamingPayment(
+                    policyId,
+                    pricePerCall,
+                    new anchor.BN(1_000),
+                    allowedCategory,
+                    nonce,
+                    new anchor.BN(currentSlot + 100),
+                    [...Buffer.alloc(64)],
+                    noRequestId
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(meterPda),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(meterPda),
+                    authorization: authPdaFor(nonce),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    config: configPda,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    nullifier: await nullifierPdaFor(agentKeypair.publicKey, meterPda, nonce, pricePerCall, policyPda),
+                })
+                .signers([agentKeypair])
+                .rpc();
+            const info = await provider.connection.getAccountInfo(authPdaFor(nonce));
+            expect(info!.data[9]).to.equal(1); // authorization_kinds::STREAMING
+
+            try {
+                await program.methods
+                    .recordMeteredUsage(nonce, new anchor.BN(1))
+                    .accounts({
+                        authority: provider.wallet.publicKey,
+                        agent: agentKeypair.publicKey,
+                        meter: meterPda,
+                        meterCounters: countersPdaFor(meterPda),
+                        agentPolicy: policyPda,
+                        authorization: authPdaFor(nonce),
+                        meterUsage: usagePdaFor(meterPda),
+                        config: configPda,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown WrongAuthorizationKind error");
+            } catch (err: any) {
+                expect(err.error.errorCode.code).to.equal("WrongAuthorizationKind");
+            }
+        });
+    });
+
+    // =========================================================================
+    // TEST 83: Groth16 proof verification
+    // =========================================================================
+    describe("groth16 verification", () => {
+        // Proof for (amount, category, policy_hash, meter, nonce) under the
+        // development keys
+        const fixtures = groth16Fixtures;
+        const zkAgent = Keypair.generate();
+        const proofHeader = Buffer.from([1, 1]); // version 1, proof_schemes::GROTH16
+        const validProof = Buffer.concat([proofHeader, Buffer.from(fixtures.payment_proof, "hex")]);
+
+        before(async () => {
+            await program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(fixtures.policy_hash, "hex")],
+                    allowedCategory: fixtures.category,
+                    maxPerTx: new anchor.BN(fixtures.max_per_tx),
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+        });
+
+        it("accepts a valid proof", async () => {
+            const nonce = fixtureNonce;
+            await authorizeWithProof(zkAgent, nonce, validProof);
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, zkAgent.publicKey, fixtureMeterPda));
+            expect(auth.zkVerified).to.equal(true);
+        });
+
+        it("fails the pairing check for a proof that doesn't match", async () => {
+            // A and C swapped: both still valid curve points
+            const proof = Buffer.concat([
+                proofHeader,
+                validProof.subarray(194, 258),
+                validProof.subarray(66, 194),
+                validProof.subarray(2, 66),
+            ]);
+            await expectError(
+                () => authorizeWithProof(zkAgent, new anchor.BN(Date.now()), proof),
+                "InvalidProof",
+                "PairingFailed"
+            );
+        });
+
+        it("rejects a point that is not on the curve", async () => {
+            const proof = Buffer.from(validProof);
+            proof[65] ^= 1; // last byte of A's y coordinate
+            await expectError(
+                () => authorizeWithProof(zkAgent, new anchor.BN(Date.now()), proof),
+                "MalformedProof",
+                "MalformedPoint"
+            );
+        });
+
+        it("rejects a proof with trailing bytes", async () => {
+            const proof = Buffer.concat([validProof, Buffer.alloc(1)]);
+            await expectError(
+                () => authorizeWithProof(zkAgent, new anchor.BN(Date.now()), proof),
+                "MalformedProof",
+                "MalformedProof"
+            );
+        });
+
+        it("rejects a truncated proof", async () => {
+            // Below MIN_PROOF_LEN, a Groth16 proof and its header. Proofs
+            // above MAX_PROOF_LEN don't fit a transaction; the unit tests
+            // cover ProofTooLarge.
+            await expectError(
+                () => authorizeWithProof(zkAgent, new anchor.BN(Date.now()), validProof.subarray(0, 200)),
+                "ProofTooSmall"
+            );
+        });
+    });
+
+    // =========================================================================
+    // TEST 84: Verifying key accounts
+    // =========================================================================
+    describe("verifying keys", () => {
+        const zkAgent = Keypair.generate();
+        const keys = groth16Fixtures.keys;
+        const proofV1 = Buffer.concat([Buffer.from([1, 1]), Buffer.from(groth16Fixtures.payment_proof, "hex")]);
+        const unusedCircuit = 99;
+
+        before(async () => {
+            await program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                    allowedCategory: groth16Fixtures.category,
+                    maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+        });
+
+        after(async () => {
+            await setVerifyingKey(paymentPolicyCircuit, vkParams(keys.payment_policy));
+        });
+
+        it("stores the key and its number of public inputs", async () => {
+            const vk = await program.account.verifyingKey.fetch(vkPdaFor(paymentPolicyCircuit));
+            expect(vk.circuitId).to.equal(paymentPolicyCircuit);
+            expect(vk.circuitVersion).to.equal(1);
+            expect(vk.locked).to.equal(false);
+            expect(vk.ic.length).to.equal(7);
+
+            const budgetVk = await program.account.verifyingKey.fetch(vkPdaFor(budgetCircuit));
+            expect(budgetVk.ic.length).to.equal(8);
+        });
+
+        it("rejects a v1 proof once the v2 key is installed", async () => {
+            await authorizeWithProof(zkAgent, fixtureNonce, proofV1);
+
+            await setVerifyingKey(paymentPolicyCircuit, vkParams(keys.payment_policy_v2, 2));
+
+            // Still tagged v1
+            await expectError(
+                () => authorizeWithProof(zkAgent, new anchor.BN(Date.now() + 8401), proofV1),
+                "InvalidProof",
+                "VersionMismatch"
+            );
+
+            // The v2 key installed as version 1: the points don't satisfy it
+            await setVerifyingKey(paymentPolicyCircuit, vkParams(keys.payment_policy_v2, 1));
+            await expectError(
+                () => authorizeWithProof(zkAgent, new anchor.BN(Date.now() + 8402), proofV1),
+                "InvalidProof",
+                "PairingFailed"
+            );
+        });
+
+        it("rejects a key without its constant term", async () => {
+            const params = vkParams(keys.payment_policy);
+            params.ic = [];
+            await expectError(() => setVerifyingKey(unusedCircuit, params), "InvalidVerifyingKey");
+        });
+
+        it("rejects a non-admin", async () => {
+            const stranger = Keypair.generate();
+            await expectError(
+                () =>
+                    program.methods
+                        .setVerifyingKey(unusedCircuit, vkParams(keys.payment_policy))
+                        .accounts({
+                            admin: stranger.publicKey,
+                            config: configPda,
+                            verifyingKey: vkPdaFor(unusedCircuit),
+                            systemProgram: SystemProgram.programId,
+                        })
+                        .signers([stranger])
+                        .rpc(),
+                "Unauthorized"
+            );
+        });
+
+        it("freezes a locked key for good", async () => {
+            await setVerifyingKey(unusedCircuit, vkParams(keys.budget, 1, true));
+
+            await expectError(
+                () => setVerifyingKey(unusedCircuit, vkParams(keys.payment_policy)),
+                "VerifyingKeyLocked"
+            );
+
+            const vk = await program.account.verifyingKey.fetch(vkPdaFor(unusedCircuit));
+            expect(vk.locked).to.equal(true);
+            expect(vk.ic.length).to.equal(8);
+        });
+    });
+
+    // =========================================================================
+    // TEST 85: External verifier program via CPI
+    // =========================================================================
+    describe("external verifier", () => {
+        // programs/mock_verifier: accepts proofs whose first byte is ACCEPT_MAGIC
+        const mockVerifierId = new PublicKey("DUURAM5mYBrePqiCb9x2Ai2dgcjtLpi471LME5yqpWjj");
+        const acceptMagic = 0xa5;
+        const zkAgent = Keypair.generate();
+        const mockProof = (firstByte: number) =>
+            Buffer.concat([Buffer.from([1, 1, firstByte]), Buffer.alloc(255)]); // Groth16 v1 header
+
+        before(async () => {
+            await program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                    allowedCategory: groth16Fixtures.category,
+                    maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+
+            await updateConfig({ verifierProgram: mockVerifierId, externalVerifier: true });
+        });
+
+        after(async () => {
+            await updateConfig();
+        });
+
+        it("authorizes when the external verifier accepts", async () => {
+            const nonce = new anchor.BN(Date.now() + 8500);
+            await authorizeWithProof(zkAgent, nonce, mockProof(acceptMagic), { verifierProgram: mockVerifierId });
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, zkAgent.publicKey, fixtureMeterPda));
+            expect(auth.zkVerified).to.equal(true);
+            expect(auth.verificationMethod).to.equal(verificationCpi);
+            expect(auth.verifierRef.toBase58()).to.equal(mockVerifierId.toBase58());
+        });
+
+        it("fails when the external verifier rejects", async () => {
+            try {
+                await authorizeWithProof(zkAgent, new anchor.BN(Date.now() + 8501), mockProof(0), {
+                    verifierProgram: mockVerifierId,
+                });
+                expect.fail("Should have been rejected by the verifier");
+            } catch (err: any) {
+                expect(err.logs.join("\n")).to.include("Mock verifier: proof rejected");
+            }
+        });
+
+        it("rejects a verifier program other than the configured one", async () => {
+            await expectError(
+                () => authorizeWithProof(zkAgent, new anchor.BN(Date.now() + 8502), mockProof(acceptMagic)),
+                "VerifierProgramMismatch"
+            );
+        });
+
+        it("requires a verifier program to enable the external path", async () => {
+            await expectError(() => updateConfig({ externalVerifier: true }), "VerifierProgramMissing");
+        });
+    });
+
+    // =========================================================================
+    // TEST 86: Proof header version and scheme
+    // =========================================================================
+    describe("proof header", () => {
+        const zkAgent = Keypair.generate();
+        const groth16 = 1; // proof_schemes::GROTH16
+        const withHeader = (version: number, scheme: number = groth16) =>
+            Buffer.concat([Buffer.from([version, scheme]), Buffer.from(groth16Fixtures.payment_proof, "hex")]);
+
+        before(async () => {
+            await program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                    allowedCategory: groth16Fixtures.category,
+                    maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+        });
+
+        it("accepts a v1 Groth16 proof and records its version", async () => {
+            const nonce = fixtureNonce;
+            await authorizeWithProof(zkAgent, nonce, withHeader(1));
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, zkAgent.publicKey, fixtureMeterPda));
+            expect(auth.proofVersion).to.equal(1);
+        });
+
+        it("rejects version 0", async () => {
+            await expectError(
+                () => authorizeWithProof(zkAgent, new anchor.BN(Date.now()), withHeader(0)),
+                "UnsupportedProofVersion"
+            );
+        });
+
+        it("rejects version 99", async () => {
+            await expectError(
+                () => authorizeWithProof(zkAgent, new anchor.BN(Date.now()), withHeader(99)),
+                "UnsupportedProofVersion"
+            );
+        });
+
+        it("rejects an unknown scheme", async () => {
+            await expectError(
+                () => authorizeWithProof(zkAgent, new anchor.BN(Date.now()), withHeader(1, 7)),
+                "UnsupportedProofScheme"
+            );
+        });
+
+        it("rejects a truncated header", async () => {
+            await expectError(
+                () => authorizeWithProof(zkAgent, new anchor.BN(Date.now()), Buffer.from([1])),
+                "ProofTooSmall"
+            );
+        });
+    });
+
+    // =========================================================================
+    // TEST 87: Daily limit circuit (version 2 proofs)
+    // =========================================================================
+    describe("daily limit circuit", () => {
+        const zkAgent = Keypair.generate();
+        const proofV1 = Buffer.concat([Buffer.from([1, 1]), Buffer.from(groth16Fixtures.payment_proof, "hex")]);
+        const proofV2 = (payload: string) => Buffer.concat([Buffer.from([2, 1]), Buffer.from(payload, "hex")]);
+
+        before(async () => {
+            await program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                    allowedCategory: groth16Fixtures.category,
+                    maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+        });
+
+        it("rejects a v2 proof against the payment policy key", async () => {
+            await expectError(
+                () =>
+                    authorizeWithProof(
+                        zkAgent,
+                        new anchor.BN(Date.now() + 8700),
+                        proofV2(groth16Fixtures.daily_limit_proof)
+                    ),
+                "WrongVerifyingKey"
+            );
+        });
+
+        it("accepts a v2 proof bound to the policy's spent_today", async () => {
+            await setVerifyingKey(dailyLimitCircuit, vkParams(groth16Fixtures.keys.daily_limit, 2));
+
+            const policy = await program.account.agentPolicy.fetch(agentPolicyPdaFor(zkAgent.publicKey));
+            expect(policy.spentToday.toNumber()).to.equal(0);
+
+            const nonce = new anchor.BN(groth16Fixtures.daily_limit_nonce);
+            await authorizeWithProof(zkAgent, nonce, proofV2(groth16Fixtures.daily_limit_proof), {
+                verifyingKey: vkPdaFor(dailyLimitCircuit),
+            });
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, zkAgent.publicKey, fixtureMeterPda));
+            expect(auth.proofVersion).to.equal(2);
+            expect(auth.zkVerified).to.equal(true);
+        });
+
+        it("rejects a proof made for another spent_today", async () => {
+            await expectError(
+                () =>
+                    authorizeWithProof(
+                        zkAgent,
+                        new anchor.BN(Date.now() + 8702),
+                        proofV2(groth16Fixtures.daily_limit_spent_proof),
+                        { verifyingKey: vkPdaFor(dailyLimitCircuit) }
+                    ),
+                "InvalidProof",
+                "PairingFailed"
+            );
+        });
+
+        it("rejects a v1 proof against the daily limit key", async () => {
+            await expectError(
+                () =>
+                    authorizeWithProof(zkAgent, new anchor.BN(Date.now() + 8703), proofV1, {
+                        verifyingKey: vkPdaFor(dailyLimitCircuit),
+                    }),
+                "WrongVerifyingKey"
+            );
+        });
+
+        it("still accepts v1 proofs with the payment policy key", async () => {
+            const nonce = fixtureNonce;
+            await authorizeWithProof(zkAgent, nonce, proofV1);
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, zkAgent.publicKey, fixtureMeterPda));
+            expect(auth.proofVersion).to.equal(1);
+        });
+    });
+
+    // =========================================================================
+    // TEST 88: Proofs bound to their meter and nonce
+    // =========================================================================
+    describe("proof replay", () => {
+        const zkAgent = Keypair.generate();
+        const proof = Buffer.concat([Buffer.from([1, 1]), Buffer.from(groth16Fixtures.payment_proof, "hex")]);
+
+        before(async () => {
+            await program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                    allowedCategory: groth16Fixtures.category,
+                    maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+        });
+
+        it("rejects the proof under another nonce", async () => {
+            await expectError(
+                () => authorizeWithProof(zkAgent, fixtureNonce.addn(1), proof),
+                "InvalidProof",
+                "PairingFailed"
+            );
+        });
+
+        it("rejects the proof for another meter", async () => {
+            await expectError(
+                () => authorizeWithProof(zkAgent, fixtureNonce, proof, { meter: meterPda }),
+                "InvalidProof",
+                "PairingFailed"
+            );
+        });
+
+        it("accepts the proof for its own meter and nonce", async () => {
+            await authorizeWithProof(zkAgent, fixtureNonce, proof);
+
+            const auth = await program.account.authorization.fetch(
+                authPdaFor(fixtureNonce, zkAgent.publicKey, fixtureMeterPda)
+            );
+            expect(auth.zkVerified).to.equal(true);
+            expect(auth.meter.toBase58()).to.equal(fixtureMeterPda.toBase58());
+        });
+    });
+
+    // =========================================================================
+    // TEST 89: Proof nullifiers
+    // =========================================================================
+    describe("proof nullifiers", () => {
+        const zkAgent = Keypair.generate();
+        const proof = Buffer.concat([Buffer.from([1, 1]), Buffer.from(groth16Fixtures.payment_proof, "hex")]);
+        const authorization = authPdaFor(fixtureNonce, zkAgent.publicKey, fixtureMeterPda);
+        let nullifier: PublicKey;
+
+        before(async () => {
+            await program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                    allowedCategory: groth16Fixtures.category,
+                    maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+            nullifier = await nullifierPdaFor(
+                zkAgent.publicKey,
+                fixtureMeterPda,
+                fixtureNonce,
+                new anchor.BN(groth16Fixtures.amount),
+                agentPolicyPdaFor(zkAgent.publicKey)
+            );
+        });
+
+        const closeNullifier = () =>
+            program.methods
+                .closeNullifier()
+                .accounts({
+                    nullifier,
+                    authorization,
+                    rentPayer: provider.wallet.publicKey,
+                    config: configPda,
+                })
+                .rpc();
+
+        let expiresAtSlot: anchor.BN;
+
+        it("records the proof's nullifier", async () => {
+            expiresAtSlot = new anchor.BN((await provider.connection.getSlot()) + 100);
+            await authorizeWithProof(zkAgent, fixtureNonce, proof, { expiresAtSlot });
+
+            const record = await program.account.nullifier.fetch(nullifier);
+            expect(record.agent.toBase58()).to.equal(zkAgent.publicKey.toBase58());
+            expect(record.authorization.toBase58()).to.equal(authorization.toBase58());
+            expect(record.rentPayer.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+        });
+
+        it("keeps the nullifier while its authorization is live", async () => {
+            await expectError(() => closeNullifier(), "AuthorizationStillLive");
+        });
+
+        it("rejects the same instruction after the authorization is closed", async () => {
+            await program.methods
+                .revokeAuthorization(fixtureNonce)
+                .accounts({
+                    authority: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    authorization,
+                })
+                .signers([zkAgent])
+                .rpc();
+            await program.methods
+                .closeAuthorization()
+                .accounts({ authorization, rentPayer: provider.wallet.publicKey, config: configPda })
+                .rpc();
+
+            await expectError(
+                () => authorizeWithProof(zkAgent, fixtureNonce, proof, { expiresAtSlot }),
+                "ProofAlreadyUsed"
+            );
+        });
+
+        it("rejects the same payment through the batch instruction", async () => {
+            await expectError(
+                () =>
+                    program.methods
+                        .authorizePaymentsBatch(
+                            policyId,
+                            [{
+                                amount: new anchor.BN(groth16Fixtures.amount),
+                                category: groth16Fixtures.category,
+                                nonce: fixtureNonce,
+                                expiresAtSlot,
+                                requestId: noRequestId,
+                            }],
+                            [[...proof]]
+                        )
+                        .accounts({
+                            agent: zkAgent.publicKey,
+                            agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                            meter: fixtureMeterPda,
+                            allowedMeter: null,
+                            deniedMeter: deniedPdaFor(fixtureMeterPda, zkAgent.publicKey),
+                            meterAccess: null,
+                            meterUsage: usagePdaFor(fixtureMeterPda, zkAgent.publicKey),
+                            payer: provider.wallet.publicKey,
+                            systemProgram: SystemProgram.programId,
+                            config: configPda,
+                            verifierProgram: program.programId,
+                            verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                        })
+                        .remainingAccounts([
+                            { pubkey: authorization, isSigner: false, isWritable: true },
+                            { pubkey: nullifier, isSigner: false, isWritable: true },
+                        ])
+                        .preInstructions([anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 400_000 })])
+                        .signers([zkAgent])
+                        .rpc(),
+                "ProofAlreadyUsed"
+            );
+        });
+
+        it("closes the nullifier once its authorization is gone", async () => {
+            const balanceBefore = await provider.connection.getBalance(provider.wallet.publicKey);
+            await closeNullifier();
+
+            expect(await provider.connection.getAccountInfo(nullifier)).to.equal(null);
+            // The rent refund outweighs the fee
+            expect(await provider.connection.getBalance(provider.wallet.publicKey)).to.be.greaterThan(balanceBefore);
+        });
+    });
+
+    // =========================================================================
+    // TEST 90: Staged proof verification
+    // =========================================================================
+    describe("staged proof verification", () => {
+        const zkAgent = Keypair.generate();
+        const proof = Buffer.concat([Buffer.from([1, 1]), Buffer.from(groth16Fixtures.payment_proof, "hex")]);
+        const amount = new anchor.BN(groth16Fixtures.amount);
+        const pendingPda = PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("pending"),
+                zkAgent.publicKey.toBuffer(),
+                fixtureMeterPda.toBuffer(),
+                fixtureNonce.toArrayLike(Buffer, 'le', 8)
+            ],
+            program.programId
+        )[0];
+        // Stage 0 uploads the header and A, stage 1 B, stage 2 C
+        const chunks = [proof.subarray(0, 66), proof.subarray(66, 194), proof.subarray(194)];
+        // Each stage must fit the default per-instruction compute limit
+        const stageComputeBudget = 200_000;
+
+        before(async () => {
+            await program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                    allowedCategory: groth16Fixtures.category,
+                    maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+        });
+
+        // Runs a stage and returns the compute units it consumed
+        const runStage = async (stage: number, chunk: Buffer, stageAmount: anchor.BN = amount) => {
+            const signature = await program.methods
+                .verifyProofStage(policyId, stageAmount, groth16Fixtures.category, fixtureNonce, stage, chunk)
+                .accounts({
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    meter: fixtureMeterPda,
+                    pendingVerification: pendingPda,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    config: configPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc({ commitment: "confirmed" });
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            return tx!.meta!.computeUnitsConsumed!;
+        };
+
+        const finalizeAccounts = async () => ({
+            agent: zkAgent.publicKey,
+            agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+            meter: fixtureMeterPda,
+            allowedMeter: null,
+            deniedMeter: deniedPdaFor(fixtureMeterPda, zkAgent.publicKey),
+            meterAccess: null,
+            meterUsage: usagePdaFor(fixtureMeterPda, zkAgent.publicKey),
+            authorization: authPdaFor(fixtureNonce, zkAgent.publicKey, fixtureMeterPda),
+            config: configPda,
+            payer: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+            verifierProgram: program.programId,
+            verifyingKey: null,
+            nullifier: await nullifierPdaFor(
+                zkAgent.publicKey,
+                fixtureMeterPda,
+                fixtureNonce,
+                amount,
+                agentPolicyPdaFor(zkAgent.publicKey)
+            ),
+            pendingVerification: pendingPda,
+            vault: null,
+            escrow: null,
+            mint: null,
+            tokenProgram: null,
+            associatedTokenProgram: null,
+        });
+
+        const finalizeAuthorization = async () => {
+            const currentSlot = await provider.connection.getSlot();
+            await program.methods
+                .finalizeAuthorization(
+                    policyId,
+                    amount,
+                    singleCall,
+                    groth16Fixtures.category,
+                    fixtureNonce,
+                    new anchor.BN(currentSlot + 100),
+                    noRequestId,
+                    expireBySlot,
+                    anyConsumer,
+                    noHashLock,
+                    noTip
+                )
+                .accounts(await finalizeAccounts())
+                .signers([zkAgent])
+                .rpc();
+        };
+
+        it("binds the pending verification at stage 0", async () => {
+            expect(await runStage(0, chunks[0])).to.be.lessThan(stageComputeBudget);
+
+            const pending = await program.account.pendingVerification.fetch(pendingPda);
+            expect(pending.agent.toBase58()).to.equal(zkAgent.publicKey.toBase58());
+            expect(pending.meter.toBase58()).to.equal(fixtureMeterPda.toBase58());
+            expect(pending.nonce.toString()).to.equal(fixtureNonce.toString());
+            expect(pending.amount.toString()).to.equal(amount.toString());
+            expect(pending.nextStage).to.equal(1);
+        });
+
+        it("rejects stage 0 again", async () => {
+            await expectError(() => runStage(0, chunks[0]), "InvalidVerificationStage");
+        });
+
+        it("rejects a later stage for another amount", async () => {
+            await expectError(() => runStage(1, chunks[1], amount.addn(1)), "PendingVerificationMismatch");
+        });
+
+        it("rejects skipping a stage", async () => {
+            await expectError(
+                () => runStage(2, Buffer.concat([chunks[1], chunks[2]])),
+                "InvalidVerificationStage"
+            );
+        });
+
+        it("won't finalize before the last stage", async () => {
+            expect(await runStage(1, chunks[1])).to.be.lessThan(stageComputeBudget);
+            await expectError(() => finalizeAuthorization(), "ProofNotVerified");
+        });
+
+        it("keeps the pending verification until it expires", async () => {
+            await expectError(
+                () =>
+                    program.methods
+                        .closePendingVerification()
+                        .accounts({ pendingVerification: pendingPda, rentPayer: provider.wallet.publicKey })
+                        .rpc(),
+                "PendingVerificationLive"
+            );
+        });
+
+        it("verifies the proof in stages and finalizes the authorization", async () => {
+            expect(await runStage(2, chunks[2])).to.be.lessThan(stageComputeBudget);
+            expect((await program.account.pendingVerification.fetch(pendingPda)).nextStage).to.equal(3);
+
+            await finalizeAuthorization();
+
+            const auth = await program.account.authorization.fetch(
+                authPdaFor(fixtureNonce, zkAgent.publicKey, fixtureMeterPda)
+            );
+            expect(auth.zkVerified).to.equal(true);
+            expect(auth.verificationMethod).to.equal(verificationEmbedded);
+            expect(auth.verifierRef.toBase58()).to.equal(vkPdaFor(paymentPolicyCircuit).toBase58());
+            expect(auth.proofVersion).to.equal(1);
+            expect(await provider.connection.getAccountInfo(pendingPda)).to.equal(null);
+            const nullifier = await nullifierPdaFor(
+                zkAgent.publicKey,
+                fixtureMeterPda,
+                fixtureNonce,
+                amount,
+                agentPolicyPdaFor(zkAgent.publicKey)
+            );
+            expect(await provider.connection.getAccountInfo(nullifier)).to.not.equal(null);
+        });
+    });
+
+    // =========================================================================
+    // TEST 91: Policy hash recomputation
+    // =========================================================================
+    describe("policy hash salt", () => {
+        const saltAgent = Keypair.generate();
+        const salt = Buffer.alloc(32, 1);
+        // policy_hash::compute in lib.rs
+        const circuitPolicyHash = (max: anchor.BN, category: number, policySalt: Buffer) =>
+            crypto
+                .createHash("sha256")
+                .update(Buffer.concat([max.toArrayLike(Buffer, "le", 8), Buffer.from([category]), policySalt.subarray(0, 31)]))
+                .digest();
+
+        const setSaltedPolicy = (hash: Buffer, max: anchor.BN) =>
+            program.methods
+                .setPolicy(policyParams({ policyHash: [...hash], maxPerTx: max, policySalt: [...salt] }))
+                .accounts({
+                    owner: saltAgent.publicKey,
+                    agent: saltAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(saltAgent.publicKey),
+                    retiredAgent: retiredPdaFor(saltAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([saltAgent])
+                .rpc();
+
+        it("accepts a policy hash that its salt reproduces", async () => {
+            const hash = circuitPolicyHash(maxPerTx, allowedCategory, salt);
+            await setSaltedPolicy(hash, maxPerTx);
+
+            const policy = await program.account.agentPolicy.fetch(agentPolicyPdaFor(saltAgent.publicKey));
+            expect(Buffer.from(policy.policyHash).toString("hex")).to.equal(hash.toString("hex"));
+        });
+
+        it("rejects a policy hash made for other limits", async () => {
+            const hash = circuitPolicyHash(maxPerTx, allowedCategory, salt);
+            await expectError(() => setSaltedPolicy(hash, maxPerTx.muln(2)), "PolicyHashMismatch");
+        });
+
+        it("rejects an unrelated hash when a salt is given", async () => {
+            await expectError(() => setSaltedPolicy(Buffer.from(policyHash), maxPerTx), "PolicyHashMismatch");
+        });
+    });
+
+    // =========================================================================
+    // TEST 93: Meter circuits
+    // =========================================================================
+    describe("meter circuits", () => {
+        const zkAgent = Keypair.generate();
+        const proofV1 = Buffer.concat([Buffer.from([1, 1]), Buffer.from(groth16Fixtures.payment_proof, "hex")]);
+        const proofV2 = Buffer.concat([Buffer.from([2, 1]), Buffer.alloc(256)]);
+
+        const setMeterCircuit = (circuitId: number, oldCircuitId: number) =>
+            program.methods
+                .setMeterCircuit(circuitId)
+                .accounts({
+                    authority: fixtureMeterAuthority.publicKey,
+                    meterId: fixtureMeterId,
+                    meter: fixtureMeterPda,
+                    oldVerifyingKey: oldCircuitId === 0 ? null : vkPdaFor(oldCircuitId),
+                    verifyingKey: circuitId === 0 ? null : vkPdaFor(circuitId),
+                })
+                .signers([fixtureMeterAuthority])
+                .rpc();
+
+        const removeVerifyingKey = (circuitId: number) =>
+            program.methods
+                .removeVerifyingKey(circuitId)
+                .accounts({
+                    admin: provider.wallet.publicKey,
+                    config: configPda,
+                    verifyingKey: vkPdaFor(circuitId),
+                })
+                .rpc();
+
+        before(async () => {
+            await program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                    allowedCategory: groth16Fixtures.category,
+                    maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+        });
+
+        it("rejects a circuit meters can't use", async () => {
+            await expectError(() => setMeterCircuit(budgetCircuit, 0), "InvalidCircuit");
+        });
+
+        it("pins the meter's circuit and counts it on the key", async () => {
+            await setMeterCircuit(paymentPolicyCircuit, 0);
+
+            const meter = await program.account.meter.fetch(fixtureMeterPda);
+            expect(meter.circuitId).to.equal(paymentPolicyCircuit);
+            const vk = await program.account.verifyingKey.fetch(vkPdaFor(paymentPolicyCircuit));
+            expect(vk.meterCount).to.equal(1);
+        });
+
+        it("rejects a proof for another circuit than the meter's", async () => {
+            await expectError(
+                () => authorizeWithProof(zkAgent, fixtureNonce.addn(20), proofV2),
+                "ProofCircuitMismatch"
+            );
+        });
+
+        it("accepts a proof for the meter's circuit", async () => {
+            await authorizeWithProof(zkAgent, fixtureNonce, proofV1);
+
+            const auth = await program.account.authorization.fetch(
+                authPdaFor(fixtureNonce, zkAgent.publicKey, fixtureMeterPda)
+            );
+            expect(auth.zkVerified).to.equal(true);
+        });
+
+        it("won't remove a key a meter uses", async () => {
+            await expectError(() => removeVerifyingKey(paymentPolicyCircuit), "VerifyingKeyInUse");
+        });
+
+        it("removes a key no meter uses", async () => {
+            const unusedCircuit = 200;
+            await setVerifyingKey(unusedCircuit, vkParams(groth16Fixtures.keys.payment_policy));
+            await removeVerifyingKey(unusedCircuit);
+
+            expect(await provider.connection.getAccountInfo(vkPdaFor(unusedCircuit))).to.be.null;
+        });
+
+        it("releases the key when the meter is unpinned", async () => {
+            await setMeterCircuit(0, paymentPolicyCircuit);
+
+            const vk = await program.account.verifyingKey.fetch(vkPdaFor(paymentPolicyCircuit));
+            expect(vk.meterCount).to.equal(0);
+        });
+    });
+
+    // =========================================================================
+    // TEST 94: UltraHonk proofs
+    // =========================================================================
+    describe("UltraHonk proofs", () => {
+        // programs/mock_verifier stands in for the circuit's Honk verifier
+        const mockVerifierId = new PublicKey("DUURAM5mYBrePqiCb9x2Ai2dgcjtLpi471LME5yqpWjj");
+        const ultraHonk = 2; // proof_schemes::ULTRA_HONK
+        const zkAgent = Keypair.generate();
+        const honkProof = (payload: string, scheme: number = ultraHonk) =>
+            Buffer.concat([Buffer.from([honkFixtures.circuit_version, scheme]), Buffer.from(payload, "hex")]);
+
+        const setHonkVerifyingKey = () =>
+            program.methods
+                .setHonkVerifyingKey(dailyLimitCircuit, {
+                    circuitVersion: honkFixtures.circuit_version,
+                    verifierProgram: mockVerifierId,
+                    vkHash: [...Buffer.from(honkFixtures.vk_hash, "hex")],
+                    locked: false,
+                })
+                .accounts({
+                    admin: provider.wallet.publicKey,
+                    config: configPda,
+                    honkVerifyingKey: honkVkPdaFor(dailyLimitCircuit),
+                    groth16VerifyingKey: vkPdaFor(dailyLimitCircuit),
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc();
+
+        // The daily limit circuit switches to UltraHonk for these tests
+        before(async () => {
+            await program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                    allowedCategory: groth16Fixtures.category,
+                    maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+
+            await program.methods
+                .removeVerifyingKey(dailyLimitCircuit)
+                .accounts({
+                    admin: provider.wallet.publicKey,
+                    config: configPda,
+                    verifyingKey: vkPdaFor(dailyLimitCircuit),
+                })
+                .rpc();
+            await setHonkVerifyingKey();
+        });
+
+        after(async () => {
+            await program.methods
+                .removeHonkVerifyingKey(dailyLimitCircuit)
+                .accounts({
+                    admin: provider.wallet.publicKey,
+                    config: configPda,
+                    honkVerifyingKey: honkVkPdaFor(dailyLimitCircuit),
+                })
+                .rpc();
+            await setVerifyingKey(dailyLimitCircuit, vkParams(groth16Fixtures.keys.daily_limit, 2));
+        });
+
+        const honkVerifier = {
+            verifierProgram: mockVerifierId,
+            verifyingKey: null,
+            honkVerifyingKey: honkVkPdaFor(dailyLimitCircuit),
+        };
+
+        it("authorizes an UltraHonk proof its Honk verifier accepts", async () => {
+            const nonce = new anchor.BN(Date.now() + 9400);
+            await authorizeWithProof(zkAgent, nonce, honkProof(honkFixtures.proof), honkVerifier);
+
+            const auth = await program.account.authorization.fetch(
+                authPdaFor(nonce, zkAgent.publicKey, fixtureMeterPda)
+            );
+            expect(auth.zkVerified).to.equal(true);
+            expect(auth.verificationMethod).to.equal(verificationCpi);
+            expect(auth.verifierRef.toBase58()).to.equal(mockVerifierId.toBase58());
+            expect(auth.proofVersion).to.equal(honkFixtures.circuit_version);
+        });
+
+        it("fails when the Honk verifier rejects", async () => {
+            try {
+                await authorizeWithProof(zkAgent, new anchor.BN(Date.now() + 9401), honkProof(honkFixtures.rejected_proof), honkVerifier);
+                expect.fail("Should have been rejected by the verifier");
+            } catch (err: any) {
+                expect(err.logs.join("\n")).to.include("Mock verifier: proof rejected");
+            }
+        });
+
+        it("rejects a Groth16 proof for an UltraHonk circuit", async () => {
+            await expectError(
+                () =>
+                    authorizeWithProof(
+                        zkAgent,
+                        new anchor.BN(Date.now() + 9402),
+                        honkProof(groth16Fixtures.daily_limit_proof, 1), // proof_schemes::GROTH16
+                        honkVerifier
+                    ),
+                "ProofSchemeMismatch"
+            );
+        });
+
+        it("keeps a circuit to one scheme", async () => {
+            await expectError(
+                () => setVerifyingKey(dailyLimitCircuit, vkParams(groth16Fixtures.keys.daily_limit, 2)),
+                "CircuitSchemeConflict"
+            );
+        });
+
+        it("counts meters pinned to an UltraHonk circuit", async () => {
+            const setMeterCircuit = (circuitId: number, oldCircuitId: number) =>
+                program.methods
+                    .setMeterCircuit(circuitId)
+                    .accounts({
+                        authority: fixtureMeterAuthority.publicKey,
+                        meterId: fixtureMeterId,
+                        meter: fixtureMeterPda,
+                        oldHonkVerifyingKey: oldCircuitId === 0 ? null : honkVkPdaFor(oldCircuitId),
+                        honkVerifyingKey: circuitId === 0 ? null : honkVkPdaFor(circuitId),
+                    })
+                    .signers([fixtureMeterAuthority])
+                    .rpc();
+
+            await setMeterCircuit(dailyLimitCircuit, 0);
+            let vk = await program.account.honkVerifyingKey.fetch(honkVkPdaFor(dailyLimitCircuit));
+            expect(vk.meterCount).to.equal(1);
+
+            await setMeterCircuit(0, dailyLimitCircuit);
+            vk = await program.account.honkVerifyingKey.fetch(honkVkPdaFor(dailyLimitCircuit));
+            expect(vk.meterCount).to.equal(0);
+        });
+    });
+
+    // =========================================================================
+    // TEST 95: policy oracle attestations
+    // =========================================================================
+    describe("attested payments", () => {
+        const oracleKeypair = Keypair.generate();
+
+        const setMeterRejectAttested = (reject: boolean) =>
+            program.methods
+                .setMeterRejectAttested(reject)
+                .accounts({
+                    authority: provider.wallet.publicKey,
+                    meterId: meterIdKeypair.publicKey,
+                    meter: meterPda,
+                })
+                .rpc();
+
+        const attestation = (fields: {
+            nonce: anchor.BN,
+            expiresAtSlot: anchor.BN,
+            amount?: anchor.BN,
+            signer?: Keypair,
+        }) => {
+            const message = Buffer.concat([
+                Buffer.from("agent_blink_pay:attestation"),
+                agentKeypair.publicKey.toBuffer(),
+                meterPda.toBuffer(),
+                (fields.amount ?? pricePerCall).toArrayLike(Buffer, "le", 8),
+                Buffer.from([allowedCategory]),
+                fields.nonce.toArrayLike(Buffer, "le", 8),
+                fields.expiresAtSlot.toArrayLike(Buffer, "le", 8),
+            ]);
+            return Ed25519Program.createInstructionWithPrivateKey({
+                privateKey: (fields.signer ?? oracleKeypair).secretKey,
+                message,
+            });
+        };
+
+        const authorizeAttested = async (
+            nonce: anchor.BN,
+            preInstructions: (expiresAtSlot: anchor.BN) => anchor.web3.TransactionInstruction[] =
+                (expiresAtSlot) => [attestation({ nonce, expiresAtSlot })],
+            instructions: PublicKey | null = SYSVAR_INSTRUCTIONS_PUBKEY
+        ) => {
+            const expiresAtSlot = new anchor.BN((await provider.connection.getSlot()) + 100);
+            await program.methods
+                .authorizePaymentAttested(
+                    policyId,
+                    pricePerCall,
+                    singleCall,
+                    allowedCategory,
+                    nonce,
+                    expiresAtSlot,
+                    noRequestId,
+                    expireBySlot,
+                    anyConsumer
+                )
+                .accounts({
+                    agent: agentKeypair.publicKey,
+                    agentPolicy: policyPda,
+                    meter: meterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(meterPda),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(meterPda),
+                    authorization: authPdaFor(nonce, agentKeypair.publicKey, meterPda),
+                    config: configPda,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    verifierProgram: program.programId,
+                    verifyingKey: null,
+                    instructions,
+                    vault: null,
+                    escrow: null,
+                    mint: null,
+                    tokenProgram: null,
+                    associatedTokenProgram: null,
+                })
+                .preInstructions(preInstructions(expiresAtSlot))
+                .signers([agentKeypair])
+                .rpc();
+        };
+
+        before(async () => {
+            await updateConfig({ policyOracle: oracleKeypair.publicKey });
+        });
+
+        after(async () => {
+            await updateConfig({});
+            await setMeterRejectAttested(false);
+            await setPolicyFlags();
+        });
+
+        it("authorizes a payment the oracle attested", async () => {
+            const nonce = new anchor.BN(Date.now() + 9500);
+            await authorizeAttested(nonce);
+
+            const auth = await program.account.authorization.fetch(authPdaFor(nonce, agentKeypair.publicKey, meterPda));
+            expect(auth.attested).to.equal(true);
+            expect(auth.zkVerified).to.equal(false);
+            expect(auth.verificationMethod).to.equal(verificationAttestation);
+            expect(auth.verifierRef.toBase58()).to.equal(oracleKeypair.publicKey.toBase58());
+            expect(auth.proofVersion).to.equal(0);
+            await record(nonce);
+        });
+
+        it("rejects an attestation by another key", async () => {
+            const nonce = new anchor.BN(Date.now() + 9501);
+            await expectError(
+                () => authorizeAttested(nonce, (expiresAtSlot) => [
+                    attestation({ nonce, expiresAtSlot, signer: Keypair.generate() }),
+                ]),
+                "InvalidAttestation"
+            );
+        });
+
+        it("rejects an attestation of other payment terms", async () => {
+            const nonce = new anchor.BN(Date.now() + 9502);
+            await expectError(
+                () => authorizeAttested(nonce, (expiresAtSlot) => [
+                    attestation({ nonce, expiresAtSlot, amount: pricePerCall.muln(2) }),
+                ]),
+                "InvalidAttestation"
+            );
+            await expectError(
+                () => authorizeAttested(nonce, (expiresAtSlot) => [
+                    attestation({ nonce: nonce.addn(1), expiresAtSlot }),
+                ]),
+                "InvalidAttestation"
+            );
+            await expectError(
+                () => authorizeAttested(nonce, (expiresAtSlot) => [
+                    attestation({ nonce, expiresAtSlot: expiresAtSlot.addn(1) }),
+                ]),
+                "InvalidAttestation"
+            );
+        });
+
+        it("rejects a missing Ed25519 instruction or sysvar", async () => {
+            const nonce = new anchor.BN(Date.now() + 9503);
+            await expectError(() => authorizeAttested(nonce, () => []), "InvalidAttestation");
+            await expectError(
+                () => authorizeAttested(nonce, undefined, null),
+                "InvalidAttestation"
+            );
+        });
+
+        it("rejects an Ed25519 instruction that isn't directly before it", async () => {
+            const nonce = new anchor.BN(Date.now() + 9504);
+            await expectError(
+                () => authorizeAttested(nonce, (expiresAtSlot) => [
+                    attestation({ nonce, expiresAtSlot }),
+                    anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 400_000 }),
+                ]),
+                "InvalidAttestation"
+            );
+        });
+
+        it("rejects an Ed25519 instruction reading fields from another instruction", async () => {
+            // Offsets: signature, key and message instruction indices at 4, 8
+            // and 14. Pointing them at instruction 0, the Ed25519 instruction
+            // itself, still verifies but mustn't be accepted.
+            for (const indexOffset of [4, 8, 14]) {
+                const nonce = new anchor.BN(Date.now() + 9505 + indexOffset);
+                await expectError(
+                    () => authorizeAttested(nonce, (expiresAtSlot) => {
+                        const ix = attestation({ nonce, expiresAtSlot });
+                        ix.data.writeUInt16LE(0, indexOffset);
+                        return [ix];
+                    }),
+                    "InvalidAttestation"
+                );
+            }
+        });
+
+        it("lets a meter refuse attested payments", async () => {
+            await setMeterRejectAttested(true);
+            let meter = await program.account.meter.fetch(meterPda);
+            expect(meter.rejectAttested).to.equal(true);
+            await expectError(
+                () => authorizeAttested(new anchor.BN(Date.now() + 9520)),
+                "AttestationRejectedByMeter"
+            );
+
+            await setMeterRejectAttested(false);
+            meter = await program.account.meter.fetch(meterPda);
+            expect(meter.rejectAttested).to.equal(false);
+        });
+
+        it("still requires a proof when the policy does", async () => {
+            await setPolicyFlags({ alwaysRequireZk: true });
+            await expectError(
+                () => authorizeAttested(new anchor.BN(Date.now() + 9521)),
+                "ZkRequiredByPolicy"
+            );
+            await setPolicyFlags();
+        });
+
+        it("rejects attestations while no oracle is configured", async () => {
+            await updateConfig({});
+            await expectError(
+                () => authorizeAttested(new anchor.BN(Date.now() + 9522)),
+                "AttestationsDisabled"
+            );
+            await updateConfig({ policyOracle: oracleKeypair.publicKey });
+        });
+    });
+
+    // =========================================================================
+    // TEST 96: Proof receipts
+    // =========================================================================
+    describe("proof receipts", () => {
+        const zkAgent = Keypair.generate();
+        const proof = Buffer.concat([Buffer.from([1, 1]), Buffer.from(groth16Fixtures.payment_proof, "hex")]);
+        const amount = new anchor.BN(groth16Fixtures.amount);
+        // The fixture proof's public inputs: its amount, meter and nonce
+        const publicInputs = Buffer.from(groth16Fixtures.public_input_vectors[0].field_elements.join(""), "hex");
+        const publicInputsHash = crypto.createHash("sha256").update(publicInputs).digest();
+        const receiptPda = PublicKey.findProgramAddressSync(
+            [Buffer.from("proof_receipt"), zkAgent.publicKey.toBuffer(), publicInputsHash],
+            program.programId
+        )[0];
+
+        before(async () => {
+            await program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                    allowedCategory: groth16Fixtures.category,
+                    maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+        });
+
+        const submitReceipt = (hash: Buffer = publicInputsHash) =>
+            program.methods
+                .submitProofReceipt(policyId, [...hash], publicInputs, proof)
+                .accounts({
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    proofReceipt: PublicKey.findProgramAddressSync(
+                        [Buffer.from("proof_receipt"), zkAgent.publicKey.toBuffer(), hash],
+                        program.programId
+                    )[0],
+                    config: configPda,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                    honkVerifyingKey: null,
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+
+        const authorizeWithReceipt = (nonce: anchor.BN = fixtureNonce) =>
+            authorizeWithProof(zkAgent, nonce, Buffer.alloc(0), { proofReceipt: receiptPda });
+
+        it("rejects public inputs that don't match their hash", async () => {
+            await expectError(
+                () => submitReceipt(crypto.createHash("sha256").update("other inputs").digest()),
+                "InvalidInputs"
+            );
+        });
+
+        it("verifies the proof into a receipt", async () => {
+            await submitReceipt();
+
+            const receipt = await program.account.proofReceipt.fetch(receiptPda);
+            expect(receipt.agent.toBase58()).to.equal(zkAgent.publicKey.toBase58());
+            expect(receipt.policy.toBase58()).to.equal(agentPolicyPdaFor(zkAgent.publicKey).toBase58());
+            expect(Buffer.from(receipt.publicInputsHash).equals(publicInputsHash)).to.equal(true);
+            expect(receipt.proofVersion).to.equal(1);
+            expect(receipt.verifyingKey.toBase58()).to.equal(vkPdaFor(paymentPolicyCircuit).toBase58());
+            expect(receipt.verificationMethod).to.equal(verificationEmbedded);
+        });
+
+        it("keeps the receipt until it expires", async () => {
+            await expectError(
+                () =>
+                    program.methods
+                        .closeProofReceipt()
+                        .accounts({ proofReceipt: receiptPda, rentPayer: provider.wallet.publicKey })
+                        .rpc(),
+                "ProofReceiptLive"
+            );
+        });
+
+        it("rejects the receipt under another nonce", async () => {
+            await expectError(() => authorizeWithReceipt(fixtureNonce.addn(1)), "ProofReceiptMismatch");
+        });
+
+        it("authorizes the payment from the receipt and closes it", async () => {
+            await authorizeWithReceipt();
+
+            const auth = await program.account.authorization.fetch(
+                authPdaFor(fixtureNonce, zkAgent.publicKey, fixtureMeterPda)
+            );
+            expect(auth.zkVerified).to.equal(true);
+            expect(auth.verificationMethod).to.equal(verificationEmbedded);
+            expect(auth.verifierRef.toBase58()).to.equal(vkPdaFor(paymentPolicyCircuit).toBase58());
+            expect(auth.proofVersion).to.equal(1);
+            expect(await provider.connection.getAccountInfo(receiptPda)).to.equal(null);
+            const nullifier = await nullifierPdaFor(
+                zkAgent.publicKey,
+                fixtureMeterPda,
+                fixtureNonce,
+                amount,
+                agentPolicyPdaFor(zkAgent.publicKey)
+            );
+            expect(await provider.connection.getAccountInfo(nullifier)).to.not.equal(null);
+        });
+    });
+
+    // =========================================================================
+    // TEST 97: Private meter allowlists (version 3 proofs)
+    // =========================================================================
+    describe("meter allowlist circuit", () => {
+        const zkAgent = Keypair.generate();
+        const proofV1 = Buffer.concat([Buffer.from([1, 1]), Buffer.from(groth16Fixtures.payment_proof, "hex")]);
+        const proofV3 = Buffer.concat([Buffer.from([3, 1]), Buffer.from(groth16Fixtures.allowlist_proof, "hex")]);
+        const allowlistNonce = new anchor.BN(groth16Fixtures.allowlist_nonce);
+        // The fixture meter is in the tree of allowlist_meters only
+        const memberRoot = [...Buffer.from(groth16Fixtures.allowlist_root, "hex")];
+        const nonMemberRoot = [...Buffer.from(groth16Fixtures.allowlist_non_member_root, "hex")];
+        const allowlistVerifier = { verifyingKey: vkPdaFor(meterAllowlistCircuit) };
+
+        const setAllowlistRoot = (allowlistRoot: number[]) =>
+            program.methods
+                .setPolicy(policyParams({
+                    policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                    allowedCategory: groth16Fixtures.category,
+                    maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                    allowlistRoot,
+                }))
+                .accounts({
+                    owner: zkAgent.publicKey,
+                    agent: zkAgent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                    retiredAgent: retiredPdaFor(zkAgent.publicKey),
+                    payer: provider.wallet.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([zkAgent])
+                .rpc();
+
+        before(async () => {
+            await setVerifyingKey(meterAllowlistCircuit, vkParams(groth16Fixtures.keys.meter_allowlist, 3));
+            await setAllowlistRoot(noAllowlistRoot);
+        });
+
+        it("rejects a root that isn't a field element", async () => {
+            await expectError(() => setAllowlistRoot(Array(32).fill(0xff)), "InvalidAllowlistRoot");
+        });
+
+        it("rejects a v3 proof for a policy without an allowlist", async () => {
+            await expectError(
+                () => authorizeWithProof(zkAgent, allowlistNonce, proofV3, allowlistVerifier),
+                "AllowlistCircuitMismatch"
+            );
+        });
+
+        it("rejects a meter outside the policy's allowlist", async () => {
+            await setAllowlistRoot(nonMemberRoot);
+            const policy = await program.account.agentPolicy.fetch(agentPolicyPdaFor(zkAgent.publicKey));
+            expect(policy.allowlistRoot).to.deep.equal(nonMemberRoot);
+
+            // The only proof for the fixture meter is against another tree
+            await expectError(
+                () => authorizeWithProof(zkAgent, allowlistNonce, proofV3, allowlistVerifier),
+                "InvalidProof",
+                "PairingFailed"
+            );
+        });
+
+        it("accepts a v3 proof that the meter is on the allowlist", async () => {
+            await setAllowlistRoot(memberRoot);
+            await authorizeWithProof(zkAgent, allowlistNonce, proofV3, allowlistVerifier);
+
+            const auth = await program.account.authorization.fetch(
+                authPdaFor(allowlistNonce, zkAgent.publicKey, fixtureMeterPda)
+            );
+            expect(auth.proofVersion).to.equal(3);
+            expect(auth.zkVerified).to.equal(true);
+        });
+
+        it("rejects proofs that don't check the allowlist", async () => {
+            await expectError(
+                () => authorizeWithProof(zkAgent, fixtureNonce, proofV1),
+                "AllowlistCircuitMismatch"
+            );
+        });
+
+        it("rejects payments without a proof", async () => {
+            const currentSlot = await provider.connection.getSlot();
+            const nonce = new anchor.BN(Date.now() + 9700);
+            await expectError(
+                () =>
+                    program.methods
+                        .authorizePaymentSimple(
+                            policyId,
+                            new anchor.BN(groth16Fixtures.amount),
+                            singleCall,
+                            groth16Fixtures.category,
+                            nonce,
+                            new anchor.BN(currentSlot + 100),
+                            noRequestId,
+                            expireBySlot,
+                            anyConsumer
+                        )
+                        .accounts({
+                            agent: zkAgent.publicKey,
+                            agentPolicy: agentPolicyPdaFor(zkAgent.publicKey),
+                            meter: fixtureMeterPda,
+                            allowedMeter: null,
+                            deniedMeter: deniedPdaFor(fixtureMeterPda, zkAgent.publicKey),
+                            meterAccess: null,
+                            meterUsage: usagePdaFor(fixtureMeterPda, zkAgent.publicKey),
+                            authorization: authPdaFor(nonce, zkAgent.publicKey, fixtureMeterPda),
+                            config: configPda,
+                            payer: provider.wallet.publicKey,
+                            systemProgram: SystemProgram.programId,
+                            verifierProgram: program.programId,
+                            vault: null,
+                            escrow: null,
+                            mint: null,
+                            tokenProgram: null,
+                            associatedTokenProgram: null,
+                        })
+                        .signers([zkAgent])
+                        .rpc(),
+                "ZkRequiredByPolicy"
+            );
+        });
+    });
+
+    // =========================================================================
+    // TEST 98: Aggregated proofs for authorize_payments_batch
+    // =========================================================================
+    describe("aggregated batch proofs", () => {
+        const groth16Batch = 3; // proof_schemes::GROTH16_BATCH
+        // Both agents pay for their own authorizations, which keeps the
+        // transaction small enough for a compute unit limit instruction
+        const aggregatedAgent = Keypair.generate();
+        const separateAgent = Keypair.generate();
+        const batchNonces: anchor.BN[] = groth16Fixtures.batch_nonces.map((n: number) => new anchor.BN(n));
+        const batchProofs: Buffer[] = groth16Fixtures.batch_proofs.map((p: string) => Buffer.from(p, "hex"));
+        const aggregatedProof = Buffer.concat([Buffer.from([1, groth16Batch]), Buffer.from(groth16Fixtures.batch_proof, "hex")]);
+        let aggregatedUnits = 0;
+
+        before(async () => {
+            for (const agent of [aggregatedAgent, separateAgent]) {
+                await provider.connection.confirmTransaction(
+                    await provider.connection.requestAirdrop(agent.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+                );
+                await program.methods
+                    .setPolicy(policyParams({
+                        policyHash: [...Buffer.from(groth16Fixtures.policy_hash, "hex")],
+                        allowedCategory: groth16Fixtures.category,
+                        maxPerTx: new anchor.BN(groth16Fixtures.max_per_tx),
+                    }))
+                    .accounts({
+                        owner: agent.publicKey,
+                        agent: agent.publicKey,
+                        agentPolicy: agentPolicyPdaFor(agent.publicKey),
+                        retiredAgent: retiredPdaFor(agent.publicKey),
+                        payer: provider.wallet.publicKey,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .signers([agent])
+                    .rpc();
+            }
+        });
+
+        // Authorizes the fixture payments for `nonces` and returns the
+        // compute units the transaction consumed
+        const authorizeBatch = async (agent: Keypair, nonces: anchor.BN[], proofs: Buffer[]) => {
+            const currentSlot = await provider.connection.getSlot();
+            const signature = await program.methods
+                .authorizePaymentsBatch(
+                    policyId,
+                    nonces.map((nonce) => ({
+                        amount: new anchor.BN(groth16Fixtures.amount),
+                        category: groth16Fixtures.category,
+                        nonce,
+                        expiresAtSlot: new anchor.BN(currentSlot + 100),
+                        requestId: noRequestId,
+                    })),
+                    proofs.map((proof) => [...proof])
+                )
+                .accounts({
+                    agent: agent.publicKey,
+                    agentPolicy: agentPolicyPdaFor(agent.publicKey),
+                    meter: fixtureMeterPda,
+                    allowedMeter: null,
+                    deniedMeter: deniedPdaFor(fixtureMeterPda, agent.publicKey),
+                    meterAccess: null,
+                    meterUsage: usagePdaFor(fixtureMeterPda, agent.publicKey),
+                    payer: agent.publicKey,
+                    systemProgram: SystemProgram.programId,
+                    config: configPda,
+                    verifierProgram: program.programId,
+                    verifyingKey: vkPdaFor(paymentPolicyCircuit),
+                })
+                .remainingAccounts((await Promise.all(nonces.map(async (nonce) => [
+                    authPdaFor(nonce, agent.publicKey, fixtureMeterPda),
+                    await nullifierPdaFor(
+                        agent.publicKey,
+                        fixtureMeterPda,
+                        nonce,
+                        new anchor.BN(groth16Fixtures.amount),
+                        agentPolicyPdaFor(agent.publicKey)
+                    ),
+                ]))).flat().map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })))
+                .preInstructions([anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 400_000 })])
+                .signers([agent])
+                .rpc({ commitment: "confirmed" });
+            const tx = await provider.connection.getTransaction(signature, {
+                commitment: "confirmed",
+                maxSupportedTransactionVersion: 0,
+            });
+            return tx!.meta!.computeUnitsConsumed!;
+        };
+
+        it("rejects a batch_count other than the number of entries", async () => {
+            await expectError(
+                () => authorizeBatch(aggregatedAgent, batchNonces.slice(0, 1), [aggregatedProof]),
+                "BatchCountMismatch"
+            );
+            const authPda = authPdaFor(batchNonces[0], aggregatedAgent.publicKey, fixtureMeterPda);
+            expect(await provider.connection.getAccountInfo(authPda)).to.equal(null);
+        });
+
+        it("rejects the proofs aggregated in another order", async () => {
+            const swapped = Buffer.concat([
+                Buffer.from([1, groth16Batch, batchProofs.length]),
+                ...[...batchProofs].reverse(),
+            ]);
+            await expectError(() => authorizeBatch(aggregatedAgent, batchNonces, [swapped]), "InvalidProof");
+        });
+
+        it("authorizes every entry with one aggregated proof", async () => {
+            aggregatedUnits = await authorizeBatch(aggregatedAgent, batchNonces, [aggregatedProof]);
+
+            for (const nonce of batchNonces) {
+                const auth = await program.account.authorization.fetch(
+                    authPdaFor(nonce, aggregatedAgent.publicKey, fixtureMeterPda)
+                );
+                expect(auth.zkVerified).to.equal(true);
+                expect(auth.proofVersion).to.equal(1);
+                expect(auth.verificationMethod).to.equal(verificationEmbedded);
+                expect(auth.verifierRef.toBase58()).to.equal(vkPdaFor(paymentPolicyCircuit).toBase58());
+            }
+        });
+
+        it("costs fewer compute units than one proof per entry", async () => {
+            const separateUnits = await authorizeBatch(
+                separateAgent,
+                batchNonces,
+                batchProofs.map((proof) => Buffer.concat([Buffer.from([1, 1]), proof]))
+            );
+
+            expect(aggregatedUnits).to.be.greaterThan(0);
+            expect(aggregatedUnits).to.be.lessThan(separateUnits);
+        });
+    });
 });
@@ -0,0 +1,308 @@
+"""Development Groth16 keys and fixture proofs for the agent_blink_pay tests.
+
+The verifying keys the tests install with set_verifying_key come from the
+setup secrets below, which are published on purpose: knowing them, this
+script produces proofs for any public inputs without the Noir toolchain.
+Never deploy these keys. `payment_policy_v2` stands in for a new circuit
+version: a different key for the same public inputs. `daily_limit` is the
+daily limit circuit's key (public inputs: the payment policy inputs, then
+spent_today), with `daily_limit_proof` for a policy that has spent nothing
+today and `daily_limit_spent_proof` for one that has spent
+`daily_limit_spent`. `meter_allowlist` is the meter allowlist circuit's key
+(public inputs: the payment policy inputs, then allowlist_root), with
+`allowlist_proof` proving the fixture meter is in the tree of
+`allowlist_meters` whose root is `allowlist_root`. No proof exists for a
+tree without it, such as the one of `allowlist_non_member_meters`
+(`allowlist_non_member_root`). `batch_proofs` are payment proofs for the
+entries of an authorize_payments_batch, one per `batch_nonces` nonce, and
+`batch_proof` is the same proofs aggregated (proof_schemes::GROTH16_BATCH):
+their batch_count, then each proof in nonce order.
+
+Payment proofs also commit to the meter and the nonce they authorize, so
+the fixture proofs are made for a fixture meter, created by the tests from
+`meter_authority_seed` and `meter_id`, and for `nonce` (`daily_limit_nonce`
+for the daily limit proofs, `allowlist_nonce` for the allowlist proof). The
+script derives the meter's address itself, hence the ed25519 helpers below.
+
+`public_input_vectors` are golden vectors of the payment policy circuit's
+public-input encoding (agent_blink_pay::public_inputs), for checking an
+off-chain prover byte-for-byte. The program's unit tests use the same ones.
+
+    python3 groth16_fixtures.py   # rewrites groth16_fixtures.json
+"""
+
+import hashlib, json, os, random, struct
+
+p = 21888242871839275222246405745257275088696311157297823662689037894645226208583
+r = 21888242871839275222246405745257275088548364400416034343698204186575808495617
+
+# Fp2 = Fp[u]/(u^2+1)
+def f2add(a, b): return ((a[0]+b[0]) % p, (a[1]+b[1]) % p)
+def f2sub(a, b): return ((a[0]-b[0]) % p, (a[1]-b[1]) % p)
+def f2mul(a, b): return ((a[0]*b[0]-a[1]*b[1]) % p, (a[0]*b[1]+a[1]*b[0]) % p)
+def f2inv(a):
+    d = pow(a[0]*a[0]+a[1]*a[1], p-2, p)
+    return (a[0]*d % p, (-a[1])*d % p)
+
+class F1:
+    zero = 0; one = 1
+    add = staticmethod(lambda a, b: (a+b) % p)
+    sub = staticmethod(lambda a, b: (a-b) % p)
+    mul = staticmethod(lambda a, b: a*b % p)
+    inv = staticmethod(lambda a: pow(a, p-2, p))
+class F2:
+    zero = (0, 0); one = (1, 0)
+    add = staticmethod(f2add); sub = staticmethod(f2sub)
+    mul = staticmethod(f2mul); inv = staticmethod(f2inv)
+
+def padd(F, P, Q):
+    if P is None: return Q
+    if Q is None: return P
+    (x1, y1), (x2, y2) = P, Q
+    if x1 == x2:
+        if y1 == y2:
+            lam = F.mul(F.mul(F.add(F.add(F.mul(x1, x1), F.mul(x1, x1)), F.mul(x1, x1)), F.one), F.inv(F.add(y1, y1)))
+        else:
+            return None
+    else:
+        lam = F.mul(F.sub(y2, y1), F.inv(F.sub(x2, x1)))
+    x3 = F.sub(F.sub(F.mul(lam, lam), x1), x2)
+    y3 = F.sub(F.mul(lam, F.sub(x1, x3)), y1)
+    return (x3, y3)
+
+def pmul(F, P, k):
+    R = None
+    while k:
+        if k & 1: R = padd(F, R, P)
+        P = padd(F, P, P); k >>= 1
+    return R
+
+G1 = (1, 2)
+G2 = ((10857046999023057135944570762232829481370756359578518086990519993285655852781,
+       11559732032986387107991004021392285783925812861821192530917403151452391805634),
+      (8495653923123431417604973247489272438418190587263600148770280649306958101930,
+       4082367875863433681332203403145435568316851327593401208105741076214120093531))
+
+# checks
+assert (G1[1]**2 - G1[0]**3 - 3) % p == 0
+b2 = f2mul((3, 0), f2inv((9, 1)))
+x, y = G2
+assert f2sub(f2mul(y, y), f2add(f2mul(f2mul(x, x), x), b2)) == (0, 0)
+assert pmul(F2, G2, r) is None and pmul(F1, G1, r) is None
+
+def g1(k): return pmul(F1, G1, k % r)
+def g2(k): return pmul(F2, G2, k % r)
+def enc1(P): return P[0].to_bytes(32, 'big') + P[1].to_bytes(32, 'big')
+def enc2(P): return b''.join(v.to_bytes(32, 'big') for v in (P[0][1], P[0][0], P[1][1], P[1][0]))
+
+# Development setup secrets (published on purpose)
+rng = random.Random(0xB11CC)
+alpha, beta, gamma, delta = (rng.randrange(1, r) for _ in range(4))
+ic_payment = [rng.randrange(1, r) for _ in range(7)]   # 6 public inputs
+ic_budget = [rng.randrange(1, r) for _ in range(8)]    # 7 public inputs
+v2 = [rng.randrange(1, r) for _ in range(4)]
+ic_payment_v2 = [rng.randrange(1, r) for _ in range(7)]
+ic_daily_limit = [rng.randrange(1, r) for _ in range(8)]   # 7 public inputs
+ic_meter_allowlist = [rng.randrange(1, r) for _ in range(8)]   # 7 public inputs
+
+def key(alpha, beta, gamma, delta, ic):
+    return {
+        "alpha_g1": enc1(g1(alpha)).hex(),
+        "beta_g2": enc2(g2(beta)).hex(),
+        "gamma_g2": enc2(g2(gamma)).hex(),
+        "delta_g2": enc2(g2(delta)).hex(),
+        "ic": [enc1(g1(s)).hex() for s in ic],
+    }
+
+# Knowing the secrets, any A = a*G1, B = b*G2, C = c*G1 with
+# a*b = alpha*beta + x*gamma + c*delta passes the verifier's pairing check,
+# where x*G1 = vk_x for the public inputs
+def prove(ic, inputs, seed):
+    pr = random.Random(seed)
+    b, c = pr.randrange(1, r), pr.randrange(1, r)
+    x = (ic[0] + sum(i * s for i, s in zip(inputs, ic[1:]))) % r
+    a = (alpha * beta + x * gamma + c * delta) * pow(b, r - 2, r) % r
+    return enc1(g1(a)) + enc2(g2(b)) + enc1(g1(c))
+
+# policy_hash as its high and low 16 bytes (public_inputs in lib.rs)
+def hash_inputs(h):
+    return [int.from_bytes(h[:16], 'big'), int.from_bytes(h[16:], 'big')]
+
+# The meter as SHA-256 of its address with the top byte cleared
+def meter_input(meter):
+    return int.from_bytes(b"\x00" + hashlib.sha256(meter).digest()[1:], 'big')
+
+def payment_inputs(amount, category, h, meter, nonce):
+    return [amount, category] + hash_inputs(h) + [meter_input(meter), nonce]
+
+def field_elements(amount, category, h, meter, nonce):
+    """Canonical encoding: 32-byte big-endian field elements."""
+    return [v.to_bytes(32, 'big').hex() for v in payment_inputs(amount, category, h, meter, nonce)]
+
+# Ed25519, only as far as Solana addresses need it (RFC 8032)
+q = 2**255 - 19
+d = -121665 * pow(121666, q - 2, q) % q
+sqrt_m1 = pow(2, (q - 1) // 4, q)
+
+def ed_add(P, Q):
+    (x1, y1), (x2, y2) = P, Q
+    t = d * x1 * x2 * y1 * y2 % q
+    return ((x1 * y2 + x2 * y1) * pow(1 + t, q - 2, q) % q,
+            (y1 * y2 + x1 * x2) * pow(1 - t, q - 2, q) % q)
+
+def ed_mul(P, k):
+    R = (0, 1)
+    while k:
+        if k & 1: R = ed_add(R, P)
+        P = ed_add(P, P); k >>= 1
+    return R
+
+def ed_x(y):
+    """An x for y on the curve, or None."""
+    x2 = (y * y - 1) * pow(d * y * y + 1, q - 2, q) % q
+    x = pow(x2, (q + 3) // 8, q)
+    if (x * x - x2) % q:
+        x = x * sqrt_m1 % q
+    return x if (x * x - x2) % q == 0 else None
+
+ed_base_y = 4 * pow(5, q - 2, q) % q
+ed_base = (ed_x(ed_base_y), ed_base_y)
+if ed_base[0] & 1:
+    ed_base = (q - ed_base[0], ed_base_y)
+
+def ed_pubkey(seed):
+    h = hashlib.sha512(seed).digest()
+    a = int.from_bytes(h[:32], 'little') & ((1 << 254) - 8) | (1 << 254)
+    x, y = ed_mul(ed_base, a)
+    return (y | (x & 1) << 255).to_bytes(32, 'little')
+
+def b58decode(s):
+    alphabet = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz"
+    n = 0
+    for ch in s:
+        n = n * 58 + alphabet.index(ch)
+    return n.to_bytes(32, 'big')
+
+def find_program_address(seeds, program_id):
+    for bump in range(255, -1, -1):
+        h = hashlib.sha256(b"".join(seeds) + bytes([bump]) + program_id + b"ProgramDerivedAddress").digest()
+        if ed_x(int.from_bytes(h, 'little') & ((1 << 255) - 1)) is None:
+            return h
+    raise ValueError("no bump")
+
+program_id = b58decode("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS")
+meter_authority_seed = hashlib.sha256(b"agent_blink_pay fixture meter authority").digest()
+meter_id = hashlib.sha256(b"agent_blink_pay fixture meter id").digest()
+meter = find_program_address([b"meter", ed_pubkey(meter_authority_seed), meter_id], program_id)
+nonce, daily_limit_nonce, allowlist_nonce = 1, 2, 3
+batch_nonces = [4, 5]
+
+# zk/meter_allowlist: a SHA-256 Merkle tree of ALLOWLIST_DEPTH levels over
+# meter inputs, unused leaves 0, each node's hash with the top byte cleared
+ALLOWLIST_DEPTH = 8
+
+def allowlist_root(meters):
+    level = [meter_input(m) for m in meters]
+    level += [0] * (2**ALLOWLIST_DEPTH - len(level))
+    for _ in range(ALLOWLIST_DEPTH):
+        level = [
+            int.from_bytes(b"\x00" + hashlib.sha256(
+                level[i].to_bytes(32, 'big') + level[i + 1].to_bytes(32, 'big')
+            ).digest()[1:], 'big')
+            for i in range(0, len(level), 2)
+        ]
+    return level[0]
+
+other_meters = [hashlib.sha256(b"agent_blink_pay fixture allowlist meter %d" % i).digest() for i in range(3)]
+allowlist_meters = [other_meters[0], meter, other_meters[1]]
+allowlist_non_member_meters = other_meters
+
+def circuit_policy_hash(max_per_tx, category, salt):
+    """The circuits' policy commitment: the salt's last byte is not hashed."""
+    return hashlib.sha256(struct.pack('<Q', max_per_tx) + bytes([category]) + salt[:31]).digest()
+
+max_per_tx, category = 1000000, 1
+policy_hash = hashlib.sha256(struct.pack('<Q', max_per_tx) + bytes([category]) + b"BlinkPay").digest()
+amount = 50000
+daily_limit_spent = 10000
+
+batch_proofs = [
+    prove(ic_payment, payment_inputs(amount, category, policy_hash, meter, n), 5 + i)
+    for i, n in enumerate(batch_nonces)
+]
+
+out = {
+    "max_per_tx": max_per_tx,
+    "category": category,
+    "amount": amount,
+    "keys": {
+        "payment_policy": key(alpha, beta, gamma, delta, ic_payment),
+        "budget": key(alpha, beta, gamma, delta, ic_budget),
+        "payment_policy_v2": key(*v2, ic_payment_v2),
+        "daily_limit": key(alpha, beta, gamma, delta, ic_daily_limit),
+        "meter_allowlist": key(alpha, beta, gamma, delta, ic_meter_allowlist),
+    },
+    "policy_hash": policy_hash.hex(),
+    "meter_authority_seed": meter_authority_seed.hex(),
+    "meter_authority": ed_pubkey(meter_authority_seed).hex(),
+    "meter_id": meter_id.hex(),
+    "meter": meter.hex(),
+    "nonce": nonce,
+    "daily_limit_nonce": daily_limit_nonce,
+    "payment_proof": prove(ic_payment, payment_inputs(amount, category, policy_hash, meter, nonce), 1).hex(),
+    "daily_limit_spent": daily_limit_spent,
+    "daily_limit_proof": prove(
+        ic_daily_limit, payment_inputs(amount, category, policy_hash, meter, daily_limit_nonce) + [0], 2
+    ).hex(),
+    "daily_limit_spent_proof": prove(
+        ic_daily_limit,
+        payment_inputs(amount, category, policy_hash, meter, daily_limit_nonce) + [daily_limit_spent],
+        3,
+    ).hex(),
+    "allowlist_nonce": allowlist_nonce,
+    "allowlist_meters": [m.hex() for m in allowlist_meters],
+    "allowlist_root": allowlist_root(allowlist_meters).to_bytes(32, 'big').hex(),
+    "allowlist_non_member_meters": [m.hex() for m in allowlist_non_member_meters],
+    "allowlist_non_member_root": allowlist_root(allowlist_non_member_meters).to_bytes(32, 'big').hex(),
+    "allowlist_proof": prove(
+        ic_meter_allowlist,
+        payment_inputs(amount, category, policy_hash, meter, allowlist_nonce) + [allowlist_root(allowlist_meters)],
+        4,
+    ).hex(),
+    "batch_nonces": batch_nonces,
+    "batch_proofs": [p.hex() for p in batch_proofs],
+    "batch_proof": (bytes([len(batch_proofs)]) + b"".join(batch_proofs)).hex(),
+    "public_input_vectors": [
+        {
+            "amount": a,
+            "category": c,
+            "policy_hash": h.hex(),
+            "meter": m.hex(),
+            "nonce": n,
+            "field_elements": field_elements(a, c, h, m, n),
+        }
+        for a, c, h, m, n in [
+            (amount, category, policy_hash, meter, nonce),
+            (2**64 - 1, 255, b"\xff" * 32, b"\xff" * 32, 2**64 - 1),
+            (0, 0, bytes(range(32)), bytes(32), 0),
+        ]
+    ],
+    # policy_hash::compute in lib.rs, compute_policy_hash in the circuits
+    "policy_hash_vectors": [
+        {
+            "max_per_tx": m,
+            "allowed_category": c,
+            "salt": salt.hex(),
+            "policy_hash": circuit_policy_hash(m, c, salt).hex(),
+        }
+        for m, c, salt in [
+            (1000000, 1, bytes([1]) * 32),
+            (2**64 - 1, 255, b"\xff" * 32),
+            (0, 0, bytes(range(32))),
+        ]
+    ],
+}
+path = os.path.join(os.path.dirname(os.path.abspath(__file__)), "groth16_fixtures.json")
+with open(path, "w") as f:
+    json.dump(out, f, indent=1)
+    f.write("\n")